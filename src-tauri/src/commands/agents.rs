@@ -2,6 +2,7 @@ use anyhow::Result;
 use chrono;
 use dirs;
 use log::{debug, error, info, warn};
+use r2d2_sqlite::SqliteConnectionManager;
 use reqwest;
 use rusqlite::{params, Connection, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
@@ -92,8 +93,14 @@ pub struct AgentData {
     pub hooks: Option<String>,
 }
 
+/// Pool of connections to the agents database
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+
 /// Database connection state
-pub struct AgentDb(pub Mutex<Connection>);
+pub struct AgentDb(pub DbPool);
+
+/// Connections kept open for concurrent commands
+const DB_POOL_SIZE: u32 = 8;
 
 /// Real-time JSONL reading and processing functions
 impl AgentRunMetrics {
@@ -213,17 +220,35 @@ pub async fn get_agent_run_with_metrics(run: AgentRun) -> AgentRunWithMetrics {
     }
 }
 
-/// Initialize the agents database
-pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
+/// Open the agents database as a connection pool and create its tables.
+///
+/// Connections use WAL journaling so request logging doesn't block readers, and wait
+/// for a busy database instead of failing.
+pub fn init_database(app: &AppHandle) -> Result<DbPool, String> {
     let app_dir = app
         .path()
         .app_data_dir()
         .expect("Failed to get app data dir");
     std::fs::create_dir_all(&app_dir).expect("Failed to create app data dir");
 
-    let db_path = app_dir.join("agents.db");
-    let conn = Connection::open(db_path)?;
+    let manager = SqliteConnectionManager::file(app_dir.join("agents.db")).with_init(|conn| {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;
+             PRAGMA busy_timeout = 5000;",
+        )
+    });
+    let pool = r2d2::Pool::builder()
+        .max_size(DB_POOL_SIZE)
+        .build(manager)
+        .map_err(|e| e.to_string())?;
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    create_tables(&conn).map_err(|e| e.to_string())?;
+    Ok(pool)
+}
 
+/// Create the agent and settings tables, upgrading tables of older versions
+pub fn create_tables(conn: &Connection) -> SqliteResult<()> {
     // Create agents table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS agents (
@@ -342,13 +367,13 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     )?;
 
-    Ok(conn)
+    Ok(())
 }
 
 /// List all agents
 #[tauri::command]
 pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     let mut stmt = conn
         .prepare("SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents ORDER BY created_at DESC")
@@ -394,7 +419,7 @@ pub async fn create_agent(
     enable_network: Option<bool>,
     hooks: Option<String>,
 ) -> Result<Agent, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
     let model = model.unwrap_or_else(|| "sonnet".to_string());
     let enable_file_read = enable_file_read.unwrap_or(true);
     let enable_file_write = enable_file_write.unwrap_or(true);
@@ -450,7 +475,7 @@ pub async fn update_agent(
     enable_network: Option<bool>,
     hooks: Option<String>,
 ) -> Result<Agent, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
     let model = model.unwrap_or_else(|| "sonnet".to_string());
 
     // Build dynamic query based on provided parameters
@@ -523,7 +548,7 @@ pub async fn update_agent(
 /// Delete an agent
 #[tauri::command]
 pub async fn delete_agent(db: State<'_, AgentDb>, id: i64) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     conn.execute("DELETE FROM agents WHERE id = ?1", params![id])
         .map_err(|e| e.to_string())?;
@@ -534,7 +559,7 @@ pub async fn delete_agent(db: State<'_, AgentDb>, id: i64) -> Result<(), String>
 /// Get a single agent by ID
 #[tauri::command]
 pub async fn get_agent(db: State<'_, AgentDb>, id: i64) -> Result<Agent, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     let agent = conn
         .query_row(
@@ -568,7 +593,7 @@ pub async fn list_agent_runs(
     db: State<'_, AgentDb>,
     agent_id: Option<i64>,
 ) -> Result<Vec<AgentRun>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     let query = if agent_id.is_some() {
         "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
@@ -619,7 +644,7 @@ pub async fn list_agent_runs(
 /// Get a single agent run by ID
 #[tauri::command]
 pub async fn get_agent_run(db: State<'_, AgentDb>, id: i64) -> Result<AgentRun, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     let run = conn
         .query_row(
@@ -734,7 +759,7 @@ pub async fn execute_agent(
 
     // Create a new run record
     let run_id = {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let conn = db.0.get().map_err(|e| e.to_string())?;
         conn.execute(
             "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![agent_id, agent.name, agent.icon, task, execution_model, project_path, ""],
@@ -821,6 +846,13 @@ async fn spawn_agent_system(
 ) -> Result<i64, String> {
     // Build the command
     let mut cmd = create_agent_system_command(&claude_path, args, &project_path);
+    // Attribute the run's gateway usage to its project
+    if let Some((key, value)) =
+        crate::commands::llm_gateway::project_attribution_env(&app, cmd.as_std(), &project_path)
+            .await
+    {
+        cmd.env(key, value);
+    }
 
     // Spawn the process
     info!("🚀 Spawning Claude system process...");
@@ -838,7 +870,7 @@ async fn spawn_agent_system(
 
     // Update the database with PID and status
     {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let conn = db.0.get().map_err(|e| e.to_string())?;
         conn.execute(
             "UPDATE agent_runs SET status = 'running', pid = ?1, process_started_at = ?2 WHERE id = ?3",
             params![pid as i64, now, run_id],
@@ -1136,7 +1168,7 @@ pub async fn list_running_sessions(
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
 ) -> Result<Vec<AgentRun>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     // First get all running sessions from the database
     let mut stmt = conn.prepare(
@@ -1224,7 +1256,7 @@ pub async fn kill_agent_session(
     // If registry kill didn't work, try fallback with PID from database
     if !killed_via_registry {
         let pid_result = {
-            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            let conn = db.0.get().map_err(|e| e.to_string())?;
             conn.query_row(
                 "SELECT pid FROM agent_runs WHERE id = ?1 AND status = 'running'",
                 params![run_id],
@@ -1240,7 +1272,7 @@ pub async fn kill_agent_session(
     }
 
     // Update the database to mark as cancelled
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
     let updated = conn.execute(
         "UPDATE agent_runs SET status = 'cancelled', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
         params![run_id],
@@ -1258,7 +1290,7 @@ pub async fn get_session_status(
     db: State<'_, AgentDb>,
     run_id: i64,
 ) -> Result<Option<String>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     match conn.query_row(
         "SELECT status FROM agent_runs WHERE id = ?1",
@@ -1274,7 +1306,7 @@ pub async fn get_session_status(
 /// Cleanup finished processes and update their status
 #[tauri::command]
 pub async fn cleanup_finished_processes(db: State<'_, AgentDb>) -> Result<Vec<i64>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     // Get all running processes
     let mut stmt = conn
@@ -1529,7 +1561,7 @@ pub async fn stream_session_output(
 /// Export a single agent to JSON format
 #[tauri::command]
 pub async fn export_agent(db: State<'_, AgentDb>, id: i64) -> Result<String, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     // Fetch the agent
     let agent = conn
@@ -1580,7 +1612,7 @@ pub async fn export_agent_to_file(
 /// Get the stored Claude binary path from settings
 #[tauri::command]
 pub async fn get_claude_binary_path(db: State<'_, AgentDb>) -> Result<Option<String>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     match conn.query_row(
         "SELECT value FROM app_settings WHERE key = 'claude_binary_path'",
@@ -1596,7 +1628,7 @@ pub async fn get_claude_binary_path(db: State<'_, AgentDb>) -> Result<Option<Str
 /// Set the Claude binary path in settings
 #[tauri::command]
 pub async fn set_claude_binary_path(db: State<'_, AgentDb>, path: String) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     // Validate that the path exists and is executable
     let path_buf = std::path::PathBuf::from(&path);
@@ -1715,7 +1747,7 @@ pub async fn import_agent(db: State<'_, AgentDb>, json_data: String) -> Result<A
     }
 
     let agent_data = export_data.agent;
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     // Check if an agent with the same name already exists
     let existing_count: i64 = conn
@@ -305,6 +305,15 @@ fn create_system_command(claude_path: &str, args: Vec<String>, project_path: &st
     cmd
 }
 
+/// Attribute a session's gateway usage to its project while the LLM gateway is running
+async fn attribute_to_project(app: &AppHandle, cmd: &mut Command, project_path: &str) {
+    if let Some((key, value)) =
+        crate::commands::llm_gateway::project_attribution_env(app, cmd.as_std(), project_path).await
+    {
+        cmd.env(key, value);
+    }
+}
+
 /// Gets the user's home directory path
 #[tauri::command]
 pub async fn get_home_directory() -> Result<String, String> {
@@ -943,7 +952,8 @@ pub async fn execute_claude_code(
         "--dangerously-skip-permissions".to_string(),
     ];
 
-    let cmd = create_system_command(&claude_path, args, &project_path);
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    attribute_to_project(&app, &mut cmd, &project_path).await;
     spawn_claude_process(app, cmd, prompt, model, project_path).await
 }
 
@@ -975,7 +985,8 @@ pub async fn continue_claude_code(
         "--dangerously-skip-permissions".to_string(),
     ];
 
-    let cmd = create_system_command(&claude_path, args, &project_path);
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    attribute_to_project(&app, &mut cmd, &project_path).await;
     spawn_claude_process(app, cmd, prompt, model, project_path).await
 }
 
@@ -1010,7 +1021,8 @@ pub async fn resume_claude_code(
         "--dangerously-skip-permissions".to_string(),
     ];
 
-    let cmd = create_system_command(&claude_path, args, &project_path);
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    attribute_to_project(&app, &mut cmd, &project_path).await;
     spawn_claude_process(app, cmd, prompt, model, project_path).await
 }
 
@@ -0,0 +1,333 @@
+//! Provider Adapters - Declarative description of upstream provider APIs
+//!
+//! An [`AdapterSpec`] tells the gateway how to talk to a provider: which header carries
+//! the credential, where the chat and model endpoints live, and how request/response
+//! fields map onto the OpenAI chat completions shape used internally by the gateway.
+//!
+//! Built-in providers get their spec from [`AdapterSpec::for_provider`]. Attaching a spec
+//! to a `ProviderConfig` overrides it, which lets users add exotic providers without code
+//! changes.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::zhipu;
+use super::{LLMProvider, ProviderConfig};
+
+/// How the provider key is turned into the credential that is sent
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// The key itself, through `auth_template`
+    #[default]
+    Key,
+    /// A JWT signed with the secret of an `id.secret` Zhipu key; other keys are sent as-is
+    ZhipuJwt,
+}
+
+/// Moves a value from one dotted path to another (e.g. `max_tokens` → `parameters.max_new_tokens`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldMapping {
+    /// Source path; numeric segments index into arrays
+    pub from: String,
+    /// Destination path; missing objects are created
+    pub to: String,
+}
+
+/// Declarative adapter specification
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AdapterSpec {
+    /// Header carrying the credential (empty disables header auth)
+    pub auth_header: String,
+    /// Header value template, `{api_key}` is replaced with the provider key
+    pub auth_template: String,
+    /// Credential derived from the key before it goes into `auth_template`
+    pub auth_scheme: AuthScheme,
+    /// Chat endpoint path appended to the base URL, `{model}` is replaced with the model ID
+    pub chat_path: String,
+    /// Model listing path appended to the base URL
+    pub models_path: String,
+    /// Extra query parameters; values may use `{api_key}` and `{model}`
+    pub query_params: HashMap<String, String>,
+    /// Static fields merged into every request body (request values win)
+    pub request_defaults: serde_json::Map<String, Value>,
+    /// Field moves applied to the outgoing request body
+    pub request_mappings: Vec<FieldMapping>,
+    /// Field copies applied to non-streaming responses to produce the OpenAI shape
+    pub response_mappings: Vec<FieldMapping>,
+}
+
+impl Default for AdapterSpec {
+    /// Plain OpenAI-compatible API with bearer authentication
+    fn default() -> Self {
+        Self {
+            auth_header: "Authorization".to_string(),
+            auth_template: "Bearer {api_key}".to_string(),
+            auth_scheme: AuthScheme::Key,
+            chat_path: "/chat/completions".to_string(),
+            models_path: "/models".to_string(),
+            query_params: HashMap::new(),
+            request_defaults: serde_json::Map::new(),
+            request_mappings: Vec::new(),
+            response_mappings: Vec::new(),
+        }
+    }
+}
+
+impl AdapterSpec {
+    /// Built-in spec for a provider
+    pub fn for_provider(provider: &LLMProvider) -> Self {
+        match provider {
+            // Anthropic's OpenAI SDK compatibility layer accepts the native key header
+            LLMProvider::Anthropic => Self {
+                auth_header: "x-api-key".to_string(),
+                auth_template: "{api_key}".to_string(),
+                ..Self::default()
+            },
+            // Gemini exposes its OpenAI-compatible surface under /openai
+            LLMProvider::Gemini => Self {
+                chat_path: "/openai/chat/completions".to_string(),
+                models_path: "/openai/models".to_string(),
+                ..Self::default()
+            },
+            LLMProvider::Zhipu => Self {
+                auth_scheme: AuthScheme::ZhipuJwt,
+                ..Self::default()
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Spec to use for a configured provider: its own override or the built-in one
+    pub fn resolve(config: &ProviderConfig) -> Self {
+        config
+            .adapter
+            .clone()
+            .unwrap_or_else(|| Self::for_provider(&config.provider))
+    }
+
+    /// Full chat endpoint URL for a model
+    pub fn chat_url(&self, base_url: &str, model: &str) -> String {
+        join_url(base_url, &self.chat_path.replace("{model}", model))
+    }
+
+    /// Full model listing URL
+    pub fn models_url(&self, base_url: &str) -> String {
+        join_url(base_url, &self.models_path)
+    }
+
+    /// Attach credentials and query parameters to an upstream request
+    pub fn authorize(
+        &self,
+        mut request: reqwest::RequestBuilder,
+        api_key: Option<&str>,
+        model: &str,
+    ) -> reqwest::RequestBuilder {
+        let api_key = api_key.unwrap_or_default();
+
+        if !self.auth_header.is_empty() && !api_key.is_empty() {
+            let credential = match self.auth_scheme {
+                AuthScheme::ZhipuJwt => zhipu::token(api_key),
+                AuthScheme::Key => None,
+            };
+            request = request.header(
+                self.auth_header.as_str(),
+                self.auth_template
+                    .replace("{api_key}", credential.as_deref().unwrap_or(api_key)),
+            );
+        }
+
+        if !self.query_params.is_empty() {
+            let query: Vec<(String, String)> = self
+                .query_params
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        k.clone(),
+                        v.replace("{api_key}", api_key).replace("{model}", model),
+                    )
+                })
+                .collect();
+            request = request.query(&query);
+        }
+
+        request
+    }
+
+    /// Apply request defaults and field mappings to an OpenAI-shaped body
+    pub fn map_request(&self, mut body: Value) -> Value {
+        if let Some(obj) = body.as_object_mut() {
+            for (key, value) in &self.request_defaults {
+                obj.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+
+        for mapping in &self.request_mappings {
+            if let Some(value) = take_path(&mut body, &mapping.from) {
+                set_path(&mut body, &mapping.to, value);
+            }
+        }
+
+        body
+    }
+
+    /// Apply response field mappings to a non-streaming provider response
+    pub fn map_response(&self, mut body: Value) -> Value {
+        for mapping in &self.response_mappings {
+            if let Some(value) = get_path(&body, &mapping.from).cloned() {
+                set_path(&mut body, &mapping.to, value);
+            }
+        }
+        body
+    }
+}
+
+fn join_url(base_url: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// Look up a dotted path such as `choices.0.message.content`
+pub(crate) fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|s| !s.is_empty())
+        .try_fold(value, |current, segment| match current {
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            Value::Object(map) => map.get(segment),
+            _ => None,
+        })
+}
+
+/// Remove and return the value at a dotted path
+pub(crate) fn take_path(value: &mut Value, path: &str) -> Option<Value> {
+    let (parent, last) = match path.rsplit_once('.') {
+        Some((parent, last)) => (get_path_mut(value, parent)?, last),
+        None => (value, path),
+    };
+    match parent {
+        Value::Object(map) => map.remove(last),
+        Value::Array(items) => {
+            let index = last.parse::<usize>().ok()?;
+            (index < items.len()).then(|| items.remove(index))
+        }
+        _ => None,
+    }
+}
+
+/// Set the value at a dotted path, creating intermediate objects/arrays as needed
+pub(crate) fn set_path(value: &mut Value, path: &str, new_value: Value) {
+    let segments: Vec<&str> = path.split('.').filter(|s| !s.is_empty()).collect();
+    let Some((last, parents)) = segments.split_last() else {
+        *value = new_value;
+        return;
+    };
+
+    let mut current = value;
+    for segment in parents {
+        current = match child_slot(current, segment) {
+            Some(child) => child,
+            None => return,
+        };
+    }
+    if let Some(slot) = child_slot(current, last) {
+        *slot = new_value;
+    }
+}
+
+/// Mutable slot for `segment` inside `value`, turning scalars into a container first
+fn child_slot<'a>(value: &'a mut Value, segment: &str) -> Option<&'a mut Value> {
+    let index = segment.parse::<usize>().ok();
+    if !value.is_object() && !value.is_array() {
+        *value = match index {
+            Some(_) => Value::Array(Vec::new()),
+            None => Value::Object(serde_json::Map::new()),
+        };
+    }
+
+    match value {
+        Value::Array(items) => {
+            let index = index?;
+            if index >= items.len() {
+                items.resize(index + 1, Value::Null);
+            }
+            items.get_mut(index)
+        }
+        Value::Object(map) => Some(map.entry(segment.to_string()).or_insert(Value::Null)),
+        _ => None,
+    }
+}
+
+fn get_path_mut<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.')
+        .filter(|s| !s.is_empty())
+        .try_fold(value, |current, segment| match current {
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(move |i| items.get_mut(i)),
+            Value::Object(map) => map.get_mut(segment),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builtin_specs() {
+        let gemini = AdapterSpec::for_provider(&LLMProvider::Gemini);
+        assert_eq!(
+            gemini.chat_url(
+                "https://generativelanguage.googleapis.com/v1beta/",
+                "gemini-1.5-pro"
+            ),
+            "https://generativelanguage.googleapis.com/v1beta/openai/chat/completions"
+        );
+
+        let openai = AdapterSpec::for_provider(&LLMProvider::OpenAI);
+        assert_eq!(
+            openai.models_url("https://api.openai.com/v1"),
+            "https://api.openai.com/v1/models"
+        );
+    }
+
+    #[test]
+    fn test_custom_spec_maps_request_and_response() {
+        let spec = AdapterSpec {
+            chat_path: "/models/{model}/generate".to_string(),
+            request_defaults: json!({"stream": false}).as_object().unwrap().clone(),
+            request_mappings: vec![FieldMapping {
+                from: "max_tokens".to_string(),
+                to: "parameters.max_new_tokens".to_string(),
+            }],
+            response_mappings: vec![FieldMapping {
+                from: "output.text".to_string(),
+                to: "choices.0.message.content".to_string(),
+            }],
+            ..AdapterSpec::default()
+        };
+
+        assert_eq!(
+            spec.chat_url("https://example.com/api", "tiny"),
+            "https://example.com/api/models/tiny/generate"
+        );
+
+        let request = spec.map_request(json!({"model": "tiny", "max_tokens": 16}));
+        assert_eq!(request["parameters"]["max_new_tokens"], 16);
+        assert!(request.get("max_tokens").is_none());
+        assert_eq!(request["stream"], false);
+
+        let response = spec.map_response(json!({"output": {"text": "hello"}}));
+        assert_eq!(
+            get_path(&response, "choices.0.message.content"),
+            Some(&json!("hello"))
+        );
+    }
+}
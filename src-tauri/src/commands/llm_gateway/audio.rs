@@ -0,0 +1,175 @@
+//! Audio - Transcription uploads and speech synthesis
+//!
+//! OpenAI and Groq share the OpenAI `/audio/transcriptions` API, so uploads are forwarded
+//! with only the model replaced by the routed one. Files over the providers' 25 MB limit
+//! are rejected locally instead of being uploaded first. Whisper models are billed per
+//! minute of audio and newer models per token; responses report one or the other.
+//!
+//! Speech requests go to the OpenAI `/audio/speech` API with the routed model and, when
+//! the client used a voice alias, the provider's voice. Audio is streamed back as it is
+//! synthesized.
+
+use axum::body::Bytes;
+use axum::extract::Multipart;
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use super::usage::{self, TokenUsage};
+
+/// Largest audio file OpenAI and Groq accept
+pub const MAX_AUDIO_BYTES: usize = 25 * 1024 * 1024;
+
+/// A transcription request as uploaded by the client
+pub struct AudioUpload {
+    pub file: Bytes,
+    pub file_name: String,
+    pub content_type: Option<String>,
+    /// Text fields (`model`, `language`, `response_format`, ...) in upload order
+    pub fields: Vec<(String, String)>,
+}
+
+impl AudioUpload {
+    /// Read a multipart upload, checking that it has a file within the size limit
+    pub async fn read(mut multipart: Multipart) -> Result<Self, (StatusCode, String)> {
+        let invalid = |e: axum::extract::multipart::MultipartError| (e.status(), e.body_text());
+        let mut file = None;
+        let mut fields = Vec::new();
+
+        while let Some(field) = multipart.next_field().await.map_err(invalid)? {
+            let name = field.name().unwrap_or_default().to_string();
+            if name == "file" {
+                let file_name = field.file_name().unwrap_or("audio").to_string();
+                let content_type = field.content_type().map(str::to_string);
+                let bytes = field.bytes().await.map_err(invalid)?;
+                if bytes.len() > MAX_AUDIO_BYTES {
+                    return Err((
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        format!(
+                            "Audio file is {:.1} MB, the limit is {} MB",
+                            bytes.len() as f64 / (1024.0 * 1024.0),
+                            MAX_AUDIO_BYTES / (1024 * 1024)
+                        ),
+                    ));
+                }
+                file = Some((bytes, file_name, content_type));
+            } else {
+                fields.push((name, field.text().await.map_err(invalid)?));
+            }
+        }
+
+        let (file, file_name, content_type) =
+            file.ok_or((StatusCode::BAD_REQUEST, "'file' is required".to_string()))?;
+        Ok(Self {
+            file,
+            file_name,
+            content_type,
+            fields,
+        })
+    }
+
+    /// Value of a text field
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Upstream form with `model` replaced by the routed model
+    pub fn into_form(self, model: &str) -> Result<reqwest::multipart::Form, String> {
+        let mut file =
+            reqwest::multipart::Part::bytes(self.file.to_vec()).file_name(self.file_name);
+        if let Some(content_type) = &self.content_type {
+            file = file
+                .mime_str(content_type)
+                .map_err(|e| format!("Invalid file content type: {}", e))?;
+        }
+
+        let form = self
+            .fields
+            .into_iter()
+            .filter(|(name, _)| name != "model")
+            .fold(reqwest::multipart::Form::new(), |form, (name, value)| {
+                form.text(name, value)
+            });
+        Ok(form.text("model", model.to_string()).part("file", file))
+    }
+}
+
+/// Upstream body for a speech request, with the routed model and aliased voice
+pub fn map_speech_request(
+    request: &Value,
+    model: &str,
+    voice: Option<&str>,
+) -> Result<Value, String> {
+    if speech_input(request).trim().is_empty() {
+        return Err("'input' is required".to_string());
+    }
+    let mut body = request.clone();
+    body["model"] = Value::String(model.to_string());
+    if let Some(voice) = voice {
+        body["voice"] = Value::String(voice.to_string());
+    }
+    if body.get("voice").and_then(|v| v.as_str()).is_none() {
+        return Err("'voice' is required".to_string());
+    }
+    Ok(body)
+}
+
+/// Text a speech request asks to synthesize
+pub fn speech_input(request: &Value) -> &str {
+    request
+        .get("input")
+        .and_then(|i| i.as_str())
+        .unwrap_or_default()
+}
+
+/// Seconds of audio a JSON transcription response reports
+pub fn transcription_seconds(body: &Value) -> Option<f64> {
+    // `usage.seconds` on OpenAI's JSON responses, `duration` on verbose JSON
+    body.pointer("/usage/seconds")
+        .or_else(|| body.get("duration"))
+        .and_then(|s| s.as_f64())
+}
+
+/// Token usage a JSON transcription response reports, for models billed per token
+pub fn transcription_usage(body: &Value) -> Option<TokenUsage> {
+    body.pointer("/usage/input_tokens")?;
+    usage::extract_usage(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_speech_request() {
+        let request = json!({"model": "tts-1", "input": "Hello there", "voice": "narrator"});
+        let body = map_speech_request(&request, "gpt-4o-mini-tts", Some("onyx")).unwrap();
+        assert_eq!(body["model"], "gpt-4o-mini-tts");
+        assert_eq!(body["voice"], "onyx");
+        assert_eq!(body["input"], "Hello there");
+
+        assert!(map_speech_request(&json!({"input": "hi"}), "tts-1", None).is_err());
+        assert!(map_speech_request(&json!({"voice": "alloy"}), "tts-1", None).is_err());
+    }
+
+    #[test]
+    fn test_transcription_usage() {
+        let whisper = json!({"text": "hi", "usage": {"type": "duration", "seconds": 90}});
+        assert_eq!(transcription_seconds(&whisper), Some(90.0));
+        assert_eq!(transcription_usage(&whisper), None);
+
+        let verbose = json!({"text": "hi", "duration": 12.5, "segments": []});
+        assert_eq!(transcription_seconds(&verbose), Some(12.5));
+
+        let tokens = json!({
+            "text": "hi",
+            "usage": {"type": "tokens", "input_tokens": 120, "output_tokens": 8},
+        });
+        assert_eq!(transcription_seconds(&tokens), None);
+        let usage = transcription_usage(&tokens).unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (120, 8));
+    }
+}
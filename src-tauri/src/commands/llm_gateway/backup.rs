@@ -0,0 +1,253 @@
+//! Database Backups - Snapshots of the app database
+//!
+//! Backups are taken with SQLite's online backup API, so a snapshot is consistent even
+//! while the gateway keeps logging requests. Restoring copies a snapshot back the same
+//! way, after first backing up the current database, then brings the restored tables
+//! up to date with this build's schema.
+//!
+//! [`schedule`] runs for the lifetime of the app and takes an automatic backup once the
+//! configured interval has passed since the last one, checking every hour. Only the
+//! newest automatic backups are kept; backups taken by hand are never deleted.
+
+use chrono::{DateTime, Local};
+use rusqlite::{Connection, DatabaseName};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager};
+
+use super::{load_gateway_settings, migrations};
+use crate::commands::agents::{create_tables, AgentDb};
+
+/// How often the schedule checks whether a backup is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+const DEFAULT_INTERVAL_HOURS: u32 = 24;
+const DEFAULT_KEEP: usize = 7;
+
+/// File name prefix of automatic backups, the only ones pruned
+const AUTO_PREFIX: &str = "agents-auto-";
+/// File name prefix of backups taken by hand
+pub const MANUAL_PREFIX: &str = "agents-";
+/// File name prefix of the backups taken before a restore
+pub const PRE_RESTORE_PREFIX: &str = "agents-pre-restore-";
+
+/// Automatic database backups
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BackupConfig {
+    /// Take backups automatically
+    #[serde(default)]
+    pub enabled: bool,
+    /// Hours between automatic backups (default 24)
+    #[serde(default)]
+    pub interval_hours: Option<u32>,
+    /// Automatic backups kept (default 7)
+    #[serde(default)]
+    pub keep: Option<usize>,
+    /// Directory backups are written to (default `backups` in the app data directory)
+    #[serde(default)]
+    pub directory: Option<String>,
+}
+
+/// A backup file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackupInfo {
+    pub path: String,
+    pub size_bytes: u64,
+    /// When the file was written (RFC 3339)
+    pub created_at: String,
+    /// Whether the schedule took it, and may delete it
+    pub automatic: bool,
+}
+
+fn backup_info(path: &Path) -> Option<BackupInfo> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?;
+    Some(BackupInfo {
+        path: path.display().to_string(),
+        size_bytes: metadata.len(),
+        created_at: DateTime::<Local>::from(modified).to_rfc3339(),
+        automatic: path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(AUTO_PREFIX)),
+    })
+}
+
+/// Directory backups go to
+pub fn backup_dir(app: &AppHandle, config: Option<&BackupConfig>) -> Result<PathBuf, String> {
+    match config
+        .and_then(|c| c.directory.as_deref())
+        .filter(|d| !d.is_empty())
+    {
+        Some(directory) => Ok(PathBuf::from(directory)),
+        None => app
+            .path()
+            .app_data_dir()
+            .map(|dir| dir.join("backups"))
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Path for a new backup in `dir`, named after the current time
+pub fn new_backup_path(dir: &Path, prefix: &str) -> PathBuf {
+    dir.join(format!(
+        "{}{}.db",
+        prefix,
+        Local::now().format("%Y%m%d-%H%M%S")
+    ))
+}
+
+/// Write a snapshot of the database to `path`
+pub fn backup_to(conn: &Connection, path: &Path) -> Result<BackupInfo, String> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    conn.backup(DatabaseName::Main, path, None)
+        .map_err(|e| format!("Failed to back up to {}: {}", path.display(), e))?;
+    backup_info(path).ok_or_else(|| format!("Backup {} was not written", path.display()))
+}
+
+/// Replace the database with the snapshot at `path`, then upgrade its tables
+pub fn restore_from(conn: &mut Connection, path: &Path) -> Result<(), String> {
+    if !path.is_file() {
+        return Err(format!("Backup {} not found", path.display()));
+    }
+    conn.restore(
+        DatabaseName::Main,
+        path,
+        None::<fn(rusqlite::backup::Progress)>,
+    )
+    .map_err(|e| format!("Failed to restore {}: {}", path.display(), e))?;
+    create_tables(conn).map_err(|e| e.to_string())?;
+    migrations::run(conn)?;
+    Ok(())
+}
+
+/// Backups in `dir`, newest first
+pub fn list_backups(dir: &Path) -> Vec<BackupInfo> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<BackupInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "db")
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(MANUAL_PREFIX))
+        })
+        .filter_map(|path| backup_info(&path))
+        .collect();
+    // Names sort by time within each kind; the timestamp decides across kinds
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.path.cmp(&a.path)));
+    backups
+}
+
+/// Delete the automatic backups beyond the newest `keep`
+fn prune(dir: &Path, keep: usize) -> Vec<PathBuf> {
+    let mut automatic: Vec<PathBuf> = list_backups(dir)
+        .into_iter()
+        .filter(|b| b.automatic)
+        .map(|b| PathBuf::from(b.path))
+        .collect();
+    automatic.sort_by(|a, b| b.cmp(a));
+    let stale = automatic.split_off(keep.min(automatic.len()));
+    for path in &stale {
+        if let Err(e) = std::fs::remove_file(path) {
+            log::warn!("Failed to delete old backup {}: {}", path.display(), e);
+        }
+    }
+    stale
+}
+
+/// Take an automatic backup if the interval has passed since the last one
+fn run_due(app: &AppHandle) -> Result<(), String> {
+    let db = app.state::<AgentDb>();
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let Some(config) = load_gateway_settings(&conn).backups.filter(|c| c.enabled) else {
+        return Ok(());
+    };
+    let dir = backup_dir(app, Some(&config))?;
+    let interval = Duration::from_secs(
+        config
+            .interval_hours
+            .unwrap_or(DEFAULT_INTERVAL_HOURS)
+            .max(1) as u64
+            * 3600,
+    );
+    let last = list_backups(&dir)
+        .into_iter()
+        .filter(|b| b.automatic)
+        .filter_map(|b| std::fs::metadata(&b.path).and_then(|m| m.modified()).ok())
+        .max();
+    let due = last.is_none_or(|last| {
+        SystemTime::now()
+            .duration_since(last)
+            .is_ok_and(|age| age >= interval)
+    });
+    if !due {
+        return Ok(());
+    }
+    let backup = backup_to(&conn, &new_backup_path(&dir, AUTO_PREFIX))?;
+    log::info!("Backed up the database to {}", backup.path);
+    prune(&dir, config.keep.unwrap_or(DEFAULT_KEEP).max(1));
+    Ok(())
+}
+
+/// Take scheduled backups for as long as the app runs
+pub async fn schedule(app: AppHandle) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = run_due(&app) {
+            log::warn!("Failed to back up the database: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = Connection::open(dir.path().join("agents.db")).unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES ('theme', 'dark')",
+            [],
+        )
+        .unwrap();
+
+        let backup = backup_to(&conn, &dir.path().join("backups/agents-manual.db")).unwrap();
+        assert!(!backup.automatic);
+        conn.execute("UPDATE app_settings SET value = 'light'", [])
+            .unwrap();
+        restore_from(&mut conn, Path::new(&backup.path)).unwrap();
+        let theme: String = conn
+            .query_row("SELECT value FROM app_settings", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(theme, "dark");
+        // The restored database has the gateway tables too
+        assert!(migrations::current_version(&conn).unwrap() > 0);
+
+        for day in 1..=4 {
+            let path = dir
+                .path()
+                .join(format!("backups/{}2026100{}-000000.db", AUTO_PREFIX, day));
+            backup_to(&conn, &path).unwrap();
+        }
+        let stale = prune(&dir.path().join("backups"), 2);
+        assert_eq!(stale.len(), 2);
+        assert!(stale.iter().all(|p| {
+            let name = p.file_name().unwrap().to_str().unwrap();
+            name.ends_with("20261001-000000.db") || name.ends_with("20261002-000000.db")
+        }));
+        assert_eq!(list_backups(&dir.path().join("backups")).len(), 3);
+    }
+}
@@ -0,0 +1,528 @@
+//! Batches - Bulk processing of JSONL request files through the gateway
+//!
+//! A batch is a file with one request per line in OpenAI's batch input format
+//! (`custom_id`, `method`, `url`, `body`). Requests are stored in SQLite and sent to the
+//! running gateway's own endpoints, so they get the same routing, rate limits, failover
+//! and usage logging as interactive traffic, with a per-batch cap on concurrency.
+//! Results are stored as they arrive and progress is reported with
+//! [`BATCH_PROGRESS_EVENT`]. Batches interrupted by the gateway stopping pick up where
+//! they left off the next time it starts.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::commands::agents::AgentDb;
+
+/// Event emitted with the [`BatchJob`] each time one of its requests completes
+pub const BATCH_PROGRESS_EVENT: &str = "llm-gateway-batch-progress";
+
+/// Requests sent at once when the batch doesn't say
+pub const DEFAULT_CONCURRENCY: u32 = 4;
+
+const MAX_CONCURRENCY: u32 = 32;
+
+/// Gateway endpoints a batch request may target
+const BATCH_ENDPOINTS: &[&str] = &[
+    "/v1/chat/completions",
+    "/v1/completions",
+    "/v1/embeddings",
+    "/v1/messages",
+    "/v1/responses",
+];
+
+/// Create the batch tables if they don't exist yet
+pub fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS gateway_batches (
+            id TEXT PRIMARY KEY,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            finished_at TEXT,
+            status TEXT NOT NULL,
+            concurrency INTEGER NOT NULL,
+            total INTEGER NOT NULL,
+            completed INTEGER NOT NULL DEFAULT 0,
+            failed INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS gateway_batch_items (
+            batch_id TEXT NOT NULL,
+            line INTEGER NOT NULL,
+            custom_id TEXT NOT NULL,
+            url TEXT NOT NULL,
+            body TEXT NOT NULL,
+            status_code INTEGER,
+            response TEXT,
+            error TEXT,
+            PRIMARY KEY (batch_id, line)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// State of a batch
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    InProgress,
+    /// Cancel requested; requests already sent are still finishing
+    Cancelling,
+    Cancelled,
+    Completed,
+}
+
+impl BatchStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            BatchStatus::InProgress => "in_progress",
+            BatchStatus::Cancelling => "cancelling",
+            BatchStatus::Cancelled => "cancelled",
+            BatchStatus::Completed => "completed",
+        }
+    }
+
+    fn parse(status: &str) -> Self {
+        match status {
+            "cancelling" => BatchStatus::Cancelling,
+            "cancelled" => BatchStatus::Cancelled,
+            "completed" => BatchStatus::Completed,
+            _ => BatchStatus::InProgress,
+        }
+    }
+}
+
+/// One line of a batch input file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub custom_id: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub url: String,
+    pub body: Value,
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+/// A batch and its progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJob {
+    pub id: String,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+    pub status: BatchStatus,
+    pub concurrency: u32,
+    pub total: u64,
+    pub completed: u64,
+    pub failed: u64,
+}
+
+/// Outcome of one batch request; `status_code` is unset until it has been sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub custom_id: String,
+    pub status_code: Option<u16>,
+    pub response: Option<Value>,
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    /// Line of an OpenAI batch output file
+    pub fn output_line(&self) -> Value {
+        json!({
+            "custom_id": self.custom_id,
+            "response": self.status_code.map(|status_code| json!({
+                "status_code": status_code,
+                "body": self.response,
+            })),
+            "error": self.error.as_ref().map(|message| json!({"message": message})),
+        })
+    }
+}
+
+/// Parse a JSONL batch input file, rejecting it whole if any line is invalid
+pub fn parse_requests(input: &str) -> Result<Vec<BatchRequest>, String> {
+    let mut requests: Vec<BatchRequest> = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: BatchRequest = serde_json::from_str(line)
+            .map_err(|e| format!("Line {}: invalid batch request: {}", number + 1, e))?;
+        if !request.method.eq_ignore_ascii_case("POST") {
+            return Err(format!(
+                "Line {}: only POST requests can be batched",
+                number + 1
+            ));
+        }
+        if !BATCH_ENDPOINTS.contains(&request.url.as_str()) {
+            return Err(format!(
+                "Line {}: '{}' can't be batched, use one of {}",
+                number + 1,
+                request.url,
+                BATCH_ENDPOINTS.join(", ")
+            ));
+        }
+        if requests.iter().any(|r| r.custom_id == request.custom_id) {
+            return Err(format!(
+                "Line {}: duplicate custom_id '{}'",
+                number + 1,
+                request.custom_id
+            ));
+        }
+        requests.push(request);
+    }
+    if requests.is_empty() {
+        return Err("The batch has no requests".to_string());
+    }
+    Ok(requests)
+}
+
+/// Store a new batch with its requests
+pub fn create_batch(
+    conn: &Connection,
+    requests: &[BatchRequest],
+    concurrency: Option<u32>,
+) -> rusqlite::Result<BatchJob> {
+    let id = format!("batch_{}", uuid::Uuid::new_v4().simple());
+    let concurrency = concurrency
+        .unwrap_or(DEFAULT_CONCURRENCY)
+        .clamp(1, MAX_CONCURRENCY);
+    conn.execute(
+        "INSERT INTO gateway_batches (id, status, concurrency, total) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            id,
+            BatchStatus::InProgress.as_str(),
+            concurrency,
+            requests.len() as i64
+        ],
+    )?;
+    for (line, request) in requests.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO gateway_batch_items (batch_id, line, custom_id, url, body)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id,
+                line as i64,
+                request.custom_id,
+                request.url,
+                request.body.to_string()
+            ],
+        )?;
+    }
+    get_batch(conn, &id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)
+}
+
+fn job_from_row(row: &rusqlite::Row) -> rusqlite::Result<BatchJob> {
+    Ok(BatchJob {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        finished_at: row.get(2)?,
+        status: BatchStatus::parse(&row.get::<_, String>(3)?),
+        concurrency: row.get(4)?,
+        total: row.get::<_, i64>(5)? as u64,
+        completed: row.get::<_, i64>(6)? as u64,
+        failed: row.get::<_, i64>(7)? as u64,
+    })
+}
+
+const JOB_COLUMNS: &str =
+    "id, created_at, finished_at, status, concurrency, total, completed, failed";
+
+pub fn get_batch(conn: &Connection, id: &str) -> rusqlite::Result<Option<BatchJob>> {
+    conn.query_row(
+        &format!("SELECT {} FROM gateway_batches WHERE id = ?1", JOB_COLUMNS),
+        params![id],
+        job_from_row,
+    )
+    .optional()
+}
+
+/// All batches, newest first
+pub fn list_batches(conn: &Connection) -> rusqlite::Result<Vec<BatchJob>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM gateway_batches ORDER BY created_at DESC, rowid DESC",
+        JOB_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], job_from_row)?;
+    rows.collect()
+}
+
+/// Results of a batch in input order
+pub fn batch_results(conn: &Connection, id: &str) -> rusqlite::Result<Vec<BatchResult>> {
+    let mut stmt = conn.prepare(
+        "SELECT custom_id, status_code, response, error FROM gateway_batch_items
+         WHERE batch_id = ?1 ORDER BY line",
+    )?;
+    let rows = stmt.query_map(params![id], |row| {
+        let response: Option<String> = row.get(2)?;
+        Ok(BatchResult {
+            custom_id: row.get(0)?,
+            status_code: row.get(1)?,
+            response: response.map(|r| serde_json::from_str(&r).unwrap_or(Value::String(r))),
+            error: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// IDs of the batches that haven't finished
+pub fn unfinished_batches(conn: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT id FROM gateway_batches WHERE finished_at IS NULL")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Ask a running batch to stop; returns whether it was still running
+pub fn cancel_batch(conn: &Connection, id: &str) -> rusqlite::Result<bool> {
+    let updated = conn.execute(
+        "UPDATE gateway_batches SET status = ?2 WHERE id = ?1 AND status = ?3",
+        params![
+            id,
+            BatchStatus::Cancelling.as_str(),
+            BatchStatus::InProgress.as_str()
+        ],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Requests of a batch that haven't been sent yet
+fn pending_requests(conn: &Connection, id: &str) -> rusqlite::Result<Vec<(i64, BatchRequest)>> {
+    let mut stmt = conn.prepare(
+        "SELECT line, custom_id, url, body FROM gateway_batch_items
+         WHERE batch_id = ?1 AND status_code IS NULL AND error IS NULL ORDER BY line",
+    )?;
+    let rows = stmt.query_map(params![id], |row| {
+        let body: String = row.get(3)?;
+        Ok((
+            row.get(0)?,
+            BatchRequest {
+                custom_id: row.get(1)?,
+                method: default_method(),
+                url: row.get(2)?,
+                body: serde_json::from_str(&body).unwrap_or_default(),
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+fn record_result(
+    conn: &Connection,
+    id: &str,
+    line: i64,
+    status_code: Option<u16>,
+    response: Option<&Value>,
+    error: Option<&str>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE gateway_batch_items SET status_code = ?3, response = ?4, error = ?5
+         WHERE batch_id = ?1 AND line = ?2",
+        params![
+            id,
+            line,
+            status_code,
+            response.map(|r| r.to_string()),
+            error
+        ],
+    )?;
+    let counter = if error.is_none() {
+        "completed"
+    } else {
+        "failed"
+    };
+    conn.execute(
+        &format!(
+            "UPDATE gateway_batches SET {0} = {0} + 1 WHERE id = ?1",
+            counter
+        ),
+        params![id],
+    )?;
+    Ok(())
+}
+
+fn finish_batch(conn: &Connection, id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE gateway_batches
+         SET status = CASE WHEN status = ?2 THEN ?3 ELSE ?4 END,
+             finished_at = CURRENT_TIMESTAMP
+         WHERE id = ?1",
+        params![
+            id,
+            BatchStatus::Cancelling.as_str(),
+            BatchStatus::Cancelled.as_str(),
+            BatchStatus::Completed.as_str()
+        ],
+    )?;
+    Ok(())
+}
+
+fn with_db<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    let db = app.state::<AgentDb>();
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    f(&conn).map_err(|e| e.to_string())
+}
+
+fn emit_progress(app: &AppHandle, id: &str) {
+    if let Ok(Some(job)) = with_db(app, |conn| get_batch(conn, id)) {
+        let _ = app.emit(BATCH_PROGRESS_EVENT, job);
+    }
+}
+
+/// Send one request to the gateway, returning its status and body
+async fn send_request(
+    http: &reqwest::Client,
+    port: u16,
+    request: &BatchRequest,
+) -> Result<(u16, Value), String> {
+    let mut body = request.body.clone();
+    if let Some(fields) = body.as_object_mut() {
+        fields.insert("stream".to_string(), Value::Bool(false));
+    }
+    let response = http
+        .post(format!("http://127.0.0.1:{}{}", port, request.url))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status().as_u16();
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    Ok((
+        status,
+        serde_json::from_str(&text).unwrap_or(Value::String(text)),
+    ))
+}
+
+/// Work through the unsent requests of a batch against the gateway on `port`
+pub async fn run(app: AppHandle, port: u16, id: String) {
+    use futures::StreamExt;
+
+    let (job, pending) = match with_db(&app, |conn| {
+        Ok((get_batch(conn, &id)?, pending_requests(conn, &id)?))
+    }) {
+        Ok((Some(job), pending)) => (job, pending),
+        Ok((None, _)) => return,
+        Err(e) => {
+            log::warn!("Failed to load batch {}: {}", id, e);
+            return;
+        }
+    };
+    // Straight to the local gateway, never through the configured outbound proxy
+    let http = match reqwest::Client::builder().no_proxy().build() {
+        Ok(http) => http,
+        Err(e) => {
+            log::warn!("Failed to create batch client: {}", e);
+            return;
+        }
+    };
+    log::info!(
+        "Running batch {} ({} requests, concurrency {})",
+        id,
+        pending.len(),
+        job.concurrency
+    );
+
+    futures::stream::iter(pending)
+        .for_each_concurrent(job.concurrency as usize, |(line, request)| {
+            let (app, http, id) = (&app, &http, &id);
+            async move {
+                let cancelled = with_db(app, |conn| get_batch(conn, id))
+                    .ok()
+                    .flatten()
+                    .is_none_or(|job| job.status != BatchStatus::InProgress);
+                if cancelled {
+                    return;
+                }
+                let result = match send_request(http, port, &request).await {
+                    Ok((status, body)) if status < 400 => with_db(app, |conn| {
+                        record_result(conn, id, line, Some(status), Some(&body), None)
+                    }),
+                    Ok((status, body)) => {
+                        let message = body
+                            .pointer("/error/message")
+                            .and_then(|m| m.as_str())
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("Request failed with status {}", status));
+                        with_db(app, |conn| {
+                            record_result(conn, id, line, Some(status), Some(&body), Some(&message))
+                        })
+                    }
+                    Err(e) => with_db(app, |conn| {
+                        record_result(conn, id, line, None, None, Some(&e))
+                    }),
+                };
+                if let Err(e) = result {
+                    log::warn!(
+                        "Failed to store result of batch {} line {}: {}",
+                        id,
+                        line,
+                        e
+                    );
+                }
+                emit_progress(app, id);
+            }
+        })
+        .await;
+
+    if let Err(e) = with_db(&app, |conn| finish_batch(conn, &id)) {
+        log::warn!("Failed to finish batch {}: {}", id, e);
+    }
+    emit_progress(&app, &id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_lifecycle() {
+        let input = r#"{"custom_id": "a", "method": "POST", "url": "/v1/chat/completions", "body": {"model": "fast"}}
+
+{"custom_id": "b", "url": "/v1/embeddings", "body": {"model": "embed", "input": "hi"}}"#;
+        let requests = parse_requests(input).unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(
+            parse_requests(r#"{"custom_id": "a", "url": "/v1/models", "body": {}}"#)
+                .unwrap_err()
+                .starts_with("Line 1")
+        );
+        assert!(parse_requests(&format!("{}\n{}", input, input)).is_err());
+
+        let conn = Connection::open_in_memory().unwrap();
+        ensure_schema(&conn).unwrap();
+        let job = create_batch(&conn, &requests, Some(100)).unwrap();
+        assert_eq!(job.concurrency, MAX_CONCURRENCY);
+        assert_eq!(job.total, 2);
+
+        record_result(
+            &conn,
+            &job.id,
+            0,
+            Some(200),
+            Some(&json!({"ok": true})),
+            None,
+        )
+        .unwrap();
+        let pending = pending_requests(&conn, &job.id).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].1.custom_id, "b");
+
+        assert!(cancel_batch(&conn, &job.id).unwrap());
+        finish_batch(&conn, &job.id).unwrap();
+        let job = get_batch(&conn, &job.id).unwrap().unwrap();
+        assert_eq!(job.status, BatchStatus::Cancelled);
+        assert_eq!(job.completed, 1);
+
+        let results = batch_results(&conn, &job.id).unwrap();
+        assert_eq!(results[0].output_line()["response"]["body"]["ok"], true);
+        assert!(results[1].status_code.is_none());
+    }
+}
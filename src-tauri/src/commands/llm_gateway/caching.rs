@@ -0,0 +1,54 @@
+//! Prompt Caching - `cache_control` breakpoints on providers with and without caching
+//!
+//! Native Anthropic endpoints receive requests untouched, so breakpoints pass straight
+//! through. OpenRouter accepts them on OpenAI-shaped requests too; every other provider
+//! gets them stripped, since strict OpenAI-compatible APIs reject unknown fields.
+
+use serde_json::Value;
+
+use super::{LLMProvider, ProviderConfig};
+
+/// Whether a provider honours `cache_control` breakpoints
+pub fn supports_prompt_caching(provider: &ProviderConfig) -> bool {
+    match provider.provider {
+        LLMProvider::Anthropic => provider.adapter.is_none(),
+        LLMProvider::OpenRouter => true,
+        _ => false,
+    }
+}
+
+/// Remove every `cache_control` field from a request body
+pub fn strip_cache_control(value: &mut Value) {
+    match value {
+        Value::Object(obj) => {
+            obj.remove("cache_control");
+            obj.values_mut().for_each(strip_cache_control);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_cache_control),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_strip_cache_control() {
+        let mut body = json!({
+            "messages": [{"role": "system", "content": [
+                {"type": "text", "text": "Long prompt", "cache_control": {"type": "ephemeral"}}
+            ]}],
+            "tools": [{"type": "function", "cache_control": {"type": "ephemeral"}}]
+        });
+        strip_cache_control(&mut body);
+        assert_eq!(
+            body,
+            json!({
+                "messages": [{"role": "system", "content": [{"type": "text", "text": "Long prompt"}]}],
+                "tools": [{"type": "function"}]
+            })
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Cassettes - Upstream responses recorded to disk and replayed
+//!
+//! In record mode every upstream response is saved to the cassette directory, keyed by
+//! the request's method, URL and body, if any, before it is handed on. In replay mode
+//! requests are answered from the directory without touching the network, so
+//! translation, streaming and failover can be tested against real provider output
+//! offline. Replay fails requests that were never recorded rather than sending them, as
+//! well as those with a streamed body, such as multipart uploads, which can't be keyed.
+//!
+//! Credentials are sent in headers, which are left out of the key and never stored.
+//! Streamed responses are recorded whole and replayed in one piece.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Whether responses are recorded or replayed
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CassetteMode {
+    /// Send requests and save their responses, replacing earlier recordings
+    #[default]
+    Record,
+    /// Answer requests from recordings only
+    Replay,
+}
+
+/// Where and how upstream responses are recorded
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CassetteConfig {
+    #[serde(default)]
+    pub mode: CassetteMode,
+    /// Directory holding the recordings, one file per request
+    pub dir: String,
+}
+
+/// A recorded response
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct Recording {
+    /// Method and path of the request, for whoever browses the cassette
+    request: String,
+    status: u16,
+    content_type: Option<String>,
+    /// Response body, base64-encoded
+    body: String,
+}
+
+/// Identity of a request in a cassette, or `None` when its body is streamed
+fn request_key(request: &reqwest::Request) -> Option<String> {
+    let body = match request.body() {
+        Some(body) => body.as_bytes()?,
+        None => &[],
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(request.method().as_str().as_bytes());
+    hasher.update(request.url().as_str().as_bytes());
+    hasher.update(body);
+    Some(
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+    )
+}
+
+fn recording_path(dir: &str, key: &str) -> PathBuf {
+    Path::new(dir).join(format!("{}.json", key))
+}
+
+/// Response standing in for the upstream one
+fn response(status: u16, content_type: Option<&str>, body: Vec<u8>) -> reqwest::Response {
+    let mut response = axum::http::Response::new(reqwest::Body::from(body));
+    *response.status_mut() =
+        reqwest::StatusCode::from_u16(status).unwrap_or(reqwest::StatusCode::BAD_GATEWAY);
+    let mut headers = HeaderMap::new();
+    if let Some(value) = content_type.and_then(|t| HeaderValue::from_str(t).ok()) {
+        headers.insert(CONTENT_TYPE, value);
+    }
+    *response.headers_mut() = headers;
+    response.into()
+}
+
+impl CassetteConfig {
+    /// Recorded response to `request`, or a 502 when it was never recorded or has a
+    /// streamed body that can't be matched
+    pub fn replay(&self, request: &reqwest::Request) -> reqwest::Response {
+        let recording = request_key(request)
+            .and_then(|key| std::fs::read_to_string(recording_path(&self.dir, &key)).ok())
+            .and_then(|json| serde_json::from_str::<Recording>(&json).ok())
+            .and_then(|r| Some((r.status, r.content_type, STANDARD.decode(r.body).ok()?)));
+        match recording {
+            Some((status, content_type, body)) => response(status, content_type.as_deref(), body),
+            None => {
+                let error = serde_json::json!({"error": {
+                    "message": format!(
+                        "No recording of {} {} in cassette {}",
+                        request.method(),
+                        request.url().path(),
+                        self.dir
+                    ),
+                    "type": "server_error",
+                }});
+                response(
+                    502,
+                    Some("application/json"),
+                    error.to_string().into_bytes(),
+                )
+            }
+        }
+    }
+
+    /// Save `response` as the recording of `request` and hand back an equivalent one.
+    /// The response is read whole, so a stream reaches the client in one piece.
+    pub async fn record(
+        &self,
+        request: Option<&reqwest::Request>,
+        response: reqwest::Response,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let Some(key) = request.and_then(request_key) else {
+            return Ok(response);
+        };
+        let status = response.status().as_u16();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = response.bytes().await?.to_vec();
+
+        let recording = Recording {
+            request: request
+                .map(|r| format!("{} {}", r.method(), r.url().path()))
+                .unwrap_or_default(),
+            status,
+            content_type: content_type.clone(),
+            body: STANDARD.encode(&body),
+        };
+        let written = std::fs::create_dir_all(&self.dir).and_then(|_| {
+            let json = serde_json::to_string_pretty(&recording).map_err(std::io::Error::other)?;
+            std::fs::write(recording_path(&self.dir, &key), json)
+        });
+        if let Err(e) = written {
+            log::warn!("Failed to record response in cassette {}: {}", self.dir, e);
+        }
+        Ok(self::response(status, content_type.as_deref(), body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let cassette = CassetteConfig {
+            mode: CassetteMode::Record,
+            dir: dir.path().to_string_lossy().into_owned(),
+        };
+        let client = reqwest::Client::new();
+        let request = |content: &str| {
+            client
+                .post("https://api.example.com/v1/chat/completions")
+                .bearer_auth("sk-secret")
+                .body(format!(r#"{{"messages":"{}"}}"#, content))
+                .build()
+                .unwrap()
+        };
+
+        let upstream = response(200, Some("text/event-stream"), b"data: [DONE]\n\n".to_vec());
+        let recorded = cassette
+            .record(Some(&request("hi")), upstream)
+            .await
+            .unwrap();
+        assert_eq!(
+            recorded.bytes().await.unwrap().as_ref(),
+            b"data: [DONE]\n\n"
+        );
+
+        let replayed = cassette.replay(&request("hi"));
+        assert_eq!(replayed.status(), 200);
+        assert_eq!(replayed.headers()[CONTENT_TYPE], "text/event-stream");
+        assert_eq!(
+            replayed.bytes().await.unwrap().as_ref(),
+            b"data: [DONE]\n\n"
+        );
+
+        let missing = cassette.replay(&request("bye"));
+        assert_eq!(missing.status(), 502);
+        let error: serde_json::Value = missing.json().await.unwrap();
+        assert!(error["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("No recording of POST /v1/chat/completions"));
+
+        let stored = std::fs::read_dir(dir.path())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let stored = std::fs::read_to_string(stored.path()).unwrap();
+        assert!(stored.contains("POST /v1/chat/completions"));
+        assert!(!stored.contains("sk-secret"));
+
+        // Bodiless requests are keyed by method and URL
+        let models = |url: &str| client.get(url).bearer_auth("sk-secret").build().unwrap();
+        let upstream = response(200, Some("application/json"), br#"{"data":[]}"#.to_vec());
+        cassette
+            .record(Some(&models("https://api.example.com/v1/models")), upstream)
+            .await
+            .unwrap();
+        let replayed = cassette.replay(&models("https://api.example.com/v1/models"));
+        assert_eq!(replayed.status(), 200);
+        assert_eq!(replayed.bytes().await.unwrap().as_ref(), br#"{"data":[]}"#);
+        let other = cassette.replay(&models("https://api.example.com/v1/models?page=2"));
+        assert_eq!(other.status(), 502);
+
+        // Streamed bodies can't be matched, and aren't sent either
+        let upload = client
+            .post("https://api.example.com/v1/files")
+            .body(reqwest::Body::wrap_stream(futures::stream::empty::<
+                Result<Vec<u8>, std::io::Error>,
+            >()))
+            .build()
+            .unwrap();
+        assert_eq!(cassette.replay(&upload).status(), 502);
+    }
+}
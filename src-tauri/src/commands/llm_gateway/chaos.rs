@@ -0,0 +1,213 @@
+//! Chaos - Faults injected into upstream requests on purpose
+//!
+//! A debug setting for checking that retries, mirrors and failover behave before they
+//! are needed. Each attempt at a request to a selected provider may, at the configured
+//! rates, time out, be rate limited with a 429, or have a malformed chunk slipped into
+//! its event stream. Faults are injected per attempt, so retry policies see them too.
+//!
+//! Timeouts are real: the request is sent to a local socket that never answers, and
+//! fails however the provider's timeout makes it fail.
+
+use axum::body::Bytes;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use serde::{Deserialize, Serialize};
+
+use super::router::roll_percent;
+use super::ProviderConfig;
+
+/// Chunk cut off in the middle of its JSON
+const MALFORMED_CHUNK: &[u8] = b"data: {\"choices\": [{\"delta\": {\"content\"\n\n";
+
+/// Faults to inject and where
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ChaosConfig {
+    /// Provider names or types to inject faults into; every provider when empty
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// Share of attempts that time out, 0.0-1.0
+    #[serde(default)]
+    pub timeout_rate: f64,
+    /// Share of attempts answered with a 429, 0.0-1.0
+    #[serde(default)]
+    pub rate_limit_rate: f64,
+    /// Share of streamed responses given a malformed chunk, 0.0-1.0
+    #[serde(default)]
+    pub malformed_chunk_rate: f64,
+}
+
+/// A fault injected into one attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    Timeout,
+    RateLimit,
+    MalformedChunk,
+}
+
+impl ChaosConfig {
+    /// Whether faults are injected into requests to `provider`
+    pub fn applies_to(&self, provider: &ProviderConfig) -> bool {
+        let kind = provider.provider.to_string();
+        self.providers.is_empty()
+            || self
+                .providers
+                .iter()
+                .any(|p| p.eq_ignore_ascii_case(&provider.name) || p.eq_ignore_ascii_case(&kind))
+    }
+
+    /// Fault for a roll in `0..100`, the rates taking consecutive slices of the range
+    fn fault_for(&self, roll: u8) -> Option<Fault> {
+        let roll = roll as f64;
+        let mut threshold = 0.0;
+        for (rate, fault) in [
+            (self.timeout_rate, Fault::Timeout),
+            (self.rate_limit_rate, Fault::RateLimit),
+            (self.malformed_chunk_rate, Fault::MalformedChunk),
+        ] {
+            threshold += rate.clamp(0.0, 1.0) * 100.0;
+            if roll < threshold {
+                return Some(fault);
+            }
+        }
+        None
+    }
+
+    /// Fault to inject into the next attempt, if any
+    pub fn roll(&self) -> Option<Fault> {
+        self.fault_for(roll_percent())
+    }
+}
+
+/// Response standing in for the upstream one
+fn response(
+    status: reqwest::StatusCode,
+    headers: HeaderMap,
+    body: reqwest::Body,
+) -> reqwest::Response {
+    let mut response = axum::http::Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    response.into()
+}
+
+/// A 429 in OpenAI's format, asking to retry after a second
+pub fn rate_limited() -> reqwest::Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("1"));
+    let body = serde_json::json!({"error": {
+        "message": "Rate limit injected by gateway chaos mode",
+        "type": "rate_limit_error",
+    }});
+    response(
+        reqwest::StatusCode::TOO_MANY_REQUESTS,
+        headers,
+        reqwest::Body::from(body.to_string()),
+    )
+}
+
+/// Send `request` to a local socket that accepts it and never answers, so it fails
+/// with a genuine timeout
+pub async fn stall(request: reqwest::RequestBuilder) -> Result<reqwest::Response, reqwest::Error> {
+    let (client, request) = request.build_split();
+    let mut request = request?;
+    let listener = match tokio::net::TcpListener::bind(("127.0.0.1", 0)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::warn!("Chaos mode couldn't open a socket to stall on: {}", e);
+            return Ok(response(
+                reqwest::StatusCode::GATEWAY_TIMEOUT,
+                HeaderMap::new(),
+                reqwest::Body::from(""),
+            ));
+        }
+    };
+    let port = listener.local_addr().map(|a| a.port()).unwrap_or(0);
+    let url = request.url_mut();
+    let _ = url.set_scheme("http");
+    let _ = url.set_host(Some("127.0.0.1"));
+    let _ = url.set_port(Some(port));
+    let result = client.execute(request).await;
+    drop(listener);
+    result
+}
+
+/// Slip a malformed chunk into an event stream after its first chunk. Other responses
+/// are left as they are.
+pub fn corrupt(upstream: reqwest::Response) -> reqwest::Response {
+    let is_event_stream = upstream
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+    if !upstream.status().is_success() || !is_event_stream {
+        return upstream;
+    }
+    let status = upstream.status();
+    let mut headers = upstream.headers().clone();
+    headers.remove(reqwest::header::CONTENT_LENGTH);
+    let chunks = futures::stream::unfold(Some((upstream, 0usize)), |state| async move {
+        let (mut upstream, sent) = state?;
+        if sent == 1 {
+            let malformed = Bytes::from_static(MALFORMED_CHUNK);
+            return Some((Ok(malformed), Some((upstream, sent + 1))));
+        }
+        match upstream.chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), Some((upstream, sent + 1)))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+    response(status, headers, reqwest::Body::wrap_stream(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_faults() {
+        let chaos = ChaosConfig {
+            providers: vec!["deepseek".to_string()],
+            timeout_rate: 0.1,
+            rate_limit_rate: 0.25,
+            malformed_chunk_rate: 0.0,
+        };
+        assert_eq!(chaos.fault_for(9), Some(Fault::Timeout));
+        assert_eq!(chaos.fault_for(10), Some(Fault::RateLimit));
+        assert_eq!(chaos.fault_for(34), Some(Fault::RateLimit));
+        assert_eq!(chaos.fault_for(35), None);
+        assert_eq!(ChaosConfig::default().fault_for(0), None);
+
+        let providers = crate::commands::llm_gateway::get_default_providers();
+        let selected: Vec<&str> = providers
+            .iter()
+            .filter(|p| chaos.applies_to(p))
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(selected, vec!["DeepSeek"]);
+
+        let limited = rate_limited();
+        assert_eq!(limited.status(), 429);
+        assert_eq!(limited.headers()[RETRY_AFTER], "1");
+    }
+
+    #[tokio::test]
+    async fn test_corrupt() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        let chunks = futures::stream::iter(vec![
+            Ok::<_, std::io::Error>(Bytes::from_static(b"data: {}\n\n")),
+            Ok(Bytes::from_static(b"data: [DONE]\n\n")),
+        ]);
+        let upstream = response(
+            reqwest::StatusCode::OK,
+            headers,
+            reqwest::Body::wrap_stream(chunks),
+        );
+        let body = corrupt(upstream).text().await.unwrap();
+        assert_eq!(
+            body,
+            "data: {}\n\ndata: {\"choices\": [{\"delta\": {\"content\"\n\ndata: [DONE]\n\n"
+        );
+    }
+}
@@ -0,0 +1,732 @@
+//! Upstream Client - HTTP clients, timeouts and retries for provider requests
+//!
+//! Requests use one shared client with the gateway-wide timeout unless their provider
+//! overrides it. A provider's total timeout is applied per request; a connect timeout
+//! needs a client of its own, so one is built per distinct value and reused. Failed
+//! attempts are retried according to the provider's [`RetryPolicy`]: connection errors,
+//! timeouts and the configured statuses are retried with a fixed or exponential backoff.
+//!
+//! Requests go through the gateway's outbound proxy when one is set, or the provider's
+//! own, which takes precedence. HTTP(S) and SOCKS5 proxies are supported.
+//! TLS options follow the same rule: extra trusted CA certificates for endpoints behind
+//! TLS interception, client certificates for mutual TLS, or, as a last resort, accepting
+//! invalid certificates.
+//!
+//! Every client keeps a pool of idle connections, tuned by [`PoolConfig`], so repeated
+//! requests to a provider skip the TCP and TLS handshakes. HTTP/2 is negotiated where
+//! the endpoint offers it. Tauri commands outside the server share [`shared_client`].
+//!
+//! A provider may pin host names to addresses, bypassing DNS, and list mirror base URLs.
+//! Requests that can't connect to the provider's endpoint move on to the next mirror,
+//! and the one that answered is tried first from then on.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use super::cassette::{CassetteConfig, CassetteMode};
+use super::chaos::{self, ChaosConfig, Fault};
+use super::{scrub, ProviderConfig};
+
+/// Wait before the first retry when unset
+const DEFAULT_INITIAL_BACKOFF_MS: u64 = 500;
+
+/// Upper bound of the wait between retries
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Interval of TCP keep-alive probes on pooled connections when unset
+const DEFAULT_TCP_KEEPALIVE_SECS: u64 = 60;
+
+/// Timeout of the client shared by commands outside the server
+const SHARED_CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the wait between retries grows
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackoffStrategy {
+    /// The initial backoff before every retry
+    Fixed,
+    /// The initial backoff, doubled for each further retry
+    #[default]
+    Exponential,
+}
+
+fn default_retry_statuses() -> Vec<u16> {
+    vec![429, 500, 502, 503, 504]
+}
+
+/// Retries of failed requests to a provider
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Upstream statuses worth retrying (default 429 and 5xx gateway errors)
+    #[serde(default = "default_retry_statuses")]
+    pub retry_on_status: Vec<u16>,
+    #[serde(default)]
+    pub backoff: BackoffStrategy,
+    /// Wait before the first retry (default 500ms)
+    #[serde(default)]
+    pub initial_backoff_ms: Option<u64>,
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `attempt` (starting at 0)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let initial = Duration::from_millis(
+            self.initial_backoff_ms
+                .unwrap_or(DEFAULT_INITIAL_BACKOFF_MS),
+        );
+        let delay = match self.backoff {
+            BackoffStrategy::Fixed => initial,
+            BackoffStrategy::Exponential => initial.saturating_mul(2u32.saturating_pow(attempt)),
+        };
+        delay.min(MAX_BACKOFF)
+    }
+
+    fn should_retry(&self, result: &Result<reqwest::Response, reqwest::Error>) -> bool {
+        match result {
+            Ok(response) => self.retry_on_status.contains(&response.status().as_u16()),
+            Err(e) => e.is_connect() || e.is_timeout(),
+        }
+    }
+}
+
+/// Outbound proxy for provider requests
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct ProxyConfig {
+    /// Proxy URL: `http://`, `https://`, `socks5://` or `socks5h://` (the proxy resolves
+    /// host names); may reference environment variables as `${VAR}`
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Comma-separated hosts reached directly, such as `localhost,127.0.0.1,.internal`
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+}
+
+// Credentials stay out of debug output
+impl std::fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("url", &scrub::scrub(&self.url))
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| scrub::SCRUBBED))
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+impl ProxyConfig {
+    fn proxy(&self) -> reqwest::Result<reqwest::Proxy> {
+        let mut proxy = reqwest::Proxy::all(&self.url)?;
+        if let Some(username) = self.username.as_deref().filter(|u| !u.is_empty()) {
+            proxy = proxy.basic_auth(username, self.password.as_deref().unwrap_or_default());
+        }
+        if let Some(no_proxy) = self.no_proxy.as_deref() {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(no_proxy));
+        }
+        Ok(proxy)
+    }
+
+    /// Check that the proxy URL is usable
+    pub fn validate(&self) -> Result<(), String> {
+        self.proxy()
+            .map(|_| ())
+            .map_err(|e| format!("Invalid proxy '{}': {}", scrub::scrub(&self.url), e))
+    }
+}
+
+/// TLS options for provider connections
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct TlsConfig {
+    /// PEM file with CA certificates trusted in addition to the system's
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+    /// PEM file with the client certificate chain for mutual TLS
+    #[serde(default)]
+    pub client_cert_path: Option<String>,
+    /// PEM file with the client certificate's PKCS#8 private key
+    #[serde(default)]
+    pub client_key_path: Option<String>,
+    /// Skip certificate and host name verification; only for trusted networks
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read '{}': {}", path, e))
+}
+
+impl TlsConfig {
+    fn apply(&self, mut builder: reqwest::ClientBuilder) -> Result<reqwest::ClientBuilder, String> {
+        if let Some(path) = &self.ca_cert_path {
+            let certificates = reqwest::Certificate::from_pem_bundle(&read_pem(path)?)
+                .map_err(|e| format!("Invalid CA certificates in '{}': {}", path, e))?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        match (&self.client_cert_path, &self.client_key_path) {
+            (Some(cert), Some(key)) => {
+                let identity = reqwest::Identity::from_pkcs8_pem(&read_pem(cert)?, &read_pem(key)?)
+                    .map_err(|e| format!("Invalid client certificate '{}': {}", cert, e))?;
+                builder = builder.identity(identity);
+            }
+            (None, None) => {}
+            _ => return Err("A client certificate needs both a certificate and a key".to_string()),
+        }
+        if self.accept_invalid_certs {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder)
+    }
+
+    /// Check that the certificate files can be loaded
+    pub fn validate(&self) -> Result<(), String> {
+        self.apply(reqwest::Client::builder()).map(|_| ())
+    }
+}
+
+/// Connection pool and keep-alive tuning
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct PoolConfig {
+    /// Idle connections kept open per host (default unlimited)
+    #[serde(default)]
+    pub max_idle_per_host: Option<usize>,
+    /// Close connections idle for longer than this (default 90s)
+    #[serde(default)]
+    pub idle_timeout_seconds: Option<u64>,
+    /// Interval of TCP keep-alive probes (default 60s)
+    #[serde(default)]
+    pub tcp_keepalive_seconds: Option<u64>,
+    /// Interval of HTTP/2 pings keeping idle connections alive; unset sends none
+    #[serde(default)]
+    pub http2_keep_alive_seconds: Option<u64>,
+}
+
+impl PoolConfig {
+    fn apply(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if let Some(max) = self.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(seconds) = self.idle_timeout_seconds {
+            builder = builder.pool_idle_timeout(Duration::from_secs(seconds));
+        }
+        let keepalive = self
+            .tcp_keepalive_seconds
+            .unwrap_or(DEFAULT_TCP_KEEPALIVE_SECS);
+        builder = builder.tcp_keepalive(Duration::from_secs(keepalive));
+        if let Some(seconds) = self.http2_keep_alive_seconds {
+            builder = builder
+                .http2_keep_alive_interval(Duration::from_secs(seconds))
+                .http2_keep_alive_while_idle(true);
+        }
+        builder.http2_adaptive_window(true)
+    }
+}
+
+/// Parse a DNS override: comma-separated IP addresses, each with an optional port that
+/// defaults to the URL's
+fn dns_addresses(value: &str) -> Result<Vec<SocketAddr>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(|address| {
+            address
+                .parse::<SocketAddr>()
+                .or_else(|_| address.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 0)))
+                .map_err(|_| format!("Invalid address '{}' in DNS override", address))
+        })
+        .collect()
+}
+
+/// Check that every DNS override names valid addresses
+pub fn validate_dns_overrides(overrides: &BTreeMap<String, String>) -> Result<(), String> {
+    overrides
+        .values()
+        .try_for_each(|value| dns_addresses(value).map(|_| ()))
+}
+
+/// What sets a provider's client apart from the shared one
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct ClientOptions {
+    connect_timeout_seconds: Option<u32>,
+    /// Longest silence while reading a response
+    read_timeout_seconds: Option<u32>,
+    proxy: Option<ProxyConfig>,
+    tls: Option<TlsConfig>,
+    pool: Option<PoolConfig>,
+    dns_overrides: BTreeMap<String, String>,
+}
+
+/// Client whose connections and reads time out after `timeout` unless the options say
+/// otherwise. There's no overall deadline, which would cut long streams off; requests
+/// that aren't streamed get one of their own.
+fn build_client(timeout: Duration, options: &ClientOptions) -> Result<reqwest::Client, String> {
+    let seconds = |s: Option<u32>| s.map_or(timeout, |s| Duration::from_secs(s as u64));
+    let mut builder = options.pool.clone().unwrap_or_default().apply(
+        reqwest::Client::builder()
+            .connect_timeout(seconds(options.connect_timeout_seconds))
+            .read_timeout(seconds(options.read_timeout_seconds)),
+    );
+    if let Some(proxy) = &options.proxy {
+        builder = builder.proxy(proxy.proxy().map_err(|e| e.to_string())?);
+    }
+    if let Some(tls) = &options.tls {
+        builder = tls.apply(builder)?;
+    }
+    for (host, value) in &options.dns_overrides {
+        builder = builder.resolve_to_addrs(host, &dns_addresses(value)?);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Pooled client for provider requests made outside the gateway server, such as
+/// connection tests and model discovery
+pub fn shared_client() -> reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            // Nothing sent through it streams, so it keeps an overall deadline
+            ClientOptions::default()
+                .pool
+                .unwrap_or_default()
+                .apply(reqwest::Client::builder().timeout(SHARED_CLIENT_TIMEOUT))
+                .build()
+                .unwrap_or_else(|e| {
+                    log::warn!("Failed to build the shared client: {}", e);
+                    reqwest::Client::new()
+                })
+        })
+        .clone()
+}
+
+/// Clients for upstream requests, cheap to clone
+#[derive(Clone)]
+pub struct UpstreamClient {
+    shared: reqwest::Client,
+    timeout: Duration,
+    /// The gateway's outbound proxy, TLS options and pool tuning
+    proxy: Option<ProxyConfig>,
+    tls: Option<TlsConfig>,
+    pool: Option<PoolConfig>,
+    /// Clients for providers with their own connect timeout, proxy, TLS options, pool or
+    /// DNS overrides
+    by_options: Arc<Mutex<HashMap<ClientOptions, reqwest::Client>>>,
+    /// Index of the endpoint that last answered, by provider name; 0 is the base URL,
+    /// then the mirrors in order
+    endpoints: Arc<Mutex<HashMap<String, usize>>>,
+    /// Recording or replay of upstream responses
+    cassette: Option<CassetteConfig>,
+    /// Faults injected into upstream requests
+    chaos: Option<ChaosConfig>,
+}
+
+/// Whether the response to `request` is an event stream: its body asks for one, it only
+/// accepts events, or its endpoint streams by name (`converse-stream` and the like)
+fn streams_response(request: &reqwest::Request) -> bool {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+    header("accept").contains("text/event-stream")
+        || header("x-dashscope-sse").eq_ignore_ascii_case("enable")
+        || request.url().path().to_ascii_lowercase().contains("stream")
+        || request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .and_then(|body| serde_json::from_slice::<serde_json::Value>(body).ok())
+            .is_some_and(|body| body["stream"] == true)
+}
+
+/// Move `url` from the `from` base URL onto `to`, if it starts with `from`
+fn rebase(url: &reqwest::Url, from: &str, to: &str) -> Option<reqwest::Url> {
+    let rest = url.as_str().strip_prefix(from.trim_end_matches('/'))?;
+    reqwest::Url::parse(&format!("{}{}", to.trim_end_matches('/'), rest)).ok()
+}
+
+impl UpstreamClient {
+    pub fn new(
+        timeout_seconds: u32,
+        proxy: Option<ProxyConfig>,
+        tls: Option<TlsConfig>,
+        pool: Option<PoolConfig>,
+        cassette: Option<CassetteConfig>,
+        chaos: Option<ChaosConfig>,
+    ) -> Result<Self, String> {
+        let timeout = Duration::from_secs(timeout_seconds as u64);
+        let shared = build_client(
+            timeout,
+            &ClientOptions {
+                proxy: proxy.clone(),
+                tls: tls.clone(),
+                pool: pool.clone(),
+                ..Default::default()
+            },
+        )?;
+        Ok(Self {
+            shared,
+            timeout,
+            proxy,
+            tls,
+            pool,
+            by_options: Arc::new(Mutex::new(HashMap::new())),
+            endpoints: Arc::new(Mutex::new(HashMap::new())),
+            cassette,
+            chaos,
+        })
+    }
+
+    /// The client for requests that don't belong to a provider
+    pub fn shared(&self) -> &reqwest::Client {
+        &self.shared
+    }
+
+    fn client_for(&self, provider: &ProviderConfig) -> reqwest::Client {
+        if provider.connect_timeout_seconds.is_none()
+            && provider.timeout_seconds.is_none()
+            && provider.proxy.is_none()
+            && provider.tls.is_none()
+            && provider.pool.is_none()
+            && provider.dns_overrides.is_empty()
+        {
+            return self.shared.clone();
+        }
+        let options = ClientOptions {
+            connect_timeout_seconds: provider.connect_timeout_seconds,
+            read_timeout_seconds: provider.timeout_seconds,
+            proxy: provider.proxy.clone().or_else(|| self.proxy.clone()),
+            tls: provider.tls.clone().or_else(|| self.tls.clone()),
+            pool: provider.pool.clone().or_else(|| self.pool.clone()),
+            dns_overrides: provider.dns_overrides.clone(),
+        };
+        let Ok(mut clients) = self.by_options.lock() else {
+            return self.shared.clone();
+        };
+        if let Some(client) = clients.get(&options) {
+            return client.clone();
+        }
+        match build_client(self.timeout, &options) {
+            Ok(client) => {
+                clients.insert(options, client.clone());
+                client
+            }
+            Err(e) => {
+                log::warn!("Failed to build client for {}: {}", provider.name, e);
+                self.shared.clone()
+            }
+        }
+    }
+
+    /// Start a POST request to `provider` with its connect and read timeouts applied
+    pub fn post(
+        &self,
+        provider: &ProviderConfig,
+        url: impl reqwest::IntoUrl,
+    ) -> reqwest::RequestBuilder {
+        self.client_for(provider).post(url)
+    }
+
+    /// Start a GET request to `provider` with its connect and read timeouts applied
+    pub fn get(
+        &self,
+        provider: &ProviderConfig,
+        url: impl reqwest::IntoUrl,
+    ) -> reqwest::RequestBuilder {
+        self.client_for(provider).get(url)
+    }
+
+    /// Give `request` the provider's overall deadline unless its response is streamed,
+    /// where only the time between chunks is limited
+    fn apply_deadline(&self, provider: &ProviderConfig, request: &mut reqwest::Request) {
+        if request.timeout().is_none() && !streams_response(request) {
+            *request.timeout_mut() = Some(
+                provider
+                    .timeout_seconds
+                    .map_or(self.timeout, |s| Duration::from_secs(s as u64)),
+            );
+        }
+    }
+
+    /// Send a request to `provider`, retrying it as its policy allows and falling back to
+    /// its mirrors when the endpoint is unreachable. Requests with a streamed body, such
+    /// as multipart uploads, can't be repeated and are sent once. With a cassette, the
+    /// response is recorded or replayed instead.
+    pub async fn send(
+        &self,
+        provider: &ProviderConfig,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let (client, request) = request.build_split();
+        let mut request = request?;
+        self.apply_deadline(provider, &mut request);
+        let Some(cassette) = &self.cassette else {
+            let request = reqwest::RequestBuilder::from_parts(client, request);
+            return self.send_live(provider, request).await;
+        };
+        match cassette.mode {
+            CassetteMode::Replay => Ok(cassette.replay(&request)),
+            CassetteMode::Record => {
+                let recorded = request.try_clone();
+                let request = reqwest::RequestBuilder::from_parts(client, request);
+                let response = self.send_live(provider, request).await?;
+                cassette.record(recorded.as_ref(), response).await
+            }
+        }
+    }
+
+    async fn send_live(
+        &self,
+        provider: &ProviderConfig,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        if provider.mirrors.is_empty() {
+            return self.send_with_retries(provider, request).await;
+        }
+        let (client, request) = request.build_split();
+        let request = request?;
+        let endpoints: Vec<&str> = std::iter::once(provider.base_url.as_str())
+            .chain(provider.mirrors.iter().map(String::as_str))
+            .collect();
+        let first = self
+            .endpoints
+            .lock()
+            .ok()
+            .and_then(|e| e.get(&provider.name).copied())
+            .filter(|&i| i < endpoints.len())
+            .unwrap_or(0);
+        let mut order = (0..endpoints.len()).map(|i| (first + i) % endpoints.len());
+        let mut index = order.next().unwrap_or(0);
+        loop {
+            let Some(mut attempt) = request.try_clone() else {
+                return client.execute(request).await;
+            };
+            if let Some(url) = rebase(request.url(), &provider.base_url, endpoints[index]) {
+                *attempt.url_mut() = url;
+            }
+            let builder = reqwest::RequestBuilder::from_parts(client.clone(), attempt);
+            let result = self.send_with_retries(provider, builder).await;
+            let unreachable = matches!(&result, Err(e) if e.is_connect() || e.is_timeout());
+            match order.next() {
+                Some(next) if unreachable => {
+                    log::warn!(
+                        "{} unreachable at {}, trying {}",
+                        provider.name,
+                        endpoints[index],
+                        endpoints[next]
+                    );
+                    index = next;
+                }
+                _ => {
+                    if result.is_ok() {
+                        if let Ok(mut endpoints) = self.endpoints.lock() {
+                            endpoints.insert(provider.name.clone(), index);
+                        }
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    async fn send_with_retries(
+        &self,
+        provider: &ProviderConfig,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let Some(policy) = provider.retry.as_ref().filter(|p| p.max_retries > 0) else {
+            return self.attempt(provider, request).await;
+        };
+        let mut attempt = 0;
+        loop {
+            let Some(retry) = request.try_clone() else {
+                return self.attempt(provider, request).await;
+            };
+            let result = self.attempt(provider, retry).await;
+            if attempt >= policy.max_retries || !policy.should_retry(&result) {
+                return result;
+            }
+            let delay = policy.backoff(attempt);
+            match &result {
+                Ok(response) => log::info!(
+                    "Retrying {} after status {} in {:?}",
+                    provider.name,
+                    response.status(),
+                    delay
+                ),
+                Err(e) => log::info!(
+                    "Retrying {} after error in {:?}: {}",
+                    provider.name,
+                    delay,
+                    e
+                ),
+            }
+            attempt += 1;
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Send one attempt at a request, with any fault chaos mode picks for it
+    async fn attempt(
+        &self,
+        provider: &ProviderConfig,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let fault = self
+            .chaos
+            .as_ref()
+            .filter(|chaos| chaos.applies_to(provider))
+            .and_then(ChaosConfig::roll);
+        if let Some(fault) = fault {
+            log::info!("Injecting {:?} into request to {}", fault, provider.name);
+        }
+        match fault {
+            Some(Fault::Timeout) => chaos::stall(request).await,
+            Some(Fault::RateLimit) => Ok(chaos::rate_limited()),
+            Some(Fault::MalformedChunk) => Ok(chaos::corrupt(request.send().await?)),
+            None => request.send().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_backoff() {
+        let policy: RetryPolicy = serde_json::from_str(r#"{"max_retries": 3}"#).unwrap();
+        assert_eq!(policy.retry_on_status, vec![429, 500, 502, 503, 504]);
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(10), MAX_BACKOFF);
+
+        let fixed = RetryPolicy {
+            backoff: BackoffStrategy::Fixed,
+            initial_backoff_ms: Some(200),
+            ..policy
+        };
+        assert_eq!(fixed.backoff(5), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_streams_response() {
+        let client = reqwest::Client::new();
+        let streams =
+            |request: reqwest::RequestBuilder| streams_response(&request.build().unwrap());
+        let chat = || client.post("https://api.openai.com/v1/chat/completions");
+        assert!(streams(chat().json(&serde_json::json!({"stream": true}))));
+        assert!(!streams(chat().json(&serde_json::json!({"stream": false}))));
+        assert!(!streams(client.get("https://api.openai.com/v1/models")));
+        assert!(streams(
+            client
+                .get("https://api.replicate.com/v1/stream/abc")
+                .header("accept", "text/event-stream")
+        ));
+        assert!(streams(chat().header("X-DashScope-SSE", "enable")));
+    }
+
+    #[tokio::test]
+    async fn test_deadline_spares_streams() {
+        let http = UpstreamClient::new(120, None, None, None, None, None).unwrap();
+        let mut provider = crate::commands::llm_gateway::get_default_providers().remove(0);
+        provider.timeout_seconds = Some(30);
+        let deadline = |body: serde_json::Value| {
+            let mut request = http
+                .post(&provider, "https://api.openai.com/v1/chat/completions")
+                .json(&body)
+                .build()
+                .unwrap();
+            http.apply_deadline(&provider, &mut request);
+            request.timeout().copied()
+        };
+        assert_eq!(
+            deadline(serde_json::json!({"stream": false})),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(deadline(serde_json::json!({"stream": true})), None);
+    }
+
+    #[test]
+    fn test_proxy_config() {
+        let proxy = ProxyConfig {
+            url: "socks5h://127.0.0.1:1080".to_string(),
+            username: Some("user".to_string()),
+            password: Some("hunter2".to_string()),
+            no_proxy: Some("localhost".to_string()),
+        };
+        assert!(proxy.validate().is_ok());
+        assert!(!format!("{:?}", proxy).contains("hunter2"));
+        let invalid = ProxyConfig {
+            url: "not a url".to_string(),
+            ..proxy
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_tls_config() {
+        assert!(TlsConfig::default().validate().is_ok());
+        let missing = TlsConfig {
+            ca_cert_path: Some("/nonexistent/ca.pem".to_string()),
+            ..Default::default()
+        };
+        assert!(missing.validate().is_err());
+        let half = TlsConfig {
+            client_cert_path: Some("/nonexistent/client.pem".to_string()),
+            ..Default::default()
+        };
+        assert!(half.validate().unwrap_err().contains("both"));
+    }
+
+    #[test]
+    fn test_client_reuse() {
+        let client = UpstreamClient::new(60, None, None, None, None, None).unwrap();
+        let mut provider = crate::commands::llm_gateway::get_default_providers().remove(0);
+        client.client_for(&provider);
+        assert!(client.by_options.lock().unwrap().is_empty());
+
+        provider.pool = Some(PoolConfig {
+            max_idle_per_host: Some(4),
+            ..Default::default()
+        });
+        client.client_for(&provider);
+        client.client_for(&provider);
+        assert_eq!(client.by_options.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_mirrors_and_dns_overrides() {
+        let url = reqwest::Url::parse("https://api.openai.com/v1/chat/completions").unwrap();
+        assert_eq!(
+            rebase(
+                &url,
+                "https://api.openai.com/v1/",
+                "https://mirror.example.cn/openai/v1"
+            )
+            .unwrap()
+            .as_str(),
+            "https://mirror.example.cn/openai/v1/chat/completions"
+        );
+        assert!(rebase(&url, "https://other.example.com/v1", "https://mirror").is_none());
+
+        assert_eq!(
+            dns_addresses("10.0.0.1, 10.0.0.2:8443").unwrap(),
+            vec![
+                "10.0.0.1:0".parse::<SocketAddr>().unwrap(),
+                "10.0.0.2:8443".parse().unwrap()
+            ]
+        );
+        let overrides = BTreeMap::from([("api.openai.com".to_string(), "nope".to_string())]);
+        assert!(validate_dns_overrides(&overrides).is_err());
+    }
+}
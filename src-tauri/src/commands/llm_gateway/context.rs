@@ -0,0 +1,389 @@
+//! Context Fitting - Keeps prompts within the routed model's context window
+//!
+//! The prompt is estimated before dispatch and checked against the model's context
+//! length minus the requested completion budget. Oversized prompts are truncated from
+//! the oldest turns, moved to a larger-context variant of the same model family, or
+//! rejected up front with a clear error instead of an opaque upstream one.
+//!
+//! Truncated turns can instead be compressed by a cheap summarizer model; the summary
+//! is prepended to the first remaining turn so long agent sessions keep their history.
+//! The transcript is redacted as for any request sent to the summarizer's provider.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::limits::{completion_budget, estimate_prompt_tokens, estimate_value_tokens};
+use super::redact::Redactor;
+use super::router::RouteTarget;
+use super::translate::content_text;
+
+/// Tokens reserved for a summary when none is configured
+pub const DEFAULT_SUMMARY_TOKENS: u32 = 1000;
+/// Longest excerpt of a single dropped message shown to the summarizer
+const TRANSCRIPT_MESSAGE_CHARS: usize = 4000;
+
+/// What to do with a prompt that doesn't fit the routed model's context window
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextOverflow {
+    /// Drop the oldest conversation turns until the prompt fits
+    Truncate,
+    /// Switch to a larger-context variant of the same model family
+    Upgrade,
+    /// Fail the request with an explanatory error
+    Reject,
+}
+
+/// Adjustment made to fit a request into its model's context window
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContextFit {
+    Fits,
+    /// The given number of oldest messages were dropped
+    Truncated(usize),
+    /// The request was moved to this model
+    Upgraded(String),
+}
+
+/// Context length of the routed model, if it is listed with one
+pub fn context_window(route: &RouteTarget) -> Option<u64> {
+    route
+        .provider
+        .models
+        .iter()
+        .find(|m| m.id == route.model)
+        .map(|m| m.max_tokens as u64)
+        .filter(|window| *window > 0)
+}
+
+/// Model family: the ID without a trailing context-size segment (`moonshot-v1-32k`
+/// belongs to `moonshot-v1`)
+fn model_family(model: &str) -> &str {
+    match model.rsplit_once('-') {
+        Some((family, size))
+            if size.len() > 1
+                && size.ends_with('k')
+                && size[..size.len() - 1].chars().all(|c| c.is_ascii_digit()) =>
+        {
+            family
+        }
+        _ => model,
+    }
+}
+
+/// Smallest model of the route's provider in the same family with room for `needed`
+/// tokens
+fn larger_variant(route: &RouteTarget, needed: u64) -> Option<String> {
+    let family = model_family(&route.model);
+    route
+        .provider
+        .models
+        .iter()
+        .filter(|m| m.id != route.model && model_family(&m.id) == family)
+        .filter(|m| m.max_tokens as u64 >= needed)
+        .min_by_key(|m| m.max_tokens)
+        .map(|m| m.id.clone())
+}
+
+/// System and developer messages are never dropped
+fn is_pinned(message: &Value) -> bool {
+    matches!(message["role"].as_str(), Some("system") | Some("developer"))
+}
+
+/// Whether a conversation may start at `message`: a user turn that doesn't answer a
+/// tool call from a dropped assistant turn
+fn starts_turn(message: &Value) -> bool {
+    message["role"] == "user"
+        && !message["content"]
+            .as_array()
+            .is_some_and(|blocks| blocks.iter().any(|b| b["type"] == "tool_result"))
+}
+
+/// Number of oldest conversation messages to drop so the prompt fits `budget`, or
+/// `None` if it can't fit without dropping the latest message
+pub fn overflow_prefix(request: &Value, budget: u64) -> Option<usize> {
+    let total = estimate_prompt_tokens(request);
+    if total <= budget {
+        return Some(0);
+    }
+    let excess = total - budget;
+
+    let messages: Vec<&Value> = request["messages"]
+        .as_array()?
+        .iter()
+        .filter(|m| !is_pinned(m))
+        .collect();
+    let mut freed = 0;
+    for count in 1..messages.len() {
+        freed += estimate_value_tokens(messages[count - 1]);
+        if freed >= excess && starts_turn(messages[count]) {
+            return Some(count);
+        }
+    }
+    None
+}
+
+/// Drop the `count` oldest conversation messages, keeping pinned ones
+pub fn drop_oldest(request: &mut Value, count: usize) {
+    let Some(messages) = request["messages"].as_array_mut() else {
+        return;
+    };
+    let mut remaining = count;
+    messages.retain(|m| {
+        if remaining == 0 || is_pinned(m) {
+            return true;
+        }
+        remaining -= 1;
+        false
+    });
+}
+
+/// The `count` oldest conversation messages, skipping pinned ones
+pub fn oldest_messages(request: &Value, count: usize) -> Vec<Value> {
+    request["messages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|m| !is_pinned(m))
+        .take(count)
+        .cloned()
+        .collect()
+}
+
+fn excerpt(text: &str) -> String {
+    match text.char_indices().nth(TRANSCRIPT_MESSAGE_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Plain-text transcript of messages (Anthropic or OpenAI shape) for the summarizer
+pub fn transcript(messages: &[Value]) -> String {
+    let mut lines = Vec::new();
+    for message in messages {
+        let role = message["role"].as_str().unwrap_or("user");
+        let mut parts = vec![content_text(&message["content"])];
+        for block in message["content"].as_array().into_iter().flatten() {
+            match block["type"].as_str() {
+                Some("tool_use") => parts.push(format!(
+                    "[called {} with {}]",
+                    block["name"].as_str().unwrap_or("tool"),
+                    block["input"]
+                )),
+                Some("tool_result") => parts.push(format!(
+                    "[tool result: {}]",
+                    content_text(&block["content"])
+                )),
+                _ => {}
+            }
+        }
+        for call in message["tool_calls"].as_array().into_iter().flatten() {
+            parts.push(format!(
+                "[called {} with {}]",
+                call.pointer("/function/name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or("tool"),
+                call.pointer("/function/arguments")
+                    .and_then(|a| a.as_str())
+                    .unwrap_or("{}")
+            ));
+        }
+        let text = parts
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        lines.push(format!("{}: {}", role, excerpt(&text)));
+    }
+    lines.join("\n\n")
+}
+
+/// OpenAI-shaped request asking the summarizer to compress a transcript, with sensitive
+/// values masked by `redactor` when redaction applies to the summarizer
+pub fn summary_request(
+    transcript: &str,
+    max_tokens: u32,
+    redactor: Option<&mut Redactor>,
+) -> Value {
+    let mut request = json!({
+        "messages": [
+            {
+                "role": "system",
+                "content": "Summarize the earlier part of a conversation between a user and an AI \
+                    assistant so the assistant can continue without it. Keep decisions, facts, \
+                    file names, code identifiers, open tasks and tool outcomes. Be concise."
+            },
+            {"role": "user", "content": transcript}
+        ],
+        "max_tokens": max_tokens,
+        "temperature": 0.2,
+        "stream": false
+    });
+    if let Some(redactor) = redactor {
+        redactor.redact_request(&mut request);
+    }
+    request
+}
+
+/// Drop the `count` oldest conversation messages and prepend `summary` to the first
+/// remaining one
+pub fn replace_with_summary(request: &mut Value, count: usize, summary: &str) {
+    drop_oldest(request, count);
+    let Some(first) = request["messages"]
+        .as_array_mut()
+        .and_then(|messages| messages.iter_mut().find(|m| !is_pinned(m)))
+    else {
+        return;
+    };
+
+    let note = format!("Summary of the earlier conversation:\n{}", summary.trim());
+    match &mut first["content"] {
+        Value::Array(blocks) => blocks.insert(0, json!({"type": "text", "text": note})),
+        content => {
+            let text = content.as_str().unwrap_or_default().to_string();
+            *content = Value::String(format!("{}\n\n{}", note, text));
+        }
+    }
+}
+
+/// Fit `request` into the context window of `route`'s model according to `mode`
+pub fn fit_context(
+    request: &mut Value,
+    route: &mut RouteTarget,
+    mode: ContextOverflow,
+) -> Result<ContextFit, String> {
+    let Some(window) = context_window(route) else {
+        return Ok(ContextFit::Fits);
+    };
+    let completion = completion_budget(request);
+    let prompt = estimate_prompt_tokens(request);
+    if prompt + completion <= window {
+        return Ok(ContextFit::Fits);
+    }
+
+    let too_long = || {
+        format!(
+            "Prompt of ~{} tokens plus {} completion tokens exceeds the {}-token context window of {}",
+            prompt, completion, window, route.model
+        )
+    };
+    match mode {
+        ContextOverflow::Reject => Err(too_long()),
+        ContextOverflow::Upgrade => {
+            let model = larger_variant(route, prompt + completion).ok_or_else(too_long)?;
+            route.model = model.clone();
+            Ok(ContextFit::Upgraded(model))
+        }
+        ContextOverflow::Truncate => {
+            let count =
+                overflow_prefix(request, window.saturating_sub(completion)).ok_or_else(too_long)?;
+            drop_oldest(request, count);
+            Ok(ContextFit::Truncated(count))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::llm_gateway::redact::PiiKind;
+    use crate::commands::llm_gateway::{GatewaySettings, LLMProvider, RedactionConfig};
+    use serde_json::json;
+
+    fn moonshot_route(model: &str) -> RouteTarget {
+        let provider = GatewaySettings::default()
+            .providers
+            .into_iter()
+            .find(|p| p.provider == LLMProvider::Moonshot)
+            .unwrap();
+        RouteTarget {
+            provider,
+            model: model.to_string(),
+            ab: None,
+        }
+    }
+
+    fn conversation(turn_chars: usize, turns: usize) -> Value {
+        let mut messages = vec![json!({"role": "system", "content": "Be helpful"})];
+        for i in 0..turns {
+            messages.push(
+                json!({"role": "user", "content": format!("{} {}", i, "x".repeat(turn_chars))}),
+            );
+            messages.push(json!({"role": "assistant", "content": "ok"}));
+        }
+        messages.push(json!({"role": "user", "content": "latest"}));
+        json!({"messages": messages, "max_tokens": 1000})
+    }
+
+    #[test]
+    fn test_replace_with_summary() {
+        let mut request = conversation(10, 2);
+        let dropped = oldest_messages(&request, 2);
+        assert_eq!(
+            transcript(&dropped),
+            format!("user: 0 {}\n\nassistant: ok", "x".repeat(10))
+        );
+
+        replace_with_summary(&mut request, 2, "User asked twice.");
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0]["role"], "system");
+        assert!(messages[1]["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("Summary of the earlier conversation:\nUser asked twice.\n\n1 "));
+    }
+
+    #[test]
+    fn test_summary_request_redaction() {
+        let text = "user: mail ops@example.com";
+        let plain = summary_request(text, 500, None);
+        assert_eq!(plain["messages"][1]["content"], text);
+        assert_eq!(plain["max_tokens"], 500);
+
+        let mut redactor = Redactor::new(&RedactionConfig {
+            enabled: true,
+            providers: Vec::new(),
+            detectors: vec![PiiKind::Email],
+            custom_patterns: Vec::new(),
+            restore_responses: true,
+        });
+        let mut request = summary_request(text, 500, Some(&mut redactor));
+        assert_eq!(request["messages"][1]["content"], "user: mail [EMAIL_1]");
+        assert!(!request.to_string().contains("ops@example.com"));
+
+        redactor.restore(&mut request);
+        assert_eq!(request["messages"][1]["content"], text);
+    }
+
+    #[test]
+    fn test_model_family() {
+        assert_eq!(model_family("moonshot-v1-128k"), "moonshot-v1");
+        assert_eq!(model_family("gpt-4o-mini"), "gpt-4o-mini");
+    }
+
+    #[test]
+    fn test_fit_context_modes() {
+        // ~10 turns of ~1000 tokens against an 8K window
+        let request = conversation(4000, 10);
+
+        let mut route = moonshot_route("moonshot-v1-8k");
+        let fit = fit_context(&mut request.clone(), &mut route, ContextOverflow::Upgrade);
+        assert_eq!(fit, Ok(ContextFit::Upgraded("moonshot-v1-32k".to_string())));
+        assert_eq!(route.model, "moonshot-v1-32k");
+
+        let mut route = moonshot_route("moonshot-v1-8k");
+        assert!(fit_context(&mut request.clone(), &mut route, ContextOverflow::Reject).is_err());
+
+        let mut truncated = request.clone();
+        let fit = fit_context(&mut truncated, &mut route, ContextOverflow::Truncate).unwrap();
+        let ContextFit::Truncated(count) = fit else {
+            panic!("expected truncation, got {:?}", fit);
+        };
+        assert_eq!(count % 2, 0);
+        let messages = truncated["messages"].as_array().unwrap();
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["role"], "user");
+        assert_eq!(messages.last().unwrap()["content"], "latest");
+        assert!(estimate_prompt_tokens(&truncated) + 1000 <= 8192);
+    }
+}
@@ -0,0 +1,195 @@
+//! GitHub Copilot - Copilot chat models for existing Copilot subscribers
+//!
+//! Signing in uses GitHub's device flow: the app shows a user code, the user enters it
+//! on github.com, and polling then yields a GitHub OAuth token that is stored as the
+//! Copilot provider's key. Copilot's chat API doesn't take that token directly; it is
+//! exchanged for a short-lived Copilot session token, cached until shortly before it
+//! expires. Requests also have to identify an editor integration.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::client::UpstreamClient;
+use super::ProviderConfig;
+
+/// OAuth app of GitHub's own Copilot editor integrations
+const CLIENT_ID: &str = "Iv1.b507a08c87ecfe98";
+
+const DEVICE_CODE_URL: &str = "https://github.com/login/device/code";
+const ACCESS_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+const SESSION_TOKEN_URL: &str = "https://api.github.com/copilot_internal/v2/token";
+
+/// Seconds before expiry at which a session token is renewed
+const RENEW_MARGIN_SECS: u64 = 60;
+
+/// Headers identifying the editor integration, which Copilot requires on every request
+pub fn editor_headers() -> HashMap<String, String> {
+    [
+        ("Editor-Version", "vscode/1.95.0"),
+        ("Editor-Plugin-Version", "copilot-chat/0.22.0"),
+        ("Copilot-Integration-Id", "vscode-chat"),
+        ("User-Agent", "GitHubCopilotChat/0.22.0"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect()
+}
+
+/// Code the user enters at `verification_uri` to authorize the app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceLogin {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    /// Seconds to wait between polls
+    pub interval: u64,
+    /// Seconds until the code expires
+    pub expires_in: u64,
+}
+
+/// Outcome of polling a device login
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoginPoll {
+    /// The user hasn't authorized the app yet
+    Pending,
+    /// Polling too fast; wait `interval` seconds between polls from now on
+    SlowDown { interval: u64 },
+    /// Authorized; the token has been stored
+    Complete,
+}
+
+/// Start a device login
+pub async fn start_device_login(client: &reqwest::Client) -> Result<DeviceLogin, String> {
+    let response = client
+        .post(DEVICE_CODE_URL)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .form(&[("client_id", CLIENT_ID), ("scope", "read:user")])
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach GitHub: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("GitHub refused the login: {}", response.status()));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Unexpected device code response: {}", e))
+}
+
+/// Poll a device login; returns the GitHub token once the user has authorized the app
+pub async fn poll_device_login(
+    client: &reqwest::Client,
+    device_code: &str,
+) -> Result<Result<String, LoginPoll>, String> {
+    let response: Value = client
+        .post(ACCESS_TOKEN_URL)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .form(&[
+            ("client_id", CLIENT_ID),
+            ("device_code", device_code),
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+        ])
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach GitHub: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Unexpected token response: {}", e))?;
+    poll_outcome(&response)
+}
+
+/// Interpret GitHub's answer to a device login poll
+fn poll_outcome(response: &Value) -> Result<Result<String, LoginPoll>, String> {
+    if let Some(token) = response["access_token"].as_str() {
+        return Ok(Ok(token.to_string()));
+    }
+    match response["error"].as_str() {
+        Some("authorization_pending") => Ok(Err(LoginPoll::Pending)),
+        Some("slow_down") => Ok(Err(LoginPoll::SlowDown {
+            interval: response["interval"].as_u64().unwrap_or(10),
+        })),
+        Some("expired_token") => Err("The login code expired, start again".to_string()),
+        Some("access_denied") => Err("The login was denied on GitHub".to_string()),
+        _ => Err(response["error_description"]
+            .as_str()
+            .unwrap_or("GitHub login failed")
+            .to_string()),
+    }
+}
+
+/// Copilot session token for the provider's GitHub token. The outer error is a transport
+/// failure; the inner one is GitHub's response when it refuses the exchange (no Copilot
+/// subscription, revoked token), handed on to the client like any upstream error.
+pub async fn session_token(
+    http: &UpstreamClient,
+    provider: &ProviderConfig,
+) -> Result<Result<String, reqwest::Response>, reqwest::Error> {
+    static TOKENS: OnceLock<Mutex<HashMap<String, (String, u64)>>> = OnceLock::new();
+    let tokens = TOKENS.get_or_init(Default::default);
+
+    let github_token = provider.api_key.clone().unwrap_or_default();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    if let Some((token, expires_at)) = tokens
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&github_token)
+    {
+        if now + RENEW_MARGIN_SECS < *expires_at {
+            return Ok(Ok(token.clone()));
+        }
+    }
+
+    let mut request = http.get(provider, SESSION_TOKEN_URL).header(
+        reqwest::header::AUTHORIZATION,
+        format!("token {}", github_token),
+    );
+    for (name, value) in editor_headers() {
+        request = request.header(name, value);
+    }
+    let response = http.send(provider, request).await?;
+    if !response.status().is_success() {
+        return Ok(Err(response));
+    }
+    let session: Value = response.json().await?;
+    let token = session["token"].as_str().unwrap_or_default().to_string();
+    let expires_at = session["expires_at"].as_u64().unwrap_or(now);
+    tokens
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(github_token, (token.clone(), expires_at));
+    Ok(Ok(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_poll_outcome() {
+        assert_eq!(
+            poll_outcome(&json!({"access_token": "gho_abc", "token_type": "bearer"})),
+            Ok(Ok("gho_abc".to_string()))
+        );
+        assert_eq!(
+            poll_outcome(&json!({"error": "authorization_pending"})),
+            Ok(Err(LoginPoll::Pending))
+        );
+        assert_eq!(
+            poll_outcome(&json!({"error": "slow_down", "interval": 10})),
+            Ok(Err(LoginPoll::SlowDown { interval: 10 }))
+        );
+        assert!(poll_outcome(&json!({"error": "expired_token"})).is_err());
+        assert_eq!(
+            serde_json::to_value(LoginPoll::SlowDown { interval: 10 }).unwrap(),
+            json!({"status": "slow_down", "interval": 10})
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Currencies - Model prices quoted in other currencies and costs shown in one
+//!
+//! Providers such as Moonshot and Qwen list prices in CNY. A model may name the currency
+//! its prices are in; the running gateway converts them to USD with the configured
+//! exchange rates, so spend is still logged, capped and budgeted in USD. Usage reports
+//! and exports additionally show costs in the display currency.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::GatewaySettings;
+
+/// Exchange rates and the currency costs are shown in
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CurrencyConfig {
+    /// ISO 4217 code costs are shown in; USD when unset
+    #[serde(default)]
+    pub display: Option<String>,
+    /// Units of each currency one USD buys, by ISO 4217 code
+    #[serde(default)]
+    pub rates: HashMap<String, f64>,
+}
+
+/// Currency costs are shown in, with its units per USD
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DisplayCurrency {
+    pub code: String,
+    pub per_usd: f64,
+}
+
+impl CurrencyConfig {
+    /// Units of `code` one USD buys, if known
+    pub fn per_usd(&self, code: &str) -> Option<f64> {
+        if code.eq_ignore_ascii_case("USD") {
+            return Some(1.0);
+        }
+        self.rates
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(code))
+            .map(|(_, rate)| *rate)
+            .filter(|rate| *rate > 0.0)
+    }
+
+    /// The display currency, unless it is USD or has no rate
+    pub fn display_currency(&self) -> Option<DisplayCurrency> {
+        let code = self
+            .display
+            .as_deref()
+            .filter(|c| !c.eq_ignore_ascii_case("USD"))?;
+        match self.per_usd(code) {
+            Some(per_usd) => Some(DisplayCurrency {
+                code: code.to_uppercase(),
+                per_usd,
+            }),
+            None => {
+                log::warn!("No exchange rate for display currency {}", code);
+                None
+            }
+        }
+    }
+}
+
+impl DisplayCurrency {
+    pub fn convert(&self, usd: f64) -> f64 {
+        usd * self.per_usd
+    }
+}
+
+/// `usd` formatted in the display currency, or in dollars without one
+pub fn format_cost(currency: Option<&DisplayCurrency>, usd: f64, decimals: usize) -> String {
+    match currency {
+        Some(currency) => format!("{:.*} {}", decimals, currency.convert(usd), currency.code),
+        None => format!("${:.*}", decimals, usd),
+    }
+}
+
+/// Convert the prices of models quoted in other currencies to USD, for the running
+/// gateway. Models whose currency has no rate keep their prices, as if they were USD.
+pub fn normalize_prices(settings: &mut GatewaySettings) {
+    let config = settings.currency.clone().unwrap_or_default();
+    for provider in &mut settings.providers {
+        for model in &mut provider.models {
+            let Some(code) = model.currency.as_deref() else {
+                continue;
+            };
+            let Some(rate) = config.per_usd(code) else {
+                log::warn!(
+                    "No exchange rate for {}, taking {}/{} prices as USD",
+                    code,
+                    provider.name,
+                    model.id
+                );
+                continue;
+            };
+            let to_usd = |price: &mut f64| *price /= rate;
+            to_usd(&mut model.input_price);
+            to_usd(&mut model.output_price);
+            [
+                &mut model.cache_read_price,
+                &mut model.cache_write_price,
+                &mut model.image_price,
+                &mut model.audio_price,
+                &mut model.speech_price,
+                &mut model.request_fee,
+            ]
+            .into_iter()
+            .flatten()
+            .for_each(to_usd);
+            for window in &mut model.price_windows {
+                to_usd(&mut window.input_price);
+                to_usd(&mut window.output_price);
+            }
+            for tier in &mut model.price_tiers {
+                to_usd(&mut tier.input_price);
+                to_usd(&mut tier.output_price);
+                tier.cache_read_price.iter_mut().for_each(to_usd);
+                tier.cache_write_price.iter_mut().for_each(to_usd);
+            }
+            model.currency = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_prices() {
+        let mut settings = GatewaySettings {
+            currency: Some(CurrencyConfig {
+                display: Some("cny".to_string()),
+                rates: HashMap::from([("CNY".to_string(), 7.0)]),
+            }),
+            ..Default::default()
+        };
+        let model = &mut settings.providers[0].models[0];
+        model.currency = Some("CNY".to_string());
+        model.input_price = 14.0;
+        model.cache_read_price = Some(3.5);
+
+        normalize_prices(&mut settings);
+        let model = &settings.providers[0].models[0];
+        assert_eq!(
+            (model.input_price, model.cache_read_price),
+            (2.0, Some(0.5))
+        );
+        assert_eq!(model.currency, None);
+
+        let display = settings.currency.unwrap().display_currency();
+        assert_eq!(format_cost(display.as_ref(), 1.5, 2), "10.50 CNY");
+        assert_eq!(format_cost(None, 1.5, 4), "$1.5000");
+    }
+}
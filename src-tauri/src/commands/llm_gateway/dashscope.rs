@@ -0,0 +1,236 @@
+//! DashScope Native API - Qwen through Alibaba's own request envelope
+//!
+//! DashScope's compatible-mode endpoint covers plain chat but hides features such as
+//! search enhancement (`enable_search`) and partial mode (an assistant message with
+//! `partial: true` that the model continues). Providers switched to the native API get
+//! chat completion requests wrapped in DashScope's `input`/`parameters` envelope, streamed
+//! through `X-DashScope-SSE`, and the answers mapped back into the chat completion shape
+//! the rest of the gateway works with. Extra request fields pass through as parameters.
+
+use serde_json::{json, Map, Value};
+
+use super::translate::{SseParser, StreamTranslator};
+use super::{LLMProvider, ProviderConfig};
+
+/// Header turning on server-sent events
+pub const SSE_HEADER: &str = "X-DashScope-SSE";
+
+const GENERATION_PATH: &str = "/api/v1/services/aigc/text-generation/generation";
+
+/// Whether requests to `provider` use DashScope's native API
+pub fn uses_native_api(provider: &ProviderConfig) -> bool {
+    provider.provider == LLMProvider::Qwen && provider.native_api
+}
+
+/// Native generation endpoint for a provider configured with the compatible-mode URL
+pub fn generation_url(base_url: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    let host = base
+        .strip_suffix("/compatible-mode/v1")
+        .or_else(|| base.strip_suffix("/api/v1"))
+        .unwrap_or(base);
+    format!("{}{}", host, GENERATION_PATH)
+}
+
+/// Native request equivalent to a chat completion request
+pub fn to_native_request(body: &Value) -> Value {
+    let stream = body.get("stream").and_then(|s| s.as_bool()) == Some(true);
+    let mut parameters = Map::new();
+    for (field, value) in body.as_object().into_iter().flatten() {
+        if !matches!(
+            field.as_str(),
+            "model" | "messages" | "stream" | "stream_options"
+        ) {
+            parameters.insert(field.clone(), value.clone());
+        }
+    }
+    if let Some(max_tokens) = parameters.remove("max_completion_tokens") {
+        parameters.entry("max_tokens").or_insert(max_tokens);
+    }
+    parameters.insert("result_format".to_string(), json!("message"));
+    if stream {
+        // Deltas rather than the whole text so far
+        parameters.insert("incremental_output".to_string(), json!(true));
+    }
+
+    json!({
+        "model": body.get("model").cloned().unwrap_or(Value::Null),
+        "input": { "messages": body.get("messages").cloned().unwrap_or_else(|| json!([])) },
+        "parameters": parameters,
+    })
+}
+
+/// DashScope reports an unfinished choice as the string "null"
+fn finish_reason(choice: &Value) -> Value {
+    match choice.get("finish_reason") {
+        Some(Value::String(reason)) if reason != "null" => json!(reason),
+        _ => Value::Null,
+    }
+}
+
+fn openai_usage(usage: &Value) -> Value {
+    let input = usage["input_tokens"].as_u64().unwrap_or_default();
+    let output = usage["output_tokens"].as_u64().unwrap_or_default();
+    json!({
+        "prompt_tokens": input,
+        "completion_tokens": output,
+        "total_tokens": input + output,
+    })
+}
+
+/// Choices of a native response; `text` results carry no choice list
+fn choices(response: &Value) -> Vec<Value> {
+    match response
+        .pointer("/output/choices")
+        .and_then(|c| c.as_array())
+    {
+        Some(choices) => choices.clone(),
+        None => vec![json!({
+            "message": {"role": "assistant", "content": response.pointer("/output/text")},
+            "finish_reason": response.pointer("/output/finish_reason"),
+        })],
+    }
+}
+
+/// Chat completion equivalent to a native response
+pub fn to_openai_response(response: &Value, model: &str) -> Value {
+    let choices: Vec<Value> = choices(response)
+        .iter()
+        .enumerate()
+        .map(|(index, choice)| {
+            json!({
+                "index": index,
+                "message": choice.get("message").cloned().unwrap_or_else(|| json!({})),
+                "finish_reason": finish_reason(choice),
+            })
+        })
+        .collect();
+    json!({
+        "id": response.get("request_id").cloned().unwrap_or(Value::Null),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": model,
+        "choices": choices,
+        "usage": openai_usage(&response["usage"]),
+    })
+}
+
+/// Translates a native `X-DashScope-SSE` stream into chat completion chunks
+pub struct NativeStreamTranslator {
+    parser: SseParser,
+    model: String,
+    created: i64,
+}
+
+impl NativeStreamTranslator {
+    pub fn new(model: &str) -> Self {
+        Self {
+            parser: SseParser::default(),
+            model: model.to_string(),
+            created: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    fn chunk(&self, event: &Value) -> String {
+        // Native streams report failures as events carrying a code and message
+        if event.get("output").is_none() {
+            if let Some(message) = event.get("message") {
+                let error = json!({"error": {"message": message, "code": event.get("code")}});
+                return format!("data: {}\n\n", error);
+            }
+        }
+        let choices: Vec<Value> = choices(event)
+            .iter()
+            .enumerate()
+            .map(|(index, choice)| {
+                json!({
+                    "index": index,
+                    "delta": choice.get("message").cloned().unwrap_or_else(|| json!({})),
+                    "finish_reason": finish_reason(choice),
+                })
+            })
+            .collect();
+        let mut chunk = json!({
+            "id": event.get("request_id").cloned().unwrap_or(Value::Null),
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": choices,
+        });
+        let finished = chunk["choices"]
+            .as_array()
+            .is_some_and(|c| c.iter().any(|c| !c["finish_reason"].is_null()));
+        if finished && event.get("usage").is_some() {
+            chunk["usage"] = openai_usage(&event["usage"]);
+        }
+        format!("data: {}\n\n", chunk)
+    }
+}
+
+impl StreamTranslator for NativeStreamTranslator {
+    fn push(&mut self, chunk: &[u8]) -> String {
+        let mut out = String::new();
+        for payload in self.parser.push(chunk) {
+            if let Ok(event) = serde_json::from_str::<Value>(&payload) {
+                out.push_str(&self.chunk(&event));
+            }
+        }
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        "data: [DONE]\n\n".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_translation() {
+        assert_eq!(
+            generation_url("https://dashscope.aliyuncs.com/compatible-mode/v1/"),
+            "https://dashscope.aliyuncs.com/api/v1/services/aigc/text-generation/generation"
+        );
+
+        let request = to_native_request(&json!({
+            "model": "qwen-plus",
+            "messages": [
+                {"role": "user", "content": "Write a haiku"},
+                {"role": "assistant", "content": "Autumn", "partial": true}
+            ],
+            "stream": true,
+            "max_completion_tokens": 50,
+            "enable_search": true
+        }));
+        assert_eq!(request["input"]["messages"][1]["partial"], true);
+        assert_eq!(request["parameters"]["enable_search"], true);
+        assert_eq!(request["parameters"]["max_tokens"], 50);
+        assert_eq!(request["parameters"]["incremental_output"], true);
+        assert!(request["parameters"].get("stream").is_none());
+
+        let response = to_openai_response(
+            &json!({
+                "request_id": "r1",
+                "output": {"choices": [{"finish_reason": "stop",
+                    "message": {"role": "assistant", "content": " moon"}}]},
+                "usage": {"input_tokens": 12, "output_tokens": 3}
+            }),
+            "qwen-plus",
+        );
+        assert_eq!(response["choices"][0]["message"]["content"], " moon");
+        assert_eq!(response["usage"]["total_tokens"], 15);
+
+        let mut translator = NativeStreamTranslator::new("qwen-plus");
+        let out = translator.push(
+            b"id:1\nevent:result\n:HTTP_STATUS/200\ndata:{\"output\":{\"choices\":[{\"message\":{\"content\":\"Au\",\"role\":\"assistant\"},\"finish_reason\":\"null\"}]},\"usage\":{\"input_tokens\":5,\"output_tokens\":1}}\n\n",
+        );
+        let chunk: Value =
+            serde_json::from_str(out.trim().strip_prefix("data: ").unwrap()).unwrap();
+        assert_eq!(chunk["choices"][0]["delta"]["content"], "Au");
+        assert!(chunk["choices"][0]["finish_reason"].is_null());
+        assert!(chunk.get("usage").is_none());
+        assert_eq!(translator.finish(), "data: [DONE]\n\n");
+    }
+}
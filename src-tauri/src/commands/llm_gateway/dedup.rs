@@ -0,0 +1,343 @@
+//! In-flight Deduplication - One upstream call for concurrent identical requests
+//!
+//! Agents that retry too eagerly send the same request again while the first is still
+//! running. Identical JSON requests (same endpoint, body, credentials and routing
+//! headers) that arrive while one is in flight don't start another upstream call: they
+//! follow the first one and receive its status, headers and body as they arrive, so
+//! streams fan out live. A request that arrives after the first has finished runs anew.
+//! When the first response is cut short, by an upstream error or its client going away,
+//! the followers' responses end with an error too rather than looking complete.
+//! Clients opt out per request with [`NO_DEDUP_HEADER`].
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Header turning deduplication off for a request
+pub const NO_DEDUP_HEADER: &str = "x-doggy-no-dedup";
+
+/// Largest request body considered for deduplication
+const MAX_DEDUP_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Headers besides the gateway's own `x-doggy-*` ones that change what a request does
+const KEY_HEADERS: &[&str] = &[
+    "authorization",
+    "x-api-key",
+    "x-goog-api-key",
+    "anthropic-version",
+    "anthropic-beta",
+    "accept",
+];
+
+/// Response of a request in flight, as far as it has arrived
+#[derive(Debug, Default)]
+struct Recorded {
+    head: Option<(StatusCode, HeaderMap)>,
+    chunks: Vec<Bytes>,
+    /// Set once no more chunks will arrive: `Ok` when the body ended, else why it didn't
+    end: Option<Result<(), String>>,
+}
+
+#[derive(Debug, Default)]
+struct Flight {
+    recorded: Mutex<Recorded>,
+    /// Bumped whenever `recorded` changes
+    progress: watch::Sender<()>,
+}
+
+impl Flight {
+    fn update(&self, change: impl FnOnce(&mut Recorded)) {
+        change(&mut self.recorded.lock().unwrap_or_else(|e| e.into_inner()));
+        self.progress.send_replace(());
+    }
+}
+
+/// Requests in flight, by key
+#[derive(Default)]
+pub struct InFlight {
+    flights: Mutex<HashMap<String, Arc<Flight>>>,
+}
+
+impl InFlight {
+    /// The flight to follow for `key`, or else a newly registered one to lead
+    fn join(&self, key: &str) -> Result<Arc<Flight>, Arc<Flight>> {
+        let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+        match flights.get(key) {
+            Some(flight) => Ok(flight.clone()),
+            None => {
+                let flight = Arc::new(Flight::default());
+                flights.insert(key.to_string(), flight.clone());
+                Err(flight)
+            }
+        }
+    }
+
+    fn remove(&self, key: &str, flight: &Arc<Flight>) {
+        let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+        if flights.get(key).is_some_and(|f| Arc::ptr_eq(f, flight)) {
+            flights.remove(key);
+        }
+    }
+}
+
+/// Identity of a request for deduplication
+fn request_key(method: &Method, uri: &str, headers: &HeaderMap, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str().as_bytes());
+    hasher.update(uri.as_bytes());
+    let mut names: Vec<&str> = headers
+        .keys()
+        .map(|name| name.as_str())
+        .filter(|name| KEY_HEADERS.contains(name) || name.starts_with("x-doggy-"))
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    for name in names {
+        for value in headers.get_all(name) {
+            hasher.update(name.as_bytes());
+            hasher.update(value.as_bytes());
+        }
+    }
+    hasher.update(body);
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Whether a request may be coalesced: a bounded JSON POST without the opt-out header
+fn is_candidate(request: &Request) -> bool {
+    let headers = request.headers();
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    let length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    request.method() == Method::POST
+        && is_json
+        && length.is_some_and(|length| length <= MAX_DEDUP_BYTES)
+        && !headers.contains_key(NO_DEDUP_HEADER)
+}
+
+/// Finishes a led flight when the leader's response is done or dropped
+struct Leader {
+    in_flight: Arc<InFlight>,
+    key: String,
+    flight: Arc<Flight>,
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        self.flight.update(|recorded| {
+            recorded
+                .end
+                .get_or_insert_with(|| Err("The original request was cancelled".to_string()));
+        });
+        self.in_flight.remove(&self.key, &self.flight);
+    }
+}
+
+/// Response replaying a flight's recorded body and following it until it finishes, or
+/// `None` if the leader went away before responding
+async fn follow(flight: Arc<Flight>) -> Option<Response> {
+    let mut progress = flight.progress.subscribe();
+    let (status, headers) = loop {
+        {
+            let recorded = flight.recorded.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(head) = &recorded.head {
+                break head.clone();
+            }
+            if recorded.end.is_some() {
+                return None;
+            }
+        }
+        if progress.changed().await.is_err() {
+            return None;
+        }
+    };
+
+    let body = futures::stream::unfold(Some((0, progress)), move |state| {
+        let flight = flight.clone();
+        async move {
+            let (next, mut progress) = state?;
+            loop {
+                {
+                    let recorded = flight.recorded.lock().unwrap_or_else(|e| e.into_inner());
+                    if let Some(chunk) = recorded.chunks.get(next) {
+                        return Some((Ok(chunk.clone()), Some((next + 1, progress))));
+                    }
+                    match &recorded.end {
+                        Some(Ok(())) => return None,
+                        Some(Err(e)) => return Some((Err(std::io::Error::other(e.clone())), None)),
+                        None => {}
+                    }
+                }
+                progress.changed().await.ok()?;
+            }
+        }
+    });
+    let mut response = Response::new(Body::from_stream(body));
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Some(response)
+}
+
+/// Coalesce concurrent identical requests onto one upstream call
+pub async fn coalesce(
+    State(in_flight): State<Arc<InFlight>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !is_candidate(&request) {
+        return next.run(request).await;
+    }
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_DEDUP_BYTES as usize).await {
+        Ok(bytes) => bytes,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Failed to read request body").into_response(),
+    };
+    let key = request_key(
+        &parts.method,
+        &parts.uri.to_string(),
+        &parts.headers,
+        &bytes,
+    );
+
+    let flight = match in_flight.join(&key) {
+        Ok(flight) => {
+            if let Some(response) = follow(flight).await {
+                log::debug!(
+                    "Served a duplicate {} request from one in flight",
+                    parts.uri
+                );
+                return response;
+            }
+            // The leader was cancelled before responding; this request runs on its own
+            return next
+                .run(Request::from_parts(parts, Body::from(bytes)))
+                .await;
+        }
+        Err(flight) => flight,
+    };
+    let leader = Leader {
+        in_flight,
+        key,
+        flight: flight.clone(),
+    };
+
+    let response = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+    flight.update(|recorded| {
+        recorded.head = Some((response.status(), response.headers().clone()));
+    });
+    response.map(|body| {
+        let state = Some((body.into_data_stream(), leader));
+        Body::from_stream(futures::stream::unfold(state, |state| async move {
+            let (mut stream, leader) = state?;
+            let item = stream.next().await;
+            leader.flight.update(|recorded| match &item {
+                Some(Ok(chunk)) => recorded.chunks.push(chunk.clone()),
+                Some(Err(e)) => recorded.end = Some(Err(e.to_string())),
+                None => recorded.end = Some(Ok(())),
+            });
+            let item = item?;
+            let state = item.is_ok().then_some((stream, leader));
+            Some((item, state))
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_key() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "sk-a".parse().unwrap());
+        headers.insert("x-stainless-retry-count", "0".parse().unwrap());
+        let body = br#"{"model":"claude-3-5-sonnet","messages":[]}"#;
+        let key = request_key(&Method::POST, "/v1/messages", &headers, body);
+
+        // Retries of the same request coalesce
+        headers.insert("x-stainless-retry-count", "1".parse().unwrap());
+        assert_eq!(
+            request_key(&Method::POST, "/v1/messages", &headers, body),
+            key
+        );
+
+        // Other clients and routing choices don't
+        headers.insert("x-doggy-model", "deepseek-chat".parse().unwrap());
+        assert_ne!(
+            request_key(&Method::POST, "/v1/messages", &headers, body),
+            key
+        );
+        headers.remove("x-doggy-model");
+        headers.insert("x-api-key", "sk-b".parse().unwrap());
+        assert_ne!(
+            request_key(&Method::POST, "/v1/messages", &headers, body),
+            key
+        );
+
+        let in_flight = InFlight::default();
+        let led = in_flight.join(&key).unwrap_err();
+        assert!(Arc::ptr_eq(&in_flight.join(&key).unwrap(), &led));
+        in_flight.remove(&key, &led);
+        assert!(in_flight.join(&key).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fan_out() {
+        use axum::{middleware, routing::post, Router};
+        use tower::ServiceExt;
+
+        let (tx, rx) = futures::channel::mpsc::unbounded::<Result<Bytes, std::io::Error>>();
+        let rx = Arc::new(Mutex::new(Some(rx)));
+        // A second upstream call would find the body taken and panic
+        let upstream = move || {
+            let body = Body::from_stream(rx.lock().unwrap().take().unwrap());
+            async move { body }
+        };
+        let in_flight = Arc::new(InFlight::default());
+        let app = Router::new()
+            .route("/v1/messages", post(upstream))
+            .layer(middleware::from_fn_with_state(in_flight, coalesce));
+        let request = || {
+            Request::post("/v1/messages")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::CONTENT_LENGTH, "2")
+                .body(Body::from("{}"))
+                .unwrap()
+        };
+
+        let leader = app.clone().oneshot(request()).await.unwrap();
+        let follower = app.clone().oneshot(request()).await.unwrap();
+        let mut leader = leader.into_body().into_data_stream();
+        let mut follower = follower.into_body().into_data_stream();
+
+        tx.unbounded_send(Ok(Bytes::from_static(b"data: 1\n\n")))
+            .unwrap();
+        assert_eq!(leader.next().await.unwrap().unwrap(), "data: 1\n\n");
+        assert_eq!(follower.next().await.unwrap().unwrap(), "data: 1\n\n");
+
+        // The upstream failing partway fails the follower too
+        tx.unbounded_send(Err(std::io::Error::other("reset")))
+            .unwrap();
+        assert!(leader.next().await.unwrap().is_err());
+        assert!(follower.next().await.unwrap().is_err());
+        assert!(follower.next().await.is_none());
+    }
+}
@@ -0,0 +1,418 @@
+//! Documents - Anthropic `document` content blocks on non-Anthropic providers
+//!
+//! Providers that accept file inputs get PDFs passed through as OpenAI `file` parts.
+//! For the rest, the text is extracted locally and inlined so document-heavy prompts
+//! don't just fail. PDF extraction is best-effort: it reads the text-showing operators
+//! of uncompressed or Flate-compressed content streams and ignores custom font encodings.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use flate2::read::ZlibDecoder;
+use serde_json::{json, Value};
+use std::io::Read;
+
+use super::translate::content_text;
+use super::{LLMProvider, ProviderConfig};
+
+/// Whether a provider takes document blocks without local text extraction
+pub fn supports_file_input(provider: &ProviderConfig) -> bool {
+    provider.supports_file_input
+        || (provider.provider == LLMProvider::Anthropic && provider.adapter.is_none())
+}
+
+fn document_title(block: &Value) -> Option<&str> {
+    block.get("title").and_then(|t| t.as_str())
+}
+
+/// Convert an Anthropic document block into an OpenAI content part for providers with
+/// file input support
+pub fn anthropic_document_to_openai(block: &Value) -> Option<Value> {
+    let source = block.get("source")?;
+    match source.get("type").and_then(|t| t.as_str())? {
+        "base64" => {
+            let media_type = source.get("media_type")?.as_str()?;
+            Some(json!({
+                "type": "file",
+                "file": {
+                    "filename": document_title(block).unwrap_or("document.pdf"),
+                    "file_data": format!("data:{};base64,{}", media_type, source.get("data")?.as_str()?),
+                }
+            }))
+        }
+        // Plain-text and URL documents have no file form, so they are inlined either way
+        _ => Some(json!({"type": "text", "text": document_text(block)})),
+    }
+}
+
+/// Text of a document block, extracting it from PDFs locally
+pub fn document_text(block: &Value) -> String {
+    let source = block.get("source").cloned().unwrap_or(Value::Null);
+    let source_type = source.get("type").and_then(|t| t.as_str()).unwrap_or("");
+    let media_type = source
+        .get("media_type")
+        .and_then(|t| t.as_str())
+        .unwrap_or("");
+    let data = source.get("data").and_then(|d| d.as_str()).unwrap_or("");
+
+    let body = match source_type {
+        "text" => data.to_string(),
+        "content" => source.get("content").map(content_text).unwrap_or_default(),
+        "url" => format!(
+            "[Document not inlined: {}]",
+            source.get("url").and_then(|u| u.as_str()).unwrap_or("")
+        ),
+        "base64" => match STANDARD.decode(data.trim()) {
+            Ok(bytes) if media_type == "application/pdf" => {
+                let text = extract_pdf_text(&bytes);
+                if text.is_empty() {
+                    "[PDF document with no extractable text]".to_string()
+                } else {
+                    text
+                }
+            }
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(_) => "[Unreadable document]".to_string(),
+        },
+        _ => "[Unsupported document]".to_string(),
+    };
+
+    match document_title(block) {
+        Some(title) => format!("Document: {}\n\n{}", title, body),
+        None => body,
+    }
+}
+
+/// Replace document blocks with their extracted text when `provider` can't take files
+pub fn inline_documents(request: &mut Value, provider: &ProviderConfig) {
+    if supports_file_input(provider) {
+        return;
+    }
+
+    let blocks = request
+        .get_mut("messages")
+        .and_then(|m| m.as_array_mut())
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m.get_mut("content").and_then(|c| c.as_array_mut()))
+        .flatten()
+        .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("document"));
+
+    for block in blocks {
+        *block = json!({"type": "text", "text": document_text(block)});
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| p + from)
+}
+
+/// Best-effort text of a PDF, taken from its page content streams in file order
+pub fn extract_pdf_text(pdf: &[u8]) -> String {
+    let mut text = String::new();
+    let mut pos = 0;
+
+    while let Some(start) = find(pdf, b"stream", pos) {
+        pos = start + b"stream".len();
+        if pdf[..start].ends_with(b"end") {
+            continue;
+        }
+
+        let mut data_start = pos;
+        if pdf.get(data_start) == Some(&b'\r') {
+            data_start += 1;
+        }
+        if pdf.get(data_start) == Some(&b'\n') {
+            data_start += 1;
+        }
+        let Some(end) = find(pdf, b"endstream", data_start) else {
+            break;
+        };
+        pos = end + b"endstream".len();
+
+        // The stream dictionary sits between the object header and the `stream` keyword
+        let header = pdf[..start]
+            .windows(3)
+            .rposition(|w| w == b"obj")
+            .unwrap_or(0);
+        let dict = String::from_utf8_lossy(&pdf[header..start]);
+        // Fonts, images, object streams and other typed streams hold no page text
+        if ["/Subtype", "/Length1", "/Type"]
+            .iter()
+            .any(|key| dict.contains(key))
+        {
+            continue;
+        }
+
+        let raw = &pdf[data_start..end];
+        let content = if dict.contains("/FlateDecode") {
+            let mut decoded = Vec::new();
+            // A corrupt stream still yields whatever decoded before the error
+            let _ = ZlibDecoder::new(raw).read_to_end(&mut decoded);
+            decoded
+        } else if dict.contains("/Filter") {
+            continue;
+        } else {
+            raw.to_vec()
+        };
+
+        let page = content_stream_text(&content);
+        if !page.trim().is_empty() {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(page.trim());
+        }
+    }
+    text
+}
+
+/// Decode a PDF string: UTF-16BE with a byte order mark, else Latin-1
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Parse a literal string starting after its opening parenthesis; returns the bytes
+/// and the position after the closing parenthesis
+fn literal_string(content: &[u8], mut i: usize) -> (Vec<u8>, usize) {
+    let mut bytes = Vec::new();
+    let mut depth = 1;
+    while i < content.len() {
+        let c = content[i];
+        i += 1;
+        match c {
+            b'\\' => {
+                let Some(&escaped) = content.get(i) else {
+                    break;
+                };
+                i += 1;
+                match escaped {
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'b' => bytes.push(0x08),
+                    b'f' => bytes.push(0x0C),
+                    b'0'..=b'7' => {
+                        let mut value = (escaped - b'0') as u32;
+                        for _ in 0..2 {
+                            match content.get(i) {
+                                Some(d @ b'0'..=b'7') => {
+                                    value = value * 8 + (d - b'0') as u32;
+                                    i += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        bytes.push(value as u8);
+                    }
+                    // Line continuation
+                    b'\r' | b'\n' => {
+                        if escaped == b'\r' && content.get(i) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    other => bytes.push(other),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                bytes.push(c);
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+                bytes.push(c);
+            }
+            _ => bytes.push(c),
+        }
+    }
+    (bytes, i)
+}
+
+fn is_delimiter(c: u8) -> bool {
+    c.is_ascii_whitespace() || b"()<>[]{}/%".contains(&c)
+}
+
+/// Text shown by a page content stream's `Tj`, `TJ`, `'` and `"` operators
+fn content_stream_text(content: &[u8]) -> String {
+    let mut out = String::new();
+    let mut strings: Vec<String> = Vec::new();
+    let mut numbers: Vec<f64> = Vec::new();
+    let mut in_array = false;
+    let mut i = 0;
+
+    let newline = |out: &mut String| {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+    };
+
+    while i < content.len() {
+        let c = content[i];
+        match c {
+            b'(' => {
+                let (bytes, next) = literal_string(content, i + 1);
+                strings.push(decode_pdf_string(&bytes));
+                i = next;
+            }
+            b'<' if content.get(i + 1) == Some(&b'<') => i += 2,
+            b'>' if content.get(i + 1) == Some(&b'>') => i += 2,
+            b'<' => {
+                let end = find(content, b">", i).unwrap_or(content.len());
+                let hex: Vec<u8> = content[i + 1..end]
+                    .iter()
+                    .copied()
+                    .filter(|c| c.is_ascii_hexdigit())
+                    .collect();
+                let bytes: Vec<u8> = hex
+                    .chunks(2)
+                    .filter_map(|pair| {
+                        let digits = std::str::from_utf8(pair).ok()?;
+                        u8::from_str_radix(&format!("{:0<2}", digits), 16).ok()
+                    })
+                    .collect();
+                strings.push(decode_pdf_string(&bytes));
+                i = end + 1;
+            }
+            b'[' => {
+                in_array = true;
+                i += 1;
+            }
+            b']' => {
+                in_array = false;
+                i += 1;
+            }
+            b'%' => {
+                while i < content.len() && content[i] != b'\n' && content[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            b'/' => {
+                i += 1;
+                while i < content.len() && !is_delimiter(content[i]) {
+                    i += 1;
+                }
+            }
+            _ if c.is_ascii_whitespace() => i += 1,
+            _ => {
+                let start = i;
+                i += 1;
+                while i < content.len() && !is_delimiter(content[i]) {
+                    i += 1;
+                }
+                let token = &content[start..i];
+
+                if let Some(number) = std::str::from_utf8(token)
+                    .ok()
+                    .and_then(|t| t.parse::<f64>().ok())
+                {
+                    // Large negative kerning inside a TJ array separates words
+                    if in_array && number <= -200.0 {
+                        strings.push(" ".to_string());
+                    } else if !in_array {
+                        numbers.push(number);
+                    }
+                    continue;
+                }
+
+                match token {
+                    b"Tj" | b"TJ" => out.extend(strings.drain(..)),
+                    b"'" | b"\"" => {
+                        newline(&mut out);
+                        out.extend(strings.drain(..));
+                    }
+                    b"T*" | b"ET" => newline(&mut out),
+                    b"Td" | b"TD" if numbers.get(1).is_some_and(|ty| *ty != 0.0) => {
+                        newline(&mut out)
+                    }
+                    b"Td" | b"TD" | b"Tm"
+                        if !out.is_empty() && !out.ends_with(char::is_whitespace) =>
+                    {
+                        out.push(' ')
+                    }
+                    _ => {}
+                }
+                strings.clear();
+                numbers.clear();
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn pdf_with_content(content: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut pdf = b"%PDF-1.4\n1 0 obj\n<< /Type /Page >>\nendobj\n".to_vec();
+        pdf.extend_from_slice(
+            format!(
+                "4 0 obj\n<< /Length {} /Filter /FlateDecode >>\nstream\n",
+                compressed.len()
+            )
+            .as_bytes(),
+        );
+        pdf.extend_from_slice(&compressed);
+        pdf.extend_from_slice(b"\nendstream\nendobj\n%%EOF\n");
+        pdf
+    }
+
+    #[test]
+    fn test_extract_pdf_text() {
+        let pdf = pdf_with_content(
+            b"BT /F1 12 Tf 72 712 Td (Quarterly \\(Q3\\) report) Tj 0 -14 Td \
+              [(Revenue)-250(grew)] TJ ET",
+        );
+        assert_eq!(
+            extract_pdf_text(&pdf),
+            "Quarterly (Q3) report\nRevenue grew"
+        );
+    }
+
+    #[test]
+    fn test_inline_documents() {
+        let pdf = pdf_with_content(b"BT (Hello) Tj ET");
+        let mut request = json!({
+            "messages": [{"role": "user", "content": [
+                {"type": "document", "title": "greeting.pdf", "source": {
+                    "type": "base64", "media_type": "application/pdf", "data": STANDARD.encode(&pdf)
+                }},
+                {"type": "text", "text": "Summarize"}
+            ]}]
+        });
+
+        let mut provider = ProviderConfig {
+            supports_file_input: true,
+            ..Default::default()
+        };
+        inline_documents(&mut request, &provider);
+        assert_eq!(request["messages"][0]["content"][0]["type"], "document");
+
+        provider.supports_file_input = false;
+        inline_documents(&mut request, &provider);
+        assert_eq!(
+            request["messages"][0]["content"][0],
+            json!({"type": "text", "text": "Document: greeting.pdf\n\nHello"})
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Dry Runs - Where a request would go and what it would cost, without sending it
+//!
+//! A chat completion sent with [`DRY_RUN_HEADER`] is routed like any other (templates,
+//! rules, strategies, aliases, vision and saturation rerouting, guardrails) but never
+//! reaches a provider or the rate limits. The gateway answers with the chosen provider
+//! and model, the fallbacks it would try, and the cost estimated from the prompt's token
+//! count and the completion budget the request asks for.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::limits::{completion_budget, estimate_prompt_tokens};
+use super::router::RouteTarget;
+use super::usage::{usage_cost, TokenUsage};
+
+/// Header asking for a dry run instead of a completion
+pub const DRY_RUN_HEADER: &str = "x-doggy-dry-run";
+
+/// Provider and model a request would be sent to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DryRunTarget {
+    /// Provider display name
+    pub provider: String,
+    pub provider_type: String,
+    pub model: String,
+}
+
+/// Answer to a dry run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DryRunEstimate {
+    pub object: String,
+    pub target: DryRunTarget,
+    /// Routes tried in order when the target fails or is rate limited
+    pub fallbacks: Vec<DryRunTarget>,
+    /// Estimated prompt tokens
+    pub prompt_tokens: u64,
+    /// Completion tokens the request allows, when it sets a limit
+    pub max_completion_tokens: Option<u64>,
+    /// Cost of the prompt alone, fees included; unknown for unpriced models
+    pub prompt_cost_usd: Option<f64>,
+    /// Cost with the whole completion budget used; needs a limit on the request
+    pub max_cost_usd: Option<f64>,
+}
+
+impl From<&RouteTarget> for DryRunTarget {
+    fn from(route: &RouteTarget) -> Self {
+        Self {
+            provider: route.provider.name.clone(),
+            provider_type: route.provider.provider.to_string(),
+            model: route.model.clone(),
+        }
+    }
+}
+
+/// Estimate for `request` routed to `route`
+pub fn estimate(route: &RouteTarget, fallbacks: &[RouteTarget], request: &Value) -> DryRunEstimate {
+    let prompt_tokens = estimate_prompt_tokens(request);
+    let max_completion_tokens = Some(completion_budget(request)).filter(|&t| t > 0);
+    let model = route
+        .provider
+        .models
+        .iter()
+        .find(|m| m.id == route.model && !m.pricing_unknown);
+    let cost = |output_tokens: u64| {
+        let usage = TokenUsage {
+            input_tokens: prompt_tokens,
+            output_tokens,
+            estimated: true,
+            ..Default::default()
+        };
+        model.map(|model| usage_cost(&route.provider.provider, model, usage))
+    };
+    DryRunEstimate {
+        object: "gateway.dry_run".to_string(),
+        target: route.into(),
+        fallbacks: fallbacks.iter().map(DryRunTarget::from).collect(),
+        prompt_tokens,
+        max_completion_tokens,
+        prompt_cost_usd: cost(0),
+        max_cost_usd: max_completion_tokens.and_then(cost),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::llm_gateway::get_default_providers;
+    use serde_json::json;
+
+    #[test]
+    fn test_estimate() {
+        let mut provider = get_default_providers().remove(0);
+        provider.models[0].input_price = 2.0;
+        provider.models[0].output_price = 10.0;
+        let route = RouteTarget {
+            model: provider.models[0].id.clone(),
+            provider,
+            ab: None,
+        };
+        let request = json!({
+            "messages": [{"role": "user", "content": "word ".repeat(4000)}],
+            "max_tokens": 1000
+        });
+
+        let planned = estimate(&route, &[], &request);
+        assert_eq!(planned.target.model, route.model);
+        assert_eq!(planned.max_completion_tokens, Some(1000));
+        let prompt_cost = planned.prompt_tokens as f64 * 2.0 / 1_000_000.0;
+        assert!((planned.prompt_cost_usd.unwrap() - prompt_cost).abs() < 1e-9);
+        assert!((planned.max_cost_usd.unwrap() - prompt_cost - 0.01).abs() < 1e-9);
+
+        let unlimited = estimate(&route, &[], &json!({"messages": []}));
+        assert_eq!(
+            (unlimited.max_completion_tokens, unlimited.max_cost_usd),
+            (None, None)
+        );
+    }
+}
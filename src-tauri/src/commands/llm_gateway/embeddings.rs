@@ -0,0 +1,256 @@
+//! Embeddings - Translation of OpenAI embedding requests to provider APIs
+//!
+//! Clients send OpenAI `/v1/embeddings` requests. OpenAI-compatible providers (OpenAI,
+//! Qwen's compatible mode, custom backends) receive them unchanged; Gemini and Ollama are
+//! called through their native embedding APIs and their responses are turned back into
+//! the OpenAI shape, including `base64` encoding when the client asks for it.
+
+use base64::Engine;
+use serde_json::{json, Value};
+
+use super::adapter::AdapterSpec;
+use super::usage::estimate_text_tokens;
+use super::{LLMProvider, ProviderConfig};
+
+/// Upstream API an embedding request is sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddingApi {
+    /// `POST {base}/embeddings`
+    OpenAI,
+    /// `POST {base}/models/{model}:batchEmbedContents`
+    Gemini,
+    /// `POST {host}/api/embed`
+    Ollama,
+}
+
+impl EmbeddingApi {
+    /// API to use for a configured provider
+    pub fn for_provider(provider: &ProviderConfig) -> Self {
+        match provider.provider {
+            LLMProvider::Gemini => Self::Gemini,
+            LLMProvider::Ollama => Self::Ollama,
+            _ => Self::OpenAI,
+        }
+    }
+
+    /// Full endpoint URL for a model
+    pub fn url(&self, base_url: &str, model: &str) -> String {
+        let base_url = base_url.trim_end_matches('/');
+        match self {
+            Self::OpenAI => format!("{}/embeddings", base_url),
+            Self::Gemini => format!("{}/models/{}:batchEmbedContents", base_url, model),
+            // The configured URL points at Ollama's OpenAI-compatible `/v1` surface
+            Self::Ollama => format!("{}/api/embed", base_url.trim_end_matches("/v1")),
+        }
+    }
+
+    /// Attach the provider's credentials to an upstream request
+    pub fn authorize(
+        &self,
+        request: reqwest::RequestBuilder,
+        provider: &ProviderConfig,
+        model: &str,
+    ) -> reqwest::RequestBuilder {
+        let api_key = provider.api_key.as_deref().filter(|k| !k.is_empty());
+        match (self, api_key) {
+            (Self::Gemini, Some(key)) => request.header("x-goog-api-key", key),
+            (Self::Gemini, None) => request,
+            _ => AdapterSpec::resolve(provider).authorize(request, api_key, model),
+        }
+    }
+
+    /// Upstream request body for an OpenAI embedding request
+    pub fn map_request(&self, request: &Value, model: &str) -> Result<Value, String> {
+        let dimensions = request.get("dimensions").and_then(|d| d.as_u64());
+        match self {
+            Self::OpenAI => {
+                let mut body = request.clone();
+                body["model"] = Value::String(model.to_string());
+                Ok(body)
+            }
+            Self::Gemini => {
+                let requests: Vec<Value> = input_texts(request)?
+                    .into_iter()
+                    .map(|text| {
+                        let mut item = json!({
+                            "model": format!("models/{}", model),
+                            "content": {"parts": [{"text": text}]},
+                        });
+                        if let Some(dimensions) = dimensions {
+                            item["outputDimensionality"] = json!(dimensions);
+                        }
+                        item
+                    })
+                    .collect();
+                Ok(json!({ "requests": requests }))
+            }
+            Self::Ollama => {
+                let mut body = json!({"model": model, "input": input_texts(request)?});
+                if let Some(dimensions) = dimensions {
+                    body["dimensions"] = json!(dimensions);
+                }
+                Ok(body)
+            }
+        }
+    }
+
+    /// Input tokens counted by the provider, if it reports them
+    pub fn reported_tokens(&self, body: &Value) -> Option<u64> {
+        match self {
+            Self::OpenAI => body.pointer("/usage/prompt_tokens"),
+            Self::Gemini => None,
+            Self::Ollama => body.get("prompt_eval_count"),
+        }
+        .and_then(|count| count.as_u64())
+    }
+
+    /// OpenAI-shaped response for an upstream response, reporting `prompt_tokens` as usage
+    pub fn map_response(
+        &self,
+        body: Value,
+        model: &str,
+        base64: bool,
+        prompt_tokens: u64,
+    ) -> Result<Value, String> {
+        let vectors: Vec<&Value> = match self {
+            Self::OpenAI => return Ok(body),
+            Self::Gemini => body
+                .get("embeddings")
+                .and_then(|e| e.as_array())
+                .ok_or("Gemini response has no embeddings")?
+                .iter()
+                .filter_map(|e| e.get("values"))
+                .collect(),
+            Self::Ollama => body
+                .get("embeddings")
+                .and_then(|e| e.as_array())
+                .ok_or("Ollama response has no embeddings")?
+                .iter()
+                .collect(),
+        };
+
+        let data = vectors
+            .into_iter()
+            .enumerate()
+            .map(|(index, vector)| {
+                let embedding = if base64 {
+                    Value::String(encode_base64(vector))
+                } else {
+                    vector.clone()
+                };
+                json!({"object": "embedding", "index": index, "embedding": embedding})
+            })
+            .collect::<Vec<_>>();
+        Ok(json!({
+            "object": "list",
+            "data": data,
+            "model": model,
+            "usage": {"prompt_tokens": prompt_tokens, "total_tokens": prompt_tokens},
+        }))
+    }
+}
+
+/// Texts to embed; token ID inputs are only understood by OpenAI-compatible providers
+fn input_texts(request: &Value) -> Result<Vec<String>, String> {
+    let unsupported = || "Token ID inputs are not supported by this provider".to_string();
+    match request.get("input") {
+        Some(Value::String(text)) => Ok(vec![text.clone()]),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string).ok_or_else(unsupported))
+            .collect(),
+        _ => Err("'input' must be a string or an array of strings".to_string()),
+    }
+}
+
+/// Whether the client asked for base64-encoded vectors
+pub fn wants_base64(request: &Value) -> bool {
+    request.get("encoding_format").and_then(|f| f.as_str()) == Some("base64")
+}
+
+/// Rough token count of the texts in an embedding request
+pub fn estimate_input_tokens(request: &Value) -> u64 {
+    match request.get("input") {
+        Some(Value::String(text)) => estimate_text_tokens(text),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(text) => estimate_text_tokens(text),
+                Value::Array(ids) => ids.len() as u64,
+                _ => 0,
+            })
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// Vector as little-endian `f32`s in base64, as OpenAI returns with `encoding_format: base64`
+fn encode_base64(vector: &Value) -> String {
+    let bytes: Vec<u8> = vector
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_f64())
+        .flat_map(|v| (v as f32).to_le_bytes())
+        .collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gemini_and_ollama_translation() {
+        let request = json!({
+            "model": "text-embedding-004",
+            "input": ["hello", "world"],
+            "dimensions": 2,
+        });
+
+        let gemini = EmbeddingApi::Gemini;
+        assert_eq!(
+            gemini.url(
+                "https://generativelanguage.googleapis.com/v1beta",
+                "text-embedding-004"
+            ),
+            "https://generativelanguage.googleapis.com/v1beta/models/text-embedding-004:batchEmbedContents"
+        );
+        let body = gemini.map_request(&request, "text-embedding-004").unwrap();
+        assert_eq!(body["requests"][1]["content"]["parts"][0]["text"], "world");
+        assert_eq!(body["requests"][0]["outputDimensionality"], 2);
+        let response = gemini
+            .map_response(
+                json!({"embeddings": [{"values": [0.5, 1.0]}, {"values": [0.0, -1.0]}]}),
+                "text-embedding-004",
+                false,
+                2,
+            )
+            .unwrap();
+        assert_eq!(response["data"][1]["index"], 1);
+        assert_eq!(response["data"][1]["embedding"], json!([0.0, -1.0]));
+        assert_eq!(response["usage"]["prompt_tokens"], 2);
+
+        let ollama = EmbeddingApi::Ollama;
+        assert_eq!(
+            ollama.url("http://localhost:11434/v1", "nomic-embed-text"),
+            "http://localhost:11434/api/embed"
+        );
+        let upstream = json!({"embeddings": [[1.0, 2.0]], "prompt_eval_count": 3});
+        assert_eq!(gemini.reported_tokens(&upstream), None);
+        assert_eq!(ollama.reported_tokens(&upstream), Some(3));
+        let response = ollama
+            .map_response(upstream, "nomic-embed-text", true, 3)
+            .unwrap();
+        let encoded = response["data"][0]["embedding"].as_str().unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        assert_eq!(bytes[..4], 1.0f32.to_le_bytes());
+        assert_eq!(bytes[4..], 2.0f32.to_le_bytes());
+
+        assert!(ollama
+            .map_request(&json!({"input": [[1, 2, 3]]}), "nomic-embed-text")
+            .is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! Error Responses - Error bodies in the schema of the API a client is speaking
+//!
+//! Handlers fail with a status and at most a plain message, and upstream errors arrive in
+//! whatever schema the provider uses. The [`provider_errors`] middleware rewrites every
+//! error response into the shape the endpoint's SDKs parse (Anthropic's
+//! `{"type":"error","error":{...}}`, OpenAI's `{"error":{...}}`, Gemini's and Ollama's),
+//! so clients show the actual reason instead of a bare status. Streams that fail partway
+//! end with an error event in the same schema rather than just stopping.
+
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+
+use super::scrub::scrub;
+use super::sse::EventBoundary;
+
+/// Largest error body read for rewriting; longer bodies are replaced by the status reason
+const MAX_ERROR_BYTES: usize = 256 * 1024;
+
+/// Anthropic's status for an overloaded API, which other SDKs don't know
+const OVERLOADED: u16 = 529;
+
+/// Error schema of an endpoint family
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorDialect {
+    Anthropic,
+    OpenAI,
+    Gemini,
+    Ollama,
+}
+
+impl ErrorDialect {
+    /// Dialect clients of a gateway path expect
+    pub fn for_path(path: &str) -> Self {
+        if path.starts_with("/v1/messages") {
+            Self::Anthropic
+        } else if path.starts_with("/v1beta/") {
+            Self::Gemini
+        } else if path.starts_with("/api/") {
+            Self::Ollama
+        } else {
+            Self::OpenAI
+        }
+    }
+
+    /// Whether `body` already follows this dialect's schema
+    fn is_native(&self, body: &Value) -> bool {
+        match self {
+            Self::Anthropic => body["type"] == "error" && body["error"]["message"].is_string(),
+            Self::OpenAI => body["error"]["message"].is_string() && body.get("type").is_none(),
+            Self::Gemini => {
+                body["error"]["message"].is_string() && body["error"]["code"].is_number()
+            }
+            Self::Ollama => body["error"].is_string(),
+        }
+    }
+}
+
+/// Upstream error details worth keeping across schemas
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorDetail {
+    pub message: String,
+    /// Machine-readable code, e.g. `context_length_exceeded`
+    pub code: Option<String>,
+}
+
+/// Pull the message and code out of an error body in any provider's schema
+pub fn parse_error(text: &str) -> ErrorDetail {
+    let Ok(body) = serde_json::from_str::<Value>(text) else {
+        return ErrorDetail {
+            message: text.trim().to_string(),
+            code: None,
+        };
+    };
+    // Some providers wrap the error in a one-element list
+    let body = body.as_array().and_then(|a| a.first()).unwrap_or(&body);
+    let error = body.get("error").unwrap_or(body);
+    let message = error
+        .get("message")
+        .or_else(|| body.get("detail"))
+        .or_else(|| body.get("message"))
+        .and_then(|m| m.as_str())
+        .or_else(|| error.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| text.trim().to_string());
+    let code = ["code", "type", "status"].iter().find_map(|field| {
+        error
+            .get(*field)
+            .and_then(|c| c.as_str())
+            .filter(|c| *c != "error")
+            .map(str::to_string)
+    });
+    ErrorDetail { message, code }
+}
+
+/// Status to report to clients of `dialect`
+pub fn client_status(dialect: ErrorDialect, status: StatusCode) -> StatusCode {
+    if status.as_u16() == OVERLOADED && dialect != ErrorDialect::Anthropic {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        status
+    }
+}
+
+fn anthropic_type(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 | 422 => "invalid_request_error",
+        401 => "authentication_error",
+        402 => "billing_error",
+        403 => "permission_error",
+        404 => "not_found_error",
+        413 => "request_too_large",
+        429 => "rate_limit_error",
+        504 => "timeout_error",
+        503 | OVERLOADED => "overloaded_error",
+        _ => "api_error",
+    }
+}
+
+fn openai_type(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        401 => "authentication_error",
+        403 => "permission_error",
+        404 => "not_found_error",
+        429 => "rate_limit_error",
+        s if s >= 500 => "server_error",
+        _ => "invalid_request_error",
+    }
+}
+
+fn gemini_status(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 | 413 | 422 => "INVALID_ARGUMENT",
+        401 => "UNAUTHENTICATED",
+        403 => "PERMISSION_DENIED",
+        404 => "NOT_FOUND",
+        429 => "RESOURCE_EXHAUSTED",
+        503 | OVERLOADED => "UNAVAILABLE",
+        504 => "DEADLINE_EXCEEDED",
+        _ => "INTERNAL",
+    }
+}
+
+/// Error body for clients of `dialect`
+pub fn error_body(dialect: ErrorDialect, status: StatusCode, detail: &ErrorDetail) -> Value {
+    let message = if detail.message.is_empty() {
+        status.canonical_reason().unwrap_or("Error").to_string()
+    } else {
+        detail.message.clone()
+    };
+    match dialect {
+        ErrorDialect::Anthropic => json!({
+            "type": "error",
+            "error": { "type": anthropic_type(status), "message": message },
+        }),
+        ErrorDialect::OpenAI => json!({
+            "error": {
+                "message": message,
+                "type": openai_type(status),
+                "param": null,
+                "code": detail.code,
+            },
+        }),
+        ErrorDialect::Gemini => json!({
+            "error": {
+                "code": status.as_u16(),
+                "message": message,
+                "status": gemini_status(status),
+            },
+        }),
+        ErrorDialect::Ollama => json!({ "error": message }),
+    }
+}
+
+/// SSE event telling a client its stream failed partway, sent between events
+pub fn stream_error_event(dialect: ErrorDialect, message: &str) -> String {
+    let detail = ErrorDetail {
+        message: message.to_string(),
+        code: None,
+    };
+    let body = error_body(dialect, StatusCode::BAD_GATEWAY, &detail);
+    match dialect {
+        ErrorDialect::Anthropic => format!("event: error\ndata: {}\n\n", body),
+        _ => format!("data: {}\n\n", body),
+    }
+}
+
+/// End an SSE stream with an error event when its upstream fails, instead of cutting the
+/// connection and leaving the client with a silently truncated response. Only complete
+/// events are passed on, so the event a failure interrupts is dropped rather than
+/// dispatched with half its data.
+fn report_stream_errors<S, E>(
+    stream: S,
+    dialect: ErrorDialect,
+) -> impl futures::Stream<Item = Result<Bytes, E>>
+where
+    S: futures::Stream<Item = Result<Bytes, E>> + Unpin,
+    E: std::fmt::Display,
+{
+    use futures::StreamExt;
+    // The start of the event being received, held back until it ends
+    let state = (stream, EventBoundary::default(), Vec::new());
+    futures::stream::unfold(Some(state), move |state| async move {
+        let (mut stream, mut boundary, mut partial) = state?;
+        loop {
+            match stream.next().await {
+                Some(Ok(chunk)) => {
+                    let Some(end) = boundary.push(&chunk) else {
+                        partial.extend_from_slice(&chunk);
+                        continue;
+                    };
+                    let events = if partial.is_empty() {
+                        chunk.slice(..end)
+                    } else {
+                        partial.extend_from_slice(&chunk[..end]);
+                        Bytes::from(std::mem::take(&mut partial))
+                    };
+                    partial.extend_from_slice(&chunk[end..]);
+                    return Some((Ok(events), Some((stream, boundary, partial))));
+                }
+                Some(Err(e)) => {
+                    let message = format!("Upstream stream failed: {}", scrub(&e.to_string()));
+                    log::warn!("{}", message);
+                    let event = stream_error_event(dialect, &message);
+                    return Some((Ok(Bytes::from(event)), None));
+                }
+                None if partial.is_empty() => return None,
+                // An upstream that closes cleanly mid-event still gets its last bytes out
+                None => return Some((Ok(Bytes::from(partial)), None)),
+            }
+        }
+    })
+}
+
+/// Rewrite error responses into the schema of the endpoint the client called
+pub async fn provider_errors(request: Request, next: Next) -> Response {
+    let dialect = ErrorDialect::for_path(request.uri().path());
+    let response = next.run(request).await;
+    let is_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/event-stream"));
+    if is_stream {
+        return response
+            .map(|body| Body::from_stream(report_stream_errors(body.into_data_stream(), dialect)));
+    }
+    if !(response.status().is_client_error() || response.status().is_server_error()) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = axum::body::to_bytes(body, MAX_ERROR_BYTES)
+        .await
+        .unwrap_or_default();
+    let text = String::from_utf8_lossy(&bytes);
+    let status = parts.status;
+    parts.status = client_status(dialect, status);
+    if serde_json::from_str::<Value>(&text).is_ok_and(|body| dialect.is_native(&body)) {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    let body = error_body(dialect, status, &parse_error(&text));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_bodies() {
+        let openai = r#"{"error":{"message":"This model's maximum context length is 8192 tokens","type":"invalid_request_error","code":"context_length_exceeded"}}"#;
+        let detail = parse_error(openai);
+        assert_eq!(detail.code.as_deref(), Some("context_length_exceeded"));
+        let body = error_body(ErrorDialect::Anthropic, StatusCode::BAD_REQUEST, &detail);
+        assert_eq!(body["type"], "error");
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert!(body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("This model's maximum"));
+
+        let anthropic =
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let detail = parse_error(anthropic);
+        assert_eq!(detail.message, "Overloaded");
+        let status = StatusCode::from_u16(OVERLOADED).unwrap();
+        assert_eq!(
+            client_status(ErrorDialect::OpenAI, status),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        let body = error_body(ErrorDialect::OpenAI, status, &detail);
+        assert_eq!(body["error"]["type"], "server_error");
+        assert_eq!(body["error"]["code"], "overloaded_error");
+
+        let gemini =
+            r#"[{"error":{"code":429,"message":"Quota exceeded","status":"RESOURCE_EXHAUSTED"}}]"#;
+        assert_eq!(parse_error(gemini).message, "Quota exceeded");
+
+        let plain = parse_error("Unknown or disabled provider 'foo'");
+        let body = error_body(ErrorDialect::Ollama, StatusCode::BAD_REQUEST, &plain);
+        assert_eq!(body["error"], "Unknown or disabled provider 'foo'");
+        let empty = error_body(
+            ErrorDialect::Gemini,
+            StatusCode::BAD_GATEWAY,
+            &parse_error(""),
+        );
+        assert_eq!(empty["error"]["message"], "Bad Gateway");
+        assert_eq!(empty["error"]["status"], "INTERNAL");
+
+        let event = stream_error_event(ErrorDialect::Anthropic, "reset");
+        assert!(event.starts_with("event: error\ndata: {"));
+        assert!(stream_error_event(ErrorDialect::OpenAI, "reset").starts_with("data: {\"error\""));
+
+        assert_eq!(
+            ErrorDialect::for_path("/v1/messages"),
+            ErrorDialect::Anthropic
+        );
+        assert_eq!(
+            ErrorDialect::for_path("/v1/embeddings"),
+            ErrorDialect::OpenAI
+        );
+    }
+
+    #[tokio::test]
+    async fn test_report_stream_errors() {
+        use futures::StreamExt;
+        let reported = |chunks: Vec<&'static str>| {
+            let mut items: Vec<Result<Bytes, std::io::Error>> = chunks
+                .into_iter()
+                .map(|c| Ok(Bytes::from_static(c.as_bytes())))
+                .collect();
+            items.push(Err(std::io::Error::other("reset")));
+            report_stream_errors(futures::stream::iter(items), ErrorDialect::OpenAI)
+                .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let between = reported(vec!["data: {}\n\n"]).await.concat();
+        assert!(between.starts_with("data: {}\n\ndata: {\"error\""));
+
+        // The interrupted event is dropped; a single newline ends a line, not the event
+        let mid_event = reported(vec!["data: {}\n\ndata: {\"id\"", ": 1}\n"]).await;
+        assert_eq!(mid_event[0], "data: {}\n\n");
+        assert!(mid_event[1].starts_with("data: {\"error\""));
+        assert_eq!(mid_event.len(), 2);
+
+        // Events split across chunks are passed on once they end
+        let split = reported(vec![
+            "data: {\"id\"",
+            ": 1}\r\n\r",
+            "\ndata: {\"id\": 2}\n\n",
+        ])
+        .await;
+        assert_eq!(split[0], "data: {\"id\": 1}\r\n\r\ndata: {\"id\": 2}\n\n");
+        assert!(split[1].starts_with("data: {\"error\""));
+    }
+}
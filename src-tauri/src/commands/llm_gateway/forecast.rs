@@ -0,0 +1,183 @@
+//! Spend Forecast - Projected end-of-month spend from recent usage
+//!
+//! Each provider's month-to-date spend is extended by its average daily spend over
+//! the last few full days, for the rest of the month including what remains of today.
+//! Providers without recent history fall back to their month-to-date average. Days
+//! follow local time, like the request log's daily totals.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Full days averaged when unset
+pub const DEFAULT_WINDOW_DAYS: u32 = 7;
+
+/// Projected spend of one provider, or of all of them
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SpendForecast {
+    /// Provider, or `None` for the total
+    pub provider: Option<String>,
+    pub month_to_date_usd: f64,
+    /// Average daily spend the projection assumes
+    pub daily_average_usd: f64,
+    /// Spend expected by the end of the month
+    pub projected_usd: f64,
+}
+
+/// Projected end-of-month spend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForecastReport {
+    /// Month projected (YYYY-MM)
+    pub month: String,
+    pub days_in_month: u32,
+    /// Full days averaged
+    pub window_days: u32,
+    pub total: SpendForecast,
+    /// Highest projection first
+    pub by_provider: Vec<SpendForecast>,
+}
+
+fn days_in_month(day: NaiveDate) -> u32 {
+    let first = day.with_day(1).unwrap_or(day);
+    let next = first
+        .checked_add_months(chrono::Months::new(1))
+        .unwrap_or(first);
+    (next - first).num_days() as u32
+}
+
+/// Spend per provider between `start` and `end`, inclusive
+fn spend_by_provider(
+    conn: &Connection,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> rusqlite::Result<BTreeMap<String, f64>> {
+    let mut stmt = conn.prepare(
+        "SELECT provider, COALESCE(SUM(cost_usd), 0) FROM gateway_request_log
+         WHERE date(created_at, 'localtime') BETWEEN ?1 AND ?2
+         GROUP BY provider",
+    )?;
+    let rows = stmt.query_map(params![start.to_string(), end.to_string()], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+    })?;
+    rows.collect()
+}
+
+/// Project the spend of the month containing `now` (local time), averaging the
+/// `window_days` full days before it
+pub fn forecast(
+    conn: &Connection,
+    now: NaiveDateTime,
+    window_days: u32,
+) -> rusqlite::Result<ForecastReport> {
+    let today = now.date();
+    let month_start = today.with_day(1).unwrap_or(today);
+    let days_in_month = days_in_month(today);
+    let window_days = window_days.max(1);
+
+    let month_to_date = spend_by_provider(conn, month_start, today)?;
+    let yesterday = today - Duration::days(1);
+    let recent = spend_by_provider(
+        conn,
+        yesterday - Duration::days(window_days as i64 - 1),
+        yesterday,
+    )?;
+
+    // Rest of the month, counting the part of today still ahead
+    let today_left = 1.0 - now.num_seconds_from_midnight() as f64 / 86_400.0;
+    let remaining_days = (days_in_month - today.day()) as f64 + today_left;
+    let elapsed_days = today.day() as f64 - today_left;
+
+    let mut by_provider: Vec<SpendForecast> = month_to_date
+        .keys()
+        .chain(recent.keys())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .map(|provider| {
+            let spent = month_to_date.get(provider).copied().unwrap_or(0.0);
+            let daily_average_usd = match recent.get(provider) {
+                Some(recent) => recent / window_days as f64,
+                None => spent / elapsed_days.max(1.0),
+            };
+            SpendForecast {
+                provider: Some(provider.clone()),
+                month_to_date_usd: spent,
+                daily_average_usd,
+                projected_usd: spent + daily_average_usd * remaining_days,
+            }
+        })
+        .collect();
+    by_provider.sort_by(|a, b| b.projected_usd.total_cmp(&a.projected_usd));
+
+    let total = by_provider
+        .iter()
+        .fold(SpendForecast::default(), |mut total, p| {
+            total.month_to_date_usd += p.month_to_date_usd;
+            total.daily_average_usd += p.daily_average_usd;
+            total.projected_usd += p.projected_usd;
+            total
+        });
+    Ok(ForecastReport {
+        month: today.format("%Y-%m").to_string(),
+        days_in_month,
+        window_days,
+        total,
+        by_provider,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::llm_gateway::migrations;
+    use crate::commands::llm_gateway::usage::{self, RequestRecord};
+
+    fn record(conn: &Connection, provider: &str, local_time: &str, cost: f64) {
+        usage::insert_record(
+            conn,
+            &RequestRecord {
+                provider: provider.to_string(),
+                cost_usd: Some(cost),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE gateway_request_log SET created_at = datetime(?1, 'utc')
+             WHERE id = last_insert_rowid()",
+            [local_time],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_forecast() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::run(&conn).unwrap();
+        // Before the month and the averaging window
+        record(&conn, "openai", "2026-09-30 12:00:00", 100.0);
+        record(&conn, "openai", "2026-10-02 12:00:00", 14.0);
+        // Only today, so no recent history
+        record(&conn, "anthropic", "2026-10-08 09:00:00", 3.0);
+        record(&conn, "openai", "2026-10-08 09:00:00", 1.0);
+
+        let now = NaiveDate::from_ymd_opt(2026, 10, 8)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let report = forecast(&conn, now, 7).unwrap();
+        assert_eq!(report.month, "2026-10");
+        assert_eq!(report.days_in_month, 31);
+
+        let openai = &report.by_provider[0];
+        assert_eq!(openai.provider.as_deref(), Some("openai"));
+        assert_eq!(openai.month_to_date_usd, 15.0);
+        assert_eq!(openai.daily_average_usd, 2.0);
+        assert_eq!(openai.projected_usd, 15.0 + 2.0 * 23.5);
+
+        // Month-to-date average over the 7.5 days elapsed
+        let anthropic = &report.by_provider[1];
+        assert_eq!(anthropic.daily_average_usd, 3.0 / 7.5);
+        assert_eq!(report.total.month_to_date_usd, 18.0);
+    }
+}
@@ -0,0 +1,517 @@
+//! Gemini API - Native `generateContent` surface on top of chat completions
+//!
+//! Gemini CLI and the Gemini SDKs call `/v1beta/models/{model}:generateContent` and
+//! `:streamGenerateContent`. Their requests are translated to chat completions so any
+//! routed provider can answer them, and the results are translated back. Streams are
+//! always served as server-sent events, the format the SDKs ask for with `alt=sse`.
+
+use serde_json::{json, Map, Value};
+
+use super::translate::{parse_arguments, SseParser, StreamTranslator};
+
+/// Generation config fields with a direct chat completion equivalent
+const CONFIG_FIELDS: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("topP", "top_p"),
+    ("maxOutputTokens", "max_tokens"),
+    ("stopSequences", "stop"),
+    ("candidateCount", "n"),
+    ("seed", "seed"),
+    ("presencePenalty", "presence_penalty"),
+    ("frequencyPenalty", "frequency_penalty"),
+];
+
+/// Split a `{model}:{method}` path segment
+pub fn split_target(target: &str) -> Option<(&str, &str)> {
+    target
+        .rsplit_once(':')
+        .filter(|(model, _)| !model.is_empty())
+}
+
+/// Gemini's OpenAPI schema subset as JSON Schema, lowercasing the `STRING`-style types
+fn json_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| match (key.as_str(), value) {
+                    ("type", Value::String(t)) => (key.clone(), json!(t.to_lowercase())),
+                    // Property names are user data, not schema keywords
+                    ("properties", Value::Object(properties)) => (
+                        key.clone(),
+                        Value::Object(
+                            properties
+                                .iter()
+                                .map(|(name, property)| (name.clone(), json_schema(property)))
+                                .collect::<Map<_, _>>(),
+                        ),
+                    ),
+                    _ => (key.clone(), json_schema(value)),
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(json_schema).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Tool call ID for the `nth` call of a function, so calls and their responses pair up
+/// when the client doesn't send IDs
+fn call_id(part: &Value, name: &str, nth: usize) -> String {
+    match part.get("id").and_then(|i| i.as_str()) {
+        Some(id) => id.to_string(),
+        None => format!("{}_{}", name, nth),
+    }
+}
+
+/// Chat messages for the contents of a Gemini request
+fn contents_to_messages(contents: &[Value], messages: &mut Vec<Value>) {
+    let mut calls_seen: Vec<String> = Vec::new();
+    let mut responses_seen: Vec<String> = Vec::new();
+    let count = |seen: &mut Vec<String>, name: &str| {
+        seen.push(name.to_string());
+        seen.iter().filter(|n| *n == name).count() - 1
+    };
+
+    for content in contents {
+        let role = match content.get("role").and_then(|r| r.as_str()) {
+            Some("model") => "assistant",
+            _ => "user",
+        };
+        let mut parts = Vec::new();
+        let mut tool_calls = Vec::new();
+        for part in content
+            .get("parts")
+            .and_then(|p| p.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if part.get("thought").and_then(|t| t.as_bool()) == Some(true) {
+                continue;
+            }
+            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                parts.push(json!({"type": "text", "text": text}));
+            } else if let Some(data) = part.get("inlineData") {
+                let mime = data
+                    .get("mimeType")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("image/png");
+                let data = data
+                    .get("data")
+                    .and_then(|d| d.as_str())
+                    .unwrap_or_default();
+                parts.push(json!({
+                    "type": "image_url",
+                    "image_url": {"url": format!("data:{};base64,{}", mime, data)},
+                }));
+            } else if let Some(call) = part.get("functionCall") {
+                let name = call
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or_default();
+                let nth = count(&mut calls_seen, name);
+                tool_calls.push(json!({
+                    "id": call_id(call, name, nth),
+                    "type": "function",
+                    "function": {
+                        "name": name,
+                        "arguments": call.get("args").unwrap_or(&json!({})).to_string(),
+                    },
+                }));
+            } else if let Some(response) = part.get("functionResponse") {
+                let name = response
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or_default();
+                let nth = count(&mut responses_seen, name);
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": call_id(response, name, nth),
+                    "content": response.get("response").unwrap_or(&json!({})).to_string(),
+                }));
+            }
+        }
+
+        if !tool_calls.is_empty() {
+            let text: String = parts
+                .iter()
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect();
+            messages.push(json!({
+                "role": "assistant",
+                "content": if text.is_empty() { Value::Null } else { json!(text) },
+                "tool_calls": tool_calls,
+            }));
+        } else if !parts.is_empty() {
+            messages.push(json!({"role": role, "content": parts}));
+        }
+    }
+}
+
+/// Chat completion request equivalent to a Gemini `generateContent` request
+pub fn gemini_to_chat_request(request: &Value, model: &str, stream: bool) -> Value {
+    let mut messages = Vec::new();
+    let system: String = request
+        .pointer("/systemInstruction/parts")
+        .and_then(|p| p.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if !system.is_empty() {
+        messages.push(json!({"role": "system", "content": system}));
+    }
+    if let Some(contents) = request.get("contents").and_then(|c| c.as_array()) {
+        contents_to_messages(contents, &mut messages);
+    }
+
+    let mut chat = json!({"model": model, "messages": messages});
+    if stream {
+        chat["stream"] = json!(true);
+        chat["stream_options"] = json!({"include_usage": true});
+    }
+
+    let config = request.get("generationConfig");
+    for (gemini, openai) in CONFIG_FIELDS {
+        if let Some(value) = config.and_then(|c| c.get(*gemini)) {
+            chat[*openai] = value.clone();
+        }
+    }
+    let schema = config.and_then(|c| {
+        c.get("responseJsonSchema")
+            .cloned()
+            .or_else(|| c.get("responseSchema").map(json_schema))
+    });
+    match (schema, config.and_then(|c| c.get("responseMimeType"))) {
+        (Some(schema), _) => {
+            chat["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {"name": "response", "schema": schema},
+            })
+        }
+        (None, Some(mime)) if mime == "application/json" => {
+            chat["response_format"] = json!({"type": "json_object"})
+        }
+        _ => {}
+    }
+
+    let tools: Vec<Value> = request
+        .get("tools")
+        .and_then(|t| t.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|tool| tool.get("functionDeclarations").and_then(|f| f.as_array()))
+        .flatten()
+        .map(|declaration| {
+            let parameters = declaration
+                .get("parametersJsonSchema")
+                .cloned()
+                .or_else(|| declaration.get("parameters").map(json_schema))
+                .unwrap_or(json!({"type": "object", "properties": {}}));
+            let mut function = json!({
+                "name": declaration.get("name").cloned().unwrap_or_default(),
+                "parameters": parameters,
+            });
+            if let Some(description) = declaration.get("description") {
+                function["description"] = description.clone();
+            }
+            json!({"type": "function", "function": function})
+        })
+        .collect();
+    if !tools.is_empty() {
+        chat["tools"] = Value::Array(tools);
+        let calling = request.pointer("/toolConfig/functionCallingConfig");
+        let allowed = calling
+            .and_then(|c| c.get("allowedFunctionNames"))
+            .and_then(|a| a.as_array())
+            .filter(|a| a.len() == 1);
+        match (
+            calling.and_then(|c| c.get("mode")).and_then(|m| m.as_str()),
+            allowed,
+        ) {
+            (Some("ANY"), Some(allowed)) => {
+                chat["tool_choice"] = json!({"type": "function", "function": {"name": allowed[0]}})
+            }
+            (Some("ANY"), None) => chat["tool_choice"] = json!("required"),
+            (Some("NONE"), _) => chat["tool_choice"] = json!("none"),
+            _ => {}
+        }
+    }
+    chat
+}
+
+/// Gemini finish reason for a chat completion finish reason
+fn finish_reason(reason: &str) -> &'static str {
+    match reason {
+        "length" => "MAX_TOKENS",
+        "content_filter" => "SAFETY",
+        _ => "STOP",
+    }
+}
+
+/// Gemini usage metadata for chat completion usage
+fn usage_metadata(usage: &Value) -> Value {
+    let count = |pointer: &str| usage.pointer(pointer).and_then(|v| v.as_u64()).unwrap_or(0);
+    let prompt = count("/prompt_tokens");
+    let candidates = count("/completion_tokens");
+    let mut metadata = json!({
+        "promptTokenCount": prompt,
+        "candidatesTokenCount": candidates,
+        "totalTokenCount": prompt + candidates,
+    });
+    let cached = count("/prompt_tokens_details/cached_tokens");
+    if cached > 0 {
+        metadata["cachedContentTokenCount"] = json!(cached);
+    }
+    metadata
+}
+
+fn function_call_part(call: &Value) -> Value {
+    let mut function_call = json!({
+        "name": call.pointer("/function/name").cloned().unwrap_or_default(),
+        "args": parse_arguments(call.pointer("/function/arguments")),
+    });
+    if let Some(id) = call.get("id").filter(|i| i.is_string()) {
+        function_call["id"] = id.clone();
+    }
+    json!({ "functionCall": function_call })
+}
+
+/// Gemini `generateContent` response for a chat completion
+pub fn chat_to_gemini_response(body: &Value, model: &str) -> Value {
+    let candidates: Vec<Value> = body
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .map(|choice| {
+            let mut parts = Vec::new();
+            if let Some(text) = choice
+                .pointer("/message/content")
+                .and_then(|c| c.as_str())
+                .filter(|t| !t.is_empty())
+            {
+                parts.push(json!({ "text": text }));
+            }
+            for call in choice
+                .pointer("/message/tool_calls")
+                .and_then(|c| c.as_array())
+                .into_iter()
+                .flatten()
+            {
+                parts.push(function_call_part(call));
+            }
+            json!({
+                "content": {"role": "model", "parts": parts},
+                "finishReason": finish_reason(
+                    choice.get("finish_reason").and_then(|r| r.as_str()).unwrap_or("stop"),
+                ),
+                "index": choice.get("index").cloned().unwrap_or(json!(0)),
+            })
+        })
+        .collect();
+
+    let mut response = json!({"candidates": candidates, "modelVersion": model});
+    if let Some(usage) = body.get("usage").filter(|u| u.is_object()) {
+        response["usageMetadata"] = usage_metadata(usage);
+    }
+    response
+}
+
+/// Converts chat completion chunks into Gemini `streamGenerateContent` chunks
+///
+/// Text is forwarded as it arrives. Function calls are only complete once their
+/// arguments have streamed in, so they go out with the final chunk.
+#[derive(Debug, Default)]
+pub struct GeminiStreamTranslator {
+    parser: SseParser,
+    model: String,
+    /// Streamed calls keyed by their index in the upstream `tool_calls` array
+    calls: Vec<(u64, Value)>,
+    finish_reason: Option<String>,
+    usage: Option<Value>,
+    finished: bool,
+}
+
+impl GeminiStreamTranslator {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            ..Self::default()
+        }
+    }
+
+    fn chunk(&self, parts: Vec<Value>) -> String {
+        let chunk = json!({
+            "candidates": [{"content": {"role": "model", "parts": parts}, "index": 0}],
+            "modelVersion": self.model,
+        });
+        format!("data: {}\n\n", chunk)
+    }
+
+    fn push_chunk(&mut self, chunk: &Value) -> String {
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            self.usage = Some(usage.clone());
+        }
+        let Some(choice) = chunk.pointer("/choices/0") else {
+            return String::new();
+        };
+        for call in choice
+            .pointer("/delta/tool_calls")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let index = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+            let arguments = call
+                .pointer("/function/arguments")
+                .and_then(|a| a.as_str())
+                .unwrap_or_default();
+            match self.calls.iter_mut().find(|(i, _)| *i == index) {
+                Some((_, streamed)) => {
+                    let joined = format!(
+                        "{}{}",
+                        streamed["function"]["arguments"]
+                            .as_str()
+                            .unwrap_or_default(),
+                        arguments
+                    );
+                    streamed["function"]["arguments"] = json!(joined);
+                }
+                None => self.calls.push((index, call.clone())),
+            }
+        }
+        if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+        }
+        match choice
+            .pointer("/delta/content")
+            .and_then(|c| c.as_str())
+            .filter(|t| !t.is_empty())
+        {
+            Some(text) => self.chunk(vec![json!({ "text": text })]),
+            None => String::new(),
+        }
+    }
+
+    /// Final chunk with the function calls, finish reason and usage
+    fn complete(&mut self) -> String {
+        if self.finished {
+            return String::new();
+        }
+        self.finished = true;
+        let parts: Vec<Value> = self
+            .calls
+            .iter()
+            .map(|(_, call)| function_call_part(call))
+            .collect();
+        let mut chunk = json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": parts},
+                "finishReason": finish_reason(self.finish_reason.as_deref().unwrap_or("stop")),
+                "index": 0,
+            }],
+            "modelVersion": self.model,
+        });
+        if let Some(usage) = &self.usage {
+            chunk["usageMetadata"] = usage_metadata(usage);
+        }
+        format!("data: {}\n\n", chunk)
+    }
+}
+
+impl StreamTranslator for GeminiStreamTranslator {
+    fn push(&mut self, chunk: &[u8]) -> String {
+        let mut out = String::new();
+        for payload in self.parser.push(chunk) {
+            if payload == "[DONE]" {
+                out.push_str(&self.complete());
+                continue;
+            }
+            if let Ok(chunk) = serde_json::from_str::<Value>(&payload) {
+                out.push_str(&self.push_chunk(&chunk));
+            }
+        }
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        self.complete()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_translation() {
+        let request = json!({
+            "systemInstruction": {"parts": [{"text": "Be brief."}]},
+            "contents": [
+                {"role": "user", "parts": [{"text": "Weather in Paris?"}]},
+                {"role": "model", "parts": [{"functionCall": {"name": "weather", "args": {"city": "Paris"}}}]},
+                {"role": "user", "parts": [{"functionResponse": {"name": "weather", "response": {"temp": 21}}}]},
+            ],
+            "tools": [{"functionDeclarations": [{
+                "name": "weather",
+                "parameters": {"type": "OBJECT", "properties": {"type": {"type": "STRING"}}},
+            }]}],
+            "toolConfig": {"functionCallingConfig": {"mode": "ANY"}},
+            "generationConfig": {"maxOutputTokens": 64, "responseMimeType": "application/json"},
+        });
+        let chat = gemini_to_chat_request(&request, "gemini-2.5-pro", true);
+        let messages = chat["messages"].as_array().unwrap();
+        assert_eq!(messages[0]["content"], "Be brief.");
+        assert_eq!(messages[2]["tool_calls"][0]["id"], "weather_0");
+        assert_eq!(messages[3]["tool_call_id"], "weather_0");
+        let parameters = &chat["tools"][0]["function"]["parameters"];
+        assert_eq!(parameters["type"], "object");
+        assert_eq!(parameters["properties"]["type"]["type"], "string");
+        assert_eq!(chat["tool_choice"], "required");
+        assert_eq!(chat["max_tokens"], 64);
+        assert_eq!(chat["response_format"]["type"], "json_object");
+        assert_eq!(chat["stream_options"]["include_usage"], true);
+        assert_eq!(
+            split_target("gemini-2.5-pro:streamGenerateContent"),
+            Some(("gemini-2.5-pro", "streamGenerateContent"))
+        );
+    }
+
+    #[test]
+    fn test_response_translation() {
+        let response = chat_to_gemini_response(
+            &json!({
+                "choices": [{
+                    "message": {"content": "Sunny.", "tool_calls": [
+                        {"id": "call_1", "function": {"name": "weather", "arguments": "{\"city\":\"Paris\"}"}},
+                    ]},
+                    "finish_reason": "length",
+                }],
+                "usage": {"prompt_tokens": 10, "completion_tokens": 5},
+            }),
+            "gemini-2.5-pro",
+        );
+        let candidate = &response["candidates"][0];
+        assert_eq!(candidate["content"]["parts"][0]["text"], "Sunny.");
+        assert_eq!(
+            candidate["content"]["parts"][1]["functionCall"]["args"]["city"],
+            "Paris"
+        );
+        assert_eq!(candidate["finishReason"], "MAX_TOKENS");
+        assert_eq!(response["usageMetadata"]["totalTokenCount"], 15);
+
+        let mut translator = GeminiStreamTranslator::new("gemini-2.5-pro");
+        let out = translator.push(
+            b"data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n\
+              data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"name\":\"f\",\"arguments\":\"{\\\"a\\\"\"}}]}}]}\n\n\
+              data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\":1}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n\
+              data: [DONE]\n\n",
+        );
+        assert!(out.contains("\"text\":\"Hi\""));
+        assert!(out.contains("\"args\":{\"a\":1}"));
+        assert!(out.contains("\"finishReason\":\"STOP\""));
+        assert!(translator.finish().is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+//! Guardrails - Keyword and topic blocklists that keep matching prompts off providers
+//!
+//! Each rule lists keywords (matched case-insensitively as whole words) and regular
+//! expressions for a topic. A request routed to one of the rule's providers whose
+//! system prompt or messages match is either rejected with an explanation or rerouted to
+//! a local Ollama model so its content never leaves the machine.
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::router::{default_model, RouteTarget};
+use super::{GatewaySettings, GuardrailRule, LLMProvider};
+
+/// What happens to a request matching a guardrail
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailAction {
+    /// Fail the request with an explanatory error
+    #[default]
+    Reject,
+    /// Serve the request with a local Ollama model instead
+    Local,
+}
+
+/// Keys whose values are binary data or identifiers rather than prompt text
+const NON_TEXT_KEYS: &[&str] = &[
+    "data",
+    "url",
+    "image_url",
+    "id",
+    "tool_use_id",
+    "type",
+    "role",
+];
+
+fn collect_text(value: &Value, out: &mut String) {
+    match value {
+        Value::String(text) => {
+            out.push_str(text);
+            out.push('\n');
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_text(item, out)),
+        Value::Object(obj) => {
+            for (key, child) in obj {
+                if !NON_TEXT_KEYS.contains(&key.as_str()) {
+                    collect_text(child, out);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Prompt text of an Anthropic or OpenAI shaped request
+pub fn request_text(request: &Value) -> String {
+    let mut text = String::new();
+    for field in ["system", "messages"] {
+        if let Some(value) = request.get(field) {
+            collect_text(value, &mut text);
+        }
+    }
+    text
+}
+
+/// Compile a rule's keywords and patterns into one case-insensitive expression
+fn rule_regex(rule: &GuardrailRule) -> Option<Regex> {
+    let alternatives: Vec<String> = rule
+        .keywords
+        .iter()
+        .filter(|k| !k.trim().is_empty())
+        .map(|k| format!(r"\b{}\b", regex::escape(k.trim())))
+        .chain(rule.patterns.iter().map(|p| format!("(?:{})", p)))
+        .collect();
+    if alternatives.is_empty() {
+        return None;
+    }
+    match RegexBuilder::new(&alternatives.join("|"))
+        .case_insensitive(true)
+        .build()
+    {
+        Ok(regex) => Some(regex),
+        Err(e) => {
+            log::warn!(
+                "Skipping guardrail '{}' with invalid pattern: {}",
+                rule.name,
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Local Ollama route for a request moved off its provider
+fn local_route(settings: &GatewaySettings, rule: &GuardrailRule) -> Option<RouteTarget> {
+    let provider = settings
+        .providers
+        .iter()
+        .find(|p| p.provider == LLMProvider::Ollama)?;
+    let model = match &rule.local_model {
+        Some(model) => model.clone(),
+        None => default_model(provider)?.to_string(),
+    };
+    Some(RouteTarget {
+        provider: provider.clone(),
+        model,
+        ab: None,
+    })
+}
+
+/// Check a request against the guardrails of its route's provider. Returns the local
+/// route to use instead, if a matching rule reroutes, or an error if one rejects it.
+pub fn enforce(
+    settings: &GatewaySettings,
+    request: &Value,
+    route: &RouteTarget,
+) -> Result<Option<RouteTarget>, String> {
+    let rules: Vec<&GuardrailRule> = settings
+        .guardrails
+        .iter()
+        .filter(|rule| rule.enabled)
+        .filter(|rule| {
+            rule.providers.is_empty() || rule.providers.contains(&route.provider.provider)
+        })
+        // Rerouting a local request again would be a no-op
+        .filter(|rule| {
+            rule.action != GuardrailAction::Local || route.provider.provider != LLMProvider::Ollama
+        })
+        .collect();
+    if rules.is_empty() {
+        return Ok(None);
+    }
+
+    let text = request_text(request);
+    for rule in rules {
+        let Some(matched) = rule_regex(rule).and_then(|regex| regex.find(&text)) else {
+            continue;
+        };
+        log::info!(
+            "Request to {} matched guardrail '{}' on '{}'",
+            route.provider.provider,
+            rule.name,
+            matched.as_str()
+        );
+        let blocked = || {
+            format!(
+                "Request blocked by guardrail '{}': content matching '{}' may not be sent to {}",
+                rule.name,
+                matched.as_str(),
+                route.provider.provider
+            )
+        };
+        return match rule.action {
+            GuardrailAction::Reject => Err(blocked()),
+            GuardrailAction::Local => local_route(settings, rule)
+                .map(Some)
+                .ok_or_else(|| format!("{} (no local Ollama model is configured)", blocked())),
+        };
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(action: GuardrailAction) -> GuardrailRule {
+        GuardrailRule {
+            name: "internal".to_string(),
+            enabled: true,
+            providers: vec![LLMProvider::DeepSeek],
+            keywords: vec!["Project Falcon".to_string()],
+            patterns: vec![r"INTERNAL-\d+".to_string()],
+            action,
+            local_model: None,
+        }
+    }
+
+    fn route(settings: &GatewaySettings, provider: LLMProvider) -> RouteTarget {
+        let provider = settings
+            .providers
+            .iter()
+            .find(|p| p.provider == provider)
+            .unwrap()
+            .clone();
+        RouteTarget {
+            model: default_model(&provider).unwrap().to_string(),
+            provider,
+            ab: None,
+        }
+    }
+
+    #[test]
+    fn test_enforce() {
+        let mut settings = GatewaySettings {
+            guardrails: vec![rule(GuardrailAction::Reject)],
+            ..Default::default()
+        };
+        let request = json!({
+            "system": "You are a reviewer.",
+            "messages": [{"role": "user", "content": [{"type": "text", "text": "Review project falcon"}]}]
+        });
+
+        let deepseek = route(&settings, LLMProvider::DeepSeek);
+        let err = enforce(&settings, &request, &deepseek).unwrap_err();
+        assert!(err.contains("'internal'"));
+        let openai = route(&settings, LLMProvider::OpenAI);
+        assert!(matches!(enforce(&settings, &request, &openai), Ok(None)));
+        let unrelated = json!({"messages": [{"role": "user", "content": "Review falcons"}]});
+        assert!(matches!(
+            enforce(&settings, &unrelated, &deepseek),
+            Ok(None)
+        ));
+
+        settings.guardrails = vec![rule(GuardrailAction::Local)];
+        let local = enforce(&settings, &request, &deepseek).unwrap().unwrap();
+        assert_eq!(local.provider.provider, LLMProvider::Ollama);
+    }
+}
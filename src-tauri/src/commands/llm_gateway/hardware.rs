@@ -0,0 +1,304 @@
+//! Local Hardware - Resource usage of the machines serving local providers
+//!
+//! For Ollama providers and providers served from this machine (LM Studio, llama.cpp and
+//! the like on localhost), the gateway samples CPU load and GPU utilization and VRAM
+//! (through `nvidia-smi`, when present) of this machine, and the models an Ollama server
+//! has loaded. The latest sample is shown on the provider's status. A provider with a
+//! [`SaturationGuard`] is passed over for large prompts while its machine is saturated,
+//! as long as a fallback is available.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use super::ollama_server;
+use super::{GatewaySettings, GatewayStatus, LLMProvider, ProviderConfig, ProviderStatus};
+
+/// How often local providers are sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Latest resource usage of a local provider's machine
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HardwareUsage {
+    /// Load average over the last minute per CPU core; above 1.0 means CPUs are queued
+    pub cpu_load: Option<f64>,
+    /// Busiest GPU's utilization, 0.0-1.0
+    pub gpu_utilization: Option<f64>,
+    /// VRAM in use across GPUs
+    pub vram_used_mb: Option<u64>,
+    pub vram_total_mb: Option<u64>,
+    /// Models the Ollama server has loaded
+    #[serde(default)]
+    pub loaded_models: Vec<LoadedModel>,
+    /// When the sample was taken
+    pub sampled_at: String,
+}
+
+/// Model loaded on an Ollama server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LoadedModel {
+    pub name: String,
+    /// Memory the model takes in total, and the part of it in VRAM
+    pub size_mb: u64,
+    pub vram_mb: u64,
+}
+
+/// Thresholds past which a provider's machine counts as saturated for large prompts
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SaturationGuard {
+    /// Prompts at least this large (estimated tokens) avoid a saturated machine
+    #[serde(default = "default_min_prompt_tokens")]
+    pub min_prompt_tokens: u64,
+    /// Highest acceptable load average per core
+    #[serde(default)]
+    pub max_cpu_load: Option<f64>,
+    /// Highest acceptable GPU utilization, 0.0-1.0
+    #[serde(default)]
+    pub max_gpu_utilization: Option<f64>,
+    /// Highest acceptable share of VRAM in use, 0.0-1.0
+    #[serde(default)]
+    pub max_vram_used: Option<f64>,
+}
+
+fn default_min_prompt_tokens() -> u64 {
+    32000
+}
+
+impl HardwareUsage {
+    /// Threshold of `guard` the sample exceeds, if any
+    pub fn saturation(&self, guard: &SaturationGuard) -> Option<String> {
+        if let (Some(load), Some(max)) = (self.cpu_load, guard.max_cpu_load) {
+            if load > max {
+                return Some(format!("CPU load {:.2} above {:.2}", load, max));
+            }
+        }
+        if let (Some(utilization), Some(max)) = (self.gpu_utilization, guard.max_gpu_utilization) {
+            if utilization > max {
+                return Some(format!(
+                    "GPU utilization {:.0}% above {:.0}%",
+                    utilization * 100.0,
+                    max * 100.0
+                ));
+            }
+        }
+        if let (Some(used), Some(total), Some(max)) =
+            (self.vram_used_mb, self.vram_total_mb, guard.max_vram_used)
+        {
+            let share = used as f64 / total.max(1) as f64;
+            if share > max {
+                return Some(format!(
+                    "VRAM {:.0}% used, above {:.0}%",
+                    share * 100.0,
+                    max * 100.0
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// Whether a base URL points at this machine
+fn on_this_machine(base_url: &str) -> bool {
+    reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .is_some_and(|host| {
+            host == "localhost" || host.starts_with("127.") || host == "[::1]" || host == "0.0.0.0"
+        })
+}
+
+/// Whether a provider serves models locally, so its hardware is worth sampling
+pub fn is_local(provider: &ProviderConfig) -> bool {
+    provider.provider == LLMProvider::Ollama || on_this_machine(&provider.base_url)
+}
+
+/// One-minute load average per core, from `/proc/loadavg` or `sysctl vm.loadavg` output
+fn parse_load_average(output: &str, cores: usize) -> Option<f64> {
+    let load: f64 = output
+        .split_whitespace()
+        .find(|field| *field != "{")?
+        .parse()
+        .ok()?;
+    Some(load / cores.max(1) as f64)
+}
+
+/// GPU utilization and VRAM from `nvidia-smi` CSV output, one line per GPU
+fn parse_nvidia_smi(output: &str) -> Option<(f64, u64, u64)> {
+    let gpus: Vec<(f64, u64, u64)> = output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(',').map(str::trim);
+            Some((
+                fields.next()?.parse::<f64>().ok()? / 100.0,
+                fields.next()?.parse().ok()?,
+                fields.next()?.parse().ok()?,
+            ))
+        })
+        .collect();
+    if gpus.is_empty() {
+        return None;
+    }
+    Some((
+        gpus.iter().map(|g| g.0).fold(0.0, f64::max),
+        gpus.iter().map(|g| g.1).sum(),
+        gpus.iter().map(|g| g.2).sum(),
+    ))
+}
+
+/// Output of a command, if it ran and succeeded
+async fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// CPU and GPU usage of this machine
+async fn sample_this_machine(usage: &mut HardwareUsage) {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let load = match tokio::fs::read_to_string("/proc/loadavg").await {
+        Ok(loadavg) => Some(loadavg),
+        Err(_) => command_output("sysctl", &["-n", "vm.loadavg"]).await,
+    };
+    usage.cpu_load = load.and_then(|load| parse_load_average(&load, cores));
+
+    let gpus = command_output(
+        "nvidia-smi",
+        &[
+            "--query-gpu=utilization.gpu,memory.used,memory.total",
+            "--format=csv,noheader,nounits",
+        ],
+    )
+    .await;
+    if let Some((utilization, used, total)) = gpus.as_deref().and_then(parse_nvidia_smi) {
+        usage.gpu_utilization = Some(utilization);
+        usage.vram_used_mb = Some(used);
+        usage.vram_total_mb = Some(total);
+    }
+}
+
+/// Models an Ollama server has loaded, from its /api/ps endpoint
+async fn loaded_models(client: &reqwest::Client, provider: &ProviderConfig) -> Vec<LoadedModel> {
+    let url = ollama_server::api_url(&provider.base_url, "/api/ps");
+    let body = match client.get(url).send().await {
+        Ok(response) if response.status().is_success() => {
+            response.json::<serde_json::Value>().await.ok()
+        }
+        _ => None,
+    };
+    let megabytes = |model: &serde_json::Value, key: &str| {
+        model.get(key).and_then(|v| v.as_u64()).unwrap_or(0) / (1024 * 1024)
+    };
+    body.as_ref()
+        .and_then(|body| body.get("models")?.as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|model| {
+            Some(LoadedModel {
+                name: model.get("name")?.as_str()?.to_string(),
+                size_mb: megabytes(model, "size"),
+                vram_mb: megabytes(model, "size_vram"),
+            })
+        })
+        .collect()
+}
+
+/// Sample the hardware behind every enabled local provider and record it on the
+/// providers' status, for as long as the gateway runs
+pub async fn monitor(settings: Arc<RwLock<GatewaySettings>>, status: Arc<RwLock<GatewayStatus>>) {
+    let client = super::client::shared_client();
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let providers: Vec<ProviderConfig> = settings
+            .read()
+            .await
+            .providers
+            .iter()
+            .filter(|p| p.enabled && is_local(p))
+            .cloned()
+            .collect();
+        if providers.is_empty() {
+            continue;
+        }
+
+        // This machine is sampled once however many local providers it serves
+        let mut machine = HardwareUsage::default();
+        if providers.iter().any(|p| on_this_machine(&p.base_url)) {
+            sample_this_machine(&mut machine).await;
+        }
+        for provider in providers {
+            let mut usage = if on_this_machine(&provider.base_url) {
+                machine.clone()
+            } else {
+                HardwareUsage::default()
+            };
+            if provider.provider == LLMProvider::Ollama {
+                usage.loaded_models = loaded_models(&client, &provider).await;
+            }
+            usage.sampled_at = chrono::Utc::now().to_rfc3339();
+
+            let mut status = status.write().await;
+            let entry = status
+                .provider_status
+                .entry(provider.provider.to_string())
+                .or_insert(ProviderStatus {
+                    available: true,
+                    latency_ms: None,
+                    last_error: None,
+                    request_count: 0,
+                    error_count: 0,
+                    quota: None,
+                    hardware: None,
+                });
+            entry.hardware = Some(usage);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saturation() {
+        assert_eq!(
+            parse_load_average("6.00 4.10 3.50 2/1024 12345\n", 8),
+            Some(0.75)
+        );
+        assert_eq!(parse_load_average("{ 12.00 9.51 8.20 }\n", 8), Some(1.5));
+        let (utilization, used, total) =
+            parse_nvidia_smi("97, 22000, 24576\n12, 1000, 24576\n").unwrap();
+        assert_eq!((utilization, used, total), (0.97, 23000, 49152));
+        assert_eq!(parse_nvidia_smi(""), None);
+
+        let usage = HardwareUsage {
+            cpu_load: Some(0.5),
+            gpu_utilization: Some(utilization),
+            vram_used_mb: Some(used),
+            vram_total_mb: Some(total),
+            ..Default::default()
+        };
+        let mut guard = SaturationGuard {
+            min_prompt_tokens: 32000,
+            max_cpu_load: Some(1.0),
+            max_gpu_utilization: None,
+            max_vram_used: Some(0.9),
+        };
+        assert_eq!(usage.saturation(&guard), None);
+        guard.max_gpu_utilization = Some(0.9);
+        assert_eq!(
+            usage.saturation(&guard).as_deref(),
+            Some("GPU utilization 97% above 90%")
+        );
+
+        assert!(on_this_machine("http://127.0.0.1:1234/v1"));
+        assert!(!on_this_machine("https://api.openai.com/v1"));
+    }
+}
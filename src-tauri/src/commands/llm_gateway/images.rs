@@ -0,0 +1,208 @@
+//! Image Generation - Translation of OpenAI image generation requests to provider APIs
+//!
+//! Clients send OpenAI `/v1/images/generations` requests. OpenAI (DALL·E and gpt-image)
+//! and OpenAI-compatible backends receive them nearly unchanged; Zhipu CogView gets the
+//! subset of fields it accepts. Providers return images as URLs or as base64 whatever
+//! the client asked for, so responses are converted to the requested `response_format`.
+
+use base64::Engine;
+use serde_json::{json, Value};
+
+use super::adapter::AdapterSpec;
+use super::{LLMProvider, ProviderConfig};
+
+/// Upstream API an image request is sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageApi {
+    /// OpenAI and compatible backends
+    OpenAI,
+    /// Zhipu CogView, which only returns URLs and takes a single prompt and size
+    Zhipu,
+}
+
+/// How the client wants images returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Url,
+    B64Json,
+}
+
+impl ImageFormat {
+    /// Requested format; OpenAI defaults to URLs
+    pub fn from_request(request: &Value) -> Self {
+        match request.get("response_format").and_then(|f| f.as_str()) {
+            Some("b64_json") => Self::B64Json,
+            _ => Self::Url,
+        }
+    }
+}
+
+impl ImageApi {
+    /// API to use for a configured provider
+    pub fn for_provider(provider: &ProviderConfig) -> Self {
+        match provider.provider {
+            LLMProvider::Zhipu => Self::Zhipu,
+            _ => Self::OpenAI,
+        }
+    }
+
+    /// Full endpoint URL
+    pub fn url(&self, base_url: &str) -> String {
+        format!("{}/images/generations", base_url.trim_end_matches('/'))
+    }
+
+    /// Attach the provider's credentials to an upstream request
+    pub fn authorize(
+        &self,
+        request: reqwest::RequestBuilder,
+        provider: &ProviderConfig,
+        model: &str,
+    ) -> reqwest::RequestBuilder {
+        AdapterSpec::resolve(provider).authorize(request, provider.api_key.as_deref(), model)
+    }
+
+    /// Upstream request body for an OpenAI image generation request
+    pub fn map_request(&self, request: &Value, model: &str) -> Result<Value, String> {
+        let prompt = request
+            .get("prompt")
+            .and_then(|p| p.as_str())
+            .filter(|p| !p.trim().is_empty())
+            .ok_or("'prompt' is required")?;
+        match self {
+            Self::OpenAI => {
+                let mut body = request.clone();
+                body["model"] = Value::String(model.to_string());
+                // gpt-image models always return base64 and reject the field
+                if model.starts_with("gpt-image") {
+                    if let Some(obj) = body.as_object_mut() {
+                        obj.remove("response_format");
+                    }
+                }
+                Ok(body)
+            }
+            Self::Zhipu => {
+                let mut body = json!({"model": model, "prompt": prompt});
+                for field in ["size", "quality", "user_id"] {
+                    if let Some(value) = request.get(field) {
+                        body[field] = value.clone();
+                    }
+                }
+                Ok(body)
+            }
+        }
+    }
+}
+
+/// Number of images in a response
+pub fn image_count(body: &Value) -> usize {
+    body.get("data")
+        .and_then(|d| d.as_array())
+        .map_or(0, |data| data.len())
+}
+
+/// Convert every image in an OpenAI-shaped response to `format`. Base64 images become
+/// `data:` URLs; URL images are downloaded.
+pub async fn unify_format(
+    http: &reqwest::Client,
+    body: &mut Value,
+    format: ImageFormat,
+) -> Result<(), String> {
+    let mime = format!(
+        "image/{}",
+        body.get("output_format")
+            .and_then(|f| f.as_str())
+            .unwrap_or("png")
+    );
+    let Some(data) = body.get_mut("data").and_then(|d| d.as_array_mut()) else {
+        return Ok(());
+    };
+
+    for image in data {
+        let Some(image) = image.as_object_mut() else {
+            continue;
+        };
+        match format {
+            ImageFormat::Url => {
+                if image.contains_key("url") {
+                    continue;
+                }
+                if let Some(b64) = image.remove("b64_json") {
+                    let b64 = b64.as_str().unwrap_or_default();
+                    image.insert(
+                        "url".to_string(),
+                        Value::String(format!("data:{};base64,{}", mime, b64)),
+                    );
+                }
+            }
+            ImageFormat::B64Json => {
+                if image.contains_key("b64_json") {
+                    continue;
+                }
+                if let Some(url) = image.remove("url") {
+                    let b64 = download_base64(http, url.as_str().unwrap_or_default()).await?;
+                    image.insert("b64_json".to_string(), Value::String(b64));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn download_base64(http: &reqwest::Client, url: &str) -> Result<String, String> {
+    let response = http
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to download generated image: {}", e))?;
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to download generated image: {}", e))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_mapping_and_format() {
+        let request = json!({
+            "model": "dall-e-3",
+            "prompt": "a corgi in a spacesuit",
+            "n": 1,
+            "size": "1024x1024",
+            "response_format": "url",
+        });
+
+        let body = ImageApi::Zhipu
+            .map_request(&request, "cogview-3-plus")
+            .unwrap();
+        assert_eq!(
+            body,
+            json!({"model": "cogview-3-plus", "prompt": "a corgi in a spacesuit", "size": "1024x1024"})
+        );
+        let body = ImageApi::OpenAI
+            .map_request(&request, "gpt-image-1")
+            .unwrap();
+        assert!(body.get("response_format").is_none());
+        assert!(ImageApi::OpenAI
+            .map_request(&json!({"prompt": " "}), "dall-e-3")
+            .is_err());
+
+        let mut response = json!({
+            "data": [{"b64_json": "aGVsbG8="}],
+            "output_format": "webp",
+        });
+        unify_format(&reqwest::Client::new(), &mut response, ImageFormat::Url)
+            .await
+            .unwrap();
+        assert_eq!(image_count(&response), 1);
+        assert_eq!(
+            response["data"][0]["url"],
+            "data:image/webp;base64,aGVsbG8="
+        );
+        assert!(response["data"][0].get("b64_json").is_none());
+    }
+}
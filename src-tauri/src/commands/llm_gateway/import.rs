@@ -0,0 +1,608 @@
+//! Config Import - Converts other gateways' configuration files into provider configs
+//!
+//! LiteLLM `config.yaml` files list deployments as `provider/model` names with optional
+//! API bases and keys. Deployments are grouped into one provider per built-in provider
+//! (or per API base for OpenAI-compatible servers) and merged into the saved settings.
+//!
+//! claude-code-router's `config.json` names providers by endpoint. Its default route
+//! becomes the default provider and model, the background route an alias for the Haiku
+//! models Claude Code uses for background work, and `maxtoken` transformers become
+//! transformation rules. Routes and transformers without an equivalent are reported.
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use super::{
+    ConfigImportResult, GatewaySettings, LLMProvider, ModelAlias, ModelConfig, ProviderConfig,
+    TransformRule, DEFAULT_DISCOVERED_CONTEXT,
+};
+
+/// Priority given to imported OpenAI-compatible servers
+const CUSTOM_PRIORITY: i32 = 8;
+
+#[derive(Debug, Deserialize)]
+struct LiteLLMConfig {
+    #[serde(default)]
+    model_list: Vec<LiteLLMDeployment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LiteLLMDeployment {
+    model_name: String,
+    litellm_params: LiteLLMParams,
+    #[serde(default)]
+    model_info: LiteLLMModelInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct LiteLLMParams {
+    model: String,
+    #[serde(default)]
+    api_base: Option<String>,
+    #[serde(default)]
+    api_key: Option<String>,
+    #[serde(default)]
+    rpm: Option<u32>,
+    #[serde(default)]
+    tpm: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LiteLLMModelInfo {
+    #[serde(default)]
+    max_input_tokens: Option<u32>,
+    #[serde(default)]
+    max_tokens: Option<u32>,
+    #[serde(default)]
+    input_cost_per_token: Option<f64>,
+    #[serde(default)]
+    output_cost_per_token: Option<f64>,
+    #[serde(default)]
+    supports_vision: Option<bool>,
+}
+
+/// Settings converted from a foreign config, and the entries that couldn't be
+#[derive(Debug, Default)]
+pub struct Imported {
+    pub providers: Vec<ProviderConfig>,
+    /// Default route
+    pub default: Option<ModelAlias>,
+    pub aliases: HashMap<String, ModelAlias>,
+    pub transform_rules: Vec<TransformRule>,
+    pub timeout_seconds: Option<u32>,
+    /// Skipped entries, with the reason
+    pub skipped: Vec<String>,
+}
+
+/// Built-in provider served by a LiteLLM provider prefix
+fn litellm_provider(prefix: &str) -> Option<LLMProvider> {
+    Some(match prefix {
+        "openai" | "text-completion-openai" => LLMProvider::OpenAI,
+        "anthropic" => LLMProvider::Anthropic,
+        "gemini" => LLMProvider::Gemini,
+        "deepseek" => LLMProvider::DeepSeek,
+        "moonshot" => LLMProvider::Moonshot,
+        "dashscope" => LLMProvider::Qwen,
+        "groq" => LLMProvider::Groq,
+        "ollama" | "ollama_chat" => LLMProvider::Ollama,
+        "openrouter" => LLMProvider::OpenRouter,
+        "huggingface" => LLMProvider::HuggingFace,
+        "replicate" => LLMProvider::Replicate,
+        "github_copilot" => LLMProvider::Copilot,
+        "hosted_vllm" | "lm_studio" | "openai_like" | "custom_openai" => LLMProvider::Custom,
+        _ => return None,
+    })
+}
+
+/// Turn LiteLLM's `os.environ/NAME` secret references into `${NAME}`
+fn env_reference(value: &str) -> String {
+    match value.strip_prefix("os.environ/") {
+        Some(name) => format!("${{{}}}", name),
+        None => value.to_string(),
+    }
+}
+
+fn host(base_url: &str) -> &str {
+    let rest = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest);
+    rest.split(['/', '?']).next().unwrap_or(rest)
+}
+
+fn deployment_model(deployment: &LiteLLMDeployment, id: &str) -> ModelConfig {
+    let info = &deployment.model_info;
+    let mut capabilities = vec!["coding".to_string()];
+    if info.supports_vision == Some(true) {
+        capabilities.push("vision".to_string());
+    }
+    ModelConfig {
+        id: id.to_string(),
+        name: deployment.model_name.clone(),
+        capabilities,
+        input_price: info.input_cost_per_token.unwrap_or(0.0) * 1_000_000.0,
+        output_price: info.output_cost_per_token.unwrap_or(0.0) * 1_000_000.0,
+        max_tokens: info
+            .max_input_tokens
+            .or(info.max_tokens)
+            .unwrap_or(DEFAULT_DISCOVERED_CONTEXT),
+        is_default: false,
+        pricing_unknown: info.input_cost_per_token.is_none(),
+        rpm_limit: deployment.litellm_params.rpm,
+        tpm_limit: deployment.litellm_params.tpm,
+        ..Default::default()
+    }
+}
+
+/// Convert the `model_list` of a LiteLLM proxy config
+pub fn from_litellm(yaml: &str) -> Result<Imported, String> {
+    let config: LiteLLMConfig =
+        serde_yaml::from_str(yaml).map_err(|e| format!("Invalid LiteLLM config: {}", e))?;
+    let templates = GatewaySettings::default().providers;
+    let mut imported = Imported::default();
+
+    for deployment in &config.model_list {
+        let params = &deployment.litellm_params;
+        let skip =
+            |reason: &str| format!("{} ({}): {}", deployment.model_name, params.model, reason);
+        let Some((prefix, id)) = params.model.split_once('/') else {
+            imported.skipped.push(skip("model has no provider prefix"));
+            continue;
+        };
+        let Some(mut kind) = litellm_provider(prefix) else {
+            imported
+                .skipped
+                .push(skip(&format!("provider '{}' is not supported", prefix)));
+            continue;
+        };
+        let api_base = params.api_base.as_deref().map(env_reference);
+        // OpenAI deployments pointed elsewhere are OpenAI-compatible servers
+        if kind == LLMProvider::OpenAI
+            && api_base
+                .as_deref()
+                .is_some_and(|b| host(b) != "api.openai.com")
+        {
+            kind = LLMProvider::Custom;
+        }
+
+        let existing = imported.providers.iter_mut().find(|p| {
+            p.provider == kind
+                && (kind != LLMProvider::Custom || Some(&p.base_url) == api_base.as_ref())
+        });
+        let provider = match existing {
+            Some(provider) => provider,
+            None => {
+                let template = templates.iter().find(|p| p.provider == kind);
+                let base_url = match (&api_base, template) {
+                    // LiteLLM's Ollama base is the server root, not the OpenAI-compatible API
+                    (Some(base), _) if kind == LLMProvider::Ollama && !base.ends_with("/v1") => {
+                        format!("{}/v1", base.trim_end_matches('/'))
+                    }
+                    (Some(base), _) => base.clone(),
+                    (None, Some(template)) => template.base_url.clone(),
+                    (None, None) => {
+                        imported.skipped.push(skip("no api_base is configured"));
+                        continue;
+                    }
+                };
+                let provider = match template {
+                    Some(template) => ProviderConfig {
+                        base_url,
+                        models: Vec::new(),
+                        ..template.clone()
+                    },
+                    None => ProviderConfig {
+                        provider: kind.clone(),
+                        name: format!("LiteLLM {}", host(&base_url)),
+                        base_url,
+                        priority: CUSTOM_PRIORITY,
+                        ..Default::default()
+                    },
+                };
+                imported.providers.push(provider);
+                imported
+                    .providers
+                    .last_mut()
+                    .expect("provider was just added")
+            }
+        };
+
+        provider.enabled = true;
+        if let Some(key) = &params.api_key {
+            let key = env_reference(key);
+            match &provider.api_key {
+                None => provider.api_key = Some(key),
+                // Further keys of load-balanced deployments join the rotation
+                Some(first) if *first != key && !provider.api_keys.contains(&key) => {
+                    provider.api_keys.push(key)
+                }
+                Some(_) => {}
+            }
+        }
+        if !provider.models.iter().any(|m| m.id == id) {
+            let mut model = deployment_model(deployment, id);
+            model.is_default = provider.models.is_empty();
+            provider.models.push(model);
+        }
+    }
+
+    Ok(imported)
+}
+
+#[derive(Debug, Deserialize)]
+struct CcrConfig {
+    #[serde(rename = "Providers", alias = "providers", default)]
+    providers: Vec<CcrProvider>,
+    #[serde(rename = "Router", default)]
+    router: Map<String, Value>,
+    #[serde(rename = "API_TIMEOUT_MS", default)]
+    api_timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CcrProvider {
+    name: String,
+    api_base_url: String,
+    #[serde(default)]
+    api_key: Option<String>,
+    #[serde(default)]
+    models: Vec<String>,
+    /// `use` lists transformers for every model; other keys hold per-model `use` lists
+    #[serde(default)]
+    transformer: Map<String, Value>,
+}
+
+/// claude-code-router transformers that only select a provider's API format, which the
+/// gateway's built-in adapters already handle
+const FORMAT_TRANSFORMERS: &[&str] = &[
+    "anthropic",
+    "openai",
+    "deepseek",
+    "gemini",
+    "groq",
+    "openrouter",
+];
+
+/// Model prefix Claude Code requests background work with, as routed by claude-code-router
+const BACKGROUND_MODEL: &str = "claude-3-5-haiku*";
+
+/// Turn claude-code-router's `$NAME` secret references into `${NAME}`
+fn ccr_env_reference(value: &str) -> String {
+    let bare = Regex::new(r"^\$([A-Za-z_][A-Za-z0-9_]*)$").expect("reference pattern is valid");
+    bare.replace(value, "$${$1}").into_owned()
+}
+
+/// Strip the endpoint path claude-code-router expects from an API base URL
+fn ccr_base_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    ["/chat/completions", "/messages"]
+        .iter()
+        .find_map(|endpoint| url.strip_suffix(endpoint))
+        .unwrap_or(url)
+        .to_string()
+}
+
+/// Transformer names and options in a `use` list
+fn transformer_uses(uses: &Value) -> Vec<(&str, Option<&Value>)> {
+    uses.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| match entry {
+            Value::String(name) => Some((name.as_str(), None)),
+            Value::Array(pair) => Some((pair.first()?.as_str()?, pair.get(1))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Convert the providers, routes and transformers of a claude-code-router config
+pub fn from_claude_code_router(json: &str) -> Result<Imported, String> {
+    let config: CcrConfig = serde_json::from_str(json)
+        .map_err(|e| format!("Invalid claude-code-router config: {}", e))?;
+    let templates = GatewaySettings::default().providers;
+    let mut imported = Imported {
+        timeout_seconds: config.api_timeout_ms.map(|ms| (ms / 1000).max(1) as u32),
+        ..Default::default()
+    };
+    // Gateway provider behind each claude-code-router provider name
+    let mut kinds: HashMap<&str, LLMProvider> = HashMap::new();
+
+    for ccr in &config.providers {
+        let global = ccr
+            .transformer
+            .get("use")
+            .map(transformer_uses)
+            .unwrap_or_default();
+        let template = templates
+            .iter()
+            .find(|t| host(&t.base_url) == host(&ccr.api_base_url));
+        let mut provider = match template {
+            Some(template) => ProviderConfig {
+                models: Vec::new(),
+                ..template.clone()
+            },
+            None => ProviderConfig {
+                provider: if global.iter().any(|(name, _)| *name == "anthropic") {
+                    LLMProvider::Anthropic
+                } else {
+                    LLMProvider::Custom
+                },
+                name: ccr.name.clone(),
+                base_url: ccr_base_url(&ccr.api_base_url),
+                priority: CUSTOM_PRIORITY,
+                ..Default::default()
+            },
+        };
+        provider.enabled = true;
+        provider.api_key = ccr.api_key.as_deref().map(ccr_env_reference);
+        provider.models = ccr
+            .models
+            .iter()
+            .enumerate()
+            .map(|(i, id)| ModelConfig {
+                id: id.clone(),
+                name: id.clone(),
+                capabilities: vec!["coding".to_string()],
+                max_tokens: DEFAULT_DISCOVERED_CONTEXT,
+                is_default: i == 0,
+                pricing_unknown: true,
+                ..Default::default()
+            })
+            .collect();
+
+        let per_model = ccr
+            .transformer
+            .iter()
+            .filter(|(key, _)| *key != "use")
+            .map(|(model, entry)| (format!("{}*", model), entry.get("use")));
+        let scoped =
+            std::iter::once(("*".to_string(), ccr.transformer.get("use"))).chain(per_model);
+        for (match_model, uses) in scoped {
+            for (name, options) in uses.map(transformer_uses).unwrap_or_default() {
+                if FORMAT_TRANSFORMERS.contains(&name) {
+                    continue;
+                }
+                let max_tokens = options.and_then(|o| o.get("max_tokens"));
+                match (name, max_tokens) {
+                    ("maxtoken", Some(max_tokens)) => {
+                        imported.transform_rules.push(TransformRule {
+                            name: format!("{} max tokens", ccr.name),
+                            enabled: true,
+                            provider: Some(provider.provider.clone()),
+                            match_model: match_model.clone(),
+                            replacements: Vec::new(),
+                            headers: HashMap::new(),
+                            parameters: Map::from_iter([(
+                                "max_tokens".to_string(),
+                                max_tokens.clone(),
+                            )]),
+                        })
+                    }
+                    _ => imported.skipped.push(format!(
+                        "Transformer '{}' on {}: no gateway equivalent",
+                        name, ccr.name
+                    )),
+                }
+            }
+        }
+
+        kinds.insert(&ccr.name, provider.provider.clone());
+        imported.providers.push(provider);
+    }
+
+    for (key, target) in &config.router {
+        // Thresholds and other settings accompany the routes
+        let Some(target) = target.as_str() else {
+            continue;
+        };
+        let alias = target.split_once(',').and_then(|(name, model)| {
+            Some(ModelAlias {
+                provider: kinds.get(name.trim())?.clone(),
+                model: model.trim().to_string(),
+            })
+        });
+        match (key.as_str(), alias) {
+            (_, None) => imported
+                .skipped
+                .push(format!("Router.{} ({}): unknown provider", key, target)),
+            ("default", alias) => imported.default = alias,
+            ("background", Some(alias)) => {
+                imported.aliases.insert(BACKGROUND_MODEL.to_string(), alias);
+            }
+            (_, Some(_)) => imported.skipped.push(format!(
+                "Router.{} ({}): no gateway equivalent",
+                key, target
+            )),
+        }
+    }
+
+    Ok(imported)
+}
+
+/// Merge imported settings into `settings`.
+///
+/// Built-in providers take the imported endpoint, key and any models they lack, keeping
+/// their existing models' prices; OpenAI-compatible servers replace the custom provider
+/// with the same name. Imported transformation rules replace those with the same name.
+pub fn merge(mut settings: GatewaySettings, imported: Imported) -> ConfigImportResult {
+    let mut names = Vec::new();
+    for provider in imported.providers {
+        names.push(provider.name.clone());
+        let existing = settings.providers.iter_mut().find(|p| {
+            p.provider == provider.provider
+                && (provider.provider != LLMProvider::Custom || p.name == provider.name)
+        });
+        let Some(existing) = existing else {
+            settings.providers.push(provider);
+            continue;
+        };
+        if provider.provider == LLMProvider::Custom {
+            *existing = provider;
+            continue;
+        }
+        existing.enabled = true;
+        existing.base_url = provider.base_url;
+        if provider.api_key.is_some() {
+            existing.api_key = provider.api_key;
+            existing.api_keys = provider.api_keys;
+        }
+        for mut model in provider.models {
+            if !existing.models.iter().any(|m| m.id == model.id) {
+                model.is_default = false;
+                existing.models.push(model);
+            }
+        }
+    }
+
+    if let Some(default) = imported.default {
+        let provider = settings.providers.iter_mut().find(|p| {
+            p.provider == default.provider && p.models.iter().any(|m| m.id == default.model)
+        });
+        if let Some(provider) = provider {
+            for model in &mut provider.models {
+                model.is_default = model.id == default.model;
+            }
+        }
+        settings.default_provider = default.provider;
+    }
+    settings.model_aliases.extend(imported.aliases);
+    for rule in imported.transform_rules {
+        settings.transform_rules.retain(|r| r.name != rule.name);
+        settings.transform_rules.push(rule);
+    }
+    if let Some(timeout) = imported.timeout_seconds {
+        settings.timeout_seconds = timeout;
+    }
+
+    ConfigImportResult {
+        settings,
+        providers: names,
+        skipped: imported.skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+model_list:
+  - model_name: gpt-4o
+    litellm_params:
+      model: openai/gpt-4o
+      api_key: os.environ/OPENAI_API_KEY
+      rpm: 500
+  - model_name: gpt-4o
+    litellm_params:
+      model: openai/gpt-4o
+      api_key: os.environ/OPENAI_API_KEY_2
+  - model_name: local-qwen
+    litellm_params:
+      model: hosted_vllm/Qwen/Qwen2.5-Coder-32B-Instruct
+      api_base: http://gpu-box:8000/v1
+    model_info:
+      max_input_tokens: 32768
+  - model_name: llama
+    litellm_params:
+      model: ollama/llama3.1
+      api_base: http://localhost:11434
+  - model_name: claude
+    litellm_params:
+      model: bedrock/anthropic.claude-3-5-sonnet
+general_settings:
+  master_key: sk-1234
+"#;
+
+    #[test]
+    fn test_from_litellm_and_merge() {
+        let imported = from_litellm(CONFIG).unwrap();
+        assert_eq!(imported.providers.len(), 3);
+        assert_eq!(imported.skipped.len(), 1);
+        assert!(imported.skipped[0].contains("'bedrock'"));
+
+        let openai = &imported.providers[0];
+        assert_eq!(openai.provider, LLMProvider::OpenAI);
+        assert_eq!(openai.api_key.as_deref(), Some("${OPENAI_API_KEY}"));
+        assert_eq!(openai.api_keys, vec!["${OPENAI_API_KEY_2}".to_string()]);
+        assert_eq!(openai.models.len(), 1);
+        assert_eq!(openai.models[0].rpm_limit, Some(500));
+
+        let vllm = &imported.providers[1];
+        assert_eq!(vllm.provider, LLMProvider::Custom);
+        assert_eq!(vllm.name, "LiteLLM gpu-box:8000");
+        assert_eq!(vllm.models[0].id, "Qwen/Qwen2.5-Coder-32B-Instruct");
+        assert_eq!(vllm.models[0].max_tokens, 32768);
+        assert!(vllm.models[0].is_default);
+
+        assert_eq!(imported.providers[2].base_url, "http://localhost:11434/v1");
+
+        let providers = GatewaySettings::default().providers.len();
+        let result = merge(GatewaySettings::default(), imported);
+        assert_eq!(result.providers.len(), 3);
+        assert_eq!(result.settings.providers.len(), providers + 1);
+        let openai = result
+            .settings
+            .providers
+            .iter()
+            .find(|p| p.provider == LLMProvider::OpenAI)
+            .unwrap();
+        assert_eq!(openai.api_key.as_deref(), Some("${OPENAI_API_KEY}"));
+        assert_eq!(openai.models.iter().filter(|m| m.is_default).count(), 1);
+    }
+
+    #[test]
+    fn test_from_claude_code_router() {
+        let config = r#"{
+            "API_TIMEOUT_MS": 600000,
+            "Providers": [
+                {
+                    "name": "deepseek",
+                    "api_base_url": "https://api.deepseek.com/chat/completions",
+                    "api_key": "$DEEPSEEK_API_KEY",
+                    "models": ["deepseek-chat", "deepseek-reasoner"],
+                    "transformer": {"use": ["deepseek"], "deepseek-chat": {"use": ["tooluse"]}}
+                },
+                {
+                    "name": "local",
+                    "api_base_url": "http://gpu-box:8000/v1/chat/completions",
+                    "models": ["qwen2.5-coder"],
+                    "transformer": {"use": [["maxtoken", {"max_tokens": 8192}]]}
+                }
+            ],
+            "Router": {
+                "default": "deepseek,deepseek-reasoner",
+                "background": "local,qwen2.5-coder",
+                "think": "deepseek,deepseek-reasoner",
+                "longContextThreshold": 60000
+            }
+        }"#;
+        let imported = from_claude_code_router(config).unwrap();
+        assert_eq!(imported.providers[0].provider, LLMProvider::DeepSeek);
+        assert_eq!(
+            imported.providers[0].api_key.as_deref(),
+            Some("${DEEPSEEK_API_KEY}")
+        );
+        assert_eq!(imported.providers[1].provider, LLMProvider::Custom);
+        assert_eq!(imported.providers[1].base_url, "http://gpu-box:8000/v1");
+        assert_eq!(imported.transform_rules.len(), 1);
+        assert_eq!(imported.transform_rules[0].parameters["max_tokens"], 8192);
+        assert_eq!(imported.skipped.len(), 2);
+
+        let result = merge(GatewaySettings::default(), imported);
+        let settings = result.settings;
+        assert_eq!(settings.default_provider, LLMProvider::DeepSeek);
+        let deepseek = settings
+            .providers
+            .iter()
+            .find(|p| p.provider == LLMProvider::DeepSeek)
+            .unwrap();
+        let default = deepseek.models.iter().find(|m| m.is_default).unwrap();
+        assert_eq!(default.id, "deepseek-reasoner");
+        assert_eq!(
+            settings.model_aliases[BACKGROUND_MODEL].provider,
+            LLMProvider::Custom
+        );
+        assert_eq!(settings.timeout_seconds, 600);
+    }
+}
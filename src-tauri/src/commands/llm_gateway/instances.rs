@@ -0,0 +1,192 @@
+//! Gateway Instances - Additional listeners with their own routing
+//!
+//! Besides its main port the gateway can listen on further ports, each a
+//! [`GatewayInstance`] limited to a subset of the providers, with its own default
+//! provider and smart routing. One port can so be pinned to DeepSeek while another
+//! routes smartly. An instance's settings are the main settings narrowed by its
+//! [`InstanceConfig`]; instances start and stop with the gateway, or one by one, and
+//! keep their own status.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tauri::AppHandle;
+use tokio::sync::RwLock;
+
+use super::{watchdog, GatewaySettings, GatewayStatus, LLMProvider};
+
+fn default_true() -> bool {
+    true
+}
+
+/// An additional gateway listener
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstanceConfig {
+    /// Unique name of the instance
+    pub name: String,
+    pub port: u16,
+    /// Whether the instance starts with the gateway
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Providers the instance may route to; empty allows every enabled provider
+    #[serde(default)]
+    pub providers: Vec<LLMProvider>,
+    /// Provider of requests no rule routes elsewhere, instead of the main default
+    #[serde(default)]
+    pub default_provider: Option<LLMProvider>,
+    /// Smart routing, instead of the main setting
+    #[serde(default)]
+    pub smart_routing: Option<bool>,
+}
+
+impl InstanceConfig {
+    /// The main settings narrowed to this instance
+    pub fn settings(&self, base: &GatewaySettings) -> GatewaySettings {
+        let mut settings = base.clone();
+        settings.port = self.port;
+        settings.instances.clear();
+        if !self.providers.is_empty() {
+            for provider in &mut settings.providers {
+                provider.enabled &= self.providers.contains(&provider.provider);
+            }
+        }
+        if let Some(provider) = &self.default_provider {
+            settings.default_provider = provider.clone();
+        }
+        if let Some(smart_routing) = self.smart_routing {
+            settings.smart_routing = smart_routing;
+        }
+        settings
+    }
+}
+
+/// Check instance names are unique and no two listeners share a port
+pub fn validate(settings: &GatewaySettings) -> Result<(), String> {
+    let mut names = HashSet::new();
+    let mut ports = HashSet::from([settings.port]);
+    for instance in &settings.instances {
+        if instance.name.trim().is_empty() {
+            return Err("Gateway instance names must not be empty".to_string());
+        }
+        if !names.insert(instance.name.as_str()) {
+            return Err(format!("Duplicate gateway instance '{}'", instance.name));
+        }
+        if !ports.insert(instance.port) {
+            return Err(format!(
+                "Gateway instance '{}' uses port {}, which another listener already uses",
+                instance.name, instance.port
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// State of a running instance
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstanceStatus {
+    pub name: String,
+    pub port: u16,
+    pub running: bool,
+    pub requests_processed: u64,
+    pub last_error: Option<String>,
+}
+
+/// A running additional listener
+pub struct GatewayInstance {
+    pub config: InstanceConfig,
+    pub settings: Arc<RwLock<GatewaySettings>>,
+    pub status: Arc<RwLock<GatewayStatus>>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl GatewayInstance {
+    /// Start serving `config` with settings derived from `base`, whose environment
+    /// references are resolved
+    pub fn start(app: AppHandle, config: InstanceConfig, base: &GatewaySettings) -> Self {
+        let settings = Arc::new(RwLock::new(config.settings(base)));
+        let status = Arc::new(RwLock::new(GatewayStatus {
+            running: true,
+            port: config.port,
+            requests_processed: 0,
+            provider_status: HashMap::new(),
+            last_error: None,
+            model_warnings: Vec::new(),
+        }));
+        let handle = tokio::spawn(watchdog::supervise(
+            app,
+            config.port,
+            settings.clone(),
+            status.clone(),
+            false,
+        ));
+        log::info!(
+            "Gateway instance '{}' started on port {}",
+            config.name,
+            config.port
+        );
+        Self {
+            config,
+            settings,
+            status,
+            handle,
+        }
+    }
+
+    pub fn stop(self) {
+        self.handle.abort();
+        log::info!("Gateway instance '{}' stopped", self.config.name);
+    }
+
+    pub async fn status(&self) -> InstanceStatus {
+        let status = self.status.read().await;
+        InstanceStatus {
+            name: self.config.name.clone(),
+            port: self.config.port,
+            running: status.running,
+            requests_processed: status.requests_processed,
+            last_error: status.last_error.clone(),
+        }
+    }
+
+    /// Hand updated main settings to the instance; port changes apply on restart
+    pub async fn update(&mut self, base: &GatewaySettings) {
+        if let Some(config) = base.instances.iter().find(|i| i.name == self.config.name) {
+            self.config = InstanceConfig {
+                port: self.config.port,
+                ..config.clone()
+            };
+        }
+        *self.settings.write().await = self.config.settings(base);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instance_settings() {
+        let mut base = GatewaySettings::default();
+        let deepseek: InstanceConfig = serde_json::from_str(
+            r#"{"name": "deepseek", "port": 8766, "providers": ["deepseek"], "default_provider": "deepseek"}"#,
+        )
+        .unwrap();
+        base.instances.push(deepseek.clone());
+        assert!(validate(&base).is_ok());
+
+        let settings = deepseek.settings(&base);
+        assert_eq!(settings.port, 8766);
+        assert_eq!(settings.default_provider, LLMProvider::DeepSeek);
+        assert!(settings.instances.is_empty());
+        assert!(settings
+            .providers
+            .iter()
+            .all(|p| !p.enabled || p.provider == LLMProvider::DeepSeek));
+
+        base.instances.push(InstanceConfig {
+            name: "smart".to_string(),
+            ..deepseek
+        });
+        assert!(validate(&base).unwrap_err().contains("port 8766"));
+    }
+}
@@ -0,0 +1,161 @@
+//! Environment Interpolation - Resolves `${VAR}` references in provider configuration
+//!
+//! API keys and base URLs may be stored as references like `${OPENAI_API_KEY}` so the
+//! secrets themselves never reach the database. References are resolved from the
+//! environment when the gateway starts; the saved settings keep the references.
+
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+use super::{client, GatewaySettings, ProviderConfig, ProxyConfig, TlsConfig, WebhookConfig};
+
+fn reference() -> &'static Regex {
+    static REFERENCE: OnceLock<Regex> = OnceLock::new();
+    REFERENCE.get_or_init(|| {
+        Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").expect("reference pattern is valid")
+    })
+}
+
+fn interpolate_with(
+    value: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut missing = None;
+    let resolved = reference().replace_all(value, |caps: &Captures| {
+        lookup(&caps[1]).unwrap_or_else(|| {
+            missing.get_or_insert_with(|| caps[1].to_string());
+            String::new()
+        })
+    });
+    match missing {
+        Some(name) => Err(format!("Environment variable '{}' is not set", name)),
+        None => Ok(resolved.into_owned()),
+    }
+}
+
+/// Replace `${VAR}` references in `value` with the environment's values
+pub fn interpolate(value: &str) -> Result<String, String> {
+    interpolate_with(value, |name| std::env::var(name).ok())
+}
+
+/// Resolve the references in a proxy's URL and password, then check it
+fn resolve_proxy(
+    proxy: &mut ProxyConfig,
+    resolve: impl Fn(&str) -> Result<String, String>,
+) -> Result<(), String> {
+    proxy.url = resolve(&proxy.url)?;
+    proxy.password = proxy.password.as_deref().map(&resolve).transpose()?;
+    proxy.validate()
+}
+
+/// Resolve the references in TLS certificate paths, then check the files load
+fn resolve_tls(
+    tls: &mut TlsConfig,
+    resolve: impl Fn(&str) -> Result<String, String>,
+) -> Result<(), String> {
+    for path in [
+        &mut tls.ca_cert_path,
+        &mut tls.client_cert_path,
+        &mut tls.client_key_path,
+    ] {
+        *path = path.as_deref().map(&resolve).transpose()?;
+    }
+    tls.validate()
+}
+
+/// Resolve the references in a provider's base URL, mirrors, API keys, proxy and TLS
+/// options, then check its DNS overrides
+pub fn resolve_provider(provider: &mut ProviderConfig) -> Result<(), String> {
+    let name = provider.name.clone();
+    let resolve =
+        |value: &str| interpolate(value).map_err(|e| format!("{} (provider '{}')", e, name));
+    if let Some(proxy) = provider.proxy.as_mut() {
+        resolve_proxy(proxy, resolve)?;
+    }
+    if let Some(tls) = provider.tls.as_mut() {
+        resolve_tls(tls, resolve)?;
+    }
+    provider.mirrors = provider
+        .mirrors
+        .iter()
+        .map(|mirror| resolve(mirror))
+        .collect::<Result<_, _>>()?;
+    client::validate_dns_overrides(&provider.dns_overrides)
+        .map_err(|e| format!("{} (provider '{}')", e, name))?;
+    provider.base_url = resolve(&provider.base_url)?;
+    provider.api_key = provider.api_key.as_deref().map(resolve).transpose()?;
+    provider.openai_organization = provider
+        .openai_organization
+        .as_deref()
+        .map(resolve)
+        .transpose()?;
+    provider.openai_project = provider
+        .openai_project
+        .as_deref()
+        .map(resolve)
+        .transpose()?;
+    provider.api_keys = provider
+        .api_keys
+        .iter()
+        .map(|key| resolve(key))
+        .collect::<Result<_, _>>()?;
+    Ok(())
+}
+
+/// Resolve the references in a webhook's URL and secret, then check it
+fn resolve_webhook(webhook: &mut WebhookConfig) -> Result<(), String> {
+    webhook.url = interpolate(&webhook.url)?;
+    webhook.secret = webhook.secret.as_deref().map(interpolate).transpose()?;
+    webhook.validate()
+}
+
+/// Resolve the references of the gateway's proxy, TLS options, webhooks and every
+/// enabled provider
+pub fn resolve_settings(settings: &mut GatewaySettings) -> Result<(), String> {
+    if let Some(proxy) = settings.proxy.as_mut() {
+        resolve_proxy(proxy, interpolate)?;
+    }
+    if let Some(tls) = settings.tls.as_mut() {
+        resolve_tls(tls, interpolate)?;
+    }
+    settings
+        .webhooks
+        .iter_mut()
+        .filter(|w| w.enabled)
+        .try_for_each(resolve_webhook)?;
+    settings
+        .providers
+        .iter_mut()
+        .filter(|p| p.enabled)
+        .try_for_each(resolve_provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate() {
+        let lookup = |name: &str| match name {
+            "OPENAI_API_KEY" => Some("sk-test".to_string()),
+            "PROXY_HOST" => Some("proxy.local".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            interpolate_with("${OPENAI_API_KEY}", lookup).unwrap(),
+            "sk-test"
+        );
+        assert_eq!(
+            interpolate_with("https://${PROXY_HOST}:8443/v1", lookup).unwrap(),
+            "https://proxy.local:8443/v1"
+        );
+        assert_eq!(
+            interpolate_with("plain-key $HOME", lookup).unwrap(),
+            "plain-key $HOME"
+        );
+        assert_eq!(
+            interpolate_with("${MISSING_KEY}", lookup).unwrap_err(),
+            "Environment variable 'MISSING_KEY' is not set"
+        );
+    }
+}
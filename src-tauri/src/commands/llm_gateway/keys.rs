@@ -0,0 +1,193 @@
+//! API Key Pools - Rotates between a provider's API keys and benches failing ones
+//!
+//! Keys that come back with 429 are benched briefly; keys rejected with 401/403 are
+//! benched for much longer since they're likely revoked or mistyped.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::ProviderConfig;
+
+const THROTTLE_BENCH: Duration = Duration::from_secs(60);
+const AUTH_BENCH: Duration = Duration::from_secs(600);
+
+/// How the next key is picked from a provider's pool
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyRotation {
+    /// Cycle through the keys in order
+    #[default]
+    RoundRobin,
+    /// Prefer the key that was throttled longest ago (never-throttled keys first)
+    LeastRecentlyThrottled,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct KeyState {
+    benched_until: Option<Instant>,
+    last_throttled: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    next: usize,
+    keys: HashMap<String, KeyState>,
+}
+
+/// Rotation and health state of every provider's keys
+#[derive(Debug, Default)]
+pub struct KeyPool {
+    pools: Mutex<HashMap<String, PoolState>>,
+}
+
+/// All usable keys of a provider: the primary key followed by the extra ones
+pub fn provider_keys(provider: &ProviderConfig) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for key in provider.api_key.iter().chain(provider.api_keys.iter()) {
+        if !key.is_empty() && !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+    keys
+}
+
+fn pool_id(provider: &ProviderConfig) -> String {
+    format!("{}:{}", provider.provider, provider.name)
+}
+
+impl KeyPool {
+    /// Pick the key for the next request, skipping benched keys.
+    ///
+    /// If every key is benched, the one whose bench expires first is used anyway.
+    pub fn select(&self, provider: &ProviderConfig, now: Instant) -> Option<String> {
+        let keys = provider_keys(provider);
+        if keys.len() <= 1 {
+            return keys.into_iter().next();
+        }
+
+        let mut pools = self.pools.lock().unwrap_or_else(|e| e.into_inner());
+        let pool = pools.entry(pool_id(provider)).or_default();
+        let state = |key: &String| pool.keys.get(key).copied().unwrap_or_default();
+
+        let available: Vec<&String> = keys
+            .iter()
+            .filter(|k| state(k).benched_until.is_none_or(|until| until <= now))
+            .collect();
+
+        let chosen = if available.is_empty() {
+            keys.iter().min_by_key(|k| state(k).benched_until)
+        } else {
+            match provider.key_rotation {
+                KeyRotation::RoundRobin => Some(available[pool.next % available.len()]),
+                KeyRotation::LeastRecentlyThrottled => available
+                    .iter()
+                    .copied()
+                    .min_by_key(|k| state(k).last_throttled),
+            }
+        }
+        .cloned();
+
+        pool.next = pool.next.wrapping_add(1);
+        chosen
+    }
+
+    /// Record the upstream status a key received
+    pub fn report(&self, provider: &ProviderConfig, key: &str, status: u16, now: Instant) {
+        self.report_with_window(provider, key, status, None, now);
+    }
+
+    /// Record the upstream status a key received, benching a throttled key for the
+    /// window the provider asked for rather than the default
+    pub fn report_with_window(
+        &self,
+        provider: &ProviderConfig,
+        key: &str,
+        status: u16,
+        window: Option<Duration>,
+        now: Instant,
+    ) {
+        let bench = match status {
+            429 => window.unwrap_or(THROTTLE_BENCH),
+            401 | 403 => AUTH_BENCH,
+            _ => return,
+        };
+        if provider_keys(provider).len() <= 1 {
+            return;
+        }
+
+        let mut pools = self.pools.lock().unwrap_or_else(|e| e.into_inner());
+        let state = pools
+            .entry(pool_id(provider))
+            .or_default()
+            .keys
+            .entry(key.to_string())
+            .or_default();
+        state.benched_until = Some(now + bench);
+        if status == 429 {
+            state.last_throttled = Some(now);
+        }
+        log::warn!(
+            "Benching a {} API key for {:?} after HTTP {}",
+            provider.name,
+            bench,
+            status
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(rotation: KeyRotation) -> ProviderConfig {
+        ProviderConfig {
+            name: "Pool".to_string(),
+            api_key: Some("a".to_string()),
+            api_keys: vec!["b".to_string(), "c".to_string(), "a".to_string()],
+            key_rotation: rotation,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_round_robin_skips_benched_keys() {
+        let pool = KeyPool::default();
+        let provider = provider(KeyRotation::RoundRobin);
+        let now = Instant::now();
+
+        let picks: Vec<_> = (0..3)
+            .map(|_| pool.select(&provider, now).unwrap())
+            .collect();
+        assert_eq!(picks, vec!["a", "b", "c"]);
+
+        pool.report(&provider, "b", 401, now);
+        for _ in 0..4 {
+            assert_ne!(pool.select(&provider, now).unwrap(), "b");
+        }
+
+        // Benches expire
+        let later = now + AUTH_BENCH;
+        let picks: Vec<_> = (0..3)
+            .map(|_| pool.select(&provider, later).unwrap())
+            .collect();
+        assert!(picks.contains(&"b".to_string()));
+    }
+
+    #[test]
+    fn test_least_recently_throttled() {
+        let pool = KeyPool::default();
+        let provider = provider(KeyRotation::LeastRecentlyThrottled);
+        let now = Instant::now();
+
+        pool.report(&provider, "a", 429, now);
+        pool.report(&provider, "b", 429, now + Duration::from_secs(1));
+        assert_eq!(pool.select(&provider, now).unwrap(), "c");
+
+        // Once benches expire, the key throttled longest ago wins over the newer one
+        pool.report(&provider, "c", 429, now + Duration::from_secs(2));
+        let later = now + THROTTLE_BENCH + Duration::from_secs(5);
+        assert_eq!(pool.select(&provider, later).unwrap(), "a");
+    }
+}
@@ -0,0 +1,271 @@
+//! Legacy Completions - OpenAI `/v1/completions` on top of chat completions
+//!
+//! Models listed with the `completions` capability (e.g. `gpt-3.5-turbo-instruct`) get
+//! legacy requests forwarded as they are. Every other model is served by turning the
+//! prompt into a single user message and the chat completion, streamed or not, back into
+//! the `text_completion` shape.
+
+use serde_json::{json, Value};
+
+use super::translate::{SseParser, StreamTranslator};
+use super::ProviderConfig;
+
+/// Model capability marking native support for legacy completions
+pub const COMPLETIONS_CAPABILITY: &str = "completions";
+
+/// Fields with the same meaning in legacy and chat requests
+const SHARED_FIELDS: &[&str] = &[
+    "max_tokens",
+    "temperature",
+    "top_p",
+    "n",
+    "stop",
+    "presence_penalty",
+    "frequency_penalty",
+    "logit_bias",
+    "seed",
+    "user",
+    "stream",
+    "stream_options",
+];
+
+/// Whether a model accepts legacy completion requests itself
+pub fn supports_completions(provider: &ProviderConfig, model: &str) -> bool {
+    provider
+        .models
+        .iter()
+        .find(|m| m.id == model)
+        .is_some_and(|m| m.capabilities.iter().any(|c| c == COMPLETIONS_CAPABILITY))
+}
+
+/// Full legacy completions endpoint URL
+pub fn completions_url(base_url: &str) -> String {
+    format!("{}/completions", base_url.trim_end_matches('/'))
+}
+
+/// The single prompt of a legacy request
+fn prompt(request: &Value) -> Result<&str, String> {
+    match request.get("prompt") {
+        Some(Value::String(prompt)) => Ok(prompt),
+        Some(Value::Array(prompts)) if prompts.len() == 1 => prompts[0]
+            .as_str()
+            .ok_or_else(|| "Token ID prompts are only supported by completion models".to_string()),
+        Some(Value::Array(_)) => {
+            Err("Batched prompts are only supported by completion models".to_string())
+        }
+        _ => Err("'prompt' is required".to_string()),
+    }
+}
+
+/// Prompt to prepend to the generated text when the request asks for it with `echo`
+pub fn echo_prefix(request: &Value) -> Option<String> {
+    if request.get("echo").and_then(|e| e.as_bool()) != Some(true) {
+        return None;
+    }
+    prompt(request).ok().map(str::to_string)
+}
+
+/// Chat completion request equivalent to a legacy completion request
+pub fn completion_to_chat_request(request: &Value) -> Result<Value, String> {
+    let mut chat = json!({
+        "model": request.get("model").cloned().unwrap_or(Value::Null),
+        "messages": [{"role": "user", "content": prompt(request)?}],
+    });
+    for field in SHARED_FIELDS {
+        if let Some(value) = request.get(*field) {
+            chat[*field] = value.clone();
+        }
+    }
+    Ok(chat)
+}
+
+/// Chat-shaped view of a request that can't be emulated, with a user message per text
+/// prompt, for routing and screening it on its way to a completion model
+pub fn routing_request(request: &Value) -> Value {
+    let messages: Vec<Value> = match request.get("prompt") {
+        Some(Value::String(prompt)) => vec![json!({"role": "user", "content": prompt})],
+        Some(Value::Array(prompts)) => prompts
+            .iter()
+            .filter_map(|p| p.as_str())
+            .map(|p| json!({"role": "user", "content": p}))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut chat = json!({
+        "model": request.get("model").cloned().unwrap_or(Value::Null),
+        "messages": messages,
+    });
+    if let Some(max_tokens) = request.get("max_tokens") {
+        chat["max_tokens"] = max_tokens.clone();
+    }
+    chat
+}
+
+fn completion_id(id: Option<&Value>) -> Value {
+    match id.and_then(|id| id.as_str()) {
+        Some(id) => Value::String(id.replacen("chatcmpl-", "cmpl-", 1)),
+        None => Value::Null,
+    }
+}
+
+/// Legacy completion response for a chat completion
+pub fn chat_to_completion_response(body: &Value, echo: Option<&str>) -> Value {
+    let choices: Vec<Value> = body
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+        .map(|choice| {
+            let text = choice
+                .pointer("/message/content")
+                .and_then(|c| c.as_str())
+                .unwrap_or_default();
+            json!({
+                "text": format!("{}{}", echo.unwrap_or_default(), text),
+                "index": choice.get("index").cloned().unwrap_or(json!(0)),
+                "logprobs": null,
+                "finish_reason": choice.get("finish_reason").cloned().unwrap_or(Value::Null),
+            })
+        })
+        .collect();
+
+    let mut response = json!({
+        "id": completion_id(body.get("id")),
+        "object": "text_completion",
+        "created": body.get("created").cloned().unwrap_or(Value::Null),
+        "model": body.get("model").cloned().unwrap_or(Value::Null),
+        "choices": choices,
+    });
+    if let Some(usage) = body.get("usage") {
+        response["usage"] = usage.clone();
+    }
+    response
+}
+
+/// Converts chat completion chunks into legacy completion chunks
+#[derive(Debug, Default)]
+pub struct CompletionStreamTranslator {
+    parser: SseParser,
+    echo: Option<String>,
+    /// Choice indexes whose echoed prompt has been sent
+    echoed: Vec<u64>,
+}
+
+impl CompletionStreamTranslator {
+    pub fn new(echo: Option<String>) -> Self {
+        Self {
+            echo,
+            ..Self::default()
+        }
+    }
+
+    fn translate_chunk(&mut self, chunk: &Value) -> Value {
+        let mut choices = Vec::new();
+        for choice in chunk
+            .get("choices")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let index = choice.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+            let mut text = String::new();
+            if let Some(echo) = &self.echo {
+                if !self.echoed.contains(&index) {
+                    self.echoed.push(index);
+                    text.push_str(echo);
+                }
+            }
+            if let Some(content) = choice.pointer("/delta/content").and_then(|c| c.as_str()) {
+                text.push_str(content);
+            }
+            choices.push(json!({
+                "text": text,
+                "index": index,
+                "logprobs": null,
+                "finish_reason": choice.get("finish_reason").cloned().unwrap_or(Value::Null),
+            }));
+        }
+
+        let mut out = json!({
+            "id": completion_id(chunk.get("id")),
+            "object": "text_completion",
+            "created": chunk.get("created").cloned().unwrap_or(Value::Null),
+            "model": chunk.get("model").cloned().unwrap_or(Value::Null),
+            "choices": choices,
+        });
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            out["usage"] = usage.clone();
+        }
+        out
+    }
+}
+
+impl StreamTranslator for CompletionStreamTranslator {
+    fn push(&mut self, chunk: &[u8]) -> String {
+        let mut out = String::new();
+        for payload in self.parser.push(chunk) {
+            if payload == "[DONE]" {
+                out.push_str("data: [DONE]\n\n");
+                continue;
+            }
+            let Ok(chunk) = serde_json::from_str::<Value>(&payload) else {
+                continue;
+            };
+            out.push_str(&format!("data: {}\n\n", self.translate_chunk(&chunk)));
+        }
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emulated_completion() {
+        let request = json!({
+            "model": "davinci-002",
+            "prompt": ["def add(a, b):"],
+            "max_tokens": 32,
+            "echo": true,
+            "suffix": "\n",
+        });
+        let chat = completion_to_chat_request(&request).unwrap();
+        assert_eq!(chat["messages"][0]["content"], "def add(a, b):");
+        assert_eq!(chat["max_tokens"], 32);
+        assert!(chat.get("suffix").is_none());
+        let batched = json!({"prompt": ["a", "b"]});
+        assert!(completion_to_chat_request(&batched).is_err());
+        assert_eq!(routing_request(&batched)["messages"][1]["content"], "b");
+
+        let echo = echo_prefix(&request);
+        let response = chat_to_completion_response(
+            &json!({
+                "id": "chatcmpl-1",
+                "model": "gpt-4o-mini",
+                "choices": [{"index": 0, "message": {"content": " return a + b"}, "finish_reason": "stop"}],
+                "usage": {"prompt_tokens": 5, "completion_tokens": 4},
+            }),
+            echo.as_deref(),
+        );
+        assert_eq!(response["id"], "cmpl-1");
+        assert_eq!(response["object"], "text_completion");
+        assert_eq!(
+            response["choices"][0]["text"],
+            "def add(a, b): return a + b"
+        );
+        assert_eq!(response["usage"]["completion_tokens"], 4);
+
+        let mut translator = CompletionStreamTranslator::new(None);
+        let out = translator.push(
+            b"data: {\"id\":\"chatcmpl-2\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"Hi\"}}]}\n\ndata: [DONE]\n\n",
+        );
+        assert!(out.contains("\"text\":\"Hi\""));
+        assert!(out.contains("\"id\":\"cmpl-2\""));
+        assert!(out.ends_with("data: [DONE]\n\n"));
+    }
+}
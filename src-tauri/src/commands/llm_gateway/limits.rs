@@ -0,0 +1,364 @@
+//! Rate Limits - Sliding-window RPM/TPM accounting per provider model
+//!
+//! Providers enforce per-model request and token budgets. Tracking them locally lets the
+//! gateway reroute or wait before a request would be rejected with a 429.
+//!
+//! When a provider rejects a request with a 429 anyway, dispatch to it is paused for the
+//! window its `Retry-After` or rate-limit reset headers name, and requests are rerouted
+//! or queued in the meantime just as for local limits.
+
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::router::RouteTarget;
+use super::usage::TokenEstimator;
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// Pause after a 429 that doesn't say how long to back off
+pub const DEFAULT_THROTTLE_PAUSE: Duration = Duration::from_secs(10);
+/// Longest pause a provider's headers can ask for
+const MAX_THROTTLE_PAUSE: Duration = Duration::from_secs(600);
+/// Flat token cost of an image, whatever its encoded size
+const IMAGE_TOKENS: u64 = 1600;
+
+/// Configured per-minute limits of a model
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelLimits {
+    pub rpm: Option<u32>,
+    pub tpm: Option<u32>,
+}
+
+impl ModelLimits {
+    /// Limits configured for the routed model, if it is listed by its provider
+    pub fn for_route(route: &RouteTarget) -> Self {
+        route
+            .provider
+            .models
+            .iter()
+            .find(|m| m.id == route.model)
+            .map(|m| Self {
+                rpm: m.rpm_limit,
+                tpm: m.tpm_limit,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.rpm.is_none() && self.tpm.is_none()
+    }
+}
+
+fn push_value(estimator: &mut TokenEstimator, value: &Value) -> u64 {
+    match value {
+        Value::Object(obj) => {
+            // Base64 image data says nothing about the image's token cost
+            if matches!(
+                obj.get("type").and_then(|t| t.as_str()),
+                Some("image") | Some("image_url")
+            ) {
+                return IMAGE_TOKENS;
+            }
+            obj.iter()
+                .map(|(key, value)| {
+                    estimator.push(key);
+                    push_value(estimator, value)
+                })
+                .sum()
+        }
+        Value::Array(items) => items.iter().map(|v| push_value(estimator, v)).sum(),
+        Value::String(text) => {
+            estimator.push(text);
+            0
+        }
+        other => {
+            estimator.push(&other.to_string());
+            0
+        }
+    }
+}
+
+/// Rough token estimate of any part of a request
+pub fn estimate_value_tokens(value: &Value) -> u64 {
+    let mut estimator = TokenEstimator::default();
+    let images = push_value(&mut estimator, value);
+    estimator.tokens() + images
+}
+
+/// Rough prompt token estimate of a chat request
+pub fn estimate_prompt_tokens(request: &Value) -> u64 {
+    ["system", "messages", "tools"]
+        .iter()
+        .filter_map(|field| request.get(*field))
+        .map(estimate_value_tokens)
+        .sum()
+}
+
+/// Completion tokens a request asks for
+pub fn completion_budget(request: &Value) -> u64 {
+    request
+        .get("max_tokens")
+        .or_else(|| request.get("max_completion_tokens"))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0)
+}
+
+/// Rough token estimate for admission: the prompt estimate plus the requested
+/// completion budget.
+pub fn estimate_tokens(request: &Value) -> u32 {
+    (estimate_prompt_tokens(request) + completion_budget(request)) as u32
+}
+
+/// A pause of `seconds` as read from a header, which may hold any number at all, capped
+/// at [`MAX_THROTTLE_PAUSE`]
+fn throttle_pause(seconds: f64) -> Duration {
+    Duration::try_from_secs_f64(seconds.max(0.0))
+        .map_or(MAX_THROTTLE_PAUSE, |pause| pause.min(MAX_THROTTLE_PAUSE))
+}
+
+/// Parse a rate-limit reset duration in OpenAI's format, such as `1s`, `6m0s` or `20ms`
+fn parse_reset(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..split].parse().ok()?;
+        rest = &rest[split..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_len] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" | "" => 1.0,
+            "ms" => 0.001,
+            _ => return None,
+        };
+        total += number * seconds;
+        rest = &rest[unit_len..];
+    }
+    Some(throttle_pause(total))
+}
+
+/// How long a 429 response asks clients to back off, from `retry-after-ms`,
+/// `Retry-After` (seconds or an HTTP date) or OpenAI's `x-ratelimit-reset-*` headers
+pub fn retry_after(headers: &reqwest::header::HeaderMap, now: SystemTime) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let window = if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        throttle_pause(ms / 1000.0)
+    } else if let Some(value) = header("retry-after") {
+        match value.trim().parse::<f64>() {
+            Ok(seconds) => throttle_pause(seconds),
+            Err(_) => {
+                let at: SystemTime = chrono::DateTime::parse_from_rfc2822(value).ok()?.into();
+                at.duration_since(now).unwrap_or_default()
+            }
+        }
+    } else {
+        ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"]
+            .into_iter()
+            .filter_map(|name| header(name).and_then(parse_reset))
+            .max()?
+    };
+    Some(window.min(MAX_THROTTLE_PAUSE))
+}
+
+/// Per-model sliding windows of admitted requests, and providers paused after a 429
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    windows: Mutex<HashMap<String, VecDeque<(Instant, u32)>>>,
+    paused: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    /// Hold requests to `provider` back until `until`
+    pub fn pause(&self, provider: &str, until: Instant) {
+        let mut paused = self.paused.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = paused.entry(provider.to_string()).or_insert(until);
+        *entry = (*entry).max(until);
+    }
+
+    /// Time left until `provider` takes requests again, if it is paused
+    pub fn paused_for(&self, provider: &str, now: Instant) -> Option<Duration> {
+        let paused = self.paused.lock().unwrap_or_else(|e| e.into_inner());
+        paused
+            .get(provider)
+            .filter(|until| **until > now)
+            .map(|until| *until - now)
+    }
+
+    /// Admit a request of `tokens` against `key`'s limits, or return how long to wait
+    /// until it would fit.
+    pub fn try_acquire(
+        &self,
+        key: &str,
+        limits: ModelLimits,
+        tokens: u32,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        if limits.is_unlimited() {
+            return Ok(());
+        }
+
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let window = windows.entry(key.to_string()).or_default();
+        while window
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= WINDOW)
+        {
+            window.pop_front();
+        }
+
+        let expires = |index: usize| window[index].0 + WINDOW - now;
+        let mut wait = Duration::ZERO;
+
+        if let Some(rpm) = limits.rpm.map(|r| r.max(1) as usize) {
+            if window.len() >= rpm {
+                wait = wait.max(expires(window.len() - rpm));
+            }
+        }
+
+        if let Some(tpm) = limits.tpm {
+            let used: u32 = window.iter().map(|(_, t)| *t).sum();
+            // A single oversized request is admitted into an empty window
+            if used > 0 && used.saturating_add(tokens) > tpm {
+                let mut freed = 0u32;
+                for (index, (_, t)) in window.iter().enumerate() {
+                    freed += t;
+                    if used - freed + tokens <= tpm || freed == used {
+                        wait = wait.max(expires(index));
+                        break;
+                    }
+                }
+            }
+        }
+
+        if wait > Duration::ZERO {
+            return Err(wait);
+        }
+        window.push_back((now, tokens));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after() {
+        let now = SystemTime::now();
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut headers = reqwest::header::HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", "7")]), now),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(
+            retry_after(
+                &headers(&[("retry-after-ms", "1500"), ("retry-after", "7")]),
+                now
+            ),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            retry_after(
+                &headers(&[
+                    ("x-ratelimit-reset-requests", "1m30s"),
+                    ("x-ratelimit-reset-tokens", "250ms"),
+                ]),
+                now
+            ),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", "86400")]), now),
+            Some(MAX_THROTTLE_PAUSE)
+        );
+        // Absurd values are capped rather than trusted
+        for (name, value) in [
+            ("retry-after", "inf"),
+            ("retry-after-ms", "1e300"),
+            ("x-ratelimit-reset-tokens", &"9".repeat(400)),
+        ] {
+            assert_eq!(
+                retry_after(&headers(&[(name, value)]), now),
+                Some(MAX_THROTTLE_PAUSE)
+            );
+        }
+        assert_eq!(
+            retry_after(&headers(&[("retry-after", "NaN")]), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after(&headers(&[]), now), None);
+
+        let limiter = RateLimiter::default();
+        let start = Instant::now();
+        limiter.pause("openai", start + Duration::from_secs(5));
+        assert_eq!(
+            limiter.paused_for("openai", start),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            limiter.paused_for("openai", start + Duration::from_secs(5)),
+            None
+        );
+        assert_eq!(limiter.paused_for("anthropic", start), None);
+    }
+
+    #[test]
+    fn test_rpm_limit() {
+        let limiter = RateLimiter::default();
+        let limits = ModelLimits {
+            rpm: Some(2),
+            tpm: None,
+        };
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire("m", limits, 10, start).is_ok());
+        assert!(limiter
+            .try_acquire("m", limits, 10, start + Duration::from_secs(10))
+            .is_ok());
+        let wait = limiter
+            .try_acquire("m", limits, 10, start + Duration::from_secs(20))
+            .unwrap_err();
+        assert_eq!(wait, Duration::from_secs(40));
+
+        // The first request has left the window
+        assert!(limiter
+            .try_acquire("m", limits, 10, start + Duration::from_secs(61))
+            .is_ok());
+        // Other models are tracked separately
+        assert!(limiter.try_acquire("other", limits, 10, start).is_ok());
+    }
+
+    #[test]
+    fn test_tpm_limit() {
+        let limiter = RateLimiter::default();
+        let limits = ModelLimits {
+            rpm: None,
+            tpm: Some(1000),
+        };
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire("m", limits, 600, start).is_ok());
+        let wait = limiter
+            .try_acquire("m", limits, 600, start + Duration::from_secs(30))
+            .unwrap_err();
+        assert_eq!(wait, Duration::from_secs(30));
+        assert!(limiter
+            .try_acquire("m", limits, 300, start + Duration::from_secs(30))
+            .is_ok());
+    }
+}
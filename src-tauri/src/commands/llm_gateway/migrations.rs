@@ -0,0 +1,205 @@
+//! Migrations - Versioned schema changes of the gateway tables
+//!
+//! Gateway tables live in the app database next to the agent tables. Each schema change
+//! is a numbered [`Migration`], applied once, in order, in its own transaction when the
+//! app starts; applied versions are recorded in `gateway_schema_migrations`. A database
+//! migrated by a newer build is left alone rather than written with an older schema.
+//!
+//! New tables and columns get a new migration at the end of [`MIGRATIONS`]; released
+//! migrations are never edited.
+
+use rusqlite::{params, Connection};
+
+use super::{audit, batches, reports, templates, usage};
+
+/// A numbered schema change
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+/// Every migration, oldest first. The first ones create the tables that predate
+/// migrations, upgrading tables created by older builds in place.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Request log",
+        apply: usage::ensure_schema,
+    },
+    Migration {
+        version: 2,
+        description: "Prompt templates",
+        apply: templates::ensure_schema,
+    },
+    Migration {
+        version: 3,
+        description: "Batches",
+        apply: batches::ensure_schema,
+    },
+    Migration {
+        version: 4,
+        description: "Usage reports",
+        apply: reports::ensure_schema,
+    },
+    Migration {
+        version: 5,
+        description: "Settings audit log",
+        apply: audit::ensure_schema,
+    },
+    Migration {
+        version: 6,
+        description: "Request log client keys",
+        apply: usage::add_client_key,
+    },
+    Migration {
+        version: 7,
+        description: "Request log stream timing",
+        apply: usage::add_stream_timing,
+    },
+    Migration {
+        version: 8,
+        description: "Request log time index",
+        apply: usage::add_created_at_index,
+    },
+    Migration {
+        version: 9,
+        description: "Request log provider names",
+        apply: usage::add_provider_name,
+    },
+];
+
+/// Add a column unless the table already has it
+pub fn add_column(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists = conn
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+            table
+        ))?
+        .exists([column])?;
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Latest migration applied to the database
+pub fn current_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS gateway_schema_migrations (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM gateway_schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Apply the pending migrations, returning how many were applied
+pub fn run(conn: &Connection) -> Result<usize, String> {
+    let current = current_version(conn).map_err(|e| e.to_string())?;
+    let latest = MIGRATIONS.last().map_or(0, |m| m.version);
+    if current > latest {
+        return Err(format!(
+            "Database schema version {} is newer than this build supports ({}); update the app",
+            current, latest
+        ));
+    }
+
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+    for migration in &pending {
+        let apply = || -> rusqlite::Result<()> {
+            let tx = conn.unchecked_transaction()?;
+            (migration.apply)(&tx)?;
+            tx.execute(
+                "INSERT INTO gateway_schema_migrations (version, description) VALUES (?1, ?2)",
+                params![migration.version, migration.description],
+            )?;
+            tx.commit()
+        };
+        apply().map_err(|e| {
+            format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.description, e
+            )
+        })?;
+        log::info!(
+            "Applied gateway migration {}: {}",
+            migration.version,
+            migration.description
+        );
+    }
+    Ok(pending.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations() {
+        assert!(MIGRATIONS
+            .windows(2)
+            .all(|w| w[1].version == w[0].version + 1));
+
+        // A request log from before project and session tracking
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE gateway_request_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                requested_model TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                status_code INTEGER NOT NULL,
+                latency_ms INTEGER,
+                input_tokens INTEGER,
+                output_tokens INTEGER,
+                cost_usd REAL,
+                retries INTEGER NOT NULL DEFAULT 0,
+                experiment TEXT,
+                arm TEXT
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO gateway_request_log (requested_model, provider, model, status_code, cost_usd)
+             VALUES ('gpt-4o', 'openai', 'gpt-4o', 200, 0.5)",
+            [],
+        )
+        .unwrap();
+
+        assert_eq!(run(&conn).unwrap(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), MIGRATIONS.len() as u32);
+        assert_eq!(run(&conn).unwrap(), 0);
+        let (project, cost): (Option<String>, f64) = conn
+            .query_row(
+                "SELECT project, cost_usd FROM gateway_request_log",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((project, cost), (None, 0.5));
+
+        conn.execute(
+            "INSERT INTO gateway_schema_migrations (version, description) VALUES (99, 'future')",
+            [],
+        )
+        .unwrap();
+        assert!(run(&conn).unwrap_err().contains("newer than this build"));
+    }
+}
@@ -0,0 +1,190 @@
+//! Mock Provider - Canned chat completions for offline development and tests
+//!
+//! A `mock` provider never leaves the machine. It answers every chat completion with a
+//! deterministic reply: the first scripted response whose pattern matches the last user
+//! message, or an echo of that message. Latency and a share of failed requests can be
+//! simulated, so routing, failover and retries can be exercised without real APIs.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::limits::estimate_prompt_tokens;
+use super::router::roll_percent;
+use super::usage::estimate_text_tokens;
+use super::ProviderConfig;
+
+/// Behavior of a mock provider
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MockConfig {
+    /// Delay before every answer
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Share of requests failed with `failure_status`, 0.0-1.0
+    #[serde(default)]
+    pub failure_rate: f64,
+    /// Status of simulated failures; 503 when unset
+    #[serde(default)]
+    pub failure_status: Option<u16>,
+    /// Scripted answers, tried in order
+    #[serde(default)]
+    pub responses: Vec<MockResponse>,
+}
+
+/// Answer to requests whose last user message matches a pattern
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MockResponse {
+    /// Regular expression matched against the last user message
+    pub pattern: String,
+    /// Reply content
+    #[serde(default)]
+    pub content: String,
+    /// Fail with this status instead of replying
+    #[serde(default)]
+    pub status: Option<u16>,
+}
+
+/// Text of the last user message of a chat request
+fn last_user_message(body: &Value) -> String {
+    let messages = body["messages"].as_array().cloned().unwrap_or_default();
+    let Some(message) = messages.iter().rev().find(|m| m["role"] == "user") else {
+        return String::new();
+    };
+    match &message["content"] {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Error body in OpenAI's format
+fn error_body(status: u16, message: &str) -> Value {
+    let kind = match status {
+        429 => "rate_limit_error",
+        400..=499 => "invalid_request_error",
+        _ => "server_error",
+    };
+    json!({"error": {"message": message, "type": kind}})
+}
+
+/// Status and body answering a chat request to `model`; `failed` makes it a simulated
+/// failure
+pub fn reply(config: &MockConfig, model: &str, body: &Value, failed: bool) -> (u16, Value) {
+    if failed {
+        let status = config.failure_status.unwrap_or(503);
+        return (
+            status,
+            error_body(status, "Simulated mock provider failure"),
+        );
+    }
+
+    let prompt = last_user_message(body);
+    let scripted = config
+        .responses
+        .iter()
+        .find(|response| match Regex::new(&response.pattern) {
+            Ok(regex) => regex.is_match(&prompt),
+            Err(e) => {
+                log::warn!(
+                    "Skipping mock response with pattern {}: {}",
+                    response.pattern,
+                    e
+                );
+                false
+            }
+        });
+    if let Some(status) = scripted.and_then(|r| r.status) {
+        return (status, error_body(status, "Scripted mock provider failure"));
+    }
+    let content = match scripted {
+        Some(response) => response.content.clone(),
+        None => format!("Mock reply to: {}", prompt),
+    };
+
+    let prompt_tokens = estimate_prompt_tokens(body);
+    let completion_tokens = estimate_text_tokens(&content);
+    let completion = json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion",
+        "created": 0,
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop",
+        }],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens,
+        },
+    });
+    (200, completion)
+}
+
+/// Answer a chat request to a mock provider after its simulated latency
+pub async fn respond(provider: &ProviderConfig, model: &str, body: &Value) -> (u16, Value) {
+    let config = provider.mock.clone().unwrap_or_default();
+    if config.latency_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(config.latency_ms)).await;
+    }
+    let failed = (roll_percent() as f64) < config.failure_rate * 100.0;
+    reply(&config, model, body, failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply() {
+        let config = MockConfig {
+            responses: vec![
+                MockResponse {
+                    pattern: "(?i)weather".to_string(),
+                    content: "Sunny".to_string(),
+                    status: None,
+                },
+                MockResponse {
+                    pattern: "^overload".to_string(),
+                    status: Some(429),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let request = |text: &str| {
+            json!({"messages": [
+                {"role": "system", "content": "Be brief"},
+                {"role": "user", "content": text}
+            ]})
+        };
+
+        let (status, body) = reply(
+            &config,
+            "mock-model",
+            &request("What's the Weather?"),
+            false,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["choices"][0]["message"]["content"], "Sunny");
+        assert_eq!(body["model"], "mock-model");
+
+        let (_, body) = reply(&config, "mock-model", &request("ping"), false);
+        assert_eq!(
+            body["choices"][0]["message"]["content"],
+            "Mock reply to: ping"
+        );
+
+        let (status, body) = reply(&config, "mock-model", &request("overloaded"), false);
+        assert_eq!(status, 429);
+        assert_eq!(body["error"]["type"], "rate_limit_error");
+
+        assert_eq!(reply(&config, "mock-model", &request("ping"), true).0, 503);
+    }
+}
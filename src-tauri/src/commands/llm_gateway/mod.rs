@@ -0,0 +1,3357 @@
+//! LLM Gateway Module - Multi-Model API Proxy for Claude Code
+//!
+//! This module provides a local HTTP server that acts as a proxy between Claude Code
+//! and various LLM providers (OpenAI, DeepSeek, Moonshot, Qwen, etc.).
+//!
+//! Key features:
+//! - Unified API interface compatible with Anthropic/OpenAI formats
+//! - Intelligent model routing based on task type
+//! - Cost optimization through smart model selection
+//! - Failover support when primary providers are unavailable
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::RwLock;
+
+use crate::commands::agents::AgentDb;
+
+pub mod adapter;
+mod audio;
+mod audit;
+pub mod backup;
+mod batches;
+mod caching;
+mod cassette;
+mod chaos;
+mod client;
+mod context;
+mod copilot;
+mod currency;
+mod dashscope;
+mod dedup;
+mod documents;
+mod dryrun;
+mod embeddings;
+mod errors;
+mod forecast;
+mod gemini;
+mod guardrails;
+mod hardware;
+mod images;
+mod import;
+mod interpolate;
+mod instances;
+pub mod keys;
+mod legacy;
+mod limits;
+pub mod migrations;
+mod mock;
+mod moderation;
+mod moonshot;
+mod notify;
+mod ollama;
+mod ollama_server;
+mod portable;
+pub mod pricing;
+mod profiles;
+mod queue;
+mod quirks;
+mod quota;
+mod reasoning;
+mod redact;
+pub mod registry;
+mod replicate;
+pub mod reports;
+mod responses;
+mod router;
+mod rules;
+mod scrub;
+mod selftest;
+mod server;
+mod slo;
+mod sse;
+mod structured;
+mod subscription;
+pub mod templates;
+mod transform;
+mod translate;
+pub mod tray;
+pub mod usage;
+mod vault;
+mod vision;
+mod watchdog;
+mod webhooks;
+mod zhipu;
+
+use adapter::AdapterSpec;
+use audit::AuditEntry;
+use backup::{BackupConfig, BackupInfo};
+use batches::{BatchJob, BatchResult};
+use cassette::CassetteConfig;
+use chaos::ChaosConfig;
+use client::{PoolConfig, ProxyConfig, RetryPolicy, TlsConfig};
+use context::ContextOverflow;
+use currency::CurrencyConfig;
+use forecast::ForecastReport;
+use guardrails::GuardrailAction;
+use hardware::{HardwareUsage, SaturationGuard};
+use instances::{GatewayInstance, InstanceConfig, InstanceStatus};
+use keys::KeyRotation;
+use mock::MockConfig;
+use moderation::ModerationAction;
+use notify::NotificationConfig;
+use ollama_server::OllamaConfig;
+use pricing::{ModelPricing, PriceTier, PriceWindow, PricingSyncResult};
+use profiles::GatewayProfiles;
+use quota::ProviderQuota;
+use reasoning::ReasoningOutput;
+use redact::PiiKind;
+use registry::{ModelWarning, RegistrySyncResult};
+use reports::{ReportConfig, ReportPeriod, UsageGrouping, UsageReport};
+use router::MaintenanceWindow;
+use rules::{RoutingRule, RuleExplanation, RuleInput};
+use slo::SloConfig;
+use structured::StructuredOutputMode;
+use templates::PromptTemplate;
+use usage::{AbTestArmStats, ModelLatency};
+use watchdog::WatchdogConfig;
+use webhooks::WebhookConfig;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// Supported LLM providers
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LLMProvider {
+    Anthropic,
+    OpenAI,
+    Gemini,
+    DeepSeek,
+    Moonshot,
+    Qwen,
+    Zhipu,
+    Groq,
+    Ollama,
+    OpenRouter,
+    HuggingFace,
+    Replicate,
+    Copilot,
+    Mock,
+    #[default]
+    Custom,
+}
+
+impl std::fmt::Display for LLMProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LLMProvider::Anthropic => write!(f, "anthropic"),
+            LLMProvider::OpenAI => write!(f, "openai"),
+            LLMProvider::Gemini => write!(f, "gemini"),
+            LLMProvider::DeepSeek => write!(f, "deepseek"),
+            LLMProvider::Moonshot => write!(f, "moonshot"),
+            LLMProvider::Qwen => write!(f, "qwen"),
+            LLMProvider::Zhipu => write!(f, "zhipu"),
+            LLMProvider::Groq => write!(f, "groq"),
+            LLMProvider::Ollama => write!(f, "ollama"),
+            LLMProvider::OpenRouter => write!(f, "openrouter"),
+            LLMProvider::HuggingFace => write!(f, "huggingface"),
+            LLMProvider::Replicate => write!(f, "replicate"),
+            LLMProvider::Copilot => write!(f, "copilot"),
+            LLMProvider::Mock => write!(f, "mock"),
+            LLMProvider::Custom => write!(f, "custom"),
+        }
+    }
+}
+
+/// Provider configuration
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    /// Provider identifier
+    pub provider: LLMProvider,
+    /// Display name
+    pub name: String,
+    /// API base URL; may reference environment variables as `${VAR}`
+    pub base_url: String,
+    /// API key (stored securely), or an environment reference like `${OPENAI_API_KEY}`
+    pub api_key: Option<String>,
+    /// Whether this provider is enabled
+    pub enabled: bool,
+    /// Priority for model routing (lower = higher priority)
+    pub priority: i32,
+    /// Supported models
+    pub models: Vec<ModelConfig>,
+    /// Custom headers
+    pub headers: HashMap<String, String>,
+    /// Declarative adapter overriding the built-in request handling
+    #[serde(default)]
+    pub adapter: Option<AdapterSpec>,
+    /// Additional API keys rotated together with `api_key`
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// How requests are spread across the keys
+    #[serde(default)]
+    pub key_rotation: KeyRotation,
+    /// Largest inline image the provider accepts, in bytes; bigger images are re-encoded
+    #[serde(default)]
+    pub max_image_bytes: Option<u64>,
+    /// Largest image width/height the provider accepts; bigger images are downscaled
+    #[serde(default)]
+    pub max_image_dimension: Option<u32>,
+    /// Whether the provider accepts PDF `file` content parts; otherwise document text is
+    /// extracted locally
+    #[serde(default)]
+    pub supports_file_input: bool,
+    /// How structured output requests are served; inferred from the provider when unset
+    #[serde(default)]
+    pub structured_output: Option<StructuredOutputMode>,
+    /// Connection timeout in seconds; unset uses `timeout_seconds`
+    #[serde(default)]
+    pub connect_timeout_seconds: Option<u32>,
+    /// Request timeout in seconds, overriding the gateway's `timeout_seconds`
+    #[serde(default)]
+    pub timeout_seconds: Option<u32>,
+    /// Retries of failed requests; unset sends each request once
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    /// Outbound proxy for this provider, overriding the gateway's `proxy`
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// TLS options for this provider, replacing the gateway's `tls`
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Connection pool tuning for this provider, replacing the gateway's `pool`
+    #[serde(default)]
+    pub pool: Option<PoolConfig>,
+    /// Host names resolved to fixed addresses instead of through DNS; each value lists
+    /// comma-separated IP addresses, optionally with ports
+    #[serde(default)]
+    pub dns_overrides: BTreeMap<String, String>,
+    /// Alternate base URLs tried in order when `base_url` is unreachable
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    /// Offset from UTC of the provider's local time, in minutes, which model price
+    /// windows follow
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    /// Monthly spend ceiling in USD; once reached the provider isn't routed to until the
+    /// next month
+    #[serde(default)]
+    pub monthly_spend_cap_usd: Option<f64>,
+    /// Use the provider's native API instead of its OpenAI-compatible endpoint, where
+    /// the gateway supports one (Qwen's DashScope)
+    #[serde(default)]
+    pub native_api: bool,
+    /// Forward clients' Claude subscription OAuth tokens instead of `api_key` on native
+    /// Messages requests (Anthropic providers only)
+    #[serde(default)]
+    pub oauth_passthrough: bool,
+    /// `OpenAI-Organization` sent with every request, for keys belonging to several
+    /// organizations; may reference environment variables
+    #[serde(default)]
+    pub openai_organization: Option<String>,
+    /// `OpenAI-Project` sent with every request; may reference environment variables
+    #[serde(default)]
+    pub openai_project: Option<String>,
+    /// Scheduled downtime during which the provider isn't routed to
+    #[serde(default)]
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+    /// Latency and error objectives; breaching them lowers the provider's priority
+    #[serde(default)]
+    pub slo: Option<SloConfig>,
+    /// Model loading on the local server (Ollama providers only)
+    #[serde(default)]
+    pub ollama: Option<OllamaConfig>,
+    /// Skip this provider for large prompts while its machine is saturated (local
+    /// providers only)
+    #[serde(default)]
+    pub saturation_guard: Option<SaturationGuard>,
+    /// Canned replies, latency and failures (mock providers only)
+    #[serde(default)]
+    pub mock: Option<MockConfig>,
+}
+
+// Keys and credential headers stay out of debug output
+impl std::fmt::Debug for ProviderConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let masked = |key: &String| {
+            if key.is_empty() {
+                String::new()
+            } else {
+                scrub::SCRUBBED.to_string()
+            }
+        };
+        let headers: Vec<String> = self
+            .headers
+            .iter()
+            .map(|(name, value)| scrub::scrub(&format!("{}: {}", name, value)))
+            .collect();
+        f.debug_struct("ProviderConfig")
+            .field("provider", &self.provider)
+            .field("name", &self.name)
+            .field("base_url", &scrub::scrub(&self.base_url))
+            .field("api_key", &self.api_key.as_ref().map(masked))
+            .field("enabled", &self.enabled)
+            .field("priority", &self.priority)
+            .field("models", &self.models)
+            .field("headers", &headers)
+            .field("adapter", &self.adapter)
+            .field(
+                "api_keys",
+                &self.api_keys.iter().map(masked).collect::<Vec<_>>(),
+            )
+            .field("key_rotation", &self.key_rotation)
+            .field("max_image_bytes", &self.max_image_bytes)
+            .field("max_image_dimension", &self.max_image_dimension)
+            .field("supports_file_input", &self.supports_file_input)
+            .field("structured_output", &self.structured_output)
+            .field("connect_timeout_seconds", &self.connect_timeout_seconds)
+            .field("timeout_seconds", &self.timeout_seconds)
+            .field("retry", &self.retry)
+            .field("proxy", &self.proxy)
+            .field("tls", &self.tls)
+            .field("pool", &self.pool)
+            .field("dns_overrides", &self.dns_overrides)
+            .field("mirrors", &self.mirrors)
+            .field("utc_offset_minutes", &self.utc_offset_minutes)
+            .field("monthly_spend_cap_usd", &self.monthly_spend_cap_usd)
+            .field("native_api", &self.native_api)
+            .field("oauth_passthrough", &self.oauth_passthrough)
+            .field("openai_organization", &self.openai_organization)
+            .field("openai_project", &self.openai_project)
+            .field("maintenance_windows", &self.maintenance_windows)
+            .field("slo", &self.slo)
+            .field("ollama", &self.ollama)
+            .field("saturation_guard", &self.saturation_guard)
+            .field("mock", &self.mock)
+            .finish()
+    }
+}
+
+impl ProviderConfig {
+    /// Headers sent with every request to the provider: the OpenAI account scope, if
+    /// set, and the custom headers
+    pub fn request_headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        [
+            ("OpenAI-Organization", &self.openai_organization),
+            ("OpenAI-Project", &self.openai_project),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.as_deref().filter(|v| !v.is_empty())?)))
+        .chain(
+            self.headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        )
+    }
+}
+
+/// Model configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelConfig {
+    /// Model ID as used by the provider
+    pub id: String,
+    /// Display name
+    pub name: String,
+    /// Model capabilities (coding, reasoning, creative, fast, vision, completions)
+    pub capabilities: Vec<String>,
+    /// Input price per 1M tokens (USD)
+    pub input_price: f64,
+    /// Output price per 1M tokens (USD)
+    pub output_price: f64,
+    /// Maximum context length
+    pub max_tokens: u32,
+    /// Whether this is the default model for this provider
+    pub is_default: bool,
+    /// Prices are placeholders because the model was discovered, not configured
+    #[serde(default)]
+    pub pricing_unknown: bool,
+    /// Requests-per-minute cap enforced by the provider
+    #[serde(default)]
+    pub rpm_limit: Option<u32>,
+    /// Tokens-per-minute cap enforced by the provider
+    #[serde(default)]
+    pub tpm_limit: Option<u32>,
+    /// Prompt cache read price per 1M tokens (USD); the provider's usual discount if unset
+    #[serde(default)]
+    pub cache_read_price: Option<f64>,
+    /// Prompt cache write price per 1M tokens (USD); the provider's usual surcharge if unset
+    #[serde(default)]
+    pub cache_write_price: Option<f64>,
+    /// Context overflow handling for this model, overriding the gateway-wide setting
+    #[serde(default)]
+    pub context_overflow: Option<ContextOverflow>,
+    /// Price per generated image (USD) for image models billed per image
+    #[serde(default)]
+    pub image_price: Option<f64>,
+    /// Price per minute of audio (USD) for transcription models billed by duration
+    #[serde(default)]
+    pub audio_price: Option<f64>,
+    /// Price per 1M input characters (USD) for speech models billed by character
+    #[serde(default)]
+    pub speech_price: Option<f64>,
+    /// Prices replacing `input_price` and `output_price` during parts of the day
+    #[serde(default)]
+    pub price_windows: Vec<PriceWindow>,
+    /// Prices replacing the token prices for requests with long prompts
+    #[serde(default)]
+    pub price_tiers: Vec<PriceTier>,
+    /// Flat charge per request (USD), such as a per-search fee
+    #[serde(default)]
+    pub request_fee: Option<f64>,
+    /// Percentage added to the cost of every request, such as OpenRouter's platform fee
+    #[serde(default)]
+    pub fee_percent: Option<f64>,
+    /// ISO 4217 code of the currency the prices are in; USD when unset
+    #[serde(default)]
+    pub currency: Option<String>,
+}
+
+/// LLM Gateway settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewaySettings {
+    /// Whether the gateway is enabled
+    pub enabled: bool,
+    /// Local server port
+    pub port: u16,
+    /// Auto-start gateway on app launch
+    pub auto_start: bool,
+    /// Default provider
+    pub default_provider: LLMProvider,
+    /// Enable intelligent routing
+    pub smart_routing: bool,
+    /// Enable cost optimization
+    pub cost_optimization: bool,
+    /// Failover enabled
+    pub failover_enabled: bool,
+    /// Request timeout in seconds: the whole request, or for streamed responses the
+    /// longest wait for the next chunk
+    pub timeout_seconds: u32,
+    /// Provider configurations
+    pub providers: Vec<ProviderConfig>,
+    /// Incoming model name rewrites; keys ending in `*` match by prefix
+    #[serde(default)]
+    pub model_aliases: HashMap<String, ModelAlias>,
+    /// Generic text-to-speech voice names mapped to a provider's model and voice
+    #[serde(default)]
+    pub voice_aliases: HashMap<String, VoiceAlias>,
+    /// Pricing manifest URL; `None` uses the default LiteLLM manifest
+    #[serde(default)]
+    pub pricing_manifest_url: Option<String>,
+    /// Local price overrides keyed by model ID or `provider/model`
+    #[serde(default)]
+    pub pricing_overrides: HashMap<String, ModelPricing>,
+    /// Maximum concurrent upstream requests; `None` = unlimited
+    #[serde(default)]
+    pub max_concurrent_requests: Option<u32>,
+    /// How long a request may wait for a concurrency slot; `None` = 60 seconds
+    #[serde(default)]
+    pub queue_timeout_seconds: Option<u32>,
+    /// A/B traffic splits, checked before aliases
+    #[serde(default)]
+    pub ab_tests: Vec<AbTest>,
+    /// Shadow traffic mirroring
+    #[serde(default)]
+    pub shadow: Option<ShadowConfig>,
+    /// Speculative parallel dispatch for latency-critical models
+    #[serde(default)]
+    pub speculative: Option<SpeculativeConfig>,
+    /// Whether reasoning model output is returned as thinking blocks or stripped
+    #[serde(default)]
+    pub reasoning_output: ReasoningOutput,
+    /// What to do with prompts exceeding the routed model's context window; unset
+    /// forwards them unchecked
+    #[serde(default)]
+    pub context_overflow: Option<ContextOverflow>,
+    /// Summarize turns dropped by `truncate` context fitting
+    #[serde(default)]
+    pub summarization: Option<SummarizationConfig>,
+    /// System prompt rewrites applied to requests routed to matching models
+    #[serde(default)]
+    pub system_prompt_rules: Vec<SystemPromptRule>,
+    /// Request rewrites applied to requests routed to matching models
+    #[serde(default)]
+    pub transform_rules: Vec<TransformRule>,
+    /// Masking of personal data and credentials in outgoing prompts
+    #[serde(default)]
+    pub redaction: Option<RedactionConfig>,
+    /// Keyword and topic blocklists checked before requests leave the machine
+    #[serde(default)]
+    pub guardrails: Vec<GuardrailRule>,
+    /// Detection and automatic restart of a server that exited unexpectedly
+    #[serde(default)]
+    pub watchdog: Option<WatchdogConfig>,
+    /// Screening of outgoing prompts with a moderation model
+    #[serde(default)]
+    pub moderation: Option<ModerationConfig>,
+    /// Outbound proxy for provider requests, applied when the gateway starts
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// TLS options for provider connections, applied when the gateway starts
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Connection pool tuning for provider connections, applied when the gateway starts
+    #[serde(default)]
+    pub pool: Option<PoolConfig>,
+    /// Daily and weekly usage reports
+    #[serde(default)]
+    pub reports: Option<ReportConfig>,
+    /// Monthly spend budget in USD, for notifications when reached or forecast to be
+    #[serde(default)]
+    pub monthly_budget_usd: Option<f64>,
+    /// Desktop notifications about spend and provider health
+    #[serde(default)]
+    pub notifications: Option<NotificationConfig>,
+    /// Endpoints notified of provider outages, budget alerts and request errors
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Automatic backups of the app database
+    #[serde(default)]
+    pub backups: Option<BackupConfig>,
+    /// Additional listeners, each with its own port and routing
+    #[serde(default)]
+    pub instances: Vec<InstanceConfig>,
+    /// Rules deciding routes before the requested model is resolved, in evaluation order
+    #[serde(default)]
+    pub routing_rules: Vec<RoutingRule>,
+    /// Repair of responses that fail the JSON schema their request declared
+    #[serde(default)]
+    pub json_repair: Option<JsonRepairConfig>,
+    /// Exchange rates for models priced in other currencies, and the currency costs are
+    /// shown in; everything is USD when unset
+    #[serde(default)]
+    pub currency: Option<CurrencyConfig>,
+    /// Refresh the model registry from the pricing manifest once a day
+    #[serde(default)]
+    pub model_registry_sync: bool,
+    /// Record upstream responses to disk, or replay recorded ones instead of calling
+    /// providers; applied when the gateway starts
+    #[serde(default)]
+    pub cassette: Option<CassetteConfig>,
+    /// Inject timeouts, 429s and malformed chunks into provider requests, to try out
+    /// retries and failover; applied when the gateway starts
+    #[serde(default)]
+    pub chaos: Option<ChaosConfig>,
+}
+
+/// Traffic split between two models for requests matching a model pattern
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AbTest {
+    /// Experiment name used to group recorded outcomes
+    pub name: String,
+    /// Whether the split is active
+    pub enabled: bool,
+    /// Requested model name to split; a trailing `*` matches by prefix
+    pub match_model: String,
+    /// Route for the remaining traffic
+    pub control: ModelAlias,
+    /// Route under evaluation
+    pub variant: ModelAlias,
+    /// Percentage (0-100) of matching traffic sent to the variant
+    pub variant_percent: u8,
+}
+
+/// Mirrors a sample of traffic to a secondary model whose responses are only logged
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ShadowConfig {
+    /// Experiment name the primary and shadow outcomes are recorded under
+    pub name: String,
+    /// Whether mirroring is active
+    pub enabled: bool,
+    /// Shadow route; the provider only needs to be configured, not enabled
+    pub target: ModelAlias,
+    /// Percentage (0-100) of requests to mirror
+    pub sample_percent: u8,
+}
+
+/// Races a second provider against the routed one and keeps the first successful answer.
+/// Every speculated request is paid for twice.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpeculativeConfig {
+    /// Whether fan-out is active
+    pub enabled: bool,
+    /// Requested model names to speculate on; a trailing `*` matches by prefix
+    pub match_models: Vec<String>,
+    /// Route raced against the primary one
+    pub secondary: ModelAlias,
+}
+
+/// How a system prompt rule combines its text with the client's system prompt
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemPromptAction {
+    Prepend,
+    Append,
+    Replace,
+}
+
+/// Rewrites the system prompt of requests routed to matching models
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SystemPromptRule {
+    /// Whether the rule is applied
+    pub enabled: bool,
+    /// Provider the rule applies to; every provider when unset
+    #[serde(default)]
+    pub provider: Option<LLMProvider>,
+    /// Routed model ID to match; a trailing `*` matches by prefix
+    pub match_model: String,
+    /// How the text is combined with the client's system prompt
+    pub action: SystemPromptAction,
+    /// Instructions to add
+    pub text: String,
+}
+
+/// Regex replacement applied to the text of the system prompt and messages
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextReplacement {
+    /// Regular expression to find
+    pub pattern: String,
+    /// Replacement text; `$1` or `${name}` refer to capture groups
+    pub replacement: String,
+}
+
+/// Rewrites requests routed to matching models
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransformRule {
+    /// Name shown in logs
+    pub name: String,
+    /// Whether the rule is applied
+    pub enabled: bool,
+    /// Provider the rule applies to; every provider when unset
+    #[serde(default)]
+    pub provider: Option<LLMProvider>,
+    /// Routed model ID to match; a trailing `*` matches by prefix
+    pub match_model: String,
+    /// Text replacements, applied in order
+    #[serde(default)]
+    pub replacements: Vec<TextReplacement>,
+    /// Headers added to the upstream request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Top-level request parameters to set; `null` removes the parameter
+    #[serde(default)]
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Masks sensitive values in prompts before they are sent upstream
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RedactionConfig {
+    /// Whether prompts are redacted
+    pub enabled: bool,
+    /// Providers whose requests are redacted; every provider when empty
+    #[serde(default)]
+    pub providers: Vec<LLMProvider>,
+    /// Built-in detectors to run
+    #[serde(default)]
+    pub detectors: Vec<PiiKind>,
+    /// Additional regular expressions whose matches are masked
+    #[serde(default)]
+    pub custom_patterns: Vec<String>,
+    /// Swap placeholders in responses back for the original values. Streamed responses
+    /// are then buffered upstream and replayed.
+    #[serde(default)]
+    pub restore_responses: bool,
+}
+
+/// Blocklist keeping requests on a topic away from providers
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GuardrailRule {
+    /// Topic name shown in errors and logs
+    pub name: String,
+    /// Whether the rule is checked
+    pub enabled: bool,
+    /// Providers the rule guards; every provider when empty
+    #[serde(default)]
+    pub providers: Vec<LLMProvider>,
+    /// Keywords matched case-insensitively as whole words
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Regular expressions matched case-insensitively
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Whether matching requests are rejected or served locally
+    #[serde(default)]
+    pub action: GuardrailAction,
+    /// Ollama model serving rerouted requests; the provider's default when unset
+    #[serde(default)]
+    pub local_model: Option<String>,
+}
+
+/// Moderation model that screens outgoing prompts.
+///
+/// The model's provider only needs to be configured, not enabled for routing.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModerationConfig {
+    /// Whether prompts are screened
+    pub enabled: bool,
+    /// OpenAI-compatible moderation model such as `omni-moderation-latest`
+    pub model: ModelAlias,
+    /// Whether flagged requests are blocked or only logged
+    #[serde(default)]
+    pub action: ModerationAction,
+    /// Categories that count as flagged; every category when empty
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+/// Cheap model that compresses the turns context truncation would drop into a summary.
+///
+/// The summarizer's provider only needs to be configured, not enabled for routing, so a
+/// local Ollama model can be used.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SummarizationConfig {
+    /// Whether dropped turns are summarized instead of discarded
+    pub enabled: bool,
+    /// Model writing the summaries
+    pub model: ModelAlias,
+    /// Tokens reserved for the summary (default 1000)
+    #[serde(default)]
+    pub max_summary_tokens: Option<u32>,
+}
+
+/// Cheap model that fixes responses failing the JSON schema their request declared
+/// (`response_format: json_schema` or a forced tool). Such requests are no longer
+/// streamed as they arrive, since the whole response has to be checked first.
+///
+/// Like the summarizer, the repair model's provider only needs to be configured.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JsonRepairConfig {
+    /// Whether invalid responses are repaired before they are returned
+    pub enabled: bool,
+    /// Model doing the repair
+    pub model: ModelAlias,
+}
+
+/// Provider/model pair an incoming model name is rewritten to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelAlias {
+    /// Target provider
+    pub provider: LLMProvider,
+    /// Model ID as understood by the target provider
+    pub model: String,
+}
+
+/// Speech model and voice a generic voice name (e.g. "narrator") is rewritten to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VoiceAlias {
+    /// Target provider
+    pub provider: LLMProvider,
+    /// Speech model ID as understood by the target provider
+    pub model: String,
+    /// Voice as understood by the target provider
+    pub voice: String,
+}
+
+impl Default for GatewaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8765,
+            auto_start: false,
+            default_provider: LLMProvider::OpenAI,
+            smart_routing: true,
+            cost_optimization: true,
+            failover_enabled: true,
+            timeout_seconds: 120,
+            providers: get_default_providers(),
+            model_aliases: HashMap::new(),
+            voice_aliases: HashMap::new(),
+            pricing_manifest_url: None,
+            pricing_overrides: HashMap::new(),
+            max_concurrent_requests: None,
+            queue_timeout_seconds: None,
+            ab_tests: Vec::new(),
+            shadow: None,
+            speculative: None,
+            reasoning_output: ReasoningOutput::default(),
+            context_overflow: None,
+            summarization: None,
+            system_prompt_rules: Vec::new(),
+            transform_rules: Vec::new(),
+            redaction: None,
+            guardrails: Vec::new(),
+            watchdog: None,
+            moderation: None,
+            proxy: None,
+            tls: None,
+            pool: None,
+            reports: None,
+            monthly_budget_usd: None,
+            notifications: None,
+            webhooks: Vec::new(),
+            backups: None,
+            instances: Vec::new(),
+            routing_rules: Vec::new(),
+            json_repair: None,
+            currency: None,
+            model_registry_sync: false,
+            cassette: None,
+            chaos: None,
+        }
+    }
+}
+
+/// Gateway status information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayStatus {
+    /// Whether the gateway server is running
+    pub running: bool,
+    /// Current port
+    pub port: u16,
+    /// Number of requests processed
+    pub requests_processed: u64,
+    /// Provider health status
+    pub provider_status: HashMap<String, ProviderStatus>,
+    /// Last error if any
+    pub last_error: Option<String>,
+    /// Configured models that are deprecated or misconfigured per the model registry
+    #[serde(default)]
+    pub model_warnings: Vec<ModelWarning>,
+}
+
+/// Provider status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderStatus {
+    /// Whether the provider is available
+    pub available: bool,
+    /// Last response time in ms
+    pub latency_ms: Option<u64>,
+    /// Last error message
+    pub last_error: Option<String>,
+    /// Requests count
+    pub request_count: u64,
+    /// Error count
+    pub error_count: u64,
+    /// Remaining rate limits and credit last reported by the provider
+    #[serde(default)]
+    pub quota: Option<ProviderQuota>,
+    /// Latest resource usage of a local provider's machine
+    #[serde(default)]
+    pub hardware: Option<HardwareUsage>,
+}
+
+/// Request/Response types for the gateway
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatRequest {
+    pub model: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    pub stream: Option<bool>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub id: String,
+    pub model: String,
+    pub content: String,
+    pub usage: Option<UsageInfo>,
+    pub provider: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageInfo {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub total_tokens: u32,
+}
+
+/// Result of probing a self-hosted OpenAI-compatible endpoint (vLLM, LM Studio, etc.)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderProbeResult {
+    /// Whether the /models endpoint answered successfully
+    pub reachable: bool,
+    /// Models discovered from the /models endpoint
+    pub models: Vec<ModelConfig>,
+    /// Whether a chat completion round-trip succeeded
+    pub chat_ok: bool,
+    /// Whether the endpoint answered a `stream: true` request with SSE
+    pub supports_streaming: bool,
+    /// Round-trip latency of the chat completion in ms
+    pub latency_ms: Option<u64>,
+    /// Start of the completion text returned by the round-trip
+    pub sample_response: Option<String>,
+    /// First error encountered while probing
+    pub error: Option<String>,
+}
+
+/// Outcome of refreshing one provider's model list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelRefreshResult {
+    /// Provider that was queried
+    pub provider: LLMProvider,
+    /// Provider display name
+    pub name: String,
+    /// IDs of models added to the provider configuration
+    pub added: Vec<String>,
+    /// Error returned by the provider's /models endpoint
+    pub error: Option<String>,
+}
+
+/// Outcome of checking one provider's prepaid credit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderCreditsResult {
+    /// Provider display name
+    pub name: String,
+    /// Credit reported by the provider
+    pub quota: Option<ProviderQuota>,
+    /// Error returned by the provider's credits endpoint
+    pub error: Option<String>,
+}
+
+/// Outcome of importing another tool's configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigImportResult {
+    /// Saved settings after the import
+    pub settings: GatewaySettings,
+    /// Names of the providers added or updated
+    pub providers: Vec<String>,
+    /// Entries that couldn't be imported, with the reason
+    pub skipped: Vec<String>,
+}
+
+/// Compact view of the gateway for the tray menu
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewaySnapshot {
+    pub running: bool,
+    /// Port the gateway serves on, or will when started
+    pub port: u16,
+    pub requests_processed: u64,
+    /// Provider and model requests for unknown models are routed to
+    pub current_provider: Option<String>,
+    pub current_model: Option<String>,
+    /// Cost of today's requests (local time)
+    pub today_spend_usd: f64,
+    pub providers: Vec<ProviderToggle>,
+}
+
+/// A configured provider and whether it takes requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderToggle {
+    pub provider: LLMProvider,
+    pub name: String,
+    pub enabled: bool,
+}
+
+// ============================================================================
+// Default Provider Configurations
+// ============================================================================
+
+fn get_default_providers() -> Vec<ProviderConfig> {
+    vec![
+        // OpenAI
+        ProviderConfig {
+            provider: LLMProvider::OpenAI,
+            name: "OpenAI".to_string(),
+            base_url: "https://api.openai.com/v1".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 1,
+            models: vec![
+                ModelConfig {
+                    id: "gpt-4o".to_string(),
+                    name: "GPT-4o".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string(), "creative".to_string(), "vision".to_string()],
+                    input_price: 2.5,
+                    output_price: 10.0,
+                    max_tokens: 128000,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "gpt-4o-mini".to_string(),
+                    name: "GPT-4o Mini".to_string(),
+                    capabilities: vec!["coding".to_string(), "fast".to_string(), "vision".to_string()],
+                    input_price: 0.15,
+                    output_price: 0.6,
+                    max_tokens: 128000,
+                    is_default: false,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "gpt-4-turbo".to_string(),
+                    name: "GPT-4 Turbo".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string(), "vision".to_string()],
+                    input_price: 10.0,
+                    output_price: 30.0,
+                    max_tokens: 128000,
+                    is_default: false,
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            supports_file_input: true,
+            ..Default::default()
+        },
+        // Google Gemini
+        ProviderConfig {
+            provider: LLMProvider::Gemini,
+            name: "Google Gemini".to_string(),
+            base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 2,
+            models: vec![
+                ModelConfig {
+                    id: "gemini-2.0-flash-exp".to_string(),
+                    name: "Gemini 2.0 Flash".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string(), "fast".to_string(), "vision".to_string()],
+                    input_price: 0.0,
+                    output_price: 0.0,
+                    max_tokens: 1048576,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "gemini-1.5-pro".to_string(),
+                    name: "Gemini 1.5 Pro".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string(), "creative".to_string(), "vision".to_string()],
+                    input_price: 1.25,
+                    output_price: 5.0,
+                    max_tokens: 2097152,
+                    is_default: false,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "gemini-1.5-flash".to_string(),
+                    name: "Gemini 1.5 Flash".to_string(),
+                    capabilities: vec!["coding".to_string(), "fast".to_string(), "vision".to_string()],
+                    input_price: 0.075,
+                    output_price: 0.3,
+                    max_tokens: 1048576,
+                    is_default: false,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "gemini-1.5-flash-8b".to_string(),
+                    name: "Gemini 1.5 Flash 8B".to_string(),
+                    capabilities: vec!["fast".to_string(), "vision".to_string()],
+                    input_price: 0.0375,
+                    output_price: 0.15,
+                    max_tokens: 1048576,
+                    is_default: false,
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            ..Default::default()
+        },
+        // DeepSeek
+        ProviderConfig {
+            provider: LLMProvider::DeepSeek,
+            name: "DeepSeek".to_string(),
+            base_url: "https://api.deepseek.com/v1".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 2,
+            models: vec![
+                ModelConfig {
+                    id: "deepseek-chat".to_string(),
+                    name: "DeepSeek Chat".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string()],
+                    input_price: 0.14,
+                    output_price: 0.28,
+                    max_tokens: 64000,
+                    is_default: false,
+                    price_windows: vec![pricing::deepseek_off_peak(0.07, 0.14)],
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "deepseek-coder".to_string(),
+                    name: "DeepSeek Coder".to_string(),
+                    capabilities: vec!["coding".to_string()],
+                    input_price: 0.14,
+                    output_price: 0.28,
+                    max_tokens: 64000,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "deepseek-reasoner".to_string(),
+                    name: "DeepSeek Reasoner (R1)".to_string(),
+                    capabilities: vec!["reasoning".to_string(), "coding".to_string()],
+                    input_price: 0.55,
+                    output_price: 2.19,
+                    max_tokens: 64000,
+                    is_default: false,
+                    price_windows: vec![pricing::deepseek_off_peak(0.1375, 0.5475)],
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            utc_offset_minutes: 8 * 60,
+            ..Default::default()
+        },
+        // Moonshot (Kimi)
+        ProviderConfig {
+            provider: LLMProvider::Moonshot,
+            name: "Moonshot (Kimi)".to_string(),
+            base_url: "https://api.moonshot.cn/v1".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 3,
+            models: vec![
+                ModelConfig {
+                    id: "moonshot-v1-8k".to_string(),
+                    name: "Moonshot V1 8K".to_string(),
+                    capabilities: vec!["coding".to_string(), "fast".to_string()],
+                    input_price: 0.012,
+                    output_price: 0.012,
+                    max_tokens: 8192,
+                    is_default: false,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "moonshot-v1-32k".to_string(),
+                    name: "Moonshot V1 32K".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string()],
+                    input_price: 0.024,
+                    output_price: 0.024,
+                    max_tokens: 32768,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "moonshot-v1-128k".to_string(),
+                    name: "Moonshot V1 128K".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string()],
+                    input_price: 0.06,
+                    output_price: 0.06,
+                    max_tokens: 131072,
+                    is_default: false,
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            ..Default::default()
+        },
+        // Qwen (Alibaba)
+        ProviderConfig {
+            provider: LLMProvider::Qwen,
+            name: "Qwen (Alibaba)".to_string(),
+            base_url: "https://dashscope.aliyuncs.com/compatible-mode/v1".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 4,
+            models: vec![
+                ModelConfig {
+                    id: "qwen-turbo".to_string(),
+                    name: "Qwen Turbo".to_string(),
+                    capabilities: vec!["fast".to_string()],
+                    input_price: 0.002,
+                    output_price: 0.006,
+                    max_tokens: 8192,
+                    is_default: false,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "qwen-plus".to_string(),
+                    name: "Qwen Plus".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string()],
+                    input_price: 0.004,
+                    output_price: 0.012,
+                    max_tokens: 32768,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "qwen-max".to_string(),
+                    name: "Qwen Max".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string(), "creative".to_string()],
+                    input_price: 0.02,
+                    output_price: 0.06,
+                    max_tokens: 32768,
+                    is_default: false,
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            ..Default::default()
+        },
+        // Zhipu (GLM)
+        ProviderConfig {
+            provider: LLMProvider::Zhipu,
+            name: "Zhipu AI (GLM)".to_string(),
+            base_url: "https://open.bigmodel.cn/api/paas/v4".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 5,
+            models: vec![
+                ModelConfig {
+                    id: "glm-4".to_string(),
+                    name: "GLM-4".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string()],
+                    input_price: 0.1,
+                    output_price: 0.1,
+                    max_tokens: 128000,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "glm-4-flash".to_string(),
+                    name: "GLM-4 Flash".to_string(),
+                    capabilities: vec!["fast".to_string()],
+                    input_price: 0.001,
+                    output_price: 0.001,
+                    max_tokens: 128000,
+                    is_default: false,
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            ..Default::default()
+        },
+        // Groq
+        ProviderConfig {
+            provider: LLMProvider::Groq,
+            name: "Groq".to_string(),
+            base_url: "https://api.groq.com/openai/v1".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 6,
+            models: vec![
+                ModelConfig {
+                    id: "llama-3.3-70b-versatile".to_string(),
+                    name: "Llama 3.3 70B".to_string(),
+                    capabilities: vec!["coding".to_string(), "fast".to_string()],
+                    input_price: 0.59,
+                    output_price: 0.79,
+                    max_tokens: 32768,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "mixtral-8x7b-32768".to_string(),
+                    name: "Mixtral 8x7B".to_string(),
+                    capabilities: vec!["fast".to_string()],
+                    input_price: 0.24,
+                    output_price: 0.24,
+                    max_tokens: 32768,
+                    is_default: false,
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            ..Default::default()
+        },
+        // Ollama (Local)
+        ProviderConfig {
+            provider: LLMProvider::Ollama,
+            name: "Ollama (Local)".to_string(),
+            base_url: "http://localhost:11434/v1".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 10,
+            models: vec![
+                ModelConfig {
+                    id: "llama3.2".to_string(),
+                    name: "Llama 3.2".to_string(),
+                    capabilities: vec!["coding".to_string()],
+                    input_price: 0.0,
+                    output_price: 0.0,
+                    max_tokens: 131072,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "qwen2.5-coder".to_string(),
+                    name: "Qwen 2.5 Coder".to_string(),
+                    capabilities: vec!["coding".to_string()],
+                    input_price: 0.0,
+                    output_price: 0.0,
+                    max_tokens: 32768,
+                    is_default: false,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "deepseek-r1".to_string(),
+                    name: "DeepSeek R1".to_string(),
+                    capabilities: vec!["reasoning".to_string(), "coding".to_string()],
+                    input_price: 0.0,
+                    output_price: 0.0,
+                    max_tokens: 64000,
+                    is_default: false,
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            ..Default::default()
+        },
+        // OpenRouter
+        ProviderConfig {
+            provider: LLMProvider::OpenRouter,
+            name: "OpenRouter".to_string(),
+            base_url: "https://openrouter.ai/api/v1".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 7,
+            models: vec![
+                ModelConfig {
+                    id: "anthropic/claude-3.5-sonnet".to_string(),
+                    name: "Claude 3.5 Sonnet".to_string(),
+                    capabilities: vec!["coding".to_string(), "reasoning".to_string(), "vision".to_string()],
+                    input_price: 3.0,
+                    output_price: 15.0,
+                    max_tokens: 200000,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "google/gemini-2.0-flash-exp".to_string(),
+                    name: "Gemini 2.0 Flash".to_string(),
+                    capabilities: vec!["coding".to_string(), "fast".to_string(), "vision".to_string()],
+                    input_price: 0.0,
+                    output_price: 0.0,
+                    max_tokens: 1048576,
+                    is_default: false,
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            supports_file_input: true,
+            ..Default::default()
+        },
+        // Hugging Face inference router; dedicated Inference Endpoints speak the same API
+        ProviderConfig {
+            provider: LLMProvider::HuggingFace,
+            name: "Hugging Face".to_string(),
+            base_url: "https://router.huggingface.co/v1".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 8,
+            models: vec![ModelConfig {
+                id: "Qwen/Qwen2.5-Coder-32B-Instruct".to_string(),
+                name: "Qwen 2.5 Coder 32B".to_string(),
+                capabilities: vec!["coding".to_string()],
+                input_price: 0.0,
+                output_price: 0.0,
+                max_tokens: 32768,
+                is_default: true,
+                ..Default::default()
+            }],
+            headers: HashMap::new(),
+            ..Default::default()
+        },
+        // Replicate (asynchronous predictions)
+        ProviderConfig {
+            provider: LLMProvider::Replicate,
+            name: "Replicate".to_string(),
+            base_url: "https://api.replicate.com/v1".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 9,
+            models: vec![ModelConfig {
+                id: "meta/meta-llama-3-70b-instruct".to_string(),
+                name: "Llama 3 70B Instruct".to_string(),
+                capabilities: vec!["coding".to_string()],
+                input_price: 0.65,
+                output_price: 2.75,
+                max_tokens: 8192,
+                is_default: true,
+                ..Default::default()
+            }],
+            headers: HashMap::new(),
+            ..Default::default()
+        },
+        // GitHub Copilot, signed in with a device login rather than a pasted key
+        ProviderConfig {
+            provider: LLMProvider::Copilot,
+            name: "GitHub Copilot".to_string(),
+            base_url: "https://api.githubcopilot.com".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 11,
+            models: vec![
+                ModelConfig {
+                    id: "gpt-4o".to_string(),
+                    name: "GPT-4o (Copilot)".to_string(),
+                    capabilities: vec!["coding".to_string(), "vision".to_string()],
+                    input_price: 0.0,
+                    output_price: 0.0,
+                    max_tokens: 64000,
+                    is_default: true,
+                    ..Default::default()
+                },
+                ModelConfig {
+                    id: "claude-3.5-sonnet".to_string(),
+                    name: "Claude 3.5 Sonnet (Copilot)".to_string(),
+                    capabilities: vec!["coding".to_string()],
+                    input_price: 0.0,
+                    output_price: 0.0,
+                    max_tokens: 90000,
+                    is_default: false,
+                    ..Default::default()
+                },
+            ],
+            headers: HashMap::new(),
+            ..Default::default()
+        },
+        // Canned replies for offline development and tests; never leaves the machine
+        ProviderConfig {
+            provider: LLMProvider::Mock,
+            name: "Mock".to_string(),
+            base_url: "mock://localhost".to_string(),
+            api_key: None,
+            enabled: false,
+            priority: 99,
+            models: vec![ModelConfig {
+                id: "mock-model".to_string(),
+                name: "Mock Model".to_string(),
+                capabilities: vec!["coding".to_string(), "fast".to_string()],
+                input_price: 0.0,
+                output_price: 0.0,
+                max_tokens: 128000,
+                is_default: true,
+                ..Default::default()
+            }],
+            headers: HashMap::new(),
+            mock: Some(MockConfig::default()),
+            ..Default::default()
+        },
+    ]
+}
+
+// ============================================================================
+// Gateway State
+// ============================================================================
+
+/// Global gateway state
+pub struct LLMGatewayState {
+    pub settings: Arc<RwLock<GatewaySettings>>,
+    pub status: Arc<RwLock<GatewayStatus>>,
+    pub server_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    /// Running additional listeners, by name
+    pub instances: Arc<RwLock<BTreeMap<String, GatewayInstance>>>,
+}
+
+impl Default for LLMGatewayState {
+    fn default() -> Self {
+        Self {
+            settings: Arc::new(RwLock::new(GatewaySettings::default())),
+            status: Arc::new(RwLock::new(GatewayStatus {
+                running: false,
+                port: 8765,
+                requests_processed: 0,
+                provider_status: HashMap::new(),
+                last_error: None,
+                model_warnings: Vec::new(),
+            })),
+            server_handle: Arc::new(RwLock::new(None)),
+            instances: Arc::new(RwLock::new(BTreeMap::new())),
+        }
+    }
+}
+
+/// Whether the stored settings are encrypted with a master password
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsEncryptionStatus {
+    pub encrypted: bool,
+    /// Whether the password has been entered this session
+    pub unlocked: bool,
+}
+
+const SETTINGS_LOCKED: &str =
+    "Gateway settings are encrypted; unlock them with the master password first";
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// `app_settings` key of the gateway settings
+const SETTINGS_KEY: &str = "llm_gateway_settings";
+
+/// Raw stored value of an `app_settings` key: plain JSON or an encrypted envelope
+fn stored_setting(conn: &Connection, key: &str) -> Option<String> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![key],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+}
+
+fn write_setting(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )
+    .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(())
+}
+
+/// Whether the stored settings are encrypted with a master password
+fn gateway_settings_encrypted(conn: &Connection) -> bool {
+    stored_setting(conn, SETTINGS_KEY).is_some_and(|stored| vault::is_encrypted(&stored))
+}
+
+/// Whether the stored settings are encrypted and haven't been unlocked this session
+fn gateway_settings_locked(conn: &Connection) -> bool {
+    vault::current_key().is_none() && gateway_settings_encrypted(conn)
+}
+
+/// JSON to store, encrypted if the vault is unlocked
+fn seal_setting(conn: &Connection, json_str: String) -> Result<String, String> {
+    match vault::current_key() {
+        Some(key) => key.seal(&json_str),
+        // Never overwrite encrypted settings with defaults loaded while locked
+        None if gateway_settings_locked(conn) => Err(SETTINGS_LOCKED.to_string()),
+        None => Ok(json_str),
+    }
+}
+
+/// JSON of a stored value, decrypted if it is encrypted
+fn open_setting(stored: String) -> Result<String, String> {
+    if !vault::is_encrypted(&stored) {
+        return Ok(stored);
+    }
+    vault::current_key().ok_or(SETTINGS_LOCKED)?.open(&stored)
+}
+
+/// Load gateway settings from the database, falling back to defaults
+pub(crate) fn load_gateway_settings(conn: &Connection) -> GatewaySettings {
+    if let Some(stored) = stored_setting(conn, SETTINGS_KEY) {
+        match open_setting(stored) {
+            Ok(json_str) => {
+                if let Ok(settings) = serde_json::from_str::<GatewaySettings>(&json_str) {
+                    return settings;
+                }
+            }
+            Err(e) => log::warn!("Using default gateway settings: {}", e),
+        }
+    }
+
+    GatewaySettings::default()
+}
+
+/// Persist gateway settings to the database, encrypted if the vault is unlocked, and
+/// record the fields `action` changed in the audit log
+pub(crate) fn persist_gateway_settings(
+    conn: &Connection,
+    settings: &GatewaySettings,
+    action: &str,
+) -> Result<(), String> {
+    let json_str = serde_json::to_string(settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    let sealed = seal_setting(conn, json_str)?;
+    let previous = load_gateway_settings(conn);
+    write_setting(conn, SETTINGS_KEY, &sealed)?;
+    audit::record_changes(conn, action, &previous, settings)
+        .map_err(|e| format!("Failed to record settings changes: {}", e))?;
+    Ok(())
+}
+
+/// Hand updated settings to the running gateway with environment references resolved
+async fn apply_running_settings(state: &LLMGatewayState, mut settings: GatewaySettings) {
+    if let Err(e) = interpolate::resolve_settings(&mut settings) {
+        log::warn!("Updated settings not applied to the running gateway: {}", e);
+        return;
+    }
+    currency::normalize_prices(&mut settings);
+    for instance in state.instances.write().await.values_mut() {
+        instance.update(&settings).await;
+    }
+    *state.settings.write().await = settings;
+}
+
+/// Get gateway settings
+#[tauri::command]
+pub async fn get_llm_gateway_settings(db: State<'_, AgentDb>) -> Result<GatewaySettings, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    Ok(load_gateway_settings(&conn))
+}
+
+/// Save gateway settings
+#[tauri::command]
+pub async fn save_llm_gateway_settings(
+    db: State<'_, AgentDb>,
+    settings: GatewaySettings,
+) -> Result<(), String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    persist_gateway_settings(&conn, &settings, "save_llm_gateway_settings")
+}
+
+/// List recorded settings changes, newest first
+#[tauri::command]
+pub async fn list_audit_log(
+    db: State<'_, AgentDb>,
+    limit: Option<usize>,
+) -> Result<Vec<AuditEntry>, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    audit::list(&conn, limit.unwrap_or(100)).map_err(|e| e.to_string())
+}
+
+/// Get gateway status
+#[tauri::command]
+pub async fn get_llm_gateway_status(
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+) -> Result<GatewayStatus, String> {
+    let mut status = state.status.read().await.clone();
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    status.model_warnings = registry::warnings(
+        &load_gateway_settings(&conn),
+        &registry::load(&conn),
+        chrono::Local::now().date_naive(),
+    );
+    Ok(status)
+}
+
+/// Start the LLM gateway server
+#[tauri::command]
+pub async fn start_llm_gateway(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+) -> Result<(), String> {
+    // Load settings
+    {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        if gateway_settings_locked(&conn) {
+            return Err(SETTINGS_LOCKED.to_string());
+        }
+    }
+    let mut settings = get_llm_gateway_settings(db).await?;
+    
+    if !settings.enabled {
+        return Err("LLM Gateway is not enabled".to_string());
+    }
+    interpolate::resolve_settings(&mut settings)?;
+    currency::normalize_prices(&mut settings);
+    instances::validate(&settings)?;
+
+    // Check if already running
+    {
+        let status = state.status.read().await;
+        if status.running {
+            return Err("Gateway is already running".to_string());
+        }
+    }
+
+    let port = settings.port;
+
+    // Start the enabled additional listeners
+    {
+        let mut running = state.instances.write().await;
+        for config in settings.instances.iter().filter(|i| i.enabled) {
+            if !running.contains_key(&config.name) {
+                let instance = GatewayInstance::start(app.clone(), config.clone(), &settings);
+                running.insert(config.name.clone(), instance);
+            }
+        }
+    }
+
+    // Update settings in state
+    {
+        let mut state_settings = state.settings.write().await;
+        *state_settings = settings;
+    }
+
+    // Start the server
+    let settings_clone = state.settings.clone();
+    let status_clone = state.status.clone();
+
+    // Replace any supervisor still waiting out a restart backoff, so only one serves.
+    // The status is set first: from here on the supervisor records its failures.
+    {
+        let mut server_handle = state.server_handle.write().await;
+        if let Some(previous) = server_handle.take() {
+            previous.abort();
+        }
+        {
+            let mut status = state.status.write().await;
+            status.running = true;
+            status.port = port;
+            status.last_error = None;
+        }
+        *server_handle = Some(tokio::spawn(watchdog::supervise(
+            app,
+            port,
+            settings_clone,
+            status_clone,
+            true,
+        )));
+    }
+
+    log::info!("LLM Gateway started on port {}", port);
+    Ok(())
+}
+
+/// Stop the LLM gateway server
+#[tauri::command]
+pub async fn stop_llm_gateway(state: State<'_, LLMGatewayState>) -> Result<(), String> {
+    // Abort the server task
+    {
+        let mut server_handle = state.server_handle.write().await;
+        if let Some(handle) = server_handle.take() {
+            handle.abort();
+        }
+    }
+    for (_, instance) in std::mem::take(&mut *state.instances.write().await) {
+        instance.stop();
+    }
+
+    // Update status
+    {
+        let mut status = state.status.write().await;
+        status.running = false;
+    }
+
+    log::info!("LLM Gateway stopped");
+    Ok(())
+}
+
+/// List the running additional listeners
+#[tauri::command]
+pub async fn list_gateway_instances(
+    state: State<'_, LLMGatewayState>,
+) -> Result<Vec<InstanceStatus>, String> {
+    let instances = state.instances.read().await;
+    let mut statuses = Vec::new();
+    for instance in instances.values() {
+        statuses.push(instance.status().await);
+    }
+    Ok(statuses)
+}
+
+/// Start the additional listener `name` while the gateway is running
+#[tauri::command]
+pub async fn start_gateway_instance(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+    name: String,
+) -> Result<InstanceStatus, String> {
+    if !state.status.read().await.running {
+        return Err("Gateway is not running".to_string());
+    }
+    let mut settings = get_llm_gateway_settings(db).await?;
+    interpolate::resolve_settings(&mut settings)?;
+    currency::normalize_prices(&mut settings);
+    instances::validate(&settings)?;
+    let config = settings
+        .instances
+        .iter()
+        .find(|i| i.name == name)
+        .cloned()
+        .ok_or_else(|| format!("Gateway instance '{}' not found", name))?;
+
+    let mut running = state.instances.write().await;
+    if running.contains_key(&name) {
+        return Err(format!("Gateway instance '{}' is already running", name));
+    }
+    let instance = GatewayInstance::start(app, config, &settings);
+    let status = instance.status().await;
+    running.insert(name, instance);
+    Ok(status)
+}
+
+/// Stop the additional listener `name`
+#[tauri::command]
+pub async fn stop_gateway_instance(
+    state: State<'_, LLMGatewayState>,
+    name: String,
+) -> Result<(), String> {
+    let instance = state
+        .instances
+        .write()
+        .await
+        .remove(&name)
+        .ok_or_else(|| format!("Gateway instance '{}' is not running", name))?;
+    instance.stop();
+    Ok(())
+}
+
+/// Explain which routing rule a sample request matches at `time` (HH:MM, default now),
+/// using `rules` in place of the saved rules when given
+#[tauri::command]
+pub async fn test_routing_rules(
+    db: State<'_, AgentDb>,
+    request: serde_json::Value,
+    rules: Option<Vec<RoutingRule>>,
+    source_key: Option<String>,
+    time: Option<String>,
+) -> Result<RuleExplanation, String> {
+    let time = match time {
+        Some(time) => chrono::NaiveTime::parse_from_str(&time, "%H:%M")
+            .map_err(|e| format!("Invalid time '{}': {}", time, e))?,
+        None => chrono::Local::now().time(),
+    };
+    let settings = get_llm_gateway_settings(db).await?;
+    let rules = rules.unwrap_or_else(|| settings.routing_rules.clone());
+    let input = RuleInput::from_request(&request, source_key.as_deref(), time);
+    Ok(rules::explain(&settings, &rules, &input))
+}
+
+/// Start the gateway on app launch if `auto_start` is set. Locked settings are
+/// checked again when they are unlocked
+pub async fn auto_start(app: AppHandle) {
+    let settings = {
+        let db = app.state::<AgentDb>();
+        let conn = match db.0.get() {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::warn!("Failed to open database for gateway auto-start: {}", e);
+                return;
+            }
+        };
+        if gateway_settings_locked(&conn) {
+            return;
+        }
+        load_gateway_settings(&conn)
+    };
+    if settings.auto_start {
+        start_automatically(&app).await;
+    }
+}
+
+/// Start the gateway without a caller to report to, recording a failure as the
+/// status' `last_error`. Failing to bind is recorded there by the supervisor
+async fn start_automatically(app: &AppHandle) {
+    if let Err(e) = start_llm_gateway(app.clone(), app.state(), app.state()).await {
+        log::warn!("Failed to auto-start LLM Gateway: {}", e);
+        let state = app.state::<LLMGatewayState>();
+        state.status.write().await.last_error = Some(format!("Auto-start failed: {}", e));
+    }
+}
+
+/// Current gateway state as shown by the tray menu
+pub(crate) async fn gateway_snapshot(
+    db: &AgentDb,
+    state: &LLMGatewayState,
+) -> Result<GatewaySnapshot, String> {
+    let (settings, today_spend_usd) = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        let spend = usage::today_spend(&conn).map_err(|e| e.to_string())?;
+        (load_gateway_settings(&conn), spend)
+    };
+    let status = state.status.read().await.clone();
+    let route = router::resolve_route(&settings, None);
+
+    Ok(GatewaySnapshot {
+        running: status.running,
+        port: if status.running {
+            status.port
+        } else {
+            settings.port
+        },
+        requests_processed: status.requests_processed,
+        current_provider: route.as_ref().map(|r| r.provider.name.clone()),
+        current_model: route.map(|r| r.model),
+        today_spend_usd,
+        providers: settings
+            .providers
+            .iter()
+            .map(|p| ProviderToggle {
+                provider: p.provider.clone(),
+                name: p.name.clone(),
+                enabled: p.enabled,
+            })
+            .collect(),
+    })
+}
+
+/// Get a compact snapshot of the gateway's state
+#[tauri::command]
+pub async fn get_gateway_snapshot(
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+) -> Result<GatewaySnapshot, String> {
+    gateway_snapshot(&db, &state).await
+}
+
+/// Enable or disable a provider by name, applying the change to the running gateway
+#[tauri::command]
+pub async fn set_llm_provider_enabled(
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+    name: String,
+    enabled: bool,
+) -> Result<GatewaySettings, String> {
+    let settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        let mut settings = load_gateway_settings(&conn);
+        let provider = settings
+            .providers
+            .iter_mut()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("Provider '{}' not found", name))?;
+        provider.enabled = enabled;
+        persist_gateway_settings(&conn, &settings, "set_llm_provider_enabled")?;
+        settings
+    };
+    if state.status.read().await.running {
+        apply_running_settings(&state, settings.clone()).await;
+    }
+    Ok(settings)
+}
+
+/// Start signing in to GitHub Copilot; the user enters the returned code on GitHub
+#[tauri::command]
+pub async fn start_copilot_login() -> Result<copilot::DeviceLogin, String> {
+    copilot::start_device_login(&client::shared_client()).await
+}
+
+/// Check on a Copilot sign-in, storing the GitHub token on the Copilot provider once
+/// the user has authorized it
+#[tauri::command]
+pub async fn poll_copilot_login(
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+    device_code: String,
+) -> Result<copilot::LoginPoll, String> {
+    let token = match copilot::poll_device_login(&client::shared_client(), &device_code).await? {
+        Ok(token) => token,
+        Err(pending) => return Ok(pending),
+    };
+
+    let settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        let mut settings = load_gateway_settings(&conn);
+        let provider = settings
+            .providers
+            .iter_mut()
+            .find(|p| p.provider == LLMProvider::Copilot)
+            .ok_or("No GitHub Copilot provider is configured")?;
+        provider.api_key = Some(token);
+        provider.enabled = true;
+        persist_gateway_settings(&conn, &settings, "poll_copilot_login")?;
+        settings
+    };
+    if state.status.read().await.running {
+        apply_running_settings(&state, settings).await;
+    }
+    Ok(copilot::LoginPoll::Complete)
+}
+
+/// Test a provider connection
+#[tauri::command]
+pub async fn test_llm_provider(
+    provider: LLMProvider,
+    base_url: String,
+    api_key: String,
+) -> Result<ProviderStatus, String> {
+    use std::time::Instant;
+
+    // Mock providers answer in-process
+    if provider == LLMProvider::Mock {
+        return Ok(ProviderStatus {
+            available: true,
+            latency_ms: Some(0),
+            last_error: None,
+            request_count: 1,
+            error_count: 0,
+            quota: None,
+            hardware: None,
+        });
+    }
+
+    let base_url = interpolate::interpolate(&base_url)?;
+    let api_key = interpolate::interpolate(&api_key)?;
+    let client = client::shared_client();
+    let start = Instant::now();
+
+    // Build the models endpoint URL and auth from the provider's built-in adapter
+    let spec = AdapterSpec::for_provider(&provider);
+    let url = spec.models_url(&base_url);
+
+    let mut request = spec.authorize(client.get(&url), Some(&api_key), "");
+    if provider == LLMProvider::Anthropic {
+        request = request.header("anthropic-version", "2023-06-01");
+    }
+
+    match request.send().await {
+        Ok(response) => {
+            let latency = start.elapsed().as_millis() as u64;
+            let quota = quota::from_headers(response.headers());
+            if response.status().is_success() {
+                Ok(ProviderStatus {
+                    available: true,
+                    latency_ms: Some(latency),
+                    last_error: None,
+                    request_count: 1,
+                    error_count: 0,
+                    quota,
+                    hardware: None,
+                })
+            } else {
+                let error_text = response.text().await.unwrap_or_default();
+                Ok(ProviderStatus {
+                    available: false,
+                    latency_ms: Some(latency),
+                    last_error: Some(scrub::scrub_secrets(&error_text, [api_key.as_str()])),
+                    request_count: 1,
+                    error_count: 1,
+                    quota,
+                    hardware: None,
+                })
+            }
+        }
+        Err(e) => Ok(ProviderStatus {
+            available: false,
+            latency_ms: None,
+            last_error: Some(scrub::scrub_secrets(&e.to_string(), [api_key.as_str()])),
+            request_count: 1,
+            error_count: 1,
+            quota: None,
+            hardware: None,
+        }),
+    }
+}
+
+/// Context length assumed for discovered models that don't report one
+const DEFAULT_DISCOVERED_CONTEXT: u32 = 32768;
+
+/// Convert a `/models` response into model configs.
+///
+/// Handles the OpenAI `{"data": [...]}` envelope as well as a bare array, and picks up
+/// vLLM's `max_model_len` when present.
+fn parse_models_response(body: &serde_json::Value) -> Vec<ModelConfig> {
+    let entries = body
+        .get("data")
+        .and_then(|d| d.as_array())
+        .or_else(|| body.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut models: Vec<ModelConfig> = entries
+        .iter()
+        .filter_map(|entry| {
+            let id = entry.get("id").and_then(|v| v.as_str())?.to_string();
+            let max_tokens = entry
+                .get("max_model_len")
+                .or_else(|| entry.get("context_length"))
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32)
+                .unwrap_or(DEFAULT_DISCOVERED_CONTEXT);
+            Some(ModelConfig {
+                name: id.clone(),
+                id,
+                capabilities: vec!["coding".to_string()],
+                input_price: 0.0,
+                output_price: 0.0,
+                max_tokens,
+                is_default: false,
+                pricing_unknown: true,
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    if let Some(first) = models.first_mut() {
+        first.is_default = true;
+    }
+    models
+}
+
+/// Build an authenticated request against an OpenAI-compatible endpoint
+fn openai_compatible_request(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    url: &str,
+    api_key: Option<&str>,
+) -> reqwest::RequestBuilder {
+    let request = client.request(method, url);
+    match api_key.filter(|k| !k.is_empty()) {
+        Some(key) => request.header("Authorization", format!("Bearer {}", key)),
+        None => request,
+    }
+}
+
+/// Send a tiny completion through every enabled provider and through the gateway's own
+/// routing, via the running gateway
+#[tauri::command]
+pub async fn run_gateway_selftest(
+    state: State<'_, LLMGatewayState>,
+) -> Result<selftest::SelfTestReport, String> {
+    let port = {
+        let status = state.status.read().await;
+        if !status.running {
+            return Err("Gateway is not running".to_string());
+        }
+        status.port
+    };
+    let settings = state.settings.read().await.clone();
+    let report = selftest::run(&settings, port).await;
+    for check in report.checks.iter().filter(|c| !c.passed) {
+        log::warn!(
+            "Self-test of {} failed: {}",
+            check.target,
+            check.error.as_deref().unwrap_or_default()
+        );
+    }
+    Ok(report)
+}
+
+/// Probe a custom OpenAI-compatible endpoint: list models, validate a chat
+/// completion round-trip and detect streaming support.
+#[tauri::command]
+pub async fn probe_custom_llm_provider(
+    base_url: String,
+    api_key: Option<String>,
+    model: Option<String>,
+) -> Result<ProviderProbeResult, String> {
+    use std::time::Instant;
+
+    let client = client::shared_client();
+    let base = base_url.trim_end_matches('/');
+    let api_key = api_key.as_deref();
+
+    let mut result = ProviderProbeResult {
+        reachable: false,
+        models: Vec::new(),
+        chat_ok: false,
+        supports_streaming: false,
+        latency_ms: None,
+        sample_response: None,
+        error: None,
+    };
+
+    // Step 1: discover models
+    let models_url = format!("{}/models", base);
+    match openai_compatible_request(&client, reqwest::Method::GET, &models_url, api_key)
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => {
+            result.reachable = true;
+            let body: serde_json::Value = response.json().await.unwrap_or_default();
+            result.models = parse_models_response(&body);
+        }
+        Ok(response) => {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            result.error = Some(scrub::scrub_secrets(
+                &format!("GET {} returned {}: {}", models_url, status, text),
+                api_key,
+            ));
+            return Ok(result);
+        }
+        Err(e) => {
+            result.error = Some(scrub::scrub(&format!("GET {} failed: {}", models_url, e)));
+            return Ok(result);
+        }
+    }
+
+    let test_model = match model.or_else(|| result.models.first().map(|m| m.id.clone())) {
+        Some(m) => m,
+        None => {
+            result.error = Some("Endpoint did not report any models".to_string());
+            return Ok(result);
+        }
+    };
+
+    // Step 2: validate a non-streaming chat completion round-trip
+    let chat_url = format!("{}/chat/completions", base);
+    let body = serde_json::json!({
+        "model": test_model,
+        "messages": [{"role": "user", "content": "Reply with the word: pong"}],
+        "max_tokens": 8,
+    });
+    let start = Instant::now();
+    match openai_compatible_request(&client, reqwest::Method::POST, &chat_url, api_key)
+        .json(&body)
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => {
+            result.latency_ms = Some(start.elapsed().as_millis() as u64);
+            let json: serde_json::Value = response.json().await.unwrap_or_default();
+            match json.pointer("/choices/0/message/content") {
+                Some(content) => {
+                    result.chat_ok = true;
+                    result.sample_response =
+                        content.as_str().map(|s| s.chars().take(200).collect());
+                }
+                None => {
+                    result.error = Some("Chat completion response has no choices".to_string());
+                }
+            }
+        }
+        Ok(response) => {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            result.error = Some(scrub::scrub_secrets(
+                &format!("POST {} returned {}: {}", chat_url, status, text),
+                api_key,
+            ));
+            return Ok(result);
+        }
+        Err(e) => {
+            result.error = Some(scrub::scrub(&format!("POST {} failed: {}", chat_url, e)));
+            return Ok(result);
+        }
+    }
+
+    // Step 3: detect streaming support from the first SSE chunk
+    let mut stream_body = body;
+    stream_body["stream"] = serde_json::Value::Bool(true);
+    if let Ok(mut response) =
+        openai_compatible_request(&client, reqwest::Method::POST, &chat_url, api_key)
+            .json(&stream_body)
+            .send()
+            .await
+    {
+        let is_sse = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.contains("text/event-stream"))
+            .unwrap_or(false);
+        let first_chunk_is_sse = match response.chunk().await {
+            Ok(Some(chunk)) => String::from_utf8_lossy(&chunk)
+                .trim_start()
+                .starts_with("data:"),
+            _ => false,
+        };
+        result.supports_streaming =
+            response.status().is_success() && (is_sse || first_chunk_is_sse);
+    }
+
+    Ok(result)
+}
+
+/// Add (or replace) a custom OpenAI-compatible provider after validating that a
+/// chat completion round-trip against its default model succeeds.
+#[tauri::command]
+pub async fn add_custom_llm_provider(
+    db: State<'_, AgentDb>,
+    config: ProviderConfig,
+) -> Result<GatewaySettings, String> {
+    let default_model = config
+        .models
+        .iter()
+        .find(|m| m.is_default)
+        .or_else(|| config.models.first())
+        .map(|m| m.id.clone())
+        .ok_or("Provider has no models configured")?;
+
+    let probe = probe_custom_llm_provider(
+        config.base_url.clone(),
+        config.api_key.clone(),
+        Some(default_model.clone()),
+    )
+    .await?;
+    if !probe.chat_ok {
+        return Err(format!(
+            "Chat completion with model '{}' failed: {}",
+            default_model,
+            probe.error.unwrap_or_else(|| "unknown error".to_string())
+        ));
+    }
+
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let mut settings = load_gateway_settings(&conn);
+    settings
+        .providers
+        .retain(|p| !(p.provider == LLMProvider::Custom && p.name == config.name));
+    settings.providers.push(config);
+    persist_gateway_settings(&conn, &settings, "add_custom_llm_provider")?;
+
+    Ok(settings)
+}
+
+/// Append discovered models that aren't configured yet, returning the added IDs.
+///
+/// Configured models keep their prices and flags; new ones are never made the default.
+fn merge_discovered_models(
+    models: &mut Vec<ModelConfig>,
+    discovered: Vec<ModelConfig>,
+) -> Vec<String> {
+    let mut added = Vec::new();
+    for mut model in discovered {
+        if models.iter().any(|m| m.id == model.id) {
+            continue;
+        }
+        model.is_default = false;
+        model.pricing_unknown = true;
+        added.push(model.id.clone());
+        models.push(model);
+    }
+    added
+}
+
+/// Models listed by a provider's /models endpoint
+async fn fetch_models(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+) -> Result<Vec<ModelConfig>, String> {
+    let spec = AdapterSpec::resolve(provider);
+    let url = spec.models_url(&provider.base_url);
+    let request = client.get(&url).timeout(std::time::Duration::from_secs(15));
+    let mut request = spec.authorize(request, provider.api_key.as_deref(), "");
+    if provider.provider == LLMProvider::Anthropic {
+        request = request.header("anthropic-version", "2023-06-01");
+    }
+    for (name, value) in provider.request_headers() {
+        request = request.header(name, value);
+    }
+
+    match request.send().await {
+        Ok(response) if response.status().is_success() => response
+            .json::<serde_json::Value>()
+            .await
+            .map(|body| parse_models_response(&body))
+            .map_err(|e| format!("Invalid /models response: {}", e)),
+        Ok(response) => Err(format!(
+            "{}: {}",
+            response.status(),
+            response.text().await.unwrap_or_default()
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Query every enabled provider's /models endpoint and merge newly available models
+/// into the saved configuration.
+#[tauri::command]
+pub async fn refresh_provider_models(
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+) -> Result<Vec<ModelRefreshResult>, String> {
+    let providers: Vec<ProviderConfig> = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        load_gateway_settings(&conn)
+    }
+    .providers
+    .into_iter()
+    .filter(|p| p.enabled)
+    .collect();
+
+    let client = client::shared_client();
+
+    let mut fetched = Vec::new();
+    for provider in providers {
+        // Query with references resolved, but keep them in the saved configuration
+        let mut resolved = provider.clone();
+        let discovered = match interpolate::resolve_provider(&mut resolved) {
+            Ok(()) => fetch_models(&client, &resolved).await,
+            Err(e) => Err(e),
+        }
+        .map_err(|e| scrub::scrub_secrets(&e, resolved.api_key.as_deref()));
+        fetched.push((provider, discovered));
+    }
+
+    // Merge into the settings as saved now, since they may have changed while fetching
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let mut settings = load_gateway_settings(&conn);
+    let mut results = Vec::new();
+    for (provider, discovered) in fetched {
+        let (added, error) = match discovered {
+            Ok(discovered) => {
+                let current = settings
+                    .providers
+                    .iter_mut()
+                    .find(|p| p.provider == provider.provider && p.name == provider.name);
+                let added = current
+                    .map(|current| merge_discovered_models(&mut current.models, discovered))
+                    .unwrap_or_default();
+                (added, None)
+            }
+            Err(e) => {
+                log::warn!("Failed to refresh models for {}: {}", provider.name, e);
+                (Vec::new(), Some(e))
+            }
+        };
+        results.push(ModelRefreshResult {
+            provider: provider.provider,
+            name: provider.name,
+            added,
+            error,
+        });
+    }
+
+    if results.iter().any(|r| !r.added.is_empty()) {
+        persist_gateway_settings(&conn, &settings, "refresh_provider_models")?;
+        drop(conn);
+        apply_running_settings(&state, settings).await;
+    }
+
+    Ok(results)
+}
+
+/// The named Ollama provider with references resolved, for talking to its server
+fn resolved_ollama_provider(
+    settings: &GatewaySettings,
+    name: &str,
+) -> Result<ProviderConfig, String> {
+    let mut provider = settings
+        .providers
+        .iter()
+        .find(|p| p.provider == LLMProvider::Ollama && p.name == name)
+        .cloned()
+        .ok_or_else(|| format!("No Ollama provider named '{}'", name))?;
+    interpolate::resolve_provider(&mut provider)?;
+    Ok(provider)
+}
+
+/// Match the model lists of the named Ollama provider, or of every enabled one, to the
+/// models installed on their servers, saving and applying any change
+async fn sync_installed_models(
+    db: &AgentDb,
+    state: &LLMGatewayState,
+    only: Option<&str>,
+) -> Result<Vec<ollama_server::ModelSync>, String> {
+    let mut settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        load_gateway_settings(&conn)
+    };
+
+    let client = client::shared_client();
+    let mut results = Vec::new();
+    for provider in settings.providers.iter_mut().filter(|p| {
+        p.provider == LLMProvider::Ollama && only.map_or(p.enabled, |name| p.name == name)
+    }) {
+        let mut resolved = provider.clone();
+        let installed = match interpolate::resolve_provider(&mut resolved) {
+            Ok(()) => ollama_server::list_models(&client, &resolved).await,
+            Err(e) => Err(e),
+        };
+        let mut result = ollama_server::ModelSync {
+            name: provider.name.clone(),
+            ..Default::default()
+        };
+        match installed {
+            Ok(installed) => {
+                (result.added, result.removed) =
+                    ollama_server::sync_models(&mut provider.models, &installed);
+            }
+            Err(e) => {
+                log::warn!("Failed to list models on {}: {}", provider.name, e);
+                result.error = Some(e);
+            }
+        }
+        results.push(result);
+    }
+
+    if results
+        .iter()
+        .any(|r| !r.added.is_empty() || !r.removed.is_empty())
+    {
+        {
+            let conn = db.0.get().map_err(|e| e.to_string())?;
+            persist_gateway_settings(&conn, &settings, "sync_ollama_models")?;
+        }
+        apply_running_settings(state, settings).await;
+    }
+
+    Ok(results)
+}
+
+/// List the models installed on an Ollama provider's server
+#[tauri::command]
+pub async fn list_ollama_models(
+    db: State<'_, AgentDb>,
+    provider: String,
+) -> Result<Vec<ollama_server::InstalledModel>, String> {
+    let settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        load_gateway_settings(&conn)
+    };
+    let provider = resolved_ollama_provider(&settings, &provider)?;
+    ollama_server::list_models(&client::shared_client(), &provider).await
+}
+
+/// Pull a model onto an Ollama provider's server, emitting progress events, and add it
+/// to the provider's model list
+#[tauri::command]
+pub async fn pull_ollama_model(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+    provider: String,
+    model: String,
+) -> Result<ollama_server::ModelSync, String> {
+    let settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        load_gateway_settings(&conn)
+    };
+    let resolved = resolved_ollama_provider(&settings, &provider)?;
+    ollama_server::pull_model(&client::shared_client(), &resolved, &model, |progress| {
+        let _ = app.emit(ollama_server::PULL_PROGRESS_EVENT, progress);
+    })
+    .await?;
+
+    let mut results = sync_installed_models(&db, &state, Some(&provider)).await?;
+    results
+        .pop()
+        .ok_or_else(|| format!("No Ollama provider named '{}'", provider))
+}
+
+/// Delete a model from an Ollama provider's server and from the provider's model list
+#[tauri::command]
+pub async fn delete_ollama_model(
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+    provider: String,
+    model: String,
+) -> Result<ollama_server::ModelSync, String> {
+    let settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        load_gateway_settings(&conn)
+    };
+    let resolved = resolved_ollama_provider(&settings, &provider)?;
+    ollama_server::delete_model(&client::shared_client(), &resolved, &model).await?;
+
+    let mut results = sync_installed_models(&db, &state, Some(&provider)).await?;
+    results
+        .pop()
+        .ok_or_else(|| format!("No Ollama provider named '{}'", provider))
+}
+
+/// Match every enabled Ollama provider's model list to the models installed on its server
+#[tauri::command]
+pub async fn sync_ollama_models(
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+) -> Result<Vec<ollama_server::ModelSync>, String> {
+    sync_installed_models(&db, &state, None).await
+}
+
+/// Check the prepaid credit of every enabled OpenRouter provider, recording it on the
+/// provider status and emitting a quota warning when it runs low
+#[tauri::command]
+pub async fn refresh_provider_credits(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+) -> Result<Vec<ProviderCreditsResult>, String> {
+    let settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        load_gateway_settings(&conn)
+    };
+
+    let client = client::shared_client();
+    let mut results = Vec::new();
+    for provider in settings
+        .providers
+        .iter()
+        .filter(|p| p.enabled && p.provider == LLMProvider::OpenRouter)
+    {
+        let mut resolved = provider.clone();
+        let fetched = match interpolate::resolve_provider(&mut resolved) {
+            Ok(()) => quota::fetch_openrouter_credits(&client, &resolved).await,
+            Err(e) => Err(e),
+        }
+        .map_err(|e| scrub::scrub_secrets(&e, resolved.api_key.as_deref()));
+
+        match fetched {
+            Ok(quota) => {
+                let key = provider.provider.to_string();
+                if let Some(warning) = quota::record_quota(&state.status, &key, quota.clone()).await
+                {
+                    log::warn!("{} quota running low: {}", provider.name, warning.message);
+                    let _ = app.emit(quota::QUOTA_WARNING_EVENT, warning);
+                }
+                results.push(ProviderCreditsResult {
+                    name: provider.name.clone(),
+                    quota: Some(quota),
+                    error: None,
+                });
+            }
+            Err(e) => {
+                log::warn!("Failed to check credits for {}: {}", provider.name, e);
+                results.push(ProviderCreditsResult {
+                    name: provider.name.clone(),
+                    quota: None,
+                    error: Some(e),
+                });
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// Update model prices from the pricing manifest and local overrides.
+///
+/// A manifest fetch failure is reported in the result; overrides are still applied.
+#[tauri::command]
+pub async fn sync_model_pricing(
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+) -> Result<PricingSyncResult, String> {
+    let url = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        pricing::manifest_url(&load_gateway_settings(&conn))
+    };
+
+    let fetched = pricing::fetch_manifest(&url).await;
+    let (manifest, error) = match fetched {
+        Ok(body) => (pricing::parse_pricing_manifest(&body), None),
+        Err(e) => {
+            log::warn!("Failed to fetch pricing manifest: {}", e);
+            (HashMap::new(), Some(e))
+        }
+    };
+
+    // Price the settings as saved now, since they may have changed while fetching
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let mut settings = load_gateway_settings(&conn);
+    let mut result = pricing::apply_pricing(&mut settings, &manifest);
+    result.error = error;
+
+    if !result.updated.is_empty() {
+        persist_gateway_settings(&conn, &settings, "sync_model_pricing")?;
+        drop(conn);
+        apply_running_settings(&state, settings).await;
+    }
+
+    Ok(result)
+}
+
+/// Refresh the model registry from the pricing manifest and check the configured models
+/// against it
+#[tauri::command]
+pub async fn sync_model_registry(db: State<'_, AgentDb>) -> Result<RegistrySyncResult, String> {
+    registry::sync(&db).await
+}
+
+/// Get per-arm outcome statistics of A/B tests, optionally for a single experiment
+#[tauri::command]
+pub async fn get_ab_test_results(
+    db: State<'_, AgentDb>,
+    experiment: Option<String>,
+) -> Result<Vec<AbTestArmStats>, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    usage::ab_test_stats(&conn, experiment.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Get time to first token and streaming throughput per model over the last `days`
+/// days, today included (default 7)
+#[tauri::command]
+pub async fn get_gateway_latency_stats(
+    db: State<'_, AgentDb>,
+    days: Option<u32>,
+) -> Result<Vec<ModelLatency>, String> {
+    let days = days.unwrap_or(7).max(1);
+    let since = chrono::Local::now().date_naive() - chrono::Duration::days(days as i64 - 1);
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    usage::latency_stats(&conn, since).map_err(|e| e.to_string())
+}
+
+/// Start a batch from a JSONL file of requests; the gateway has to be running
+#[tauri::command]
+pub async fn create_gateway_batch(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+    path: String,
+    concurrency: Option<u32>,
+) -> Result<BatchJob, String> {
+    let port = {
+        let status = state.status.read().await;
+        if !status.running {
+            return Err("Start the gateway to run batches".to_string());
+        }
+        status.port
+    };
+    let input =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read batch file: {}", e))?;
+    let requests = batches::parse_requests(&input)?;
+    let job = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        batches::create_batch(&conn, &requests, concurrency).map_err(|e| e.to_string())?
+    };
+    tokio::spawn(batches::run(app, port, job.id.clone()));
+    Ok(job)
+}
+
+/// List batches, newest first
+#[tauri::command]
+pub async fn list_gateway_batches(db: State<'_, AgentDb>) -> Result<Vec<BatchJob>, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    batches::list_batches(&conn).map_err(|e| e.to_string())
+}
+
+/// Get the per-request results of a batch in input order
+#[tauri::command]
+pub async fn get_gateway_batch_results(
+    db: State<'_, AgentDb>,
+    id: String,
+) -> Result<Vec<BatchResult>, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    batches::batch_results(&conn, &id).map_err(|e| e.to_string())
+}
+
+/// Stop a batch from sending further requests; returns whether it was still running
+#[tauri::command]
+pub async fn cancel_gateway_batch(db: State<'_, AgentDb>, id: String) -> Result<bool, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    batches::cancel_batch(&conn, &id).map_err(|e| e.to_string())
+}
+
+fn parse_date(date: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date '{}': {}", date, e))
+}
+
+/// Produce and store the usage report of the period containing `date` (YYYY-MM-DD,
+/// default today), writing it to the configured report directory
+#[tauri::command]
+pub async fn generate_usage_report(
+    db: State<'_, AgentDb>,
+    period: ReportPeriod,
+    date: Option<String>,
+) -> Result<UsageReport, String> {
+    let day = match date {
+        Some(date) => parse_date(&date)?,
+        None => chrono::Local::now().date_naive(),
+    };
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let settings = load_gateway_settings(&conn);
+    let config = settings.reports.unwrap_or_default();
+    let currency = settings.currency.and_then(|c| c.display_currency());
+    reports::create_report(&conn, &config, currency, period, day)
+}
+
+/// List stored usage reports, newest first
+#[tauri::command]
+pub async fn list_usage_reports(
+    db: State<'_, AgentDb>,
+    limit: Option<usize>,
+) -> Result<Vec<UsageReport>, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    reports::list_reports(&conn, limit.unwrap_or(50))
+}
+
+/// Write the request log between `start_date` and `end_date` (YYYY-MM-DD, inclusive,
+/// unbounded when unset) to `path` as CSV for `.csv` paths and JSON otherwise, one row
+/// per request or grouped; returns the number of rows written
+#[tauri::command]
+pub async fn export_gateway_usage(
+    db: State<'_, AgentDb>,
+    path: String,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    group_by: Option<UsageGrouping>,
+) -> Result<usize, String> {
+    let start = parse_date(start_date.as_deref().unwrap_or("1970-01-01"))?;
+    let end = parse_date(end_date.as_deref().unwrap_or("9999-12-31"))?;
+    let path = std::path::Path::new(&path);
+    let rows = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        let currency = load_gateway_settings(&conn)
+            .currency
+            .and_then(|c| c.display_currency());
+        reports::export_usage(
+            &conn,
+            start,
+            end,
+            group_by.unwrap_or_default(),
+            path,
+            currency.as_ref(),
+        )?
+    };
+    log::info!("Exported {} usage rows to {}", rows, path.display());
+    Ok(rows)
+}
+
+/// Project each provider's end-of-month spend from its average daily spend over the
+/// last `window_days` full days (default 7)
+#[tauri::command]
+pub async fn forecast_gateway_spend(
+    db: State<'_, AgentDb>,
+    window_days: Option<u32>,
+) -> Result<ForecastReport, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    forecast::forecast(
+        &conn,
+        chrono::Local::now().naive_local(),
+        window_days.unwrap_or(forecast::DEFAULT_WINDOW_DAYS),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// List the stored prompt templates
+#[tauri::command]
+pub async fn list_prompt_templates(db: State<'_, AgentDb>) -> Result<Vec<PromptTemplate>, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    templates::list_templates(&conn).map_err(|e| e.to_string())
+}
+
+/// Create a prompt template or update the one with the same name
+#[tauri::command]
+pub async fn save_prompt_template(
+    db: State<'_, AgentDb>,
+    template: PromptTemplate,
+) -> Result<PromptTemplate, String> {
+    if template.name.trim().is_empty() {
+        return Err("Template name is required".to_string());
+    }
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    templates::save_template(&conn, &template).map_err(|e| e.to_string())?;
+    templates::get_template(&conn, &template.name)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Template '{}' was not saved", template.name))
+}
+
+/// Delete a prompt template
+#[tauri::command]
+pub async fn delete_prompt_template(db: State<'_, AgentDb>, name: String) -> Result<(), String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    if !templates::delete_template(&conn, &name).map_err(|e| e.to_string())? {
+        return Err(format!("Template '{}' not found", name));
+    }
+    Ok(())
+}
+
+/// Import providers and models from a LiteLLM proxy `config.yaml` into the saved
+/// settings. `os.environ/NAME` keys become `${NAME}` environment references.
+#[tauri::command]
+pub async fn import_litellm_config(
+    db: State<'_, AgentDb>,
+    path: String,
+) -> Result<ConfigImportResult, String> {
+    let yaml =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let imported = import::from_litellm(&yaml)?;
+    if imported.providers.is_empty() {
+        return Err(format!(
+            "No supported models found in {} ({} skipped)",
+            path,
+            imported.skipped.len()
+        ));
+    }
+
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let result = import::merge(load_gateway_settings(&conn), imported);
+    persist_gateway_settings(&conn, &result.settings, "import_litellm_config")?;
+    log::info!(
+        "Imported {} providers from {}",
+        result.providers.len(),
+        path
+    );
+    Ok(result)
+}
+
+/// Import providers, routes and transformers from a claude-code-router `config.json`,
+/// by default `~/.claude-code-router/config.json`, into the saved settings
+#[tauri::command]
+pub async fn import_claude_code_router_config(
+    db: State<'_, AgentDb>,
+    path: Option<String>,
+) -> Result<ConfigImportResult, String> {
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => dirs::home_dir()
+            .ok_or("Could not find home directory")?
+            .join(".claude-code-router")
+            .join("config.json"),
+    };
+    let json = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let imported = import::from_claude_code_router(&json)?;
+
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let result = import::merge(load_gateway_settings(&conn), imported);
+    persist_gateway_settings(&conn, &result.settings, "import_claude_code_router_config")?;
+    log::info!(
+        "Imported {} providers from {}",
+        result.providers.len(),
+        path.display()
+    );
+    Ok(result)
+}
+
+/// Write the saved gateway settings to a JSON file, or TOML for `.toml` paths.
+/// Without `include_secrets`, provider keys and credential headers are left out.
+#[tauri::command]
+pub async fn export_gateway_settings(
+    db: State<'_, AgentDb>,
+    path: String,
+    include_secrets: bool,
+) -> Result<(), String> {
+    let mut settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        if gateway_settings_locked(&conn) {
+            return Err(SETTINGS_LOCKED.to_string());
+        }
+        load_gateway_settings(&conn)
+    };
+    if !include_secrets {
+        portable::strip_secrets(&mut settings);
+    }
+    let path = std::path::Path::new(&path);
+    let contents = portable::to_file_contents(&settings, path)?;
+    std::fs::write(path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    log::info!("Exported gateway settings to {}", path.display());
+    Ok(())
+}
+
+/// Replace the saved gateway settings with those in an exported file. Providers the
+/// file has no keys for keep the keys configured on this machine.
+#[tauri::command]
+pub async fn import_gateway_settings(
+    db: State<'_, AgentDb>,
+    path: String,
+) -> Result<GatewaySettings, String> {
+    let path = std::path::Path::new(&path);
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut settings = portable::from_file_contents(&contents, path)?;
+
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    portable::keep_local_secrets(&mut settings, &load_gateway_settings(&conn));
+    persist_gateway_settings(&conn, &settings, "import_gateway_settings")?;
+    log::info!("Imported gateway settings from {}", path.display());
+    Ok(settings)
+}
+
+/// Back up the app database to `path`, or to a new file in the backup directory
+#[tauri::command]
+pub async fn backup_gateway_db(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    path: Option<String>,
+) -> Result<BackupInfo, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => {
+            let config = load_gateway_settings(&conn).backups;
+            let dir = backup::backup_dir(&app, config.as_ref())?;
+            backup::new_backup_path(&dir, backup::MANUAL_PREFIX)
+        }
+    };
+    let backup = backup::backup_to(&conn, &path)?;
+    log::info!("Backed up the database to {}", backup.path);
+    Ok(backup)
+}
+
+/// Replace the app database with the backup at `path`, returning the backup of the
+/// current database taken first
+#[tauri::command]
+pub async fn restore_gateway_db(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+    path: String,
+) -> Result<BackupInfo, String> {
+    let (previous, settings) = {
+        let mut conn = db.0.get().map_err(|e| e.to_string())?;
+        let config = load_gateway_settings(&conn).backups;
+        let dir = backup::backup_dir(&app, config.as_ref())?;
+        let previous = backup::backup_to(
+            &conn,
+            &backup::new_backup_path(&dir, backup::PRE_RESTORE_PREFIX),
+        )?;
+        backup::restore_from(&mut conn, std::path::Path::new(&path))?;
+        (previous, load_gateway_settings(&conn))
+    };
+    apply_running_settings(&state, settings).await;
+    log::info!("Restored the database from {}", path);
+    Ok(previous)
+}
+
+/// List the backups in the backup directory, newest first
+#[tauri::command]
+pub async fn list_gateway_backups(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+) -> Result<Vec<BackupInfo>, String> {
+    let config = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        load_gateway_settings(&conn).backups
+    };
+    let dir = backup::backup_dir(&app, config.as_ref())?;
+    Ok(backup::list_backups(&dir))
+}
+
+/// Whether the stored gateway settings are encrypted and unlocked
+#[tauri::command]
+pub async fn get_settings_encryption_status(
+    db: State<'_, AgentDb>,
+) -> Result<SettingsEncryptionStatus, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    Ok(SettingsEncryptionStatus {
+        encrypted: gateway_settings_encrypted(&conn),
+        unlocked: vault::current_key().is_some(),
+    })
+}
+
+/// Encrypt the stored gateway settings with a master password
+#[tauri::command]
+pub async fn enable_settings_encryption(
+    db: State<'_, AgentDb>,
+    password: String,
+) -> Result<(), String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    if gateway_settings_encrypted(&conn) {
+        return Err("Gateway settings are already encrypted".to_string());
+    }
+    let settings = load_gateway_settings(&conn);
+    let stored_profiles = stored_setting(&conn, profiles::PROFILES_KEY);
+    vault::set_key(Some(vault::VaultKey::generate(&password)?));
+    let encrypt = || -> Result<(), String> {
+        persist_gateway_settings(&conn, &settings, "enable_settings_encryption")?;
+        if let Some(json_str) = stored_profiles {
+            let sealed = seal_setting(&conn, json_str)?;
+            write_setting(&conn, profiles::PROFILES_KEY, &sealed)?;
+        }
+        Ok(())
+    };
+    if let Err(e) = encrypt() {
+        vault::set_key(None);
+        return Err(e);
+    }
+    log::info!("Gateway settings encrypted");
+    Ok(())
+}
+
+/// Unlock encrypted gateway settings for this session
+#[tauri::command]
+pub async fn unlock_gateway_settings(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+    password: String,
+) -> Result<GatewaySettings, String> {
+    let settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        let stored = stored_setting(&conn, SETTINGS_KEY)
+            .filter(|s| vault::is_encrypted(s))
+            .ok_or("Gateway settings are not encrypted")?;
+        let (key, plaintext) = vault::VaultKey::unlock(&stored, &password)?;
+        let settings = serde_json::from_str::<GatewaySettings>(&plaintext)
+            .map_err(|e| format!("Failed to parse settings: {}", e))?;
+        vault::set_key(Some(key));
+        settings
+    };
+    apply_running_settings(&state, settings.clone()).await;
+    // Auto-start was skipped on launch while the settings were locked
+    if settings.auto_start && !state.status.read().await.running {
+        start_automatically(&app).await;
+    }
+    Ok(settings)
+}
+
+/// Decrypt the stored gateway settings and stop requiring a master password
+#[tauri::command]
+pub async fn disable_settings_encryption(
+    db: State<'_, AgentDb>,
+    password: String,
+) -> Result<(), String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let stored = stored_setting(&conn, SETTINGS_KEY)
+        .filter(|s| vault::is_encrypted(s))
+        .ok_or("Gateway settings are not encrypted")?;
+    let (key, plaintext) = vault::VaultKey::unlock(&stored, &password)?;
+    let profiles = stored_setting(&conn, profiles::PROFILES_KEY)
+        .filter(|s| vault::is_encrypted(s))
+        .map(|s| key.open(&s))
+        .transpose()?;
+    write_setting(&conn, SETTINGS_KEY, &plaintext)?;
+    if let Some(profiles) = profiles {
+        write_setting(&conn, profiles::PROFILES_KEY, &profiles)?;
+    }
+    vault::set_key(None);
+    log::info!("Gateway settings decrypted");
+    Ok(())
+}
+
+/// Stored configuration profiles and the active one
+#[tauri::command]
+pub async fn list_gateway_profiles(db: State<'_, AgentDb>) -> Result<GatewayProfiles, String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    profiles::list(&conn)
+}
+
+/// Save the current gateway settings as a named profile and make it active
+#[tauri::command]
+pub async fn save_gateway_profile(db: State<'_, AgentDb>, name: String) -> Result<(), String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    profiles::save_current(&conn, &name)
+}
+
+/// Delete a configuration profile
+#[tauri::command]
+pub async fn delete_gateway_profile(db: State<'_, AgentDb>, name: String) -> Result<(), String> {
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    if !profiles::delete(&conn, &name)? {
+        return Err(format!("Profile '{}' not found", name));
+    }
+    Ok(())
+}
+
+/// Make a stored profile the current settings and hot-swap it into the running gateway.
+/// A different port only takes effect once the gateway is restarted.
+#[tauri::command]
+pub async fn switch_gateway_profile(
+    db: State<'_, AgentDb>,
+    state: State<'_, LLMGatewayState>,
+    name: String,
+) -> Result<GatewaySettings, String> {
+    let settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        profiles::switch(&conn, &name)?
+    };
+
+    let (running, port) = {
+        let status = state.status.read().await;
+        (status.running, status.port)
+    };
+    if running {
+        if settings.port != port {
+            log::warn!(
+                "Profile '{}' uses port {}; the gateway keeps serving on {} until restarted",
+                name,
+                settings.port,
+                port
+            );
+        }
+        apply_running_settings(&state, settings.clone()).await;
+    }
+    log::info!("Switched to gateway profile '{}'", name);
+    Ok(settings)
+}
+
+/// Get default providers configuration
+#[tauri::command]
+pub async fn get_default_llm_providers() -> Result<Vec<ProviderConfig>, String> {
+    Ok(get_default_providers())
+}
+
+/// Base URL Claude Code uses to reach the gateway listening on `port`
+fn gateway_base_url(port: u16) -> String {
+    format!("http://127.0.0.1:{}", port)
+}
+
+/// Whether `base_url` reaches the gateway listening on `port`
+fn points_at_gateway(base_url: &str, port: u16) -> bool {
+    reqwest::Url::parse(base_url).is_ok_and(|url| {
+        matches!(url.host_str(), Some("127.0.0.1" | "localhost" | "[::1]"))
+            && url.port_or_known_default() == Some(port)
+    })
+}
+
+/// Value of `key` in the environment `command` runs with
+fn command_env(command: &std::process::Command, key: &str) -> Option<String> {
+    match command.get_envs().find(|(name, _)| *name == key) {
+        Some((_, value)) => value.and_then(|v| v.to_str()).map(str::to_string),
+        None => std::env::var(key).ok(),
+    }
+}
+
+/// Claude Code environment variable sending the gateway's project header after the
+/// `existing` custom headers, so usage is attributed to the project at `project_path`.
+/// A project header already among them is left to win.
+fn project_header_env(project_path: &str, existing: Option<&str>) -> Option<(String, String)> {
+    // Custom headers are newline-separated, and header values are visible ASCII
+    let name: String = std::path::Path::new(project_path)
+        .file_name()?
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_graphic() || c == ' ' { c } else { '_' })
+        .collect();
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let existing = existing.map(str::trim).filter(|e| !e.is_empty());
+    let sets_project = |headers: &str| {
+        headers.lines().any(|line| {
+            line.split(':')
+                .next()
+                .is_some_and(|n| n.trim().eq_ignore_ascii_case(usage::PROJECT_HEADER))
+        })
+    };
+    if existing.is_some_and(sets_project) {
+        return None;
+    }
+
+    let header = format!("{}: {}", usage::PROJECT_HEADER, name);
+    let value = match existing {
+        Some(existing) => format!("{}\n{}", existing, header),
+        None => header,
+    };
+    Some(("ANTHROPIC_CUSTOM_HEADERS".to_string(), value))
+}
+
+/// Project attribution for a Claude Code session the app launches in `project_path`
+/// with `command`, when the gateway is running and the session's base URL points at it
+pub async fn project_attribution_env(
+    app: &AppHandle,
+    command: &std::process::Command,
+    project_path: &str,
+) -> Option<(String, String)> {
+    let state = app.try_state::<LLMGatewayState>()?;
+    let status = state.status.read().await;
+    if !status.running {
+        return None;
+    }
+    let base_url = command_env(command, "ANTHROPIC_BASE_URL")?;
+    if !points_at_gateway(&base_url, status.port) {
+        return None;
+    }
+    project_header_env(
+        project_path,
+        command_env(command, "ANTHROPIC_CUSTOM_HEADERS").as_deref(),
+    )
+}
+
+/// Generate environment variables for Claude Code to use the gateway, attributing its
+/// usage to the project at `project_path` when given
+#[tauri::command]
+pub async fn get_gateway_env_vars(
+    state: State<'_, LLMGatewayState>,
+    project_path: Option<String>,
+) -> Result<HashMap<String, String>, String> {
+    let status = state.status.read().await;
+    let settings = state.settings.read().await;
+
+    if !status.running {
+        return Err("Gateway is not running".to_string());
+    }
+
+    let mut env_vars = HashMap::new();
+    
+    // Set the API base URL to point to our local gateway
+    env_vars.insert("ANTHROPIC_BASE_URL".to_string(), gateway_base_url(status.port));
+    
+    // Use a placeholder API key (the gateway handles actual auth)
+    env_vars.insert(
+        "ANTHROPIC_API_KEY".to_string(),
+        "gateway-proxy-key".to_string(),
+    );
+
+    // Clear any conflicting auth token
+    env_vars.insert("ANTHROPIC_AUTH_TOKEN".to_string(), String::new());
+
+    // Add provider-specific info for debugging
+    env_vars.insert(
+        "LLM_GATEWAY_PROVIDER".to_string(),
+        settings.default_provider.to_string(),
+    );
+
+    let custom_headers = std::env::var("ANTHROPIC_CUSTOM_HEADERS").ok();
+    env_vars.extend(
+        project_path
+            .as_deref()
+            .and_then(|path| project_header_env(path, custom_headers.as_deref())),
+    );
+
+    Ok(env_vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_providers() {
+        let providers = get_default_providers();
+        assert!(!providers.is_empty());
+        
+        // Check that OpenAI is included
+        let openai = providers.iter().find(|p| p.provider == LLMProvider::OpenAI);
+        assert!(openai.is_some());
+        
+        // Check that models are defined
+        let openai = openai.unwrap();
+        assert!(!openai.models.is_empty());
+    }
+
+    #[test]
+    fn test_gateway_settings_default() {
+        let settings = GatewaySettings::default();
+        assert_eq!(settings.port, 8765);
+        assert!(!settings.enabled);
+        assert!(!settings.providers.is_empty());
+    }
+
+    #[test]
+    fn test_request_headers() {
+        let provider = ProviderConfig {
+            openai_organization: Some("org-abc".to_string()),
+            openai_project: Some(String::new()),
+            headers: HashMap::from([("X-Team".to_string(), "ml".to_string())]),
+            ..Default::default()
+        };
+        assert_eq!(
+            provider.request_headers().collect::<Vec<_>>(),
+            vec![("OpenAI-Organization", "org-abc"), ("X-Team", "ml")]
+        );
+    }
+
+    #[test]
+    fn test_parse_models_response() {
+        // vLLM reports the context window as max_model_len
+        let body = serde_json::json!({
+            "object": "list",
+            "data": [
+                {"id": "Qwen/Qwen2.5-Coder-32B-Instruct", "object": "model", "max_model_len": 32768},
+                {"id": "meta-llama/Llama-3.1-8B-Instruct", "object": "model"}
+            ]
+        });
+        let models = parse_models_response(&body);
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].id, "Qwen/Qwen2.5-Coder-32B-Instruct");
+        assert!(models[0].is_default);
+        assert!(!models[1].is_default);
+        assert_eq!(models[1].max_tokens, DEFAULT_DISCOVERED_CONTEXT);
+
+        // Some servers return a bare array
+        let bare = serde_json::json!([{"id": "local-model"}]);
+        assert_eq!(parse_models_response(&bare).len(), 1);
+    }
+
+    #[test]
+    fn test_merge_discovered_models() {
+        let mut models = get_default_providers()[0].models.clone();
+        let configured = models.len();
+        let body = serde_json::json!({"data": [{"id": "gpt-4o"}, {"id": "gpt-4.1"}]});
+
+        let added = merge_discovered_models(&mut models, parse_models_response(&body));
+        assert_eq!(added, vec!["gpt-4.1".to_string()]);
+        assert_eq!(models.len(), configured + 1);
+
+        // Configured entries keep their pricing, new ones are flagged
+        let gpt4o = models.iter().find(|m| m.id == "gpt-4o").unwrap();
+        assert!(gpt4o.is_default && !gpt4o.pricing_unknown);
+        let discovered = models.last().unwrap();
+        assert!(discovered.pricing_unknown && !discovered.is_default);
+    }
+
+    #[test]
+    fn test_project_header_env() {
+        let header = |path: &str, existing: Option<&str>| {
+            project_header_env(path, existing).map(|(key, value)| {
+                assert_eq!(key, "ANTHROPIC_CUSTOM_HEADERS");
+                value
+            })
+        };
+        assert_eq!(
+            header("/work/doggy", None).as_deref(),
+            Some("x-doggy-project: doggy")
+        );
+        assert_eq!(
+            header("/work/doggy", Some("X-Team: infra\n")).as_deref(),
+            Some("X-Team: infra\nx-doggy-project: doggy")
+        );
+        assert_eq!(header("/work/doggy", Some("X-Doggy-Project: other")), None);
+        assert_eq!(
+            header("/work/bad\nX-Evil: 1", None).as_deref(),
+            Some("x-doggy-project: bad_X-Evil: 1")
+        );
+        assert_eq!(header("/", None), None);
+
+        assert!(points_at_gateway(&gateway_base_url(8080), 8080));
+        assert!(points_at_gateway("http://localhost:8080/", 8080));
+        assert!(!points_at_gateway("http://127.0.0.1:9090", 8080));
+        assert!(!points_at_gateway("https://api.anthropic.com", 8080));
+    }
+}
@@ -0,0 +1,77 @@
+//! Moderation - Screening of outgoing prompts with a moderation model
+//!
+//! When enabled, the prompt text of every chat request is sent to an OpenAI-compatible
+//! `/moderations` endpoint before the request is dispatched. Requests flagged in one of
+//! the configured categories are blocked with an explanation, or let through and logged
+//! when the policy only flags them. Moderation failures let requests through, so an
+//! outage of the moderation provider doesn't take the gateway down with it.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// What happens to a request the moderation model flags
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+    /// Fail the request with the flagged categories
+    #[default]
+    Block,
+    /// Serve the request and log the flagged categories
+    Flag,
+}
+
+/// Full moderation endpoint URL
+pub fn moderation_url(base_url: &str) -> String {
+    format!("{}/moderations", base_url.trim_end_matches('/'))
+}
+
+/// Moderation request for a prompt's text
+pub fn moderation_request(text: &str, model: &str) -> Value {
+    json!({"model": model, "input": text})
+}
+
+/// Categories a moderation response flags, limited to `categories` unless it is empty
+pub fn flagged_categories(response: &Value, categories: &[String]) -> Vec<String> {
+    let mut flagged: Vec<String> = response
+        .get("results")
+        .and_then(|r| r.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|result| result.get("categories").and_then(|c| c.as_object()))
+        .flat_map(|result| {
+            result
+                .iter()
+                .filter(|(_, flagged)| flagged.as_bool() == Some(true))
+                .map(|(category, _)| category.clone())
+        })
+        .filter(|category| categories.is_empty() || categories.contains(category))
+        .collect();
+    flagged.sort();
+    flagged.dedup();
+    flagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flagged_categories() {
+        let response = json!({
+            "results": [{
+                "flagged": true,
+                "categories": {"violence": true, "harassment": false, "self-harm": true},
+            }],
+        });
+        assert_eq!(
+            flagged_categories(&response, &[]),
+            vec!["self-harm", "violence"]
+        );
+        assert_eq!(
+            flagged_categories(&response, &["violence".to_string()]),
+            vec!["violence"]
+        );
+        assert!(flagged_categories(&response, &["sexual".to_string()]).is_empty());
+        assert!(flagged_categories(&json!({"results": []}), &[]).is_empty());
+    }
+}
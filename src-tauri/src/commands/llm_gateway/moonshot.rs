@@ -0,0 +1,170 @@
+//! Moonshot Context Caching - Explicit caches for the prefixes clients mark as cacheable
+//!
+//! Claude Code marks its long, stable prefix (system prompt and early turns) with
+//! Anthropic `cache_control` breakpoints. Moonshot has no implicit prompt caching but
+//! lets clients upload a prefix once through its caching API and then reference it with a
+//! `role: cache` message, which bills the cached tokens at a fraction of the price. The
+//! messages up to the last breakpoint are cached here under a hash of their content, so
+//! repeated requests with the same prefix reuse one cache until it expires.
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::caching::strip_cache_control;
+use super::limits;
+use super::ProviderConfig;
+
+/// Model family caches are created for
+pub const CACHE_MODEL: &str = "moonshot-v1";
+
+/// Lifetime of a cache, renewed whenever a request uses it
+pub const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Smallest prefix worth the cost of creating a cache
+const MIN_CACHED_TOKENS: u64 = 1024;
+
+/// Whether a request fragment carries a `cache_control` breakpoint
+fn has_breakpoint(value: &Value) -> bool {
+    match value {
+        Value::Object(obj) => obj.contains_key("cache_control") || obj.values().any(has_breakpoint),
+        Value::Array(items) => items.iter().any(has_breakpoint),
+        _ => false,
+    }
+}
+
+/// Leading messages of an OpenAI-shaped request through its last breakpoint, if they
+/// are long enough to cache. The final message is never cached, since it is the turn
+/// being answered.
+pub fn cacheable_prefix(body: &Value) -> Option<usize> {
+    let messages = body.get("messages")?.as_array()?;
+    let count = messages
+        .iter()
+        .rposition(has_breakpoint)
+        .map(|last| last + 1)?
+        .min(messages.len().saturating_sub(1));
+    let prefix = json!({ "messages": &messages[..count] });
+    (count > 0 && limits::estimate_prompt_tokens(&prefix) >= MIN_CACHED_TOKENS).then_some(count)
+}
+
+/// Cache key of a request prefix, scoped to the account whose key created it
+pub fn prefix_key(provider: &ProviderConfig, body: &Value, count: usize) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(provider.base_url.as_bytes());
+    hasher.update(provider.api_key.as_deref().unwrap_or_default().as_bytes());
+    if let Some(messages) = body.get("messages").and_then(|m| m.as_array()) {
+        for message in messages.iter().take(count) {
+            hasher.update(message.to_string().as_bytes());
+        }
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Body creating a cache of a request's first `count` messages
+pub fn create_request(body: &Value, count: usize) -> Value {
+    let mut messages: Vec<Value> = body["messages"]
+        .as_array()
+        .map(|m| m.iter().take(count).cloned().collect())
+        .unwrap_or_default();
+    messages.iter_mut().for_each(strip_cache_control);
+    json!({
+        "model": CACHE_MODEL,
+        "messages": messages,
+        "ttl": CACHE_TTL.as_secs(),
+    })
+}
+
+/// Replace a request's first `count` messages with a reference to cache `id`
+pub fn use_cache(body: &mut Value, count: usize, id: &str) {
+    let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+        return;
+    };
+    messages.splice(
+        ..count.min(messages.len()),
+        [json!({
+            "role": "cache",
+            "content": format!("cache_id={};reset_ttl={}", id, CACHE_TTL.as_secs()),
+        })],
+    );
+}
+
+/// Caches created by this gateway, by prefix key
+#[derive(Debug, Default)]
+pub struct ContextCaches {
+    entries: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl ContextCaches {
+    /// Cache id for a prefix, unless it has expired
+    pub fn get(&self, key: &str, now: Instant) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (_, expires)| *expires > now);
+        entries.get(key).map(|(id, _)| id.clone())
+    }
+
+    /// Record a cache, or its renewed lifetime after a request used it
+    pub fn insert(&self, key: String, id: String, now: Instant) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, (id, now + CACHE_TTL));
+    }
+
+    /// Forget a cache the provider no longer accepts
+    pub fn remove(&self, key: &str) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_cache() {
+        let long = "Follow the repository conventions. ".repeat(400);
+        let mut body = json!({
+            "model": "moonshot-v1-128k",
+            "messages": [
+                {"role": "system", "content": [
+                    {"type": "text", "text": long, "cache_control": {"type": "ephemeral"}}
+                ]},
+                {"role": "user", "content": "Fix the bug"}
+            ]
+        });
+        assert_eq!(cacheable_prefix(&body), Some(1));
+        assert_eq!(
+            cacheable_prefix(&json!({"messages": [{"role": "user", "content": "hi"}]})),
+            None
+        );
+
+        let create = create_request(&body, 1);
+        assert_eq!(create["model"], CACHE_MODEL);
+        assert!(create["messages"][0]["content"][0]
+            .get("cache_control")
+            .is_none());
+
+        use_cache(&mut body, 1, "cache-abc");
+        assert_eq!(body["messages"][0]["role"], "cache");
+        assert_eq!(
+            body["messages"][0]["content"],
+            "cache_id=cache-abc;reset_ttl=3600"
+        );
+        assert_eq!(body["messages"][1]["content"], "Fix the bug");
+
+        let caches = ContextCaches::default();
+        let now = Instant::now();
+        caches.insert("k".to_string(), "cache-abc".to_string(), now);
+        assert_eq!(caches.get("k", now).as_deref(), Some("cache-abc"));
+        assert_eq!(caches.get("k", now + CACHE_TTL), None);
+    }
+}
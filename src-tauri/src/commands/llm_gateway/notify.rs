@@ -0,0 +1,426 @@
+//! Notifications - Desktop notifications and webhooks about spend and provider health
+//!
+//! The server raises a [`GatewayAlert`] when today's spend crosses a configured
+//! threshold, when the month's spend reaches the budget or is forecast to, when a
+//! provider starts failing or recovers, when failover moved requests to another
+//! provider, and when a request fails. Alerts the [`NotificationConfig`] enables are
+//! shown as desktop notifications, except request errors, which are too frequent for
+//! that; every alert also goes to the webhooks whose filter selects it.
+//!
+//! A provider counts as down after a connection error or a 5xx answer and as
+//! recovered after its next success; client errors don't change it. Failover between
+//! the same two providers is announced at most once per [`FAILOVER_QUIET_PERIOD`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+use tokio::sync::RwLock;
+
+use super::{forecast, webhooks, GatewaySettings};
+use crate::commands::agents::AgentDb;
+
+/// Minimum time between two announcements of the same failover
+const FAILOVER_QUIET_PERIOD: Duration = Duration::from_secs(600);
+
+/// Longest upstream error text carried by a request error alert
+const MAX_ERROR_CHARS: usize = 500;
+
+fn default_true() -> bool {
+    true
+}
+
+/// Which gateway events are announced with desktop notifications
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NotificationConfig {
+    /// Whether notifications are shown at all
+    pub enabled: bool,
+    /// Amounts of today's spend in USD announced when crossed
+    #[serde(default)]
+    pub spend_thresholds_usd: Vec<f64>,
+    /// Announce reaching the monthly budget, or a forecast exceeding it
+    #[serde(default = "default_true")]
+    pub budget: bool,
+    /// Announce providers going down and recovering
+    #[serde(default = "default_true")]
+    pub provider_health: bool,
+    /// Announce requests moved to another provider by failover
+    #[serde(default = "default_true")]
+    pub failover: bool,
+}
+
+/// Kind of a [`GatewayAlert`], used to filter webhooks
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertEvent {
+    SpendThreshold,
+    BudgetExceeded,
+    BudgetForecast,
+    ProviderDown,
+    ProviderRecovered,
+    Failover,
+    RequestError,
+}
+
+impl AlertEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertEvent::SpendThreshold => "spend_threshold",
+            AlertEvent::BudgetExceeded => "budget_exceeded",
+            AlertEvent::BudgetForecast => "budget_forecast",
+            AlertEvent::ProviderDown => "provider_down",
+            AlertEvent::ProviderRecovered => "provider_recovered",
+            AlertEvent::Failover => "failover",
+            AlertEvent::RequestError => "request_error",
+        }
+    }
+}
+
+/// Something about the gateway worth telling the user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GatewayAlert {
+    SpendThreshold {
+        threshold_usd: f64,
+        today_spend_usd: f64,
+    },
+    BudgetExceeded {
+        budget_usd: f64,
+        month_spend_usd: f64,
+    },
+    BudgetForecast {
+        budget_usd: f64,
+        projected_usd: f64,
+    },
+    ProviderDown {
+        provider: String,
+        error: String,
+    },
+    ProviderRecovered {
+        provider: String,
+    },
+    Failover {
+        from: String,
+        to: String,
+    },
+    RequestError {
+        provider: String,
+        model: String,
+        status_code: u16,
+        error: String,
+    },
+}
+
+impl GatewayAlert {
+    pub fn event(&self) -> AlertEvent {
+        match self {
+            GatewayAlert::SpendThreshold { .. } => AlertEvent::SpendThreshold,
+            GatewayAlert::BudgetExceeded { .. } => AlertEvent::BudgetExceeded,
+            GatewayAlert::BudgetForecast { .. } => AlertEvent::BudgetForecast,
+            GatewayAlert::ProviderDown { .. } => AlertEvent::ProviderDown,
+            GatewayAlert::ProviderRecovered { .. } => AlertEvent::ProviderRecovered,
+            GatewayAlert::Failover { .. } => AlertEvent::Failover,
+            GatewayAlert::RequestError { .. } => AlertEvent::RequestError,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            GatewayAlert::SpendThreshold { .. } => "Gateway spend",
+            GatewayAlert::BudgetExceeded { .. } => "Gateway budget reached",
+            GatewayAlert::BudgetForecast { .. } => "Gateway budget forecast",
+            GatewayAlert::ProviderDown { .. } => "Provider down",
+            GatewayAlert::ProviderRecovered { .. } => "Provider recovered",
+            GatewayAlert::Failover { .. } => "Provider failover",
+            GatewayAlert::RequestError { .. } => "Request failed",
+        }
+    }
+
+    fn body(&self) -> String {
+        match self {
+            GatewayAlert::SpendThreshold {
+                threshold_usd,
+                today_spend_usd,
+            } => format!(
+                "Today's spend passed ${:.2} and is now ${:.2}",
+                threshold_usd, today_spend_usd
+            ),
+            GatewayAlert::BudgetExceeded {
+                budget_usd,
+                month_spend_usd,
+            } => format!(
+                "This month's spend of ${:.2} reached the ${:.2} budget",
+                month_spend_usd, budget_usd
+            ),
+            GatewayAlert::BudgetForecast {
+                budget_usd,
+                projected_usd,
+            } => format!(
+                "This month's spend is on track for ${:.2}, over the ${:.2} budget",
+                projected_usd, budget_usd
+            ),
+            GatewayAlert::ProviderDown { provider, error } => {
+                format!("{} is failing: {}", provider, error)
+            }
+            GatewayAlert::ProviderRecovered { provider } => {
+                format!("{} is answering again", provider)
+            }
+            GatewayAlert::Failover { from, to } => {
+                format!("Requests for {} are being served by {}", from, to)
+            }
+            GatewayAlert::RequestError {
+                provider,
+                model,
+                status_code,
+                error,
+            } => format!(
+                "{} request to {} failed with {}: {}",
+                provider, model, status_code, error
+            ),
+        }
+    }
+
+    fn enabled_in(&self, config: &NotificationConfig) -> bool {
+        config.enabled
+            && match self {
+                GatewayAlert::SpendThreshold { .. } => true,
+                GatewayAlert::BudgetExceeded { .. } | GatewayAlert::BudgetForecast { .. } => {
+                    config.budget
+                }
+                GatewayAlert::ProviderDown { .. } | GatewayAlert::ProviderRecovered { .. } => {
+                    config.provider_health
+                }
+                GatewayAlert::Failover { .. } => config.failover,
+                GatewayAlert::RequestError { .. } => false,
+            }
+    }
+}
+
+/// Highest threshold passed when a total went from `before` to `after`
+fn crossed(thresholds: &[f64], before: f64, after: f64) -> Option<f64> {
+    thresholds
+        .iter()
+        .copied()
+        .filter(|t| before < *t && *t <= after)
+        .max_by(f64::total_cmp)
+}
+
+/// Raises alerts for the server, remembering what was already announced
+pub struct Notifier {
+    app: AppHandle,
+    settings: Arc<RwLock<GatewaySettings>>,
+    /// Providers currently considered down
+    down: Mutex<HashSet<String>>,
+    /// Last announcement of each failover, by the providers involved
+    failovers: Mutex<HashMap<(String, String), Instant>>,
+    /// Month (YYYY-MM) whose budget forecast was announced
+    forecast_month: Mutex<Option<String>>,
+}
+
+impl Notifier {
+    pub fn new(app: AppHandle, settings: Arc<RwLock<GatewaySettings>>) -> Self {
+        Self {
+            app,
+            settings,
+            down: Mutex::new(HashSet::new()),
+            failovers: Mutex::new(HashMap::new()),
+            forecast_month: Mutex::new(None),
+        }
+    }
+
+    /// Deliver an alert to the webhooks that want it and announce it, if
+    /// notifications for it are enabled
+    pub async fn raise(&self, alert: GatewayAlert) {
+        let (config, hooks) = {
+            let settings = self.settings.read().await;
+            (settings.notifications.clone(), settings.webhooks.clone())
+        };
+        webhooks::deliver(&hooks, &alert);
+        if !config.is_some_and(|c| alert.enabled_in(&c)) {
+            return;
+        }
+        log::info!("Gateway alert: {}", alert.body());
+        let shown = self
+            .app
+            .notification()
+            .builder()
+            .title(alert.title())
+            .body(alert.body())
+            .show();
+        if let Err(e) = shown {
+            log::warn!("Failed to show notification: {}", e);
+        }
+    }
+
+    /// Note the outcome of a provider request: `outage` carries the error of a
+    /// connection failure or 5xx answer
+    pub async fn provider_result(&self, provider: &str, outage: Option<String>) {
+        let changed = match self.down.lock() {
+            Ok(mut down) => match &outage {
+                Some(_) => down.insert(provider.to_string()),
+                None => down.remove(provider),
+            },
+            Err(_) => false,
+        };
+        if !changed {
+            return;
+        }
+        let provider = provider.to_string();
+        self.raise(match outage {
+            Some(error) => GatewayAlert::ProviderDown { provider, error },
+            None => GatewayAlert::ProviderRecovered { provider },
+        })
+        .await;
+    }
+
+    /// Note a request that failed with `status_code`
+    pub async fn request_error(&self, provider: &str, model: &str, status_code: u16, error: &str) {
+        self.raise(GatewayAlert::RequestError {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            status_code,
+            error: error.chars().take(MAX_ERROR_CHARS).collect(),
+        })
+        .await;
+    }
+
+    /// Note that failover moved a request from one provider to another
+    pub async fn failover(&self, from: &str, to: &str) {
+        if from == to {
+            return;
+        }
+        let now = Instant::now();
+        let key = (from.to_string(), to.to_string());
+        let due = match self.failovers.lock() {
+            Ok(mut failovers) => {
+                let due = failovers
+                    .get(&key)
+                    .is_none_or(|last| now.duration_since(*last) >= FAILOVER_QUIET_PERIOD);
+                if due {
+                    failovers.insert(key, now);
+                }
+                due
+            }
+            Err(_) => false,
+        };
+        if due {
+            self.raise(GatewayAlert::Failover {
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+            .await;
+        }
+    }
+
+    /// Note a request costing `cost_usd`, after which today's and the month's spend
+    /// stand at the given totals
+    pub async fn spend(&self, cost_usd: f64, today_spend_usd: f64, month_spend_usd: f64) {
+        let (thresholds, budget) = {
+            let settings = self.settings.read().await;
+            let thresholds = settings
+                .notifications
+                .as_ref()
+                .map(|n| n.spend_thresholds_usd.clone())
+                .unwrap_or_default();
+            (thresholds, settings.monthly_budget_usd)
+        };
+        if let Some(threshold_usd) =
+            crossed(&thresholds, today_spend_usd - cost_usd, today_spend_usd)
+        {
+            self.raise(GatewayAlert::SpendThreshold {
+                threshold_usd,
+                today_spend_usd,
+            })
+            .await;
+        }
+
+        let Some(budget_usd) = budget.filter(|b| *b > 0.0) else {
+            return;
+        };
+        if crossed(&[budget_usd], month_spend_usd - cost_usd, month_spend_usd).is_some() {
+            self.raise(GatewayAlert::BudgetExceeded {
+                budget_usd,
+                month_spend_usd,
+            })
+            .await;
+            return;
+        }
+        if month_spend_usd < budget_usd {
+            self.check_forecast(budget_usd).await;
+        }
+    }
+
+    /// Announce, once a month, a forecast exceeding the budget
+    async fn check_forecast(&self, budget_usd: f64) {
+        let now = chrono::Local::now().naive_local();
+        let month = now.format("%Y-%m").to_string();
+        if self
+            .forecast_month
+            .lock()
+            .is_ok_and(|m| m.as_deref() == Some(month.as_str()))
+        {
+            return;
+        }
+        let projected_usd = {
+            let db = self.app.state::<AgentDb>();
+            let Ok(conn) = db.0.get() else {
+                return;
+            };
+            match forecast::forecast(&conn, now, forecast::DEFAULT_WINDOW_DAYS) {
+                Ok(report) => report.total.projected_usd,
+                Err(e) => {
+                    log::warn!("Failed to forecast spend: {}", e);
+                    return;
+                }
+            }
+        };
+        if projected_usd <= budget_usd {
+            return;
+        }
+        if let Ok(mut m) = self.forecast_month.lock() {
+            *m = Some(month);
+        }
+        self.raise(GatewayAlert::BudgetForecast {
+            budget_usd,
+            projected_usd,
+        })
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossed_thresholds() {
+        let thresholds = [1.0, 5.0, 10.0];
+        assert_eq!(crossed(&thresholds, 0.5, 0.9), None);
+        assert_eq!(crossed(&thresholds, 0.9, 1.0), Some(1.0));
+        assert_eq!(crossed(&thresholds, 0.9, 6.0), Some(5.0));
+        assert_eq!(crossed(&thresholds, 1.0, 4.0), None);
+    }
+
+    #[test]
+    fn test_alert_filters() {
+        let config: NotificationConfig =
+            serde_json::from_str(r#"{"enabled": true, "failover": false}"#).unwrap();
+        let down = GatewayAlert::ProviderDown {
+            provider: "openai".to_string(),
+            error: "502 Bad Gateway".to_string(),
+        };
+        assert!(down.enabled_in(&config));
+        assert_eq!(down.body(), "openai is failing: 502 Bad Gateway");
+        let failover = GatewayAlert::Failover {
+            from: "OpenAI".to_string(),
+            to: "Anthropic".to_string(),
+        };
+        assert!(!failover.enabled_in(&config));
+        assert_eq!(
+            serde_json::to_value(&failover).unwrap()["event"],
+            "failover"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! Ollama API - `/api/chat` and `/api/tags` on top of chat completions
+//!
+//! Editors and plugins that only speak Ollama can point at the gateway in place of a
+//! local Ollama server and reach any configured provider. Chat requests are translated
+//! to chat completions and back; streams use Ollama's newline-delimited JSON, with
+//! function calls delivered whole in the final line once their arguments are complete.
+
+use serde_json::{json, Value};
+
+use super::translate::{parse_arguments, SseParser, StreamTranslator};
+use super::GatewaySettings;
+
+/// Ollama options with a direct chat completion equivalent
+const OPTION_FIELDS: &[(&str, &str)] = &[
+    ("temperature", "temperature"),
+    ("top_p", "top_p"),
+    ("num_predict", "max_tokens"),
+    ("stop", "stop"),
+    ("seed", "seed"),
+    ("presence_penalty", "presence_penalty"),
+    ("frequency_penalty", "frequency_penalty"),
+];
+
+fn timestamp() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+}
+
+/// Whether the client wants a streamed answer; Ollama streams unless told not to
+pub fn is_stream(request: &Value) -> bool {
+    request.get("stream").and_then(|s| s.as_bool()) != Some(false)
+}
+
+/// Chat completion request equivalent to an Ollama `/api/chat` request
+pub fn ollama_to_chat_request(request: &Value) -> Value {
+    let mut messages = Vec::new();
+    // Ollama has no call IDs: calls are numbered and tool results answer the oldest
+    // open call of their tool
+    let mut open_calls: Vec<(String, String)> = Vec::new();
+    let mut call_count = 0;
+    for message in request
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let role = message
+            .get("role")
+            .and_then(|r| r.as_str())
+            .unwrap_or("user");
+        let text = message
+            .get("content")
+            .and_then(|c| c.as_str())
+            .unwrap_or_default();
+
+        if role == "tool" {
+            let name = message.get("tool_name").and_then(|n| n.as_str());
+            let position = open_calls
+                .iter()
+                .position(|(call, _)| name.is_none_or(|name| call == name));
+            let id = match position {
+                Some(position) => open_calls.remove(position).1,
+                None => format!("call_{}", call_count),
+            };
+            messages.push(json!({"role": "tool", "tool_call_id": id, "content": text}));
+            continue;
+        }
+
+        let mut chat_message = json!({"role": role, "content": text});
+        if let Some(images) = message.get("images").and_then(|i| i.as_array()) {
+            let mut parts = vec![json!({"type": "text", "text": text})];
+            parts.extend(images.iter().filter_map(|i| i.as_str()).map(|image| {
+                json!({
+                    "type": "image_url",
+                    "image_url": {"url": format!("data:image/png;base64,{}", image)},
+                })
+            }));
+            chat_message["content"] = Value::Array(parts);
+        }
+        if let Some(calls) = message.get("tool_calls").and_then(|c| c.as_array()) {
+            let calls: Vec<Value> = calls
+                .iter()
+                .map(|call| {
+                    let name = call
+                        .pointer("/function/name")
+                        .and_then(|n| n.as_str())
+                        .unwrap_or_default();
+                    let id = format!("call_{}", call_count);
+                    call_count += 1;
+                    open_calls.push((name.to_string(), id.clone()));
+                    let arguments = call.pointer("/function/arguments").unwrap_or(&Value::Null);
+                    json!({
+                        "id": id,
+                        "type": "function",
+                        "function": {
+                            "name": name,
+                            "arguments": parse_arguments(Some(arguments)).to_string(),
+                        },
+                    })
+                })
+                .collect();
+            chat_message["tool_calls"] = Value::Array(calls);
+        }
+        messages.push(chat_message);
+    }
+
+    let stream = is_stream(request);
+    let mut chat = json!({
+        "model": request.get("model").cloned().unwrap_or(Value::Null),
+        "messages": messages,
+        "stream": stream,
+    });
+    if stream {
+        chat["stream_options"] = json!({"include_usage": true});
+    }
+    let options = request.get("options");
+    for (ollama, openai) in OPTION_FIELDS {
+        if let Some(value) = options.and_then(|o| o.get(*ollama)) {
+            chat[*openai] = value.clone();
+        }
+    }
+    if let Some(tools) = request
+        .get("tools")
+        .and_then(|t| t.as_array())
+        .filter(|t| !t.is_empty())
+    {
+        chat["tools"] = Value::Array(tools.clone());
+    }
+    match request.get("format") {
+        Some(Value::String(format)) if format == "json" => {
+            chat["response_format"] = json!({"type": "json_object"})
+        }
+        Some(schema @ Value::Object(_)) => {
+            chat["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": {"name": "response", "schema": schema},
+            })
+        }
+        _ => {}
+    }
+    chat
+}
+
+fn tool_calls(calls: &[Value]) -> Vec<Value> {
+    calls
+        .iter()
+        .map(|call| {
+            json!({
+                "function": {
+                    "name": call.pointer("/function/name").cloned().unwrap_or_default(),
+                    "arguments": parse_arguments(call.pointer("/function/arguments")),
+                },
+            })
+        })
+        .collect()
+}
+
+/// Fields of the final line of an Ollama answer
+fn done_fields(line: &mut Value, finish_reason: Option<&str>, usage: Option<&Value>) {
+    let count = |pointer: &str| {
+        usage
+            .and_then(|u| u.pointer(pointer))
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0)
+    };
+    line["done"] = json!(true);
+    line["done_reason"] = json!(match finish_reason {
+        Some("length") => "length",
+        _ => "stop",
+    });
+    line["prompt_eval_count"] = json!(count("/prompt_tokens"));
+    line["eval_count"] = json!(count("/completion_tokens"));
+}
+
+/// Ollama `/api/chat` response for a chat completion
+pub fn chat_to_ollama_response(body: &Value, model: &str) -> Value {
+    let message = body.pointer("/choices/0/message");
+    let mut response = json!({
+        "model": model,
+        "created_at": timestamp(),
+        "message": {
+            "role": "assistant",
+            "content": message
+                .and_then(|m| m.get("content"))
+                .and_then(|c| c.as_str())
+                .unwrap_or_default(),
+        },
+    });
+    if let Some(calls) = message
+        .and_then(|m| m.get("tool_calls"))
+        .and_then(|c| c.as_array())
+        .filter(|c| !c.is_empty())
+    {
+        response["message"]["tool_calls"] = Value::Array(tool_calls(calls));
+    }
+    done_fields(
+        &mut response,
+        body.pointer("/choices/0/finish_reason")
+            .and_then(|r| r.as_str()),
+        body.get("usage"),
+    );
+    response
+}
+
+/// Ollama `/api/tags` listing of the models and aliases the gateway can route
+pub fn list_tags(settings: &GatewaySettings) -> Value {
+    let modified_at = timestamp();
+    let tag = |name: &str, family: &str| {
+        json!({
+            "name": name,
+            "model": name,
+            "modified_at": modified_at,
+            "size": 0,
+            "digest": "",
+            "details": {
+                "format": "",
+                "family": family,
+                "families": [family],
+                "parameter_size": "",
+                "quantization_level": "",
+            },
+        })
+    };
+
+    let mut models: Vec<Value> = settings
+        .providers
+        .iter()
+        .filter(|p| p.enabled)
+        .flat_map(|p| {
+            let family = p.provider.to_string();
+            p.models.iter().map(move |m| (m.id.clone(), family.clone()))
+        })
+        .map(|(id, family)| tag(&id, &family))
+        .collect();
+    let mut aliases: Vec<&String> = settings.model_aliases.keys().collect();
+    aliases.sort();
+    models.extend(aliases.into_iter().map(|alias| tag(alias, "alias")));
+    json!({ "models": models })
+}
+
+/// Converts chat completion chunks into Ollama's newline-delimited JSON stream
+#[derive(Debug, Default)]
+pub struct OllamaStreamTranslator {
+    parser: SseParser,
+    model: String,
+    /// Streamed calls keyed by their index in the upstream `tool_calls` array
+    calls: Vec<(u64, Value)>,
+    finish_reason: Option<String>,
+    usage: Option<Value>,
+    finished: bool,
+}
+
+impl OllamaStreamTranslator {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            ..Self::default()
+        }
+    }
+
+    fn line(&self, message: Value) -> Value {
+        json!({
+            "model": self.model,
+            "created_at": timestamp(),
+            "message": message,
+            "done": false,
+        })
+    }
+
+    fn push_chunk(&mut self, chunk: &Value) -> String {
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            self.usage = Some(usage.clone());
+        }
+        let Some(choice) = chunk.pointer("/choices/0") else {
+            return String::new();
+        };
+        for call in choice
+            .pointer("/delta/tool_calls")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let index = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+            let arguments = call
+                .pointer("/function/arguments")
+                .and_then(|a| a.as_str())
+                .unwrap_or_default();
+            match self.calls.iter_mut().find(|(i, _)| *i == index) {
+                Some((_, streamed)) => {
+                    let joined = format!(
+                        "{}{}",
+                        streamed["function"]["arguments"]
+                            .as_str()
+                            .unwrap_or_default(),
+                        arguments
+                    );
+                    streamed["function"]["arguments"] = json!(joined);
+                }
+                None => self.calls.push((index, call.clone())),
+            }
+        }
+        if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+        }
+        match choice
+            .pointer("/delta/content")
+            .and_then(|c| c.as_str())
+            .filter(|t| !t.is_empty())
+        {
+            Some(text) => format!(
+                "{}\n",
+                self.line(json!({"role": "assistant", "content": text}))
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Final line with the function calls, done reason and token counts
+    fn complete(&mut self) -> String {
+        if self.finished {
+            return String::new();
+        }
+        self.finished = true;
+        let mut message = json!({"role": "assistant", "content": ""});
+        if !self.calls.is_empty() {
+            let calls: Vec<Value> = self.calls.iter().map(|(_, call)| call.clone()).collect();
+            message["tool_calls"] = Value::Array(tool_calls(&calls));
+        }
+        let mut line = self.line(message);
+        done_fields(
+            &mut line,
+            self.finish_reason.as_deref(),
+            self.usage.as_ref(),
+        );
+        format!("{}\n", line)
+    }
+}
+
+impl StreamTranslator for OllamaStreamTranslator {
+    fn push(&mut self, chunk: &[u8]) -> String {
+        let mut out = String::new();
+        for payload in self.parser.push(chunk) {
+            if payload == "[DONE]" {
+                out.push_str(&self.complete());
+                continue;
+            }
+            if let Ok(chunk) = serde_json::from_str::<Value>(&payload) {
+                out.push_str(&self.push_chunk(&chunk));
+            }
+        }
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        self.complete()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_translation() {
+        let request = json!({
+            "model": "llama3.2",
+            "messages": [
+                {"role": "user", "content": "Describe it", "images": ["aGk="]},
+                {"role": "assistant", "content": "", "tool_calls": [
+                    {"function": {"name": "lookup", "arguments": {"q": "cat"}}},
+                ]},
+                {"role": "tool", "content": "a cat", "tool_name": "lookup"},
+            ],
+            "options": {"num_predict": 100, "temperature": 0.2},
+            "format": "json",
+        });
+        let chat = ollama_to_chat_request(&request);
+        assert_eq!(chat["stream"], true);
+        assert_eq!(
+            chat["messages"][0]["content"][1]["image_url"]["url"],
+            "data:image/png;base64,aGk="
+        );
+        assert_eq!(
+            chat["messages"][1]["tool_calls"][0]["function"]["arguments"],
+            "{\"q\":\"cat\"}"
+        );
+        assert_eq!(chat["messages"][2]["tool_call_id"], "call_0");
+        assert_eq!(chat["max_tokens"], 100);
+        assert_eq!(chat["response_format"]["type"], "json_object");
+    }
+
+    #[test]
+    fn test_response_translation() {
+        let response = chat_to_ollama_response(
+            &json!({
+                "choices": [{"message": {"content": "Hi"}, "finish_reason": "length"}],
+                "usage": {"prompt_tokens": 7, "completion_tokens": 2},
+            }),
+            "llama3.2",
+        );
+        assert_eq!(response["message"]["content"], "Hi");
+        assert_eq!(response["done"], true);
+        assert_eq!(response["done_reason"], "length");
+        assert_eq!(response["prompt_eval_count"], 7);
+
+        let mut translator = OllamaStreamTranslator::new("llama3.2");
+        let out = translator.push(
+            b"data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n\
+              data: {\"choices\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n\
+              data: {\"choices\":[],\"usage\":{\"prompt_tokens\":3,\"completion_tokens\":2}}\n\n\
+              data: [DONE]\n\n",
+        );
+        let lines: Vec<Value> = out
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1]["message"]["content"], "lo");
+        assert_eq!(lines[2]["done"], true);
+        assert_eq!(lines[2]["eval_count"], 2);
+    }
+}
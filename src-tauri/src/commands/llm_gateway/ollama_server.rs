@@ -0,0 +1,385 @@
+//! Ollama Server - Managing the local Ollama instance an Ollama provider points at
+//!
+//! Ollama loads a model on its first request and unloads it after a few idle minutes,
+//! so the first local request after a while stalls for the load. Providers can have
+//! their default model loaded when the gateway starts, and keep models loaded for a
+//! chosen time. Ollama's OpenAI-compatible endpoint resets a model's keep-alive to the
+//! server default on every request, so the configured one is re-applied through the
+//! native API once each request completes.
+//!
+//! The models installed on the server can also be listed, pulled and deleted, with the
+//! provider's model list kept in step with what is installed.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::client::UpstreamClient;
+use super::router;
+use super::{LLMProvider, ModelConfig, ProviderConfig, DEFAULT_DISCOVERED_CONTEXT};
+
+/// Event emitted as a model pull progresses
+pub const PULL_PROGRESS_EVENT: &str = "llm-gateway-ollama-pull";
+
+/// Longest a model pull may take; large models are tens of gigabytes
+const PULL_TIMEOUT: Duration = Duration::from_secs(4 * 60 * 60);
+
+/// Model loading behavior of an Ollama provider
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OllamaConfig {
+    /// Load the default model when the gateway starts
+    #[serde(default)]
+    pub warm_up: bool,
+    /// How long models stay loaded after a request, as an Ollama duration ("30m", "2h")
+    /// or seconds; "-1" keeps them loaded, unset leaves it to the server
+    #[serde(default)]
+    pub keep_alive: Option<String>,
+}
+
+/// URL of a native API endpoint of the server behind an OpenAI-compatible base URL
+pub fn api_url(base_url: &str, path: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    let root = base.strip_suffix("/v1").unwrap_or(base);
+    format!("{}{}", root, path)
+}
+
+/// `keep_alive` as Ollama takes it: whole seconds as a number, durations as strings
+fn keep_alive_value(keep_alive: &str) -> Value {
+    let keep_alive = keep_alive.trim();
+    match keep_alive.parse::<i64>() {
+        Ok(seconds) => json!(seconds),
+        Err(_) => json!(keep_alive),
+    }
+}
+
+/// Generate request without a prompt, which only loads `model` and sets its keep-alive
+pub fn load_request(model: &str, keep_alive: Option<&str>) -> Value {
+    let mut request = json!({ "model": model });
+    if let Some(keep_alive) = keep_alive {
+        request["keep_alive"] = keep_alive_value(keep_alive);
+    }
+    request
+}
+
+/// Configured keep-alive of a provider, if it is an Ollama provider with one
+pub fn keep_alive(provider: &ProviderConfig) -> Option<&str> {
+    if provider.provider != LLMProvider::Ollama {
+        return None;
+    }
+    provider.ollama.as_ref()?.keep_alive.as_deref()
+}
+
+/// Load `model` on the provider's server with its configured keep-alive
+pub async fn load(
+    http: &UpstreamClient,
+    provider: &ProviderConfig,
+    model: &str,
+) -> Result<(), String> {
+    let url = api_url(&provider.base_url, "/api/generate");
+    let request = http
+        .post(provider, url)
+        .json(&load_request(model, keep_alive(provider)));
+    match http.send(provider, request).await {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!(
+            "{}: {}",
+            response.status(),
+            response.text().await.unwrap_or_default()
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Model installed on an Ollama server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstalledModel {
+    /// Model name with its tag, e.g. "llama3.2:latest"
+    pub name: String,
+    /// Size on disk in bytes
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub modified_at: Option<String>,
+    /// Parameter count, e.g. "3.2B"
+    #[serde(default)]
+    pub parameter_size: Option<String>,
+    /// Quantization, e.g. "Q4_K_M"
+    #[serde(default)]
+    pub quantization_level: Option<String>,
+}
+
+/// Payload of [`PULL_PROGRESS_EVENT`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PullProgress {
+    /// Provider the model is pulled to
+    pub provider: String,
+    pub model: String,
+    /// Step reported by Ollama, e.g. "pulling manifest" or "success"
+    pub status: String,
+    /// Bytes of the layer being downloaded
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+}
+
+/// Changes made to a provider's model list to match the installed models
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelSync {
+    /// Provider display name
+    pub name: String,
+    /// IDs of models added to the provider configuration
+    pub added: Vec<String>,
+    /// IDs of models removed because they are no longer installed
+    pub removed: Vec<String>,
+    /// Error listing the installed models; the configuration is left alone
+    pub error: Option<String>,
+}
+
+/// Model ID as configured: Ollama resolves an untagged name to ":latest"
+fn model_id(name: &str) -> &str {
+    name.strip_suffix(":latest").unwrap_or(name)
+}
+
+/// Parse an /api/tags response
+fn parse_tags(body: &Value) -> Vec<InstalledModel> {
+    body.get("models")
+        .and_then(|m| m.as_array())
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|model| {
+                    let details = model.get("details");
+                    let detail = |key: &str| {
+                        details
+                            .and_then(|d| d.get(key))
+                            .and_then(|v| v.as_str())
+                            .map(str::to_string)
+                    };
+                    Some(InstalledModel {
+                        name: model.get("name")?.as_str()?.to_string(),
+                        size: model.get("size").and_then(|v| v.as_u64()).unwrap_or(0),
+                        modified_at: model
+                            .get("modified_at")
+                            .and_then(|v| v.as_str())
+                            .map(str::to_string),
+                        parameter_size: detail("parameter_size"),
+                        quantization_level: detail("quantization_level"),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Models installed on the provider's server
+pub async fn list_models(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+) -> Result<Vec<InstalledModel>, String> {
+    let url = api_url(&provider.base_url, "/api/tags");
+    match client.get(url).send().await {
+        Ok(response) if response.status().is_success() => response
+            .json::<Value>()
+            .await
+            .map(|body| parse_tags(&body))
+            .map_err(|e| format!("Invalid /api/tags response: {}", e)),
+        Ok(response) => Err(format!(
+            "{}: {}",
+            response.status(),
+            response.text().await.unwrap_or_default()
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Pull `model` onto the provider's server, reporting each progress line Ollama sends
+pub async fn pull_model(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    model: &str,
+    mut on_progress: impl FnMut(PullProgress),
+) -> Result<(), String> {
+    let url = api_url(&provider.base_url, "/api/pull");
+    let mut response = client
+        .post(url)
+        .timeout(PULL_TIMEOUT)
+        .json(&json!({ "model": model, "stream": true }))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "{}: {}",
+            response.status(),
+            response.text().await.unwrap_or_default()
+        ));
+    }
+
+    // Progress arrives as newline-delimited JSON, split across chunks arbitrarily
+    let mut buffer = Vec::new();
+    let mut succeeded = false;
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        buffer.extend_from_slice(&chunk);
+        while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let Ok(update) = serde_json::from_slice::<Value>(&line) else {
+                continue;
+            };
+            if let Some(error) = update.get("error").and_then(|v| v.as_str()) {
+                return Err(error.to_string());
+            }
+            let status = update
+                .get("status")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+            succeeded |= status == "success";
+            on_progress(PullProgress {
+                provider: provider.name.clone(),
+                model: model.to_string(),
+                status,
+                total: update.get("total").and_then(|v| v.as_u64()),
+                completed: update.get("completed").and_then(|v| v.as_u64()),
+            });
+        }
+    }
+    if succeeded {
+        Ok(())
+    } else {
+        Err("Pull ended before completing".to_string())
+    }
+}
+
+/// Delete `model` from the provider's server
+pub async fn delete_model(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+    model: &str,
+) -> Result<(), String> {
+    let url = api_url(&provider.base_url, "/api/delete");
+    match client
+        .delete(url)
+        .json(&json!({ "model": model }))
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!(
+            "{}: {}",
+            response.status(),
+            response.text().await.unwrap_or_default()
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Make a provider's model list match the installed models, returning the added and
+/// removed IDs.
+///
+/// Configured models that are still installed keep their settings; if the default was
+/// removed, the first remaining model becomes the default.
+pub fn sync_models(
+    models: &mut Vec<ModelConfig>,
+    installed: &[InstalledModel],
+) -> (Vec<String>, Vec<String>) {
+    let installed: Vec<&str> = installed.iter().map(|m| model_id(&m.name)).collect();
+    let mut removed = Vec::new();
+    models.retain(|m| {
+        let keep = installed.contains(&model_id(&m.id));
+        if !keep {
+            removed.push(m.id.clone());
+        }
+        keep
+    });
+
+    let mut added = Vec::new();
+    for id in installed {
+        if models.iter().any(|m| model_id(&m.id) == id) {
+            continue;
+        }
+        added.push(id.to_string());
+        models.push(ModelConfig {
+            id: id.to_string(),
+            name: id.to_string(),
+            capabilities: vec!["coding".to_string()],
+            max_tokens: DEFAULT_DISCOVERED_CONTEXT,
+            ..Default::default()
+        });
+    }
+
+    if !models.iter().any(|m| m.is_default) {
+        if let Some(first) = models.first_mut() {
+            first.is_default = true;
+        }
+    }
+    (added, removed)
+}
+
+/// Load the default model of every enabled Ollama provider that asks for it
+pub async fn warm_up(http: UpstreamClient, providers: Vec<ProviderConfig>) {
+    for provider in providers.iter().filter(|p| {
+        p.enabled
+            && p.provider == LLMProvider::Ollama
+            && p.ollama.as_ref().is_some_and(|o| o.warm_up)
+    }) {
+        let Some(model) = router::default_model(provider) else {
+            continue;
+        };
+        match load(&http, provider, model).await {
+            Ok(()) => log::info!("Loaded {} on {}", model, provider.name),
+            Err(e) => log::warn!("Failed to load {} on {}: {}", model, provider.name, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_request() {
+        assert_eq!(
+            api_url("http://localhost:11434/v1/", "/api/generate"),
+            "http://localhost:11434/api/generate"
+        );
+        assert_eq!(
+            api_url("http://gpu-box:11434", "/api/tags"),
+            "http://gpu-box:11434/api/tags"
+        );
+        assert_eq!(
+            load_request("llama3.2", Some("30m")),
+            json!({"model": "llama3.2", "keep_alive": "30m"})
+        );
+        assert_eq!(load_request("llama3.2", Some("-1"))["keep_alive"], -1);
+        assert_eq!(load_request("llama3.2", None), json!({"model": "llama3.2"}));
+    }
+
+    #[test]
+    fn test_sync_models() {
+        let installed = parse_tags(&json!({"models": [
+            {"name": "llama3.2:latest", "size": 2019393189,
+             "details": {"parameter_size": "3.2B", "quantization_level": "Q4_K_M"}},
+            {"name": "gemma3:12b", "size": 8149190253u64},
+        ]}));
+        assert_eq!(installed[0].parameter_size.as_deref(), Some("3.2B"));
+
+        let mut models = vec![
+            ModelConfig {
+                id: "deepseek-r1".to_string(),
+                is_default: true,
+                ..Default::default()
+            },
+            ModelConfig {
+                id: "llama3.2".to_string(),
+                max_tokens: 131072,
+                ..Default::default()
+            },
+        ];
+        let (added, removed) = sync_models(&mut models, &installed);
+        assert_eq!(added, vec!["gemma3:12b"]);
+        assert_eq!(removed, vec!["deepseek-r1"]);
+        // The configured entry for an installed model is kept as it was
+        assert_eq!(models[0].max_tokens, 131072);
+        assert!(models[0].is_default);
+        assert_eq!(models[1].max_tokens, DEFAULT_DISCOVERED_CONTEXT);
+    }
+}
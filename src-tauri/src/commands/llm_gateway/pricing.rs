@@ -0,0 +1,429 @@
+//! Pricing Sync - Keeps per-model prices current from a maintained manifest
+//!
+//! The manifest is a JSON object keyed by model ID, optionally prefixed with the provider
+//! (`deepseek/deepseek-chat`). Entries may use per-1M prices (`input_price`/`output_price`)
+//! or LiteLLM-style per-token costs (`input_cost_per_token`/`output_cost_per_token`), with
+//! optional prompt cache prices (`cache_read_price`/`cache_read_input_token_cost` and
+//! `cache_write_price`/`cache_creation_input_token_cost`) and long-prompt tiers
+//! (`tiers` or LiteLLM's `input_cost_per_token_above_200k_tokens` and the like).
+//! User overrides from the settings are applied on top of the manifest.
+//!
+//! Models may also carry time-windowed prices, such as off-peak discounts, which apply
+//! during a window of the provider's local day, and tiered prices, which apply to
+//! requests whose prompt is larger than a threshold. A window's discount carries over to
+//! the tiers, so a long prompt sent off-peak gets both.
+//!
+//! Tiers go by prompt length rather than monthly volume: that is how the manifests and
+//! provider price lists tier per-token prices, while volume discounts are negotiated and
+//! settled on the invoice, out of sight of a single request. A negotiated rate is
+//! configured as the model's price or a pricing override instead.
+
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use super::rules::TimeWindow;
+use super::{GatewaySettings, ModelConfig, ProviderConfig};
+
+/// Manifest used when no custom URL is configured
+pub const DEFAULT_PRICING_MANIFEST_URL: &str =
+    "https://raw.githubusercontent.com/BerriAI/litellm/main/model_prices_and_context_window.json";
+
+/// Prices of a model, per 1M tokens (USD)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelPricing {
+    pub input_price: f64,
+    pub output_price: f64,
+    #[serde(default)]
+    pub cache_read_price: Option<f64>,
+    #[serde(default)]
+    pub cache_write_price: Option<f64>,
+    #[serde(default)]
+    pub tiers: Vec<PriceTier>,
+}
+
+/// Prices a model charges for a whole request once its prompt (cached tokens included)
+/// is larger than a threshold, per 1M tokens (USD)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PriceTier {
+    pub above_input_tokens: u64,
+    pub input_price: f64,
+    pub output_price: f64,
+    /// The model's usual multiple of `input_price` if unset
+    #[serde(default)]
+    pub cache_read_price: Option<f64>,
+    #[serde(default)]
+    pub cache_write_price: Option<f64>,
+}
+
+/// Prices a model charges during part of the provider's day, per 1M tokens (USD)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PriceWindow {
+    /// Hours in the provider's local time
+    #[serde(flatten)]
+    pub hours: TimeWindow,
+    pub input_price: f64,
+    pub output_price: f64,
+}
+
+/// DeepSeek's off-peak window, 00:30-08:30 Beijing time, at the given prices
+pub fn deepseek_off_peak(input_price: f64, output_price: f64) -> PriceWindow {
+    let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap_or_default();
+    PriceWindow {
+        hours: TimeWindow {
+            start: time(0, 30),
+            end: time(8, 30),
+        },
+        input_price,
+        output_price,
+    }
+}
+
+/// URL of the manifest prices (and the model registry) are read from
+pub fn manifest_url(settings: &GatewaySettings) -> String {
+    settings
+        .pricing_manifest_url
+        .clone()
+        .filter(|u| !u.is_empty())
+        .unwrap_or_else(|| DEFAULT_PRICING_MANIFEST_URL.to_string())
+}
+
+/// Fetch the manifest at `url`
+pub async fn fetch_manifest(url: &str) -> Result<Value, String> {
+    let response = super::client::shared_client()
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned {}", url, response.status()));
+    }
+    response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("Invalid pricing manifest: {}", e))
+}
+
+/// `model` with the token prices it charges at `now`, per the provider's local time. Its
+/// tiers are discounted in proportion to the window's prices.
+pub fn priced_at<'a>(
+    provider: &ProviderConfig,
+    model: &'a ModelConfig,
+    now: DateTime<Utc>,
+) -> Cow<'a, ModelConfig> {
+    let offset = chrono::Duration::minutes(provider.utc_offset_minutes as i64);
+    let local = (now.naive_utc() + offset).time();
+    match model.price_windows.iter().find(|w| w.hours.contains(local)) {
+        Some(window) => {
+            let scale = |windowed: f64, usual: f64| {
+                if usual > 0.0 {
+                    windowed / usual
+                } else {
+                    1.0
+                }
+            };
+            let input_scale = scale(window.input_price, model.input_price);
+            let output_scale = scale(window.output_price, model.output_price);
+            let mut model = model.clone();
+            model.input_price = window.input_price;
+            model.output_price = window.output_price;
+            for tier in &mut model.price_tiers {
+                tier.input_price *= input_scale;
+                tier.output_price *= output_scale;
+            }
+            Cow::Owned(model)
+        }
+        None => Cow::Borrowed(model),
+    }
+}
+
+/// `model` with the token prices of the highest tier a prompt of `prompt_tokens` reaches
+pub fn tiered(model: &ModelConfig, prompt_tokens: u64) -> Cow<'_, ModelConfig> {
+    let tier = model
+        .price_tiers
+        .iter()
+        .filter(|t| prompt_tokens > t.above_input_tokens)
+        .max_by_key(|t| t.above_input_tokens);
+    match tier {
+        Some(tier) => {
+            let mut model = model.clone();
+            model.input_price = tier.input_price;
+            model.output_price = tier.output_price;
+            model.cache_read_price = tier.cache_read_price;
+            model.cache_write_price = tier.cache_write_price;
+            Cow::Owned(model)
+        }
+        None => Cow::Borrowed(model),
+    }
+}
+
+/// Outcome of a pricing sync
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PricingSyncResult {
+    /// `provider/model` keys whose prices changed
+    pub updated: Vec<String>,
+    /// `provider/model` keys with no manifest or override entry
+    pub unmatched: Vec<String>,
+    /// Error fetching the manifest (overrides are still applied)
+    pub error: Option<String>,
+}
+
+fn parse_entry(entry: &Value) -> Option<ModelPricing> {
+    let field = |name: &str| entry.get(name).and_then(|v| v.as_f64());
+
+    if let (Some(input), Some(output)) = (field("input_price"), field("output_price")) {
+        return Some(ModelPricing {
+            input_price: input,
+            output_price: output,
+            cache_read_price: field("cache_read_price"),
+            cache_write_price: field("cache_write_price"),
+            tiers: entry
+                .get("tiers")
+                .and_then(|t| serde_json::from_value(t.clone()).ok())
+                .unwrap_or_default(),
+        });
+    }
+
+    // Round away float noise from the per-token → per-1M conversion
+    let per_million = |cost: f64| (cost * 1e12).round() / 1e6;
+    let input_cost = field("input_cost_per_token")?;
+    let output_cost = field("output_cost_per_token")?;
+
+    // LiteLLM names long-prompt prices `input_cost_per_token_above_200k_tokens` and so on
+    let mut tiers: Vec<PriceTier> = entry
+        .as_object()
+        .into_iter()
+        .flat_map(|fields| fields.keys())
+        .filter_map(|key| {
+            let size = key
+                .strip_prefix("input_cost_per_token_above_")?
+                .strip_suffix("_tokens")?;
+            let threshold: u64 = size.strip_suffix('k')?.parse().ok()?;
+            let above = |name: &str| field(&format!("{}_above_{}_tokens", name, size));
+            Some(PriceTier {
+                above_input_tokens: threshold * 1000,
+                input_price: per_million(above("input_cost_per_token")?),
+                output_price: per_million(above("output_cost_per_token").unwrap_or(output_cost)),
+                cache_read_price: above("cache_read_input_token_cost").map(per_million),
+                cache_write_price: above("cache_creation_input_token_cost").map(per_million),
+            })
+        })
+        .collect();
+    tiers.sort_by_key(|t| t.above_input_tokens);
+
+    Some(ModelPricing {
+        input_price: per_million(input_cost),
+        output_price: per_million(output_cost),
+        cache_read_price: field("cache_read_input_token_cost").map(per_million),
+        cache_write_price: field("cache_creation_input_token_cost").map(per_million),
+        tiers,
+    })
+}
+
+/// Parse a pricing manifest, skipping entries without usable prices
+pub fn parse_pricing_manifest(manifest: &Value) -> HashMap<String, ModelPricing> {
+    manifest
+        .as_object()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|(key, entry)| Some((key.clone(), parse_entry(entry)?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn lookup<'a>(
+    table: &'a HashMap<String, ModelPricing>,
+    provider: &str,
+    model: &str,
+) -> Option<&'a ModelPricing> {
+    table
+        .get(&format!("{}/{}", provider, model))
+        .or_else(|| table.get(model))
+}
+
+/// Apply manifest prices and then the settings' overrides to every configured model
+pub fn apply_pricing(
+    settings: &mut GatewaySettings,
+    manifest: &HashMap<String, ModelPricing>,
+) -> PricingSyncResult {
+    let overrides = settings.pricing_overrides.clone();
+    let mut result = PricingSyncResult::default();
+
+    for provider in settings.providers.iter_mut() {
+        let provider_key = provider.provider.to_string();
+        for model in provider.models.iter_mut() {
+            let key = format!("{}/{}", provider_key, model.id);
+            let pricing = lookup(&overrides, &provider_key, &model.id)
+                .or_else(|| lookup(manifest, &provider_key, &model.id));
+
+            let Some(pricing) = pricing else {
+                result.unmatched.push(key);
+                continue;
+            };
+
+            let cache_read_price = pricing.cache_read_price.or(model.cache_read_price);
+            let cache_write_price = pricing.cache_write_price.or(model.cache_write_price);
+            // Configured tiers stay unless the source has its own
+            let tiers = if pricing.tiers.is_empty() {
+                &model.price_tiers
+            } else {
+                &pricing.tiers
+            };
+            if model.input_price != pricing.input_price
+                || model.output_price != pricing.output_price
+                || model.cache_read_price != cache_read_price
+                || model.cache_write_price != cache_write_price
+                || model.price_tiers != *tiers
+                || model.pricing_unknown
+            {
+                model.price_tiers = tiers.clone();
+                model.input_price = pricing.input_price;
+                model.output_price = pricing.output_price;
+                model.cache_read_price = cache_read_price;
+                model.cache_write_price = cache_write_price;
+                model.pricing_unknown = false;
+                result.updated.push(key);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::llm_gateway::LLMProvider;
+    use serde_json::json;
+
+    #[test]
+    fn test_manifest_and_overrides() {
+        let manifest = parse_pricing_manifest(&json!({
+            "gpt-4o": {"input_cost_per_token": 0.0000025, "output_cost_per_token": 0.00001},
+            "deepseek/deepseek-chat": {
+                "input_cost_per_token": 0.00000027,
+                "output_cost_per_token": 0.0000011,
+                "cache_read_input_token_cost": 0.00000007
+            },
+            "gemini/gemini-2.5-pro": {
+                "input_cost_per_token": 0.00000125,
+                "output_cost_per_token": 0.00001,
+                "input_cost_per_token_above_200k_tokens": 0.0000025,
+                "output_cost_per_token_above_200k_tokens": 0.000015
+            },
+            "sample_spec": {"max_tokens": "set to max output tokens"}
+        }));
+        assert_eq!(manifest.len(), 3);
+        assert_eq!(manifest["gpt-4o"].input_price, 2.5);
+        assert_eq!(
+            manifest["deepseek/deepseek-chat"].cache_read_price,
+            Some(0.07)
+        );
+        let tier = manifest["gemini/gemini-2.5-pro"].tiers[0];
+        assert_eq!(
+            (tier.above_input_tokens, tier.input_price, tier.output_price),
+            (200_000, 2.5, 15.0)
+        );
+
+        let mut settings = GatewaySettings::default();
+        settings.pricing_overrides.insert(
+            "gpt-4o-mini".to_string(),
+            ModelPricing {
+                input_price: 0.1,
+                output_price: 0.4,
+                ..Default::default()
+            },
+        );
+        let result = apply_pricing(&mut settings, &manifest);
+
+        assert!(result
+            .updated
+            .contains(&"deepseek/deepseek-chat".to_string()));
+        assert!(result.updated.contains(&"openai/gpt-4o-mini".to_string()));
+        assert!(!result.updated.contains(&"openai/gpt-4o".to_string()));
+        assert!(result.unmatched.contains(&"openai/gpt-4-turbo".to_string()));
+
+        let mini = settings.providers[0]
+            .models
+            .iter()
+            .find(|m| m.id == "gpt-4o-mini")
+            .unwrap();
+        assert_eq!(mini.input_price, 0.1);
+    }
+
+    #[test]
+    fn test_priced_at_off_peak() {
+        let settings = GatewaySettings::default();
+        let deepseek = settings
+            .providers
+            .iter()
+            .find(|p| p.provider == LLMProvider::DeepSeek)
+            .unwrap();
+        let chat = deepseek
+            .models
+            .iter()
+            .find(|m| m.id == "deepseek-chat")
+            .unwrap();
+        let at = |time: &str| {
+            let time = format!("2026-10-08T{}:00Z", time);
+            pricing_at(deepseek, chat, time.parse().unwrap())
+        };
+        // 17:00 UTC is 01:00 in Beijing
+        assert_eq!(at("17:00"), (0.07, 0.14));
+        assert_eq!(at("16:30"), (0.07, 0.14));
+        assert_eq!(at("00:30"), (0.14, 0.28));
+        assert_eq!(at("12:00"), (0.14, 0.28));
+    }
+
+    #[test]
+    fn test_tiered_in_window() {
+        let settings = GatewaySettings::default();
+        let deepseek = settings
+            .providers
+            .iter()
+            .find(|p| p.provider == LLMProvider::DeepSeek)
+            .unwrap();
+        let tier = |above_input_tokens, input_price, output_price| PriceTier {
+            above_input_tokens,
+            input_price,
+            output_price,
+            cache_read_price: None,
+            cache_write_price: None,
+        };
+        let model = ModelConfig {
+            input_price: 1.0,
+            output_price: 4.0,
+            price_windows: vec![deepseek_off_peak(0.5, 1.0)],
+            price_tiers: vec![tier(1000, 2.0, 8.0), tier(10_000, 3.0, 12.0)],
+            ..Default::default()
+        };
+        let prices = |model: &ModelConfig, prompt_tokens| {
+            let model = tiered(model, prompt_tokens);
+            (model.input_price, model.output_price)
+        };
+
+        // The threshold itself isn't above it; past two thresholds the higher tier wins
+        assert_eq!(prices(&model, 1000), (1.0, 4.0));
+        assert_eq!(prices(&model, 1001), (2.0, 8.0));
+        assert_eq!(prices(&model, 20_000), (3.0, 12.0));
+
+        // Off-peak halves input and quarters output prices, tiers included
+        let off_peak = priced_at(deepseek, &model, "2026-10-08T17:00:00Z".parse().unwrap());
+        assert_eq!(prices(&off_peak, 500), (0.5, 1.0));
+        assert_eq!(prices(&off_peak, 1001), (1.0, 2.0));
+        assert_eq!(prices(&off_peak, 20_000), (1.5, 3.0));
+    }
+
+    fn pricing_at(
+        provider: &ProviderConfig,
+        model: &ModelConfig,
+        now: DateTime<Utc>,
+    ) -> (f64, f64) {
+        let model = priced_at(provider, model, now);
+        (model.input_price, model.output_price)
+    }
+}
@@ -0,0 +1,143 @@
+//! Configuration Profiles - Named sets of gateway settings to switch between
+//!
+//! The gateway always works from the current settings. Profiles such as "work" or
+//! "offline-only" are snapshots of them, stored together in one (optionally encrypted)
+//! `app_settings` value. Switching first saves the current settings back into the active
+//! profile, so edits made since the last switch aren't lost.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{
+    load_gateway_settings, open_setting, persist_gateway_settings, seal_setting, stored_setting,
+    write_setting, GatewaySettings,
+};
+
+/// `app_settings` key of the stored profiles
+pub const PROFILES_KEY: &str = "llm_gateway_profiles";
+
+/// `app_settings` key of the active profile's name
+const ACTIVE_KEY: &str = "llm_gateway_active_profile";
+
+/// Stored profile names and the active one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayProfiles {
+    pub profiles: Vec<String>,
+    pub active: Option<String>,
+}
+
+fn load(conn: &Connection) -> Result<BTreeMap<String, GatewaySettings>, String> {
+    match stored_setting(conn, PROFILES_KEY) {
+        Some(stored) => serde_json::from_str(&open_setting(stored)?)
+            .map_err(|e| format!("Failed to parse profiles: {}", e)),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+fn store(conn: &Connection, profiles: &BTreeMap<String, GatewaySettings>) -> Result<(), String> {
+    let json_str = serde_json::to_string(profiles)
+        .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+    write_setting(conn, PROFILES_KEY, &seal_setting(conn, json_str)?)
+}
+
+fn active(conn: &Connection) -> Option<String> {
+    stored_setting(conn, ACTIVE_KEY).filter(|name| !name.is_empty())
+}
+
+/// Stored profile names, in order, and the active one
+pub fn list(conn: &Connection) -> Result<GatewayProfiles, String> {
+    Ok(GatewayProfiles {
+        profiles: load(conn)?.into_keys().collect(),
+        active: active(conn),
+    })
+}
+
+/// Save the current settings as profile `name` and make it the active profile
+pub fn save_current(conn: &Connection, name: &str) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Profile name must not be empty".to_string());
+    }
+    let mut profiles = load(conn)?;
+    profiles.insert(name.to_string(), load_gateway_settings(conn));
+    store(conn, &profiles)?;
+    write_setting(conn, ACTIVE_KEY, name)
+}
+
+/// Delete profile `name`; returns whether it existed
+pub fn delete(conn: &Connection, name: &str) -> Result<bool, String> {
+    let mut profiles = load(conn)?;
+    if profiles.remove(name).is_none() {
+        return Ok(false);
+    }
+    store(conn, &profiles)?;
+    if active(conn).as_deref() == Some(name) {
+        write_setting(conn, ACTIVE_KEY, "")?;
+    }
+    Ok(true)
+}
+
+/// Make profile `name` the current settings, returning them
+pub fn switch(conn: &Connection, name: &str) -> Result<GatewaySettings, String> {
+    let mut profiles = load(conn)?;
+    let settings = profiles
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("Profile '{}' not found", name))?;
+    if let Some(current) = active(conn).filter(|current| current != name) {
+        profiles.insert(current, load_gateway_settings(conn));
+        store(conn, &profiles)?;
+    }
+    persist_gateway_settings(conn, &settings, "switch_gateway_profile")?;
+    write_setting(conn, ACTIVE_KEY, name)?;
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::llm_gateway::migrations;
+
+    #[test]
+    fn test_profiles() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+            [],
+        )
+        .unwrap();
+        migrations::run(&conn).unwrap();
+
+        let work = GatewaySettings {
+            port: 9000,
+            ..Default::default()
+        };
+        persist_gateway_settings(&conn, &work, "test").unwrap();
+        save_current(&conn, "work").unwrap();
+        let offline = GatewaySettings {
+            port: 9001,
+            ..Default::default()
+        };
+        persist_gateway_settings(&conn, &offline, "test").unwrap();
+        save_current(&conn, "offline-only").unwrap();
+
+        // Edits since the last switch are kept in the profile being left
+        let edited = GatewaySettings {
+            port: 9002,
+            ..Default::default()
+        };
+        persist_gateway_settings(&conn, &edited, "test").unwrap();
+        assert_eq!(switch(&conn, "work").unwrap().port, 9000);
+        assert_eq!(load_gateway_settings(&conn).port, 9000);
+        assert_eq!(switch(&conn, "offline-only").unwrap().port, 9002);
+
+        let listed = list(&conn).unwrap();
+        assert_eq!(listed.profiles, vec!["offline-only", "work"]);
+        assert_eq!(listed.active.as_deref(), Some("offline-only"));
+        assert!(switch(&conn, "personal").is_err());
+
+        assert!(delete(&conn, "offline-only").unwrap());
+        assert_eq!(list(&conn).unwrap().active, None);
+    }
+}
@@ -0,0 +1,255 @@
+//! Request Queue - Caps concurrent upstream requests and queues the rest in FIFO order
+//!
+//! Bursts from parallel agents otherwise hit providers all at once and come back as a
+//! storm of 429s. Requests beyond the limit wait for a slot up to a per-request timeout.
+//!
+//! Waiters are served by priority class, FIFO within a class, so interactive sessions
+//! aren't starved by batch agent runs.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Header clients use to tag a request's priority class
+pub const PRIORITY_HEADER: &str = "x-doggy-priority";
+
+/// Priority class of a queued request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RequestPriority {
+    /// Served first; untagged requests are treated as interactive
+    #[default]
+    Interactive,
+    /// Batch work that yields to interactive requests
+    Background,
+}
+
+impl RequestPriority {
+    /// Parse a header value, defaulting to interactive for unknown values
+    pub fn from_header(value: Option<&str>) -> Self {
+        match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+            Some("background") | Some("batch") | Some("low") => Self::Background,
+            _ => Self::Interactive,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    active: usize,
+    /// Waiters per priority class, highest priority first
+    waiting: [VecDeque<oneshot::Sender<()>>; 2],
+}
+
+/// Priority-aware FIFO concurrency limiter for upstream requests
+#[derive(Debug)]
+pub struct RequestQueue {
+    /// Maximum concurrent requests, 0 = unlimited
+    max_concurrent: usize,
+    state: Mutex<QueueState>,
+}
+
+/// Slot in the queue; released (and handed to the next waiter) on drop
+#[derive(Debug)]
+pub struct QueuePermit {
+    queue: Arc<RequestQueue>,
+}
+
+/// A queued request's end of its handoff channel. A slot handed over to a waiter that
+/// gave up (timed out, or dropped when its client disconnected) is released again.
+struct Waiter {
+    queue: Arc<RequestQueue>,
+    rx: oneshot::Receiver<()>,
+    claimed: bool,
+}
+
+impl Waiter {
+    /// Whether a slot was handed over, closing the channel to further handoffs
+    fn handed_over(&mut self) -> bool {
+        self.rx.close();
+        self.rx.try_recv().is_ok()
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        if !self.claimed && self.handed_over() {
+            self.queue.release();
+        }
+    }
+}
+
+impl RequestQueue {
+    pub fn new(max_concurrent: Option<u32>) -> Arc<Self> {
+        Arc::new(Self {
+            max_concurrent: max_concurrent.unwrap_or(0) as usize,
+            state: Mutex::new(QueueState::default()),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for a slot; `None` if none frees up within `timeout`
+    pub async fn acquire(
+        self: &Arc<Self>,
+        priority: RequestPriority,
+        timeout: Duration,
+    ) -> Option<QueuePermit> {
+        let rx = {
+            let mut state = self.lock();
+            if self.max_concurrent == 0 || state.active < self.max_concurrent {
+                state.active += 1;
+                return Some(QueuePermit {
+                    queue: self.clone(),
+                });
+            }
+            let (tx, rx) = oneshot::channel();
+            state.waiting[priority as usize].push_back(tx);
+            rx
+        };
+
+        let mut waiter = Waiter {
+            queue: self.clone(),
+            rx,
+            claimed: false,
+        };
+        // A slot may have been handed over right as the timeout fired
+        let handed = matches!(
+            tokio::time::timeout(timeout, &mut waiter.rx).await,
+            Ok(Ok(()))
+        ) || waiter.handed_over();
+        waiter.claimed = handed;
+        handed.then(|| QueuePermit {
+            queue: self.clone(),
+        })
+    }
+
+    fn release(&self) {
+        let mut state = self.lock();
+        // Hand the slot straight to the next live waiter, keeping `active` unchanged
+        for class in state.waiting.iter_mut() {
+            while let Some(tx) = class.pop_front() {
+                if tx.send(()).is_ok() {
+                    return;
+                }
+            }
+        }
+        state.active = state.active.saturating_sub(1);
+    }
+}
+
+impl Drop for QueuePermit {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fifo_handoff_and_timeout() {
+        let queue = RequestQueue::new(Some(1));
+        let first = queue
+            .acquire(RequestPriority::Interactive, Duration::from_millis(10))
+            .await
+            .unwrap();
+
+        // Queue is full: a short wait times out
+        assert!(queue
+            .acquire(RequestPriority::Interactive, Duration::from_millis(10))
+            .await
+            .is_none());
+
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                queue
+                    .acquire(RequestPriority::Interactive, Duration::from_secs(5))
+                    .await
+                    .is_some()
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // The timed-out waiter is only skipped once a slot is released
+        assert_eq!(queue.lock().waiting[0].len(), 2);
+
+        drop(first);
+        assert!(waiter.await.unwrap());
+        assert_eq!(queue.lock().active, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_returns_slot() {
+        let queue = RequestQueue::new(Some(1));
+        let first = queue
+            .acquire(RequestPriority::Interactive, Duration::from_millis(10))
+            .await
+            .unwrap();
+        let mut waiter =
+            Box::pin(queue.acquire(RequestPriority::Interactive, Duration::from_secs(5)));
+        assert!(futures::poll!(&mut waiter).is_pending());
+
+        // The slot is handed to the waiter, whose client disconnects before taking it
+        drop(first);
+        assert_eq!(queue.lock().active, 1);
+        drop(waiter);
+        assert_eq!(queue.lock().active, 0);
+        assert!(queue
+            .acquire(RequestPriority::Interactive, Duration::from_millis(10))
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_unlimited() {
+        let queue = RequestQueue::new(None);
+        let permits: Vec<_> = futures::future::join_all(
+            (0..10).map(|_| queue.acquire(RequestPriority::Interactive, Duration::from_millis(1))),
+        )
+        .await;
+        assert!(permits.iter().all(|p| p.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_interactive_jumps_background() {
+        let queue = RequestQueue::new(Some(1));
+        let first = queue
+            .acquire(RequestPriority::Interactive, Duration::from_millis(10))
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for (name, priority) in [
+            ("background", RequestPriority::Background),
+            ("interactive", RequestPriority::Interactive),
+        ] {
+            let queue = queue.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let _permit = queue.acquire(priority, Duration::from_secs(5)).await;
+                tx.send(name).unwrap();
+            });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        drop(first);
+        assert_eq!(rx.recv().await, Some("interactive"));
+        assert_eq!(rx.recv().await, Some("background"));
+    }
+
+    #[test]
+    fn test_priority_header() {
+        assert_eq!(
+            RequestPriority::from_header(Some("Background")),
+            RequestPriority::Background
+        );
+        assert_eq!(
+            RequestPriority::from_header(None),
+            RequestPriority::Interactive
+        );
+    }
+}
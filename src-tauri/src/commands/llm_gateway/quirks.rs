@@ -0,0 +1,175 @@
+//! Provider Quirks - Parameter normalization for pedantic OpenAI-compatible APIs
+//!
+//! "OpenAI-compatible" providers disagree on the details: which field caps the output,
+//! what ranges sampling parameters may take and which parameters exist at all. Sending
+//! a value one of them doesn't accept fails the whole request, so requests are
+//! normalized to what the routed provider takes before they leave. Reasoning model
+//! restrictions are handled separately by the reasoning module.
+
+use serde_json::{json, Value};
+
+use super::{LLMProvider, ProviderConfig};
+
+/// What a provider's chat API accepts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quirks {
+    /// Field capping output tokens
+    pub max_tokens_field: &'static str,
+    /// Inclusive temperature range
+    pub temperature: (f64, f64),
+    /// Inclusive `top_p` range
+    pub top_p: (f64, f64),
+    /// Parameters the API rejects
+    pub unsupported: &'static [&'static str],
+    /// Whether JSON mode works on streamed requests
+    pub streaming_json_mode: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            max_tokens_field: "max_tokens",
+            temperature: (0.0, 2.0),
+            top_p: (0.0, 1.0),
+            unsupported: &[],
+            streaming_json_mode: true,
+        }
+    }
+}
+
+/// Whether OpenAI only takes `max_completion_tokens` for `model`
+fn needs_max_completion_tokens(model: &str) -> bool {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    name.starts_with("gpt-5") || name.starts_with("gpt-4.1")
+}
+
+impl Quirks {
+    /// Quirks of `model` served by `provider`
+    pub fn for_route(provider: &ProviderConfig, model: &str) -> Self {
+        let defaults = Self::default();
+        match provider.provider {
+            LLMProvider::OpenAI if needs_max_completion_tokens(model) => Self {
+                max_tokens_field: "max_completion_tokens",
+                ..defaults
+            },
+            LLMProvider::Anthropic => Self {
+                temperature: (0.0, 1.0),
+                unsupported: &[
+                    "presence_penalty",
+                    "frequency_penalty",
+                    "logit_bias",
+                    "logprobs",
+                    "top_logprobs",
+                    "seed",
+                ],
+                ..defaults
+            },
+            // Groq rejects these outright and can't stream in JSON mode
+            LLMProvider::Groq => Self {
+                unsupported: &["logprobs", "top_logprobs", "logit_bias"],
+                streaming_json_mode: false,
+                ..defaults
+            },
+            LLMProvider::Moonshot => Self {
+                temperature: (0.0, 1.0),
+                ..defaults
+            },
+            // GLM takes neither penalty and only open sampling ranges
+            LLMProvider::Zhipu => Self {
+                temperature: (0.01, 1.0),
+                top_p: (0.01, 0.99),
+                unsupported: &["presence_penalty", "frequency_penalty", "logit_bias"],
+                ..defaults
+            },
+            LLMProvider::Qwen => Self {
+                temperature: (0.0, 1.99),
+                ..defaults
+            },
+            _ => defaults,
+        }
+    }
+}
+
+/// Clamp a numeric parameter into `range`
+fn clamp(obj: &mut serde_json::Map<String, Value>, field: &str, (min, max): (f64, f64)) {
+    if let Some(value) = obj.get(field).and_then(|v| v.as_f64()) {
+        if value < min || value > max {
+            obj.insert(field.to_string(), json!(value.clamp(min, max)));
+        }
+    }
+}
+
+/// Rewrite an OpenAI-shaped request into parameters `quirks` accepts
+pub fn normalize(body: &mut Value, quirks: &Quirks) {
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+
+    let other_field = if quirks.max_tokens_field == "max_tokens" {
+        "max_completion_tokens"
+    } else {
+        "max_tokens"
+    };
+    if let Some(max_tokens) = obj.remove(other_field) {
+        obj.entry(quirks.max_tokens_field).or_insert(max_tokens);
+    }
+
+    clamp(obj, "temperature", quirks.temperature);
+    clamp(obj, "top_p", quirks.top_p);
+    for field in quirks.unsupported {
+        obj.remove(*field);
+    }
+
+    let streaming = obj.get("stream").and_then(|s| s.as_bool()) == Some(true);
+    if streaming && !quirks.streaming_json_mode {
+        // The schema instructions in the prompt still ask for JSON
+        obj.remove("response_format");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(kind: LLMProvider) -> ProviderConfig {
+        ProviderConfig {
+            provider: kind,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut body = json!({"max_completion_tokens": 100, "temperature": 0.7});
+        normalize(
+            &mut body,
+            &Quirks::for_route(&provider(LLMProvider::DeepSeek), "deepseek-chat"),
+        );
+        assert_eq!(body, json!({"max_tokens": 100, "temperature": 0.7}));
+
+        let mut body = json!({"max_tokens": 100});
+        normalize(
+            &mut body,
+            &Quirks::for_route(&provider(LLMProvider::OpenAI), "gpt-5-mini"),
+        );
+        assert_eq!(body, json!({"max_completion_tokens": 100}));
+
+        let mut body = json!({"temperature": 0, "top_p": 1.0, "presence_penalty": 0.5});
+        normalize(
+            &mut body,
+            &Quirks::for_route(&provider(LLMProvider::Zhipu), "glm-4"),
+        );
+        assert_eq!(body, json!({"temperature": 0.01, "top_p": 0.99}));
+
+        let mut body = json!({
+            "stream": true,
+            "response_format": {"type": "json_object"},
+            "logprobs": true
+        });
+        normalize(
+            &mut body,
+            &Quirks::for_route(&provider(LLMProvider::Groq), "llama-3.3-70b-versatile"),
+        );
+        assert_eq!(body, json!({"stream": true}));
+    }
+}
@@ -0,0 +1,283 @@
+//! Provider Quota - remaining rate limits and credit reported by providers
+//!
+//! Providers report how much of their rate limits is left in response headers: OpenAI
+//! and compatible APIs send `x-ratelimit-remaining-*` and `x-ratelimit-limit-*`,
+//! Anthropic sends `anthropic-ratelimit-*-remaining` and `-limit`. The latest values
+//! are kept on the provider's status. OpenRouter's prepaid credit is only available
+//! from its credits endpoint, queried by [`fetch_openrouter_credits`].
+//!
+//! When a remaining amount drops below [`WARNING_FRACTION`] of its limit,
+//! [`QUOTA_WARNING_EVENT`] is emitted once, so the user hears about it before the
+//! key runs dry.
+
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
+
+use super::{GatewayStatus, ProviderConfig, ProviderStatus};
+
+/// Event emitted with a [`QuotaWarning`] when a provider's quota runs low
+pub const QUOTA_WARNING_EVENT: &str = "llm-gateway-quota-warning";
+
+/// Share of a limit left at which the user is warned
+const WARNING_FRACTION: f64 = 0.1;
+
+/// Remaining rate limits and credit last reported by a provider
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProviderQuota {
+    #[serde(default)]
+    pub remaining_requests: Option<u64>,
+    #[serde(default)]
+    pub limit_requests: Option<u64>,
+    #[serde(default)]
+    pub remaining_tokens: Option<u64>,
+    #[serde(default)]
+    pub limit_tokens: Option<u64>,
+    /// Remaining prepaid credit in USD
+    #[serde(default)]
+    pub credits_remaining: Option<f64>,
+    /// Prepaid credit bought in USD
+    #[serde(default)]
+    pub credits_total: Option<f64>,
+    /// When the values were reported (RFC 3339)
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// Payload of [`QUOTA_WARNING_EVENT`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaWarning {
+    pub provider: String,
+    pub message: String,
+    pub quota: ProviderQuota,
+}
+
+fn header_u64(headers: &HeaderMap, names: &[&str]) -> Option<u64> {
+    names.iter().find_map(|name| {
+        headers
+            .get(*name)?
+            .to_str()
+            .ok()?
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .map(|value| value.max(0.0) as u64)
+    })
+}
+
+/// Read the rate limit headers of a response, if it has any
+pub fn from_headers(headers: &HeaderMap) -> Option<ProviderQuota> {
+    let quota = ProviderQuota {
+        remaining_requests: header_u64(
+            headers,
+            &[
+                "x-ratelimit-remaining-requests",
+                "anthropic-ratelimit-requests-remaining",
+                "x-ratelimit-remaining",
+            ],
+        ),
+        limit_requests: header_u64(
+            headers,
+            &[
+                "x-ratelimit-limit-requests",
+                "anthropic-ratelimit-requests-limit",
+                "x-ratelimit-limit",
+            ],
+        ),
+        remaining_tokens: header_u64(
+            headers,
+            &[
+                "x-ratelimit-remaining-tokens",
+                "anthropic-ratelimit-tokens-remaining",
+            ],
+        ),
+        limit_tokens: header_u64(
+            headers,
+            &[
+                "x-ratelimit-limit-tokens",
+                "anthropic-ratelimit-tokens-limit",
+            ],
+        ),
+        ..Default::default()
+    };
+    if quota == ProviderQuota::default() {
+        return None;
+    }
+    Some(ProviderQuota {
+        updated_at: Some(chrono::Utc::now().to_rfc3339()),
+        ..quota
+    })
+}
+
+impl ProviderQuota {
+    /// Take the values `update` reports, keeping the rest
+    fn merge(&mut self, update: ProviderQuota) {
+        fn take<T>(current: &mut Option<T>, update: Option<T>) {
+            if update.is_some() {
+                *current = update;
+            }
+        }
+        take(&mut self.remaining_requests, update.remaining_requests);
+        take(&mut self.limit_requests, update.limit_requests);
+        take(&mut self.remaining_tokens, update.remaining_tokens);
+        take(&mut self.limit_tokens, update.limit_tokens);
+        take(&mut self.credits_remaining, update.credits_remaining);
+        take(&mut self.credits_total, update.credits_total);
+        take(&mut self.updated_at, update.updated_at);
+    }
+
+    /// Describe the amounts below [`WARNING_FRACTION`] of their limit
+    pub fn low(&self) -> Option<String> {
+        let below = |remaining: Option<f64>, limit: Option<f64>| match (remaining, limit) {
+            (Some(remaining), Some(limit)) if limit > 0.0 => remaining < limit * WARNING_FRACTION,
+            _ => false,
+        };
+        let mut low = Vec::new();
+        if below(
+            self.remaining_requests.map(|v| v as f64),
+            self.limit_requests.map(|v| v as f64),
+        ) {
+            low.push(format!(
+                "{} of {} requests left",
+                self.remaining_requests.unwrap_or_default(),
+                self.limit_requests.unwrap_or_default()
+            ));
+        }
+        if below(
+            self.remaining_tokens.map(|v| v as f64),
+            self.limit_tokens.map(|v| v as f64),
+        ) {
+            low.push(format!(
+                "{} of {} tokens left",
+                self.remaining_tokens.unwrap_or_default(),
+                self.limit_tokens.unwrap_or_default()
+            ));
+        }
+        if below(self.credits_remaining, self.credits_total) {
+            low.push(format!(
+                "${:.2} of ${:.2} credit left",
+                self.credits_remaining.unwrap_or_default(),
+                self.credits_total.unwrap_or_default()
+            ));
+        }
+        (!low.is_empty()).then(|| low.join(", "))
+    }
+}
+
+/// Record a provider's reported quota on its status, returning a warning when it has
+/// just run low
+pub async fn record_quota(
+    status: &RwLock<GatewayStatus>,
+    provider: &str,
+    update: ProviderQuota,
+) -> Option<QuotaWarning> {
+    let mut status = status.write().await;
+    let entry = status
+        .provider_status
+        .entry(provider.to_string())
+        .or_insert(ProviderStatus {
+            available: true,
+            latency_ms: None,
+            last_error: None,
+            request_count: 0,
+            error_count: 0,
+            quota: None,
+            hardware: None,
+        });
+    let quota = entry.quota.get_or_insert_with(ProviderQuota::default);
+    let was_low = quota.low().is_some();
+    quota.merge(update);
+    let message = quota.low().filter(|_| !was_low)?;
+    Some(QuotaWarning {
+        provider: provider.to_string(),
+        message,
+        quota: quota.clone(),
+    })
+}
+
+/// Query OpenRouter's remaining prepaid credit
+pub async fn fetch_openrouter_credits(
+    client: &reqwest::Client,
+    provider: &ProviderConfig,
+) -> Result<ProviderQuota, String> {
+    let url = format!("{}/credits", provider.base_url.trim_end_matches('/'));
+    let response = client
+        .get(&url)
+        .bearer_auth(provider.api_key.as_deref().unwrap_or_default())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned {}", url, response.status()));
+    }
+    let body: Value = response.json().await.map_err(|e| e.to_string())?;
+    let total = body["data"]["total_credits"].as_f64();
+    let used = body["data"]["total_usage"].as_f64();
+    let (Some(total), Some(used)) = (total, used) else {
+        return Err("Unexpected credits response".to_string());
+    };
+    Ok(ProviderQuota {
+        credits_remaining: Some((total - used).max(0.0)),
+        credits_total: Some(total),
+        updated_at: Some(chrono::Utc::now().to_rfc3339()),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(from_headers(&headers).is_none());
+
+        headers.insert(
+            "anthropic-ratelimit-requests-remaining",
+            "4".parse().unwrap(),
+        );
+        headers.insert("anthropic-ratelimit-requests-limit", "50".parse().unwrap());
+        headers.insert("x-ratelimit-remaining-tokens", "9000".parse().unwrap());
+        let quota = from_headers(&headers).unwrap();
+        assert_eq!(quota.remaining_requests, Some(4));
+        assert_eq!(quota.limit_requests, Some(50));
+        assert_eq!(quota.remaining_tokens, Some(9000));
+        assert_eq!(quota.limit_tokens, None);
+        assert_eq!(quota.low().unwrap(), "4 of 50 requests left");
+    }
+
+    #[tokio::test]
+    async fn test_quota_warning_once() {
+        let status = RwLock::new(GatewayStatus {
+            running: true,
+            port: 8765,
+            requests_processed: 0,
+            provider_status: Default::default(),
+            last_error: None,
+            model_warnings: Vec::new(),
+        });
+        let credits = |remaining| ProviderQuota {
+            credits_remaining: Some(remaining),
+            credits_total: Some(100.0),
+            ..Default::default()
+        };
+        assert!(record_quota(&status, "openrouter", credits(50.0))
+            .await
+            .is_none());
+        let warning = record_quota(&status, "openrouter", credits(5.0))
+            .await
+            .unwrap();
+        assert_eq!(warning.message, "$5.00 of $100.00 credit left");
+        assert!(record_quota(&status, "openrouter", credits(4.0))
+            .await
+            .is_none());
+        assert!(record_quota(&status, "openrouter", credits(80.0))
+            .await
+            .is_none());
+        assert!(record_quota(&status, "openrouter", credits(1.0))
+            .await
+            .is_some());
+    }
+}
@@ -0,0 +1,234 @@
+//! Reasoning Models - Parameter restrictions and reasoning output normalization
+//!
+//! Reasoning models surface their chain of thought either as a separate
+//! `reasoning_content` field (DeepSeek R1) or inline between `<think>` tags (QwQ, R1
+//! distills served by Ollama). Both are turned into Anthropic thinking blocks, or
+//! dropped, depending on the gateway settings.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// What to do with the reasoning output of a model
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasoningOutput {
+    /// Return it as thinking blocks (`reasoning_content` on the OpenAI endpoint)
+    #[default]
+    Thinking,
+    /// Drop it from the response
+    Strip,
+}
+
+const OPEN_TAG: &str = "<think>";
+const CLOSE_TAG: &str = "</think>";
+
+/// Whether `model` is an OpenAI o-series reasoning model
+fn is_openai_reasoning(model: &str) -> bool {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    ["o1", "o3", "o4"]
+        .iter()
+        .any(|series| name == *series || name.starts_with(&format!("{}-", series)))
+}
+
+/// Whether `model` is a reasoning model with sampling parameter restrictions
+pub fn is_reasoning_model(model: &str) -> bool {
+    let lower = model.to_ascii_lowercase();
+    is_openai_reasoning(&lower)
+        || ["deepseek-reasoner", "deepseek-r1", "qwq"]
+            .iter()
+            .any(|name| lower.contains(name))
+}
+
+/// Drop or rename request parameters a reasoning model rejects
+pub fn adapt_request(body: &mut Value, model: &str) {
+    if !is_reasoning_model(model) {
+        return;
+    }
+    let Some(obj) = body.as_object_mut() else {
+        return;
+    };
+    for field in [
+        "temperature",
+        "top_p",
+        "presence_penalty",
+        "frequency_penalty",
+        "logprobs",
+        "top_logprobs",
+    ] {
+        obj.remove(field);
+    }
+
+    if is_openai_reasoning(&model.to_ascii_lowercase()) {
+        if let Some(max_tokens) = obj.remove("max_tokens") {
+            obj.entry("max_completion_tokens").or_insert(max_tokens);
+        }
+        // o-series models take instructions as developer messages
+        for message in obj
+            .get_mut("messages")
+            .and_then(|m| m.as_array_mut())
+            .into_iter()
+            .flatten()
+        {
+            if message["role"] == "system" {
+                message["role"] = Value::String("developer".to_string());
+            }
+        }
+    }
+}
+
+/// Splits streamed text into thinking and answer parts at `<think>` tags, holding back
+/// a partial tag until the next chunk shows whether it completes
+#[derive(Debug, Default)]
+pub struct ThinkTagSplitter {
+    buffer: String,
+    in_think: bool,
+}
+
+impl ThinkTagSplitter {
+    /// Feed a text delta; returns `(is_thinking, text)` parts in order
+    pub fn push(&mut self, delta: &str) -> Vec<(bool, String)> {
+        self.buffer.push_str(delta);
+        let mut parts = Vec::new();
+
+        loop {
+            let tag = if self.in_think { CLOSE_TAG } else { OPEN_TAG };
+            if let Some(pos) = self.buffer.find(tag) {
+                let before: String = self.buffer.drain(..pos).collect();
+                self.buffer.drain(..tag.len());
+                if !before.is_empty() {
+                    parts.push((self.in_think, before));
+                }
+                self.in_think = !self.in_think;
+                continue;
+            }
+
+            // Keep a suffix that could be the start of the tag
+            let keep = (1..tag.len().min(self.buffer.len() + 1))
+                .rev()
+                .find(|len| {
+                    self.buffer.is_char_boundary(self.buffer.len() - len)
+                        && tag.starts_with(&self.buffer[self.buffer.len() - len..])
+                })
+                .unwrap_or(0);
+            let ready: String = self.buffer.drain(..self.buffer.len() - keep).collect();
+            if !ready.is_empty() {
+                parts.push((self.in_think, ready));
+            }
+            return parts;
+        }
+    }
+
+    /// Flush any held-back text
+    pub fn finish(&mut self) -> Vec<(bool, String)> {
+        let rest = std::mem::take(&mut self.buffer);
+        if rest.is_empty() {
+            Vec::new()
+        } else {
+            vec![(self.in_think, rest)]
+        }
+    }
+}
+
+/// Split a complete reply into its `<think>` reasoning and the answer. Replies whose
+/// opening tag was part of the prompt template only carry the closing tag.
+pub fn split_think(text: &str) -> (String, String) {
+    let mut splitter = ThinkTagSplitter::default();
+    if !text.contains(OPEN_TAG) && text.contains(CLOSE_TAG) {
+        splitter.in_think = true;
+    }
+
+    let (mut thinking, mut answer) = (String::new(), String::new());
+    let mut parts = splitter.push(text);
+    parts.extend(splitter.finish());
+    for (is_thinking, part) in parts {
+        if is_thinking {
+            thinking.push_str(&part);
+        } else {
+            answer.push_str(&part);
+        }
+    }
+    (thinking.trim().to_string(), answer.trim_start().to_string())
+}
+
+/// Move inline `<think>` reasoning of an OpenAI-shaped completion into
+/// `reasoning_content`, or drop all reasoning when stripping
+pub fn normalize_completion(response: &mut Value, mode: ReasoningOutput) {
+    let Some(message) = response.pointer_mut("/choices/0/message") else {
+        return;
+    };
+
+    if let Some(content) = message["content"]
+        .as_str()
+        .filter(|c| c.contains(CLOSE_TAG))
+    {
+        let (thinking, answer) = split_think(content);
+        message["content"] = Value::String(answer);
+        if !thinking.is_empty() && message.get("reasoning_content").is_none() {
+            message["reasoning_content"] = Value::String(thinking);
+        }
+    }
+
+    if mode == ReasoningOutput::Strip {
+        if let Some(obj) = message.as_object_mut() {
+            obj.remove("reasoning_content");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_think_tag_splitter_across_chunks() {
+        let mut splitter = ThinkTagSplitter::default();
+        let mut parts = Vec::new();
+        for chunk in ["<thi", "nk>plan</th", "ink>", "Answer <", "b>"] {
+            parts.extend(splitter.push(chunk));
+        }
+        parts.extend(splitter.finish());
+        assert_eq!(
+            parts,
+            vec![
+                (true, "plan".to_string()),
+                (false, "Answer ".to_string()),
+                (false, "<b>".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_completion() {
+        let mut response = json!({"choices": [{"message": {
+            "role": "assistant",
+            "content": "Let me think.\n</think>\n\n42"
+        }}]});
+        normalize_completion(&mut response, ReasoningOutput::Thinking);
+        let message = &response["choices"][0]["message"];
+        assert_eq!(message["content"], "42");
+        assert_eq!(message["reasoning_content"], "Let me think.");
+
+        normalize_completion(&mut response, ReasoningOutput::Strip);
+        assert!(response["choices"][0]["message"]
+            .get("reasoning_content")
+            .is_none());
+    }
+
+    #[test]
+    fn test_adapt_request() {
+        let mut body = json!({
+            "messages": [{"role": "system", "content": "Be brief"}],
+            "max_tokens": 100,
+            "temperature": 0.2
+        });
+        adapt_request(&mut body, "o3-mini");
+        assert!(body.get("temperature").is_none());
+        assert_eq!(body["max_completion_tokens"], 100);
+        assert_eq!(body["messages"][0]["role"], "developer");
+
+        let mut body = json!({"temperature": 0.2, "max_tokens": 100});
+        adapt_request(&mut body, "gpt-4o");
+        assert_eq!(body["temperature"], 0.2);
+    }
+}
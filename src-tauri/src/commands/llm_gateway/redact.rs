@@ -0,0 +1,232 @@
+//! PII Redaction - Masks personal data and credentials in prompts before they leave the
+//! machine
+//!
+//! Emails, phone numbers, API keys and custom patterns found in the system prompt and
+//! message text (including tool inputs and results) are replaced with numbered
+//! placeholders such as `[EMAIL_1]`. The same value always gets the same placeholder
+//! within a request, so the model can still refer to it, and placeholders in the response
+//! can be swapped back for the original values.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::RedactionConfig;
+
+/// Well-known provider API key and access token formats
+pub const API_KEY_PATTERN: &str = r"\b(?:sk-(?:ant-|proj-)?[A-Za-z0-9_-]{20,}|AKIA[0-9A-Z]{16}|AIza[0-9A-Za-z_-]{35}|gh[pousr]_[A-Za-z0-9]{36,}|xox[abpr]-[A-Za-z0-9-]{10,})";
+
+/// Built-in detector of sensitive values
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    Phone,
+    ApiKey,
+}
+
+impl PiiKind {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Email => "EMAIL",
+            Self::Phone => "PHONE",
+            Self::ApiKey => "API_KEY",
+        }
+    }
+
+    fn pattern(self) -> &'static str {
+        match self {
+            Self::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+            // International or grouped numbers, and mainland China mobile numbers
+            Self::Phone => {
+                r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{2,4}\)\s?|\b\d{2,4}[\s.-])\d{3,4}[\s.-]\d{4}\b|\b1[3-9]\d{9}\b"
+            }
+            Self::ApiKey => API_KEY_PATTERN,
+        }
+    }
+}
+
+/// Label of placeholders for custom pattern matches
+const CUSTOM_LABEL: &str = "REDACTED";
+
+/// Where a string sits in a request: only text fields are redacted, and tool inputs
+/// are redacted throughout
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Structure,
+    Text,
+    ToolInput,
+}
+
+impl Field {
+    fn of(key: &str) -> Self {
+        match key {
+            "text" | "content" => Self::Text,
+            "input" | "arguments" => Self::ToolInput,
+            _ => Self::Structure,
+        }
+    }
+}
+
+/// Redacts one request and remembers its placeholders
+#[derive(Debug, Default)]
+pub struct Redactor {
+    patterns: Vec<(&'static str, Regex)>,
+    /// Placeholder of each redacted value
+    placeholders: HashMap<String, String>,
+    counts: HashMap<&'static str, usize>,
+}
+
+impl Redactor {
+    /// Compile the configured detectors; invalid custom patterns are logged and skipped
+    pub fn new(config: &RedactionConfig) -> Self {
+        let mut patterns: Vec<(&'static str, Regex)> = config
+            .detectors
+            .iter()
+            .map(|kind| {
+                let regex = Regex::new(kind.pattern()).expect("built-in pattern is valid");
+                (kind.label(), regex)
+            })
+            .collect();
+        for pattern in &config.custom_patterns {
+            match Regex::new(pattern) {
+                Ok(regex) => patterns.push((CUSTOM_LABEL, regex)),
+                Err(e) => log::warn!("Skipping invalid redaction pattern '{}': {}", pattern, e),
+            }
+        }
+        Self {
+            patterns,
+            ..Default::default()
+        }
+    }
+
+    /// Number of distinct values redacted so far
+    pub fn redacted(&self) -> usize {
+        self.placeholders.len()
+    }
+
+    fn redact_text(&mut self, text: &str) -> Option<String> {
+        let mut current = text.to_string();
+        let mut changed = false;
+        for (label, regex) in &self.patterns {
+            let placeholders = &mut self.placeholders;
+            let counts = &mut self.counts;
+            let replaced = regex.replace_all(&current, |caps: &regex::Captures| {
+                placeholders
+                    .entry(caps[0].to_string())
+                    .or_insert_with(|| {
+                        let count = counts.entry(*label).or_insert(0);
+                        *count += 1;
+                        format!("[{}_{}]", label, count)
+                    })
+                    .clone()
+            });
+            if let std::borrow::Cow::Owned(replaced) = replaced {
+                current = replaced;
+                changed = true;
+            }
+        }
+        changed.then_some(current)
+    }
+
+    fn redact_value(&mut self, value: &mut Value, field: Field) {
+        match value {
+            Value::String(text) if field != Field::Structure => {
+                if let Some(redacted) = self.redact_text(text) {
+                    *text = redacted;
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.redact_value(item, field);
+                }
+            }
+            Value::Object(obj) => {
+                for (key, child) in obj.iter_mut() {
+                    let child_field = match field {
+                        Field::ToolInput => Field::ToolInput,
+                        _ => Field::of(key),
+                    };
+                    self.redact_value(child, child_field);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Redact the system prompt and messages of an Anthropic or OpenAI shaped request
+    pub fn redact_request(&mut self, request: &mut Value) {
+        if let Some(system) = request.get_mut("system") {
+            self.redact_value(system, Field::Text);
+        }
+        if let Some(messages) = request.get_mut("messages") {
+            self.redact_value(messages, Field::Structure);
+        }
+    }
+
+    /// Swap placeholders in every string of a response back for the original values
+    pub fn restore(&self, value: &mut Value) {
+        match value {
+            Value::String(text) if text.contains('[') => {
+                for (original, placeholder) in &self.placeholders {
+                    if text.contains(placeholder.as_str()) {
+                        *text = text.replace(placeholder.as_str(), original);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.restore(item)),
+            Value::Object(obj) => obj.values_mut().for_each(|child| self.restore(child)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_and_restore() {
+        let config = RedactionConfig {
+            enabled: true,
+            providers: Vec::new(),
+            detectors: vec![PiiKind::ApiKey, PiiKind::Email, PiiKind::Phone],
+            custom_patterns: vec![r"ACME-\d+".to_string()],
+            restore_responses: true,
+        };
+        let mut redactor = Redactor::new(&config);
+        let mut request = json!({
+            "system": "Contact ops@example.com or +1 415-555-0100.",
+            "messages": [
+                {"role": "user", "content": [
+                    {"type": "text", "text": "Ticket ACME-42 from ops@example.com"},
+                    {"type": "image", "source": {"type": "base64", "data": "ops@example.com"}}
+                ]},
+                {"role": "assistant", "content": [{"type": "tool_use", "id": "t1", "name": "env",
+                    "input": {"key": "sk-proj-abcdefghijklmnopqrstuvwxyz"}}]}
+            ]
+        });
+        redactor.redact_request(&mut request);
+
+        assert_eq!(request["system"], "Contact [EMAIL_1] or [PHONE_1].");
+        assert_eq!(
+            request["messages"][0]["content"][0]["text"],
+            "Ticket [REDACTED_1] from [EMAIL_1]"
+        );
+        assert_eq!(
+            request["messages"][0]["content"][1]["source"]["data"],
+            "ops@example.com"
+        );
+        assert_eq!(
+            request["messages"][1]["content"][0]["input"]["key"],
+            "[API_KEY_1]"
+        );
+        assert_eq!(redactor.redacted(), 4);
+
+        let mut response = json!({"content": [{"type": "text", "text": "Mailed [EMAIL_1]."}]});
+        redactor.restore(&mut response);
+        assert_eq!(response["content"][0]["text"], "Mailed ops@example.com.");
+    }
+}
@@ -0,0 +1,264 @@
+//! Model Registry - Context windows and deprecation dates of known models
+//!
+//! The registry is read from the same manifest as prices (LiteLLM's by default, which
+//! carries `max_input_tokens` and `deprecation_date`) and stored in `app_settings`.
+//! Configured models are checked against it: the gateway status warns about models that
+//! are deprecated or soon will be, and about context windows that differ from the
+//! registry's, which either waste context or let requests overflow.
+//!
+//! With `model_registry_sync` set, [`schedule`] refreshes the registry once a day.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use super::{load_gateway_settings, pricing, stored_setting, write_setting, GatewaySettings};
+use crate::commands::agents::AgentDb;
+
+/// `app_settings` key of the stored registry
+const REGISTRY_KEY: &str = "llm_gateway_model_registry";
+
+/// How often the schedule checks whether the registry is due for a refresh
+const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Age at which the schedule refreshes the registry
+const MAX_AGE_HOURS: i64 = 24;
+
+/// Models deprecated within this many days are warned about ahead of time
+const DEPRECATION_NOTICE_DAYS: i64 = 30;
+
+/// Configured context windows may differ from the registry's by this share unwarned
+const CONTEXT_TOLERANCE: f64 = 0.1;
+
+/// What the registry knows about a model
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelInfo {
+    /// Input tokens the model accepts
+    pub context_window: Option<u32>,
+    /// Day the provider retires the model
+    pub deprecation_date: Option<NaiveDate>,
+}
+
+/// Known models by manifest key (`model` or `provider/model`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelRegistry {
+    pub models: HashMap<String, ModelInfo>,
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+/// A configured model that needs attention
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ModelWarning {
+    /// Provider display name
+    pub provider: String,
+    pub model: String,
+    pub message: String,
+}
+
+/// Outcome of a registry sync
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegistrySyncResult {
+    /// Models in the registry
+    pub models: usize,
+    /// Warnings about the configured models against the synced registry
+    pub warnings: Vec<ModelWarning>,
+    /// Error fetching the manifest; the stored registry is kept
+    pub error: Option<String>,
+}
+
+fn parse_entry(entry: &Value) -> Option<ModelInfo> {
+    let context_window = entry
+        .get("max_input_tokens")
+        .or_else(|| entry.get("max_tokens"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v.min(u32::MAX as u64) as u32);
+    let deprecation_date = entry
+        .get("deprecation_date")
+        .and_then(|v| v.as_str())
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+    (context_window.is_some() || deprecation_date.is_some()).then_some(ModelInfo {
+        context_window,
+        deprecation_date,
+    })
+}
+
+/// Parse a manifest, skipping entries with neither a context window nor a deprecation
+pub fn parse_registry(manifest: &Value) -> HashMap<String, ModelInfo> {
+    manifest
+        .as_object()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|(key, entry)| Some((key.clone(), parse_entry(entry)?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The stored registry, empty before the first sync
+pub fn load(conn: &Connection) -> ModelRegistry {
+    stored_setting(conn, REGISTRY_KEY)
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn store(conn: &Connection, registry: &ModelRegistry) -> Result<(), String> {
+    let json = serde_json::to_string(registry).map_err(|e| e.to_string())?;
+    write_setting(conn, REGISTRY_KEY, &json)
+}
+
+/// Warnings about the configured models of enabled providers as of `today`
+pub fn warnings(
+    settings: &GatewaySettings,
+    registry: &ModelRegistry,
+    today: NaiveDate,
+) -> Vec<ModelWarning> {
+    let mut warnings = Vec::new();
+    for provider in settings.providers.iter().filter(|p| p.enabled) {
+        let provider_key = provider.provider.to_string();
+        for model in &provider.models {
+            let Some(info) = registry
+                .models
+                .get(&format!("{}/{}", provider_key, model.id))
+                .or_else(|| registry.models.get(&model.id))
+            else {
+                continue;
+            };
+            let mut warn = |message: String| {
+                warnings.push(ModelWarning {
+                    provider: provider.name.clone(),
+                    model: model.id.clone(),
+                    message,
+                })
+            };
+
+            if let Some(date) = info.deprecation_date {
+                if date <= today {
+                    warn(format!("Deprecated since {}", date));
+                } else if (date - today).num_days() <= DEPRECATION_NOTICE_DAYS {
+                    warn(format!("Deprecated on {}", date));
+                }
+            }
+            if let Some(actual) = info.context_window.filter(|_| model.max_tokens > 0) {
+                let difference = (model.max_tokens as f64 - actual as f64).abs();
+                if difference > actual as f64 * CONTEXT_TOLERANCE {
+                    warn(format!(
+                        "Configured context of {} tokens, but the model takes {}",
+                        model.max_tokens, actual
+                    ));
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// Fetch the manifest and store the registry read from it
+pub async fn sync(db: &AgentDb) -> Result<RegistrySyncResult, String> {
+    let settings = {
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        load_gateway_settings(&conn)
+    };
+    let fetched = pricing::fetch_manifest(&pricing::manifest_url(&settings)).await;
+
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let mut registry = load(&conn);
+    let error = match fetched {
+        Ok(manifest) => {
+            registry = ModelRegistry {
+                models: parse_registry(&manifest),
+                synced_at: Some(Utc::now()),
+            };
+            store(&conn, &registry)?;
+            None
+        }
+        Err(e) => {
+            log::warn!("Failed to fetch model registry: {}", e);
+            Some(e)
+        }
+    };
+    Ok(RegistrySyncResult {
+        models: registry.models.len(),
+        warnings: warnings(&settings, &registry, chrono::Local::now().date_naive()),
+        error,
+    })
+}
+
+/// Whether the schedule should refresh the registry now
+fn due(app: &AppHandle) -> Result<bool, String> {
+    let db = app.state::<AgentDb>();
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    if !load_gateway_settings(&conn).model_registry_sync {
+        return Ok(false);
+    }
+    Ok(load(&conn)
+        .synced_at
+        .is_none_or(|at| Utc::now() - at >= chrono::Duration::hours(MAX_AGE_HOURS)))
+}
+
+/// Keep the registry fresh for as long as the app runs
+pub async fn schedule(app: AppHandle) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        match due(&app) {
+            Ok(true) => {
+                if let Ok(result) = sync(&app.state::<AgentDb>()).await {
+                    for warning in &result.warnings {
+                        log::warn!(
+                            "{}/{}: {}",
+                            warning.provider,
+                            warning.model,
+                            warning.message
+                        );
+                    }
+                }
+            }
+            Ok(false) => {}
+            Err(e) => log::warn!("Failed to check the model registry: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_warnings() {
+        let registry = ModelRegistry {
+            models: parse_registry(&json!({
+                "gpt-4o": {"max_input_tokens": 128000, "max_output_tokens": 16384},
+                "gpt-4-turbo": {"max_input_tokens": 128000, "deprecation_date": "2026-11-01"},
+                "gpt-4o-mini": {"max_input_tokens": 1000000},
+                "sample_spec": {"mode": "chat"}
+            })),
+            synced_at: None,
+        };
+        assert_eq!(registry.models.len(), 3);
+
+        let mut settings = GatewaySettings::default();
+        for provider in &mut settings.providers {
+            provider.enabled = provider.provider.to_string() == "openai";
+        }
+        let today = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let warnings = warnings(&settings, &registry, today);
+        let message = |model: &str| {
+            warnings
+                .iter()
+                .find(|w| w.model == model)
+                .map(|w| w.message.as_str())
+        };
+        assert_eq!(message("gpt-4o"), None);
+        assert_eq!(message("gpt-4-turbo"), Some("Deprecated on 2026-11-01"));
+        assert_eq!(
+            message("gpt-4o-mini"),
+            Some("Configured context of 128000 tokens, but the model takes 1000000")
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Replicate Predictions - Chat completions on top of Replicate's asynchronous model API
+//!
+//! Replicate runs models as predictions: a request creates one, which then moves through
+//! `starting` and `processing` to `succeeded`, `failed` or `canceled`. Non-streaming
+//! requests ask Replicate to hold the response until the prediction finishes and poll
+//! its status when that isn't enough; streaming requests follow the prediction's event
+//! stream. Either way the client sees an ordinary chat completion (or chunk stream).
+//!
+//! Language models on Replicate take a single `prompt` plus `system_prompt`, so the
+//! conversation is flattened into a transcript.
+
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+use super::translate::StreamTranslator;
+
+/// Time between status checks of a running prediction
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest a prediction is waited for, cold boots included
+pub const MAX_WAIT: Duration = Duration::from_secs(10 * 60);
+
+/// Prediction endpoint for a model, with the version to send when `model` pins one as
+/// `owner/name:version`
+pub fn prediction_url<'a>(base_url: &str, model: &'a str) -> (String, Option<&'a str>) {
+    let base = base_url.trim_end_matches('/');
+    match model.split_once(':') {
+        Some((_, version)) => (format!("{}/predictions", base), Some(version)),
+        None => (format!("{}/models/{}/predictions", base, model), None),
+    }
+}
+
+/// Text of a message's content, whether a string or a list of parts
+fn message_text(message: &Value) -> String {
+    match &message["content"] {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Prediction request equivalent to a chat completion request
+pub fn prediction_request(body: &Value, version: Option<&str>) -> Value {
+    let messages = body["messages"].as_array().cloned().unwrap_or_default();
+    let (system, turns): (Vec<&Value>, Vec<&Value>) = messages
+        .iter()
+        .partition(|m| matches!(m["role"].as_str(), Some("system" | "developer")));
+
+    // A lone user turn is sent as is; conversations become a transcript to continue
+    let prompt = match turns.as_slice() {
+        [only] if only["role"] == "user" => message_text(only),
+        _ => {
+            let mut transcript: Vec<String> = turns
+                .iter()
+                .map(|m| {
+                    let speaker = if m["role"] == "assistant" {
+                        "Assistant"
+                    } else {
+                        "User"
+                    };
+                    format!("{}: {}", speaker, message_text(m))
+                })
+                .collect();
+            transcript.push("Assistant:".to_string());
+            transcript.join("\n\n")
+        }
+    };
+
+    let mut input = Map::new();
+    input.insert("prompt".to_string(), json!(prompt));
+    if !system.is_empty() {
+        let system: Vec<String> = system.into_iter().map(message_text).collect();
+        input.insert("system_prompt".to_string(), json!(system.join("\n\n")));
+    }
+    if let Some(max_tokens) = body
+        .get("max_tokens")
+        .or_else(|| body.get("max_completion_tokens"))
+    {
+        input.insert("max_tokens".to_string(), max_tokens.clone());
+    }
+    for field in ["temperature", "top_p", "seed"] {
+        if let Some(value) = body.get(field) {
+            input.insert(field.to_string(), value.clone());
+        }
+    }
+    let stop = match body.get("stop") {
+        Some(Value::String(stop)) => Some(stop.clone()),
+        Some(Value::Array(stops)) => Some(
+            stops
+                .iter()
+                .filter_map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        _ => None,
+    };
+    if let Some(stop) = stop {
+        input.insert("stop_sequences".to_string(), json!(stop));
+    }
+
+    let mut request = json!({
+        "input": input,
+        "stream": body.get("stream").and_then(|s| s.as_bool()) == Some(true),
+    });
+    if let Some(version) = version {
+        request["version"] = json!(version);
+    }
+    request
+}
+
+/// Whether a prediction has stopped running
+pub fn is_finished(prediction: &Value) -> bool {
+    matches!(
+        prediction["status"].as_str(),
+        Some("succeeded" | "failed" | "canceled")
+    )
+}
+
+/// Why a finished prediction produced no answer
+pub fn failure(prediction: &Value) -> Option<String> {
+    match prediction["status"].as_str() {
+        Some("failed") => Some(
+            prediction["error"]
+                .as_str()
+                .unwrap_or("Prediction failed")
+                .to_string(),
+        ),
+        Some("canceled") => Some("Prediction was canceled".to_string()),
+        _ => None,
+    }
+}
+
+/// Chat completion equivalent to a succeeded prediction; language models return their
+/// output as a list of tokens
+pub fn to_openai_response(prediction: &Value, model: &str) -> Value {
+    let content = match &prediction["output"] {
+        Value::Array(tokens) => tokens.iter().filter_map(|t| t.as_str()).collect(),
+        Value::String(text) => text.clone(),
+        _ => String::new(),
+    };
+    let input = prediction
+        .pointer("/metrics/input_token_count")
+        .and_then(|n| n.as_u64())
+        .unwrap_or_default();
+    let output = prediction
+        .pointer("/metrics/output_token_count")
+        .and_then(|n| n.as_u64())
+        .unwrap_or_default();
+    json!({
+        "id": prediction.get("id").cloned().unwrap_or(Value::Null),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop",
+        }],
+        "usage": {
+            "prompt_tokens": input,
+            "completion_tokens": output,
+            "total_tokens": input + output,
+        },
+    })
+}
+
+/// Translates a prediction's event stream (`output`, `error` and `done` events carrying
+/// plain text) into chat completion chunks
+pub struct PredictionStreamTranslator {
+    buffer: Vec<u8>,
+    event: String,
+    data: Vec<String>,
+    id: String,
+    model: String,
+    created: i64,
+    started: bool,
+    done: bool,
+}
+
+impl PredictionStreamTranslator {
+    pub fn new(id: &str, model: &str) -> Self {
+        Self {
+            buffer: Vec::new(),
+            event: String::new(),
+            data: Vec::new(),
+            id: id.to_string(),
+            model: model.to_string(),
+            created: chrono::Utc::now().timestamp(),
+            started: false,
+            done: false,
+        }
+    }
+
+    fn chunk(&mut self, mut delta: Value, finish_reason: Option<&str>) -> String {
+        if !std::mem::replace(&mut self.started, true) {
+            delta["role"] = json!("assistant");
+        }
+        let chunk = json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+        });
+        format!("data: {}\n\n", chunk)
+    }
+
+    /// Output for a complete event
+    fn dispatch(&mut self) -> String {
+        let event = std::mem::take(&mut self.event);
+        let data = std::mem::take(&mut self.data).join("\n");
+        match event.as_str() {
+            "output" if !self.done => self.chunk(json!({"content": data}), None),
+            "error" => {
+                self.done = true;
+                let error = json!({"error": {"message": data, "type": "server_error"}});
+                format!("data: {}\n\ndata: [DONE]\n\n", error)
+            }
+            "done" => self.finish(),
+            _ => String::new(),
+        }
+    }
+}
+
+impl StreamTranslator for PredictionStreamTranslator {
+    fn push(&mut self, chunk: &[u8]) -> String {
+        self.buffer.extend_from_slice(chunk);
+        let mut out = String::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                out.push_str(&self.dispatch());
+            } else if let Some(event) = line.strip_prefix("event:") {
+                self.event = event.trim().to_string();
+            } else if let Some(data) = line.strip_prefix("data:") {
+                // A single leading space is part of the field syntax; tokens keep theirs
+                self.data
+                    .push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+        }
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        if std::mem::replace(&mut self.done, true) {
+            return String::new();
+        }
+        let last = self.chunk(json!({}), Some("stop"));
+        format!("{}data: [DONE]\n\n", last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prediction_request() {
+        let (url, version) = prediction_url(
+            "https://api.replicate.com/v1/",
+            "meta/meta-llama-3-70b-instruct",
+        );
+        assert_eq!(
+            url,
+            "https://api.replicate.com/v1/models/meta/meta-llama-3-70b-instruct/predictions"
+        );
+        assert_eq!(version, None);
+        assert_eq!(
+            prediction_url("https://api.replicate.com/v1", "owner/model:abc123"),
+            (
+                "https://api.replicate.com/v1/predictions".to_string(),
+                Some("abc123")
+            )
+        );
+
+        let request = prediction_request(
+            &json!({
+                "messages": [
+                    {"role": "system", "content": "Be brief."},
+                    {"role": "user", "content": "Hi"},
+                    {"role": "assistant", "content": "Hello!"},
+                    {"role": "user", "content": [{"type": "text", "text": "Name a color"}]}
+                ],
+                "max_tokens": 20,
+                "stop": ["\n\n", "User:"],
+                "stream": true
+            }),
+            None,
+        );
+        assert_eq!(request["input"]["system_prompt"], "Be brief.");
+        assert_eq!(
+            request["input"]["prompt"],
+            "User: Hi\n\nAssistant: Hello!\n\nUser: Name a color\n\nAssistant:"
+        );
+        assert_eq!(request["input"]["max_tokens"], 20);
+        assert_eq!(request["input"]["stop_sequences"], "\n\n,User:");
+        assert_eq!(request["stream"], true);
+    }
+
+    #[test]
+    fn test_prediction_output() {
+        let prediction = json!({
+            "id": "p1",
+            "status": "succeeded",
+            "output": ["Bl", "ue"],
+            "metrics": {"input_token_count": 9, "output_token_count": 2}
+        });
+        assert!(is_finished(&prediction));
+        assert_eq!(failure(&prediction), None);
+        let response = to_openai_response(&prediction, "meta/llama");
+        assert_eq!(response["choices"][0]["message"]["content"], "Blue");
+        assert_eq!(response["usage"]["total_tokens"], 11);
+        assert_eq!(
+            failure(&json!({"status": "failed", "error": "CUDA out of memory"})).as_deref(),
+            Some("CUDA out of memory")
+        );
+
+        let mut translator = PredictionStreamTranslator::new("p1", "meta/llama");
+        let out = translator.push(b"event: output\nid: 1\ndata: Bl\n\nevent: output\ndata:  ue\n");
+        assert_eq!(out.matches("data: {").count(), 1);
+        assert!(out.contains("\"role\":\"assistant\""));
+        let out = translator.push(b"\nevent: done\ndata: {}\n\n");
+        assert!(out.contains("\"content\":\" ue\""));
+        assert!(out.contains("\"finish_reason\":\"stop\""));
+        assert!(out.ends_with("data: [DONE]\n\n"));
+        assert_eq!(translator.finish(), "");
+    }
+}
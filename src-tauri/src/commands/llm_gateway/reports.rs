@@ -0,0 +1,720 @@
+//! Usage Reports - Daily and weekly spend summaries of gateway traffic
+//!
+//! A report sums up a period of the request log: total spend, spend by provider, model
+//! and project, and the most expensive sessions. Reports are stored in the database
+//! and, when a directory is configured, written there as markdown.
+//!
+//! [`schedule`] runs for the lifetime of the app and produces the report of each
+//! period once it has ended, checking every hour. Periods follow local time and
+//! weeks start on Monday.
+//!
+//! [`export_usage`] writes the request log of a date range to CSV or JSON, either one
+//! row per request or grouped like a report, for expensing or outside analysis.
+//!
+//! Spend is kept in USD; with a display currency configured, reports are rendered in
+//! it and exports carry a converted `cost` column next to `cost_usd`.
+
+use chrono::{Datelike, Duration as DateDuration, Local, NaiveDate};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use super::currency::{self, DisplayCurrency};
+use super::load_gateway_settings;
+use crate::commands::agents::AgentDb;
+
+/// How often the schedule looks for a period without a report
+const CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Sessions listed among the most expensive when unset
+const DEFAULT_TOP_SESSIONS: usize = 10;
+
+/// Project label of requests that didn't name one
+const NO_PROJECT: &str = "(none)";
+
+/// Scheduled usage reports
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ReportConfig {
+    /// Produce a report for each day
+    #[serde(default)]
+    pub daily: bool,
+    /// Produce a report for each week
+    #[serde(default)]
+    pub weekly: bool,
+    /// Directory reports are also written to as markdown files
+    #[serde(default)]
+    pub directory: Option<String>,
+    /// Sessions listed among the most expensive (default 10)
+    #[serde(default)]
+    pub top_sessions: Option<usize>,
+}
+
+/// Span of time a report covers
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportPeriod {
+    Daily,
+    Weekly,
+}
+
+impl ReportPeriod {
+    fn as_str(self) -> &'static str {
+        match self {
+            ReportPeriod::Daily => "daily",
+            ReportPeriod::Weekly => "weekly",
+        }
+    }
+
+    /// First and last day of the period containing `day`
+    pub fn bounds(self, day: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            ReportPeriod::Daily => (day, day),
+            ReportPeriod::Weekly => {
+                let start = day - DateDuration::days(day.weekday().num_days_from_monday() as i64);
+                (start, start + DateDuration::days(6))
+            }
+        }
+    }
+
+    /// Bounds of the last period that ended before `today`
+    fn previous(self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let (start, _) = self.bounds(today);
+        self.bounds(start - DateDuration::days(1))
+    }
+}
+
+/// Requests, tokens and spend of one group in a report
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpendRow {
+    pub name: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Spend summary of a period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub period: ReportPeriod,
+    /// First day covered (YYYY-MM-DD)
+    pub start_date: String,
+    /// Last day covered (YYYY-MM-DD)
+    pub end_date: String,
+    pub requests: u64,
+    pub cost_usd: f64,
+    pub by_provider: Vec<SpendRow>,
+    pub by_model: Vec<SpendRow>,
+    pub by_project: Vec<SpendRow>,
+    /// Most expensive sessions first
+    pub top_sessions: Vec<SpendRow>,
+    pub created_at: String,
+    /// Currency the report is rendered in; USD when unset
+    #[serde(default)]
+    pub currency: Option<DisplayCurrency>,
+}
+
+/// Create the report table if it doesn't exist yet
+pub fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS gateway_usage_reports (
+            period TEXT NOT NULL,
+            start_date TEXT NOT NULL,
+            report TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (period, start_date)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Spend of the requests between `start` and `end`, grouped by the SQL expression `group`
+fn spend_rows(
+    conn: &Connection,
+    group: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+    limit: Option<usize>,
+) -> rusqlite::Result<Vec<SpendRow>> {
+    let sql = format!(
+        "SELECT {group} AS name, COUNT(*), COALESCE(SUM(input_tokens), 0),
+                COALESCE(SUM(output_tokens), 0), COALESCE(SUM(cost_usd), 0)
+         FROM gateway_request_log
+         WHERE date(created_at, 'localtime') BETWEEN ?1 AND ?2 AND name IS NOT NULL
+         GROUP BY name
+         ORDER BY 5 DESC, 2 DESC
+         LIMIT ?3"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(
+        params![
+            start.to_string(),
+            end.to_string(),
+            limit.map_or(-1, |l| l as i64)
+        ],
+        |row| {
+            Ok(SpendRow {
+                name: row.get(0)?,
+                requests: row.get::<_, i64>(1)? as u64,
+                input_tokens: row.get::<_, i64>(2)? as u64,
+                output_tokens: row.get::<_, i64>(3)? as u64,
+                cost_usd: row.get(4)?,
+            })
+        },
+    )?;
+    rows.collect()
+}
+
+/// Summarize the request log for the period containing `day`
+pub fn generate(
+    conn: &Connection,
+    period: ReportPeriod,
+    day: NaiveDate,
+    top_sessions: usize,
+) -> rusqlite::Result<UsageReport> {
+    let (start, end) = period.bounds(day);
+    let by_provider = spend_rows(conn, "provider", start, end, None)?;
+    let by_model = spend_rows(conn, "provider || '/' || model", start, end, None)?;
+    let project = format!("COALESCE(project, '{}')", NO_PROJECT);
+    let by_project = spend_rows(conn, &project, start, end, None)?;
+    let top_sessions = spend_rows(conn, "session", start, end, Some(top_sessions))?;
+    Ok(UsageReport {
+        period,
+        start_date: start.to_string(),
+        end_date: end.to_string(),
+        requests: by_provider.iter().map(|r| r.requests).sum(),
+        cost_usd: by_provider.iter().map(|r| r.cost_usd).sum(),
+        by_provider,
+        by_model,
+        by_project,
+        top_sessions,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        currency: None,
+    })
+}
+
+/// Store a report, replacing an earlier one for the same period
+pub fn save_report(conn: &Connection, report: &UsageReport) -> Result<(), String> {
+    let json = serde_json::to_string(report).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO gateway_usage_reports (period, start_date, report)
+         VALUES (?1, ?2, ?3)",
+        params![report.period.as_str(), report.start_date, json],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn has_report(conn: &Connection, period: ReportPeriod, start: NaiveDate) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM gateway_usage_reports WHERE period = ?1 AND start_date = ?2",
+        params![period.as_str(), start.to_string()],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|found| found.is_some())
+}
+
+/// Stored reports, newest first
+pub fn list_reports(conn: &Connection, limit: usize) -> Result<Vec<UsageReport>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT report FROM gateway_usage_reports
+             ORDER BY start_date DESC, period LIMIT ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![limit as i64], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?;
+    rows.map(|json| {
+        let json = json.map_err(|e| e.to_string())?;
+        serde_json::from_str(&json).map_err(|e| e.to_string())
+    })
+    .collect()
+}
+
+fn markdown_table(
+    out: &mut String,
+    currency: Option<&DisplayCurrency>,
+    title: &str,
+    label: &str,
+    rows: &[SpendRow],
+) {
+    out.push_str(&format!("\n## {}\n\n", title));
+    if rows.is_empty() {
+        out.push_str("No requests.\n");
+        return;
+    }
+    out.push_str(&format!(
+        "| {} | Requests | Input tokens | Output tokens | Cost |\n|---|---:|---:|---:|---:|\n",
+        label
+    ));
+    for row in rows {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} |\n",
+            row.name.replace('|', "\\|"),
+            row.requests,
+            row.input_tokens,
+            row.output_tokens,
+            currency::format_cost(currency, row.cost_usd, 4)
+        ));
+    }
+}
+
+/// Render a report as markdown
+pub fn render_markdown(report: &UsageReport) -> String {
+    let span = if report.start_date == report.end_date {
+        report.start_date.clone()
+    } else {
+        format!("{} to {}", report.start_date, report.end_date)
+    };
+    let currency = report.currency.as_ref();
+    let mut out = format!(
+        "# Gateway usage, {} ({})\n\nTotal spend: {} over {} requests\n",
+        span,
+        report.period.as_str(),
+        currency::format_cost(currency, report.cost_usd, 2),
+        report.requests
+    );
+    markdown_table(
+        &mut out,
+        currency,
+        "Spend by provider",
+        "Provider",
+        &report.by_provider,
+    );
+    markdown_table(
+        &mut out,
+        currency,
+        "Spend by model",
+        "Model",
+        &report.by_model,
+    );
+    markdown_table(
+        &mut out,
+        currency,
+        "Spend by project",
+        "Project",
+        &report.by_project,
+    );
+    markdown_table(
+        &mut out,
+        currency,
+        "Most expensive sessions",
+        "Session",
+        &report.top_sessions,
+    );
+    out
+}
+
+/// Write a report as markdown into `directory`, returning the file's path
+pub fn write_markdown(report: &UsageReport, directory: &str) -> Result<String, String> {
+    std::fs::create_dir_all(directory)
+        .map_err(|e| format!("Failed to create '{}': {}", directory, e))?;
+    let path = Path::new(directory).join(format!(
+        "gateway-usage-{}-{}.md",
+        report.period.as_str(),
+        report.start_date
+    ));
+    std::fs::write(&path, render_markdown(report))
+        .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))?;
+    Ok(path.display().to_string())
+}
+
+/// How exported usage is grouped
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGrouping {
+    /// One row per request
+    #[default]
+    Request,
+    Day,
+    Provider,
+    Model,
+    Project,
+    Session,
+}
+
+impl UsageGrouping {
+    /// SQL expression rows are grouped by
+    fn expression(self) -> Option<String> {
+        match self {
+            UsageGrouping::Request => None,
+            UsageGrouping::Day => Some("date(created_at, 'localtime')".to_string()),
+            UsageGrouping::Provider => Some("provider".to_string()),
+            UsageGrouping::Model => Some("provider || '/' || model".to_string()),
+            UsageGrouping::Project => Some(format!("COALESCE(project, '{}')", NO_PROJECT)),
+            UsageGrouping::Session => Some("session".to_string()),
+        }
+    }
+}
+
+/// One request of the log as exported
+#[derive(Debug, Clone, Serialize)]
+struct RequestRow {
+    created_at: String,
+    provider: String,
+    model: String,
+    requested_model: String,
+    status_code: u16,
+    input_tokens: Option<i64>,
+    output_tokens: Option<i64>,
+    cost_usd: Option<f64>,
+    project: Option<String>,
+    session: Option<String>,
+}
+
+fn request_rows(
+    conn: &Connection,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> rusqlite::Result<Vec<RequestRow>> {
+    let mut stmt = conn.prepare(
+        "SELECT datetime(created_at, 'localtime'), provider, model, requested_model,
+                status_code, input_tokens, output_tokens, cost_usd, project, session
+         FROM gateway_request_log
+         WHERE date(created_at, 'localtime') BETWEEN ?1 AND ?2
+         ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![start.to_string(), end.to_string()], |row| {
+        Ok(RequestRow {
+            created_at: row.get(0)?,
+            provider: row.get(1)?,
+            model: row.get(2)?,
+            requested_model: row.get(3)?,
+            status_code: row.get(4)?,
+            input_tokens: row.get(5)?,
+            output_tokens: row.get(6)?,
+            cost_usd: row.get(7)?,
+            project: row.get(8)?,
+            session: row.get(9)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Quote a CSV field when it holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_line(fields: &[String]) -> String {
+    let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+    fields.join(",") + "\n"
+}
+
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_default()
+}
+
+/// Rows as pretty JSON, with each row's cost in `currency` added when there is one
+fn json_rows<T: Serialize>(
+    rows: &[T],
+    currency: Option<&DisplayCurrency>,
+    cost_usd: impl Fn(&T) -> Option<f64>,
+) -> Result<String, String> {
+    let mut json = serde_json::to_value(rows).map_err(|e| e.to_string())?;
+    if let (Some(currency), Some(values)) = (currency, json.as_array_mut()) {
+        for (value, row) in values.iter_mut().zip(rows) {
+            value["cost"] = serde_json::json!(cost_usd(row).map(|c| currency.convert(c)));
+            value["currency"] = serde_json::json!(currency.code);
+        }
+    }
+    serde_json::to_string_pretty(&json).map_err(|e| e.to_string())
+}
+
+/// Render the request log between `start` and `end` as CSV, or JSON unless `csv` is set;
+/// returns the contents and the number of rows
+pub fn render_usage(
+    conn: &Connection,
+    start: NaiveDate,
+    end: NaiveDate,
+    grouping: UsageGrouping,
+    csv: bool,
+    currency: Option<&DisplayCurrency>,
+) -> Result<(String, usize), String> {
+    // Converted costs follow `cost_usd` in CSV rows
+    let converted = |cost_usd: Option<f64>| {
+        currency.map(|currency| {
+            vec![
+                optional(&cost_usd.map(|c| currency.convert(c))),
+                currency.code.clone(),
+            ]
+        })
+    };
+    let Some(group) = grouping.expression() else {
+        let rows = request_rows(conn, start, end).map_err(|e| e.to_string())?;
+        let contents = if csv {
+            let header = [
+                "created_at",
+                "provider",
+                "model",
+                "requested_model",
+                "status_code",
+                "input_tokens",
+                "output_tokens",
+                "cost_usd",
+                "project",
+                "session",
+            ];
+            let mut header: Vec<String> = header.map(str::to_string).to_vec();
+            if currency.is_some() {
+                header.splice(8..8, ["cost".to_string(), "currency".to_string()]);
+            }
+            let mut out = csv_line(&header);
+            for row in &rows {
+                let mut fields = vec![
+                    row.created_at.clone(),
+                    row.provider.clone(),
+                    row.model.clone(),
+                    row.requested_model.clone(),
+                    row.status_code.to_string(),
+                    optional(&row.input_tokens),
+                    optional(&row.output_tokens),
+                    optional(&row.cost_usd),
+                    optional(&row.project),
+                    optional(&row.session),
+                ];
+                if let Some(converted) = converted(row.cost_usd) {
+                    fields.splice(8..8, converted);
+                }
+                out.push_str(&csv_line(&fields));
+            }
+            out
+        } else {
+            json_rows(&rows, currency, |row| row.cost_usd)?
+        };
+        return Ok((contents, rows.len()));
+    };
+
+    let mut rows = spend_rows(conn, &group, start, end, None).map_err(|e| e.to_string())?;
+    if grouping == UsageGrouping::Day {
+        rows.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    let contents = if csv {
+        let header = [
+            "name",
+            "requests",
+            "input_tokens",
+            "output_tokens",
+            "cost_usd",
+        ];
+        let mut header: Vec<String> = header.map(str::to_string).to_vec();
+        if currency.is_some() {
+            header.extend(["cost".to_string(), "currency".to_string()]);
+        }
+        let mut out = csv_line(&header);
+        for row in &rows {
+            let mut fields = vec![
+                row.name.clone(),
+                row.requests.to_string(),
+                row.input_tokens.to_string(),
+                row.output_tokens.to_string(),
+                row.cost_usd.to_string(),
+            ];
+            fields.extend(converted(Some(row.cost_usd)).unwrap_or_default());
+            out.push_str(&csv_line(&fields));
+        }
+        out
+    } else {
+        json_rows(&rows, currency, |row| Some(row.cost_usd))?
+    };
+    Ok((contents, rows.len()))
+}
+
+/// Write the request log between `start` and `end` to `path`, as CSV for `.csv` paths
+/// and JSON otherwise; returns the number of rows written
+pub fn export_usage(
+    conn: &Connection,
+    start: NaiveDate,
+    end: NaiveDate,
+    grouping: UsageGrouping,
+    path: &Path,
+    currency: Option<&DisplayCurrency>,
+) -> Result<usize, String> {
+    let csv = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    let (contents, rows) = render_usage(conn, start, end, grouping, csv, currency)?;
+    std::fs::write(path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(rows)
+}
+
+/// Produce and store the report of the period containing `day` in the display
+/// `currency`, writing it to the configured directory
+pub fn create_report(
+    conn: &Connection,
+    config: &ReportConfig,
+    currency: Option<DisplayCurrency>,
+    period: ReportPeriod,
+    day: NaiveDate,
+) -> Result<UsageReport, String> {
+    let top_sessions = config.top_sessions.unwrap_or(DEFAULT_TOP_SESSIONS);
+    let mut report = generate(conn, period, day, top_sessions).map_err(|e| e.to_string())?;
+    report.currency = currency;
+    save_report(conn, &report)?;
+    if let Some(directory) = config.directory.as_deref().filter(|d| !d.is_empty()) {
+        write_markdown(&report, directory)?;
+    }
+    Ok(report)
+}
+
+/// Produce the reports of the periods that ended without one
+fn catch_up(app: &AppHandle) -> Result<(), String> {
+    let db = app.state::<AgentDb>();
+    let conn = db.0.get().map_err(|e| e.to_string())?;
+    let settings = load_gateway_settings(&conn);
+    let Some(config) = settings.reports else {
+        return Ok(());
+    };
+    let currency = settings.currency.and_then(|c| c.display_currency());
+    let today = Local::now().date_naive();
+    let periods = [
+        (config.daily, ReportPeriod::Daily),
+        (config.weekly, ReportPeriod::Weekly),
+    ];
+    for (_, period) in periods.into_iter().filter(|(enabled, _)| *enabled) {
+        let (start, _) = period.previous(today);
+        if !has_report(&conn, period, start).map_err(|e| e.to_string())? {
+            let report = create_report(&conn, &config, currency.clone(), period, start)?;
+            log::info!(
+                "Created {} usage report for {}",
+                period.as_str(),
+                report.start_date
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Produce scheduled reports for as long as the app runs
+pub async fn schedule(app: AppHandle) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = catch_up(&app) {
+            log::warn!("Failed to create usage reports: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::llm_gateway::{migrations, usage};
+
+    #[test]
+    fn test_report_periods() {
+        let thursday = NaiveDate::from_ymd_opt(2026, 10, 15).unwrap();
+        let date = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+        assert_eq!(ReportPeriod::Daily.previous(thursday), (date(14), date(14)));
+        assert_eq!(ReportPeriod::Weekly.bounds(thursday), (date(12), date(18)));
+        assert_eq!(ReportPeriod::Weekly.previous(thursday), (date(5), date(11)));
+    }
+
+    #[test]
+    fn test_generate_report() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::run(&conn).unwrap();
+
+        for (provider, project, session, cost) in [
+            ("openai", Some("doggy"), Some("s1"), 0.5),
+            ("openai", Some("doggy"), Some("s2"), 0.25),
+            ("anthropic", None, Some("s1"), 1.0),
+        ] {
+            usage::insert_record(
+                &conn,
+                &usage::RequestRecord {
+                    provider: provider.to_string(),
+                    model: "m".to_string(),
+                    status_code: 200,
+                    cost_usd: Some(cost),
+                    project: project.map(str::to_string),
+                    session: session.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        let today = Local::now().date_naive();
+        let report = generate(&conn, ReportPeriod::Daily, today, 1).unwrap();
+        assert_eq!(report.requests, 3);
+        assert_eq!(report.cost_usd, 1.75);
+        assert_eq!(report.by_provider[0].name, "anthropic");
+        assert_eq!(report.by_model[1].name, "openai/m");
+        assert_eq!(report.by_project[0].name, NO_PROJECT);
+        assert_eq!(report.top_sessions.len(), 1);
+        assert_eq!(report.top_sessions[0].name, "s1");
+        assert_eq!(report.top_sessions[0].cost_usd, 1.5);
+
+        save_report(&conn, &report).unwrap();
+        assert!(has_report(&conn, ReportPeriod::Daily, today).unwrap());
+        assert_eq!(list_reports(&conn, 10).unwrap().len(), 1);
+        assert!(render_markdown(&report).contains("| openai | 2 | 0 | 0 | $0.7500 |"));
+    }
+
+    #[test]
+    fn test_render_usage() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::run(&conn).unwrap();
+        usage::insert_record(
+            &conn,
+            &usage::RequestRecord {
+                requested_model: "gpt, \"latest\"".to_string(),
+                provider: "openai".to_string(),
+                model: "gpt-4o".to_string(),
+                status_code: 200,
+                cost_usd: Some(0.5),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let today = Local::now().date_naive();
+        let (csv, rows) =
+            render_usage(&conn, today, today, UsageGrouping::Request, true, None).unwrap();
+        assert_eq!(rows, 1);
+        assert!(csv.starts_with("created_at,provider,model,requested_model,"));
+        assert!(csv.contains(",openai,gpt-4o,\"gpt, \"\"latest\"\"\",200,,,0.5,,\n"));
+
+        let (json, _) =
+            render_usage(&conn, today, today, UsageGrouping::Model, false, None).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json[0]["name"], "openai/gpt-4o");
+        assert_eq!(json[0]["cost_usd"], 0.5);
+
+        let cny = DisplayCurrency {
+            code: "CNY".to_string(),
+            per_usd: 7.0,
+        };
+        let (csv, _) = render_usage(
+            &conn,
+            today,
+            today,
+            UsageGrouping::Request,
+            true,
+            Some(&cny),
+        )
+        .unwrap();
+        assert!(csv.contains(",cost_usd,cost,currency,project,"));
+        assert!(csv.contains(",200,,,0.5,3.5,CNY,,\n"));
+        let (json, _) =
+            render_usage(&conn, today, today, UsageGrouping::Model, false, Some(&cny)).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json[0]["cost"], 3.5);
+
+        let yesterday = today - DateDuration::days(1);
+        let (_, rows) =
+            render_usage(&conn, yesterday, yesterday, UsageGrouping::Day, true, None).unwrap();
+        assert_eq!(rows, 0);
+    }
+}
@@ -0,0 +1,662 @@
+//! Responses API - OpenAI `/v1/responses` on top of chat completions
+//!
+//! Requests are translated to chat completions before routing, so every provider and
+//! gateway feature is available to Responses clients. Function tools, images and
+//! `text.format` structured output are carried over; hosted tools (web search, file
+//! search, ...) have no chat equivalent and are dropped. The gateway keeps no state, so
+//! `previous_response_id` is rejected and clients have to send the whole conversation.
+
+use serde_json::{json, Value};
+
+use super::translate::{sse_event, SseParser, StreamTranslator};
+
+fn response_id() -> String {
+    format!("resp_{}", uuid::Uuid::new_v4().simple())
+}
+
+fn item_id(prefix: &str) -> String {
+    format!("{}_{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+/// Content of a Responses message as chat message content
+fn message_content(content: &Value) -> Value {
+    let Some(parts) = content.as_array() else {
+        return content.clone();
+    };
+    let parts: Vec<Value> = parts
+        .iter()
+        .filter_map(|part| match part.get("type").and_then(|t| t.as_str()) {
+            Some("input_text" | "output_text") => Some(json!({
+                "type": "text",
+                "text": part.get("text").cloned().unwrap_or_default(),
+            })),
+            Some("input_image") => {
+                let url = part.get("image_url").cloned()?;
+                let mut image = json!({ "url": url });
+                if let Some(detail) = part.get("detail") {
+                    image["detail"] = detail.clone();
+                }
+                Some(json!({"type": "image_url", "image_url": image}))
+            }
+            _ => None,
+        })
+        .collect();
+    Value::Array(parts)
+}
+
+/// Append Responses input items to chat messages
+fn push_input_items(items: &[Value], messages: &mut Vec<Value>) {
+    for item in items {
+        match item
+            .get("type")
+            .and_then(|t| t.as_str())
+            .unwrap_or("message")
+        {
+            "message" => {
+                let role = match item.get("role").and_then(|r| r.as_str()) {
+                    Some("developer") => "system",
+                    Some(role) => role,
+                    None => "user",
+                };
+                let content = item.get("content").cloned().unwrap_or_default();
+                messages.push(json!({"role": role, "content": message_content(&content)}));
+            }
+            "function_call" => {
+                let call = json!({
+                    "id": item.get("call_id").cloned().unwrap_or_default(),
+                    "type": "function",
+                    "function": {
+                        "name": item.get("name").cloned().unwrap_or_default(),
+                        "arguments": item.get("arguments").cloned().unwrap_or(json!("{}")),
+                    },
+                });
+                // Parallel calls belong to one assistant message
+                let previous = messages
+                    .last_mut()
+                    .filter(|m| m["role"] == "assistant" && m.get("tool_calls").is_some());
+                match previous {
+                    Some(message) => {
+                        if let Some(calls) = message["tool_calls"].as_array_mut() {
+                            calls.push(call);
+                        }
+                    }
+                    None => messages.push(json!({
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [call],
+                    })),
+                }
+            }
+            "function_call_output" => messages.push(json!({
+                "role": "tool",
+                "tool_call_id": item.get("call_id").cloned().unwrap_or_default(),
+                "content": item.get("output").cloned().unwrap_or_default(),
+            })),
+            other => log::debug!("Dropping unsupported Responses input item '{}'", other),
+        }
+    }
+}
+
+/// Chat completion request equivalent to a Responses request
+pub fn responses_to_chat_request(request: &Value) -> Result<Value, String> {
+    if request
+        .get("previous_response_id")
+        .is_some_and(|id| !id.is_null())
+    {
+        return Err(
+            "previous_response_id is not supported; send the full conversation as input"
+                .to_string(),
+        );
+    }
+
+    let mut messages = Vec::new();
+    if let Some(instructions) = request.get("instructions").and_then(|i| i.as_str()) {
+        messages.push(json!({"role": "system", "content": instructions}));
+    }
+    match request.get("input") {
+        Some(Value::String(text)) => messages.push(json!({"role": "user", "content": text})),
+        Some(Value::Array(items)) => push_input_items(items, &mut messages),
+        _ => return Err("'input' is required".to_string()),
+    }
+
+    let mut chat = json!({
+        "model": request.get("model").cloned().unwrap_or(Value::Null),
+        "messages": messages,
+    });
+    for field in [
+        "temperature",
+        "top_p",
+        "parallel_tool_calls",
+        "user",
+        "stream",
+    ] {
+        if let Some(value) = request.get(field) {
+            chat[field] = value.clone();
+        }
+    }
+    if let Some(max_tokens) = request.get("max_output_tokens") {
+        chat["max_tokens"] = max_tokens.clone();
+    }
+    if request.get("stream").and_then(|s| s.as_bool()) == Some(true) {
+        chat["stream_options"] = json!({"include_usage": true});
+    }
+    if let Some(effort) = request.pointer("/reasoning/effort") {
+        chat["reasoning_effort"] = effort.clone();
+    }
+
+    let tools: Vec<Value> = request
+        .get("tools")
+        .and_then(|t| t.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|tool| tool.get("type").and_then(|t| t.as_str()) == Some("function"))
+        .map(|tool| {
+            let mut function = json!({
+                "name": tool.get("name").cloned().unwrap_or_default(),
+                "parameters": tool.get("parameters").cloned().unwrap_or(json!({"type": "object"})),
+            });
+            for field in ["description", "strict"] {
+                if let Some(value) = tool.get(field) {
+                    function[field] = value.clone();
+                }
+            }
+            json!({"type": "function", "function": function})
+        })
+        .collect();
+    if !tools.is_empty() {
+        chat["tools"] = Value::Array(tools);
+        if let Some(choice) = request.get("tool_choice") {
+            chat["tool_choice"] = match choice.get("name") {
+                Some(name) => json!({"type": "function", "function": {"name": name}}),
+                None => choice.clone(),
+            };
+        }
+    }
+
+    match request
+        .pointer("/text/format/type")
+        .and_then(|t| t.as_str())
+    {
+        Some("json_schema") => {
+            let format = &request["text"]["format"];
+            let mut schema = json!({
+                "name": format.get("name").cloned().unwrap_or(json!("response")),
+                "schema": format.get("schema").cloned().unwrap_or(json!({})),
+            });
+            if let Some(strict) = format.get("strict") {
+                schema["strict"] = strict.clone();
+            }
+            chat["response_format"] = json!({"type": "json_schema", "json_schema": schema});
+        }
+        Some("json_object") => chat["response_format"] = json!({"type": "json_object"}),
+        _ => {}
+    }
+    Ok(chat)
+}
+
+/// Responses usage for chat completion usage
+fn responses_usage(usage: &Value) -> Value {
+    let count = |pointer: &str| usage.pointer(pointer).and_then(|v| v.as_u64()).unwrap_or(0);
+    let input = count("/prompt_tokens");
+    let output = count("/completion_tokens");
+    json!({
+        "input_tokens": input,
+        "input_tokens_details": {"cached_tokens": count("/prompt_tokens_details/cached_tokens")},
+        "output_tokens": output,
+        "output_tokens_details": {
+            "reasoning_tokens": count("/completion_tokens_details/reasoning_tokens"),
+        },
+        "total_tokens": input + output,
+    })
+}
+
+fn message_item(id: &str, text: &str, status: &str) -> Value {
+    json!({
+        "type": "message",
+        "id": id,
+        "status": status,
+        "role": "assistant",
+        "content": [{"type": "output_text", "text": text, "annotations": []}],
+    })
+}
+
+fn function_call_item(id: &str, call_id: &str, name: &str, arguments: &str, status: &str) -> Value {
+    json!({
+        "type": "function_call",
+        "id": id,
+        "call_id": call_id,
+        "name": name,
+        "arguments": arguments,
+        "status": status,
+    })
+}
+
+/// Response object wrapping output items; `finish_reason` is the chat completion's
+fn response_object(
+    id: &str,
+    model: &str,
+    created_at: Value,
+    output: Vec<Value>,
+    finish_reason: Option<&str>,
+    usage: Option<&Value>,
+) -> Value {
+    let incomplete = finish_reason == Some("length");
+    let mut response = json!({
+        "id": id,
+        "object": "response",
+        "created_at": created_at,
+        "status": if incomplete { "incomplete" } else { "completed" },
+        "model": model,
+        "output": output,
+        "usage": usage.filter(|u| u.is_object()).map(responses_usage),
+    });
+    if incomplete {
+        response["incomplete_details"] = json!({"reason": "max_output_tokens"});
+    }
+    response
+}
+
+/// Responses API response for a chat completion
+pub fn chat_to_responses_response(body: &Value, model: &str) -> Value {
+    let message = body.pointer("/choices/0/message");
+    let mut output = Vec::new();
+    if let Some(text) = message
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_str())
+        .filter(|t| !t.is_empty())
+    {
+        output.push(message_item(&item_id("msg"), text, "completed"));
+    }
+    for call in message
+        .and_then(|m| m.get("tool_calls"))
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let call_id = call.get("id").and_then(|i| i.as_str()).unwrap_or_default();
+        output.push(function_call_item(
+            &item_id("fc"),
+            call_id,
+            call.pointer("/function/name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default(),
+            call.pointer("/function/arguments")
+                .and_then(|a| a.as_str())
+                .unwrap_or("{}"),
+            "completed",
+        ));
+    }
+
+    response_object(
+        &response_id(),
+        model,
+        body.get("created").cloned().unwrap_or(Value::Null),
+        output,
+        body.pointer("/choices/0/finish_reason")
+            .and_then(|r| r.as_str()),
+        body.get("usage"),
+    )
+}
+
+/// Function call being streamed
+#[derive(Debug)]
+struct StreamedCall {
+    item_id: String,
+    call_id: String,
+    name: String,
+    arguments: String,
+    output_index: usize,
+}
+
+/// Converts chat completion chunks into Responses stream events
+#[derive(Debug)]
+pub struct ResponsesStreamTranslator {
+    parser: SseParser,
+    id: String,
+    model: String,
+    created_at: Value,
+    started: bool,
+    sequence: u64,
+    next_output_index: usize,
+    /// Item ID, output index and text of the message item, once opened
+    message: Option<(String, usize, String)>,
+    /// Calls keyed by their index in the upstream `tool_calls` array
+    calls: Vec<(u64, StreamedCall)>,
+    finish_reason: Option<String>,
+    usage: Option<Value>,
+    finished: bool,
+}
+
+impl ResponsesStreamTranslator {
+    pub fn new(model: &str) -> Self {
+        Self {
+            parser: SseParser::default(),
+            id: response_id(),
+            model: model.to_string(),
+            created_at: Value::Null,
+            started: false,
+            sequence: 0,
+            next_output_index: 0,
+            message: None,
+            calls: Vec::new(),
+            finish_reason: None,
+            usage: None,
+            finished: false,
+        }
+    }
+
+    fn emit(&mut self, out: &mut String, event: &str, mut data: Value) {
+        data["type"] = json!(event);
+        data["sequence_number"] = json!(self.sequence);
+        self.sequence += 1;
+        out.push_str(&sse_event(event, &data));
+    }
+
+    fn response(&self, output: Vec<Value>, status_done: bool) -> Value {
+        let mut response = response_object(
+            &self.id,
+            &self.model,
+            self.created_at.clone(),
+            output,
+            self.finish_reason.as_deref(),
+            self.usage.as_ref(),
+        );
+        if !status_done {
+            response["status"] = json!("in_progress");
+        }
+        response
+    }
+
+    fn push_text(&mut self, text: &str, out: &mut String) {
+        if self.message.is_none() {
+            let id = item_id("msg");
+            let output_index = self.next_output_index;
+            self.next_output_index += 1;
+            let mut item = message_item(&id, "", "in_progress");
+            item["content"] = json!([]);
+            self.emit(
+                out,
+                "response.output_item.added",
+                json!({"output_index": output_index, "item": item}),
+            );
+            self.emit(
+                out,
+                "response.content_part.added",
+                json!({
+                    "item_id": id,
+                    "output_index": output_index,
+                    "content_index": 0,
+                    "part": {"type": "output_text", "text": "", "annotations": []},
+                }),
+            );
+            self.message = Some((id, output_index, String::new()));
+        }
+        let (id, output_index) = match &mut self.message {
+            Some((id, output_index, buffer)) => {
+                buffer.push_str(text);
+                (id.clone(), *output_index)
+            }
+            None => return,
+        };
+        self.emit(
+            out,
+            "response.output_text.delta",
+            json!({
+                "item_id": id,
+                "output_index": output_index,
+                "content_index": 0,
+                "delta": text,
+            }),
+        );
+    }
+
+    fn push_call(&mut self, call: &Value, out: &mut String) {
+        let index = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+        if !self.calls.iter().any(|(i, _)| *i == index) {
+            let streamed = StreamedCall {
+                item_id: item_id("fc"),
+                call_id: call
+                    .get("id")
+                    .and_then(|i| i.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                name: call
+                    .pointer("/function/name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                arguments: String::new(),
+                output_index: self.next_output_index,
+            };
+            self.next_output_index += 1;
+            let item = function_call_item(
+                &streamed.item_id,
+                &streamed.call_id,
+                &streamed.name,
+                "",
+                "in_progress",
+            );
+            self.emit(
+                out,
+                "response.output_item.added",
+                json!({"output_index": streamed.output_index, "item": item}),
+            );
+            self.calls.push((index, streamed));
+        }
+
+        let Some(arguments) = call
+            .pointer("/function/arguments")
+            .and_then(|a| a.as_str())
+            .filter(|a| !a.is_empty())
+        else {
+            return;
+        };
+        let Some((_, streamed)) = self.calls.iter_mut().find(|(i, _)| *i == index) else {
+            return;
+        };
+        streamed.arguments.push_str(arguments);
+        let data = json!({
+            "item_id": streamed.item_id,
+            "output_index": streamed.output_index,
+            "delta": arguments,
+        });
+        self.emit(out, "response.function_call_arguments.delta", data);
+    }
+
+    fn push_chunk(&mut self, chunk: &Value, out: &mut String) {
+        if !self.started {
+            self.started = true;
+            self.created_at = chunk.get("created").cloned().unwrap_or(Value::Null);
+            let response = self.response(Vec::new(), false);
+            self.emit(out, "response.created", json!({ "response": response }));
+        }
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            self.usage = Some(usage.clone());
+        }
+        let Some(choice) = chunk.pointer("/choices/0") else {
+            return;
+        };
+        if let Some(text) = choice
+            .pointer("/delta/content")
+            .and_then(|c| c.as_str())
+            .filter(|t| !t.is_empty())
+        {
+            self.push_text(text, out);
+        }
+        for call in choice
+            .pointer("/delta/tool_calls")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            self.push_call(call, out);
+        }
+        if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+        }
+    }
+
+    /// Close the open items and complete the response
+    fn complete(&mut self, out: &mut String) {
+        if self.finished || !self.started {
+            return;
+        }
+        self.finished = true;
+
+        let mut output: Vec<(usize, Value)> = Vec::new();
+        if let Some((id, output_index, text)) = self.message.take() {
+            let location = json!({"item_id": id, "output_index": output_index, "content_index": 0});
+            let mut text_done = location.clone();
+            text_done["text"] = json!(text);
+            self.emit(out, "response.output_text.done", text_done);
+            let mut part_done = location;
+            part_done["part"] = json!({"type": "output_text", "text": text, "annotations": []});
+            self.emit(out, "response.content_part.done", part_done);
+            let item = message_item(&id, &text, "completed");
+            self.emit(
+                out,
+                "response.output_item.done",
+                json!({"output_index": output_index, "item": item}),
+            );
+            output.push((output_index, item));
+        }
+        for (_, call) in std::mem::take(&mut self.calls) {
+            self.emit(
+                out,
+                "response.function_call_arguments.done",
+                json!({
+                    "item_id": call.item_id,
+                    "output_index": call.output_index,
+                    "arguments": call.arguments,
+                }),
+            );
+            let item = function_call_item(
+                &call.item_id,
+                &call.call_id,
+                &call.name,
+                &call.arguments,
+                "completed",
+            );
+            self.emit(
+                out,
+                "response.output_item.done",
+                json!({"output_index": call.output_index, "item": item}),
+            );
+            output.push((call.output_index, item));
+        }
+
+        output.sort_by_key(|(index, _)| *index);
+        let response = self.response(output.into_iter().map(|(_, item)| item).collect(), true);
+        let event = if response["status"] == "incomplete" {
+            "response.incomplete"
+        } else {
+            "response.completed"
+        };
+        self.emit(out, event, json!({ "response": response }));
+    }
+}
+
+impl StreamTranslator for ResponsesStreamTranslator {
+    fn push(&mut self, chunk: &[u8]) -> String {
+        let mut out = String::new();
+        for payload in self.parser.push(chunk) {
+            if payload == "[DONE]" {
+                self.complete(&mut out);
+                continue;
+            }
+            if let Ok(chunk) = serde_json::from_str::<Value>(&payload) {
+                self.push_chunk(&chunk, &mut out);
+            }
+        }
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        let mut out = String::new();
+        self.complete(&mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_translation() {
+        let request = json!({
+            "model": "gpt-4.1",
+            "instructions": "Be brief.",
+            "input": [
+                {"role": "user", "content": [
+                    {"type": "input_text", "text": "What's in this image?"},
+                    {"type": "input_image", "image_url": "https://example.com/a.png"},
+                ]},
+                {"type": "function_call", "call_id": "call_1", "name": "lookup", "arguments": "{}"},
+                {"type": "function_call_output", "call_id": "call_1", "output": "a cat"},
+            ],
+            "tools": [
+                {"type": "function", "name": "lookup", "parameters": {"type": "object"}},
+                {"type": "web_search"},
+            ],
+            "tool_choice": {"type": "function", "name": "lookup"},
+            "max_output_tokens": 100,
+            "text": {"format": {"type": "json_schema", "name": "answer", "schema": {"type": "object"}}},
+        });
+        let chat = responses_to_chat_request(&request).unwrap();
+        let messages = chat["messages"].as_array().unwrap();
+        assert_eq!(
+            messages[0],
+            json!({"role": "system", "content": "Be brief."})
+        );
+        assert_eq!(
+            messages[1]["content"][1]["image_url"]["url"],
+            "https://example.com/a.png"
+        );
+        assert_eq!(messages[2]["tool_calls"][0]["function"]["name"], "lookup");
+        assert_eq!(messages[3]["role"], "tool");
+        assert_eq!(chat["tools"].as_array().unwrap().len(), 1);
+        assert_eq!(chat["tool_choice"]["function"]["name"], "lookup");
+        assert_eq!(chat["max_tokens"], 100);
+        assert_eq!(chat["response_format"]["json_schema"]["name"], "answer");
+
+        assert!(responses_to_chat_request(
+            &json!({"input": "hi", "previous_response_id": "resp_1"})
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_response_translation() {
+        let response = chat_to_responses_response(
+            &json!({
+                "created": 1,
+                "choices": [{
+                    "message": {
+                        "content": "Let me check.",
+                        "tool_calls": [{"id": "call_1", "function": {"name": "lookup", "arguments": "{\"q\":1}"}}],
+                    },
+                    "finish_reason": "tool_calls",
+                }],
+                "usage": {"prompt_tokens": 10, "completion_tokens": 5},
+            }),
+            "gpt-4.1",
+        );
+        assert_eq!(response["status"], "completed");
+        assert_eq!(response["output"][0]["content"][0]["text"], "Let me check.");
+        assert_eq!(response["output"][1]["call_id"], "call_1");
+        assert_eq!(response["usage"]["total_tokens"], 15);
+
+        let mut translator = ResponsesStreamTranslator::new("gpt-4.1");
+        let mut out = translator.push(
+            b"data: {\"created\":1,\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n\
+              data: {\"choices\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+        );
+        out.push_str(&translator.push(b"data: [DONE]\n\n"));
+        out.push_str(&translator.finish());
+        assert!(out.starts_with("event: response.created"));
+        assert!(out.contains("event: response.output_text.delta"));
+        assert!(out.contains("\"text\":\"Hello\""));
+        assert_eq!(out.matches("event: response.completed").count(), 1);
+    }
+}
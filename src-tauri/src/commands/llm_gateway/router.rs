@@ -0,0 +1,1002 @@
+//! Model Routing - Decides which provider/model serves an incoming request
+
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::pricing;
+use super::rules::TimeWindow;
+use super::vision::VISION_CAPABILITY;
+use super::{
+    AbTest, GatewaySettings, LLMProvider, ModelAlias, ModelConfig, ProviderConfig,
+    SystemPromptRule, TransformRule,
+};
+
+/// Provider and upstream model selected for a request
+#[derive(Debug, Clone)]
+pub struct RouteTarget {
+    /// Provider configuration to dispatch to
+    pub provider: ProviderConfig,
+    /// Model ID as understood by the provider
+    pub model: String,
+    /// A/B test arm this request was assigned to
+    pub ab: Option<AbAssignment>,
+}
+
+/// Experiment and arm (`control` or `variant`) a request was assigned to
+#[derive(Debug, Clone, PartialEq)]
+pub struct AbAssignment {
+    pub experiment: String,
+    pub arm: String,
+}
+
+/// Default model of a provider: the one flagged `is_default`, else the first listed
+pub fn default_model(provider: &ProviderConfig) -> Option<&str> {
+    provider
+        .models
+        .iter()
+        .find(|m| m.is_default)
+        .or_else(|| provider.models.first())
+        .map(|m| m.id.as_str())
+}
+
+/// Enabled providers ordered by priority (lower = higher priority)
+pub fn enabled_providers(settings: &GatewaySettings) -> Vec<&ProviderConfig> {
+    let mut providers: Vec<&ProviderConfig> =
+        settings.providers.iter().filter(|p| p.enabled).collect();
+    providers.sort_by_key(|p| p.priority);
+    providers
+}
+
+/// Header pinning a request to one provider; also set from a path prefix like `/deepseek/v1/...`
+pub const PROVIDER_HEADER: &str = "x-doggy-provider";
+
+/// Header naming the model to use in place of the request body's
+pub const MODEL_HEADER: &str = "x-doggy-model";
+
+/// Header choosing a routing strategy for the request
+pub const ROUTING_HEADER: &str = "x-doggy-routing";
+
+/// How a request without an explicit model override picks its route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingStrategy {
+    /// Cheapest priced model
+    Cost,
+    /// A model flagged `fast` on the provider with the lowest recent latency
+    Fast,
+    /// Priciest model, as price tracks capability
+    Quality,
+}
+
+impl RoutingStrategy {
+    /// Parse a header value, ignoring unknown values
+    pub fn from_header(value: Option<&str>) -> Option<Self> {
+        match value?.trim().to_ascii_lowercase().as_str() {
+            "cost" | "cheap" => Some(Self::Cost),
+            "fast" | "latency" => Some(Self::Fast),
+            "quality" | "best" => Some(Self::Quality),
+            _ => None,
+        }
+    }
+}
+
+/// Routing directives a client sent with one request
+#[derive(Debug, Clone, Default)]
+pub struct RouteOverrides {
+    /// Model to route instead of the body's `model`
+    pub model: Option<String>,
+    pub strategy: Option<RoutingStrategy>,
+    /// API key the client authenticated to the gateway with, for routing rules
+    pub source_key: Option<String>,
+}
+
+/// Whether a model serves chat, as opposed to images, transcription or speech
+fn is_chat_model(model: &ModelConfig) -> bool {
+    model.image_price.is_none() && model.audio_price.is_none() && model.speech_price.is_none()
+}
+
+/// Route chosen by `strategy` among the enabled providers' chat models.
+///
+/// `latency_ms` reports a provider's last observed latency; providers without one are
+/// tried after those with one.
+pub fn strategy_route(
+    settings: &GatewaySettings,
+    strategy: RoutingStrategy,
+    latency_ms: impl Fn(&ProviderConfig) -> Option<u64>,
+    now: DateTime<Utc>,
+) -> Option<RouteTarget> {
+    let candidates: Vec<(&ProviderConfig, &ModelConfig)> = enabled_providers(settings)
+        .into_iter()
+        .flat_map(|p| {
+            p.models
+                .iter()
+                .filter(|m| is_chat_model(m))
+                .map(move |m| (p, m))
+        })
+        .collect();
+    let price = |m: &ModelConfig| {
+        (m.input_price + m.output_price) * (1.0 + m.fee_percent.unwrap_or(0.0) / 100.0)
+    };
+    // Costs follow the time of day, while list prices stand in for capability
+    let cost = |(p, m): &(&ProviderConfig, &ModelConfig)| price(&pricing::priced_at(p, m, now));
+    let priced = candidates.iter().filter(|(_, m)| !m.pricing_unknown);
+
+    let (provider, model) = match strategy {
+        RoutingStrategy::Cost => priced.min_by(|a, b| cost(a).total_cmp(&cost(b))),
+        RoutingStrategy::Quality => priced
+            .rev()
+            .max_by(|a, b| price(a.1).total_cmp(&price(b.1))),
+        RoutingStrategy::Fast => {
+            let is_fast = |m: &ModelConfig| m.capabilities.iter().any(|c| c == "fast");
+            let fast: Vec<_> = candidates.iter().filter(|(_, m)| is_fast(m)).collect();
+            let pool = if fast.is_empty() {
+                candidates.iter().collect()
+            } else {
+                fast
+            };
+            pool.into_iter()
+                .min_by_key(|(p, _)| latency_ms(p).unwrap_or(u64::MAX))
+        }
+    }?;
+    Some(RouteTarget {
+        provider: (*provider).clone(),
+        model: model.id.clone(),
+        ab: None,
+    })
+}
+
+/// Enabled provider named by `key`, matching its identifier or display name case-insensitively
+pub fn find_provider<'a>(settings: &'a GatewaySettings, key: &str) -> Option<&'a ProviderConfig> {
+    enabled_providers(settings).into_iter().find(|p| {
+        p.provider.to_string().eq_ignore_ascii_case(key) || p.name.eq_ignore_ascii_case(key)
+    })
+}
+
+/// Split a leading provider segment off `path`, as in `/openai/v1/messages`, returning the
+/// segment and the remaining path
+pub fn provider_prefix<'a>(
+    settings: &GatewaySettings,
+    path: &'a str,
+) -> Option<(&'a str, &'a str)> {
+    let (segment, _) = path.strip_prefix('/')?.split_once('/')?;
+    find_provider(settings, segment)?;
+    Some((segment, &path[segment.len() + 1..]))
+}
+
+/// Route for a `provider/model` directive such as `deepseek/deepseek-coder`. Model IDs
+/// an enabled provider lists verbatim, like OpenRouter's `openai/gpt-4o`, are not directives.
+pub fn prefixed_route(settings: &GatewaySettings, model: &str) -> Option<RouteTarget> {
+    let (key, rest) = model.split_once('/')?;
+    if rest.is_empty()
+        || enabled_providers(settings)
+            .iter()
+            .any(|p| p.models.iter().any(|m| m.id == model))
+    {
+        return None;
+    }
+    let provider = find_provider(settings, key)?;
+    Some(RouteTarget {
+        provider: provider.clone(),
+        model: rest.to_string(),
+        ab: None,
+    })
+}
+
+/// Names of enabled providers whose monthly spend cap `month_spend` (keyed by provider
+/// name) has reached
+pub fn capped_providers(
+    settings: &GatewaySettings,
+    month_spend: &HashMap<String, f64>,
+) -> Vec<String> {
+    enabled_providers(settings)
+        .into_iter()
+        .filter(|p| {
+            p.monthly_spend_cap_usd
+                .is_some_and(|cap| month_spend.get(&p.name).is_some_and(|spent| *spent >= cap))
+        })
+        .map(|p| p.name.clone())
+        .collect()
+}
+
+/// Scheduled downtime of a provider, in local time
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MaintenanceWindow {
+    #[serde(flatten)]
+    pub hours: TimeWindow,
+    /// Days the window starts on; empty means every day
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+}
+
+impl MaintenanceWindow {
+    pub fn contains(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+        if !self.hours.contains(time) {
+            return false;
+        }
+        // The early hours of a window running past midnight belong to the day before
+        let day = if self.hours.start > self.hours.end && time < self.hours.end {
+            now.weekday().pred()
+        } else {
+            now.weekday()
+        };
+        self.days.is_empty() || self.days.contains(&day)
+    }
+}
+
+/// Enabled providers inside one of their maintenance windows at local time `now`
+pub fn providers_in_maintenance(settings: &GatewaySettings, now: NaiveDateTime) -> Vec<String> {
+    enabled_providers(settings)
+        .into_iter()
+        .filter(|p| p.maintenance_windows.iter().any(|w| w.contains(now)))
+        .map(|p| p.name.clone())
+        .collect()
+}
+
+/// Settings narrowed to the provider named by `key`, so that routing, aliases and
+/// fallbacks cannot leave it
+pub fn pin_provider(settings: &GatewaySettings, key: &str) -> Option<GatewaySettings> {
+    let pinned = find_provider(settings, key)?;
+    let (provider, name) = (pinned.provider.clone(), pinned.name.clone());
+
+    let mut settings = settings.clone();
+    for p in &mut settings.providers {
+        p.enabled &= p.provider == provider && p.name == name;
+    }
+    settings
+        .model_aliases
+        .retain(|_, alias| alias.provider == provider);
+    settings.default_provider = provider;
+    Some(settings)
+}
+
+/// Uniform roll in `0..100` for traffic splitting
+pub fn roll_percent() -> u8 {
+    (uuid::Uuid::new_v4().as_u128() % 100) as u8
+}
+
+/// Whether `model` matches `pattern`, where a trailing `*` matches by prefix.
+/// Returns the matched prefix length so callers can prefer the most specific pattern.
+fn pattern_match(pattern: &str, model: &str) -> Option<usize> {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => model.starts_with(prefix).then_some(prefix.len()),
+        None => (pattern == model).then_some(usize::MAX),
+    }
+}
+
+/// Look up the alias for a model name.
+///
+/// Exact keys win over wildcard keys; among wildcards (`claude-3-5-sonnet*`) the longest
+/// matching prefix wins.
+pub fn lookup_alias<'a>(settings: &'a GatewaySettings, model: &str) -> Option<&'a ModelAlias> {
+    if let Some(alias) = settings.model_aliases.get(model) {
+        return Some(alias);
+    }
+
+    settings
+        .model_aliases
+        .iter()
+        .filter(|(key, _)| key.ends_with('*'))
+        .filter_map(|(key, alias)| Some((pattern_match(key, model)?, alias)))
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, alias)| alias)
+}
+
+/// Route for the A/B test matching `model`, given a roll in `0..100`
+pub fn ab_route(settings: &GatewaySettings, model: &str, roll: u8) -> Option<RouteTarget> {
+    let test: &AbTest = settings
+        .ab_tests
+        .iter()
+        .filter(|t| t.enabled)
+        .filter_map(|t| Some((pattern_match(&t.match_model, model)?, t)))
+        .max_by_key(|(len, _)| *len)?
+        .1;
+
+    let (arm, target) = if roll < test.variant_percent {
+        ("variant", &test.variant)
+    } else {
+        ("control", &test.control)
+    };
+    let provider = enabled_providers(settings)
+        .into_iter()
+        .find(|p| p.provider == target.provider)?;
+
+    Some(RouteTarget {
+        provider: provider.clone(),
+        model: target.model.clone(),
+        ab: Some(AbAssignment {
+            experiment: test.name.clone(),
+            arm: arm.to_string(),
+        }),
+    })
+}
+
+/// Shadow route for a request given a roll in `0..100`, if mirroring applies
+pub fn shadow_route(settings: &GatewaySettings, roll: u8) -> Option<RouteTarget> {
+    let shadow = settings
+        .shadow
+        .as_ref()
+        .filter(|s| s.enabled && roll < s.sample_percent)?;
+    let provider = settings
+        .providers
+        .iter()
+        .find(|p| p.provider == shadow.target.provider)?;
+
+    Some(RouteTarget {
+        provider: provider.clone(),
+        model: shadow.target.model.clone(),
+        ab: Some(AbAssignment {
+            experiment: shadow.name.clone(),
+            arm: "shadow".to_string(),
+        }),
+    })
+}
+
+/// Whether a rule's provider and model filters match a route
+fn rule_matches(provider: Option<&LLMProvider>, match_model: &str, route: &RouteTarget) -> bool {
+    provider.is_none_or(|provider| *provider == route.provider.provider)
+        && pattern_match(match_model, &route.model).is_some()
+}
+
+/// Enabled system prompt rules matching a route, in configuration order
+pub fn system_prompt_rules(
+    settings: &GatewaySettings,
+    route: &RouteTarget,
+) -> Vec<SystemPromptRule> {
+    settings
+        .system_prompt_rules
+        .iter()
+        .filter(|rule| {
+            rule.enabled && rule_matches(rule.provider.as_ref(), &rule.match_model, route)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Enabled transformation rules matching a route, in configuration order
+pub fn transform_rules(settings: &GatewaySettings, route: &RouteTarget) -> Vec<TransformRule> {
+    settings
+        .transform_rules
+        .iter()
+        .filter(|rule| {
+            rule.enabled && rule_matches(rule.provider.as_ref(), &rule.match_model, route)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Route of the model summarizing truncated turns, if summarization is enabled. The
+/// provider only has to be configured, so a local model can be kept out of routing.
+pub fn summarizer_route(settings: &GatewaySettings) -> Option<RouteTarget> {
+    let summarization = settings.summarization.as_ref().filter(|s| s.enabled)?;
+    let provider = settings
+        .providers
+        .iter()
+        .find(|p| p.provider == summarization.model.provider)?;
+
+    Some(RouteTarget {
+        provider: provider.clone(),
+        model: summarization.model.model.clone(),
+        ab: None,
+    })
+}
+
+/// Route of the model repairing invalid JSON responses, if repair is enabled. The
+/// provider only has to be configured.
+pub fn json_repair_route(settings: &GatewaySettings) -> Option<RouteTarget> {
+    let repair = settings.json_repair.as_ref().filter(|r| r.enabled)?;
+    let provider = settings
+        .providers
+        .iter()
+        .find(|p| p.provider == repair.model.provider)?;
+
+    Some(RouteTarget {
+        provider: provider.clone(),
+        model: repair.model.model.clone(),
+        ab: None,
+    })
+}
+
+/// Route of the moderation model, if prompt moderation is enabled. The provider only has
+/// to be configured.
+pub fn moderation_route(settings: &GatewaySettings) -> Option<RouteTarget> {
+    let moderation = settings.moderation.as_ref().filter(|m| m.enabled)?;
+    let provider = settings
+        .providers
+        .iter()
+        .find(|p| p.provider == moderation.model.provider)?;
+
+    Some(RouteTarget {
+        provider: provider.clone(),
+        model: moderation.model.model.clone(),
+        ab: None,
+    })
+}
+
+/// Secondary route to race against `primary` when speculative dispatch applies
+pub fn speculative_route(
+    settings: &GatewaySettings,
+    requested_model: &str,
+    primary: &RouteTarget,
+) -> Option<RouteTarget> {
+    let config = settings.speculative.as_ref().filter(|s| s.enabled)?;
+    if !config
+        .match_models
+        .iter()
+        .any(|pattern| pattern_match(pattern, requested_model).is_some())
+    {
+        return None;
+    }
+    // Racing a route against itself only doubles the cost
+    if primary.provider.provider == config.secondary.provider
+        && primary.model == config.secondary.model
+    {
+        return None;
+    }
+
+    let provider = enabled_providers(settings)
+        .into_iter()
+        .find(|p| p.provider == config.secondary.provider)?;
+    Some(RouteTarget {
+        provider: provider.clone(),
+        model: config.secondary.model.clone(),
+        ab: primary.ab.clone(),
+    })
+}
+
+/// Body field listing the models to fall back to, in order, as OpenRouter accepts it
+pub const FALLBACK_MODELS_FIELD: &str = "models";
+
+/// Models a request body lists in its fallback field, in order
+pub fn requested_fallbacks(request: &Value) -> Vec<&str> {
+    request
+        .get(FALLBACK_MODELS_FIELD)
+        .and_then(|m| m.as_array())
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| m.as_str())
+                .filter(|m| !m.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Routes for a client's fallback list, in its order, skipping `primary` and duplicates.
+/// Used instead of [`fallback_routes`] when the client sends one.
+pub fn listed_fallback_routes(
+    settings: &GatewaySettings,
+    models: &[&str],
+    primary: &RouteTarget,
+) -> Vec<RouteTarget> {
+    let same = |a: &RouteTarget, b: &RouteTarget| {
+        a.provider.provider == b.provider.provider
+            && a.provider.name == b.provider.name
+            && a.model == b.model
+    };
+    let mut routes: Vec<RouteTarget> = Vec::new();
+    for model in models {
+        let Some(route) = resolve_route(settings, Some(model)) else {
+            continue;
+        };
+        if !same(&route, primary) && !routes.iter().any(|r| same(r, &route)) {
+            routes.push(route);
+        }
+    }
+    routes
+}
+
+/// Resolve the route for a requested model.
+///
+/// A `provider/model` directive wins outright. Otherwise A/B tests are applied first, then
+/// aliases, then an enabled provider that lists the model wins; failing that the request
+/// falls back to the default provider's default model, then to the highest-priority
+/// enabled provider.
+pub fn resolve_route(
+    settings: &GatewaySettings,
+    requested_model: Option<&str>,
+) -> Option<RouteTarget> {
+    let providers = enabled_providers(settings);
+
+    if let Some(model) = requested_model.filter(|m| !m.is_empty()) {
+        if let Some(route) = prefixed_route(settings, model) {
+            return Some(route);
+        }
+
+        if let Some(route) = ab_route(settings, model, roll_percent()) {
+            return Some(route);
+        }
+
+        if let Some(alias) = lookup_alias(settings, model) {
+            match providers.iter().find(|p| p.provider == alias.provider) {
+                Some(provider) => {
+                    return Some(RouteTarget {
+                        provider: (*provider).clone(),
+                        model: alias.model.clone(),
+                        ab: None,
+                    })
+                }
+                None => log::warn!(
+                    "Alias for '{}' targets disabled provider {}, ignoring",
+                    model,
+                    alias.provider
+                ),
+            }
+        }
+
+        if let Some(provider) = providers
+            .iter()
+            .find(|p| p.models.iter().any(|m| m.id == model))
+        {
+            return Some(RouteTarget {
+                provider: (*provider).clone(),
+                model: model.to_string(),
+                ab: None,
+            });
+        }
+    }
+
+    let fallback = providers
+        .iter()
+        .find(|p| p.provider == settings.default_provider)
+        .or_else(|| providers.first())?;
+
+    Some(RouteTarget {
+        provider: (*fallback).clone(),
+        model: default_model(fallback)?.to_string(),
+        ab: None,
+    })
+}
+
+/// Resolve the route for an embedding or image generation request.
+///
+/// A `provider/model` directive wins, then aliases apply, then an enabled provider that
+/// lists the model wins; otherwise the model is sent as requested to the default provider,
+/// since such models are rarely listed.
+pub fn direct_route(settings: &GatewaySettings, requested_model: &str) -> Option<RouteTarget> {
+    if let Some(route) = prefixed_route(settings, requested_model) {
+        return Some(route);
+    }
+    let providers = enabled_providers(settings);
+    let route = |provider: &ProviderConfig, model: &str| RouteTarget {
+        provider: provider.clone(),
+        model: model.to_string(),
+        ab: None,
+    };
+
+    if let Some(alias) = lookup_alias(settings, requested_model) {
+        if let Some(provider) = providers.iter().find(|p| p.provider == alias.provider) {
+            return Some(route(provider, &alias.model));
+        }
+    }
+    if let Some(provider) = providers
+        .iter()
+        .find(|p| p.models.iter().any(|m| m.id == requested_model))
+    {
+        return Some(route(provider, requested_model));
+    }
+    providers
+        .iter()
+        .find(|p| p.provider == settings.default_provider)
+        .or_else(|| providers.first())
+        .map(|provider| route(provider, requested_model))
+}
+
+/// Resolve the route for a text-to-speech request, with the voice to use when the
+/// requested voice is an alias for an enabled provider's voice
+pub fn speech_route(
+    settings: &GatewaySettings,
+    requested_model: &str,
+    voice: &str,
+) -> Option<(RouteTarget, Option<String>)> {
+    if let Some(alias) = settings.voice_aliases.get(voice) {
+        match enabled_providers(settings)
+            .into_iter()
+            .find(|p| p.provider == alias.provider)
+        {
+            Some(provider) => {
+                let route = RouteTarget {
+                    provider: provider.clone(),
+                    model: alias.model.clone(),
+                    ab: None,
+                };
+                return Some((route, Some(alias.voice.clone())));
+            }
+            None => log::warn!(
+                "Voice alias '{}' targets disabled provider {}, ignoring",
+                voice,
+                alias.provider
+            ),
+        }
+    }
+    direct_route(settings, requested_model).map(|route| (route, None))
+}
+
+/// Whether a route's model accepts images. Models that aren't listed by their provider,
+/// or are listed without any capabilities, are assumed to.
+pub fn supports_vision(provider: &ProviderConfig, model: &str) -> bool {
+    provider
+        .models
+        .iter()
+        .find(|m| m.id == model)
+        .is_none_or(|m| {
+            m.capabilities.is_empty() || m.capabilities.iter().any(|c| c == VISION_CAPABILITY)
+        })
+}
+
+/// Replacement route for an image request whose model can't see images: the same
+/// provider's first vision model, else the first vision model of any enabled provider.
+pub fn vision_route(settings: &GatewaySettings, current: &RouteTarget) -> Option<RouteTarget> {
+    if supports_vision(&current.provider, &current.model) {
+        return None;
+    }
+
+    let is_vision = |m: &&ModelConfig| m.capabilities.iter().any(|c| c == VISION_CAPABILITY);
+    let same_provider = current
+        .provider
+        .models
+        .iter()
+        .find(is_vision)
+        .map(|m| (current.provider.clone(), m.id.clone()));
+    let (provider, model) = same_provider.or_else(|| {
+        enabled_providers(settings).into_iter().find_map(|p| {
+            let model = p.models.iter().find(is_vision)?;
+            Some((p.clone(), model.id.clone()))
+        })
+    })?;
+
+    Some(RouteTarget {
+        provider,
+        model,
+        ab: current.ab.clone(),
+    })
+}
+
+/// Alternative routes for when `primary` can't take a request: the default model of
+/// every other enabled provider, in priority order.
+pub fn fallback_routes(settings: &GatewaySettings, primary: &RouteTarget) -> Vec<RouteTarget> {
+    enabled_providers(settings)
+        .into_iter()
+        .filter(|p| p.provider != primary.provider.provider || p.name != primary.provider.name)
+        .filter_map(|p| {
+            Some(RouteTarget {
+                provider: p.clone(),
+                model: default_model(p)?.to_string(),
+                ab: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::llm_gateway::{LLMProvider, ShadowConfig, SpeculativeConfig, VoiceAlias};
+
+    fn settings_with_aliases() -> GatewaySettings {
+        let mut settings = GatewaySettings::default();
+        for provider in settings.providers.iter_mut() {
+            provider.enabled = matches!(
+                provider.provider,
+                LLMProvider::OpenAI | LLMProvider::DeepSeek
+            );
+        }
+        settings.model_aliases.insert(
+            "claude-3-5-sonnet*".to_string(),
+            ModelAlias {
+                provider: LLMProvider::DeepSeek,
+                model: "deepseek-chat".to_string(),
+            },
+        );
+        settings.model_aliases.insert(
+            "claude-3-5-sonnet-20241022".to_string(),
+            ModelAlias {
+                provider: LLMProvider::OpenAI,
+                model: "gpt-4o".to_string(),
+            },
+        );
+        settings
+    }
+
+    #[test]
+    fn test_alias_resolution() {
+        let settings = settings_with_aliases();
+
+        // Exact alias beats the wildcard
+        let route = resolve_route(&settings, Some("claude-3-5-sonnet-20241022")).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::OpenAI);
+        assert_eq!(route.model, "gpt-4o");
+
+        // Wildcard alias
+        let route = resolve_route(&settings, Some("claude-3-5-sonnet-latest")).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::DeepSeek);
+        assert_eq!(route.model, "deepseek-chat");
+    }
+
+    #[test]
+    fn test_unknown_model_falls_back_to_default_provider() {
+        let settings = settings_with_aliases();
+
+        let route = resolve_route(&settings, Some("claude-3-haiku-20240307")).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::OpenAI);
+        assert_eq!(route.model, "gpt-4o");
+
+        // Known model IDs route to the provider that lists them
+        let route = resolve_route(&settings, Some("deepseek-reasoner")).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::DeepSeek);
+    }
+
+    #[test]
+    fn test_direct_route_keeps_unlisted_models() {
+        let mut settings = settings_with_aliases();
+        settings.model_aliases.insert(
+            "embed-small".to_string(),
+            ModelAlias {
+                provider: LLMProvider::DeepSeek,
+                model: "deepseek-embed".to_string(),
+            },
+        );
+
+        let route = direct_route(&settings, "text-embedding-3-small").unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::OpenAI);
+        assert_eq!(route.model, "text-embedding-3-small");
+
+        let route = direct_route(&settings, "embed-small").unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::DeepSeek);
+        assert_eq!(route.model, "deepseek-embed");
+    }
+
+    #[test]
+    fn test_provider_prefix() {
+        let settings = settings_with_aliases();
+
+        let (segment, rest) = provider_prefix(&settings, "/DeepSeek/v1/chat/completions").unwrap();
+        assert_eq!(segment, "DeepSeek");
+        assert_eq!(rest, "/v1/chat/completions");
+        assert!(provider_prefix(&settings, "/v1/chat/completions").is_none());
+        // Disabled providers are not matched
+        assert!(provider_prefix(&settings, "/anthropic/v1/messages").is_none());
+
+        // Neither the default provider nor an alias can leave the pinned one
+        let pinned = pin_provider(&settings, "deepseek").unwrap();
+        let route = resolve_route(&pinned, Some("claude-3-5-sonnet-20241022")).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::DeepSeek);
+        assert_eq!(route.model, "deepseek-chat");
+        let route = resolve_route(&pinned, Some("gpt-4o")).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::DeepSeek);
+        assert!(pin_provider(&settings, "anthropic").is_none());
+    }
+
+    #[test]
+    fn test_prefixed_route() {
+        let mut settings = settings_with_aliases();
+
+        let route = resolve_route(&settings, Some("deepseek/deepseek-coder")).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::DeepSeek);
+        assert_eq!(route.model, "deepseek-coder");
+        let route = direct_route(&settings, "openai/text-embedding-3-large").unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::OpenAI);
+        assert_eq!(route.model, "text-embedding-3-large");
+        // Unknown or disabled providers are not directives
+        assert!(prefixed_route(&settings, "ollama/llama3.2").is_none());
+        assert!(prefixed_route(&settings, "meta/llama-3").is_none());
+
+        // A model listed under its full ID is routed as a plain model
+        let openai = settings
+            .providers
+            .iter_mut()
+            .find(|p| p.provider == LLMProvider::OpenAI)
+            .unwrap();
+        openai.models[0].id = "deepseek/deepseek-chat".to_string();
+        assert!(prefixed_route(&settings, "deepseek/deepseek-chat").is_none());
+    }
+
+    #[test]
+    fn test_listed_fallback_routes() {
+        let settings = settings_with_aliases();
+        let request = serde_json::json!({
+            "models": ["gpt-4o", "deepseek/deepseek-reasoner", "", "deepseek-reasoner", 7]
+        });
+        let models = requested_fallbacks(&request);
+        assert_eq!(models.len(), 3);
+
+        let primary = resolve_route(&settings, Some(models[0])).unwrap();
+        let routes = listed_fallback_routes(&settings, &models, &primary);
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].provider.provider, LLMProvider::DeepSeek);
+        assert_eq!(routes[0].model, "deepseek-reasoner");
+    }
+
+    #[test]
+    fn test_capped_providers() {
+        let mut settings = settings_with_aliases();
+        // A second endpoint of the same type, with a cap of its own
+        let mut second = settings
+            .providers
+            .iter()
+            .find(|p| p.provider == LLMProvider::OpenAI)
+            .cloned()
+            .unwrap();
+        second.name = "OpenAI Team".to_string();
+        settings.providers.push(second);
+        for provider in settings.providers.iter_mut() {
+            provider.monthly_spend_cap_usd = Some(20.0);
+        }
+        let spend = HashMap::from([
+            ("OpenAI".to_string(), 20.0),
+            ("OpenAI Team".to_string(), 1.0),
+            ("DeepSeek".to_string(), 19.5),
+            // Disabled, so never routed to anyway
+            ("Anthropic".to_string(), 50.0),
+        ]);
+        assert_eq!(capped_providers(&settings, &spend), vec!["OpenAI"]);
+    }
+
+    #[test]
+    fn test_maintenance_windows() {
+        let mut settings = settings_with_aliases();
+        let time = |t: &str| chrono::NaiveTime::parse_from_str(t, "%H:%M").unwrap();
+        let openai = settings
+            .providers
+            .iter_mut()
+            .find(|p| p.provider == LLMProvider::OpenAI)
+            .unwrap();
+        openai.maintenance_windows = vec![MaintenanceWindow {
+            hours: TimeWindow {
+                start: time("23:00"),
+                end: time("02:00"),
+            },
+            days: vec![Weekday::Sun],
+        }];
+        let name = openai.name.clone();
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+
+        // 2024-06-02 is a Sunday
+        assert_eq!(
+            providers_in_maintenance(&settings, at("2024-06-02 23:30")),
+            vec![name.clone()]
+        );
+        assert_eq!(
+            providers_in_maintenance(&settings, at("2024-06-03 01:30")),
+            vec![name]
+        );
+        assert!(providers_in_maintenance(&settings, at("2024-06-03 23:30")).is_empty());
+        assert!(providers_in_maintenance(&settings, at("2024-06-02 12:00")).is_empty());
+    }
+
+    #[test]
+    fn test_strategy_route() {
+        let settings = settings_with_aliases();
+        assert_eq!(
+            RoutingStrategy::from_header(Some(" Fast")),
+            Some(RoutingStrategy::Fast)
+        );
+        assert_eq!(RoutingStrategy::from_header(Some("random")), None);
+
+        let route = strategy_route(&settings, RoutingStrategy::Cost, |_| None, Utc::now()).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::DeepSeek);
+        assert_eq!(route.model, "deepseek-chat");
+        let route =
+            strategy_route(&settings, RoutingStrategy::Quality, |_| None, Utc::now()).unwrap();
+        assert_eq!(route.model, "gpt-4-turbo");
+
+        // Only models flagged fast, unless no provider has one
+        let route = strategy_route(
+            &settings,
+            RoutingStrategy::Fast,
+            |p| (p.provider == LLMProvider::DeepSeek).then_some(800),
+            Utc::now(),
+        )
+        .unwrap();
+        assert_eq!(route.model, "gpt-4o-mini");
+        let pinned = pin_provider(&settings, "deepseek").unwrap();
+        let route = strategy_route(&pinned, RoutingStrategy::Fast, |_| None, Utc::now()).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::DeepSeek);
+    }
+
+    #[test]
+    fn test_speech_route_voice_alias() {
+        let mut settings = settings_with_aliases();
+        settings.voice_aliases.insert(
+            "narrator".to_string(),
+            VoiceAlias {
+                provider: LLMProvider::OpenAI,
+                model: "gpt-4o-mini-tts".to_string(),
+                voice: "onyx".to_string(),
+            },
+        );
+
+        let (route, voice) = speech_route(&settings, "tts-1", "narrator").unwrap();
+        assert_eq!(route.model, "gpt-4o-mini-tts");
+        assert_eq!(voice.as_deref(), Some("onyx"));
+
+        let (route, voice) = speech_route(&settings, "tts-1", "alloy").unwrap();
+        assert_eq!(route.model, "tts-1");
+        assert_eq!(voice, None);
+    }
+
+    #[test]
+    fn test_ab_split() {
+        let mut settings = settings_with_aliases();
+        settings.ab_tests.push(AbTest {
+            name: "sonnet-vs-deepseek".to_string(),
+            enabled: true,
+            match_model: "claude-3-5-sonnet*".to_string(),
+            control: ModelAlias {
+                provider: LLMProvider::OpenAI,
+                model: "gpt-4o".to_string(),
+            },
+            variant: ModelAlias {
+                provider: LLMProvider::DeepSeek,
+                model: "deepseek-chat".to_string(),
+            },
+            variant_percent: 20,
+        });
+
+        let variant = ab_route(&settings, "claude-3-5-sonnet-20241022", 19).unwrap();
+        assert_eq!(variant.model, "deepseek-chat");
+        assert_eq!(variant.ab.unwrap().arm, "variant");
+
+        let control = ab_route(&settings, "claude-3-5-sonnet-20241022", 20).unwrap();
+        assert_eq!(control.model, "gpt-4o");
+        assert_eq!(control.ab.unwrap().experiment, "sonnet-vs-deepseek");
+
+        assert!(ab_route(&settings, "claude-3-haiku-20240307", 0).is_none());
+    }
+
+    #[test]
+    fn test_speculative_route() {
+        let mut settings = settings_with_aliases();
+        settings.speculative = Some(SpeculativeConfig {
+            enabled: true,
+            match_models: vec!["claude-3-5-haiku*".to_string()],
+            secondary: ModelAlias {
+                provider: LLMProvider::DeepSeek,
+                model: "deepseek-chat".to_string(),
+            },
+        });
+
+        let primary = resolve_route(&settings, Some("claude-3-5-haiku-20241022")).unwrap();
+        let secondary = speculative_route(&settings, "claude-3-5-haiku-20241022", &primary);
+        assert_eq!(secondary.unwrap().model, "deepseek-chat");
+        assert!(speculative_route(&settings, "claude-3-5-sonnet-20241022", &primary).is_none());
+
+        // Never race a route against itself
+        let same = resolve_route(&settings, Some("deepseek-chat")).unwrap();
+        assert!(speculative_route(&settings, "claude-3-5-haiku-x", &same).is_none());
+    }
+
+    #[test]
+    fn test_shadow_sampling() {
+        let mut settings = settings_with_aliases();
+        settings.shadow = Some(ShadowConfig {
+            name: "try-qwen".to_string(),
+            enabled: true,
+            // Disabled providers can still be shadowed
+            target: ModelAlias {
+                provider: LLMProvider::Qwen,
+                model: "qwen-turbo".to_string(),
+            },
+            sample_percent: 10,
+        });
+
+        let shadow = shadow_route(&settings, 9).unwrap();
+        assert_eq!(shadow.provider.provider, LLMProvider::Qwen);
+        assert_eq!(shadow.ab.unwrap().arm, "shadow");
+        assert!(shadow_route(&settings, 10).is_none());
+    }
+
+    #[test]
+    fn test_vision_route() {
+        let settings = settings_with_aliases();
+
+        let text_only = resolve_route(&settings, Some("deepseek-chat")).unwrap();
+        let rerouted = vision_route(&settings, &text_only).unwrap();
+        assert_eq!(rerouted.provider.provider, LLMProvider::OpenAI);
+        assert_eq!(rerouted.model, "gpt-4o");
+
+        let capable = resolve_route(&settings, Some("gpt-4o-mini")).unwrap();
+        assert!(vision_route(&settings, &capable).is_none());
+    }
+}
@@ -0,0 +1,344 @@
+//! Routing Rules - Declarative routing decisions made before model resolution
+//!
+//! Rules are evaluated in configuration order against the request as the client sent it,
+//! and the first enabled rule whose conditions all hold decides the route and may set
+//! request parameters. Unset conditions match every request. Explicit client choices (a
+//! model header or a `provider/model` directive) bypass the rules.
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::limits;
+use super::router::{self, RouteTarget};
+use super::vision;
+use super::{GatewaySettings, LLMProvider};
+
+/// Local time of day range; wraps past midnight when `start` is after `end`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    /// Exclusive
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// What a request must look like for a rule to apply
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RuleConditions {
+    /// Requested model to match; a trailing `*` matches by prefix
+    pub match_model: Option<String>,
+    /// Estimated prompt tokens, inclusive
+    pub min_prompt_tokens: Option<u64>,
+    pub max_prompt_tokens: Option<u64>,
+    /// Whether the request must (or must not) offer tools
+    pub has_tools: Option<bool>,
+    /// Whether the request must (or must not) carry images
+    pub has_images: Option<bool>,
+    pub time_window: Option<TimeWindow>,
+    /// API keys clients authenticate to the gateway with; any key when empty
+    pub source_keys: Vec<String>,
+}
+
+/// Route and parameters a matching rule selects
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RuleAction {
+    /// Provider to route to; the model's provider when unset
+    pub provider: Option<LLMProvider>,
+    /// Model to route to; the provider's default model when unset
+    pub model: Option<String>,
+    /// Top-level request parameters to set; `null` removes the parameter
+    pub parameters: serde_json::Map<String, Value>,
+}
+
+/// Routing rule evaluated before the requested model is resolved
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoutingRule {
+    /// Name shown in logs and explanations
+    pub name: String,
+    /// Whether the rule is evaluated
+    pub enabled: bool,
+    #[serde(default)]
+    pub conditions: RuleConditions,
+    #[serde(default)]
+    pub action: RuleAction,
+}
+
+/// Request properties rules are matched against
+#[derive(Debug, Clone)]
+pub struct RuleInput<'a> {
+    pub model: &'a str,
+    pub prompt_tokens: u64,
+    pub has_tools: bool,
+    pub has_images: bool,
+    pub time: NaiveTime,
+    pub source_key: Option<&'a str>,
+}
+
+impl<'a> RuleInput<'a> {
+    pub fn from_request(request: &'a Value, source_key: Option<&'a str>, time: NaiveTime) -> Self {
+        let has_tools = ["tools", "functions"].iter().any(|field| {
+            request
+                .get(*field)
+                .and_then(|t| t.as_array())
+                .is_some_and(|t| !t.is_empty())
+        });
+        Self {
+            model: request
+                .get("model")
+                .and_then(|m| m.as_str())
+                .unwrap_or_default(),
+            prompt_tokens: limits::estimate_prompt_tokens(request),
+            has_tools,
+            has_images: vision::request_has_images(request),
+            time,
+            source_key,
+        }
+    }
+}
+
+/// Conditions of `conditions` that `input` fails, described for the user
+pub fn mismatches(conditions: &RuleConditions, input: &RuleInput) -> Vec<String> {
+    let mut failed = Vec::new();
+    if let Some(pattern) = &conditions.match_model {
+        let matches = match pattern.strip_suffix('*') {
+            Some(prefix) => input.model.starts_with(prefix),
+            None => input.model == pattern,
+        };
+        if !matches {
+            failed.push(format!(
+                "model '{}' doesn't match '{}'",
+                input.model, pattern
+            ));
+        }
+    }
+    if let Some(min) = conditions
+        .min_prompt_tokens
+        .filter(|m| input.prompt_tokens < *m)
+    {
+        failed.push(format!(
+            "{} prompt tokens is below {}",
+            input.prompt_tokens, min
+        ));
+    }
+    if let Some(max) = conditions
+        .max_prompt_tokens
+        .filter(|m| input.prompt_tokens > *m)
+    {
+        failed.push(format!(
+            "{} prompt tokens is above {}",
+            input.prompt_tokens, max
+        ));
+    }
+    if let Some(wanted) = conditions.has_tools.filter(|w| *w != input.has_tools) {
+        failed.push(if wanted { "no tools" } else { "has tools" }.to_string());
+    }
+    if let Some(wanted) = conditions.has_images.filter(|w| *w != input.has_images) {
+        failed.push(if wanted { "no images" } else { "has images" }.to_string());
+    }
+    if let Some(window) = conditions.time_window.filter(|w| !w.contains(input.time)) {
+        failed.push(format!(
+            "{} is outside {}-{}",
+            input.time.format("%H:%M"),
+            window.start.format("%H:%M"),
+            window.end.format("%H:%M")
+        ));
+    }
+    if !conditions.source_keys.is_empty()
+        && !input
+            .source_key
+            .is_some_and(|key| conditions.source_keys.iter().any(|k| k == key))
+    {
+        failed.push("source key isn't listed".to_string());
+    }
+    failed
+}
+
+/// First enabled rule matching `input`
+pub fn first_match<'a>(rules: &'a [RoutingRule], input: &RuleInput) -> Option<&'a RoutingRule> {
+    rules
+        .iter()
+        .find(|rule| rule.enabled && mismatches(&rule.conditions, input).is_empty())
+}
+
+/// Route an action selects, if its provider is enabled
+pub fn action_route(settings: &GatewaySettings, action: &RuleAction) -> Option<RouteTarget> {
+    let Some(provider) = &action.provider else {
+        return router::resolve_route(settings, Some(action.model.as_deref()?));
+    };
+    let provider = router::enabled_providers(settings)
+        .into_iter()
+        .find(|p| p.provider == *provider)?;
+    let model = match &action.model {
+        Some(model) => model.clone(),
+        None => router::default_model(provider)?.to_string(),
+    };
+    Some(RouteTarget {
+        provider: provider.clone(),
+        model,
+        ab: None,
+    })
+}
+
+/// Set (or, with `null`, remove) an action's parameters on a request
+pub fn apply_parameters(request: &mut Value, action: &RuleAction) {
+    if let Some(obj) = request.as_object_mut() {
+        for (name, value) in &action.parameters {
+            if value.is_null() {
+                obj.remove(name);
+            } else {
+                obj.insert(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// How one rule fared against a sample request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleEvaluation {
+    pub name: String,
+    pub enabled: bool,
+    /// Conditions the request failed; empty when the rule matches
+    pub mismatches: Vec<String>,
+}
+
+/// Which rule a sample request matches, and why the others don't
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleExplanation {
+    /// Name of the rule that applies, if any
+    pub matched: Option<String>,
+    /// Provider and model the request would be routed to
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// Rules in evaluation order, up to the one that matched
+    pub evaluations: Vec<RuleEvaluation>,
+}
+
+/// Explain how `rules` treat `input`
+pub fn explain(
+    settings: &GatewaySettings,
+    rules: &[RoutingRule],
+    input: &RuleInput,
+) -> RuleExplanation {
+    let mut evaluations = Vec::new();
+    let mut matched = None;
+    for rule in rules {
+        let failed = mismatches(&rule.conditions, input);
+        let hit = rule.enabled && failed.is_empty();
+        evaluations.push(RuleEvaluation {
+            name: rule.name.clone(),
+            enabled: rule.enabled,
+            mismatches: failed,
+        });
+        if hit {
+            matched = Some(rule);
+            break;
+        }
+    }
+
+    let route = match matched {
+        Some(rule) => action_route(settings, &rule.action),
+        None => router::resolve_route(settings, Some(input.model)),
+    };
+    RuleExplanation {
+        matched: matched.map(|r| r.name.clone()),
+        provider: route.as_ref().map(|r| r.provider.name.clone()),
+        model: route.map(|r| r.model),
+        evaluations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(name: &str, conditions: RuleConditions, action: RuleAction) -> RoutingRule {
+        RoutingRule {
+            name: name.to_string(),
+            enabled: true,
+            conditions,
+            action,
+        }
+    }
+
+    #[test]
+    fn test_rules_first_match() {
+        let mut settings = GatewaySettings::default();
+        for provider in settings.providers.iter_mut() {
+            provider.enabled = matches!(
+                provider.provider,
+                LLMProvider::OpenAI | LLMProvider::DeepSeek
+            );
+        }
+        let night: TimeWindow =
+            serde_json::from_value(json!({"start": "22:00", "end": "06:00"})).unwrap();
+        let rules = vec![
+            rule(
+                "tools to openai",
+                RuleConditions {
+                    has_tools: Some(true),
+                    ..Default::default()
+                },
+                RuleAction {
+                    provider: Some(LLMProvider::OpenAI),
+                    ..Default::default()
+                },
+            ),
+            rule(
+                "cheap at night",
+                RuleConditions {
+                    match_model: Some("claude-*".to_string()),
+                    time_window: Some(night),
+                    ..Default::default()
+                },
+                RuleAction {
+                    model: Some("deepseek-chat".to_string()),
+                    parameters: json!({"temperature": 0.2}).as_object().unwrap().clone(),
+                    ..Default::default()
+                },
+            ),
+        ];
+        let request = json!({
+            "model": "claude-3-5-sonnet",
+            "messages": [{"role": "user", "content": "hi"}]
+        });
+        let time = |t: &str| NaiveTime::parse_from_str(t, "%H:%M").unwrap();
+
+        let input = RuleInput::from_request(&request, None, time("23:30"));
+        let matched = first_match(&rules, &input).unwrap();
+        assert_eq!(matched.name, "cheap at night");
+        let route = action_route(&settings, &matched.action).unwrap();
+        assert_eq!(route.provider.provider, LLMProvider::DeepSeek);
+        let mut applied = request.clone();
+        apply_parameters(&mut applied, &matched.action);
+        assert_eq!(applied["temperature"], 0.2);
+
+        let input = RuleInput::from_request(&request, None, time("12:00"));
+        let explanation = explain(&settings, &rules, &input);
+        assert_eq!(explanation.matched, None);
+        assert_eq!(explanation.evaluations[0].mismatches, vec!["no tools"]);
+        assert_eq!(
+            explanation.evaluations[1].mismatches,
+            vec!["12:00 is outside 22:00-06:00"]
+        );
+
+        let with_tools = json!({"model": "x", "tools": [{"type": "function"}]});
+        let input = RuleInput::from_request(&with_tools, None, time("12:00"));
+        let explanation = explain(&settings, &rules, &input);
+        assert_eq!(explanation.matched.as_deref(), Some("tools to openai"));
+        assert_eq!(explanation.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(explanation.evaluations.len(), 1);
+    }
+}
@@ -0,0 +1,163 @@
+//! Self-Test - A tiny completion through every enabled provider and the gateway itself
+//!
+//! Each check goes through the running gateway on localhost, so it exercises the same
+//! adapters, credentials and request handling as real traffic: one request pinned to
+//! each enabled provider's default model, and one left to the gateway's routing. The
+//! checks run concurrently and skip request deduplication.
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+use super::dedup::NO_DEDUP_HEADER;
+use super::router::{self, PROVIDER_HEADER};
+use super::{GatewaySettings, ProviderConfig};
+
+/// Prompt of every check
+const PROMPT: &str = "Reply with the single word: pong";
+
+/// Room for the reply, and for reasoning models to get to it
+const MAX_TOKENS: u32 = 32;
+
+/// Characters of the reply kept in a check's snippet
+const SNIPPET_CHARS: usize = 80;
+
+/// How long one check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Target name of the check left to the gateway's routing
+pub const GATEWAY_TARGET: &str = "gateway";
+
+/// Outcome of one check
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SelfTestCheck {
+    /// Provider display name, or `gateway` for the routed check
+    pub target: String,
+    /// Model that was asked, or that answered for the routed check
+    pub model: Option<String>,
+    pub passed: bool,
+    pub latency_ms: Option<u64>,
+    /// Start of the reply
+    pub snippet: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Outcome of a self-test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    /// Whether every check passed
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+/// Start of a completion's reply, falling back to its reasoning when the token budget
+/// ran out before the answer
+fn snippet(body: &Value) -> Option<String> {
+    let message = body.pointer("/choices/0/message")?;
+    let text = ["content", "reasoning_content"]
+        .iter()
+        .filter_map(|field| message.get(*field)?.as_str())
+        .map(str::trim)
+        .find(|text| !text.is_empty())?;
+    Some(text.chars().take(SNIPPET_CHARS).collect())
+}
+
+/// Send the check's completion to the gateway on `port`, pinned to `provider` if given
+async fn check(
+    client: &reqwest::Client,
+    port: u16,
+    provider: Option<&ProviderConfig>,
+) -> SelfTestCheck {
+    let model = provider.and_then(router::default_model);
+    let mut result = SelfTestCheck {
+        target: provider.map_or(GATEWAY_TARGET.to_string(), |p| p.name.clone()),
+        model: model.map(str::to_string),
+        passed: false,
+        latency_ms: None,
+        snippet: None,
+        error: None,
+    };
+
+    let mut body = json!({
+        "messages": [{"role": "user", "content": PROMPT}],
+        "max_tokens": MAX_TOKENS,
+        "stream": false,
+    });
+    if let Some(model) = model {
+        body["model"] = json!(model);
+    }
+    let mut request = client
+        .post(format!("http://127.0.0.1:{}/v1/chat/completions", port))
+        .timeout(CHECK_TIMEOUT)
+        .header(NO_DEDUP_HEADER, "1")
+        .json(&body);
+    if let Some(provider) = provider {
+        request = request.header(PROVIDER_HEADER, &provider.name);
+    }
+
+    let start = Instant::now();
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    result.latency_ms = Some(start.elapsed().as_millis() as u64);
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        result.error = Some(format!("{}: {}", status, text));
+        return result;
+    }
+    match serde_json::from_str::<Value>(&text) {
+        Ok(body) => {
+            if provider.is_none() {
+                result.model = body
+                    .get("model")
+                    .and_then(|m| m.as_str())
+                    .map(str::to_string);
+            }
+            result.snippet = snippet(&body);
+            result.passed = result.snippet.is_some();
+            if !result.passed {
+                result.error = Some("Response has no reply".to_string());
+            }
+        }
+        Err(e) => result.error = Some(format!("Invalid response: {}", e)),
+    }
+    result
+}
+
+/// Run every check against the gateway serving `settings` on `port`
+pub async fn run(settings: &GatewaySettings, port: u16) -> SelfTestReport {
+    let client = super::client::shared_client();
+    let providers = router::enabled_providers(settings);
+    let pinned = providers.iter().map(|p| check(&client, port, Some(*p)));
+    let mut checks = join_all(pinned).await;
+    checks.push(check(&client, port, None).await);
+    SelfTestReport {
+        passed: checks.iter().all(|c| c.passed),
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snippet() {
+        let reply = json!({"choices": [{"message": {"content": " pong\n"}}]});
+        assert_eq!(snippet(&reply).as_deref(), Some("pong"));
+
+        let reasoning = json!({"choices": [{"message": {
+            "content": "",
+            "reasoning_content": "The user wants one word. ".repeat(10)
+        }}]});
+        assert_eq!(snippet(&reasoning).unwrap().chars().count(), SNIPPET_CHARS);
+
+        assert_eq!(snippet(&json!({"choices": []})), None);
+    }
+}
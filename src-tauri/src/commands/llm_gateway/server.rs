@@ -0,0 +1,3093 @@
+//! Gateway Server - Local HTTP proxy that forwards requests to upstream providers
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Json, ServiceExt,
+};
+use futures::future::Either;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::RwLock;
+
+use crate::commands::agents::AgentDb;
+
+use super::adapter::AdapterSpec;
+use super::audio::{self, AudioUpload};
+use super::batches::{self, BatchJob};
+use super::caching;
+use super::client::UpstreamClient;
+use super::context::{self, ContextFit, ContextOverflow};
+use super::copilot;
+use super::dashscope;
+use super::dedup::{self, InFlight};
+use super::documents;
+use super::dryrun::{self, DRY_RUN_HEADER};
+use super::embeddings::{self, EmbeddingApi};
+use super::errors;
+use super::gemini::{self, GeminiStreamTranslator};
+use super::guardrails;
+use super::hardware;
+use super::images::{self, ImageApi, ImageFormat};
+use super::keys::{self, KeyPool};
+use super::legacy::{self, CompletionStreamTranslator};
+use super::limits::{self, ModelLimits, RateLimiter};
+use super::mock;
+use super::moderation::{self, ModerationAction};
+use super::moonshot::{self, ContextCaches};
+use super::notify::Notifier;
+use super::ollama::{self, OllamaStreamTranslator};
+use super::ollama_server;
+use super::pricing;
+use super::queue::{QueuePermit, RequestPriority, RequestQueue, PRIORITY_HEADER};
+use super::quirks::{self, Quirks};
+use super::quota;
+use super::reasoning;
+use super::redact::Redactor;
+use super::replicate;
+use super::responses::{self, ResponsesStreamTranslator};
+use super::router::{self, AbAssignment, RouteOverrides, RouteTarget, RoutingStrategy};
+use super::rules::{self, RuleInput};
+use super::scrub::{scrub, scrub_secrets};
+use super::slo::{self, SloEvent, SloTracker};
+use super::sse::EventBoundary;
+use super::structured::{self, StructuredOutputMode, StructuredPlan};
+use super::subscription;
+use super::templates::{self, TEMPLATE_HEADER};
+use super::transform;
+use super::translate::{self, AnthropicStreamTranslator, StreamTranslator};
+use super::usage::{self, KeyUsage, RequestRecord, StreamUsageTap, TokenUsage};
+use super::vision::{self, ImageLimits};
+use super::{
+    GatewaySettings, GatewayStatus, LLMProvider, ProviderConfig, ProviderStatus, RedactionConfig,
+};
+
+/// Gateway server app state
+#[derive(Clone)]
+struct GatewayAppState {
+    settings: Arc<RwLock<GatewaySettings>>,
+    status: Arc<RwLock<GatewayStatus>>,
+    /// Shared client for upstream requests
+    http: UpstreamClient,
+    /// Per-model RPM/TPM accounting
+    limiter: Arc<RateLimiter>,
+    /// Global concurrency limit
+    queue: Arc<RequestQueue>,
+    /// API key rotation state
+    keys: Arc<KeyPool>,
+    /// Desktop notifications about spend and provider health
+    notifier: Arc<Notifier>,
+    /// Moonshot context caches created for marked prompt prefixes
+    contexts: Arc<ContextCaches>,
+    /// Rolling outcomes against provider SLOs
+    slo: Arc<SloTracker>,
+    /// Routing headers of the request being handled
+    overrides: RouteOverrides,
+    /// App handle for database access
+    app: AppHandle,
+}
+
+impl GatewayAppState {
+    /// State for one request, carrying its routing headers and with routing narrowed to
+    /// the provider it is pinned to, if any, and away from providers over their spend cap
+    /// or in a maintenance window, and with SLO-breaching providers demoted
+    async fn for_request(&self, headers: &HeaderMap) -> Result<Self, Response> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let mut state = self.clone();
+        state.overrides = RouteOverrides {
+            model: header(router::MODEL_HEADER).map(str::to_string),
+            strategy: RoutingStrategy::from_header(header(router::ROUTING_HEADER)),
+            source_key: client_key(headers).map(str::to_string),
+        };
+
+        if let Some(key) = header(router::PROVIDER_HEADER) {
+            let settings =
+                router::pin_provider(&*self.settings.read().await, key).ok_or_else(|| {
+                    let message = format!("Unknown or disabled provider '{}'", key);
+                    (StatusCode::BAD_REQUEST, message).into_response()
+                })?;
+            state.settings = Arc::new(RwLock::new(settings));
+        }
+
+        let capped = state.capped_providers().await;
+        if !capped.is_empty() {
+            let mut settings = state.settings.read().await.clone();
+            for provider in &mut settings.providers {
+                provider.enabled &= !capped.contains(&provider.name);
+            }
+            state.settings = Arc::new(RwLock::new(settings));
+        }
+
+        let now = chrono::Local::now().naive_local();
+        let down = router::providers_in_maintenance(&*state.settings.read().await, now);
+        if !down.is_empty() {
+            let mut settings = state.settings.read().await.clone();
+            for provider in &mut settings.providers {
+                provider.enabled &= !down.contains(&provider.name);
+            }
+            state.settings = Arc::new(RwLock::new(settings));
+        }
+
+        let (penalties, events) = self
+            .slo
+            .demotions(&*state.settings.read().await, Instant::now());
+        events.into_iter().for_each(|event| self.report_slo(event));
+        if !penalties.is_empty() {
+            let mut settings = state.settings.read().await.clone();
+            for provider in &mut settings.providers {
+                provider.priority += penalties.get(&provider.name).copied().unwrap_or_default();
+            }
+            state.settings = Arc::new(RwLock::new(settings));
+        }
+        Ok(state)
+    }
+
+    /// Log and announce a provider's SLO demotion or restoration
+    fn report_slo(&self, event: SloEvent) {
+        match &event.reason {
+            Some(reason) => log::warn!("Demoting {}: {}", event.provider, reason),
+            None => log::info!("{} is back within its SLO", event.provider),
+        }
+        let _ = self.app.emit(slo::SLO_EVENT, event);
+    }
+
+    /// Note a provider call's outcome against the provider's SLO
+    fn record_slo(&self, provider: &ProviderConfig, latency_ms: Option<u64>, error: bool) {
+        if let Some(event) = self.slo.record(provider, latency_ms, error, Instant::now()) {
+            self.report_slo(event);
+        }
+    }
+
+    /// Providers that reached their monthly spend cap. The request log is only read when
+    /// a routable provider has a cap.
+    async fn capped_providers(&self) -> Vec<String> {
+        let settings = self.settings.read().await;
+        if !router::enabled_providers(&settings)
+            .iter()
+            .any(|p| p.monthly_spend_cap_usd.is_some())
+        {
+            return Vec::new();
+        }
+        let db = self.app.state::<AgentDb>();
+        let spend =
+            db.0.get()
+                .map_err(|e| e.to_string())
+                .and_then(|conn| usage::month_spend_by_provider(&conn).map_err(|e| e.to_string()));
+        match spend {
+            Ok(spend) => router::capped_providers(&settings, &spend),
+            Err(e) => {
+                log::warn!("Failed to read provider spend for caps: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Model to route: the client's override if it sent one, else `requested`
+    fn routed_model<'a>(&'a self, requested: &'a str) -> &'a str {
+        self.overrides.model.as_deref().unwrap_or(requested)
+    }
+}
+
+/// Per-request details carried through dispatch for the request log
+struct RequestContext {
+    /// Model name as sent by the client
+    requested_model: String,
+    /// Retries the client reports for this request
+    retries: u32,
+    /// Project and session the client attributes the request to
+    project: Option<String>,
+    session: Option<String>,
+    /// Fingerprint of the API key the client authenticated with
+    client_key: Option<String>,
+    start: Instant,
+    /// When the first token was streamed to the client
+    first_token: Option<Instant>,
+}
+
+/// API key a client authenticated to the gateway with, as `x-api-key` or a bearer token
+fn client_key(headers: &HeaderMap) -> Option<&str> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    header("x-api-key")
+        .or_else(|| header(header::AUTHORIZATION.as_str())?.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+impl RequestContext {
+    fn new(headers: &HeaderMap, requested_model: String) -> Self {
+        // Anthropic and OpenAI SDKs report their retry attempt in this header
+        let retries = headers
+            .get("x-stainless-retry-count")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        Self {
+            requested_model,
+            retries,
+            project: header(usage::PROJECT_HEADER),
+            session: header(usage::SESSION_HEADER),
+            client_key: client_key(headers).map(usage::key_fingerprint),
+            start: Instant::now(),
+            first_token: None,
+        }
+    }
+}
+
+pub(super) async fn run_gateway_server(
+    app: AppHandle,
+    port: u16,
+    settings: Arc<RwLock<GatewaySettings>>,
+    status: Arc<RwLock<GatewayStatus>>,
+    primary: bool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use axum::{
+        http::Method,
+        routing::{get, post},
+        Router,
+    };
+    use tower::Layer;
+    use tower_http::cors::{Any, CorsLayer};
+
+    let (timeout_seconds, max_concurrent, proxy, tls, pool, cassette, chaos) = {
+        let settings = settings.read().await;
+        (
+            settings.timeout_seconds,
+            settings.max_concurrent_requests,
+            settings.proxy.clone(),
+            settings.tls.clone(),
+            settings.pool.clone(),
+            settings.cassette.clone(),
+            settings.chaos.clone(),
+        )
+    };
+    if let Some(cassette) = &cassette {
+        log::info!(
+            "Gateway cassette in {:?} mode at {}",
+            cassette.mode,
+            cassette.dir
+        );
+    }
+    if chaos.is_some() {
+        log::warn!("Gateway chaos mode is on, injecting faults into provider requests");
+    }
+    let http = UpstreamClient::new(timeout_seconds, proxy, tls, pool, cassette, chaos)?;
+
+    // Additional listeners leave batches to the main one
+    let unfinished_batches = if primary {
+        let db = app.state::<AgentDb>();
+        let conn = db.0.get().map_err(|e| e.to_string())?;
+        batches::unfinished_batches(&conn)?
+    } else {
+        Vec::new()
+    };
+
+    let batch_app = app.clone();
+    let warm_up = primary.then(|| (http.clone(), settings.clone()));
+    let monitor = hardware::monitor(settings.clone(), status.clone());
+    let app_state = GatewayAppState {
+        settings: settings.clone(),
+        status: status.clone(),
+        http,
+        limiter: Arc::new(RateLimiter::default()),
+        queue: RequestQueue::new(max_concurrent),
+        keys: Arc::new(KeyPool::default()),
+        notifier: Arc::new(Notifier::new(app.clone(), settings.clone())),
+        contexts: Arc::new(ContextCaches::default()),
+        slo: Arc::new(SloTracker::default()),
+        overrides: RouteOverrides::default(),
+        app,
+    };
+
+    // CORS configuration
+    let cors = CorsLayer::new()
+        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::ACCEPT,
+            header::HeaderName::from_static(PRIORITY_HEADER),
+            header::HeaderName::from_static(TEMPLATE_HEADER),
+            header::HeaderName::from_static(router::PROVIDER_HEADER),
+            header::HeaderName::from_static(router::MODEL_HEADER),
+            header::HeaderName::from_static(router::ROUTING_HEADER),
+            header::HeaderName::from_static(dedup::NO_DEDUP_HEADER),
+        ])
+        .allow_origin(Any);
+
+    // Routes
+    let app = Router::new()
+        .route("/v1/messages", post(handle_messages))
+        .route("/v1/chat/completions", post(handle_chat_completions))
+        .route("/v1/completions", post(handle_completions))
+        .route("/v1/responses", post(handle_responses))
+        .route("/v1/embeddings", post(handle_embeddings))
+        .route("/v1/images/generations", post(handle_image_generations))
+        .route(
+            "/v1/audio/transcriptions",
+            // Room for the form fields around a file at the size limit
+            post(handle_audio_transcriptions)
+                .layer(DefaultBodyLimit::max(audio::MAX_AUDIO_BYTES + 1024 * 1024)),
+        )
+        .route("/v1/audio/speech", post(handle_audio_speech))
+        .route("/v1/moderations", post(handle_moderations))
+        .route(
+            "/v1/batches",
+            post(handle_create_batch).get(handle_list_batches),
+        )
+        .route("/v1/batches/{id}", get(handle_get_batch))
+        .route("/v1/batches/{id}/output", get(handle_batch_output))
+        .route("/v1/batches/{id}/cancel", post(handle_cancel_batch))
+        .route("/v1/models", get(handle_list_models))
+        .route("/v1/usage", get(handle_usage))
+        .route("/v1beta/models/{target}", post(handle_gemini))
+        .route("/api/chat", post(handle_ollama_chat))
+        .route("/api/tags", get(handle_ollama_tags))
+        .route("/health", get(handle_health))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(InFlight::default()),
+            dedup::coalesce,
+        ))
+        .layer(middleware::from_fn(errors::provider_errors))
+        .layer(cors)
+        .with_state(app_state);
+    // Rewriting the path has to happen before the router matches it
+    let app = middleware::from_fn_with_state(settings, provider_prefix).layer(app);
+
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    log::info!("Starting LLM Gateway server on {}", addr);
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    // Connections queue on the bound socket until the server accepts them
+    for id in unfinished_batches {
+        tokio::spawn(batches::run(batch_app.clone(), port, id));
+    }
+    if let Some((http, settings)) = warm_up {
+        let providers = settings.read().await.providers.clone();
+        tokio::spawn(ollama_server::warm_up(http, providers));
+    }
+    // Hardware sampling stops with the server
+    tokio::select! {
+        result = axum::serve(listener, ServiceExt::<Request>::into_make_service(app)) => result?,
+        _ = monitor => {}
+    }
+
+    Ok(())
+}
+
+/// Strip a leading provider segment such as `/deepseek/v1/chat/completions` before
+/// routing, pinning the request to that provider
+async fn provider_prefix(
+    State(settings): State<Arc<RwLock<GatewaySettings>>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let rewritten = {
+        let settings = settings.read().await;
+        router::provider_prefix(&settings, request.uri().path()).and_then(|(segment, rest)| {
+            let uri = match request.uri().query() {
+                Some(query) => format!("{}?{}", rest, query),
+                None => rest.to_string(),
+            };
+            Some((
+                HeaderValue::from_str(segment).ok()?,
+                uri.parse::<Uri>().ok()?,
+            ))
+        })
+    };
+    if let Some((provider, uri)) = rewritten {
+        *request.uri_mut() = uri;
+        request
+            .headers_mut()
+            .insert(router::PROVIDER_HEADER, provider);
+    }
+    next.run(request).await
+}
+
+// ============================================================================
+// Upstream Dispatch
+// ============================================================================
+
+/// Point a request body at the routed model, dropping the client's fallback list, which
+/// only the gateway acts on
+fn target_model(body: &mut Value, route: &RouteTarget) {
+    body["model"] = Value::String(route.model.clone());
+    if let Some(body) = body.as_object_mut() {
+        body.remove(router::FALLBACK_MODELS_FIELD);
+    }
+}
+
+/// Send an OpenAI-shaped chat request to the routed provider
+async fn send_upstream(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    mut body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let spec = AdapterSpec::resolve(&route.provider);
+    target_model(&mut body, route);
+    if route.provider.provider == LLMProvider::Mock {
+        return Ok(send_mock(route, &body).await);
+    }
+    reasoning::adapt_request(&mut body, &route.model);
+    quirks::normalize(&mut body, &Quirks::for_route(&route.provider, &route.model));
+    if !caching::supports_prompt_caching(&route.provider) {
+        caching::strip_cache_control(&mut body);
+    }
+    let body = spec.map_request(body);
+    if dashscope::uses_native_api(&route.provider) {
+        return send_dashscope_native(http, route, &spec, body).await;
+    }
+    if route.provider.provider == LLMProvider::Replicate {
+        return send_replicate(http, route, &spec, body).await;
+    }
+
+    // Copilot takes a session token exchanged for the stored GitHub token
+    let is_copilot = route.provider.provider == LLMProvider::Copilot;
+    let api_key = if is_copilot {
+        match copilot::session_token(http, &route.provider).await? {
+            Ok(token) => Some(token),
+            Err(refused) => return Ok(refused),
+        }
+    } else {
+        route.provider.api_key.clone()
+    };
+
+    let mut request = http.post(
+        &route.provider,
+        spec.chat_url(&route.provider.base_url, &route.model),
+    );
+    request = spec.authorize(request, api_key.as_deref(), &route.model);
+    if is_copilot {
+        for (name, value) in copilot::editor_headers() {
+            request = request.header(name, value);
+        }
+    }
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+
+    http.send(&route.provider, request.json(&body)).await
+}
+
+/// Send a chat completion request through DashScope's native API and hand back the
+/// answer as a chat completion response (or chunk stream), like any other provider's
+async fn send_dashscope_native(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    spec: &AdapterSpec,
+    body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let stream = is_stream(&body);
+    let mut request = http.post(
+        &route.provider,
+        dashscope::generation_url(&route.provider.base_url),
+    );
+    request = spec.authorize(request, route.provider.api_key.as_deref(), &route.model);
+    if stream {
+        request = request.header(dashscope::SSE_HEADER, "enable");
+    }
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+    let response = http
+        .send(
+            &route.provider,
+            request.json(&dashscope::to_native_request(&body)),
+        )
+        .await?;
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let status = response.status();
+    let mut headers = response.headers().clone();
+    headers.remove(header::CONTENT_LENGTH);
+    let body = if stream {
+        let translator = dashscope::NativeStreamTranslator::new(&route.model);
+        reqwest::Body::wrap_stream(translated_stream(response, translator))
+    } else {
+        let native: Value = response.json().await?;
+        reqwest::Body::from(dashscope::to_openai_response(&native, &route.model).to_string())
+    };
+    Ok(stand_in_response(status, headers, body))
+}
+
+/// Response standing in for an upstream one whose body had to be translated
+fn stand_in_response(
+    status: reqwest::StatusCode,
+    headers: HeaderMap,
+    body: reqwest::Body,
+) -> reqwest::Response {
+    let mut response = axum::http::Response::new(body);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    response.into()
+}
+
+/// Run a chat completion request as a Replicate prediction and hand back the answer as a
+/// chat completion response (or chunk stream). Failed predictions come back as a 502
+/// carrying Replicate's error.
+async fn send_replicate(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    spec: &AdapterSpec,
+    body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let stream = is_stream(&body);
+    let (url, version) = replicate::prediction_url(&route.provider.base_url, &route.model);
+    let authorized = |request: reqwest::RequestBuilder| {
+        let mut request = spec.authorize(request, route.provider.api_key.as_deref(), &route.model);
+        for (name, value) in route.provider.request_headers() {
+            request = request.header(name, value);
+        }
+        request
+    };
+    let mut request = authorized(http.post(&route.provider, url));
+    if !stream {
+        // Hold the response until the prediction finishes, up to Replicate's limit
+        request = request.header("Prefer", "wait");
+    }
+    let response = http
+        .send(
+            &route.provider,
+            request.json(&replicate::prediction_request(&body, version)),
+        )
+        .await?;
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+    let mut prediction: Value = response.json().await?;
+
+    let json_headers = |content_type: &'static str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers
+    };
+    if let Some(stream_url) = prediction.pointer("/urls/stream").and_then(|u| u.as_str()) {
+        if stream {
+            let request = authorized(http.get(&route.provider, stream_url))
+                .header(header::ACCEPT, "text/event-stream");
+            let response = http.send(&route.provider, request).await?;
+            if !response.status().is_success() {
+                return Ok(response);
+            }
+            let id = prediction["id"].as_str().unwrap_or_default();
+            let translator = replicate::PredictionStreamTranslator::new(id, &route.model);
+            return Ok(stand_in_response(
+                response.status(),
+                json_headers("text/event-stream"),
+                reqwest::Body::wrap_stream(translated_stream(response, translator)),
+            ));
+        }
+    }
+
+    let deadline = Instant::now() + replicate::MAX_WAIT;
+    while !replicate::is_finished(&prediction) && Instant::now() < deadline {
+        let Some(poll_url) = prediction.pointer("/urls/get").and_then(|u| u.as_str()) else {
+            break;
+        };
+        tokio::time::sleep(replicate::POLL_INTERVAL).await;
+        let request = authorized(http.get(&route.provider, poll_url));
+        let response = http.send(&route.provider, request).await?;
+        if !response.status().is_success() {
+            return Ok(response);
+        }
+        prediction = response.json().await?;
+    }
+
+    let failure = match replicate::failure(&prediction) {
+        Some(error) => Some(error),
+        None if !replicate::is_finished(&prediction) => Some(format!(
+            "Prediction didn't finish within {:?}",
+            replicate::MAX_WAIT
+        )),
+        None => None,
+    };
+    if let Some(error) = failure {
+        let body = serde_json::json!({"error": {"message": error, "type": "server_error"}});
+        return Ok(stand_in_response(
+            reqwest::StatusCode::BAD_GATEWAY,
+            json_headers("application/json"),
+            reqwest::Body::from(body.to_string()),
+        ));
+    }
+    let completion = replicate::to_openai_response(&prediction, &route.model);
+    Ok(if stream {
+        stand_in_response(
+            reqwest::StatusCode::OK,
+            json_headers("text/event-stream"),
+            reqwest::Body::from(translate::completion_as_chunks(&completion)),
+        )
+    } else {
+        stand_in_response(
+            reqwest::StatusCode::OK,
+            json_headers("application/json"),
+            reqwest::Body::from(completion.to_string()),
+        )
+    })
+}
+
+/// Answer a chat completion request from a mock provider, streamed as a single chunk
+/// when asked to
+async fn send_mock(route: &RouteTarget, body: &Value) -> reqwest::Response {
+    let (status, reply) = mock::respond(&route.provider, &route.model, body).await;
+    let status = reqwest::StatusCode::from_u16(status).unwrap_or(reqwest::StatusCode::BAD_GATEWAY);
+    let (content_type, body) = if status.is_success() && is_stream(body) {
+        ("text/event-stream", translate::completion_as_chunks(&reply))
+    } else {
+        ("application/json", reply.to_string())
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    stand_in_response(status, headers, reqwest::Body::from(body))
+}
+
+/// Send an embedding request to the routed provider's embedding API
+async fn send_embeddings(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    api: EmbeddingApi,
+    body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let mut request = http.post(
+        &route.provider,
+        api.url(&route.provider.base_url, &route.model),
+    );
+    request = api.authorize(request, &route.provider, &route.model);
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+
+    http.send(&route.provider, request.json(&body)).await
+}
+
+/// Send an image generation request to the routed provider
+async fn send_image_generation(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    api: ImageApi,
+    body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let mut request = http.post(&route.provider, api.url(&route.provider.base_url));
+    request = api.authorize(request, &route.provider, &route.model);
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+
+    http.send(&route.provider, request.json(&body)).await
+}
+
+/// Send a transcription upload to the routed provider
+async fn send_transcription(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    form: reqwest::multipart::Form,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let url = format!(
+        "{}/audio/transcriptions",
+        route.provider.base_url.trim_end_matches('/')
+    );
+    let mut request = http.post(&route.provider, url);
+    request = AdapterSpec::resolve(&route.provider).authorize(
+        request,
+        route.provider.api_key.as_deref(),
+        &route.model,
+    );
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+
+    http.send(&route.provider, request.multipart(form)).await
+}
+
+/// Send a speech synthesis request to the routed provider
+async fn send_speech(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let url = format!(
+        "{}/audio/speech",
+        route.provider.base_url.trim_end_matches('/')
+    );
+    let mut request = http.post(&route.provider, url);
+    request = AdapterSpec::resolve(&route.provider).authorize(
+        request,
+        route.provider.api_key.as_deref(),
+        &route.model,
+    );
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+
+    http.send(&route.provider, request.json(&body)).await
+}
+
+/// Send a moderation request to `route`'s provider
+async fn send_moderation(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let mut request = http.post(
+        &route.provider,
+        moderation::moderation_url(&route.provider.base_url),
+    );
+    request = AdapterSpec::resolve(&route.provider).authorize(
+        request,
+        route.provider.api_key.as_deref(),
+        &route.model,
+    );
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+
+    http.send(&route.provider, request.json(&body)).await
+}
+
+/// Send a legacy completion request to a model that supports them natively
+async fn send_legacy_completion(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    mut body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    target_model(&mut body, route);
+    let mut request = http.post(
+        &route.provider,
+        legacy::completions_url(&route.provider.base_url),
+    );
+    request = AdapterSpec::resolve(&route.provider).authorize(
+        request,
+        route.provider.api_key.as_deref(),
+        &route.model,
+    );
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+
+    http.send(&route.provider, request.json(&body)).await
+}
+
+type UpstreamAttempt = (RouteTarget, Result<reqwest::Response, reqwest::Error>);
+
+/// Send an OpenAI-shaped request, racing it against the speculative secondary route when
+/// one is configured. `route` is updated to whichever route produced the response; the
+/// losing request is cancelled.
+async fn send_speculative(
+    state: &GatewayAppState,
+    route: &mut RouteTarget,
+    requested_model: &str,
+    body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let secondary = {
+        let settings = state.settings.read().await;
+        router::speculative_route(&settings, requested_model, route)
+    };
+    let Some(mut secondary) = secondary else {
+        return send_with_context_cache(state, route, body).await;
+    };
+    secondary.provider.api_key = state.keys.select(&secondary.provider, Instant::now());
+
+    let attempt = |target: RouteTarget,
+                   body: Value|
+     -> Pin<Box<dyn Future<Output = UpstreamAttempt> + Send>> {
+        let http = state.http.clone();
+        Box::pin(async move {
+            let result = send_upstream(&http, &target, body).await;
+            (target, result)
+        })
+    };
+    let succeeded = |attempt: &UpstreamAttempt| {
+        attempt
+            .1
+            .as_ref()
+            .is_ok_and(|response| response.status().is_success())
+    };
+
+    let (first, other) = match futures::future::select(
+        attempt(route.clone(), body.clone()),
+        attempt(secondary, body),
+    )
+    .await
+    {
+        Either::Left(finished) | Either::Right(finished) => finished,
+    };
+
+    let winner = if succeeded(&first) {
+        first
+    } else {
+        let second = other.await;
+        if succeeded(&second) {
+            second
+        } else {
+            first
+        }
+    };
+    log::info!("Speculative dispatch won by {}", limiter_key(&winner.0));
+
+    *route = winner.0;
+    winner.1
+}
+
+/// Retries of a request whose provider keeps answering 429
+const MAX_THROTTLE_REROUTES: u32 = 3;
+
+/// Send an OpenAI-shaped request like [`send_speculative`]. When the provider answers
+/// 429 the throttled key is benched, or the whole provider paused if it has no other
+/// keys, for the window it asked for, so later requests are admitted elsewhere.
+///
+/// The body was prepared for this provider (guardrails, images, documents, context
+/// window), so the request itself stays on it: it moves to another of the provider's
+/// keys, or waits out the window when that fits in the request timeout. The 429 is only
+/// passed on once neither can take the request in time.
+async fn dispatch_chat(
+    state: &GatewayAppState,
+    route: &mut RouteTarget,
+    requested_model: &str,
+    body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let mut retries = 0;
+    loop {
+        let result = send_speculative(state, route, requested_model, body.clone()).await;
+        let throttled = result
+            .as_ref()
+            .is_ok_and(|r| r.status() == StatusCode::TOO_MANY_REQUESTS);
+        if !throttled || retries >= MAX_THROTTLE_REROUTES {
+            return result;
+        }
+        let window = result
+            .as_ref()
+            .ok()
+            .and_then(|r| limits::retry_after(r.headers(), std::time::SystemTime::now()));
+        let now = Instant::now();
+        let key = route.provider.api_key.clone().unwrap_or_default();
+        if keys::provider_keys(&route.provider).len() > 1 {
+            state
+                .keys
+                .report_with_window(&route.provider, &key, 429, window, now);
+            route.provider.api_key = state.keys.select(&route.provider, now);
+            log::info!(
+                "Retrying request throttled by {} with another key",
+                limiter_key(route)
+            );
+        } else {
+            let window = window.unwrap_or(limits::DEFAULT_THROTTLE_PAUSE);
+            log::warn!(
+                "{} answered 429, pausing it for {:?}",
+                pause_key(route),
+                window
+            );
+            state.limiter.pause(&pause_key(route), now + window);
+            let max_wait = Duration::from_secs(state.settings.read().await.timeout_seconds as u64);
+            if window > max_wait {
+                return result;
+            }
+            log::info!(
+                "Queueing request throttled by {} for {:?}",
+                limiter_key(route),
+                window
+            );
+            tokio::time::sleep(window).await;
+        }
+        retries += 1;
+    }
+}
+
+/// Send a chat request to Moonshot through a context cache of the prefix the client
+/// marked with `cache_control`, creating the cache on first use. A cache that is still
+/// being built is only used by later requests, and one the provider rejects is dropped
+/// and the request resent in full.
+async fn send_with_context_cache(
+    state: &GatewayAppState,
+    route: &RouteTarget,
+    body: Value,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let count = match route.provider.provider {
+        LLMProvider::Moonshot => moonshot::cacheable_prefix(&body),
+        _ => None,
+    };
+    let Some(count) = count else {
+        return send_upstream(&state.http, route, body).await;
+    };
+    let key = moonshot::prefix_key(&route.provider, &body, count);
+    let now = Instant::now();
+    let id = match state.contexts.get(&key, now) {
+        Some(id) => Some(id),
+        None => create_context_cache(state, route, &body, count)
+            .await
+            .and_then(|(id, ready)| {
+                state.contexts.insert(key.clone(), id.clone(), now);
+                ready.then_some(id)
+            }),
+    };
+    let Some(id) = id else {
+        return send_upstream(&state.http, route, body).await;
+    };
+
+    let mut cached = body.clone();
+    moonshot::use_cache(&mut cached, count, &id);
+    let result = send_upstream(&state.http, route, cached).await;
+    match &result {
+        Ok(response) if response.status().is_client_error() => {
+            log::warn!(
+                "{} rejected context cache {} ({}), sending the full prompt",
+                route.provider.name,
+                id,
+                response.status()
+            );
+            state.contexts.remove(&key);
+            send_upstream(&state.http, route, body).await
+        }
+        Ok(_) => {
+            state.contexts.insert(key, id, Instant::now());
+            result
+        }
+        Err(_) => result,
+    }
+}
+
+/// Create a Moonshot context cache of a request's first `count` messages. Returns its id
+/// and whether it is ready to use.
+async fn create_context_cache(
+    state: &GatewayAppState,
+    route: &RouteTarget,
+    body: &Value,
+    count: usize,
+) -> Option<(String, bool)> {
+    let url = format!("{}/caching", route.provider.base_url.trim_end_matches('/'));
+    let mut request = state.http.post(&route.provider, url);
+    request = AdapterSpec::resolve(&route.provider).authorize(
+        request,
+        route.provider.api_key.as_deref(),
+        &route.model,
+    );
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+    let request = request.json(&moonshot::create_request(body, count));
+
+    let response = match state.http.send(&route.provider, request).await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            log::warn!(
+                "{} refused to create a context cache: {}",
+                route.provider.name,
+                response.status()
+            );
+            return None;
+        }
+        Err(e) => {
+            log::warn!(
+                "Creating a context cache on {} failed: {}",
+                route.provider.name,
+                scrub(&e.to_string())
+            );
+            return None;
+        }
+    };
+    let created: Value = response.json().await.ok()?;
+    let id = created["id"].as_str()?.to_string();
+    log::info!(
+        "Cached {} prompt messages on {} as {}",
+        count,
+        route.provider.name,
+        id
+    );
+    Some((id, created["status"] == "ready"))
+}
+
+/// Forward an Anthropic Messages request unchanged to a native Anthropic endpoint, with
+/// the client's Claude subscription token in place of the provider key when the
+/// provider passes those through
+async fn send_anthropic_native(
+    http: &UpstreamClient,
+    route: &RouteTarget,
+    mut body: Value,
+    headers: &HeaderMap,
+) -> Result<reqwest::Response, reqwest::Error> {
+    target_model(&mut body, route);
+    let url = format!("{}/messages", route.provider.base_url.trim_end_matches('/'));
+
+    let version = headers
+        .get("anthropic-version")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("2023-06-01");
+    let mut request = http
+        .post(&route.provider, url)
+        .header("anthropic-version", version);
+    let beta = headers.get("anthropic-beta").and_then(|v| v.to_str().ok());
+    let oauth = subscription::client_token(headers).filter(|_| route.provider.oauth_passthrough);
+    if let Some(token) = oauth {
+        request = request
+            .bearer_auth(token)
+            .header("anthropic-beta", subscription::with_oauth_beta(beta));
+    } else {
+        if let Some(beta) = beta {
+            request = request.header("anthropic-beta", beta);
+        }
+        if let Some(key) = route.provider.api_key.as_deref().filter(|k| !k.is_empty()) {
+            request = request.header("x-api-key", key);
+        }
+    }
+    for (name, value) in route.provider.request_headers() {
+        request = request.header(name, value);
+    }
+
+    http.send(&route.provider, request.json(&body)).await
+}
+
+/// Append a request to the gateway request log
+fn log_request(
+    state: &GatewayAppState,
+    route: &RouteTarget,
+    ctx: &RequestContext,
+    status_code: u16,
+    usage: Option<TokenUsage>,
+) {
+    let cost_usd = usage.and_then(|usage| {
+        route
+            .provider
+            .models
+            .iter()
+            .find(|m| m.id == route.model)
+            .map(|model| {
+                let model = pricing::priced_at(&route.provider, model, chrono::Utc::now());
+                usage::usage_cost(&route.provider.provider, &model, usage)
+            })
+    });
+    log_request_cost(state, route, ctx, status_code, usage, cost_usd);
+}
+
+/// Append a request whose cost is already known to the gateway request log
+fn log_request_cost(
+    state: &GatewayAppState,
+    route: &RouteTarget,
+    ctx: &RequestContext,
+    status_code: u16,
+    usage: Option<TokenUsage>,
+    cost_usd: Option<f64>,
+) {
+    // Ollama's OpenAI-compatible API resets the model's keep-alive with every request
+    if status_code < 400 && ollama_server::keep_alive(&route.provider).is_some() {
+        let http = state.http.clone();
+        let provider = route.provider.clone();
+        let model = route.model.clone();
+        tokio::spawn(async move {
+            if let Err(e) = ollama_server::load(&http, &provider, &model).await {
+                log::warn!("Failed to keep {} loaded: {}", model, e);
+            }
+        });
+    }
+
+    let (ttft_ms, tokens_per_second) = usage::stream_timing(
+        ctx.start,
+        ctx.first_token,
+        Instant::now(),
+        usage.map_or(0, |u| u.output_tokens),
+    );
+    let record = RequestRecord {
+        requested_model: ctx.requested_model.clone(),
+        provider: route.provider.provider.to_string(),
+        provider_name: Some(route.provider.name.clone()),
+        model: route.model.clone(),
+        status_code,
+        latency_ms: Some(ctx.start.elapsed().as_millis() as u64),
+        usage,
+        cost_usd,
+        retries: ctx.retries,
+        experiment: route.ab.as_ref().map(|ab| ab.experiment.clone()),
+        arm: route.ab.as_ref().map(|ab| ab.arm.clone()),
+        project: ctx.project.clone(),
+        session: ctx.session.clone(),
+        client_key: ctx.client_key.clone(),
+        ttft_ms,
+        tokens_per_second,
+    };
+
+    let db = state.app.state::<AgentDb>();
+    let result = db.0.get().map_err(|e| e.to_string()).and_then(|conn| {
+        usage::insert_record(&conn, &record)
+            .and_then(|()| Ok((usage::today_spend(&conn)?, usage::month_spend(&conn)?)))
+            .map_err(|e| e.to_string())
+    });
+    match result {
+        Ok((today_spend_usd, month_spend_usd)) => {
+            if let Some(cost) = cost_usd.filter(|c| *c > 0.0) {
+                let notifier = state.notifier.clone();
+                tokio::spawn(async move {
+                    notifier.spend(cost, today_spend_usd, month_spend_usd).await;
+                });
+            }
+            let tick = usage::CostTick {
+                provider: record.provider,
+                model: record.model,
+                cost_usd,
+                today_spend_usd,
+            };
+            let _ = state.app.emit(usage::COST_EVENT, tick);
+        }
+        Err(e) => log::warn!("Failed to log gateway request: {}", e),
+    }
+}
+
+/// Render the stored template named in the request's template header, if any, into the
+/// request body (Anthropic Messages shaped when `anthropic` is set, else OpenAI shaped)
+async fn expand_template(
+    state: &GatewayAppState,
+    headers: &HeaderMap,
+    request: &mut Value,
+    anthropic: bool,
+) -> Result<(), Response> {
+    let Some(name) = headers.get(TEMPLATE_HEADER).and_then(|v| v.to_str().ok()) else {
+        return Ok(());
+    };
+    let template = {
+        let db = state.app.state::<AgentDb>();
+        let conn =
+            db.0.get()
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())?;
+        templates::get_template(&conn, name)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())?
+    };
+    let Some(template) = template else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Unknown prompt template '{}'", name),
+        )
+            .into_response());
+    };
+    templates::apply_template(request, &template, anthropic)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())
+}
+
+/// Redaction settings, when redaction applies to requests sent to `route`
+async fn redaction_for(state: &GatewayAppState, route: &RouteTarget) -> Option<RedactionConfig> {
+    state
+        .settings
+        .read()
+        .await
+        .redaction
+        .clone()
+        .filter(|config| {
+            config.enabled
+                && (config.providers.is_empty()
+                    || config.providers.contains(&route.provider.provider))
+        })
+}
+
+/// Mask sensitive values in a request when redaction applies to its route. Returns the
+/// redactor when placeholders in the response are to be restored.
+async fn redact_request(
+    state: &GatewayAppState,
+    route: &RouteTarget,
+    request: &mut Value,
+) -> Option<Redactor> {
+    let config = redaction_for(state, route).await?;
+    let mut redactor = Redactor::new(&config);
+    redactor.redact_request(request);
+    if redactor.redacted() == 0 {
+        return None;
+    }
+    log::info!(
+        "Redacted {} sensitive values from request to {}",
+        redactor.redacted(),
+        limiter_key(route)
+    );
+    config.restore_responses.then_some(redactor)
+}
+
+/// Pick a shadow route for this request, tagging the primary route with the shadow
+/// experiment when it isn't already part of an A/B test
+async fn shadow_for(state: &GatewayAppState, route: &mut RouteTarget) -> Option<RouteTarget> {
+    let mut shadow = router::shadow_route(&*state.settings.read().await, router::roll_percent())?;
+    if route.ab.is_none() {
+        route.ab = shadow.ab.as_ref().map(|ab| AbAssignment {
+            experiment: ab.experiment.clone(),
+            arm: "primary".to_string(),
+        });
+    }
+    shadow.provider.api_key = state.keys.select(&shadow.provider, Instant::now());
+    Some(shadow)
+}
+
+/// Mirror an OpenAI-shaped request to the shadow route in the background. The response
+/// is discarded; only its outcome is logged.
+fn spawn_shadow(
+    state: &GatewayAppState,
+    shadow: RouteTarget,
+    mut body: Value,
+    requested_model: &str,
+) {
+    let state = state.clone();
+    let requested_model = requested_model.to_string();
+    disable_stream(&mut body);
+
+    tokio::spawn(async move {
+        let ctx = RequestContext {
+            requested_model,
+            retries: 0,
+            project: None,
+            session: None,
+            client_key: None,
+            start: Instant::now(),
+            first_token: None,
+        };
+        let (status_code, usage) = match send_upstream(&state.http, &shadow, body).await {
+            Ok(response) => {
+                let status_code = response.status().as_u16();
+                let body = response.json::<Value>().await.ok();
+                (status_code, body.as_ref().and_then(usage::extract_usage))
+            }
+            Err(e) => {
+                log::warn!(
+                    "Shadow request to {} failed: {}",
+                    limiter_key(&shadow),
+                    scrub(&e.to_string())
+                );
+                (StatusCode::BAD_GATEWAY.as_u16(), None)
+            }
+        };
+        log::info!(
+            "Shadow request to {} finished with {} in {}ms",
+            limiter_key(&shadow),
+            status_code,
+            ctx.start.elapsed().as_millis()
+        );
+        log_request(&state, &shadow, &ctx, status_code, usage);
+    });
+}
+
+/// Record the outcome of an upstream call and turn upstream failures into responses
+async fn complete_dispatch(
+    state: &GatewayAppState,
+    route: &RouteTarget,
+    ctx: &RequestContext,
+    result: Result<reqwest::Response, reqwest::Error>,
+) -> Result<reqwest::Response, Response> {
+    let provider_key = route.provider.provider.to_string();
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            let error = scrub(&e.to_string());
+            state
+                .notifier
+                .provider_result(&route.provider.name, Some(error.clone()))
+                .await;
+            state
+                .notifier
+                .request_error(
+                    &route.provider.name,
+                    &route.model,
+                    StatusCode::BAD_GATEWAY.as_u16(),
+                    &error,
+                )
+                .await;
+            record_provider_result(&state.status, &provider_key, None, Some(e.to_string())).await;
+            state.record_slo(&route.provider, None, true);
+            log_request(state, route, ctx, StatusCode::BAD_GATEWAY.as_u16(), None);
+            return Err(StatusCode::BAD_GATEWAY.into_response());
+        }
+    };
+
+    let latency_ms = Some(ctx.start.elapsed().as_millis() as u64);
+    let upstream_status =
+        StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    if let Some(quota) = quota::from_headers(response.headers()) {
+        if let Some(warning) = quota::record_quota(&state.status, &provider_key, quota).await {
+            log::warn!(
+                "{} quota running low: {}",
+                route.provider.name,
+                warning.message
+            );
+            let _ = state.app.emit(quota::QUOTA_WARNING_EVENT, warning);
+        }
+    }
+    if let Some(key) = route.provider.api_key.as_deref() {
+        state.keys.report(
+            &route.provider,
+            key,
+            upstream_status.as_u16(),
+            Instant::now(),
+        );
+    }
+
+    if !upstream_status.is_success() {
+        // Upstream errors may echo the rejected key back
+        let keys = route
+            .provider
+            .api_key
+            .iter()
+            .chain(&route.provider.api_keys);
+        let text = scrub_secrets(
+            &response.text().await.unwrap_or_default(),
+            keys.map(String::as_str),
+        );
+        state.record_slo(
+            &route.provider,
+            latency_ms,
+            upstream_status.is_server_error(),
+        );
+        if upstream_status.is_server_error() {
+            state
+                .notifier
+                .provider_result(&route.provider.name, Some(upstream_status.to_string()))
+                .await;
+        }
+        state
+            .notifier
+            .request_error(
+                &route.provider.name,
+                &route.model,
+                upstream_status.as_u16(),
+                &text,
+            )
+            .await;
+        record_provider_result(
+            &state.status,
+            &provider_key,
+            latency_ms,
+            Some(format!("{}: {}", upstream_status, text)),
+        )
+        .await;
+        log_request(state, route, ctx, upstream_status.as_u16(), None);
+        return Err((
+            upstream_status,
+            [(header::CONTENT_TYPE, "application/json")],
+            text,
+        )
+            .into_response());
+    }
+
+    state
+        .notifier
+        .provider_result(&route.provider.name, None)
+        .await;
+    record_provider_result(&state.status, &provider_key, latency_ms, None).await;
+    state.record_slo(&route.provider, latency_ms, false);
+    Ok(response)
+}
+
+/// Turn an upstream response body into a stream of chunks for axum
+fn upstream_body_stream(
+    response: reqwest::Response,
+) -> impl futures::Stream<Item = Result<Bytes, reqwest::Error>> {
+    futures::stream::unfold(Some(response), |state| async move {
+        let mut response = state?;
+        match response.chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), Some(response))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+/// Stream an upstream SSE body through a format translator
+fn translated_stream<T: StreamTranslator>(
+    response: reqwest::Response,
+    translator: T,
+) -> impl futures::Stream<Item = Result<Bytes, reqwest::Error>> {
+    translate_stream(Box::pin(upstream_body_stream(response)), translator)
+}
+
+/// Pass an SSE byte stream through a format translator
+fn translate_stream<S, E, T>(
+    stream: S,
+    translator: T,
+) -> impl futures::Stream<Item = Result<Bytes, E>>
+where
+    S: futures::Stream<Item = Result<Bytes, E>> + Unpin,
+    T: StreamTranslator,
+{
+    use futures::StreamExt;
+    futures::stream::unfold(Some((stream, translator)), |state| async move {
+        let (mut stream, mut translator) = state?;
+        loop {
+            match stream.next().await {
+                Some(Ok(chunk)) => {
+                    let out = translator.push(&chunk);
+                    if !out.is_empty() {
+                        return Some((Ok(Bytes::from(out)), Some((stream, translator))));
+                    }
+                }
+                None => {
+                    let out = translator.finish();
+                    return (!out.is_empty()).then(|| (Ok(Bytes::from(out)), None));
+                }
+                Some(Err(e)) => return Some((Err(e), None)),
+            }
+        }
+    })
+}
+
+/// Quiet time after which a stream sends a heartbeat
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// SSE comment sent as a heartbeat; parsers ignore it, but it resets client read timeouts
+const KEEPALIVE_COMMENT: &str = ": keep-alive\n\n";
+
+/// Interleave heartbeats into an SSE stream whenever it goes quiet for `interval`, which
+/// long tool-use turns do while the model works. Heartbeats only go out between events,
+/// after the blank line ending one, so they never land inside an event's lines.
+fn keep_alive<S, E>(stream: S, interval: Duration) -> impl futures::Stream<Item = Result<Bytes, E>>
+where
+    S: futures::Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    use futures::StreamExt;
+    let boundary = EventBoundary::default();
+    futures::stream::unfold(Some((stream, boundary)), move |state| async move {
+        let (mut stream, mut boundary) = state?;
+        loop {
+            match tokio::time::timeout(interval, stream.next()).await {
+                Ok(Some(Ok(chunk))) => {
+                    boundary.push(&chunk);
+                    return Some((Ok(chunk), Some((stream, boundary))));
+                }
+                Ok(Some(Err(e))) => return Some((Err(e), None)),
+                Ok(None) => return None,
+                Err(_) if boundary.at_boundary() => {
+                    return Some((
+                        Ok(Bytes::from_static(KEEPALIVE_COMMENT.as_bytes())),
+                        Some((stream, boundary)),
+                    ))
+                }
+                Err(_) => continue,
+            }
+        }
+    })
+}
+
+/// Wrap a body as a server-sent event stream response
+fn sse_response(body: Body) -> Response {
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/event-stream"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        Body::from_stream(keep_alive(body.into_data_stream(), KEEPALIVE_INTERVAL)),
+    )
+        .into_response()
+}
+
+/// Record the outcome of an upstream call in the gateway status
+async fn record_provider_result(
+    status: &RwLock<GatewayStatus>,
+    provider: &str,
+    latency_ms: Option<u64>,
+    error: Option<String>,
+) {
+    let error = error.map(|e| scrub(&e));
+    let mut guard = status.write().await;
+    let status = &mut *guard;
+    status.requests_processed += 1;
+
+    let entry = status
+        .provider_status
+        .entry(provider.to_string())
+        .or_insert(ProviderStatus {
+            available: true,
+            latency_ms: None,
+            last_error: None,
+            request_count: 0,
+            error_count: 0,
+            quota: None,
+            hardware: None,
+        });
+    entry.request_count += 1;
+    entry.latency_ms = latency_ms.or(entry.latency_ms);
+
+    match error {
+        Some(error) => {
+            entry.available = false;
+            entry.error_count += 1;
+            entry.last_error = Some(error.clone());
+            status.last_error = Some(error);
+        }
+        None => entry.available = true,
+    }
+}
+
+const DEFAULT_QUEUE_TIMEOUT_SECS: u64 = 60;
+
+/// Wait for a concurrency slot in the request's priority class, failing with 503 once
+/// the queue timeout passes
+async fn acquire_slot(
+    state: &GatewayAppState,
+    headers: &HeaderMap,
+) -> Result<QueuePermit, Response> {
+    let priority =
+        RequestPriority::from_header(headers.get(PRIORITY_HEADER).and_then(|v| v.to_str().ok()));
+    let timeout = state
+        .settings
+        .read()
+        .await
+        .queue_timeout_seconds
+        .map_or(DEFAULT_QUEUE_TIMEOUT_SECS, u64::from);
+
+    state
+        .queue
+        .acquire(priority, Duration::from_secs(timeout))
+        .await
+        .ok_or_else(|| {
+            log::warn!("Request timed out after {}s in the gateway queue", timeout);
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Timed out waiting in the gateway request queue",
+            )
+                .into_response()
+        })
+}
+
+/// Logs a streamed request once its body is finished or dropped, with the usage seen
+/// in the stream
+struct StreamMeter {
+    tap: StreamUsageTap,
+    state: GatewayAppState,
+    route: RouteTarget,
+    ctx: RequestContext,
+    prompt_tokens: u64,
+}
+
+impl Drop for StreamMeter {
+    fn drop(&mut self) {
+        let usage = self.tap.finish(self.prompt_tokens);
+        self.ctx.first_token = self.tap.first_output();
+        log_request(
+            &self.state,
+            &self.route,
+            &self.ctx,
+            StatusCode::OK.as_u16(),
+            Some(usage),
+        );
+    }
+}
+
+/// Feed the chunks sent to the client through a [`StreamMeter`]
+fn meter_stream<S, E>(
+    stream: S,
+    mut meter: StreamMeter,
+) -> impl futures::Stream<Item = Result<Bytes, E>>
+where
+    S: futures::Stream<Item = Result<Bytes, E>>,
+{
+    use futures::StreamExt;
+    stream.map(move |item| {
+        if let Ok(chunk) = &item {
+            meter.tap.observe(chunk);
+        }
+        item
+    })
+}
+
+/// Keep `permit` alive until the response body stream is dropped
+fn hold_permit<S: futures::Stream>(
+    stream: S,
+    permit: QueuePermit,
+) -> impl futures::Stream<Item = S::Item> {
+    use futures::StreamExt;
+    stream.map(move |item| {
+        let _ = &permit;
+        item
+    })
+}
+
+fn limiter_key(route: &RouteTarget) -> String {
+    format!("{}/{}", route.provider.provider, route.model)
+}
+
+/// Key a provider is paused under after a 429
+fn pause_key(route: &RouteTarget) -> String {
+    format!("{}:{}", route.provider.provider, route.provider.name)
+}
+
+/// Admit a request of `tokens` to `route`, or return how long to wait until its
+/// provider's 429 pause is over and the model's rate limits allow it
+fn try_admit(state: &GatewayAppState, route: &RouteTarget, tokens: u32) -> Result<(), Duration> {
+    let now = Instant::now();
+    if let Some(wait) = state.limiter.paused_for(&pause_key(route), now) {
+        return Err(wait);
+    }
+    state.limiter.try_acquire(
+        &limiter_key(route),
+        ModelLimits::for_route(route),
+        tokens,
+        now,
+    )
+}
+
+/// Resolve and admit the route for a request, pick the provider API key to use, adapt
+/// inline images and documents to what the provider accepts and fit the prompt into the
+/// model's context window
+async fn route_request(
+    state: &GatewayAppState,
+    request: &mut Value,
+) -> Result<RouteTarget, Response> {
+    let mut route = admit_route(state, request).await?;
+    let local = guardrails::enforce(&*state.settings.read().await, request, &route)
+        .map_err(|e| (StatusCode::FORBIDDEN, e).into_response())?;
+    match local {
+        Some(local) => {
+            log::info!(
+                "Guardrail moved request from {} to {}",
+                limiter_key(&route),
+                limiter_key(&local)
+            );
+            route = local;
+        }
+        None => moderate(state, request).await?,
+    }
+    route.provider.api_key = state.keys.select(&route.provider, Instant::now());
+    vision::fit_request_images(request, ImageLimits::for_provider(&route.provider))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    documents::inline_documents(request, &route.provider);
+
+    let overflow = route
+        .provider
+        .models
+        .iter()
+        .find(|m| m.id == route.model)
+        .and_then(|m| m.context_overflow)
+        .or(state.settings.read().await.context_overflow);
+    if let Some(mode) = overflow {
+        if mode == ContextOverflow::Truncate {
+            summarize_overflow(state, request, &route).await;
+        }
+        match context::fit_context(request, &mut route, mode) {
+            Ok(ContextFit::Fits) => {}
+            Ok(fit) => log::info!("Fitted request into {}: {:?}", limiter_key(&route), fit),
+            Err(e) => return Err((StatusCode::BAD_REQUEST, e).into_response()),
+        }
+    }
+    Ok(route)
+}
+
+/// Screen a request's prompt with the moderation model, failing it with 400 when it is
+/// flagged and the policy blocks. Moderation errors are logged and let the request through.
+///
+/// Runs once guardrails have passed the request, and never for one they keep on the
+/// machine. The prompt is redacted as it would be for the moderation provider.
+async fn moderate(state: &GatewayAppState, request: &Value) -> Result<(), Response> {
+    let (mut route, action, categories) = {
+        let settings = state.settings.read().await;
+        let (Some(route), Some(config)) = (
+            router::moderation_route(&settings),
+            settings.moderation.as_ref(),
+        ) else {
+            return Ok(());
+        };
+        (route, config.action, config.categories.clone())
+    };
+    let text = match redaction_for(state, &route).await {
+        Some(config) => {
+            let mut redacted = request.clone();
+            Redactor::new(&config).redact_request(&mut redacted);
+            guardrails::request_text(&redacted)
+        }
+        None => guardrails::request_text(request),
+    };
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+
+    route.provider.api_key = state.keys.select(&route.provider, Instant::now());
+    let body = moderation::moderation_request(&text, &route.model);
+    let response = match send_moderation(&state.http, &route, body).await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            log::warn!(
+                "Moderation model {} returned {}, letting the request through",
+                limiter_key(&route),
+                response.status()
+            );
+            return Ok(());
+        }
+        Err(e) => {
+            log::warn!(
+                "Moderation model {} failed: {}, letting the request through",
+                limiter_key(&route),
+                scrub(&e.to_string())
+            );
+            return Ok(());
+        }
+    };
+    let Ok(result) = response.json::<Value>().await else {
+        return Ok(());
+    };
+
+    let flagged = moderation::flagged_categories(&result, &categories);
+    if flagged.is_empty() {
+        return Ok(());
+    }
+    match action {
+        ModerationAction::Flag => {
+            log::warn!("Request flagged by moderation for {}", flagged.join(", "));
+            Ok(())
+        }
+        ModerationAction::Block => Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Request blocked by moderation: flagged for {}",
+                flagged.join(", ")
+            ),
+        )
+            .into_response()),
+    }
+}
+
+/// Replace the oldest turns that don't fit `route`'s context window with a summary
+/// written by the configured summarizer model. Failures are logged and leave the request
+/// to plain truncation.
+async fn summarize_overflow(state: &GatewayAppState, request: &mut Value, route: &RouteTarget) {
+    let (mut summarizer, max_tokens) = {
+        let settings = state.settings.read().await;
+        let Some(summarizer) = router::summarizer_route(&settings) else {
+            return;
+        };
+        let max_tokens = settings
+            .summarization
+            .as_ref()
+            .and_then(|s| s.max_summary_tokens)
+            .unwrap_or(context::DEFAULT_SUMMARY_TOKENS);
+        (summarizer, max_tokens)
+    };
+    let Some(window) = context::context_window(route) else {
+        return;
+    };
+
+    // Leave room for the summary itself
+    let budget = window
+        .saturating_sub(limits::completion_budget(request))
+        .saturating_sub(max_tokens as u64);
+    let count = match context::overflow_prefix(request, budget) {
+        Some(0) | None => return,
+        Some(count) => count,
+    };
+
+    summarizer.provider.api_key = state.keys.select(&summarizer.provider, Instant::now());
+    let transcript = context::transcript(&context::oldest_messages(request, count));
+    let mut redactor = redaction_for(state, &summarizer)
+        .await
+        .map(|config| Redactor::new(&config));
+    let body = context::summary_request(&transcript, max_tokens, redactor.as_mut());
+    let ctx = RequestContext {
+        requested_model: summarizer.model.clone(),
+        retries: 0,
+        project: None,
+        session: None,
+        client_key: None,
+        start: Instant::now(),
+        first_token: None,
+    };
+
+    let response = match send_upstream(&state.http, &summarizer, body).await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            log::warn!(
+                "Summarizer {} returned {}, truncating instead",
+                limiter_key(&summarizer),
+                response.status()
+            );
+            return;
+        }
+        Err(e) => {
+            log::warn!(
+                "Summarizer {} failed: {}, truncating instead",
+                limiter_key(&summarizer),
+                scrub(&e.to_string())
+            );
+            return;
+        }
+    };
+    let Ok(mut completion) = read_completion(state, &summarizer, &ctx, response).await else {
+        return;
+    };
+    // The request is redacted again, for its own route, once it has been fitted
+    if let Some(redactor) = &redactor {
+        redactor.restore(&mut completion);
+    }
+    let summary = completion
+        .pointer("/choices/0/message/content")
+        .and_then(|c| c.as_str())
+        .unwrap_or_default();
+    if summary.trim().is_empty() {
+        return;
+    }
+
+    context::replace_with_summary(request, count, summary);
+    log::info!(
+        "Summarized {} messages for {} with {}",
+        count,
+        limiter_key(route),
+        limiter_key(&summarizer)
+    );
+}
+
+/// Schema an OpenAI-shaped request declares, if invalid responses to it are to be repaired
+async fn repair_plan(state: &GatewayAppState, request: &Value) -> Option<StructuredPlan> {
+    router::json_repair_route(&*state.settings.read().await)?;
+    structured::declared_schema(request)
+}
+
+/// Check a response against the schema its request declared and, when it fails, have
+/// the configured repair model fix it. Failures are logged and leave the response as is.
+async fn repair_structured_output(
+    state: &GatewayAppState,
+    response: &mut Value,
+    plan: &StructuredPlan,
+) {
+    let Err((error, text)) = structured::check_response(response, plan) else {
+        return;
+    };
+    let Some(mut repairer) = router::json_repair_route(&*state.settings.read().await) else {
+        return;
+    };
+    repairer.provider.api_key = state.keys.select(&repairer.provider, Instant::now());
+    let body = structured::repair_request(plan, &text, &error);
+    let ctx = RequestContext {
+        requested_model: repairer.model.clone(),
+        retries: 0,
+        project: None,
+        session: None,
+        client_key: None,
+        start: Instant::now(),
+        first_token: None,
+    };
+
+    let completion = match send_upstream(&state.http, &repairer, body).await {
+        Ok(reply) if reply.status().is_success() => reply,
+        Ok(reply) => {
+            log::warn!(
+                "JSON repair model {} returned {}, keeping the invalid response",
+                limiter_key(&repairer),
+                reply.status()
+            );
+            return;
+        }
+        Err(e) => {
+            log::warn!(
+                "JSON repair model {} failed: {}, keeping the invalid response",
+                limiter_key(&repairer),
+                scrub(&e.to_string())
+            );
+            return;
+        }
+    };
+    let Ok(completion) = read_completion(state, &repairer, &ctx, completion).await else {
+        return;
+    };
+    let reply = completion
+        .pointer("/choices/0/message/content")
+        .and_then(|c| c.as_str())
+        .unwrap_or_default();
+    match structured::apply_repair(response, plan, reply) {
+        Ok(()) => log::info!(
+            "Repaired a response failing its schema ({}) with {}",
+            error,
+            limiter_key(&repairer)
+        ),
+        Err(e) => log::warn!("JSON repair didn't produce a valid response: {}", e),
+    }
+}
+
+/// Resolve the route for a request body's `model` field, with the fallbacks to try and
+/// the longest wait for a rate limit, without admitting it
+async fn plan_route(
+    state: &GatewayAppState,
+    request: &mut Value,
+) -> Result<(RouteTarget, Vec<RouteTarget>, Duration), Response> {
+    let (route, fallbacks, max_wait) = {
+        let settings = state.settings.read().await;
+        // A client fallback list names the primary model too when the body has none
+        let listed = router::requested_fallbacks(request);
+        let requested = request
+            .get("model")
+            .and_then(|m| m.as_str())
+            .filter(|m| !m.is_empty())
+            .or(listed.first().copied())
+            .unwrap_or_default();
+        // Rules and strategies only pick the model when the client didn't name one
+        // explicitly
+        let explicit = state.overrides.model.is_some()
+            || router::prefixed_route(&settings, requested).is_some();
+        let rule = if explicit {
+            None
+        } else {
+            let source_key = state.overrides.source_key.as_deref();
+            let input = RuleInput::from_request(request, source_key, chrono::Local::now().time());
+            rules::first_match(&settings.routing_rules, &input).cloned()
+        };
+        let by_rule = rule.as_ref().and_then(|rule| {
+            let route = rules::action_route(&settings, &rule.action);
+            match &route {
+                Some(route) => log::info!(
+                    "Routing rule '{}' sends the request to {}",
+                    rule.name,
+                    limiter_key(route)
+                ),
+                None => log::warn!(
+                    "Routing rule '{}' targets no enabled provider, ignoring",
+                    rule.name
+                ),
+            }
+            route
+        });
+        let ruled = by_rule.is_some();
+        let by_strategy = match state.overrides.strategy.filter(|_| !explicit && !ruled) {
+            Some(strategy) => {
+                let status = state.status.read().await;
+                router::strategy_route(
+                    &settings,
+                    strategy,
+                    |p| {
+                        status
+                            .provider_status
+                            .get(&p.provider.to_string())?
+                            .latency_ms
+                    },
+                    chrono::Utc::now(),
+                )
+            }
+            _ => None,
+        };
+        let mut route = by_rule
+            .or(by_strategy)
+            .or_else(|| router::resolve_route(&settings, Some(state.routed_model(requested))))
+            .ok_or_else(|| StatusCode::SERVICE_UNAVAILABLE.into_response())?;
+        let mut fallbacks = if listed.is_empty() {
+            router::fallback_routes(&settings, &route)
+        } else {
+            router::listed_fallback_routes(&settings, &listed, &route)
+        };
+        if vision::request_has_images(request) {
+            if let Some(vision_route) = router::vision_route(&settings, &route) {
+                log::info!(
+                    "{} can't take images, rerouting to {}",
+                    limiter_key(&route),
+                    limiter_key(&vision_route)
+                );
+                route = vision_route;
+            }
+            fallbacks.retain(|f| router::supports_vision(&f.provider, &f.model));
+        }
+        if let Some(rule) = rule.filter(|_| ruled) {
+            rules::apply_parameters(request, &rule.action);
+        }
+        (
+            route,
+            fallbacks,
+            Duration::from_secs(settings.timeout_seconds as u64),
+        )
+    };
+
+    let (route, fallbacks) = avoid_saturated(
+        state,
+        route,
+        fallbacks,
+        limits::estimate_prompt_tokens(request),
+    )
+    .await;
+    Ok((route, fallbacks, max_wait))
+}
+
+/// Resolve the route for a request body's `model` field and admit it against the
+/// per-model rate limits.
+///
+/// When the routed model is at its limit the request moves to the first fallback with
+/// capacity, taken from the body's `models` list when the client sends one; if none has
+/// any, it waits for the routed model's window (bounded by the request timeout) before
+/// giving up with 429.
+async fn admit_route(
+    state: &GatewayAppState,
+    request: &mut Value,
+) -> Result<RouteTarget, Response> {
+    let (route, fallbacks, max_wait) = plan_route(state, request).await?;
+
+    let tokens = limits::estimate_tokens(request);
+    let wait = match try_admit(state, &route, tokens) {
+        Ok(()) => return Ok(route),
+        Err(wait) => wait,
+    };
+
+    for fallback in fallbacks {
+        if try_admit(state, &fallback, tokens).is_ok() {
+            log::info!(
+                "{} is at its rate limit, rerouting to {}",
+                limiter_key(&route),
+                limiter_key(&fallback)
+            );
+            state
+                .notifier
+                .failover(&route.provider.name, &fallback.provider.name)
+                .await;
+            return Ok(fallback);
+        }
+    }
+
+    if wait <= max_wait {
+        log::info!(
+            "{} is at its rate limit, queueing for {:?}",
+            limiter_key(&route),
+            wait
+        );
+        tokio::time::sleep(wait).await;
+        if try_admit(state, &route, tokens).is_ok() {
+            return Ok(route);
+        }
+    }
+
+    Err((
+        StatusCode::TOO_MANY_REQUESTS,
+        format!("Rate limit reached for {}", limiter_key(&route)),
+    )
+        .into_response())
+}
+
+/// Move a large prompt off a local provider whose machine is saturated, to the first
+/// fallback that isn't; the route stays when every fallback is saturated too
+async fn avoid_saturated(
+    state: &GatewayAppState,
+    route: RouteTarget,
+    mut fallbacks: Vec<RouteTarget>,
+    prompt_tokens: u64,
+) -> (RouteTarget, Vec<RouteTarget>) {
+    let status = state.status.read().await;
+    let saturation = |target: &RouteTarget| {
+        let guard = target.provider.saturation_guard.as_ref()?;
+        if prompt_tokens < guard.min_prompt_tokens {
+            return None;
+        }
+        status
+            .provider_status
+            .get(&target.provider.provider.to_string())?
+            .hardware
+            .as_ref()?
+            .saturation(guard)
+    };
+    let Some(reason) = saturation(&route) else {
+        return (route, fallbacks);
+    };
+    let Some(index) = fallbacks.iter().position(|f| saturation(f).is_none()) else {
+        return (route, fallbacks);
+    };
+    let fallback = fallbacks.remove(index);
+    log::info!(
+        "{} is saturated ({}), rerouting a {} token prompt to {}",
+        limiter_key(&route),
+        reason,
+        prompt_tokens,
+        limiter_key(&fallback)
+    );
+    (fallback, fallbacks)
+}
+
+/// Ask for a complete response instead of a stream
+fn disable_stream(body: &mut Value) {
+    if let Some(obj) = body.as_object_mut() {
+        obj.insert("stream".to_string(), Value::Bool(false));
+        obj.remove("stream_options");
+    }
+}
+
+/// Read a complete OpenAI-shaped response, map it through the provider's adapter and log it
+async fn read_completion(
+    state: &GatewayAppState,
+    route: &RouteTarget,
+    ctx: &RequestContext,
+    response: reqwest::Response,
+) -> Result<Value, Response> {
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY.into_response())?;
+    let mut body = AdapterSpec::resolve(&route.provider).map_response(body);
+    reasoning::normalize_completion(&mut body, state.settings.read().await.reasoning_output);
+    log_request(
+        state,
+        route,
+        ctx,
+        StatusCode::OK.as_u16(),
+        usage::extract_usage(&body),
+    );
+    Ok(body)
+}
+
+fn is_stream(request: &Value) -> bool {
+    request
+        .get("stream")
+        .and_then(|s| s.as_bool())
+        .unwrap_or(false)
+}
+
+// ============================================================================
+// Handlers
+// ============================================================================
+
+/// Anthropic-compatible messages endpoint used by Claude Code
+async fn handle_messages(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Json(mut request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let permit = acquire_slot(&state, &headers).await?;
+    expand_template(&state, &headers, &mut request, true).await?;
+    let mut route = route_request(&state, &mut request).await?;
+    let shadow = shadow_for(&state, &mut route).await;
+    let (rules, transforms) = {
+        let settings = state.settings.read().await;
+        (
+            router::system_prompt_rules(&settings, &route),
+            router::transform_rules(&settings, &route),
+        )
+    };
+    translate::apply_system_rules(&mut request, &rules);
+    transform::apply_rules(&mut request, &mut route, &transforms);
+    let redactor = redact_request(&state, &route, &mut request).await;
+    let requested_model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or(&route.model)
+        .to_string();
+    let stream = is_stream(&request);
+    log::info!(
+        "Routing message request for '{}' to {}/{} (stream: {})",
+        requested_model,
+        route.provider.provider,
+        route.model,
+        stream
+    );
+
+    let ctx = RequestContext::new(&headers, requested_model.clone());
+    let prompt_tokens = limits::estimate_prompt_tokens(&request);
+    let mut body = translate::anthropic_to_openai_request(&request);
+    let repair = repair_plan(&state, &body).await;
+    let plan = structured::prepare_request(
+        &mut body,
+        StructuredOutputMode::for_provider(&route.provider),
+    );
+    if let Some(shadow) = shadow {
+        spawn_shadow(&state, shadow, body.clone(), &requested_model);
+    }
+
+    // Native Anthropic providers get the request untouched
+    if route.provider.provider == LLMProvider::Anthropic && route.provider.adapter.is_none() {
+        if redactor.is_some() {
+            disable_stream(&mut request);
+        }
+        let result = send_anthropic_native(&state.http, &route, request, &headers).await;
+        let response = complete_dispatch(&state, &route, &ctx, result).await?;
+        if stream && redactor.is_none() {
+            let meter = StreamMeter {
+                tap: StreamUsageTap::default(),
+                state: state.clone(),
+                route: route.clone(),
+                ctx,
+                prompt_tokens,
+            };
+            return Ok(sse_response(Body::from_stream(hold_permit(
+                meter_stream(upstream_body_stream(response), meter),
+                permit,
+            ))));
+        }
+        let mut body: Value = response
+            .json()
+            .await
+            .map_err(|_| StatusCode::BAD_GATEWAY.into_response())?;
+        log_request(
+            &state,
+            &route,
+            &ctx,
+            StatusCode::OK.as_u16(),
+            usage::extract_usage(&body),
+        );
+        if let Some(redactor) = &redactor {
+            redactor.restore(&mut body);
+            if stream {
+                return Ok(sse_response(Body::from(translate::message_as_events(
+                    &body,
+                ))));
+            }
+        }
+        return Ok(Json(body).into_response());
+    }
+
+    // Emulated or repaired structured output and restored placeholders need the whole
+    // response, which is then replayed as a stream
+    let buffered = plan.is_some() || repair.is_some() || redactor.is_some();
+    if buffered {
+        disable_stream(&mut body);
+    }
+    let result = dispatch_chat(&state, &mut route, &requested_model, body).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+
+    if stream && !buffered {
+        let reasoning = state.settings.read().await.reasoning_output;
+        let translator = AnthropicStreamTranslator::new(&requested_model, reasoning);
+        let meter = StreamMeter {
+            tap: StreamUsageTap::default(),
+            state: state.clone(),
+            route: route.clone(),
+            ctx,
+            prompt_tokens,
+        };
+        return Ok(sse_response(Body::from_stream(hold_permit(
+            meter_stream(translated_stream(response, translator), meter),
+            permit,
+        ))));
+    }
+
+    let mut body = read_completion(&state, &route, &ctx, response).await?;
+    if let Some(plan) = &plan {
+        structured::finish_response(&mut body, plan);
+    }
+    if let Some(repair) = &repair {
+        repair_structured_output(&state, &mut body, repair).await;
+    }
+    if let Some(redactor) = &redactor {
+        redactor.restore(&mut body);
+    }
+    if stream {
+        let reasoning = state.settings.read().await.reasoning_output;
+        let mut translator = AnthropicStreamTranslator::new(&requested_model, reasoning);
+        let mut events = translator.push(translate::completion_as_chunks(&body).as_bytes());
+        events.push_str(&translator.finish());
+        return Ok(sse_response(Body::from(events)));
+    }
+    Ok(Json(translate::openai_to_anthropic_response(
+        &body,
+        &requested_model,
+    ))
+    .into_response())
+}
+
+/// Answer a chat completion request with the route it would take and its estimated
+/// cost, without queueing it, admitting it or calling any provider
+async fn dry_run(
+    state: &GatewayAppState,
+    headers: &HeaderMap,
+    mut request: Value,
+) -> Result<Response, Response> {
+    expand_template(state, headers, &mut request, false).await?;
+    let (mut route, fallbacks, _) = plan_route(state, &mut request).await?;
+    let local = guardrails::enforce(&*state.settings.read().await, &request, &route)
+        .map_err(|e| (StatusCode::FORBIDDEN, e).into_response())?;
+    if let Some(local) = local {
+        route = local;
+    }
+    Ok(Json(dryrun::estimate(&route, &fallbacks, &request)).into_response())
+}
+
+/// OpenAI-compatible chat completions endpoint
+async fn handle_chat_completions(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Json(mut request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    if headers.contains_key(DRY_RUN_HEADER) {
+        return dry_run(&state, &headers, request).await;
+    }
+    let permit = acquire_slot(&state, &headers).await?;
+    expand_template(&state, &headers, &mut request, false).await?;
+    let mut route = route_request(&state, &mut request).await?;
+    let shadow = shadow_for(&state, &mut route).await;
+    let (rules, transforms) = {
+        let settings = state.settings.read().await;
+        (
+            router::system_prompt_rules(&settings, &route),
+            router::transform_rules(&settings, &route),
+        )
+    };
+    translate::apply_openai_system_rules(&mut request, &rules);
+    transform::apply_rules(&mut request, &mut route, &transforms);
+    let redactor = redact_request(&state, &route, &mut request).await;
+    let stream = is_stream(&request);
+    log::info!(
+        "Routing chat completion to {}/{} (stream: {})",
+        route.provider.provider,
+        route.model,
+        stream
+    );
+
+    let requested_model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or(&route.model)
+        .to_string();
+    let repair = repair_plan(&state, &request).await;
+    let plan = structured::prepare_request(
+        &mut request,
+        StructuredOutputMode::for_provider(&route.provider),
+    );
+    if let Some(shadow) = shadow {
+        spawn_shadow(&state, shadow, request.clone(), &requested_model);
+    }
+    let ctx = RequestContext::new(&headers, requested_model.clone());
+    let prompt_tokens = limits::estimate_prompt_tokens(&request);
+    let buffered = plan.is_some() || repair.is_some() || redactor.is_some();
+    if buffered {
+        disable_stream(&mut request);
+    }
+    let result = dispatch_chat(&state, &mut route, &requested_model, request).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+
+    if stream && !buffered {
+        let meter = StreamMeter {
+            tap: StreamUsageTap::default(),
+            state: state.clone(),
+            route: route.clone(),
+            ctx,
+            prompt_tokens,
+        };
+        return Ok(sse_response(Body::from_stream(hold_permit(
+            meter_stream(upstream_body_stream(response), meter),
+            permit,
+        ))));
+    }
+
+    let mut body = read_completion(&state, &route, &ctx, response).await?;
+    if let Some(plan) = &plan {
+        structured::finish_response(&mut body, plan);
+    }
+    if let Some(repair) = &repair {
+        repair_structured_output(&state, &mut body, repair).await;
+    }
+    if let Some(redactor) = &redactor {
+        redactor.restore(&mut body);
+    }
+    if stream {
+        return Ok(sse_response(Body::from(translate::completion_as_chunks(
+            &body,
+        ))));
+    }
+    Ok(Json(body).into_response())
+}
+
+/// OpenAI-compatible embeddings endpoint
+async fn handle_embeddings(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let _permit = acquire_slot(&state, &headers).await?;
+    let requested_model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let mut route = router::direct_route(
+        &*state.settings.read().await,
+        state.routed_model(&requested_model),
+    )
+    .ok_or_else(|| StatusCode::SERVICE_UNAVAILABLE.into_response())?;
+    route.provider.api_key = state.keys.select(&route.provider, Instant::now());
+
+    let api = EmbeddingApi::for_provider(&route.provider);
+    let body = api
+        .map_request(&request, &route.model)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    log::info!(
+        "Routing embeddings to {}/{}",
+        route.provider.provider,
+        route.model
+    );
+
+    let ctx = RequestContext::new(&headers, requested_model);
+    let result = send_embeddings(&state.http, &route, api, body).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY.into_response())?;
+    let usage = match api.reported_tokens(&body) {
+        Some(input_tokens) => TokenUsage {
+            input_tokens,
+            ..Default::default()
+        },
+        None => TokenUsage {
+            input_tokens: embeddings::estimate_input_tokens(&request),
+            estimated: true,
+            ..Default::default()
+        },
+    };
+    let body = api
+        .map_response(
+            body,
+            &route.model,
+            embeddings::wants_base64(&request),
+            usage.input_tokens,
+        )
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e).into_response())?;
+
+    log_request(&state, &route, &ctx, StatusCode::OK.as_u16(), Some(usage));
+    Ok(Json(body).into_response())
+}
+
+/// OpenAI-compatible image generation endpoint
+async fn handle_image_generations(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let _permit = acquire_slot(&state, &headers).await?;
+    let requested_model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let mut route = router::direct_route(
+        &*state.settings.read().await,
+        state.routed_model(&requested_model),
+    )
+    .ok_or_else(|| StatusCode::SERVICE_UNAVAILABLE.into_response())?;
+    route.provider.api_key = state.keys.select(&route.provider, Instant::now());
+
+    let api = ImageApi::for_provider(&route.provider);
+    let body = api
+        .map_request(&request, &route.model)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    log::info!(
+        "Routing image generation to {}/{}",
+        route.provider.provider,
+        route.model
+    );
+
+    let ctx = RequestContext::new(&headers, requested_model);
+    let result = send_image_generation(&state.http, &route, api, body).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+    let mut body: Value = response
+        .json()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY.into_response())?;
+    images::unify_format(
+        state.http.shared(),
+        &mut body,
+        ImageFormat::from_request(&request),
+    )
+    .await
+    .map_err(|e| (StatusCode::BAD_GATEWAY, e).into_response())?;
+
+    let usage = usage::extract_usage(&body);
+    let cost_usd = route
+        .provider
+        .models
+        .iter()
+        .find(|m| m.id == route.model)
+        .and_then(|model| {
+            usage::image_cost(
+                &route.provider.provider,
+                model,
+                images::image_count(&body),
+                usage,
+            )
+        });
+    log_request_cost(
+        &state,
+        &route,
+        &ctx,
+        StatusCode::OK.as_u16(),
+        usage,
+        cost_usd,
+    );
+    Ok(Json(body).into_response())
+}
+
+/// OpenAI-compatible audio transcription endpoint
+async fn handle_audio_transcriptions(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let _permit = acquire_slot(&state, &headers).await?;
+    let upload = AudioUpload::read(multipart)
+        .await
+        .map_err(|e| e.into_response())?;
+    let requested_model = upload.field("model").unwrap_or_default().to_string();
+    let mut route = router::direct_route(
+        &*state.settings.read().await,
+        state.routed_model(&requested_model),
+    )
+    .ok_or_else(|| StatusCode::SERVICE_UNAVAILABLE.into_response())?;
+    route.provider.api_key = state.keys.select(&route.provider, Instant::now());
+    log::info!(
+        "Routing transcription of {} bytes to {}/{}",
+        upload.file.len(),
+        route.provider.provider,
+        route.model
+    );
+
+    let form = upload
+        .into_form(&route.model)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    let ctx = RequestContext::new(&headers, requested_model);
+    let result = send_transcription(&state.http, &route, form).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+    let content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY.into_response())?;
+
+    // Text, SRT and VTT responses carry no usage
+    let body = serde_json::from_slice::<Value>(&bytes).ok();
+    let usage = body.as_ref().and_then(audio::transcription_usage);
+    let seconds = body.as_ref().and_then(audio::transcription_seconds);
+    let cost_usd = route
+        .provider
+        .models
+        .iter()
+        .find(|m| m.id == route.model)
+        .and_then(|model| usage::audio_cost(&route.provider.provider, model, seconds, usage));
+    log_request_cost(
+        &state,
+        &route,
+        &ctx,
+        StatusCode::OK.as_u16(),
+        usage,
+        cost_usd,
+    );
+
+    let mut response = Body::from(bytes).into_response();
+    if let Some(content_type) = content_type {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, content_type);
+    }
+    Ok(response)
+}
+
+/// OpenAI-compatible text-to-speech endpoint; audio is streamed back as it arrives
+async fn handle_audio_speech(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let permit = acquire_slot(&state, &headers).await?;
+    let requested_model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let requested_voice = request
+        .get("voice")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let (mut route, voice) = router::speech_route(
+        &*state.settings.read().await,
+        state.routed_model(&requested_model),
+        requested_voice,
+    )
+    .ok_or_else(|| StatusCode::SERVICE_UNAVAILABLE.into_response())?;
+    route.provider.api_key = state.keys.select(&route.provider, Instant::now());
+
+    let body = audio::map_speech_request(&request, &route.model, voice.as_deref())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    log::info!(
+        "Routing speech to {}/{} (voice: {})",
+        route.provider.provider,
+        route.model,
+        body["voice"]
+    );
+
+    let ctx = RequestContext::new(&headers, requested_model);
+    let result = send_speech(&state.http, &route, body).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+
+    let input = audio::speech_input(&request);
+    let cost_usd = route
+        .provider
+        .models
+        .iter()
+        .find(|m| m.id == route.model)
+        .map(|model| usage::speech_cost(&route.provider.provider, model, input));
+    log_request_cost(
+        &state,
+        &route,
+        &ctx,
+        StatusCode::OK.as_u16(),
+        Some(usage::speech_usage(input)),
+        cost_usd,
+    );
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .cloned()
+        .unwrap_or(header::HeaderValue::from_static("audio/mpeg"));
+    let mut response =
+        Body::from_stream(hold_permit(upstream_body_stream(response), permit)).into_response();
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, content_type);
+    Ok(response)
+}
+
+/// OpenAI-compatible moderation endpoint
+async fn handle_moderations(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Json(mut request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let _permit = acquire_slot(&state, &headers).await?;
+    let requested_model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let mut route = router::direct_route(
+        &*state.settings.read().await,
+        state.routed_model(&requested_model),
+    )
+    .ok_or_else(|| StatusCode::SERVICE_UNAVAILABLE.into_response())?;
+    route.provider.api_key = state.keys.select(&route.provider, Instant::now());
+    request["model"] = Value::String(route.model.clone());
+    log::info!(
+        "Routing moderation to {}/{}",
+        route.provider.provider,
+        route.model
+    );
+
+    let ctx = RequestContext::new(&headers, requested_model);
+    let result = send_moderation(&state.http, &route, request).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY.into_response())?;
+    log_request(&state, &route, &ctx, StatusCode::OK.as_u16(), None);
+    Ok(Json(body).into_response())
+}
+
+/// Legacy OpenAI text completions endpoint, emulated with chat completions for models
+/// that don't support it
+async fn handle_completions(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let permit = acquire_slot(&state, &headers).await?;
+    // Batched and token ID prompts can only be served by completion models
+    let converted = legacy::completion_to_chat_request(&request);
+    let mut chat = match &converted {
+        Ok(chat) => chat.clone(),
+        Err(_) => legacy::routing_request(&request),
+    };
+    let mut route = route_request(&state, &mut chat).await?;
+    let requested_model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or(&route.model)
+        .to_string();
+    let stream = is_stream(&request);
+    let native = legacy::supports_completions(&route.provider, &route.model);
+    log::info!(
+        "Routing legacy completion to {}/{} (stream: {}, native: {})",
+        route.provider.provider,
+        route.model,
+        stream,
+        native
+    );
+
+    let ctx = RequestContext::new(&headers, requested_model.clone());
+    let prompt_tokens = limits::estimate_prompt_tokens(&chat);
+    let meter = |ctx, route: &RouteTarget| StreamMeter {
+        tap: StreamUsageTap::default(),
+        state: state.clone(),
+        route: route.clone(),
+        ctx,
+        prompt_tokens,
+    };
+
+    if native {
+        let result = send_legacy_completion(&state.http, &route, request).await;
+        let response = complete_dispatch(&state, &route, &ctx, result).await?;
+        if stream {
+            return Ok(sse_response(Body::from_stream(hold_permit(
+                meter_stream(upstream_body_stream(response), meter(ctx, &route)),
+                permit,
+            ))));
+        }
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|_| StatusCode::BAD_GATEWAY.into_response())?;
+        log_request(
+            &state,
+            &route,
+            &ctx,
+            StatusCode::OK.as_u16(),
+            usage::extract_usage(&body),
+        );
+        return Ok(Json(body).into_response());
+    }
+
+    if let Err(e) = converted {
+        return Err((StatusCode::BAD_REQUEST, e).into_response());
+    }
+    let echo = legacy::echo_prefix(&request);
+    let result = dispatch_chat(&state, &mut route, &requested_model, chat).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+    if stream {
+        let translator = CompletionStreamTranslator::new(echo);
+        return Ok(sse_response(Body::from_stream(hold_permit(
+            meter_stream(translated_stream(response, translator), meter(ctx, &route)),
+            permit,
+        ))));
+    }
+    let body = read_completion(&state, &route, &ctx, response).await?;
+    Ok(Json(legacy::chat_to_completion_response(&body, echo.as_deref())).into_response())
+}
+
+/// OpenAI Responses API, served through chat completions
+async fn handle_responses(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let permit = acquire_slot(&state, &headers).await?;
+    let mut chat = responses::responses_to_chat_request(&request)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    let mut route = route_request(&state, &mut chat).await?;
+    let requested_model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or(&route.model)
+        .to_string();
+    let stream = is_stream(&request);
+    log::info!(
+        "Routing Responses request to {}/{} (stream: {})",
+        route.provider.provider,
+        route.model,
+        stream
+    );
+
+    let ctx = RequestContext::new(&headers, requested_model.clone());
+    let prompt_tokens = limits::estimate_prompt_tokens(&chat);
+    let result = dispatch_chat(&state, &mut route, &requested_model, chat).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+    if stream {
+        let translator = ResponsesStreamTranslator::new(&requested_model);
+        let meter = StreamMeter {
+            tap: StreamUsageTap::default(),
+            state: state.clone(),
+            route: route.clone(),
+            ctx,
+            prompt_tokens,
+        };
+        return Ok(sse_response(Body::from_stream(hold_permit(
+            meter_stream(translated_stream(response, translator), meter),
+            permit,
+        ))));
+    }
+    let body = read_completion(&state, &route, &ctx, response).await?;
+    Ok(Json(responses::chat_to_responses_response(
+        &body,
+        &requested_model,
+    ))
+    .into_response())
+}
+
+/// Gemini `generateContent`, `streamGenerateContent` and `countTokens`, served through
+/// chat completions
+async fn handle_gemini(
+    State(state): State<GatewayAppState>,
+    Path(target): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let Some((model, method)) = gemini::split_target(&target) else {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    };
+    let stream = match method {
+        "generateContent" => false,
+        "streamGenerateContent" => true,
+        "countTokens" => {
+            let chat = gemini::gemini_to_chat_request(&request, model, false);
+            let tokens = limits::estimate_prompt_tokens(&chat);
+            return Ok(Json(serde_json::json!({ "totalTokens": tokens })).into_response());
+        }
+        _ => return Err(StatusCode::NOT_FOUND.into_response()),
+    };
+
+    let permit = acquire_slot(&state, &headers).await?;
+    let mut chat = gemini::gemini_to_chat_request(&request, model, stream);
+    let mut route = route_request(&state, &mut chat).await?;
+    log::info!(
+        "Routing Gemini request to {}/{} (stream: {})",
+        route.provider.provider,
+        route.model,
+        stream
+    );
+
+    let ctx = RequestContext::new(&headers, model.to_string());
+    let prompt_tokens = limits::estimate_prompt_tokens(&chat);
+    let result = dispatch_chat(&state, &mut route, model, chat).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+    if stream {
+        let translator = GeminiStreamTranslator::new(model);
+        let meter = StreamMeter {
+            tap: StreamUsageTap::default(),
+            state: state.clone(),
+            route: route.clone(),
+            ctx,
+            prompt_tokens,
+        };
+        return Ok(sse_response(Body::from_stream(hold_permit(
+            meter_stream(translated_stream(response, translator), meter),
+            permit,
+        ))));
+    }
+    let body = read_completion(&state, &route, &ctx, response).await?;
+    Ok(Json(gemini::chat_to_gemini_response(&body, model)).into_response())
+}
+
+/// Ollama `/api/chat`, served through chat completions
+async fn handle_ollama_chat(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Result<Response, Response> {
+    let state = state.for_request(&headers).await?;
+    let permit = acquire_slot(&state, &headers).await?;
+    let mut chat = ollama::ollama_to_chat_request(&request);
+    let mut route = route_request(&state, &mut chat).await?;
+    let requested_model = request
+        .get("model")
+        .and_then(|m| m.as_str())
+        .unwrap_or(&route.model)
+        .to_string();
+    let stream = ollama::is_stream(&request);
+    log::info!(
+        "Routing Ollama chat to {}/{} (stream: {})",
+        route.provider.provider,
+        route.model,
+        stream
+    );
+
+    let ctx = RequestContext::new(&headers, requested_model.clone());
+    let prompt_tokens = limits::estimate_prompt_tokens(&chat);
+    let result = dispatch_chat(&state, &mut route, &requested_model, chat).await;
+    let response = complete_dispatch(&state, &route, &ctx, result).await?;
+    if stream {
+        // Metered ahead of the translation: the tap reads SSE, not NDJSON
+        let meter = StreamMeter {
+            tap: StreamUsageTap::default(),
+            state: state.clone(),
+            route: route.clone(),
+            ctx,
+            prompt_tokens,
+        };
+        let upstream = Box::pin(meter_stream(upstream_body_stream(response), meter));
+        let translator = OllamaStreamTranslator::new(&requested_model);
+        let body = Body::from_stream(hold_permit(translate_stream(upstream, translator), permit));
+        return Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response());
+    }
+    let body = read_completion(&state, &route, &ctx, response).await?;
+    Ok(Json(ollama::chat_to_ollama_response(&body, &requested_model)).into_response())
+}
+
+/// Ollama `/api/tags`: the routable models and aliases
+async fn handle_ollama_tags(State(state): State<GatewayAppState>) -> Json<Value> {
+    Json(ollama::list_tags(&*state.settings.read().await))
+}
+
+/// Options of a batch created over HTTP
+#[derive(Debug, serde::Deserialize)]
+struct BatchOptions {
+    concurrency: Option<u32>,
+}
+
+type BatchError = (StatusCode, String);
+
+/// Run the batch store operation `f` against the app database
+fn batch_store<T>(
+    state: &GatewayAppState,
+    f: impl FnOnce(&rusqlite::Connection) -> rusqlite::Result<T>,
+) -> Result<T, BatchError> {
+    let db = state.app.state::<AgentDb>();
+    let conn =
+        db.0.get()
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    f(&conn).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Date range of a usage query (YYYY-MM-DD, local time); the current month by default
+#[derive(Debug, serde::Deserialize)]
+struct UsageQuery {
+    start: Option<String>,
+    end: Option<String>,
+}
+
+/// Usage and spend of the API key the request authenticates with
+async fn handle_usage(
+    State(state): State<GatewayAppState>,
+    headers: HeaderMap,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<KeyUsage>, BatchError> {
+    use chrono::{Datelike, NaiveDate};
+
+    let key = client_key(&headers).ok_or((
+        StatusCode::UNAUTHORIZED,
+        "Send the API key to report usage for".to_string(),
+    ))?;
+    let date = |date: Option<String>, default: NaiveDate| match date {
+        Some(date) => NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid date '{}': {}", date, e),
+            )
+        }),
+        None => Ok(default),
+    };
+    let today = chrono::Local::now().date_naive();
+    let start = date(query.start, today.with_day(1).unwrap_or(today))?;
+    let end = date(query.end, today)?;
+    batch_store(&state, |conn| {
+        usage::key_usage(conn, &usage::key_fingerprint(key), start, end)
+    })
+    .map(Json)
+}
+
+fn batch_or_not_found(job: Option<BatchJob>) -> Result<BatchJob, BatchError> {
+    job.ok_or_else(|| (StatusCode::NOT_FOUND, "No such batch".to_string()))
+}
+
+/// Start a batch from a JSONL request file sent as the body
+async fn handle_create_batch(
+    State(state): State<GatewayAppState>,
+    Query(options): Query<BatchOptions>,
+    input: String,
+) -> Result<Json<BatchJob>, BatchError> {
+    let requests = batches::parse_requests(&input).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let job = batch_store(&state, |conn| {
+        batches::create_batch(conn, &requests, options.concurrency)
+    })?;
+    let port = state.status.read().await.port;
+    tokio::spawn(batches::run(state.app.clone(), port, job.id.clone()));
+    Ok(Json(job))
+}
+
+async fn handle_list_batches(
+    State(state): State<GatewayAppState>,
+) -> Result<Json<Vec<BatchJob>>, BatchError> {
+    batch_store(&state, batches::list_batches).map(Json)
+}
+
+async fn handle_get_batch(
+    State(state): State<GatewayAppState>,
+    Path(id): Path<String>,
+) -> Result<Json<BatchJob>, BatchError> {
+    batch_or_not_found(batch_store(&state, |conn| batches::get_batch(conn, &id))?).map(Json)
+}
+
+/// Results of a batch as an OpenAI-style JSONL output file
+async fn handle_batch_output(
+    State(state): State<GatewayAppState>,
+    Path(id): Path<String>,
+) -> Result<Response, BatchError> {
+    let (job, results) = batch_store(&state, |conn| {
+        Ok((
+            batches::get_batch(conn, &id)?,
+            batches::batch_results(conn, &id)?,
+        ))
+    })?;
+    batch_or_not_found(job)?;
+    let output: String = results
+        .iter()
+        .map(|result| format!("{}\n", result.output_line()))
+        .collect();
+    Ok(([(header::CONTENT_TYPE, "application/jsonl")], output).into_response())
+}
+
+async fn handle_cancel_batch(
+    State(state): State<GatewayAppState>,
+    Path(id): Path<String>,
+) -> Result<Json<BatchJob>, BatchError> {
+    let job = batch_store(&state, |conn| {
+        batches::cancel_batch(conn, &id)?;
+        batches::get_batch(conn, &id)
+    })?;
+    batch_or_not_found(job).map(Json)
+}
+
+async fn handle_list_models(
+    State(_state): State<GatewayAppState>,
+) -> Result<Json<Value>, StatusCode> {
+    // TODO: Return list of available models
+    Ok(Json(serde_json::json!({
+        "object": "list",
+        "data": []
+    })))
+}
+
+async fn handle_health(State(state): State<GatewayAppState>) -> Result<Json<Value>, StatusCode> {
+    let now = chrono::Local::now().naive_local();
+    let maintenance = router::providers_in_maintenance(&*state.settings.read().await, now);
+    Ok(Json(serde_json::json!({
+        "status": "ok",
+        "version": "0.1.0",
+        "maintenance": maintenance
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{Stream, StreamExt};
+
+    async fn next_text(stream: &mut (impl Stream<Item = Result<Bytes, ()>> + Unpin)) -> String {
+        let chunk = stream.next().await.unwrap().unwrap();
+        String::from_utf8(chunk.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive() {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let interval = Duration::from_millis(20);
+        let mut stream = Box::pin(keep_alive(rx, interval));
+
+        // Quiet between events: a heartbeat
+        assert_eq!(next_text(&mut stream).await, KEEPALIVE_COMMENT);
+
+        // Quiet inside an event, here with CRLF line endings: nothing until it ends
+        tx.unbounded_send(Ok(Bytes::from_static(b"data: {}\r\n")))
+            .unwrap();
+        assert_eq!(next_text(&mut stream).await, "data: {}\r\n");
+        let quiet = tokio::time::timeout(interval * 3, next_text(&mut stream)).await;
+        assert!(quiet.is_err());
+        tx.unbounded_send(Ok(Bytes::from_static(b"\r\n"))).unwrap();
+        assert_eq!(next_text(&mut stream).await, "\r\n");
+        assert_eq!(next_text(&mut stream).await, KEEPALIVE_COMMENT);
+
+        drop(tx);
+        assert!(stream.next().await.is_none());
+    }
+}
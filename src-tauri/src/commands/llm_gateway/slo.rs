@@ -0,0 +1,221 @@
+//! Provider SLOs - Demotion of providers that breach their latency or error objectives
+//!
+//! Providers may declare a p95 latency and an error rate they are expected to stay
+//! within. The outcome of every upstream call is kept for a rolling window; once a window
+//! holds enough requests to judge and breaches an objective, the provider's effective
+//! priority is lowered so routing prefers the others, and [`SLO_EVENT`] is emitted. The
+//! provider is restored, with another event, as soon as its window meets the objectives
+//! again or has too few requests left to judge.
+//!
+//! Only connection failures and 5xx answers count as errors; 4xx answers are the client's.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{GatewaySettings, ProviderConfig};
+
+/// Event emitted when a provider is demoted or restored
+pub const SLO_EVENT: &str = "llm-gateway-slo";
+
+/// Objectives a provider is held to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SloConfig {
+    /// Highest acceptable 95th percentile latency to the first response byte
+    #[serde(default)]
+    pub max_p95_latency_ms: Option<u64>,
+    /// Highest acceptable share of failed requests, 0.0-1.0
+    #[serde(default)]
+    pub max_error_rate: Option<f64>,
+    /// Length of the rolling window
+    #[serde(default = "default_window_minutes")]
+    pub window_minutes: u32,
+    /// Requests the window needs before the provider is judged
+    #[serde(default = "default_min_requests")]
+    pub min_requests: u32,
+    /// Added to the provider's priority while it is demoted
+    #[serde(default = "default_priority_penalty")]
+    pub priority_penalty: i32,
+}
+
+fn default_window_minutes() -> u32 {
+    10
+}
+
+fn default_min_requests() -> u32 {
+    20
+}
+
+fn default_priority_penalty() -> i32 {
+    100
+}
+
+/// Payload of [`SLO_EVENT`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SloEvent {
+    pub provider: String,
+    /// Demoted, or restored when false
+    pub demoted: bool,
+    /// Objective that was breached
+    pub reason: Option<String>,
+}
+
+struct Sample {
+    at: Instant,
+    latency_ms: Option<u64>,
+    error: bool,
+}
+
+#[derive(Default)]
+struct ProviderWindow {
+    samples: VecDeque<Sample>,
+    demoted: bool,
+}
+
+/// Rolling request outcomes and demotions, by provider name
+#[derive(Default)]
+pub struct SloTracker {
+    providers: Mutex<HashMap<String, ProviderWindow>>,
+}
+
+/// Objective `samples` breach, if there are enough of them to tell
+fn breach(slo: &SloConfig, samples: &VecDeque<Sample>) -> Option<String> {
+    if samples.is_empty() || samples.len() < slo.min_requests as usize {
+        return None;
+    }
+    let errors = samples.iter().filter(|s| s.error).count();
+    let error_rate = errors as f64 / samples.len() as f64;
+    if let Some(max) = slo.max_error_rate.filter(|max| error_rate > *max) {
+        return Some(format!(
+            "error rate {:.0}% above {:.0}%",
+            error_rate * 100.0,
+            max * 100.0
+        ));
+    }
+
+    let mut latencies: Vec<u64> = samples.iter().filter_map(|s| s.latency_ms).collect();
+    latencies.sort_unstable();
+    let p95 = latencies
+        .get((latencies.len() * 95).div_ceil(100).saturating_sub(1))
+        .copied()?;
+    slo.max_p95_latency_ms
+        .filter(|max| p95 > *max)
+        .map(|max| format!("p95 latency {}ms above {}ms", p95, max))
+}
+
+impl SloTracker {
+    /// Drop samples older than the window and update the demotion, returning the event
+    /// for a change
+    fn evaluate(
+        slo: &SloConfig,
+        name: &str,
+        window: &mut ProviderWindow,
+        now: Instant,
+    ) -> Option<SloEvent> {
+        let length = Duration::from_secs(slo.window_minutes as u64 * 60);
+        while window
+            .samples
+            .front()
+            .is_some_and(|s| now.duration_since(s.at) > length)
+        {
+            window.samples.pop_front();
+        }
+        let reason = breach(slo, &window.samples);
+        if reason.is_some() == window.demoted {
+            return None;
+        }
+        window.demoted = reason.is_some();
+        Some(SloEvent {
+            provider: name.to_string(),
+            demoted: window.demoted,
+            reason,
+        })
+    }
+
+    /// Note the outcome of a call to `provider`
+    pub fn record(
+        &self,
+        provider: &ProviderConfig,
+        latency_ms: Option<u64>,
+        error: bool,
+        now: Instant,
+    ) -> Option<SloEvent> {
+        let slo = provider.slo.as_ref()?;
+        let mut providers = self.providers.lock().unwrap_or_else(|e| e.into_inner());
+        let window = providers.entry(provider.name.clone()).or_default();
+        window.samples.push_back(Sample {
+            at: now,
+            latency_ms,
+            error,
+        });
+        Self::evaluate(slo, &provider.name, window, now)
+    }
+
+    /// Priority penalties of the providers demoted at `now`, by name, with the events
+    /// of providers restored since their windows emptied
+    pub fn demotions(
+        &self,
+        settings: &GatewaySettings,
+        now: Instant,
+    ) -> (HashMap<String, i32>, Vec<SloEvent>) {
+        let mut providers = self.providers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut penalties = HashMap::new();
+        let mut events = Vec::new();
+        for provider in &settings.providers {
+            let (Some(slo), Some(window)) = (&provider.slo, providers.get_mut(&provider.name))
+            else {
+                continue;
+            };
+            events.extend(Self::evaluate(slo, &provider.name, window, now));
+            if window.demoted {
+                penalties.insert(provider.name.clone(), slo.priority_penalty);
+            }
+        }
+        (penalties, events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demotion() {
+        let mut settings = GatewaySettings::default();
+        settings.providers[0].slo = Some(SloConfig {
+            max_p95_latency_ms: Some(2000),
+            max_error_rate: Some(0.2),
+            window_minutes: 1,
+            min_requests: 4,
+            priority_penalty: 50,
+        });
+        let provider = settings.providers[0].clone();
+        let tracker = SloTracker::default();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(tracker.record(&provider, Some(500), false, now), None);
+        }
+        let event = tracker.record(&provider, None, true, now).unwrap();
+        assert!(event.demoted);
+        assert_eq!(event.reason.as_deref(), Some("error rate 25% above 20%"));
+        let (penalties, _) = tracker.demotions(&settings, now);
+        assert_eq!(penalties.get(&provider.name), Some(&50));
+
+        // Once the failures age out of the window there is too little left to judge
+        let later = now + Duration::from_secs(90);
+        let (penalties, events) = tracker.demotions(&settings, later);
+        assert!(penalties.is_empty());
+        assert!(!events[0].demoted);
+
+        for _ in 0..3 {
+            tracker.record(&provider, Some(500), false, later);
+        }
+        let event = tracker.record(&provider, Some(9000), false, later).unwrap();
+        assert_eq!(
+            event.reason.as_deref(),
+            Some("p95 latency 9000ms above 2000ms")
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! SSE Framing - Where events end in a relayed server-sent event stream
+//!
+//! Upstream chunks split events anywhere, so anything inserted into a relayed stream
+//! has to wait for the blank line closing the current event: `\n\n`, or `\r\n\r\n`
+//! from servers ending lines with CRLF.
+
+/// Follows the bytes of a stream to find where its events end
+#[derive(Debug, Clone, Copy)]
+pub struct EventBoundary {
+    /// The last four bytes seen, enough to hold `\r\n\r\n`
+    tail: [u8; 4],
+}
+
+impl Default for EventBoundary {
+    /// A stream starts between events
+    fn default() -> Self {
+        Self { tail: *b"\r\n\r\n" }
+    }
+}
+
+impl EventBoundary {
+    /// Whether the bytes so far end between events
+    pub fn at_boundary(&self) -> bool {
+        self.tail.ends_with(b"\n\n") || self.tail == *b"\r\n\r\n"
+    }
+
+    /// Follow `chunk`, returning the length of its prefix through the last event end
+    /// in it, if any
+    pub fn push(&mut self, chunk: &[u8]) -> Option<usize> {
+        let mut end = None;
+        for (i, &byte) in chunk.iter().enumerate() {
+            self.tail = [self.tail[1], self.tail[2], self.tail[3], byte];
+            if self.at_boundary() {
+                end = Some(i + 1);
+            }
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_boundary() {
+        let mut boundary = EventBoundary::default();
+        assert!(boundary.at_boundary());
+        assert_eq!(boundary.push(b"data: {}\n\ndata: {\"id\""), Some(10));
+        assert!(!boundary.at_boundary());
+        // A single newline ends a line, not the event it belongs to
+        assert_eq!(boundary.push(b": 1}\n"), None);
+        assert!(!boundary.at_boundary());
+        assert_eq!(boundary.push(b"\n"), Some(1));
+        assert!(boundary.at_boundary());
+        assert_eq!(boundary.push(b""), None);
+        assert!(boundary.at_boundary());
+
+        // CRLF line endings, split across chunks
+        let mut boundary = EventBoundary::default();
+        assert_eq!(boundary.push(b"data: {}\r\n\r"), None);
+        assert!(!boundary.at_boundary());
+        assert_eq!(boundary.push(b"\n"), Some(1));
+        assert!(boundary.at_boundary());
+    }
+}
@@ -0,0 +1,456 @@
+//! Structured Output - JSON schema mode and forced-tool extraction across providers
+//!
+//! OpenAI `response_format: json_schema` requests and Anthropic-style extraction (a
+//! single tool forced through `tool_choice`) are passed through to providers that support
+//! them. Other providers get the schema injected as instructions, with JSON mode turned on
+//! where available, and the returned JSON is repaired and validated before it is handed
+//! back in the shape the client asked for.
+//!
+//! Whatever the provider, a response that still fails its schema can be handed to a cheap
+//! repair model along with the validation error before it reaches the client.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{LLMProvider, ProviderConfig};
+
+/// How a provider handles structured output requests
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StructuredOutputMode {
+    /// Native `json_schema` response format and forced tool calls
+    JsonSchema,
+    /// Only `json_object` mode; the schema goes into the prompt
+    JsonObject,
+    /// No JSON mode at all; the schema goes into the prompt
+    Prompt,
+}
+
+impl StructuredOutputMode {
+    /// Configured mode of a provider, else the one its API is known to support
+    pub fn for_provider(provider: &ProviderConfig) -> Self {
+        if let Some(mode) = provider.structured_output {
+            return mode;
+        }
+        match provider.provider {
+            LLMProvider::Anthropic if provider.adapter.is_none() => Self::JsonSchema,
+            LLMProvider::OpenAI
+            | LLMProvider::Gemini
+            | LLMProvider::Groq
+            | LLMProvider::Ollama
+            | LLMProvider::OpenRouter => Self::JsonSchema,
+            LLMProvider::DeepSeek
+            | LLMProvider::Moonshot
+            | LLMProvider::Qwen
+            | LLMProvider::Zhipu => Self::JsonObject,
+            _ => Self::Prompt,
+        }
+    }
+}
+
+/// Emulated structured output request, used to post-process the response
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredPlan {
+    /// Schema the response must satisfy
+    pub schema: Value,
+    /// Forced tool whose call the JSON response stands in for
+    pub tool_name: Option<String>,
+}
+
+fn schema_instructions(schema: &Value) -> String {
+    format!(
+        "Respond only with a JSON value that conforms to the following JSON Schema. \
+         Do not add any prose or code fences.\n\n{}",
+        schema
+    )
+}
+
+/// Append `text` to the system message of an OpenAI-shaped request
+fn append_system(body: &mut Value, text: &str) {
+    let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+        return;
+    };
+    match messages.first_mut() {
+        Some(first) if first["role"] == "system" => {
+            let existing = first["content"].as_str().unwrap_or_default();
+            first["content"] = Value::String(format!("{}\n\n{}", existing, text));
+        }
+        _ => messages.insert(0, json!({"role": "system", "content": text})),
+    }
+}
+
+/// Name of the single function a request forces through `tool_choice`
+fn forced_tool(body: &Value) -> Option<&str> {
+    body.get("tool_choice")?.pointer("/function/name")?.as_str()
+}
+
+/// Schema an OpenAI-shaped request expects its response to satisfy, through
+/// `response_format: json_schema` or a single forced tool
+pub fn declared_schema(body: &Value) -> Option<StructuredPlan> {
+    if body.pointer("/response_format/type") == Some(&json!("json_schema")) {
+        return Some(StructuredPlan {
+            schema: body
+                .pointer("/response_format/json_schema/schema")
+                .cloned()
+                .unwrap_or_else(|| json!({})),
+            tool_name: None,
+        });
+    }
+    let name = forced_tool(body)?.to_string();
+    let schema = body
+        .get("tools")?
+        .as_array()?
+        .iter()
+        .find(|t| t.pointer("/function/name").and_then(|n| n.as_str()) == Some(&name))?
+        .pointer("/function/parameters")
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+    Some(StructuredPlan {
+        schema,
+        tool_name: Some(name),
+    })
+}
+
+/// Rewrite an OpenAI-shaped request for a provider without native structured output.
+/// Returns `None` when the request is left untouched.
+pub fn prepare_request(body: &mut Value, mode: StructuredOutputMode) -> Option<StructuredPlan> {
+    if mode == StructuredOutputMode::JsonSchema {
+        return None;
+    }
+
+    let plan = declared_schema(body)?;
+    let obj = body.as_object_mut()?;
+    obj.remove("tools");
+    obj.remove("tool_choice");
+    match mode {
+        StructuredOutputMode::JsonObject => {
+            obj.insert(
+                "response_format".to_string(),
+                json!({"type": "json_object"}),
+            );
+        }
+        _ => {
+            obj.remove("response_format");
+        }
+    }
+    append_system(body, &schema_instructions(&plan.schema));
+    Some(plan)
+}
+
+/// Remove code fences a model wrapped around its JSON
+fn strip_fences(text: &str) -> &str {
+    let text = text.trim();
+    let Some(rest) = text.strip_prefix("```") else {
+        return text;
+    };
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+    rest.strip_suffix("```").unwrap_or(rest).trim()
+}
+
+/// Parse JSON from a model reply, repairing the usual defects: surrounding prose or
+/// fences, trailing commas and output truncated before the closing brackets.
+pub fn repair_json(text: &str) -> Option<Value> {
+    let text = strip_fences(text);
+    if let Ok(value) = serde_json::from_str(text) {
+        return Some(value);
+    }
+
+    let start = text.find(['{', '['])?;
+    let mut out = String::new();
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text[start..].chars() {
+        if in_string {
+            out.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => stack.push('}'),
+            '[' => stack.push(']'),
+            '}' | ']' => {
+                // Drop a trailing comma before the closing bracket
+                let trimmed = out.trim_end().len();
+                if out[..trimmed].ends_with(',') {
+                    out.truncate(trimmed - 1);
+                }
+                stack.pop();
+            }
+            _ => {}
+        }
+        out.push(c);
+        if stack.is_empty() {
+            break;
+        }
+    }
+
+    if in_string {
+        out.push('"');
+    }
+    let trimmed = out.trim_end().len();
+    if out[..trimmed].ends_with(',') {
+        out.truncate(trimmed - 1);
+    }
+    while let Some(close) = stack.pop() {
+        out.push(close);
+    }
+    serde_json::from_str(&out).ok()
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Check `value` against the commonly used subset of JSON Schema: `type`, `enum`,
+/// `required`, `properties` and `items`
+pub fn validate(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(ts)) => ts.iter().filter_map(|t| t.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|t| type_matches(t, value)) {
+        return Err(format!("{} should be of type {}", path, types.join(" or ")));
+    }
+
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(value) {
+            return Err(format!("{} is not one of the allowed values", path));
+        }
+    }
+
+    if let Some(obj) = value.as_object() {
+        for name in schema
+            .get("required")
+            .and_then(|r| r.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|n| n.as_str())
+        {
+            if !obj.contains_key(name) {
+                return Err(format!("{}.{} is required", path, name));
+            }
+        }
+        if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+            for (name, property) in properties {
+                if let Some(field) = obj.get(name) {
+                    validate(property, field, &format!("{}.{}", path, name))?;
+                }
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            validate(items, item, &format!("{}[{}]", path, index))?;
+        }
+    }
+    Ok(())
+}
+
+/// Repair and validate the JSON of an OpenAI-shaped response to an emulated request,
+/// turning it into the forced tool call when the client asked for one
+pub fn finish_response(response: &mut Value, plan: &StructuredPlan) {
+    let Some(message) = response.pointer_mut("/choices/0/message") else {
+        return;
+    };
+    let text = message["content"].as_str().unwrap_or_default();
+    let Some(value) = repair_json(text) else {
+        log::warn!("Structured output response is not JSON, returning it unchanged");
+        return;
+    };
+    if let Err(e) = validate(&plan.schema, &value, "$") {
+        log::warn!("Structured output response doesn't match its schema: {}", e);
+    }
+    set_response_json(response, plan, &value);
+}
+
+/// Put `value` into an OpenAI-shaped response as its content, or as the forced tool call
+fn set_response_json(response: &mut Value, plan: &StructuredPlan, value: &Value) {
+    let Some(message) = response.pointer_mut("/choices/0/message") else {
+        return;
+    };
+    match &plan.tool_name {
+        Some(name) => {
+            message["content"] = Value::Null;
+            message["tool_calls"] = json!([{
+                "id": format!("call_{}", uuid::Uuid::new_v4().simple()),
+                "type": "function",
+                "function": {"name": name, "arguments": value.to_string()},
+            }]);
+            response["choices"][0]["finish_reason"] = json!("tool_calls");
+        }
+        None => message["content"] = Value::String(value.to_string()),
+    }
+}
+
+/// JSON text of an OpenAI-shaped response: the forced tool's arguments, else the content
+fn response_json_text(response: &Value, plan: &StructuredPlan) -> Option<String> {
+    let message = response.pointer("/choices/0/message")?;
+    let arguments = plan.tool_name.as_ref().and_then(|name| {
+        message["tool_calls"]
+            .as_array()?
+            .iter()
+            .find(|c| c.pointer("/function/name").and_then(|n| n.as_str()) == Some(name))?
+            .pointer("/function/arguments")?
+            .as_str()
+    });
+    arguments
+        .or_else(|| message["content"].as_str())
+        .map(str::to_string)
+}
+
+/// Why a response doesn't satisfy its declared schema, with the offending JSON text
+pub fn check_response(response: &Value, plan: &StructuredPlan) -> Result<(), (String, String)> {
+    let text = response_json_text(response, plan).unwrap_or_default();
+    match serde_json::from_str::<Value>(&text) {
+        Ok(value) => validate(&plan.schema, &value, "$").map_err(|e| (e, text)),
+        Err(e) => Err((format!("not valid JSON: {}", e), text)),
+    }
+}
+
+/// OpenAI-shaped request asking a repair model to fix JSON that failed `error`
+pub fn repair_request(plan: &StructuredPlan, text: &str, error: &str) -> Value {
+    json!({
+        "messages": [
+            {"role": "system", "content": format!(
+                "You fix malformed JSON. Return the corrected JSON only, keeping the data \
+                 unchanged wherever possible, with no prose or code fences. It must conform \
+                 to this JSON Schema:\n\n{}",
+                plan.schema
+            )},
+            {"role": "user", "content": format!("Validation error: {}\n\nJSON:\n{}", error, text)},
+        ],
+        "temperature": 0,
+        "stream": false,
+    })
+}
+
+/// Apply a repair model's reply to a response, if it yields JSON that passes the schema
+pub fn apply_repair(
+    response: &mut Value,
+    plan: &StructuredPlan,
+    reply: &str,
+) -> Result<(), String> {
+    let value = repair_json(reply).ok_or("repair reply is not JSON")?;
+    validate(&plan.schema, &value, "$")?;
+    set_response_json(response, plan, &value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_json() {
+        assert_eq!(
+            repair_json("```json\n{\"a\": 1}\n```"),
+            Some(json!({"a": 1}))
+        );
+        assert_eq!(
+            repair_json("Here you go: {\"a\": [1, 2,], \"b\": \"x}\"} Hope it helps"),
+            Some(json!({"a": [1, 2], "b": "x}"}))
+        );
+        assert_eq!(
+            repair_json("{\"items\": [{\"name\": \"tru"),
+            Some(json!({"items": [{"name": "tru"}]}))
+        );
+        assert_eq!(repair_json("no json here"), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name", "tags"],
+            "properties": {
+                "name": {"type": "string"},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}}
+            }
+        });
+        assert!(validate(&schema, &json!({"name": "x", "tags": ["a"]}), "$").is_ok());
+        assert_eq!(
+            validate(&schema, &json!({"name": "x"}), "$").unwrap_err(),
+            "$.tags is required"
+        );
+        assert_eq!(
+            validate(&schema, &json!({"name": "x", "tags": ["c"]}), "$").unwrap_err(),
+            "$.tags[0] is not one of the allowed values"
+        );
+    }
+
+    #[test]
+    fn test_forced_tool_emulation() {
+        let mut body = json!({
+            "messages": [{"role": "user", "content": "Extract the city"}],
+            "tools": [{"type": "function", "function": {
+                "name": "record_city",
+                "parameters": {"type": "object", "required": ["city"]}
+            }}],
+            "tool_choice": {"type": "function", "function": {"name": "record_city"}}
+        });
+        assert!(prepare_request(&mut body.clone(), StructuredOutputMode::JsonSchema).is_none());
+
+        let plan = prepare_request(&mut body, StructuredOutputMode::JsonObject).unwrap();
+        assert_eq!(plan.tool_name.as_deref(), Some("record_city"));
+        assert!(body.get("tools").is_none());
+        assert_eq!(body["response_format"]["type"], "json_object");
+        assert_eq!(body["messages"][0]["role"], "system");
+
+        let mut response = json!({"choices": [{
+            "message": {"role": "assistant", "content": "{\"city\": \"Paris\"}"},
+            "finish_reason": "stop"
+        }]});
+        finish_response(&mut response, &plan);
+        let call = &response["choices"][0]["message"]["tool_calls"][0]["function"];
+        assert_eq!(call["name"], "record_city");
+        assert_eq!(call["arguments"], "{\"city\":\"Paris\"}");
+        assert_eq!(response["choices"][0]["finish_reason"], "tool_calls");
+    }
+
+    #[test]
+    fn test_check_and_repair() {
+        let request = json!({
+            "messages": [{"role": "user", "content": "List tags"}],
+            "response_format": {"type": "json_schema", "json_schema": {"schema": {
+                "type": "object", "required": ["tags"],
+                "properties": {"tags": {"type": "array"}}
+            }}}
+        });
+        let plan = declared_schema(&request).unwrap();
+        let mut response = json!({"choices": [{
+            "message": {"role": "assistant", "content": "{\"tag\": [\"a\"]}"}
+        }]});
+        let (error, text) = check_response(&response, &plan).unwrap_err();
+        assert_eq!(error, "$.tags is required");
+        assert_eq!(text, "{\"tag\": [\"a\"]}");
+        assert!(
+            repair_request(&plan, &text, &error)["messages"][1]["content"]
+                .as_str()
+                .unwrap()
+                .contains("$.tags is required")
+        );
+
+        assert!(apply_repair(&mut response, &plan, "{\"tag\": []}").is_err());
+        apply_repair(&mut response, &plan, "```json\n{\"tags\": [\"a\"]}\n```").unwrap();
+        assert!(check_response(&response, &plan).is_ok());
+    }
+}
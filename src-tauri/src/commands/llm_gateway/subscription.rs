@@ -0,0 +1,66 @@
+//! Claude Subscriptions - OAuth passthrough for Claude Pro/Max plans
+//!
+//! Claude Code signed in with a subscription authenticates with an OAuth access token
+//! rather than an API key. Anthropic providers with `oauth_passthrough` set forward that
+//! token on native Messages requests in place of their own key, so usage counts against
+//! the user's plan, while requests routed to other providers never see it. Anthropic only
+//! accepts these tokens together with the OAuth beta flag.
+
+use axum::http::{header, HeaderMap};
+
+/// Beta flag Anthropic requires on requests authenticated with an OAuth token
+pub const OAUTH_BETA: &str = "oauth-2025-04-20";
+
+/// Prefix of Claude OAuth access tokens
+const TOKEN_PREFIX: &str = "sk-ant-oat";
+
+/// Claude subscription token a client sent as its bearer token
+pub fn client_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| token.starts_with(TOKEN_PREFIX))
+}
+
+/// `anthropic-beta` value carrying the client's flags plus the OAuth flag
+pub fn with_oauth_beta(beta: Option<&str>) -> String {
+    let mut flags: Vec<&str> = beta
+        .into_iter()
+        .flat_map(|b| b.split(','))
+        .map(str::trim)
+        .filter(|flag| !flag.is_empty())
+        .collect();
+    if !flags.contains(&OAUTH_BETA) {
+        flags.push(OAUTH_BETA);
+    }
+    flags.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_token() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            "Bearer sk-ant-oat01-abc".parse().unwrap(),
+        );
+        assert_eq!(client_token(&headers), Some("sk-ant-oat01-abc"));
+        headers.insert(
+            header::AUTHORIZATION,
+            "Bearer sk-ant-api03-abc".parse().unwrap(),
+        );
+        assert_eq!(client_token(&headers), None);
+
+        assert_eq!(with_oauth_beta(None), OAUTH_BETA);
+        assert_eq!(
+            with_oauth_beta(Some("prompt-caching-2024-07-31, oauth-2025-04-20")),
+            "prompt-caching-2024-07-31,oauth-2025-04-20"
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Prompt Templates - Named prompts stored in the database and invoked through the gateway
+//!
+//! A template holds an optional system prompt and a user prompt with `{{variable}}`
+//! placeholders. Clients name it in the `x-doggy-template` header and pass values in a
+//! top-level `template_variables` object; the rendered prompt is appended to the request's
+//! messages and its system prompt replaces the client's.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Header naming the stored template to apply to a request
+pub const TEMPLATE_HEADER: &str = "x-doggy-template";
+
+/// Request field carrying the template's variable values
+const VARIABLES_FIELD: &str = "template_variables";
+
+/// A named, reusable prompt
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptTemplate {
+    /// Unique name clients invoke the template by
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// System prompt, replacing the client's when set
+    #[serde(default)]
+    pub system: Option<String>,
+    /// User prompt with `{{variable}}` placeholders
+    pub prompt: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// Create the template table if it doesn't exist yet
+pub fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS gateway_prompt_templates (
+            name TEXT PRIMARY KEY,
+            description TEXT,
+            system TEXT,
+            prompt TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn from_row(row: &rusqlite::Row) -> rusqlite::Result<PromptTemplate> {
+    Ok(PromptTemplate {
+        name: row.get(0)?,
+        description: row.get(1)?,
+        system: row.get(2)?,
+        prompt: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+/// All templates, ordered by name
+pub fn list_templates(conn: &Connection) -> rusqlite::Result<Vec<PromptTemplate>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, description, system, prompt, created_at, updated_at
+         FROM gateway_prompt_templates ORDER BY name",
+    )?;
+    let rows = stmt.query_map([], from_row)?;
+    rows.collect()
+}
+
+/// The template called `name`, if any
+pub fn get_template(conn: &Connection, name: &str) -> rusqlite::Result<Option<PromptTemplate>> {
+    conn.query_row(
+        "SELECT name, description, system, prompt, created_at, updated_at
+         FROM gateway_prompt_templates WHERE name = ?1",
+        params![name],
+        from_row,
+    )
+    .optional()
+}
+
+/// Create a template or update the one with the same name
+pub fn save_template(conn: &Connection, template: &PromptTemplate) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO gateway_prompt_templates (name, description, system, prompt)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(name) DO UPDATE SET
+            description = excluded.description,
+            system = excluded.system,
+            prompt = excluded.prompt,
+            updated_at = CURRENT_TIMESTAMP",
+        params![
+            template.name,
+            template.description,
+            template.system,
+            template.prompt
+        ],
+    )?;
+    Ok(())
+}
+
+/// Delete a template; returns whether it existed
+pub fn delete_template(conn: &Connection, name: &str) -> rusqlite::Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM gateway_prompt_templates WHERE name = ?1",
+        params![name],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Substitute `{{variable}}` placeholders, failing on variables without a value
+pub fn render(text: &str, variables: &Map<String, Value>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let name = rest[start + 2..start + len].trim();
+        match variables.get(name) {
+            Some(Value::String(value)) => out.push_str(value),
+            Some(value) => out.push_str(&value.to_string()),
+            None => return Err(format!("Missing value for template variable '{}'", name)),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Render `template` into a request. `anthropic` selects the Messages request shape,
+/// with a top-level `system` field, over the OpenAI one.
+pub fn apply_template(
+    request: &mut Value,
+    template: &PromptTemplate,
+    anthropic: bool,
+) -> Result<(), String> {
+    let Some(obj) = request.as_object_mut() else {
+        return Err("Request body must be a JSON object".to_string());
+    };
+    let variables = match obj.remove(VARIABLES_FIELD) {
+        Some(Value::Object(variables)) => variables,
+        None | Some(Value::Null) => Map::new(),
+        Some(_) => return Err(format!("'{}' must be an object", VARIABLES_FIELD)),
+    };
+    let prompt = render(&template.prompt, &variables)?;
+    let system = template
+        .system
+        .as_deref()
+        .map(|system| render(system, &variables))
+        .transpose()?;
+
+    if let (Some(system), true) = (&system, anthropic) {
+        obj.insert("system".to_string(), Value::String(system.clone()));
+    }
+    let messages = obj
+        .entry("messages")
+        .or_insert_with(|| Value::Array(Vec::new()))
+        .as_array_mut()
+        .ok_or("'messages' must be an array")?;
+    if let (Some(system), false) = (system, anthropic) {
+        messages.retain(|m| m["role"] != "system" && m["role"] != "developer");
+        messages.insert(0, json!({"role": "system", "content": system}));
+    }
+    messages.push(json!({"role": "user", "content": prompt}));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_storage_and_rendering() {
+        let conn = Connection::open_in_memory().unwrap();
+        ensure_schema(&conn).unwrap();
+        let mut template = PromptTemplate {
+            name: "review".to_string(),
+            description: None,
+            system: Some("You review {{language}} code.".to_string()),
+            prompt: "Review this:\n{{ code }}".to_string(),
+            created_at: None,
+            updated_at: None,
+        };
+        save_template(&conn, &template).unwrap();
+        template.description = Some("Code review".to_string());
+        save_template(&conn, &template).unwrap();
+
+        let stored = get_template(&conn, "review").unwrap().unwrap();
+        assert_eq!(stored.description.as_deref(), Some("Code review"));
+        assert_eq!(list_templates(&conn).unwrap().len(), 1);
+
+        let mut request = json!({
+            "model": "claude-3-5-sonnet-20241022",
+            "messages": [],
+            "template_variables": {"language": "Rust", "code": "fn main() {}"}
+        });
+        apply_template(&mut request, &stored, true).unwrap();
+        assert_eq!(request["system"], "You review Rust code.");
+        assert_eq!(
+            request["messages"][0]["content"],
+            "Review this:\nfn main() {}"
+        );
+        assert!(request.get("template_variables").is_none());
+
+        let mut request = json!({"template_variables": {"language": "Rust"}});
+        assert_eq!(
+            apply_template(&mut request, &stored, false).unwrap_err(),
+            "Missing value for template variable 'code'"
+        );
+
+        assert!(delete_template(&conn, "review").unwrap());
+        assert!(get_template(&conn, "review").unwrap().is_none());
+    }
+}
@@ -0,0 +1,125 @@
+//! Request Transformations - User-configured rewrites of requests to matching routes
+//!
+//! Rules run after routing, in configuration order, on the request as the client sent
+//! it (Anthropic or OpenAI shaped). Regex replacements rewrite the text of the system
+//! prompt and messages, parameter overrides set (or, with `null`, remove) top-level
+//! fields, and headers are added to the upstream request.
+
+use regex::Regex;
+use serde_json::Value;
+
+use super::router::RouteTarget;
+use super::{TextReplacement, TransformRule};
+
+/// Apply a replacement to every text part of system or message content
+fn replace_text(content: &mut Value, regex: &Regex, replacement: &str) {
+    match content {
+        Value::String(text) => {
+            if let std::borrow::Cow::Owned(replaced) = regex.replace_all(text, replacement) {
+                *text = replaced;
+            }
+        }
+        Value::Array(blocks) => {
+            for block in blocks {
+                if block["type"] == "text" {
+                    replace_text(&mut block["text"], regex, replacement);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn apply_replacement(request: &mut Value, replacement: &TextReplacement) {
+    let regex = match Regex::new(&replacement.pattern) {
+        Ok(regex) => regex,
+        Err(e) => {
+            log::warn!(
+                "Skipping invalid transformation pattern '{}': {}",
+                replacement.pattern,
+                e
+            );
+            return;
+        }
+    };
+    if let Some(system) = request.get_mut("system") {
+        replace_text(system, &regex, &replacement.replacement);
+    }
+    for message in request
+        .get_mut("messages")
+        .and_then(|m| m.as_array_mut())
+        .into_iter()
+        .flatten()
+    {
+        replace_text(&mut message["content"], &regex, &replacement.replacement);
+    }
+}
+
+/// Apply transformation rules to a request and its route's upstream headers
+pub fn apply_rules(request: &mut Value, route: &mut RouteTarget, rules: &[TransformRule]) {
+    for rule in rules {
+        for replacement in &rule.replacements {
+            apply_replacement(request, replacement);
+        }
+        if let Some(obj) = request.as_object_mut() {
+            for (name, value) in &rule.parameters {
+                if value.is_null() {
+                    obj.remove(name);
+                } else {
+                    obj.insert(name.clone(), value.clone());
+                }
+            }
+        }
+        route.provider.headers.extend(rule.headers.clone());
+        log::debug!("Applied transformation rule '{}'", rule.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::llm_gateway::GatewaySettings;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_apply_rules() {
+        let provider = GatewaySettings::default().providers.remove(0);
+        let mut route = RouteTarget {
+            provider,
+            model: "gpt-4o".to_string(),
+            ab: None,
+        };
+        let rule = TransformRule {
+            name: "deterministic".to_string(),
+            enabled: true,
+            provider: None,
+            match_model: "gpt-4o*".to_string(),
+            replacements: vec![TextReplacement {
+                pattern: r"(?i)\bacme corp\b".to_string(),
+                replacement: "the client".to_string(),
+            }],
+            headers: HashMap::from([("x-team".to_string(), "infra".to_string())]),
+            parameters: serde_json::Map::from_iter([
+                ("temperature".to_string(), json!(0)),
+                ("top_p".to_string(), Value::Null),
+            ]),
+        };
+
+        let mut request = json!({
+            "system": "You work for ACME Corp.",
+            "messages": [{"role": "user", "content": [{"type": "text", "text": "Email Acme Corp"}]}],
+            "temperature": 0.7,
+            "top_p": 0.9
+        });
+        apply_rules(&mut request, &mut route, &[rule]);
+        assert_eq!(request["system"], "You work for the client.");
+        assert_eq!(
+            request["messages"][0]["content"][0]["text"],
+            "Email the client"
+        );
+        assert_eq!(request["temperature"], 0);
+        assert!(request.get("top_p").is_none());
+        assert_eq!(route.provider.headers["x-team"], "infra");
+    }
+}
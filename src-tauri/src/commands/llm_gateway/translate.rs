@@ -0,0 +1,1032 @@
+//! Format Translation - Anthropic Messages API <-> OpenAI Chat Completions
+//!
+//! Claude Code speaks the Anthropic Messages API while most providers expose an
+//! OpenAI-compatible chat API. Requests are translated to the OpenAI shape before
+//! dispatch and responses (including SSE streams) are translated back.
+
+use serde_json::{json, Value};
+
+use super::documents::anthropic_document_to_openai;
+use super::reasoning::{ReasoningOutput, ThinkTagSplitter};
+use super::vision::anthropic_image_to_openai;
+use super::{SystemPromptAction, SystemPromptRule};
+
+/// Concatenate the text of a string or an array of content blocks
+pub fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Translate Anthropic tool definitions into OpenAI function tools
+fn translate_tools(tools: &[Value]) -> Vec<Value> {
+    tools
+        .iter()
+        .filter_map(|tool| {
+            let name = tool.get("name")?.clone();
+            let mut function = json!({
+                "name": name,
+                "parameters": tool
+                    .get("input_schema")
+                    .cloned()
+                    .unwrap_or_else(|| json!({"type": "object", "properties": {}})),
+            });
+            if let Some(description) = tool.get("description") {
+                function["description"] = description.clone();
+            }
+            Some(json!({"type": "function", "function": function}))
+        })
+        .collect()
+}
+
+/// Translate an Anthropic `tool_choice` into its OpenAI equivalent
+fn translate_tool_choice(choice: &Value) -> Option<Value> {
+    match choice.get("type").and_then(|t| t.as_str())? {
+        "auto" => Some(json!("auto")),
+        "any" => Some(json!("required")),
+        "none" => Some(json!("none")),
+        "tool" => Some(json!({
+            "type": "function",
+            "function": {"name": choice.get("name")?.clone()}
+        })),
+        _ => None,
+    }
+}
+
+/// Translate one Anthropic message into one or more OpenAI messages.
+///
+/// `tool_use` blocks become assistant `tool_calls`; `tool_result` blocks become `tool`
+/// role messages, which OpenAI requires to directly follow the assistant turn. Messages
+/// with images or documents keep their content as an array of `text`, `image_url` and
+/// `file` parts.
+fn translate_message(message: &Value, out: &mut Vec<Value>) {
+    let role = message
+        .get("role")
+        .and_then(|r| r.as_str())
+        .unwrap_or("user");
+    let Some(blocks) = message.get("content").and_then(|c| c.as_array()) else {
+        let content = message.get("content").map(content_text).unwrap_or_default();
+        out.push(json!({"role": role, "content": content}));
+        return;
+    };
+
+    let block_type = |b: &Value| b.get("type").and_then(|t| t.as_str()).map(str::to_string);
+
+    let tool_calls: Vec<Value> = blocks
+        .iter()
+        .filter(|b| block_type(b).as_deref() == Some("tool_use"))
+        .map(|b| {
+            json!({
+                "id": b.get("id").cloned().unwrap_or(Value::Null),
+                "type": "function",
+                "function": {
+                    "name": b.get("name").cloned().unwrap_or(Value::Null),
+                    "arguments": b.get("input").unwrap_or(&json!({})).to_string(),
+                }
+            })
+        })
+        .collect();
+
+    for result in blocks
+        .iter()
+        .filter(|b| block_type(b).as_deref() == Some("tool_result"))
+    {
+        let mut content = result.get("content").map(content_text).unwrap_or_default();
+        if result.get("is_error").and_then(|e| e.as_bool()) == Some(true) {
+            content = format!("Error: {}", content);
+        }
+        out.push(json!({
+            "role": "tool",
+            "tool_call_id": result.get("tool_use_id").cloned().unwrap_or(Value::Null),
+            "content": content,
+        }));
+    }
+
+    let text = content_text(&Value::Array(blocks.clone()));
+    let has_media = blocks
+        .iter()
+        .any(|b| matches!(block_type(b).as_deref(), Some("image") | Some("document")));
+    if has_media {
+        // Keep text, images and documents interleaved in their original order
+        let parts: Vec<Value> = blocks
+            .iter()
+            .filter_map(|b| match block_type(b).as_deref() {
+                Some("text") => Some(json!({"type": "text", "text": b.get("text")?.clone()})),
+                Some("image") => anthropic_image_to_openai(b),
+                Some("document") => anthropic_document_to_openai(b),
+                _ => None,
+            })
+            .collect();
+        let mut message = json!({"role": role, "content": parts});
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = Value::Array(tool_calls);
+        }
+        out.push(message);
+    } else if !tool_calls.is_empty() {
+        let content = if text.is_empty() {
+            Value::Null
+        } else {
+            Value::String(text)
+        };
+        out.push(json!({"role": role, "content": content, "tool_calls": tool_calls}));
+    } else if !text.is_empty() {
+        out.push(json!({"role": role, "content": text}));
+    }
+}
+
+/// Apply a system prompt rule to system content, either a string or a list of text blocks
+fn apply_system_rule(content: &mut Value, rule: &SystemPromptRule) {
+    let text = rule.text.as_str();
+    match (rule.action, content) {
+        (SystemPromptAction::Replace, content) => *content = Value::String(text.to_string()),
+        (action, Value::Array(blocks)) => {
+            let block = json!({"type": "text", "text": text});
+            if action == SystemPromptAction::Prepend {
+                blocks.insert(0, block);
+            } else {
+                blocks.push(block);
+            }
+        }
+        (action, content) => {
+            let existing = content.as_str().unwrap_or_default();
+            *content = Value::String(match (action, existing.is_empty()) {
+                (_, true) => text.to_string(),
+                (SystemPromptAction::Prepend, false) => format!("{}\n\n{}", text, existing),
+                _ => format!("{}\n\n{}", existing, text),
+            });
+        }
+    }
+}
+
+/// Apply system prompt rules to the `system` field of an Anthropic Messages request
+pub fn apply_system_rules(request: &mut Value, rules: &[SystemPromptRule]) {
+    if rules.is_empty() || !request.is_object() {
+        return;
+    }
+    let system = &mut request["system"];
+    for rule in rules {
+        apply_system_rule(system, rule);
+    }
+}
+
+/// Apply system prompt rules to the leading system message of an OpenAI chat request,
+/// inserting one if there is none
+pub fn apply_openai_system_rules(body: &mut Value, rules: &[SystemPromptRule]) {
+    if rules.is_empty() {
+        return;
+    }
+    let Some(messages) = body.get_mut("messages").and_then(|m| m.as_array_mut()) else {
+        return;
+    };
+    if !messages
+        .first()
+        .is_some_and(|m| matches!(m["role"].as_str(), Some("system") | Some("developer")))
+    {
+        messages.insert(0, json!({"role": "system", "content": ""}));
+    }
+    for rule in rules {
+        apply_system_rule(&mut messages[0]["content"], rule);
+    }
+}
+
+/// Translate an Anthropic Messages request into an OpenAI chat completions request
+pub fn anthropic_to_openai_request(request: &Value) -> Value {
+    let mut messages = Vec::new();
+
+    if let Some(system) = request.get("system") {
+        let text = content_text(system);
+        if !text.is_empty() {
+            messages.push(json!({"role": "system", "content": text}));
+        }
+    }
+
+    for message in request
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+    {
+        translate_message(message, &mut messages);
+    }
+
+    let mut body = json!({ "messages": messages });
+    for field in ["model", "max_tokens", "temperature", "top_p", "stream"] {
+        if let Some(value) = request.get(field) {
+            body[field] = value.clone();
+        }
+    }
+    if let Some(stop) = request.get("stop_sequences") {
+        body["stop"] = stop.clone();
+    }
+    if let Some(tools) = request.get("tools").and_then(|t| t.as_array()) {
+        let tools = translate_tools(tools);
+        if !tools.is_empty() {
+            body["tools"] = Value::Array(tools);
+        }
+    }
+    if let Some(choice) = request.get("tool_choice").and_then(translate_tool_choice) {
+        body["tool_choice"] = choice;
+    }
+    if body.get("stream").and_then(|s| s.as_bool()) == Some(true) {
+        body["stream_options"] = json!({"include_usage": true});
+    }
+
+    body
+}
+
+/// Map an OpenAI `finish_reason` onto an Anthropic `stop_reason`
+pub fn map_finish_reason(reason: Option<&str>) -> &'static str {
+    match reason {
+        Some("length") => "max_tokens",
+        Some("tool_calls") | Some("function_call") => "tool_use",
+        _ => "end_turn",
+    }
+}
+
+fn message_id(source: &Value) -> String {
+    match source.get("id").and_then(|i| i.as_str()) {
+        Some(id) if id.starts_with("msg_") => id.to_string(),
+        Some(id) => format!("msg_{}", id),
+        None => format!("msg_{}", uuid::Uuid::new_v4().simple()),
+    }
+}
+
+/// Parse a tool call's JSON arguments string, falling back to an empty object
+pub fn parse_arguments(arguments: Option<&Value>) -> Value {
+    match arguments {
+        Some(Value::String(raw)) if !raw.trim().is_empty() => {
+            serde_json::from_str(raw).unwrap_or_else(|_| json!({}))
+        }
+        Some(value @ Value::Object(_)) => value.clone(),
+        _ => json!({}),
+    }
+}
+
+/// Translate a non-streaming OpenAI chat completion into an Anthropic message
+pub fn openai_to_anthropic_response(response: &Value, model: &str) -> Value {
+    let choice = response.pointer("/choices/0");
+    let text = choice
+        .and_then(|c| c.pointer("/message/content"))
+        .and_then(|c| c.as_str())
+        .unwrap_or_default();
+    let finish_reason = choice
+        .and_then(|c| c.get("finish_reason"))
+        .and_then(|f| f.as_str());
+
+    let mut content = Vec::new();
+    if let Some(thinking) = choice
+        .and_then(|c| c.pointer("/message/reasoning_content"))
+        .and_then(|r| r.as_str())
+        .filter(|r| !r.is_empty())
+    {
+        content.push(json!({"type": "thinking", "thinking": thinking, "signature": ""}));
+    }
+    if !text.is_empty() {
+        content.push(json!({"type": "text", "text": text}));
+    }
+    for call in choice
+        .and_then(|c| c.pointer("/message/tool_calls"))
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+    {
+        content.push(json!({
+            "type": "tool_use",
+            "id": call.get("id").cloned().unwrap_or(Value::Null),
+            "name": call.pointer("/function/name").cloned().unwrap_or(Value::Null),
+            "input": parse_arguments(call.pointer("/function/arguments")),
+        }));
+    }
+
+    // Some providers report `stop` even when the turn ends in tool calls
+    let has_tool_use = content.iter().any(|b| b["type"] == "tool_use");
+    let stop_reason = if has_tool_use {
+        "tool_use"
+    } else {
+        map_finish_reason(finish_reason)
+    };
+
+    json!({
+        "id": message_id(response),
+        "type": "message",
+        "role": "assistant",
+        "model": model,
+        "content": content,
+        "stop_reason": stop_reason,
+        "stop_sequence": null,
+        "usage": {
+            "input_tokens": response.pointer("/usage/prompt_tokens").and_then(|t| t.as_u64()).unwrap_or(0),
+            "output_tokens": response.pointer("/usage/completion_tokens").and_then(|t| t.as_u64()).unwrap_or(0),
+        }
+    })
+}
+
+// ============================================================================
+// Streaming
+// ============================================================================
+
+/// Incremental parser yielding the `data:` payloads of a server-sent event stream
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    /// Feed raw bytes and return the data payloads of every completed line
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut payloads = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(data) = line.trim_end().strip_prefix("data:") {
+                payloads.push(data.trim_start().to_string());
+            }
+        }
+        payloads
+    }
+}
+
+/// Format a named SSE event
+pub fn sse_event(event: &str, data: &Value) -> String {
+    format!("event: {}\ndata: {}\n\n", event, data)
+}
+
+/// Replay a complete OpenAI chat completion as a chat completion chunk stream, for
+/// requests that had to be answered without upstream streaming
+pub fn completion_as_chunks(response: &Value) -> String {
+    let choice = response.pointer("/choices/0");
+    let mut delta = choice
+        .and_then(|c| c.get("message"))
+        .cloned()
+        .unwrap_or_else(|| json!({"role": "assistant"}));
+    if let Some(calls) = delta.get_mut("tool_calls").and_then(|c| c.as_array_mut()) {
+        for (index, call) in calls.iter_mut().enumerate() {
+            call["index"] = json!(index);
+        }
+    }
+
+    let chunk = json!({
+        "id": response.get("id").cloned().unwrap_or(Value::Null),
+        "object": "chat.completion.chunk",
+        "created": response.get("created").cloned().unwrap_or(Value::Null),
+        "model": response.get("model").cloned().unwrap_or(Value::Null),
+        "choices": [{
+            "index": 0,
+            "delta": delta,
+            "finish_reason": choice.and_then(|c| c.get("finish_reason")).cloned().unwrap_or(Value::Null),
+        }],
+        "usage": response.get("usage").cloned().unwrap_or(Value::Null),
+    });
+    format!("data: {}\n\ndata: [DONE]\n\n", chunk)
+}
+
+/// Replay a complete Anthropic message as a Messages event stream, for requests that had
+/// to be answered without upstream streaming
+pub fn message_as_events(message: &Value) -> String {
+    let mut start = message.clone();
+    start["content"] = json!([]);
+    start["stop_reason"] = Value::Null;
+    start["stop_sequence"] = Value::Null;
+    let mut out = sse_event(
+        "message_start",
+        &json!({"type": "message_start", "message": start}),
+    );
+
+    for (index, block) in message["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        let (empty, deltas) = match block["type"].as_str() {
+            Some("text") => (
+                json!({"type": "text", "text": ""}),
+                vec![json!({"type": "text_delta", "text": block["text"]})],
+            ),
+            Some("thinking") => {
+                let mut deltas =
+                    vec![json!({"type": "thinking_delta", "thinking": block["thinking"]})];
+                if let Some(signature) = block.get("signature") {
+                    deltas.push(json!({"type": "signature_delta", "signature": signature}));
+                }
+                (json!({"type": "thinking", "thinking": ""}), deltas)
+            }
+            Some("tool_use") => (
+                json!({"type": "tool_use", "id": block["id"], "name": block["name"], "input": {}}),
+                vec![
+                    json!({"type": "input_json_delta", "partial_json": block["input"].to_string()}),
+                ],
+            ),
+            _ => (block.clone(), Vec::new()),
+        };
+        out.push_str(&sse_event(
+            "content_block_start",
+            &json!({"type": "content_block_start", "index": index, "content_block": empty}),
+        ));
+        for delta in deltas {
+            out.push_str(&sse_event(
+                "content_block_delta",
+                &json!({"type": "content_block_delta", "index": index, "delta": delta}),
+            ));
+        }
+        out.push_str(&sse_event(
+            "content_block_stop",
+            &json!({"type": "content_block_stop", "index": index}),
+        ));
+    }
+
+    out.push_str(&sse_event(
+        "message_delta",
+        &json!({
+            "type": "message_delta",
+            "delta": {
+                "stop_reason": message["stop_reason"],
+                "stop_sequence": message["stop_sequence"]
+            },
+            "usage": message["usage"]
+        }),
+    ));
+    out.push_str(&sse_event("message_stop", &json!({"type": "message_stop"})));
+    out
+}
+
+/// Stateful converter from an upstream SSE stream to the client's stream format
+pub trait StreamTranslator: Send + 'static {
+    /// Translate a raw upstream chunk into bytes for the client
+    fn push(&mut self, chunk: &[u8]) -> String;
+    /// Flush any closing events once the upstream stream ends
+    fn finish(&mut self) -> String;
+}
+
+/// Content block currently open in the translated stream
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OpenBlock {
+    Thinking,
+    Text,
+    /// Tool call with its index in the upstream `tool_calls` array
+    Tool(u64),
+}
+
+/// Converts OpenAI chat completion chunks into Anthropic message stream events
+#[derive(Debug)]
+pub struct AnthropicStreamTranslator {
+    parser: SseParser,
+    model: String,
+    started: bool,
+    /// Block currently open and its Anthropic content index
+    open_block: Option<(OpenBlock, usize)>,
+    next_index: usize,
+    saw_tool_call: bool,
+    reasoning: ReasoningOutput,
+    think_tags: ThinkTagSplitter,
+    finished: bool,
+    stop_reason: Option<String>,
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+impl AnthropicStreamTranslator {
+    pub fn new(model: &str, reasoning: ReasoningOutput) -> Self {
+        Self {
+            parser: SseParser::default(),
+            model: model.to_string(),
+            started: false,
+            open_block: None,
+            next_index: 0,
+            saw_tool_call: false,
+            reasoning,
+            think_tags: ThinkTagSplitter::default(),
+            finished: false,
+            stop_reason: None,
+            input_tokens: 0,
+            output_tokens: 0,
+        }
+    }
+
+    fn ensure_started(&mut self, chunk: &Value, out: &mut String) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        out.push_str(&sse_event(
+            "message_start",
+            &json!({
+                "type": "message_start",
+                "message": {
+                    "id": message_id(chunk),
+                    "type": "message",
+                    "role": "assistant",
+                    "model": self.model,
+                    "content": [],
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": {"input_tokens": 0, "output_tokens": 0}
+                }
+            }),
+        ));
+    }
+
+    fn close_block(&mut self, out: &mut String) {
+        if let Some((_, index)) = self.open_block.take() {
+            out.push_str(&sse_event(
+                "content_block_stop",
+                &json!({"type": "content_block_stop", "index": index}),
+            ));
+        }
+    }
+
+    /// Open `block` unless it is already the open block; returns its content index
+    fn open(&mut self, block: OpenBlock, content_block: Value, out: &mut String) -> usize {
+        if let Some((open, index)) = self.open_block {
+            if open == block {
+                return index;
+            }
+        }
+        self.close_block(out);
+
+        let index = self.next_index;
+        self.next_index += 1;
+        self.open_block = Some((block, index));
+        out.push_str(&sse_event(
+            "content_block_start",
+            &json!({
+                "type": "content_block_start",
+                "index": index,
+                "content_block": content_block
+            }),
+        ));
+        index
+    }
+
+    fn handle_chunk(&mut self, chunk: &Value, out: &mut String) {
+        self.ensure_started(chunk, out);
+
+        if let Some(usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+            self.input_tokens = usage
+                .get("prompt_tokens")
+                .and_then(|t| t.as_u64())
+                .unwrap_or(self.input_tokens);
+            self.output_tokens = usage
+                .get("completion_tokens")
+                .and_then(|t| t.as_u64())
+                .unwrap_or(self.output_tokens);
+        }
+
+        let Some(choice) = chunk.pointer("/choices/0") else {
+            return;
+        };
+
+        if let Some(reasoning) = choice
+            .pointer("/delta/reasoning_content")
+            .and_then(|c| c.as_str())
+            .filter(|t| !t.is_empty())
+        {
+            self.emit_text(true, reasoning, out);
+        }
+
+        if let Some(text) = choice
+            .pointer("/delta/content")
+            .and_then(|c| c.as_str())
+            .filter(|t| !t.is_empty())
+        {
+            for (is_thinking, part) in self.think_tags.push(text) {
+                self.emit_text(is_thinking, &part, out);
+            }
+        }
+
+        for call in choice
+            .pointer("/delta/tool_calls")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            self.handle_tool_call_delta(call, out);
+        }
+
+        if let Some(reason) = choice.get("finish_reason").and_then(|f| f.as_str()) {
+            self.stop_reason = Some(map_finish_reason(Some(reason)).to_string());
+        }
+    }
+
+    /// Emit answer text, or reasoning as a thinking block unless reasoning is stripped
+    fn emit_text(&mut self, is_thinking: bool, text: &str, out: &mut String) {
+        let (block, content_block, delta) = if is_thinking {
+            if self.reasoning == ReasoningOutput::Strip {
+                return;
+            }
+            (
+                OpenBlock::Thinking,
+                json!({"type": "thinking", "thinking": ""}),
+                json!({"type": "thinking_delta", "thinking": text}),
+            )
+        } else {
+            (
+                OpenBlock::Text,
+                json!({"type": "text", "text": ""}),
+                json!({"type": "text_delta", "text": text}),
+            )
+        };
+        let index = self.open(block, content_block, out);
+        out.push_str(&sse_event(
+            "content_block_delta",
+            &json!({
+                "type": "content_block_delta",
+                "index": index,
+                "delta": delta
+            }),
+        ));
+    }
+
+    fn handle_tool_call_delta(&mut self, call: &Value, out: &mut String) {
+        let call_index = call.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+        let block = OpenBlock::Tool(call_index);
+
+        // The first delta of a call carries its id and name
+        let is_new = self.open_block.map(|(open, _)| open) != Some(block);
+        if is_new && call.get("id").is_none() && call.pointer("/function/name").is_none() {
+            return;
+        }
+
+        self.saw_tool_call = true;
+        let index = self.open(
+            block,
+            json!({
+                "type": "tool_use",
+                "id": call
+                    .get("id")
+                    .and_then(|i| i.as_str())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("toolu_{}", uuid::Uuid::new_v4().simple())),
+                "name": call.pointer("/function/name").cloned().unwrap_or(json!("")),
+                "input": {}
+            }),
+            out,
+        );
+
+        if let Some(arguments) = call
+            .pointer("/function/arguments")
+            .and_then(|a| a.as_str())
+            .filter(|a| !a.is_empty())
+        {
+            out.push_str(&sse_event(
+                "content_block_delta",
+                &json!({
+                    "type": "content_block_delta",
+                    "index": index,
+                    "delta": {"type": "input_json_delta", "partial_json": arguments}
+                }),
+            ));
+        }
+    }
+
+    fn close(&mut self, out: &mut String) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.ensure_started(&Value::Null, out);
+        for (is_thinking, part) in self.think_tags.finish() {
+            self.emit_text(is_thinking, &part, out);
+        }
+        self.close_block(out);
+
+        let stop_reason = if self.saw_tool_call {
+            "tool_use"
+        } else {
+            self.stop_reason.as_deref().unwrap_or("end_turn")
+        };
+        out.push_str(&sse_event(
+            "message_delta",
+            &json!({
+                "type": "message_delta",
+                "delta": {
+                    "stop_reason": stop_reason,
+                    "stop_sequence": null
+                },
+                "usage": {"input_tokens": self.input_tokens, "output_tokens": self.output_tokens}
+            }),
+        ));
+        out.push_str(&sse_event("message_stop", &json!({"type": "message_stop"})));
+    }
+}
+
+impl StreamTranslator for AnthropicStreamTranslator {
+    fn push(&mut self, chunk: &[u8]) -> String {
+        let mut out = String::new();
+        for payload in self.parser.push(chunk) {
+            if self.finished {
+                break;
+            }
+            if payload == "[DONE]" {
+                self.close(&mut out);
+                continue;
+            }
+            if let Ok(chunk) = serde_json::from_str::<Value>(&payload) {
+                self.handle_chunk(&chunk, &mut out);
+            }
+        }
+        out
+    }
+
+    fn finish(&mut self) -> String {
+        let mut out = String::new();
+        self.close(&mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(action: SystemPromptAction, text: &str) -> SystemPromptRule {
+        SystemPromptRule {
+            enabled: true,
+            provider: None,
+            match_model: "*".to_string(),
+            action,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_message_as_events() {
+        let message = json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [
+                {"type": "text", "text": "Checking"},
+                {"type": "tool_use", "id": "t1", "name": "ls", "input": {"path": "."}}
+            ],
+            "stop_reason": "tool_use",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        });
+        let mut parser = SseParser::default();
+        let events: Vec<Value> = parser
+            .push(message_as_events(&message).as_bytes())
+            .iter()
+            .map(|data| serde_json::from_str(data).unwrap())
+            .collect();
+
+        assert_eq!(events.len(), 9);
+        assert_eq!(events[0]["message"]["content"], json!([]));
+        assert_eq!(events[2]["delta"]["text"], "Checking");
+        assert_eq!(events[5]["delta"]["partial_json"], "{\"path\":\".\"}");
+        assert_eq!(events[7]["delta"]["stop_reason"], "tool_use");
+    }
+
+    #[test]
+    fn test_system_prompt_rules() {
+        let rules = vec![
+            rule(SystemPromptAction::Prepend, "Respond in Chinese."),
+            rule(SystemPromptAction::Append, "Be brief."),
+        ];
+
+        let mut request = json!({
+            "system": [{"type": "text", "text": "You code.", "cache_control": {"type": "ephemeral"}}],
+            "messages": []
+        });
+        apply_system_rules(&mut request, &rules);
+        assert_eq!(
+            content_text(&request["system"]),
+            "Respond in Chinese.\nYou code.\nBe brief."
+        );
+        assert_eq!(request["system"][1]["cache_control"]["type"], "ephemeral");
+
+        let mut body = json!({"messages": [{"role": "user", "content": "Hi"}]});
+        apply_openai_system_rules(&mut body, &rules);
+        assert_eq!(
+            body["messages"][0],
+            json!({"role": "system", "content": "Respond in Chinese.\n\nBe brief."})
+        );
+
+        apply_openai_system_rules(
+            &mut body,
+            &[rule(SystemPromptAction::Replace, "Only this.")],
+        );
+        assert_eq!(body["messages"][0]["content"], "Only this.");
+        assert_eq!(body["messages"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_anthropic_request_translation() {
+        let request = json!({
+            "model": "claude-3-5-sonnet-20241022",
+            "max_tokens": 1024,
+            "system": [{"type": "text", "text": "You are terse."}],
+            "messages": [
+                {"role": "user", "content": "Hi"},
+                {"role": "assistant", "content": [{"type": "text", "text": "Hello"}]}
+            ],
+            "stop_sequences": ["END"],
+            "stream": true
+        });
+
+        let body = anthropic_to_openai_request(&request);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][0]["content"], "You are terse.");
+        assert_eq!(body["messages"][2]["content"], "Hello");
+        assert_eq!(body["stop"], json!(["END"]));
+        assert_eq!(body["stream_options"]["include_usage"], true);
+    }
+
+    #[test]
+    fn test_openai_response_translation() {
+        let response = json!({
+            "id": "chatcmpl-123",
+            "choices": [{"message": {"role": "assistant", "content": "pong"}, "finish_reason": "length"}],
+            "usage": {"prompt_tokens": 5, "completion_tokens": 1}
+        });
+
+        let message = openai_to_anthropic_response(&response, "claude-3-5-sonnet-20241022");
+        assert_eq!(message["id"], "msg_chatcmpl-123");
+        assert_eq!(message["content"][0]["text"], "pong");
+        assert_eq!(message["stop_reason"], "max_tokens");
+        assert_eq!(message["usage"]["input_tokens"], 5);
+    }
+
+    #[test]
+    fn test_stream_translation() {
+        let mut translator =
+            AnthropicStreamTranslator::new("claude-3-5-sonnet-20241022", ReasoningOutput::Thinking);
+        let upstream = concat!(
+            "data: {\"id\":\"c1\",\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"id\":\"c1\",\"choices\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"id\":\"c1\",\"choices\":[],\"usage\":{\"prompt_tokens\":3,\"completion_tokens\":2}}\n\n",
+            "data: [DONE]\n\n"
+        );
+
+        // Split mid-line to exercise buffering
+        let (a, b) = upstream.split_at(30);
+        let mut out = translator.push(a.as_bytes());
+        out.push_str(&translator.push(b.as_bytes()));
+        out.push_str(&translator.finish());
+
+        let events: Vec<&str> = out
+            .lines()
+            .filter_map(|l| l.strip_prefix("event: "))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop"
+            ]
+        );
+        assert!(out.contains("\"output_tokens\":2"));
+    }
+
+    #[test]
+    fn test_stream_reasoning_translation() {
+        let upstream = concat!(
+            "data: {\"id\":\"c1\",\"choices\":[{\"delta\":{\"reasoning_content\":\"Hmm\"}}]}\n\n",
+            "data: {\"id\":\"c1\",\"choices\":[{\"delta\":{\"content\":\"Yes\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: [DONE]\n\n"
+        );
+
+        let mut translator =
+            AnthropicStreamTranslator::new("claude-3-5-sonnet-20241022", ReasoningOutput::Thinking);
+        let out = translator.push(upstream.as_bytes());
+        assert!(out.contains("\"thinking_delta\""));
+        assert!(out.contains("\"delta\":{\"text\":\"Yes\",\"type\":\"text_delta\"},\"index\":1"));
+
+        let mut translator =
+            AnthropicStreamTranslator::new("claude-3-5-sonnet-20241022", ReasoningOutput::Strip);
+        let out = translator.push(upstream.as_bytes());
+        assert!(!out.contains("thinking"));
+    }
+
+    #[test]
+    fn test_tool_request_translation() {
+        let request = json!({
+            "model": "claude-3-5-sonnet-20241022",
+            "tools": [{
+                "name": "bash",
+                "description": "Run a command",
+                "input_schema": {"type": "object", "properties": {"command": {"type": "string"}}}
+            }],
+            "tool_choice": {"type": "any"},
+            "messages": [
+                {"role": "user", "content": "List files"},
+                {"role": "assistant", "content": [
+                    {"type": "text", "text": "Listing."},
+                    {"type": "tool_use", "id": "toolu_1", "name": "bash", "input": {"command": "ls"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": [{"type": "text", "text": "a.txt"}]},
+                    {"type": "text", "text": "Now count them"}
+                ]}
+            ]
+        });
+
+        let body = anthropic_to_openai_request(&request);
+        assert_eq!(body["tools"][0]["function"]["name"], "bash");
+        assert_eq!(body["tools"][0]["function"]["parameters"]["type"], "object");
+        assert_eq!(body["tool_choice"], "required");
+
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages[1]["tool_calls"][0]["id"], "toolu_1");
+        assert_eq!(
+            messages[1]["tool_calls"][0]["function"]["arguments"],
+            "{\"command\":\"ls\"}"
+        );
+        assert_eq!(messages[2]["role"], "tool");
+        assert_eq!(messages[2]["content"], "a.txt");
+        assert_eq!(messages[3]["content"], "Now count them");
+    }
+
+    #[test]
+    fn test_image_request_translation() {
+        let request = json!({
+            "messages": [{"role": "user", "content": [
+                {"type": "text", "text": "Describe"},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}}
+            ]}]
+        });
+
+        let body = anthropic_to_openai_request(&request);
+        let parts = body["messages"][0]["content"].as_array().unwrap();
+        assert_eq!(parts[0], json!({"type": "text", "text": "Describe"}));
+        assert_eq!(parts[1]["image_url"]["url"], "data:image/png;base64,AAAA");
+    }
+
+    #[test]
+    fn test_tool_call_response_translation() {
+        let response = json!({
+            "id": "c2",
+            "choices": [{
+                "message": {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function", "function": {"name": "bash", "arguments": "{\"command\":\"ls\"}"}}
+                ]},
+                "finish_reason": "stop"
+            }]
+        });
+
+        let message = openai_to_anthropic_response(&response, "claude-3-5-sonnet-20241022");
+        assert_eq!(message["content"][0]["type"], "tool_use");
+        assert_eq!(message["content"][0]["input"]["command"], "ls");
+        assert_eq!(message["stop_reason"], "tool_use");
+    }
+
+    #[test]
+    fn test_stream_tool_call_translation() {
+        let mut translator =
+            AnthropicStreamTranslator::new("claude-3-5-sonnet-20241022", ReasoningOutput::Thinking);
+        let upstream = concat!(
+            "data: {\"id\":\"c3\",\"choices\":[{\"delta\":{\"content\":\"Checking\"}}]}\n\n",
+            "data: {\"id\":\"c3\",\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"bash\",\"arguments\":\"\"}}]}}]}\n\n",
+            "data: {\"id\":\"c3\",\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"command\\\":\"}}]}}]}\n\n",
+            "data: {\"id\":\"c3\",\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"ls\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+            "data: [DONE]\n\n"
+        );
+
+        let mut out = translator.push(upstream.as_bytes());
+        out.push_str(&translator.finish());
+
+        let events: Vec<Value> = out
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .map(|d| serde_json::from_str(d).unwrap())
+            .collect();
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            vec![
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop"
+            ]
+        );
+        assert_eq!(events[4]["content_block"]["name"], "bash");
+        assert_eq!(events[4]["index"], 1);
+        let json: String = events
+            .iter()
+            .filter_map(|e| e["delta"]["partial_json"].as_str())
+            .collect();
+        assert_eq!(json, "{\"command\":\"ls\"}");
+        assert_eq!(events[8]["delta"]["stop_reason"], "tool_use");
+    }
+}
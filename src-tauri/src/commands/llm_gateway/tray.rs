@@ -0,0 +1,196 @@
+//! System Tray - Gateway controls without opening the main window
+//!
+//! The tray menu shows whether the gateway is running and on which port, the provider
+//! and model requests currently default to, and today's spend. It can start or stop the
+//! gateway and enable or disable providers. The menu is rebuilt after each action and
+//! periodically, so the figures stay current while the gateway serves requests.
+
+use std::time::Duration;
+use tauri::menu::{
+    CheckMenuItem, IsMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu,
+};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Manager};
+
+use super::{
+    gateway_snapshot, set_llm_provider_enabled, start_llm_gateway, stop_llm_gateway,
+    GatewaySnapshot,
+};
+use crate::commands::agents::AgentDb;
+
+const TRAY_ID: &str = "llm-gateway";
+
+/// How often the menu is rebuilt to pick up new requests and spend
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+const TOGGLE_GATEWAY: &str = "gateway:toggle";
+const SHOW_WINDOW: &str = "gateway:show";
+const QUIT: &str = "gateway:quit";
+/// Prefix of provider toggle item IDs, followed by the provider name
+const PROVIDER_PREFIX: &str = "gateway:provider:";
+
+fn info_item(app: &AppHandle, text: String) -> tauri::Result<MenuItem<tauri::Wry>> {
+    MenuItem::new(app, text, false, None::<&str>)
+}
+
+fn build_menu(app: &AppHandle, snapshot: &GatewaySnapshot) -> tauri::Result<Menu<tauri::Wry>> {
+    let status = info_item(
+        app,
+        if snapshot.running {
+            format!("Gateway running on port {}", snapshot.port)
+        } else {
+            "Gateway stopped".to_string()
+        },
+    )?;
+    let provider = info_item(
+        app,
+        match (&snapshot.current_provider, &snapshot.current_model) {
+            (Some(provider), Some(model)) => format!("Provider: {} ({})", provider, model),
+            _ => "Provider: none enabled".to_string(),
+        },
+    )?;
+    let spend = info_item(app, format!("Today: ${:.2}", snapshot.today_spend_usd))?;
+    let toggle = MenuItem::with_id(
+        app,
+        TOGGLE_GATEWAY,
+        if snapshot.running {
+            "Stop Gateway"
+        } else {
+            "Start Gateway"
+        },
+        true,
+        None::<&str>,
+    )?;
+
+    let provider_items = snapshot
+        .providers
+        .iter()
+        .map(|p| {
+            CheckMenuItem::with_id(
+                app,
+                format!("{}{}", PROVIDER_PREFIX, p.name),
+                &p.name,
+                true,
+                p.enabled,
+                None::<&str>,
+            )
+        })
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let provider_refs: Vec<&dyn IsMenuItem<tauri::Wry>> = provider_items
+        .iter()
+        .map(|item| item as &dyn IsMenuItem<tauri::Wry>)
+        .collect();
+    let providers = Submenu::with_items(app, "Providers", true, &provider_refs)?;
+
+    Menu::with_items(
+        app,
+        &[
+            &status,
+            &provider,
+            &spend,
+            &PredefinedMenuItem::separator(app)?,
+            &toggle,
+            &providers,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, SHOW_WINDOW, "Open Doggy", true, None::<&str>)?,
+            &MenuItem::with_id(app, QUIT, "Quit", true, None::<&str>)?,
+        ],
+    )
+}
+
+/// Rebuild the tray menu from the gateway's current state
+pub async fn refresh(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let snapshot = match gateway_snapshot(&app.state::<AgentDb>(), &app.state()).await {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            log::warn!("Failed to read gateway state for the tray: {}", e);
+            return;
+        }
+    };
+    match build_menu(app, &snapshot) {
+        Ok(menu) => {
+            if let Err(e) = tray.set_menu(Some(menu)) {
+                log::warn!("Failed to update tray menu: {}", e);
+            }
+        }
+        Err(e) => log::warn!("Failed to build tray menu: {}", e),
+    }
+}
+
+async fn toggle_gateway(app: &AppHandle) -> Result<(), String> {
+    let running = app
+        .state::<super::LLMGatewayState>()
+        .status
+        .read()
+        .await
+        .running;
+    if running {
+        stop_llm_gateway(app.state()).await
+    } else {
+        start_llm_gateway(app.clone(), app.state(), app.state()).await
+    }
+}
+
+async fn toggle_provider(app: &AppHandle, name: &str) -> Result<(), String> {
+    let snapshot = gateway_snapshot(&app.state::<AgentDb>(), &app.state()).await?;
+    let enabled = snapshot
+        .providers
+        .iter()
+        .find(|p| p.name == name)
+        .map(|p| p.enabled)
+        .ok_or_else(|| format!("Provider '{}' not found", name))?;
+    set_llm_provider_enabled(app.state(), app.state(), name.to_string(), !enabled).await?;
+    Ok(())
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        SHOW_WINDOW => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        }
+        QUIT => app.exit(0),
+        id => {
+            let id = id.to_string();
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let result = match id.strip_prefix(PROVIDER_PREFIX) {
+                    Some(name) => toggle_provider(&app, name).await,
+                    None if id == TOGGLE_GATEWAY => toggle_gateway(&app).await,
+                    None => Ok(()),
+                };
+                if let Err(e) = result {
+                    log::warn!("Tray action failed: {}", e);
+                }
+                refresh(&app).await;
+            });
+        }
+    }
+}
+
+/// Create the gateway tray icon and keep its menu up to date
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Doggy LLM Gateway")
+        .menu(&Menu::new(app)?)
+        .show_menu_on_left_click(true)
+        .on_menu_event(handle_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            refresh(&app).await;
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,1026 @@
+//! Request Log - Per-request usage and outcome records for gateway traffic
+//!
+//! Every proxied request is recorded with its route, status, latency, token usage and
+//! cost, which feeds A/B comparisons and usage reporting.
+//!
+//! Streamed responses report usage in their final events, if at all. [`StreamUsageTap`]
+//! watches the events sent to the client and falls back to estimating tokens from the
+//! streamed text when the provider doesn't report usage.
+//!
+//! Clients can attribute requests to a project and session with the [`PROJECT_HEADER`]
+//! and [`SESSION_HEADER`] headers, which usage reports break spend down by. Requests are
+//! also tagged with a fingerprint of the client's gateway API key, so a client can query
+//! its own usage over HTTP.
+//!
+//! Streamed requests also record their time to first token and output throughput,
+//! which matter more than total latency for interactive use.
+//!
+//! Each recorded request is announced with [`COST_EVENT`], carrying its cost and the
+//! day's running total for live spend displays.
+
+use chrono::{Datelike, Local, Months, NaiveDate, NaiveTime, TimeZone};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use super::migrations;
+use super::pricing;
+use super::scrub::scrub;
+use super::translate::SseParser;
+use super::{LLMProvider, ModelConfig};
+
+/// Header naming the project a request belongs to
+pub const PROJECT_HEADER: &str = "x-doggy-project";
+
+/// Header naming the session a request belongs to
+pub const SESSION_HEADER: &str = "x-doggy-session";
+
+/// Event emitted with a [`CostTick`] after every recorded request
+pub const COST_EVENT: &str = "llm-gateway-cost";
+
+/// Cost of a completed request and the day's spend including it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostTick {
+    pub provider: String,
+    pub model: String,
+    /// Cost of the request, if its model has a price
+    pub cost_usd: Option<f64>,
+    /// Total cost of today's requests, in local time
+    pub today_spend_usd: f64,
+}
+
+/// Create the request log table if it doesn't exist yet
+pub fn ensure_schema(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS gateway_request_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            requested_model TEXT NOT NULL,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            status_code INTEGER NOT NULL,
+            latency_ms INTEGER,
+            input_tokens INTEGER,
+            output_tokens INTEGER,
+            cost_usd REAL,
+            retries INTEGER NOT NULL DEFAULT 0,
+            experiment TEXT,
+            arm TEXT,
+            usage_estimated BOOLEAN NOT NULL DEFAULT 0,
+            cache_read_tokens INTEGER,
+            cache_write_tokens INTEGER,
+            project TEXT,
+            session TEXT
+        )",
+        [],
+    )?;
+    // Add columns to tables created by older builds
+    migrations::add_column(
+        conn,
+        "gateway_request_log",
+        "usage_estimated",
+        "BOOLEAN NOT NULL DEFAULT 0",
+    )?;
+    migrations::add_column(conn, "gateway_request_log", "cache_read_tokens", "INTEGER")?;
+    migrations::add_column(conn, "gateway_request_log", "cache_write_tokens", "INTEGER")?;
+    migrations::add_column(conn, "gateway_request_log", "project", "TEXT")?;
+    migrations::add_column(conn, "gateway_request_log", "session", "TEXT")?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_gateway_request_log_experiment
+         ON gateway_request_log(experiment, arm)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Record which client key made each request, for the usage endpoint
+pub fn add_client_key(conn: &Connection) -> rusqlite::Result<()> {
+    migrations::add_column(conn, "gateway_request_log", "client_key", "TEXT")?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_gateway_request_log_client_key
+         ON gateway_request_log(client_key, created_at)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Index the request log by time, for the spend totals computed after every request
+pub fn add_created_at_index(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_gateway_request_log_created_at
+         ON gateway_request_log(created_at)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Record which configured provider served each request, since several may share a
+/// type. Earlier requests are left without one.
+pub fn add_provider_name(conn: &Connection) -> rusqlite::Result<()> {
+    migrations::add_column(conn, "gateway_request_log", "provider_name", "TEXT")
+}
+
+/// Record the time to first token and throughput of streamed requests
+pub fn add_stream_timing(conn: &Connection) -> rusqlite::Result<()> {
+    migrations::add_column(conn, "gateway_request_log", "ttft_ms", "INTEGER")?;
+    migrations::add_column(conn, "gateway_request_log", "tokens_per_second", "REAL")?;
+    Ok(())
+}
+
+/// Fingerprint a client's gateway API key is logged under, so the key itself is never
+/// stored
+pub fn key_fingerprint(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Token usage of a single request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    /// Uncached input tokens
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Input tokens served from the prompt cache
+    pub cache_read_tokens: u64,
+    /// Input tokens written to the prompt cache
+    pub cache_write_tokens: u64,
+    /// Counts were estimated locally rather than reported by the provider
+    pub estimated: bool,
+}
+
+/// One row of the request log
+#[derive(Debug, Clone, Default)]
+pub struct RequestRecord {
+    pub requested_model: String,
+    /// Provider type
+    pub provider: String,
+    /// Name of the configured provider, which tells apart providers of the same type
+    pub provider_name: Option<String>,
+    pub model: String,
+    pub status_code: u16,
+    pub latency_ms: Option<u64>,
+    pub usage: Option<TokenUsage>,
+    pub cost_usd: Option<f64>,
+    /// Retries reported by the client for this request
+    pub retries: u32,
+    pub experiment: Option<String>,
+    pub arm: Option<String>,
+    /// Project and session named by the client's headers
+    pub project: Option<String>,
+    pub session: Option<String>,
+    /// Fingerprint of the API key the client authenticated to the gateway with
+    pub client_key: Option<String>,
+    /// Time to the first streamed token
+    pub ttft_ms: Option<u64>,
+    /// Output tokens per second after the first one arrived
+    pub tokens_per_second: Option<f64>,
+}
+
+/// Shortest generation a throughput is computed over; anything quicker arrived in one
+/// burst and says nothing about generation speed
+const MIN_THROUGHPUT_SECS: f64 = 0.1;
+
+/// Time to first token and throughput of a stream that started at `start`, produced
+/// its first token at `first_token` and `output_tokens` in all by `end`
+pub fn stream_timing(
+    start: Instant,
+    first_token: Option<Instant>,
+    end: Instant,
+    output_tokens: u64,
+) -> (Option<u64>, Option<f64>) {
+    let Some(first_token) = first_token else {
+        return (None, None);
+    };
+    let ttft_ms = first_token.saturating_duration_since(start).as_millis() as u64;
+    let generating = end.saturating_duration_since(first_token).as_secs_f64();
+    let throughput = (generating >= MIN_THROUGHPUT_SECS).then(|| output_tokens as f64 / generating);
+    (Some(ttft_ms), throughput)
+}
+
+/// Counts in a provider usage object: uncached input, output, cache reads and cache
+/// writes. Zero input and output counts are treated as unreported.
+///
+/// OpenAI-style usage includes cached tokens in `prompt_tokens` (as
+/// `prompt_tokens_details.cached_tokens`, or DeepSeek's `prompt_cache_hit_tokens`), while
+/// Anthropic reports `input_tokens` net of `cache_read_input_tokens`.
+fn parse_usage(usage: &Value) -> (Option<u64>, Option<u64>, u64, u64) {
+    let get = |pointer: &str| usage.pointer(pointer).and_then(|v| v.as_u64());
+    let positive = |pointer: &str| get(pointer).filter(|v| *v > 0);
+    let cache_write = get("/cache_creation_input_tokens").unwrap_or(0);
+
+    if let Some(prompt) = positive("/prompt_tokens") {
+        let cached = get("/prompt_tokens_details/cached_tokens")
+            .or_else(|| get("/prompt_cache_hit_tokens"))
+            .unwrap_or(0);
+        return (
+            Some(prompt.saturating_sub(cached)),
+            positive("/completion_tokens"),
+            cached,
+            cache_write,
+        );
+    }
+    (
+        positive("/input_tokens"),
+        positive("/completion_tokens").or_else(|| positive("/output_tokens")),
+        get("/cache_read_input_tokens").unwrap_or(0),
+        cache_write,
+    )
+}
+
+/// Read usage from an OpenAI (`prompt_tokens`) or Anthropic (`input_tokens`) body
+pub fn extract_usage(body: &Value) -> Option<TokenUsage> {
+    let usage = body.get("usage").filter(|u| u.is_object())?;
+    let (input, output, cache_read, cache_write) = parse_usage(usage);
+    Some(TokenUsage {
+        input_tokens: input.unwrap_or(0),
+        output_tokens: output.unwrap_or(0),
+        cache_read_tokens: cache_read,
+        cache_write_tokens: cache_write,
+        estimated: false,
+    })
+}
+
+/// Approximate token counter: ~4 ASCII characters per token, one token per other
+/// character (CJK text tokenizes close to one token per character).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokenEstimator {
+    ascii: u64,
+    other: u64,
+}
+
+impl TokenEstimator {
+    pub fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            if c.is_ascii() {
+                self.ascii += 1;
+            } else {
+                self.other += 1;
+            }
+        }
+    }
+
+    pub fn tokens(&self) -> u64 {
+        self.ascii.div_ceil(4) + self.other
+    }
+}
+
+/// Estimate the token count of a piece of text
+pub fn estimate_text_tokens(text: &str) -> u64 {
+    let mut estimator = TokenEstimator::default();
+    estimator.push(text);
+    estimator.tokens()
+}
+
+/// Collects usage from a streamed response in the OpenAI chunk, Anthropic event or
+/// Gemini chunk format
+#[derive(Debug, Default)]
+pub struct StreamUsageTap {
+    parser: SseParser,
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+    cache_read_tokens: u64,
+    cache_write_tokens: u64,
+    output_text: TokenEstimator,
+    first_output: Option<Instant>,
+}
+
+impl StreamUsageTap {
+    pub fn observe(&mut self, chunk: &[u8]) {
+        for payload in self.parser.push(chunk) {
+            let Ok(event) = serde_json::from_str::<Value>(&payload) else {
+                continue;
+            };
+            self.observe_event(&event);
+        }
+        if self.first_output.is_none() && self.output_text.tokens() > 0 {
+            self.first_output = Some(Instant::now());
+        }
+    }
+
+    /// When the first output text arrived
+    pub fn first_output(&self) -> Option<Instant> {
+        self.first_output
+    }
+
+    fn observe_event(&mut self, event: &Value) {
+        let usage = event
+            .get("usage")
+            .or_else(|| event.pointer("/message/usage"))
+            .or_else(|| event.pointer("/response/usage"))
+            .filter(|u| u.is_object());
+        if let Some(usage) = usage {
+            let (input, output, cache_read, cache_write) = parse_usage(usage);
+            self.input_tokens = input.or(self.input_tokens);
+            self.output_tokens = output.or(self.output_tokens);
+            self.cache_read_tokens = self.cache_read_tokens.max(cache_read);
+            self.cache_write_tokens = self.cache_write_tokens.max(cache_write);
+        }
+
+        // Anthropic content_block_delta
+        if let Some(delta) = event.get("delta") {
+            for key in ["text", "partial_json", "thinking"] {
+                if let Some(text) = delta.get(key).and_then(|t| t.as_str()) {
+                    self.output_text.push(text);
+                }
+            }
+        }
+
+        // Responses API text and argument deltas
+        if let Some(text) = event.get("delta").and_then(|d| d.as_str()) {
+            self.output_text.push(text);
+        }
+
+        // OpenAI chunk deltas
+        for choice in event
+            .get("choices")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            // Legacy completion chunks carry their text directly
+            if let Some(text) = choice.get("text").and_then(|t| t.as_str()) {
+                self.output_text.push(text);
+            }
+            let Some(delta) = choice.get("delta") else {
+                continue;
+            };
+            for key in ["content", "reasoning_content"] {
+                if let Some(text) = delta.get(key).and_then(|t| t.as_str()) {
+                    self.output_text.push(text);
+                }
+            }
+            for call in delta
+                .get("tool_calls")
+                .and_then(|c| c.as_array())
+                .into_iter()
+                .flatten()
+            {
+                if let Some(args) = call.pointer("/function/arguments").and_then(|a| a.as_str()) {
+                    self.output_text.push(args);
+                }
+            }
+        }
+
+        // Gemini candidates and usage metadata
+        if let Some(metadata) = event.get("usageMetadata") {
+            let get = |key: &str| metadata.get(key).and_then(|v| v.as_u64());
+            let cached = get("cachedContentTokenCount").unwrap_or(0);
+            if let Some(prompt) = get("promptTokenCount").filter(|v| *v > 0) {
+                self.input_tokens = Some(prompt.saturating_sub(cached));
+            }
+            self.output_tokens = get("candidatesTokenCount")
+                .filter(|v| *v > 0)
+                .or(self.output_tokens);
+            self.cache_read_tokens = self.cache_read_tokens.max(cached);
+        }
+        for part in event
+            .pointer("/candidates/0/content/parts")
+            .and_then(|p| p.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                self.output_text.push(text);
+            }
+        }
+    }
+
+    /// Final usage: provider-reported counts where available, estimates otherwise
+    pub fn finish(&self, prompt_estimate: u64) -> TokenUsage {
+        let estimated = self.input_tokens.is_none() || self.output_tokens.is_none();
+        TokenUsage {
+            input_tokens: self.input_tokens.unwrap_or(prompt_estimate),
+            output_tokens: self
+                .output_tokens
+                .unwrap_or_else(|| self.output_text.tokens()),
+            cache_read_tokens: self.cache_read_tokens,
+            cache_write_tokens: self.cache_write_tokens,
+            estimated,
+        }
+    }
+}
+
+/// Per-1M prices of prompt cache reads and writes: the model's configured prices, else
+/// the provider's usual multiples of the input price (no discount for providers without
+/// known cache pricing)
+pub fn cache_prices(provider: &LLMProvider, model: &ModelConfig) -> (f64, f64) {
+    let (read, write) = match provider {
+        LLMProvider::Anthropic => (0.1, 1.25),
+        LLMProvider::DeepSeek => (0.1, 1.0),
+        LLMProvider::Gemini => (0.25, 1.0),
+        LLMProvider::OpenAI => (0.5, 1.0),
+        _ => (1.0, 1.0),
+    };
+    (
+        model.cache_read_price.unwrap_or(model.input_price * read),
+        model.cache_write_price.unwrap_or(model.input_price * write),
+    )
+}
+
+/// `cost` with the model's fees added: its percentage and then its flat per-request fee
+pub fn with_fees(model: &ModelConfig, cost: f64) -> f64 {
+    cost * (1.0 + model.fee_percent.unwrap_or(0.0) / 100.0) + model.request_fee.unwrap_or(0.0)
+}
+
+/// Cost in USD of `usage` at the model's per-1M prices for the prompt's tier, fees included
+pub fn usage_cost(provider: &LLMProvider, model: &ModelConfig, usage: TokenUsage) -> f64 {
+    let prompt_tokens = usage.input_tokens + usage.cache_read_tokens + usage.cache_write_tokens;
+    let model = pricing::tiered(model, prompt_tokens);
+    let (cache_read_price, cache_write_price) = cache_prices(provider, &model);
+    let cost = (usage.input_tokens as f64 * model.input_price
+        + usage.output_tokens as f64 * model.output_price
+        + usage.cache_read_tokens as f64 * cache_read_price
+        + usage.cache_write_tokens as f64 * cache_write_price)
+        / 1_000_000.0;
+    with_fees(&model, cost)
+}
+
+/// Cost in USD of an image generation: the model's per-image price when set, else its
+/// token prices applied to the reported usage
+pub fn image_cost(
+    provider: &LLMProvider,
+    model: &ModelConfig,
+    images: usize,
+    usage: Option<TokenUsage>,
+) -> Option<f64> {
+    match model.image_price {
+        Some(price) => Some(with_fees(model, price * images as f64)),
+        None => usage.map(|usage| usage_cost(provider, model, usage)),
+    }
+}
+
+/// Cost in USD of a transcription: the model's per-minute price when set and the audio's
+/// duration is known, else its token prices applied to the reported usage
+pub fn audio_cost(
+    provider: &LLMProvider,
+    model: &ModelConfig,
+    seconds: Option<f64>,
+    usage: Option<TokenUsage>,
+) -> Option<f64> {
+    match (model.audio_price, seconds) {
+        (Some(price), Some(seconds)) => Some(with_fees(model, price * seconds / 60.0)),
+        _ => usage.map(|usage| usage_cost(provider, model, usage)),
+    }
+}
+
+/// Cost in USD of speech synthesis: the model's per-character price when set, else its
+/// token prices applied to the estimated input tokens
+pub fn speech_cost(provider: &LLMProvider, model: &ModelConfig, input: &str) -> f64 {
+    match model.speech_price {
+        Some(price) => with_fees(model, input.chars().count() as f64 * price / 1_000_000.0),
+        None => usage_cost(provider, model, speech_usage(input)),
+    }
+}
+
+/// Estimated usage of a speech request's input text
+pub fn speech_usage(input: &str) -> TokenUsage {
+    TokenUsage {
+        input_tokens: estimate_text_tokens(input),
+        estimated: true,
+        ..Default::default()
+    }
+}
+
+pub fn insert_record(conn: &Connection, record: &RequestRecord) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO gateway_request_log
+            (requested_model, provider, model, status_code, latency_ms, input_tokens,
+             output_tokens, cost_usd, retries, experiment, arm, usage_estimated,
+             cache_read_tokens, cache_write_tokens, project, session, client_key, ttft_ms,
+             tokens_per_second, provider_name)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+                 ?18, ?19, ?20)",
+        params![
+            scrub(&record.requested_model),
+            record.provider,
+            record.model,
+            record.status_code,
+            record.latency_ms.map(|l| l as i64),
+            record.usage.map(|u| u.input_tokens as i64),
+            record.usage.map(|u| u.output_tokens as i64),
+            record.cost_usd,
+            record.retries,
+            record.experiment,
+            record.arm,
+            record.usage.is_some_and(|u| u.estimated),
+            record.usage.map(|u| u.cache_read_tokens as i64),
+            record.usage.map(|u| u.cache_write_tokens as i64),
+            record.project,
+            record.session,
+            record.client_key,
+            record.ttft_ms.map(|t| t as i64),
+            record.tokens_per_second,
+            record.provider_name,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Start of a local day as a UTC timestamp in the request log's format
+fn local_midnight(day: NaiveDate) -> String {
+    let midnight = day.and_time(NaiveTime::MIN);
+    // Where midnight falls in a daylight saving gap, the day starts an hour later
+    let start = Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            Local
+                .from_local_datetime(&(midnight + chrono::Duration::hours(1)))
+                .earliest()
+        })
+        .map_or(midnight, |t| t.naive_utc());
+    start.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// UTC bounds of the local days from `first` up to but not including `end`, so spend
+/// queries compare `created_at` directly and use its index
+fn local_range(first: NaiveDate, end: NaiveDate) -> (String, String) {
+    (local_midnight(first), local_midnight(end))
+}
+
+/// Today, in local time
+fn today_range() -> (String, String) {
+    let today = Local::now().date_naive();
+    local_range(today, today.succ_opt().unwrap_or(today))
+}
+
+/// This month, in local time
+fn month_range() -> (String, String) {
+    let today = Local::now().date_naive();
+    let first = today - chrono::Duration::days(today.day0() as i64);
+    local_range(first, first + Months::new(1))
+}
+
+/// Total cost of the requests made from `start` up to `end`
+fn spend_between(conn: &Connection, (start, end): (String, String)) -> rusqlite::Result<f64> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(cost_usd), 0) FROM gateway_request_log
+         WHERE created_at >= ?1 AND created_at < ?2",
+        params![start, end],
+        |row| row.get(0),
+    )
+}
+
+/// Total cost of the requests made today, in local time
+pub fn today_spend(conn: &Connection) -> rusqlite::Result<f64> {
+    spend_between(conn, today_range())
+}
+
+/// Total cost of the requests made this month, in local time
+pub fn month_spend(conn: &Connection) -> rusqlite::Result<f64> {
+    spend_between(conn, month_range())
+}
+
+/// Cost of this month's requests per configured provider name, in local time
+pub fn month_spend_by_provider(conn: &Connection) -> rusqlite::Result<HashMap<String, f64>> {
+    let (start, end) = month_range();
+    let mut stmt = conn.prepare(
+        "SELECT provider_name, COALESCE(SUM(cost_usd), 0) FROM gateway_request_log
+         WHERE created_at >= ?1 AND created_at < ?2 AND provider_name IS NOT NULL
+         GROUP BY provider_name",
+    )?;
+    let rows = stmt.query_map(params![start, end], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Usage of one model by a client key
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelUsage {
+    pub provider: String,
+    pub model: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// Usage of a client key between two local dates, inclusive
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct KeyUsage {
+    pub start: String,
+    pub end: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+    /// Costliest first
+    pub by_model: Vec<ModelUsage>,
+}
+
+/// Usage of the client key with fingerprint `client_key` from `start` to `end`
+pub fn key_usage(
+    conn: &Connection,
+    client_key: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> rusqlite::Result<KeyUsage> {
+    let mut stmt = conn.prepare(
+        "SELECT provider, model, COUNT(*), COALESCE(SUM(input_tokens), 0),
+                COALESCE(SUM(output_tokens), 0), COALESCE(SUM(cost_usd), 0)
+         FROM gateway_request_log
+         WHERE client_key = ?1 AND date(created_at, 'localtime') BETWEEN ?2 AND ?3
+         GROUP BY provider, model
+         ORDER BY 6 DESC",
+    )?;
+    let by_model = stmt
+        .query_map(
+            params![client_key, start.to_string(), end.to_string()],
+            |row| {
+                Ok(ModelUsage {
+                    provider: row.get(0)?,
+                    model: row.get(1)?,
+                    requests: row.get::<_, i64>(2)? as u64,
+                    input_tokens: row.get::<_, i64>(3)? as u64,
+                    output_tokens: row.get::<_, i64>(4)? as u64,
+                    cost_usd: row.get(5)?,
+                })
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut usage = KeyUsage {
+        start: start.to_string(),
+        end: end.to_string(),
+        ..Default::default()
+    };
+    for model in &by_model {
+        usage.requests += model.requests;
+        usage.input_tokens += model.input_tokens;
+        usage.output_tokens += model.output_tokens;
+        usage.cost_usd += model.cost_usd;
+    }
+    usage.by_model = by_model;
+    Ok(usage)
+}
+
+/// Streaming responsiveness of one model
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelLatency {
+    pub provider: String,
+    pub model: String,
+    /// Streamed requests with a first token
+    pub streamed_requests: u64,
+    pub avg_ttft_ms: Option<f64>,
+    pub min_ttft_ms: Option<u64>,
+    pub max_ttft_ms: Option<u64>,
+    pub avg_tokens_per_second: Option<f64>,
+    /// Average total latency over all successful requests, streamed or not
+    pub avg_latency_ms: Option<f64>,
+}
+
+/// Time to first token and throughput per model for requests made since `since`
+/// (local date), most streamed first
+pub fn latency_stats(conn: &Connection, since: NaiveDate) -> rusqlite::Result<Vec<ModelLatency>> {
+    let mut stmt = conn.prepare(
+        "SELECT provider, model, COUNT(ttft_ms), AVG(ttft_ms), MIN(ttft_ms), MAX(ttft_ms),
+                AVG(tokens_per_second), AVG(latency_ms)
+         FROM gateway_request_log
+         WHERE status_code < 400 AND date(created_at, 'localtime') >= ?1
+         GROUP BY provider, model
+         ORDER BY 3 DESC, provider, model",
+    )?;
+    let rows = stmt.query_map([since.to_string()], |row| {
+        Ok(ModelLatency {
+            provider: row.get(0)?,
+            model: row.get(1)?,
+            streamed_requests: row.get::<_, i64>(2)? as u64,
+            avg_ttft_ms: row.get(3)?,
+            min_ttft_ms: row.get::<_, Option<i64>>(4)?.map(|t| t as u64),
+            max_ttft_ms: row.get::<_, Option<i64>>(5)?.map(|t| t as u64),
+            avg_tokens_per_second: row.get(6)?,
+            avg_latency_ms: row.get(7)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Aggregated outcomes of one arm of an A/B test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbTestArmStats {
+    pub experiment: String,
+    pub arm: String,
+    pub provider: String,
+    pub model: String,
+    pub requests: u64,
+    pub errors: u64,
+    pub avg_latency_ms: Option<f64>,
+    pub total_cost_usd: f64,
+    pub avg_cost_usd: Option<f64>,
+    /// Client retries per request
+    pub retry_rate: f64,
+}
+
+/// Per-arm outcome statistics, optionally limited to one experiment
+pub fn ab_test_stats(
+    conn: &Connection,
+    experiment: Option<&str>,
+) -> rusqlite::Result<Vec<AbTestArmStats>> {
+    let mut stmt = conn.prepare(
+        "SELECT experiment, arm, provider, model, COUNT(*),
+                SUM(CASE WHEN status_code >= 400 THEN 1 ELSE 0 END),
+                AVG(latency_ms), COALESCE(SUM(cost_usd), 0), AVG(cost_usd),
+                COALESCE(AVG(retries), 0)
+         FROM gateway_request_log
+         WHERE experiment IS NOT NULL AND (?1 IS NULL OR experiment = ?1)
+         GROUP BY experiment, arm, provider, model
+         ORDER BY experiment, arm",
+    )?;
+    let rows = stmt.query_map(params![experiment], |row| {
+        Ok(AbTestArmStats {
+            experiment: row.get(0)?,
+            arm: row.get(1)?,
+            provider: row.get(2)?,
+            model: row.get(3)?,
+            requests: row.get::<_, i64>(4)? as u64,
+            errors: row.get::<_, i64>(5)? as u64,
+            avg_latency_ms: row.get(6)?,
+            total_cost_usd: row.get(7)?,
+            avg_cost_usd: row.get(8)?,
+            retry_rate: row.get(9)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extract_usage() {
+        let openai = json!({"usage": {"prompt_tokens": 10, "completion_tokens": 5}});
+        assert_eq!(
+            extract_usage(&openai),
+            Some(TokenUsage {
+                input_tokens: 10,
+                output_tokens: 5,
+                ..Default::default()
+            })
+        );
+        let anthropic = json!({"usage": {"input_tokens": 3, "output_tokens": 4}});
+        assert_eq!(extract_usage(&anthropic).unwrap().output_tokens, 4);
+
+        // Cached prompt tokens are split out of the input count
+        let cached = json!({"usage": {
+            "prompt_tokens": 1000, "completion_tokens": 5,
+            "prompt_tokens_details": {"cached_tokens": 800}
+        }});
+        let usage = extract_usage(&cached).unwrap();
+        assert_eq!((usage.input_tokens, usage.cache_read_tokens), (200, 800));
+        let anthropic = json!({"usage": {
+            "input_tokens": 20, "output_tokens": 5,
+            "cache_read_input_tokens": 1000, "cache_creation_input_tokens": 100
+        }});
+        let usage = extract_usage(&anthropic).unwrap();
+        assert_eq!(
+            (usage.cache_read_tokens, usage.cache_write_tokens),
+            (1000, 100)
+        );
+
+        // $3/M input: 20 uncached + 1000 read at 10% + 100 written at 125%
+        let model = ModelConfig {
+            input_price: 3.0,
+            ..Default::default()
+        };
+        let cost = usage_cost(&LLMProvider::Anthropic, &model, usage);
+        assert!((cost - (20.0 * 3.0 + 1000.0 * 0.3 + 100.0 * 3.75) / 1e6).abs() < 1e-12);
+
+        // Past 1000 prompt tokens the whole request is billed at $6/M, plus a 5% fee
+        let model = ModelConfig {
+            input_price: 3.0,
+            price_tiers: vec![pricing::PriceTier {
+                above_input_tokens: 1000,
+                input_price: 6.0,
+                output_price: 0.0,
+                cache_read_price: None,
+                cache_write_price: None,
+            }],
+            fee_percent: Some(5.0),
+            ..Default::default()
+        };
+        let cost = usage_cost(&LLMProvider::Anthropic, &model, usage);
+        let tokens = (20.0 * 6.0 + 1000.0 * 0.6 + 100.0 * 7.5) / 1e6;
+        assert!((cost - tokens * 1.05).abs() < 1e-12);
+
+        // A flat fee is added after the percentage, to token and per-image costs alike
+        let model = ModelConfig {
+            request_fee: Some(0.01),
+            image_price: Some(0.04),
+            ..model
+        };
+        let cost = usage_cost(&LLMProvider::Anthropic, &model, usage);
+        assert!((cost - (tokens * 1.05 + 0.01)).abs() < 1e-12);
+        let images = image_cost(&LLMProvider::OpenAI, &model, 2, None).unwrap();
+        assert!((images - (0.08 * 1.05 + 0.01)).abs() < 1e-12);
+        assert_eq!(extract_usage(&json!({"usage": null})), None);
+    }
+
+    #[test]
+    fn test_stream_usage_reported() {
+        let mut tap = StreamUsageTap::default();
+        tap.observe(
+            concat!(
+                "event: message_start\n",
+                "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+                "event: content_block_delta\n",
+                "data: {\"type\":\"content_block_delta\",\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+                "event: message_delta\n",
+                "data: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":12}}\n\n"
+            )
+            .as_bytes(),
+        );
+        let usage = tap.finish(999);
+        assert_eq!((usage.input_tokens, usage.output_tokens), (25, 12));
+        assert!(!usage.estimated);
+    }
+
+    #[test]
+    fn test_stream_usage_estimated() {
+        let mut tap = StreamUsageTap::default();
+        tap.observe(b"data: {\"choices\":[{\"delta\":{\"content\":\"abcdefgh\"}}]}\n\n");
+        tap.observe("data: {\"choices\":[{\"delta\":{\"content\":\"你好\"}}]}\n\n".as_bytes());
+        tap.observe(b"data: [DONE]\n\n");
+
+        let usage = tap.finish(40);
+        assert_eq!((usage.input_tokens, usage.output_tokens), (40, 4));
+        assert!(usage.estimated);
+    }
+
+    #[test]
+    fn test_ab_test_stats() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::run(&conn).unwrap();
+
+        for (arm, status, latency, cost, retries) in [
+            ("control", 200, 100, 0.02, 0),
+            ("control", 500, 300, 0.0, 1),
+            ("variant", 200, 50, 0.01, 0),
+        ] {
+            insert_record(
+                &conn,
+                &RequestRecord {
+                    requested_model: "claude-3-5-sonnet".to_string(),
+                    provider: "openai".to_string(),
+                    model: arm.to_string(),
+                    status_code: status,
+                    latency_ms: Some(latency),
+                    cost_usd: Some(cost),
+                    retries,
+                    experiment: Some("sonnet-vs-mini".to_string()),
+                    arm: Some(arm.to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        let stats = ab_test_stats(&conn, Some("sonnet-vs-mini")).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].arm, "control");
+        assert_eq!(stats[0].requests, 2);
+        assert_eq!(stats[0].errors, 1);
+        assert_eq!(stats[0].avg_latency_ms, Some(200.0));
+        assert_eq!(stats[0].retry_rate, 0.5);
+        assert_eq!(stats[1].total_cost_usd, 0.01);
+    }
+
+    #[test]
+    fn test_key_usage() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::run(&conn).unwrap();
+        let key = key_fingerprint("sk-doggy-team");
+        assert_eq!(key.len(), 16);
+        for (client_key, model, cost) in [
+            (Some(&key), "gpt-4o", 0.5),
+            (Some(&key), "gpt-4o", 0.25),
+            (Some(&key), "gpt-4o-mini", 0.01),
+            (None, "gpt-4o", 9.0),
+        ] {
+            insert_record(
+                &conn,
+                &RequestRecord {
+                    provider: "openai".to_string(),
+                    model: model.to_string(),
+                    usage: Some(TokenUsage {
+                        input_tokens: 100,
+                        output_tokens: 10,
+                        ..Default::default()
+                    }),
+                    cost_usd: Some(cost),
+                    client_key: client_key.cloned(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        let today = chrono::Local::now().date_naive();
+        let usage = key_usage(&conn, &key, today, today).unwrap();
+        assert_eq!(usage.requests, 3);
+        assert_eq!(usage.input_tokens, 300);
+        assert_eq!(usage.cost_usd, 0.76);
+        assert_eq!(usage.by_model[0].model, "gpt-4o");
+        assert_eq!(usage.by_model[0].requests, 2);
+    }
+
+    #[test]
+    fn test_spend_totals() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::run(&conn).unwrap();
+        for (name, cost) in [("OpenAI", 0.5), ("OpenAI", 2.0), ("Azure OpenAI", 0.25)] {
+            let record = RequestRecord {
+                provider: "openai".to_string(),
+                provider_name: Some(name.to_string()),
+                cost_usd: Some(cost),
+                ..Default::default()
+            };
+            insert_record(&conn, &record).unwrap();
+        }
+        // The second request was made long ago
+        conn.execute(
+            "UPDATE gateway_request_log SET created_at = datetime('now', '-40 days') WHERE id = 2",
+            [],
+        )
+        .unwrap();
+
+        assert_eq!(today_spend(&conn).unwrap(), 0.75);
+        assert_eq!(month_spend(&conn).unwrap(), 0.75);
+        // Providers sharing a type are totalled apart
+        let by_provider = month_spend_by_provider(&conn).unwrap();
+        assert_eq!(
+            (by_provider["OpenAI"], by_provider["Azure OpenAI"]),
+            (0.5, 0.25)
+        );
+
+        let (start, end) = today_range();
+        assert!(start < end);
+        let plan: String = conn
+            .query_row(
+                "EXPLAIN QUERY PLAN SELECT SUM(cost_usd) FROM gateway_request_log
+                 WHERE created_at >= ?1 AND created_at < ?2",
+                params![start, end],
+                |row| row.get(3),
+            )
+            .unwrap();
+        assert!(
+            plan.contains("idx_gateway_request_log_created_at"),
+            "{}",
+            plan
+        );
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        let start = Instant::now();
+        let first = start + std::time::Duration::from_millis(400);
+        let end = first + std::time::Duration::from_secs(2);
+        assert_eq!(
+            stream_timing(start, Some(first), end, 100),
+            (Some(400), Some(50.0))
+        );
+        assert_eq!(
+            stream_timing(start, Some(first), first, 1),
+            (Some(400), None)
+        );
+        assert_eq!(stream_timing(start, None, end, 100), (None, None));
+
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::run(&conn).unwrap();
+        for (model, ttft_ms, tokens_per_second) in [
+            ("deepseek-chat", Some(300), Some(40.0)),
+            ("deepseek-chat", Some(500), Some(60.0)),
+            ("deepseek-chat", None, None),
+            ("gpt-4o", Some(900), None),
+        ] {
+            insert_record(
+                &conn,
+                &RequestRecord {
+                    provider: "deepseek".to_string(),
+                    model: model.to_string(),
+                    status_code: 200,
+                    latency_ms: Some(1000),
+                    ttft_ms,
+                    tokens_per_second,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        let today = chrono::Local::now().date_naive();
+        let stats = latency_stats(&conn, today).unwrap();
+        assert_eq!(stats[0].model, "deepseek-chat");
+        assert_eq!(stats[0].streamed_requests, 2);
+        assert_eq!(stats[0].avg_ttft_ms, Some(400.0));
+        assert_eq!(stats[0].max_ttft_ms, Some(500));
+        assert_eq!(stats[0].avg_tokens_per_second, Some(50.0));
+        assert_eq!(stats[1].avg_tokens_per_second, None);
+    }
+}
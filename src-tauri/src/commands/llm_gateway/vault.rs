@@ -0,0 +1,173 @@
+//! Settings Vault - Optional master-password encryption of the stored gateway settings
+//!
+//! For users who can't keep provider keys in the system keychain, the serialized
+//! `GatewaySettings` blob can be stored encrypted with AES-256-GCM under a key derived
+//! from a master password with Argon2id. The derived key only lives in memory: after a
+//! restart the settings stay locked until the password is entered again.
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+
+/// Version of the envelope format below
+const VERSION: u32 = 1;
+
+const SALT_LEN: usize = 16;
+
+/// Encrypted settings as stored in place of the plain JSON
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    encrypted: Sealed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Sealed {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Key derived from the master password, with the salt it was derived with
+#[derive(Clone)]
+pub struct VaultKey {
+    key: [u8; 32],
+    salt: [u8; SALT_LEN],
+}
+
+impl VaultKey {
+    fn derive(password: &str, salt: [u8; SALT_LEN]) -> Result<Self, String> {
+        if password.is_empty() {
+            return Err("Master password must not be empty".to_string());
+        }
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(password.as_bytes(), &salt, &mut key)
+            .map_err(|e| format!("Failed to derive key: {}", e))?;
+        Ok(Self { key, salt })
+    }
+
+    /// Derive a key for a new password with a fresh salt
+    pub fn generate(password: &str) -> Result<Self, String> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(password, salt)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key))
+    }
+
+    /// Encrypt `plaintext` into a stored envelope
+    pub fn seal(&self, plaintext: &str) -> Result<String, String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| "Failed to encrypt settings".to_string())?;
+        let envelope = Envelope {
+            encrypted: Sealed {
+                version: VERSION,
+                salt: STANDARD.encode(self.salt),
+                nonce: STANDARD.encode(nonce),
+                ciphertext: STANDARD.encode(ciphertext),
+            },
+        };
+        serde_json::to_string(&envelope).map_err(|e| e.to_string())
+    }
+
+    /// Decrypt a stored envelope; fails on a wrong key or tampered data
+    pub fn open(&self, stored: &str) -> Result<String, String> {
+        let sealed = parse(stored).ok_or("Settings are not encrypted")?;
+        let nonce = decode(&sealed.nonce)?;
+        if nonce.len() != 12 {
+            return Err("Invalid encrypted settings".to_string());
+        }
+        let plaintext = self
+            .cipher()
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                decode(&sealed.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| "Incorrect master password".to_string())?;
+        String::from_utf8(plaintext).map_err(|_| "Invalid encrypted settings".to_string())
+    }
+
+    /// Derive the key `stored` was sealed with from `password`, checking that it opens it.
+    /// Returns the key and the decrypted settings.
+    pub fn unlock(stored: &str, password: &str) -> Result<(Self, String), String> {
+        let sealed = parse(stored).ok_or("Settings are not encrypted")?;
+        if sealed.version != VERSION {
+            return Err(format!(
+                "Unsupported encrypted settings version {}",
+                sealed.version
+            ));
+        }
+        let salt: [u8; SALT_LEN] = decode(&sealed.salt)?
+            .try_into()
+            .map_err(|_| "Invalid encrypted settings".to_string())?;
+        let key = Self::derive(password, salt)?;
+        let plaintext = key.open(stored)?;
+        Ok((key, plaintext))
+    }
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, String> {
+    STANDARD
+        .decode(value)
+        .map_err(|_| "Invalid encrypted settings".to_string())
+}
+
+fn parse(stored: &str) -> Option<Sealed> {
+    serde_json::from_str::<Envelope>(stored)
+        .ok()
+        .map(|envelope| envelope.encrypted)
+}
+
+/// Whether a stored settings value is an encrypted envelope
+pub fn is_encrypted(stored: &str) -> bool {
+    parse(stored).is_some()
+}
+
+fn slot() -> &'static Mutex<Option<VaultKey>> {
+    static KEY: OnceLock<Mutex<Option<VaultKey>>> = OnceLock::new();
+    KEY.get_or_init(|| Mutex::new(None))
+}
+
+/// The key of the unlocked settings, if they have been unlocked this session
+pub fn current_key() -> Option<VaultKey> {
+    slot().lock().ok().and_then(|key| key.clone())
+}
+
+/// Remember `key` for this session, or forget the current one
+pub fn set_key(key: Option<VaultKey>) {
+    if let Ok(mut slot) = slot().lock() {
+        *slot = key;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_unlock() {
+        let key = VaultKey::generate("correct horse").unwrap();
+        let stored = key.seal(r#"{"port":8080}"#).unwrap();
+        assert!(is_encrypted(&stored));
+        assert!(!stored.contains("8080"));
+        assert!(!is_encrypted(r#"{"port":8080}"#));
+
+        let (unlocked, plaintext) = VaultKey::unlock(&stored, "correct horse").unwrap();
+        assert_eq!(plaintext, r#"{"port":8080}"#);
+        assert_eq!(unlocked.open(&stored).unwrap(), plaintext);
+        assert_eq!(
+            VaultKey::unlock(&stored, "battery staple").err().unwrap(),
+            "Incorrect master password"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Vision - Image content handling for multimodal requests
+//!
+//! Inline images are checked against the target provider's size limits and downscaled
+//! and re-encoded as JPEG when they don't fit. Both the Anthropic `image` block and the
+//! OpenAI `image_url` data-URL part are handled.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, GenericImageView};
+use serde_json::{json, Value};
+
+use super::ProviderConfig;
+
+/// Capability tag for models that accept image input
+pub const VISION_CAPABILITY: &str = "vision";
+
+/// Size limits an image must satisfy before being sent to a provider
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImageLimits {
+    pub max_bytes: Option<u64>,
+    pub max_dimension: Option<u32>,
+}
+
+impl ImageLimits {
+    pub fn for_provider(provider: &ProviderConfig) -> Self {
+        Self {
+            max_bytes: provider.max_image_bytes,
+            max_dimension: provider.max_image_dimension,
+        }
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_bytes.is_none() && self.max_dimension.is_none()
+    }
+}
+
+/// Whether a request (Anthropic or OpenAI shape) contains image content
+pub fn request_has_images(request: &Value) -> bool {
+    request
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m.get("content").and_then(|c| c.as_array()))
+        .flatten()
+        .any(|part| {
+            matches!(
+                part.get("type").and_then(|t| t.as_str()),
+                Some("image") | Some("image_url")
+            )
+        })
+}
+
+/// Convert an Anthropic image block into an OpenAI `image_url` content part
+pub fn anthropic_image_to_openai(block: &Value) -> Option<Value> {
+    let source = block.get("source")?;
+    let url = match source.get("type").and_then(|t| t.as_str())? {
+        "base64" => format!(
+            "data:{};base64,{}",
+            source.get("media_type")?.as_str()?,
+            source.get("data")?.as_str()?
+        ),
+        "url" => source.get("url")?.as_str()?.to_string(),
+        _ => return None,
+    };
+    Some(json!({"type": "image_url", "image_url": {"url": url}}))
+}
+
+fn split_data_url(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix("data:")?;
+    let (media_type, data) = rest.split_once(";base64,")?;
+    Some((media_type, data))
+}
+
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    // JPEG has no alpha channel
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, quality))
+        .map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+/// Make an encoded image fit `limits`, returning the new media type and bytes, or
+/// `None` if it already fits.
+pub fn fit_image(bytes: &[u8], limits: ImageLimits) -> Result<Option<(String, Vec<u8>)>, String> {
+    let image = image::load_from_memory(bytes).map_err(|e| format!("Unreadable image: {}", e))?;
+    let (width, height) = image.dimensions();
+    let too_large = |len: usize| limits.max_bytes.is_some_and(|max| len as u64 > max);
+    let max_dimension = limits.max_dimension.unwrap_or(u32::MAX);
+
+    if width.max(height) <= max_dimension && !too_large(bytes.len()) {
+        return Ok(None);
+    }
+
+    let mut image = if width.max(height) > max_dimension {
+        image.resize(max_dimension, max_dimension, FilterType::Lanczos3)
+    } else {
+        image
+    };
+
+    loop {
+        for quality in [85, 70, 55] {
+            let encoded = encode_jpeg(&image, quality)?;
+            if !too_large(encoded.len()) {
+                return Ok(Some(("image/jpeg".to_string(), encoded)));
+            }
+        }
+        let (w, h) = image.dimensions();
+        if w.max(h) <= 64 {
+            return Err("Image cannot be shrunk below the provider's size limit".to_string());
+        }
+        image = image.resize(w / 2, h / 2, FilterType::Triangle);
+    }
+}
+
+/// Fit a base64 payload, returning the replacement media type and base64 data
+fn fit_base64(data: &str, limits: ImageLimits) -> Result<Option<(String, String)>, String> {
+    let bytes = STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("Invalid base64 image: {}", e))?;
+    Ok(fit_image(&bytes, limits)?.map(|(media_type, bytes)| (media_type, STANDARD.encode(bytes))))
+}
+
+/// Downscale every inline image in a request (Anthropic or OpenAI shape) to `limits`
+pub fn fit_request_images(request: &mut Value, limits: ImageLimits) -> Result<(), String> {
+    if limits.is_unlimited() {
+        return Ok(());
+    }
+
+    let parts = request
+        .get_mut("messages")
+        .and_then(|m| m.as_array_mut())
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m.get_mut("content").and_then(|c| c.as_array_mut()))
+        .flatten();
+
+    for part in parts {
+        match part.get("type").and_then(|t| t.as_str()) {
+            Some("image") => {
+                let Some(source) = part.get_mut("source") else {
+                    continue;
+                };
+                let Some(data) = source.get("data").and_then(|d| d.as_str()) else {
+                    continue;
+                };
+                if let Some((media_type, data)) = fit_base64(data, limits)? {
+                    source["media_type"] = Value::String(media_type);
+                    source["data"] = Value::String(data);
+                }
+            }
+            Some("image_url") => {
+                let Some(url) = part.pointer("/image_url/url").and_then(|u| u.as_str()) else {
+                    continue;
+                };
+                let Some((_, data)) = split_data_url(url) else {
+                    continue;
+                };
+                if let Some((media_type, data)) = fit_base64(data, limits)? {
+                    part["image_url"]["url"] =
+                        Value::String(format!("data:{};base64,{}", media_type, data));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn png_base64(width: u32, height: u32) -> String {
+        let image = ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+        });
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        STANDARD.encode(bytes.into_inner())
+    }
+
+    #[test]
+    fn test_image_block_conversion() {
+        let block = json!({
+            "type": "image",
+            "source": {"type": "base64", "media_type": "image/png", "data": "AAAA"}
+        });
+        let part = anthropic_image_to_openai(&block).unwrap();
+        assert_eq!(part["image_url"]["url"], "data:image/png;base64,AAAA");
+
+        let url = json!({"type": "image", "source": {"type": "url", "url": "https://x/y.png"}});
+        assert_eq!(
+            anthropic_image_to_openai(&url).unwrap()["image_url"]["url"],
+            "https://x/y.png"
+        );
+    }
+
+    #[test]
+    fn test_fit_request_images_downscales() {
+        let mut request = json!({
+            "messages": [{"role": "user", "content": [
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": png_base64(400, 200)}},
+                {"type": "text", "text": "What is this?"}
+            ]}]
+        });
+        assert!(request_has_images(&request));
+
+        let limits = ImageLimits {
+            max_bytes: None,
+            max_dimension: Some(100),
+        };
+        fit_request_images(&mut request, limits).unwrap();
+
+        let source = &request["messages"][0]["content"][0]["source"];
+        assert_eq!(source["media_type"], "image/jpeg");
+        let bytes = STANDARD.decode(source["data"].as_str().unwrap()).unwrap();
+        let resized = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(resized.dimensions(), (100, 50));
+    }
+}
@@ -0,0 +1,133 @@
+//! Watchdog - Supervises the gateway server task
+//!
+//! When the server exits on its own, whether with an error such as the port being taken
+//! or by panicking, the status is marked stopped with the reason as `last_error`. If
+//! auto-restart is configured the server is started again after a growing backoff, up
+//! to a limit; a run that stayed up for a while resets the count.
+
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use tokio::sync::RwLock;
+
+use super::server::run_gateway_server;
+use super::{scrub, tray, GatewaySettings, GatewayStatus};
+
+/// Restarts allowed in a row when unset
+const DEFAULT_MAX_RESTARTS: u32 = 5;
+
+/// Backoff before the first restart when unset
+const DEFAULT_INITIAL_BACKOFF_SECONDS: u32 = 1;
+
+/// Upper bound of the backoff between restarts
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How long a run must last for the restart count to start over
+const STABLE_RUN: Duration = Duration::from_secs(300);
+
+/// Automatic restarts of a gateway server that exited unexpectedly
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WatchdogConfig {
+    /// Whether the server is restarted
+    pub auto_restart: bool,
+    /// Restarts in a row before giving up (default 5)
+    #[serde(default)]
+    pub max_restarts: Option<u32>,
+    /// Wait before the first restart, doubled for each further one (default 1 second)
+    #[serde(default)]
+    pub initial_backoff_seconds: Option<u32>,
+}
+
+/// Wait before restart number `attempt` (starting at 0)
+fn backoff(initial_seconds: u32, attempt: u32) -> Duration {
+    Duration::from_secs(initial_seconds as u64)
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF)
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    format!("Gateway server panicked: {}", message)
+}
+
+/// Run the gateway server, recording its exits and restarting it as configured.
+/// `primary` is set for the main listener, which resumes unfinished batches.
+pub(super) async fn supervise(
+    app: AppHandle,
+    port: u16,
+    settings: Arc<RwLock<GatewaySettings>>,
+    status: Arc<RwLock<GatewayStatus>>,
+    primary: bool,
+) {
+    let mut restarts = 0;
+    loop {
+        let started = Instant::now();
+        let server =
+            run_gateway_server(app.clone(), port, settings.clone(), status.clone(), primary);
+        let error = match AssertUnwindSafe(server).catch_unwind().await {
+            Ok(Ok(())) => "Gateway server stopped unexpectedly".to_string(),
+            Ok(Err(e)) => scrub::scrub(&e.to_string()),
+            Err(panic) => panic_message(panic.as_ref()),
+        };
+        log::error!("Gateway server error: {}", error);
+        {
+            let mut status = status.write().await;
+            status.running = false;
+            status.last_error = Some(error);
+        }
+        tray::refresh(&app).await;
+
+        let Some(config) = settings.read().await.watchdog.clone() else {
+            return;
+        };
+        if !config.auto_restart {
+            return;
+        }
+        if started.elapsed() >= STABLE_RUN {
+            restarts = 0;
+        }
+        let max_restarts = config.max_restarts.unwrap_or(DEFAULT_MAX_RESTARTS);
+        if restarts >= max_restarts {
+            log::error!("Gateway server failed {} times, giving up", restarts + 1);
+            return;
+        }
+        let wait = backoff(
+            config
+                .initial_backoff_seconds
+                .unwrap_or(DEFAULT_INITIAL_BACKOFF_SECONDS),
+            restarts,
+        );
+        restarts += 1;
+        log::info!(
+            "Restarting gateway server in {:?} (attempt {} of {})",
+            wait,
+            restarts,
+            max_restarts
+        );
+        tokio::time::sleep(wait).await;
+
+        status.write().await.running = true;
+        tray::refresh(&app).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1, 0), Duration::from_secs(1));
+        assert_eq!(backoff(1, 3), Duration::from_secs(8));
+        assert_eq!(backoff(5, 1), Duration::from_secs(10));
+        assert_eq!(backoff(1, 10), MAX_BACKOFF);
+        assert_eq!(backoff(1, u32::MAX), MAX_BACKOFF);
+    }
+}
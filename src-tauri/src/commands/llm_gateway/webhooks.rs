@@ -0,0 +1,169 @@
+//! Webhooks - Gateway alerts delivered to self-hosted endpoints
+//!
+//! Each configured webhook receives the alerts its event filter selects as a JSON POST:
+//! the [`GatewayAlert`] fields tagged with their `event`, plus a `timestamp`. When the
+//! webhook has a secret the body is signed with HMAC-SHA256, sent as
+//! `X-Doggy-Signature: sha256=<hex>`, so receivers can reject forged calls. Failed
+//! deliveries are retried a few times in the background and then dropped.
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::time::Duration;
+
+use super::client;
+use super::notify::{AlertEvent, GatewayAlert};
+use super::scrub;
+
+/// Header carrying the body's signature
+pub const SIGNATURE_HEADER: &str = "x-doggy-signature";
+/// Header naming the event delivered
+pub const EVENT_HEADER: &str = "x-doggy-event";
+
+/// Attempts made per delivery
+const MAX_ATTEMPTS: u32 = 3;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn default_true() -> bool {
+    true
+}
+
+/// Endpoint notified of gateway alerts
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key signing the body; may be a `${VAR}` reference
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Events delivered; empty delivers all of them
+    #[serde(default)]
+    pub events: Vec<AlertEvent>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+// Secrets stay out of debug output
+impl std::fmt::Debug for WebhookConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookConfig")
+            .field("url", &scrub::scrub(&self.url))
+            .field("secret", &self.secret.as_ref().map(|_| scrub::SCRUBBED))
+            .field("events", &self.events)
+            .field("enabled", &self.enabled)
+            .finish()
+    }
+}
+
+impl WebhookConfig {
+    fn wants(&self, event: AlertEvent) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&event))
+    }
+
+    /// Check the URL is an http(s) URL
+    pub fn validate(&self) -> Result<(), String> {
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+            _ => Err(format!("Invalid webhook URL '{}'", scrub::scrub(&self.url))),
+        }
+    }
+}
+
+/// Value of [`SIGNATURE_HEADER`] for a body
+fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// JSON body delivered for an alert
+fn payload(alert: &GatewayAlert) -> Vec<u8> {
+    let mut body = serde_json::to_value(alert).unwrap_or(Value::Null);
+    if let Some(obj) = body.as_object_mut() {
+        obj.insert(
+            "timestamp".to_string(),
+            Value::String(chrono::Utc::now().to_rfc3339()),
+        );
+    }
+    body.to_string().into_bytes()
+}
+
+async fn post(webhook: &WebhookConfig, event: AlertEvent, body: Vec<u8>) {
+    for attempt in 1..=MAX_ATTEMPTS {
+        let mut request = client::shared_client()
+            .post(&webhook.url)
+            .timeout(DELIVERY_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.as_str());
+        if let Some(secret) = webhook.secret.as_deref().filter(|s| !s.is_empty()) {
+            request = request.header(SIGNATURE_HEADER, signature(secret, &body));
+        }
+        let error = match request.body(body.clone()).send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => response.status().to_string(),
+            Err(e) => scrub::scrub(&e.to_string()),
+        };
+        if attempt == MAX_ATTEMPTS {
+            log::warn!(
+                "Failed to deliver {} webhook to {}: {}",
+                event.as_str(),
+                scrub::scrub(&webhook.url),
+                error
+            );
+            return;
+        }
+        tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+    }
+}
+
+/// Deliver an alert to the webhooks that want it, in the background
+pub fn deliver(webhooks: &[WebhookConfig], alert: &GatewayAlert) {
+    let event = alert.event();
+    let mut body = None;
+    for webhook in webhooks.iter().filter(|w| w.wants(event)) {
+        let body = body.get_or_insert_with(|| payload(alert)).clone();
+        let webhook = webhook.clone();
+        tokio::spawn(async move { post(&webhook, event, body).await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_event_filter() {
+        let webhook: WebhookConfig = serde_json::from_str(
+            r#"{"url": "https://alerts.local/hook", "events": ["provider_down"]}"#,
+        )
+        .unwrap();
+        assert!(webhook.validate().is_ok());
+        assert!(webhook.wants(AlertEvent::ProviderDown));
+        assert!(!webhook.wants(AlertEvent::RequestError));
+
+        let body: Value = serde_json::from_slice(&payload(&GatewayAlert::ProviderDown {
+            provider: "OpenAI".to_string(),
+            error: "502 Bad Gateway".to_string(),
+        }))
+        .unwrap();
+        assert_eq!(body["event"], "provider_down");
+        assert_eq!(body["provider"], "OpenAI");
+        assert!(body["timestamp"].is_string());
+    }
+}
@@ -0,0 +1,91 @@
+//! Zhipu Authentication - JWTs derived from `id.secret` API keys
+//!
+//! Zhipu's console hands out keys of the form `{id}.{secret}`. Its API accepts a
+//! short-lived HS256 JWT signed with the secret in place of the raw key, which some
+//! endpoints and account types require. Tokens are cached per key and renewed shortly
+//! before they expire, so users can paste the key as-is.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Lifetime of a generated token
+const TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Tokens closer than this to expiry are renewed
+const RENEW_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Signed token for key `id` valid from `now_ms` for `ttl`
+fn sign(id: &str, secret: &str, now_ms: u64, ttl: Duration) -> String {
+    let header = json!({"alg": "HS256", "sign_type": "SIGN"});
+    let claims = json!({
+        "api_key": id,
+        "exp": now_ms + ttl.as_millis() as u64,
+        "timestamp": now_ms,
+    });
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(signing_input.as_bytes());
+    let signature = mac.finalize().into_bytes();
+    format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature))
+}
+
+/// Bearer token for a Zhipu API key: a cached JWT for `id.secret` keys, else `None`
+/// so the key is sent as-is
+pub fn token(api_key: &str) -> Option<String> {
+    static TOKENS: OnceLock<Mutex<HashMap<String, (String, SystemTime)>>> = OnceLock::new();
+
+    let (id, secret) = api_key.split_once('.')?;
+    if id.is_empty() || secret.is_empty() || secret.contains('.') {
+        return None;
+    }
+
+    let now = SystemTime::now();
+    let mut tokens = TOKENS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some((token, expires)) = tokens.get(api_key) {
+        if now + RENEW_MARGIN < *expires {
+            return Some(token.clone());
+        }
+    }
+
+    let now_ms = now.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+    let token = sign(id, secret, now_ms, TOKEN_TTL);
+    tokens.insert(api_key.to_string(), (token.clone(), now + TOKEN_TTL));
+    Some(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token() {
+        let jwt = sign("abc", "s3cret", 1_700_000_000_000, TOKEN_TTL);
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        let claims: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(claims["api_key"], "abc");
+        assert_eq!(claims["exp"], 1_700_003_600_000u64);
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+        mac.update(format!("{}.{}", parts[0], parts[1]).as_bytes());
+        mac.verify_slice(&URL_SAFE_NO_PAD.decode(parts[2]).unwrap())
+            .unwrap();
+
+        assert_eq!(token("abc.s3cret"), token("abc.s3cret"));
+        assert_eq!(token("sk-plain-key"), None);
+    }
+}
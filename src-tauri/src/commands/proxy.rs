@@ -28,7 +28,7 @@ impl Default for ProxySettings {
 /// Get proxy settings from the database
 #[tauri::command]
 pub async fn get_proxy_settings(db: State<'_, AgentDb>) -> Result<ProxySettings, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     let mut settings = ProxySettings::default();
 
@@ -67,7 +67,7 @@ pub async fn save_proxy_settings(
     db: State<'_, AgentDb>,
     settings: ProxySettings,
 ) -> Result<(), String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let conn = db.0.get().map_err(|e| e.to_string())?;
 
     // Save each setting
     let values = vec![
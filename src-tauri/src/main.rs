@@ -37,9 +37,17 @@ use commands::mcp::{
 };
 
 use commands::llm_gateway::{
-    get_default_llm_providers, get_gateway_env_vars, get_llm_gateway_settings,
-    get_llm_gateway_status, save_llm_gateway_settings, start_llm_gateway, stop_llm_gateway,
-    test_llm_provider, LLMGatewayState,
+    add_custom_llm_provider, cancel_gateway_batch, create_gateway_batch, delete_gateway_profile, delete_prompt_template,
+    disable_settings_encryption, enable_settings_encryption, export_gateway_settings, export_gateway_usage,
+    forecast_gateway_spend, generate_usage_report,
+    get_ab_test_results, get_default_llm_providers, get_gateway_batch_results, get_gateway_env_vars, get_gateway_snapshot,
+    get_llm_gateway_settings, get_llm_gateway_status, list_audit_log, backup_gateway_db, restore_gateway_db, list_gateway_backups, list_gateway_instances, start_gateway_instance, stop_gateway_instance, test_routing_rules, start_copilot_login, poll_copilot_login, get_gateway_latency_stats, list_ollama_models, pull_ollama_model, delete_ollama_model, sync_ollama_models, sync_model_registry, run_gateway_selftest,
+    get_settings_encryption_status,
+    import_claude_code_router_config, import_gateway_settings, import_litellm_config,
+    list_gateway_batches, list_gateway_profiles, list_prompt_templates, list_usage_reports, probe_custom_llm_provider,
+    refresh_provider_credits, refresh_provider_models, save_gateway_profile, save_llm_gateway_settings, save_prompt_template,
+    set_llm_provider_enabled, start_llm_gateway, stop_llm_gateway, switch_gateway_profile,
+    sync_model_pricing, test_llm_provider, unlock_gateway_settings, LLMGatewayState,
 };
 use commands::proxy::{apply_proxy_settings, get_proxy_settings, save_proxy_settings};
 use commands::storage::{
@@ -50,7 +58,6 @@ use commands::usage::{
     get_session_stats, get_usage_by_date_range, get_usage_details, get_usage_stats,
 };
 use process::ProcessRegistryState;
-use std::sync::Mutex;
 use tauri::Manager;
 
 #[cfg(target_os = "macos")]
@@ -63,14 +70,14 @@ fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
         .setup(|app| {
             // Initialize agents database
-            let conn = init_database(&app.handle()).expect("Failed to initialize agents database");
+            let pool = init_database(&app.handle()).expect("Failed to initialize agents database");
 
             // Load and apply proxy settings from the database
             {
-                let db = AgentDb(Mutex::new(conn));
-                let proxy_settings = match db.0.lock() {
+                let proxy_settings = match pool.get() {
                     Ok(conn) => {
                         // Directly query proxy settings from the database
                         let mut settings = commands::proxy::ProxySettings::default();
@@ -112,7 +119,7 @@ fn main() {
                         settings
                     }
                     Err(e) => {
-                        log::warn!("Failed to lock database for proxy settings: {}", e);
+                        log::warn!("Failed to open database for proxy settings: {}", e);
                         commands::proxy::ProxySettings::default()
                     }
                 };
@@ -121,9 +128,14 @@ fn main() {
                 apply_proxy_settings(&proxy_settings);
             }
 
-            // Re-open the connection for the app to manage
-            let conn = init_database(&app.handle()).expect("Failed to initialize agents database");
-            app.manage(AgentDb(Mutex::new(conn)));
+            let migrated = pool
+                .get()
+                .map_err(|e| e.to_string())
+                .and_then(|conn| commands::llm_gateway::migrations::run(&conn));
+            if let Err(e) = migrated {
+                log::error!("Failed to migrate the gateway tables: {}", e);
+            }
+            app.manage(AgentDb(pool));
 
             // Initialize checkpoint state
             let checkpoint_state = CheckpointState::new();
@@ -155,6 +167,29 @@ fn main() {
             // Initialize LLM Gateway state
             app.manage(LLMGatewayState::default());
 
+            // Gateway controls in the system tray
+            if let Err(e) = commands::llm_gateway::tray::init(app.handle()) {
+                log::warn!("Failed to create gateway tray icon: {}", e);
+            }
+
+            // Start the gateway if it is set to start on launch
+            tauri::async_runtime::spawn(commands::llm_gateway::auto_start(app.handle().clone()));
+
+            // Produce scheduled gateway usage reports
+            tauri::async_runtime::spawn(commands::llm_gateway::reports::schedule(
+                app.handle().clone(),
+            ));
+
+            // Keep the model registry fresh when set to
+            tauri::async_runtime::spawn(commands::llm_gateway::registry::schedule(
+                app.handle().clone(),
+            ));
+
+            // Take scheduled database backups
+            tauri::async_runtime::spawn(commands::llm_gateway::backup::schedule(
+                app.handle().clone(),
+            ));
+
             // Apply window vibrancy with rounded corners on macOS
             #[cfg(target_os = "macos")]
             {
@@ -301,11 +336,59 @@ fn main() {
             get_llm_gateway_settings,
             save_llm_gateway_settings,
             get_llm_gateway_status,
+            list_audit_log,
+            backup_gateway_db,
+            restore_gateway_db,
+            list_gateway_backups,
+            list_gateway_instances,
+            start_gateway_instance,
+            stop_gateway_instance,
+            test_routing_rules,
+            start_copilot_login,
+            poll_copilot_login,
+            get_gateway_latency_stats,
+            list_ollama_models,
+            pull_ollama_model,
+            delete_ollama_model,
+            sync_ollama_models,
+            sync_model_registry,
+            run_gateway_selftest,
             start_llm_gateway,
             stop_llm_gateway,
             test_llm_provider,
             get_default_llm_providers,
             get_gateway_env_vars,
+            probe_custom_llm_provider,
+            add_custom_llm_provider,
+            refresh_provider_credits,
+            refresh_provider_models,
+            sync_model_pricing,
+            get_ab_test_results,
+            create_gateway_batch,
+            list_gateway_batches,
+            get_gateway_batch_results,
+            cancel_gateway_batch,
+            generate_usage_report,
+            list_usage_reports,
+            export_gateway_usage,
+            forecast_gateway_spend,
+            list_prompt_templates,
+            save_prompt_template,
+            delete_prompt_template,
+            get_settings_encryption_status,
+            enable_settings_encryption,
+            unlock_gateway_settings,
+            disable_settings_encryption,
+            import_litellm_config,
+            import_claude_code_router_config,
+            export_gateway_settings,
+            import_gateway_settings,
+            list_gateway_profiles,
+            save_gateway_profile,
+            delete_gateway_profile,
+            switch_gateway_profile,
+            get_gateway_snapshot,
+            set_llm_provider_enabled,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
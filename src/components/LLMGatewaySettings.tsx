@@ -436,6 +436,14 @@ export const LLMGatewaySettings: React.FC = () => {
         </div>
       </div>
       
+      {/* Why the gateway stopped or failed to start, e.g. on launch */}
+      {!status?.running && status?.last_error && (
+        <div className="flex items-center gap-2 p-3 bg-red-500/10 border border-red-500/30 rounded-lg text-red-400 text-sm">
+          <AlertCircle size={16} />
+          Gateway stopped: {status.last_error}
+        </div>
+      )}
+      
       {/* Error message */}
       {error && (
         <div className="flex items-center gap-2 p-3 bg-red-500/10 border border-red-500/30 rounded-lg text-red-400 text-sm">
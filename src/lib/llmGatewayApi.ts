@@ -4,6 +4,7 @@
  * Provides frontend interface for managing multi-model LLM proxy settings
  */
 
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { apiCall } from './apiAdapter';
 
 // ============================================================================
@@ -22,6 +23,10 @@ export type LLMProvider =
   | 'groq'
   | 'ollama'
   | 'openrouter'
+  | 'huggingface'
+  | 'replicate'
+  | 'copilot'
+  | 'mock'
   | 'custom';
 
 /** Model configuration */
@@ -30,7 +35,7 @@ export interface ModelConfig {
   id: string;
   /** Display name */
   name: string;
-  /** Model capabilities (coding, reasoning, creative, fast) */
+  /** Model capabilities (coding, reasoning, creative, fast, vision, completions) */
   capabilities: string[];
   /** Input price per 1M tokens (USD) */
   input_price: number;
@@ -40,6 +45,153 @@ export interface ModelConfig {
   max_tokens: number;
   /** Whether this is the default model for this provider */
   is_default: boolean;
+  /** Prices are placeholders because the model was discovered, not configured */
+  pricing_unknown?: boolean;
+  /** Requests-per-minute cap enforced by the provider */
+  rpm_limit?: number;
+  /** Tokens-per-minute cap enforced by the provider */
+  tpm_limit?: number;
+  /** Prompt cache read price per 1M tokens (USD); the provider's usual discount if unset */
+  cache_read_price?: number;
+  /** Prompt cache write price per 1M tokens (USD); the provider's usual surcharge if unset */
+  cache_write_price?: number;
+  /** Context overflow handling for this model, overriding the gateway-wide setting */
+  context_overflow?: ContextOverflow;
+  /** Price per generated image (USD) for image models billed per image */
+  image_price?: number;
+  /** Price per minute of audio (USD) for transcription models billed by duration */
+  audio_price?: number;
+  /** Price per 1M input characters (USD) for speech models billed by character */
+  speech_price?: number;
+  /** Prices replacing `input_price` and `output_price` during parts of the day */
+  price_windows?: PriceWindow[];
+  /** Prices replacing the token prices for requests with long prompts */
+  price_tiers?: PriceTier[];
+  /** Flat charge per request (USD), such as a per-search fee */
+  request_fee?: number;
+  /** Percentage added to the cost of every request, such as OpenRouter's platform fee */
+  fee_percent?: number;
+  /** ISO 4217 code of the currency the prices are in; USD when unset */
+  currency?: string;
+}
+
+/** Prices a model charges during part of the provider's day, per 1M tokens (USD) */
+export interface PriceWindow {
+  /** Start in the provider's local time (HH:MM) */
+  start: string;
+  /** End, exclusive; wraps past midnight when before `start` */
+  end: string;
+  input_price: number;
+  output_price: number;
+}
+
+/** Prices for a whole request once its prompt (cached tokens included) exceeds a size, per 1M tokens (USD) */
+export interface PriceTier {
+  above_input_tokens: number;
+  input_price: number;
+  output_price: number;
+  /** The provider's usual multiple of `input_price` if unset */
+  cache_read_price?: number;
+  cache_write_price?: number;
+}
+
+/** Scheduled downtime during which a provider isn't routed to */
+export interface MaintenanceWindow {
+  /** Start in local time (HH:MM) */
+  start: string;
+  /** End, exclusive; wraps past midnight when before `start` */
+  end: string;
+  /** Days the window starts on ('Mon' … 'Sun'); empty or unset means every day */
+  days?: string[];
+}
+
+/** Thresholds past which a local provider's machine counts as saturated for large prompts */
+export interface SaturationGuard {
+  /** Prompts at least this large (estimated tokens) avoid a saturated machine (default 32000) */
+  min_prompt_tokens?: number;
+  /** Highest acceptable load average per core */
+  max_cpu_load?: number;
+  /** Highest acceptable GPU utilization, 0-1 */
+  max_gpu_utilization?: number;
+  /** Highest acceptable share of VRAM in use, 0-1 */
+  max_vram_used?: number;
+}
+
+/** Scripted answer of a mock provider */
+export interface MockResponse {
+  /** Regular expression matched against the last user message */
+  pattern: string;
+  /** Reply content */
+  content?: string;
+  /** Fail with this status instead of replying */
+  status?: number;
+}
+
+/** Behavior of a mock provider */
+export interface MockConfig {
+  /** Delay before every answer */
+  latency_ms?: number;
+  /** Share of requests failed with `failure_status`, 0-1 */
+  failure_rate?: number;
+  /** Status of simulated failures (default 503) */
+  failure_status?: number;
+  /** Scripted answers, tried in order; unmatched requests get an echo */
+  responses?: MockResponse[];
+}
+
+/** Model loading behavior of an Ollama provider */
+export interface OllamaConfig {
+  /** Load the default model when the gateway starts */
+  warm_up?: boolean;
+  /** How long models stay loaded after a request ("30m", "2h", seconds, "-1" for always) */
+  keep_alive?: string;
+}
+
+/** Latency and error objectives a provider is held to over a rolling window */
+export interface SloConfig {
+  /** Highest acceptable 95th percentile latency to the first response byte */
+  max_p95_latency_ms?: number;
+  /** Highest acceptable share of failed requests (connection failures and 5xx), 0-1 */
+  max_error_rate?: number;
+  /** Length of the rolling window (default 10) */
+  window_minutes?: number;
+  /** Requests the window needs before the provider is judged (default 20) */
+  min_requests?: number;
+  /** Added to the provider's priority while it is demoted (default 100) */
+  priority_penalty?: number;
+}
+
+/** Handling of prompts that exceed the routed model's context window */
+export type ContextOverflow = 'truncate' | 'upgrade' | 'reject';
+
+/** Moves a value between dotted JSON paths */
+export interface FieldMapping {
+  /** Source path; numeric segments index into arrays */
+  from: string;
+  /** Destination path */
+  to: string;
+}
+
+/** Declarative adapter describing an upstream provider API */
+export interface AdapterSpec {
+  /** Header carrying the credential (empty disables header auth) */
+  auth_header: string;
+  /** Header value template, `{api_key}` is replaced with the provider key */
+  auth_template: string;
+  /** Credential derived from the key; `zhipu_jwt` signs a JWT from `id.secret` keys */
+  auth_scheme?: 'key' | 'zhipu_jwt';
+  /** Chat endpoint path appended to the base URL, `{model}` is replaced */
+  chat_path: string;
+  /** Model listing path appended to the base URL */
+  models_path: string;
+  /** Extra query parameters; values may use `{api_key}` and `{model}` */
+  query_params: Record<string, string>;
+  /** Static fields merged into every request body */
+  request_defaults: Record<string, unknown>;
+  /** Field moves applied to the outgoing request body */
+  request_mappings: FieldMapping[];
+  /** Field copies applied to non-streaming responses */
+  response_mappings: FieldMapping[];
 }
 
 /** Provider configuration */
@@ -48,9 +200,9 @@ export interface ProviderConfig {
   provider: LLMProvider;
   /** Display name */
   name: string;
-  /** API base URL */
+  /** API base URL; may reference environment variables as `${VAR}` */
   base_url: string;
-  /** API key (stored securely) */
+  /** API key (stored securely), or an environment reference like `${OPENAI_API_KEY}` */
   api_key?: string;
   /** Whether this provider is enabled */
   enabled: boolean;
@@ -60,8 +212,112 @@ export interface ProviderConfig {
   models: ModelConfig[];
   /** Custom headers */
   headers: Record<string, string>;
+  /** Declarative adapter overriding the built-in request handling */
+  adapter?: AdapterSpec;
+  /** Additional API keys rotated together with `api_key` */
+  api_keys?: string[];
+  /** How requests are spread across the keys */
+  key_rotation?: KeyRotation;
+  /** Largest inline image the provider accepts, in bytes */
+  max_image_bytes?: number;
+  /** Largest image width/height the provider accepts */
+  max_image_dimension?: number;
+  /** Whether PDF documents are passed through instead of extracted locally */
+  supports_file_input?: boolean;
+  /** How structured output requests are served; inferred from the provider when unset */
+  structured_output?: StructuredOutputMode;
+  /** Connection timeout in seconds */
+  connect_timeout_seconds?: number;
+  /** Total request timeout in seconds, overriding the gateway's */
+  timeout_seconds?: number;
+  /** Retries of failed requests; unset sends each request once */
+  retry?: RetryPolicy;
+  /** Outbound proxy for this provider, overriding the gateway's `proxy` */
+  proxy?: ProxyConfig;
+  /** TLS options for this provider, replacing the gateway's `tls` */
+  tls?: TlsConfig;
+  /** Connection pool tuning for this provider, replacing the gateway's `pool` */
+  pool?: PoolConfig;
+  /** Host names resolved to fixed addresses instead of through DNS, as comma-separated IPs with optional ports */
+  dns_overrides?: Record<string, string>;
+  /** Alternate base URLs tried in order when `base_url` is unreachable */
+  mirrors?: string[];
+  /** Offset from UTC of the provider's local time, in minutes, which price windows follow */
+  utc_offset_minutes?: number;
+  /** Monthly spend ceiling in USD; once reached the provider isn't routed to until next month */
+  monthly_spend_cap_usd?: number;
+  /** Use the provider's native API instead of its OpenAI-compatible endpoint (Qwen DashScope) */
+  native_api?: boolean;
+  /** Forward clients' Claude subscription OAuth tokens instead of `api_key` (Anthropic only) */
+  oauth_passthrough?: boolean;
+  /** `OpenAI-Organization` sent with every request; may reference `${VAR}` */
+  openai_organization?: string;
+  /** `OpenAI-Project` sent with every request; may reference `${VAR}` */
+  openai_project?: string;
+  /** Scheduled downtime during which the provider isn't routed to */
+  maintenance_windows?: MaintenanceWindow[];
+  /** Latency and error objectives; breaching them lowers the provider's priority */
+  slo?: SloConfig;
+  /** Model loading on the local server (Ollama providers only) */
+  ollama?: OllamaConfig;
+  /** Skip this provider for large prompts while its machine is saturated (local providers only) */
+  saturation_guard?: SaturationGuard;
+  /** Canned replies, latency and failures (mock providers only) */
+  mock?: MockConfig;
+}
+
+/** Outbound proxy for provider requests */
+export interface ProxyConfig {
+  /** `http://`, `https://`, `socks5://` or `socks5h://` URL; may reference `${VAR}` */
+  url: string;
+  username?: string;
+  password?: string;
+  /** Comma-separated hosts reached directly, such as `localhost,127.0.0.1` */
+  no_proxy?: string;
+}
+
+/** TLS options for provider connections */
+export interface TlsConfig {
+  /** PEM file with CA certificates trusted in addition to the system's */
+  ca_cert_path?: string;
+  /** PEM file with the client certificate chain for mutual TLS */
+  client_cert_path?: string;
+  /** PEM file with the client certificate's PKCS#8 private key */
+  client_key_path?: string;
+  /** Skip certificate and host name verification; only for trusted networks */
+  accept_invalid_certs?: boolean;
+}
+
+/** Connection pool and keep-alive tuning */
+export interface PoolConfig {
+  /** Idle connections kept open per host (default unlimited) */
+  max_idle_per_host?: number;
+  /** Close connections idle for longer than this (default 90s) */
+  idle_timeout_seconds?: number;
+  /** Interval of TCP keep-alive probes (default 60s) */
+  tcp_keepalive_seconds?: number;
+  /** Interval of HTTP/2 pings keeping idle connections alive; unset sends none */
+  http2_keep_alive_seconds?: number;
 }
 
+/** Structured output support of a provider */
+export type StructuredOutputMode = 'json_schema' | 'json_object' | 'prompt';
+
+/** Retries of failed requests to a provider */
+export interface RetryPolicy {
+  /** Retries after the first attempt */
+  max_retries: number;
+  /** Upstream statuses worth retrying (default 429, 500, 502, 503, 504) */
+  retry_on_status?: number[];
+  /** How the wait between retries grows (default exponential) */
+  backoff?: 'fixed' | 'exponential';
+  /** Wait before the first retry (default 500ms) */
+  initial_backoff_ms?: number;
+}
+
+/** Key selection strategy for providers with several API keys */
+export type KeyRotation = 'round_robin' | 'least_recently_throttled';
+
 /** LLM Gateway settings */
 export interface GatewaySettings {
   /** Whether the gateway is enabled */
@@ -82,6 +338,632 @@ export interface GatewaySettings {
   timeout_seconds: number;
   /** Provider configurations */
   providers: ProviderConfig[];
+  /** Incoming model name rewrites; keys ending in `*` match by prefix */
+  model_aliases?: Record<string, ModelAlias>;
+  /** Generic text-to-speech voice names mapped to a provider's model and voice */
+  voice_aliases?: Record<string, VoiceAlias>;
+  /** Pricing manifest URL; unset uses the default LiteLLM manifest */
+  pricing_manifest_url?: string;
+  /** Local price overrides keyed by model ID or `provider/model` */
+  pricing_overrides?: Record<string, ModelPricing>;
+  /** Maximum concurrent upstream requests; unset = unlimited */
+  max_concurrent_requests?: number;
+  /** How long a request may wait for a concurrency slot; unset = 60 seconds */
+  queue_timeout_seconds?: number;
+  /** A/B traffic splits, checked before aliases */
+  ab_tests?: AbTest[];
+  /** Shadow traffic mirroring */
+  shadow?: ShadowConfig;
+  /** Speculative parallel dispatch for latency-critical models */
+  speculative?: SpeculativeConfig;
+  /** Whether reasoning model output is returned as thinking blocks or stripped */
+  reasoning_output?: ReasoningOutput;
+  /** What to do with prompts exceeding the routed model's context window; unset forwards them unchecked */
+  context_overflow?: ContextOverflow;
+  /** Summarize turns dropped by `truncate` context fitting with a cheap model */
+  summarization?: SummarizationConfig;
+  /** System prompt rewrites applied to requests routed to matching models */
+  system_prompt_rules?: SystemPromptRule[];
+  /** Request rewrites applied to requests routed to matching models */
+  transform_rules?: TransformRule[];
+  /** Masking of personal data and credentials in outgoing prompts */
+  redaction?: RedactionConfig;
+  /** Keyword and topic blocklists checked before requests leave the machine */
+  guardrails?: GuardrailRule[];
+  /** Detection and automatic restart of a server that exited unexpectedly */
+  watchdog?: WatchdogConfig;
+  /** Screening of outgoing prompts with a moderation model */
+  moderation?: ModerationConfig;
+  /** Outbound proxy for provider requests, applied when the gateway starts */
+  proxy?: ProxyConfig;
+  /** TLS options for provider connections, applied when the gateway starts */
+  tls?: TlsConfig;
+  /** Connection pool tuning for provider connections, applied when the gateway starts */
+  pool?: PoolConfig;
+  /** Daily and weekly usage reports */
+  reports?: ReportConfig;
+  /** Monthly spend budget in USD, for notifications when reached or forecast to be */
+  monthly_budget_usd?: number;
+  /** Desktop notifications about spend and provider health */
+  notifications?: NotificationConfig;
+  /** Endpoints notified of provider outages, budget alerts and request errors */
+  webhooks?: WebhookConfig[];
+  /** Automatic backups of the app database */
+  backups?: BackupConfig;
+  /** Additional listeners, each with its own port and routing */
+  instances?: InstanceConfig[];
+  /** Rules deciding routes before the requested model is resolved, in evaluation order */
+  routing_rules?: RoutingRule[];
+  /** Repair of responses that fail the JSON schema their request declared */
+  json_repair?: JsonRepairConfig;
+  /** Exchange rates for models priced in other currencies, and the currency costs are shown in */
+  currency?: CurrencyConfig;
+  /** Refresh the model registry from the pricing manifest once a day */
+  model_registry_sync?: boolean;
+  /** Record upstream responses to disk, or replay recorded ones instead of calling providers; applied when the gateway starts */
+  cassette?: CassetteConfig;
+  /** Inject timeouts, 429s and malformed chunks into provider requests, to try out retries and failover; applied when the gateway starts */
+  chaos?: ChaosConfig;
+}
+
+/** Faults injected into provider requests by chaos mode */
+export interface ChaosConfig {
+  /** Provider names or types to inject faults into; every provider when empty */
+  providers?: string[];
+  /** Share of attempts that time out, 0-1 */
+  timeout_rate?: number;
+  /** Share of attempts answered with a 429, 0-1 */
+  rate_limit_rate?: number;
+  /** Share of streamed responses given a malformed chunk, 0-1 */
+  malformed_chunk_rate?: number;
+}
+
+/** Where and how upstream responses are recorded */
+export interface CassetteConfig {
+  /** `record` sends requests and saves their responses; `replay` answers from recordings only (default record) */
+  mode?: 'record' | 'replay';
+  /** Directory holding the recordings, one file per request */
+  dir: string;
+}
+
+/** Exchange rates and the currency costs are shown in */
+export interface CurrencyConfig {
+  /** ISO 4217 code costs are shown in; USD when unset */
+  display?: string;
+  /** Units of each currency one USD buys, by ISO 4217 code */
+  rates?: Record<string, number>;
+}
+
+/** Currency a report is rendered in, with its units per USD */
+export interface DisplayCurrency {
+  code: string;
+  per_usd: number;
+}
+
+/** Conditions of a routing rule; unset conditions match every request */
+export interface RuleConditions {
+  /** Requested model to match; a trailing `*` matches by prefix */
+  match_model?: string;
+  /** Estimated prompt tokens, inclusive */
+  min_prompt_tokens?: number;
+  max_prompt_tokens?: number;
+  /** Whether the request must (or must not) offer tools */
+  has_tools?: boolean;
+  /** Whether the request must (or must not) carry images */
+  has_images?: boolean;
+  /** Local time of day (HH:MM); wraps past midnight when `start` is after `end` */
+  time_window?: { start: string; end: string };
+  /** API keys clients authenticate to the gateway with; any key when empty */
+  source_keys?: string[];
+}
+
+/** Route and parameters a matching rule selects */
+export interface RuleAction {
+  /** Provider to route to; the model's provider when unset */
+  provider?: LLMProvider;
+  /** Model to route to; the provider's default model when unset */
+  model?: string;
+  /** Top-level request parameters to set; `null` removes the parameter */
+  parameters?: Record<string, unknown>;
+}
+
+/** Routing rule evaluated before the requested model is resolved */
+export interface RoutingRule {
+  name: string;
+  enabled: boolean;
+  conditions?: RuleConditions;
+  action?: RuleAction;
+}
+
+/** Which rule a sample request matches, and why the others don't */
+export interface RuleExplanation {
+  /** Name of the rule that applies, if any */
+  matched?: string;
+  /** Provider and model the request would be routed to */
+  provider?: string;
+  model?: string;
+  /** Rules in evaluation order, up to the one that matched */
+  evaluations: { name: string; enabled: boolean; mismatches: string[] }[];
+}
+
+/** An additional gateway listener */
+export interface InstanceConfig {
+  /** Unique name of the instance */
+  name: string;
+  port: number;
+  /** Whether the instance starts with the gateway (default true) */
+  enabled?: boolean;
+  /** Providers the instance may route to; empty allows every enabled provider */
+  providers?: LLMProvider[];
+  /** Provider of requests no rule routes elsewhere, instead of the main default */
+  default_provider?: LLMProvider;
+  /** Smart routing, instead of the main setting */
+  smart_routing?: boolean;
+}
+
+/** State of a running additional listener */
+export interface InstanceStatus {
+  name: string;
+  port: number;
+  running: boolean;
+  requests_processed: number;
+  last_error?: string;
+}
+
+/** Automatic database backups */
+export interface BackupConfig {
+  /** Take backups automatically */
+  enabled?: boolean;
+  /** Hours between automatic backups (default 24) */
+  interval_hours?: number;
+  /** Automatic backups kept (default 7) */
+  keep?: number;
+  /** Directory backups are written to (default `backups` in the app data directory) */
+  directory?: string;
+}
+
+/** A database backup file */
+export interface BackupInfo {
+  path: string;
+  size_bytes: number;
+  /** When the file was written (RFC 3339) */
+  created_at: string;
+  /** Whether the schedule took it, and may delete it */
+  automatic: boolean;
+}
+
+/** Kind of gateway alert, used to filter webhooks */
+export type AlertEvent =
+  | 'spend_threshold'
+  | 'budget_exceeded'
+  | 'budget_forecast'
+  | 'provider_down'
+  | 'provider_recovered'
+  | 'failover'
+  | 'request_error';
+
+/** Endpoint notified of gateway alerts; bodies are signed in the x-doggy-signature header */
+export interface WebhookConfig {
+  url: string;
+  /** Key signing the body with HMAC-SHA256; may be a ${VAR} reference */
+  secret?: string;
+  /** Events delivered; empty delivers all of them */
+  events?: AlertEvent[];
+  /** Default true */
+  enabled?: boolean;
+}
+
+/** Which gateway events are announced with desktop notifications */
+export interface NotificationConfig {
+  /** Whether notifications are shown at all */
+  enabled: boolean;
+  /** Amounts of today's spend in USD announced when crossed */
+  spend_thresholds_usd?: number[];
+  /** Announce reaching the monthly budget, or a forecast exceeding it (default true) */
+  budget?: boolean;
+  /** Announce providers going down and recovering (default true) */
+  provider_health?: boolean;
+  /** Announce requests moved to another provider by failover (default true) */
+  failover?: boolean;
+}
+
+/** Scheduled usage reports */
+export interface ReportConfig {
+  /** Produce a report for each day */
+  daily?: boolean;
+  /** Produce a report for each week */
+  weekly?: boolean;
+  /** Directory reports are also written to as markdown files */
+  directory?: string;
+  /** Sessions listed among the most expensive (default 10) */
+  top_sessions?: number;
+}
+
+/** Span of time a usage report covers; weeks start on Monday */
+export type ReportPeriod = 'daily' | 'weekly';
+
+/** Projected spend of one provider, or of all of them */
+export interface SpendForecast {
+  /** Provider, or unset for the total */
+  provider?: string;
+  month_to_date_usd: number;
+  /** Average daily spend the projection assumes */
+  daily_average_usd: number;
+  /** Spend expected by the end of the month */
+  projected_usd: number;
+}
+
+/** Projected end-of-month spend */
+export interface ForecastReport {
+  /** Month projected (YYYY-MM) */
+  month: string;
+  days_in_month: number;
+  /** Full days averaged */
+  window_days: number;
+  total: SpendForecast;
+  /** Highest projection first */
+  by_provider: SpendForecast[];
+}
+
+/** One changed settings field in the audit log; secrets are masked */
+export interface AuditEntry {
+  id: number;
+  created_at: string;
+  /** OS user the app ran as */
+  actor: string;
+  /** Command that changed the settings */
+  action: string;
+  /** Changed field, such as `providers[0].enabled` */
+  path: string;
+  /** Value before the change, unset when the field was added */
+  old_value?: unknown;
+  /** Value after the change, unset when the field was removed */
+  new_value?: unknown;
+}
+
+/** How exported usage is grouped; `request` writes one row per request */
+export type UsageGrouping = 'request' | 'day' | 'provider' | 'model' | 'project' | 'session';
+
+/** Requests, tokens and spend of one group in a usage report */
+export interface SpendRow {
+  name: string;
+  requests: number;
+  input_tokens: number;
+  output_tokens: number;
+  cost_usd: number;
+}
+
+/** Spend summary of a period */
+export interface UsageReport {
+  period: ReportPeriod;
+  /** First day covered (YYYY-MM-DD) */
+  start_date: string;
+  /** Last day covered (YYYY-MM-DD) */
+  end_date: string;
+  requests: number;
+  cost_usd: number;
+  by_provider: SpendRow[];
+  by_model: SpendRow[];
+  by_project: SpendRow[];
+  /** Most expensive sessions first */
+  top_sessions: SpendRow[];
+  created_at: string;
+  /** Currency the report is rendered in; USD when unset */
+  currency?: DisplayCurrency;
+}
+
+/** Handling of reasoning model output */
+export type ReasoningOutput = 'thinking' | 'strip';
+
+/** How a system prompt rule combines its text with the client's system prompt */
+export type SystemPromptAction = 'prepend' | 'append' | 'replace';
+
+/** Rewrites the system prompt of requests routed to matching models */
+export interface SystemPromptRule {
+  /** Whether the rule is applied */
+  enabled: boolean;
+  /** Provider the rule applies to; every provider when unset */
+  provider?: LLMProvider;
+  /** Routed model ID to match; a trailing `*` matches by prefix */
+  match_model: string;
+  /** How the text is combined with the client's system prompt */
+  action: SystemPromptAction;
+  /** Instructions to add */
+  text: string;
+}
+
+/** Regex replacement applied to the text of the system prompt and messages */
+export interface TextReplacement {
+  /** Regular expression to find */
+  pattern: string;
+  /** Replacement text; `$1` or `${name}` refer to capture groups */
+  replacement: string;
+}
+
+/** Rewrites requests routed to matching models */
+export interface TransformRule {
+  /** Name shown in logs */
+  name: string;
+  /** Whether the rule is applied */
+  enabled: boolean;
+  /** Provider the rule applies to; every provider when unset */
+  provider?: LLMProvider;
+  /** Routed model ID to match; a trailing `*` matches by prefix */
+  match_model: string;
+  /** Text replacements, applied in order */
+  replacements?: TextReplacement[];
+  /** Headers added to the upstream request */
+  headers?: Record<string, string>;
+  /** Top-level request parameters to set; `null` removes the parameter */
+  parameters?: Record<string, unknown>;
+}
+
+/** Built-in detector of sensitive values */
+export type PiiKind = 'email' | 'phone' | 'api_key';
+
+/** Masks sensitive values in prompts before they are sent upstream */
+export interface RedactionConfig {
+  /** Whether prompts are redacted */
+  enabled: boolean;
+  /** Providers whose requests are redacted; every provider when empty */
+  providers?: LLMProvider[];
+  /** Built-in detectors to run */
+  detectors?: PiiKind[];
+  /** Additional regular expressions whose matches are masked */
+  custom_patterns?: string[];
+  /** Swap placeholders in responses back for the original values; streamed responses are then buffered */
+  restore_responses?: boolean;
+}
+
+/** What happens to a request matching a guardrail */
+export type GuardrailAction = 'reject' | 'local';
+
+/** Blocklist keeping requests on a topic away from providers */
+export interface GuardrailRule {
+  /** Topic name shown in errors and logs */
+  name: string;
+  /** Whether the rule is checked */
+  enabled: boolean;
+  /** Providers the rule guards; every provider when empty */
+  providers?: LLMProvider[];
+  /** Keywords matched case-insensitively as whole words */
+  keywords?: string[];
+  /** Regular expressions matched case-insensitively */
+  patterns?: string[];
+  /** Whether matching requests are rejected or served by a local Ollama model */
+  action?: GuardrailAction;
+  /** Ollama model serving rerouted requests; the provider's default when unset */
+  local_model?: string;
+}
+
+/** Whether flagged requests are blocked or served and logged */
+export type ModerationAction = 'block' | 'flag';
+
+/** Moderation model that screens outgoing prompts; its provider only needs to be configured */
+export interface ModerationConfig {
+  /** Whether prompts are screened */
+  enabled: boolean;
+  /** OpenAI-compatible moderation model such as `omni-moderation-latest` */
+  model: ModelAlias;
+  action?: ModerationAction;
+  /** Categories that count as flagged; every category when empty */
+  categories?: string[];
+}
+
+/** Compact view of the gateway, as shown in the tray menu */
+export interface GatewaySnapshot {
+  running: boolean;
+  /** Port the gateway serves on, or will when started */
+  port: number;
+  requests_processed: number;
+  /** Provider and model requests for unknown models are routed to */
+  current_provider?: string;
+  current_model?: string;
+  /** Cost of today's requests (local time) */
+  today_spend_usd: number;
+  providers: ProviderToggle[];
+}
+
+/** A configured provider and whether it takes requests */
+export interface ProviderToggle {
+  provider: LLMProvider;
+  name: string;
+  enabled: boolean;
+}
+
+/** Stored configuration profiles and the active one */
+export interface GatewayProfiles {
+  /** Profile names, in order */
+  profiles: string[];
+  /** Profile the current settings were last switched to or saved as */
+  active?: string;
+}
+
+/** A batch of requests and its progress */
+export interface BatchJob {
+  id: string;
+  created_at: string;
+  finished_at?: string;
+  status: 'in_progress' | 'cancelling' | 'cancelled' | 'completed';
+  /** Requests sent at once */
+  concurrency: number;
+  total: number;
+  /** Requests that succeeded */
+  completed: number;
+  /** Requests that failed */
+  failed: number;
+}
+
+/** Outcome of one batch request */
+export interface BatchResult {
+  custom_id: string;
+  /** Unset until the request has been sent */
+  status_code?: number;
+  response?: unknown;
+  error?: string;
+}
+
+/** Master-password encryption state of the stored gateway settings */
+export interface SettingsEncryptionStatus {
+  /** Whether the stored settings are encrypted */
+  encrypted: boolean;
+  /** Whether the password has been entered this session */
+  unlocked: boolean;
+}
+
+/** Automatic restarts of a gateway server that exited unexpectedly */
+export interface WatchdogConfig {
+  /** Whether the server is restarted */
+  auto_restart: boolean;
+  /** Restarts in a row before giving up (default 5) */
+  max_restarts?: number;
+  /** Wait before the first restart, doubled for each further one (default 1 second) */
+  initial_backoff_seconds?: number;
+}
+
+/** Cheap model that compresses the turns context truncation would drop into a summary */
+export interface SummarizationConfig {
+  /** Whether dropped turns are summarized instead of discarded */
+  enabled: boolean;
+  /** Model writing the summaries; its provider only needs to be configured */
+  model: ModelAlias;
+  /** Tokens reserved for the summary (default 1000) */
+  max_summary_tokens?: number;
+}
+
+/**
+ * Cheap model fixing responses that fail their request's JSON schema. Requests declaring
+ * a schema are buffered rather than streamed while repair is enabled.
+ */
+export interface JsonRepairConfig {
+  /** Whether invalid responses are repaired before they are returned */
+  enabled: boolean;
+  /** Model doing the repair; its provider only needs to be configured */
+  model: ModelAlias;
+}
+
+/**
+ * Races a second provider against the routed one and keeps the first successful answer.
+ * Every speculated request is paid for twice.
+ */
+export interface SpeculativeConfig {
+  /** Whether fan-out is active */
+  enabled: boolean;
+  /** Requested model names to speculate on; a trailing `*` matches by prefix */
+  match_models: string[];
+  /** Route raced against the primary one */
+  secondary: ModelAlias;
+}
+
+/** Mirrors a sample of traffic to a secondary model whose responses are only logged */
+export interface ShadowConfig {
+  /** Experiment name the primary and shadow outcomes are recorded under */
+  name: string;
+  /** Whether mirroring is active */
+  enabled: boolean;
+  /** Shadow route; the provider only needs to be configured, not enabled */
+  target: ModelAlias;
+  /** Percentage (0-100) of requests to mirror */
+  sample_percent: number;
+}
+
+/** Traffic split between two models for requests matching a model pattern */
+export interface AbTest {
+  /** Experiment name used to group recorded outcomes */
+  name: string;
+  /** Whether the split is active */
+  enabled: boolean;
+  /** Requested model name to split; a trailing `*` matches by prefix */
+  match_model: string;
+  /** Route for the remaining traffic */
+  control: ModelAlias;
+  /** Route under evaluation */
+  variant: ModelAlias;
+  /** Percentage (0-100) of matching traffic sent to the variant */
+  variant_percent: number;
+}
+
+/**
+ * Named prompt stored in the database. Clients invoke it through the gateway with the
+ * `x-doggy-template` header and a top-level `template_variables` object.
+ */
+export interface PromptTemplate {
+  /** Unique name clients invoke the template by */
+  name: string;
+  description?: string;
+  /** System prompt, replacing the client's when set */
+  system?: string;
+  /** User prompt with `{{variable}}` placeholders */
+  prompt: string;
+  created_at?: string;
+  updated_at?: string;
+}
+
+/** Aggregated outcomes of one arm of an A/B test */
+export interface AbTestArmStats {
+  experiment: string;
+  /** 'control' or 'variant' */
+  arm: string;
+  provider: string;
+  model: string;
+  requests: number;
+  errors: number;
+  avg_latency_ms?: number;
+  total_cost_usd: number;
+  avg_cost_usd?: number;
+  /** Client retries per request */
+  retry_rate: number;
+}
+
+/** Streaming responsiveness of one model */
+export interface ModelLatency {
+  provider: string;
+  model: string;
+  /** Streamed requests with a first token */
+  streamed_requests: number;
+  avg_ttft_ms?: number;
+  min_ttft_ms?: number;
+  max_ttft_ms?: number;
+  avg_tokens_per_second?: number;
+  /** Average total latency over all successful requests, streamed or not */
+  avg_latency_ms?: number;
+}
+
+/** Price pair for a model, per 1M tokens (USD) */
+export interface ModelPricing {
+  input_price: number;
+  output_price: number;
+  cache_read_price?: number;
+  cache_write_price?: number;
+  tiers?: PriceTier[];
+}
+
+/** Outcome of a pricing sync */
+export interface PricingSyncResult {
+  /** `provider/model` keys whose prices changed */
+  updated: string[];
+  /** `provider/model` keys with no manifest or override entry */
+  unmatched: string[];
+  /** Error fetching the manifest (overrides are still applied) */
+  error?: string;
+}
+
+/** Provider/model pair an incoming model name is rewritten to */
+export interface ModelAlias {
+  /** Target provider */
+  provider: LLMProvider;
+  /** Model ID as understood by the target provider */
+  model: string;
+}
+
+/** Speech model and voice a generic voice name (e.g. "narrator") is rewritten to */
+export interface VoiceAlias {
+  /** Target provider */
+  provider: LLMProvider;
+  /** Speech model ID as understood by the target provider */
+  model: string;
+  /** Voice as understood by the target provider */
+  voice: string;
 }
 
 /** Provider status */
@@ -96,6 +978,65 @@ export interface ProviderStatus {
   request_count: number;
   /** Error count */
   error_count: number;
+  /** Remaining rate limits and credit last reported by the provider */
+  quota?: ProviderQuota;
+  /** Latest resource usage of a local provider's machine */
+  hardware?: HardwareUsage;
+}
+
+/** Resource usage of the machine serving a local provider */
+export interface HardwareUsage {
+  /** Load average over the last minute per CPU core; above 1 means CPUs are queued */
+  cpu_load?: number;
+  /** Busiest GPU's utilization, 0-1 */
+  gpu_utilization?: number;
+  /** VRAM in use across GPUs */
+  vram_used_mb?: number;
+  vram_total_mb?: number;
+  /** Models the Ollama server has loaded */
+  loaded_models: { name: string; size_mb: number; vram_mb: number }[];
+  /** When the sample was taken */
+  sampled_at: string;
+}
+
+/** Remaining rate limits and credit last reported by a provider */
+export interface ProviderQuota {
+  remaining_requests?: number;
+  limit_requests?: number;
+  remaining_tokens?: number;
+  limit_tokens?: number;
+  /** Remaining prepaid credit in USD */
+  credits_remaining?: number;
+  /** Prepaid credit bought in USD */
+  credits_total?: number;
+  /** When the values were reported (RFC 3339) */
+  updated_at?: string;
+}
+
+/** Emitted after every recorded gateway request */
+export interface CostTick {
+  provider: string;
+  model: string;
+  /** Cost of the request, if its model has a price */
+  cost_usd?: number;
+  /** Total cost of today's requests, in local time */
+  today_spend_usd: number;
+}
+
+/** Emitted when a provider's remaining quota falls below 10% of its limit */
+export interface QuotaWarning {
+  provider: string;
+  message: string;
+  quota: ProviderQuota;
+}
+
+/** Emitted when a provider is demoted for breaching its SLO, or restored */
+export interface SloEvent {
+  provider: string;
+  /** Demoted, or restored when false */
+  demoted: boolean;
+  /** Objective that was breached */
+  reason?: string;
 }
 
 /** Gateway status information */
@@ -110,6 +1051,151 @@ export interface GatewayStatus {
   provider_status: Record<string, ProviderStatus>;
   /** Last error if any */
   last_error?: string;
+  /** Configured models that are deprecated or misconfigured per the model registry */
+  model_warnings: ModelWarning[];
+}
+
+/** A configured model that needs attention */
+export interface ModelWarning {
+  /** Provider display name */
+  provider: string;
+  model: string;
+  message: string;
+}
+
+/** Outcome of a model registry sync */
+export interface RegistrySyncResult {
+  /** Models in the registry */
+  models: number;
+  /** Warnings about the configured models against the synced registry */
+  warnings: ModelWarning[];
+  /** Error fetching the manifest; the stored registry is kept */
+  error?: string;
+}
+
+/** Outcome of one self-test check */
+export interface SelfTestCheck {
+  /** Provider display name, or `gateway` for the routed check */
+  target: string;
+  /** Model that was asked, or that answered for the routed check */
+  model?: string;
+  passed: boolean;
+  latency_ms?: number;
+  /** Start of the reply */
+  snippet?: string;
+  error?: string;
+}
+
+/** Outcome of a gateway self-test */
+export interface SelfTestReport {
+  /** Whether every check passed */
+  passed: boolean;
+  checks: SelfTestCheck[];
+}
+
+/** Result of probing a self-hosted OpenAI-compatible endpoint */
+export interface ProviderProbeResult {
+  /** Whether the /models endpoint answered successfully */
+  reachable: boolean;
+  /** Models discovered from the /models endpoint */
+  models: ModelConfig[];
+  /** Whether a chat completion round-trip succeeded */
+  chat_ok: boolean;
+  /** Whether the endpoint answered a streaming request with SSE */
+  supports_streaming: boolean;
+  /** Round-trip latency of the chat completion in ms */
+  latency_ms?: number;
+  /** Start of the completion text returned by the round-trip */
+  sample_response?: string;
+  /** First error encountered while probing */
+  error?: string;
+}
+
+/** Outcome of refreshing one provider's model list */
+export interface ModelRefreshResult {
+  /** Provider that was queried */
+  provider: LLMProvider;
+  /** Provider display name */
+  name: string;
+  /** IDs of models added to the provider configuration */
+  added: string[];
+  /** Error returned by the provider's /models endpoint */
+  error?: string;
+}
+
+/** Model installed on an Ollama server */
+export interface OllamaInstalledModel {
+  /** Model name with its tag, e.g. "llama3.2:latest" */
+  name: string;
+  /** Size on disk in bytes */
+  size: number;
+  modified_at?: string;
+  /** Parameter count, e.g. "3.2B" */
+  parameter_size?: string;
+  /** Quantization, e.g. "Q4_K_M" */
+  quantization_level?: string;
+}
+
+/** Progress of an Ollama model pull */
+export interface OllamaPullProgress {
+  /** Provider the model is pulled to */
+  provider: string;
+  model: string;
+  /** Step reported by Ollama, e.g. "pulling manifest" or "success" */
+  status: string;
+  /** Bytes of the layer being downloaded */
+  total?: number;
+  completed?: number;
+}
+
+/** Changes made to an Ollama provider's model list to match the installed models */
+export interface OllamaModelSync {
+  /** Provider display name */
+  name: string;
+  /** IDs of models added to the provider configuration */
+  added: string[];
+  /** IDs of models removed because they are no longer installed */
+  removed: string[];
+  /** Error listing the installed models; the configuration is left alone */
+  error?: string;
+}
+
+/** Outcome of checking one provider's prepaid credit */
+export interface ProviderCreditsResult {
+  /** Provider display name */
+  name: string;
+  /** Credit reported by the provider */
+  quota?: ProviderQuota;
+  /** Error returned by the provider's credits endpoint */
+  error?: string;
+}
+
+/** GitHub device login code for signing in to Copilot */
+export interface CopilotDeviceLogin {
+  device_code: string;
+  /** Code the user enters at `verification_uri` */
+  user_code: string;
+  verification_uri: string;
+  /** Seconds to wait between polls */
+  interval: number;
+  /** Seconds until the code expires */
+  expires_in: number;
+}
+
+/** Outcome of polling a Copilot sign-in */
+export type CopilotLoginPoll =
+  | { status: 'pending' }
+  | { status: 'slow_down'; interval: number }
+  | { status: 'complete' };
+
+/** Outcome of importing another tool's configuration */
+export interface ConfigImportResult {
+  /** Saved settings after the import */
+  settings: GatewaySettings;
+  /** Names of the providers added or updated */
+  providers: string[];
+  /** Entries that couldn't be imported, with the reason */
+  skipped: string[];
 }
 
 // ============================================================================
@@ -196,6 +1282,19 @@ export async function testProvider(
   }
 }
 
+/**
+ * Send a tiny completion through every enabled provider and through the gateway's own
+ * routing
+ */
+export async function runGatewaySelftest(): Promise<SelfTestReport> {
+  try {
+    return await apiCall<SelfTestReport>('run_gateway_selftest');
+  } catch (error) {
+    console.error('Failed to run gateway self-test:', error);
+    throw error;
+  }
+}
+
 /**
  * Get default providers configuration
  */
@@ -209,17 +1308,659 @@ export async function getDefaultProviders(): Promise<ProviderConfig[]> {
 }
 
 /**
- * Get environment variables for Claude Code to use the gateway
+ * Get environment variables for Claude Code to use the gateway, attributing its usage
+ * to the project at `projectPath` when given
  */
-export async function getGatewayEnvVars(): Promise<Record<string, string>> {
+export async function getGatewayEnvVars(projectPath?: string): Promise<Record<string, string>> {
   try {
-    return await apiCall<Record<string, string>>('get_gateway_env_vars');
+    return await apiCall<Record<string, string>>('get_gateway_env_vars', { projectPath });
   } catch (error) {
     console.error('Failed to get gateway env vars:', error);
     throw error;
   }
 }
 
+/**
+ * Probe a custom OpenAI-compatible endpoint (vLLM, LM Studio, ...)
+ */
+export async function probeCustomProvider(
+  baseUrl: string,
+  apiKey?: string,
+  model?: string
+): Promise<ProviderProbeResult> {
+  try {
+    return await apiCall<ProviderProbeResult>('probe_custom_llm_provider', {
+      baseUrl,
+      apiKey,
+      model,
+    });
+  } catch (error) {
+    console.error('Failed to probe custom provider:', error);
+    throw error;
+  }
+}
+
+/**
+ * Validate and add a custom OpenAI-compatible provider
+ */
+export async function addCustomProvider(config: ProviderConfig): Promise<GatewaySettings> {
+  try {
+    return await apiCall<GatewaySettings>('add_custom_llm_provider', { config });
+  } catch (error) {
+    console.error('Failed to add custom provider:', error);
+    throw error;
+  }
+}
+
+/**
+ * Query enabled providers for their current model lists and merge new models
+ */
+export async function refreshProviderModels(): Promise<ModelRefreshResult[]> {
+  try {
+    return await apiCall<ModelRefreshResult[]>('refresh_provider_models');
+  } catch (error) {
+    console.error('Failed to refresh provider models:', error);
+    throw error;
+  }
+}
+
+/**
+ * Check the prepaid credit of enabled OpenRouter providers
+ */
+export async function refreshProviderCredits(): Promise<ProviderCreditsResult[]> {
+  try {
+    return await apiCall<ProviderCreditsResult[]>('refresh_provider_credits');
+  } catch (error) {
+    console.error('Failed to refresh provider credits:', error);
+    throw error;
+  }
+}
+
+/**
+ * Refresh context windows and deprecation dates from the pricing manifest and check the
+ * configured models against them
+ */
+export async function syncModelRegistry(): Promise<RegistrySyncResult> {
+  try {
+    return await apiCall<RegistrySyncResult>('sync_model_registry');
+  } catch (error) {
+    console.error('Failed to sync model registry:', error);
+    throw error;
+  }
+}
+
+/**
+ * Update model prices from the pricing manifest and local overrides
+ */
+export async function syncModelPricing(): Promise<PricingSyncResult> {
+  try {
+    return await apiCall<PricingSyncResult>('sync_model_pricing');
+  } catch (error) {
+    console.error('Failed to sync model pricing:', error);
+    throw error;
+  }
+}
+
+/**
+ * Get per-arm outcome statistics of A/B tests
+ */
+export async function getAbTestResults(experiment?: string): Promise<AbTestArmStats[]> {
+  try {
+    return await apiCall<AbTestArmStats[]>('get_ab_test_results', { experiment });
+  } catch (error) {
+    console.error('Failed to get A/B test results:', error);
+    throw error;
+  }
+}
+
+/**
+ * Get time to first token and streaming throughput per model over the last `days` days
+ * (default 7)
+ */
+export async function getGatewayLatencyStats(days?: number): Promise<ModelLatency[]> {
+  try {
+    return await apiCall<ModelLatency[]>('get_gateway_latency_stats', { days });
+  } catch (error) {
+    console.error('Failed to get latency stats:', error);
+    throw error;
+  }
+}
+
+/**
+ * List the models installed on an Ollama provider's server
+ */
+export async function listOllamaModels(provider: string): Promise<OllamaInstalledModel[]> {
+  try {
+    return await apiCall<OllamaInstalledModel[]>('list_ollama_models', { provider });
+  } catch (error) {
+    console.error('Failed to list Ollama models:', error);
+    throw error;
+  }
+}
+
+/**
+ * Pull a model onto an Ollama provider's server and add it to the provider's models;
+ * progress arrives through `onOllamaPullProgress`
+ */
+export async function pullOllamaModel(provider: string, model: string): Promise<OllamaModelSync> {
+  try {
+    return await apiCall<OllamaModelSync>('pull_ollama_model', { provider, model });
+  } catch (error) {
+    console.error('Failed to pull Ollama model:', error);
+    throw error;
+  }
+}
+
+/**
+ * Delete a model from an Ollama provider's server and from the provider's models
+ */
+export async function deleteOllamaModel(provider: string, model: string): Promise<OllamaModelSync> {
+  try {
+    return await apiCall<OllamaModelSync>('delete_ollama_model', { provider, model });
+  } catch (error) {
+    console.error('Failed to delete Ollama model:', error);
+    throw error;
+  }
+}
+
+/**
+ * Match every enabled Ollama provider's models to the models installed on its server
+ */
+export async function syncOllamaModels(): Promise<OllamaModelSync[]> {
+  try {
+    return await apiCall<OllamaModelSync[]>('sync_ollama_models');
+  } catch (error) {
+    console.error('Failed to sync Ollama models:', error);
+    throw error;
+  }
+}
+
+/**
+ * List the stored prompt templates
+ */
+export async function listPromptTemplates(): Promise<PromptTemplate[]> {
+  try {
+    return await apiCall<PromptTemplate[]>('list_prompt_templates');
+  } catch (error) {
+    console.error('Failed to list prompt templates:', error);
+    throw error;
+  }
+}
+
+/**
+ * Create a prompt template or update the one with the same name
+ */
+export async function savePromptTemplate(template: PromptTemplate): Promise<PromptTemplate> {
+  try {
+    return await apiCall<PromptTemplate>('save_prompt_template', { template });
+  } catch (error) {
+    console.error('Failed to save prompt template:', error);
+    throw error;
+  }
+}
+
+/**
+ * Delete a prompt template
+ */
+export async function deletePromptTemplate(name: string): Promise<void> {
+  try {
+    await apiCall<void>('delete_prompt_template', { name });
+  } catch (error) {
+    console.error('Failed to delete prompt template:', error);
+    throw error;
+  }
+}
+
+/**
+ * Import providers and models from a LiteLLM proxy config.yaml
+ */
+export async function importLiteLLMConfig(path: string): Promise<ConfigImportResult> {
+  try {
+    return await apiCall<ConfigImportResult>('import_litellm_config', { path });
+  } catch (error) {
+    console.error('Failed to import LiteLLM config:', error);
+    throw error;
+  }
+}
+
+/**
+ * Import providers, routes and transformers from a claude-code-router config.json
+ * (default: ~/.claude-code-router/config.json)
+ */
+export async function importClaudeCodeRouterConfig(path?: string): Promise<ConfigImportResult> {
+  try {
+    return await apiCall<ConfigImportResult>('import_claude_code_router_config', { path });
+  } catch (error) {
+    console.error('Failed to import claude-code-router config:', error);
+    throw error;
+  }
+}
+
+/**
+ * Write the saved settings to a JSON file, or TOML for .toml paths. Without
+ * includeSecrets, provider keys and credential headers are left out.
+ */
+export async function exportGatewaySettings(path: string, includeSecrets: boolean): Promise<void> {
+  try {
+    await apiCall<void>('export_gateway_settings', { path, includeSecrets });
+  } catch (error) {
+    console.error('Failed to export gateway settings:', error);
+    throw error;
+  }
+}
+
+/**
+ * Replace the saved settings with an exported file, keeping local keys the file lacks
+ */
+export async function importGatewaySettings(path: string): Promise<GatewaySettings> {
+  try {
+    return await apiCall<GatewaySettings>('import_gateway_settings', { path });
+  } catch (error) {
+    console.error('Failed to import gateway settings:', error);
+    throw error;
+  }
+}
+
+/**
+ * List stored configuration profiles and the active one
+ */
+export async function listGatewayProfiles(): Promise<GatewayProfiles> {
+  try {
+    return await apiCall<GatewayProfiles>('list_gateway_profiles');
+  } catch (error) {
+    console.error('Failed to list gateway profiles:', error);
+    throw error;
+  }
+}
+
+/**
+ * Save the current settings as a named profile and make it active
+ */
+export async function saveGatewayProfile(name: string): Promise<void> {
+  try {
+    await apiCall<void>('save_gateway_profile', { name });
+  } catch (error) {
+    console.error('Failed to save gateway profile:', error);
+    throw error;
+  }
+}
+
+/**
+ * Delete a configuration profile
+ */
+export async function deleteGatewayProfile(name: string): Promise<void> {
+  try {
+    await apiCall<void>('delete_gateway_profile', { name });
+  } catch (error) {
+    console.error('Failed to delete gateway profile:', error);
+    throw error;
+  }
+}
+
+/**
+ * Switch to a stored profile, hot-swapping it into the running gateway
+ */
+export async function switchGatewayProfile(name: string): Promise<GatewaySettings> {
+  try {
+    return await apiCall<GatewaySettings>('switch_gateway_profile', { name });
+  } catch (error) {
+    console.error('Failed to switch gateway profile:', error);
+    throw error;
+  }
+}
+
+/**
+ * Get a compact snapshot of the gateway's state
+ */
+export async function getGatewaySnapshot(): Promise<GatewaySnapshot> {
+  try {
+    return await apiCall<GatewaySnapshot>('get_gateway_snapshot');
+  } catch (error) {
+    console.error('Failed to get gateway snapshot:', error);
+    throw error;
+  }
+}
+
+/**
+ * Enable or disable a provider by name, applying it to the running gateway
+ */
+export async function setLLMProviderEnabled(name: string, enabled: boolean): Promise<GatewaySettings> {
+  try {
+    return await apiCall<GatewaySettings>('set_llm_provider_enabled', { name, enabled });
+  } catch (error) {
+    console.error('Failed to set provider enabled:', error);
+    throw error;
+  }
+}
+
+/**
+ * Start a batch from a JSONL file of requests through the running gateway
+ */
+export async function createGatewayBatch(path: string, concurrency?: number): Promise<BatchJob> {
+  try {
+    return await apiCall<BatchJob>('create_gateway_batch', { path, concurrency });
+  } catch (error) {
+    console.error('Failed to create batch:', error);
+    throw error;
+  }
+}
+
+/**
+ * List batches, newest first
+ */
+export async function listGatewayBatches(): Promise<BatchJob[]> {
+  try {
+    return await apiCall<BatchJob[]>('list_gateway_batches');
+  } catch (error) {
+    console.error('Failed to list batches:', error);
+    throw error;
+  }
+}
+
+/**
+ * Get the per-request results of a batch
+ */
+export async function getGatewayBatchResults(id: string): Promise<BatchResult[]> {
+  try {
+    return await apiCall<BatchResult[]>('get_gateway_batch_results', { id });
+  } catch (error) {
+    console.error('Failed to get batch results:', error);
+    throw error;
+  }
+}
+
+/**
+ * Stop a batch from sending further requests
+ */
+export async function cancelGatewayBatch(id: string): Promise<boolean> {
+  try {
+    return await apiCall<boolean>('cancel_gateway_batch', { id });
+  } catch (error) {
+    console.error('Failed to cancel batch:', error);
+    throw error;
+  }
+}
+
+/**
+ * Produce and store the usage report of the period containing `date` (YYYY-MM-DD, default today)
+ */
+export async function generateUsageReport(
+  period: ReportPeriod,
+  date?: string
+): Promise<UsageReport> {
+  try {
+    return await apiCall<UsageReport>('generate_usage_report', { period, date });
+  } catch (error) {
+    console.error('Failed to generate usage report:', error);
+    throw error;
+  }
+}
+
+/**
+ * List stored usage reports, newest first
+ */
+export async function listUsageReports(limit?: number): Promise<UsageReport[]> {
+  try {
+    return await apiCall<UsageReport[]>('list_usage_reports', { limit });
+  } catch (error) {
+    console.error('Failed to list usage reports:', error);
+    throw error;
+  }
+}
+
+/**
+ * Write the request log between two dates (YYYY-MM-DD, inclusive) to a CSV file for
+ * `.csv` paths or JSON otherwise; returns the number of rows written
+ */
+export async function exportGatewayUsage(
+  path: string,
+  options: { startDate?: string; endDate?: string; groupBy?: UsageGrouping } = {}
+): Promise<number> {
+  try {
+    return await apiCall<number>('export_gateway_usage', {
+      path,
+      startDate: options.startDate,
+      endDate: options.endDate,
+      groupBy: options.groupBy,
+    });
+  } catch (error) {
+    console.error('Failed to export gateway usage:', error);
+    throw error;
+  }
+}
+
+/**
+ * Project each provider's end-of-month spend from its average over the last full days (default 7)
+ */
+export async function forecastGatewaySpend(windowDays?: number): Promise<ForecastReport> {
+  try {
+    return await apiCall<ForecastReport>('forecast_gateway_spend', { windowDays });
+  } catch (error) {
+    console.error('Failed to forecast gateway spend:', error);
+    throw error;
+  }
+}
+
+/**
+ * List recorded settings changes, newest first (default 100)
+ */
+export async function listAuditLog(limit?: number): Promise<AuditEntry[]> {
+  try {
+    return await apiCall<AuditEntry[]>('list_audit_log', { limit });
+  } catch (error) {
+    console.error('Failed to list audit log:', error);
+    throw error;
+  }
+}
+
+/**
+ * Back up the app database to a path, or to a new file in the backup directory
+ */
+export async function backupGatewayDb(path?: string): Promise<BackupInfo> {
+  try {
+    return await apiCall<BackupInfo>('backup_gateway_db', { path });
+  } catch (error) {
+    console.error('Failed to back up database:', error);
+    throw error;
+  }
+}
+
+/**
+ * Replace the app database with a backup, returning the backup of the current database taken first
+ */
+export async function restoreGatewayDb(path: string): Promise<BackupInfo> {
+  try {
+    return await apiCall<BackupInfo>('restore_gateway_db', { path });
+  } catch (error) {
+    console.error('Failed to restore database:', error);
+    throw error;
+  }
+}
+
+/**
+ * List the backups in the backup directory, newest first
+ */
+export async function listGatewayBackups(): Promise<BackupInfo[]> {
+  try {
+    return await apiCall<BackupInfo[]>('list_gateway_backups');
+  } catch (error) {
+    console.error('Failed to list backups:', error);
+    throw error;
+  }
+}
+
+/**
+ * List the running additional listeners
+ */
+export async function listGatewayInstances(): Promise<InstanceStatus[]> {
+  try {
+    return await apiCall<InstanceStatus[]>('list_gateway_instances');
+  } catch (error) {
+    console.error('Failed to list gateway instances:', error);
+    throw error;
+  }
+}
+
+/**
+ * Start an additional listener while the gateway is running
+ */
+export async function startGatewayInstance(name: string): Promise<InstanceStatus> {
+  try {
+    return await apiCall<InstanceStatus>('start_gateway_instance', { name });
+  } catch (error) {
+    console.error('Failed to start gateway instance:', error);
+    throw error;
+  }
+}
+
+/**
+ * Stop an additional listener
+ */
+export async function stopGatewayInstance(name: string): Promise<void> {
+  try {
+    await apiCall<void>('stop_gateway_instance', { name });
+  } catch (error) {
+    console.error('Failed to stop gateway instance:', error);
+    throw error;
+  }
+}
+
+/**
+ * Explain which routing rule a sample request matches at `time` (HH:MM, default now),
+ * using `rules` in place of the saved rules when given
+ */
+export async function testRoutingRules(
+  request: Record<string, unknown>,
+  rules?: RoutingRule[],
+  sourceKey?: string,
+  time?: string
+): Promise<RuleExplanation> {
+  try {
+    return await apiCall<RuleExplanation>('test_routing_rules', {
+      request,
+      rules,
+      sourceKey,
+      time,
+    });
+  } catch (error) {
+    console.error('Failed to test routing rules:', error);
+    throw error;
+  }
+}
+
+/**
+ * Start signing in to GitHub Copilot; show the user code and verification URI
+ */
+export async function startCopilotLogin(): Promise<CopilotDeviceLogin> {
+  try {
+    return await apiCall<CopilotDeviceLogin>('start_copilot_login');
+  } catch (error) {
+    console.error('Failed to start Copilot login:', error);
+    throw error;
+  }
+}
+
+/**
+ * Check on a Copilot sign-in; the token is stored on the Copilot provider once complete
+ */
+export async function pollCopilotLogin(deviceCode: string): Promise<CopilotLoginPoll> {
+  try {
+    return await apiCall<CopilotLoginPoll>('poll_copilot_login', { deviceCode });
+  } catch (error) {
+    console.error('Failed to poll Copilot login:', error);
+    throw error;
+  }
+}
+
+/**
+ * Listen for batch progress, emitted each time a request completes
+ */
+export async function onGatewayBatchProgress(
+  handler: (job: BatchJob) => void
+): Promise<UnlistenFn> {
+  return listen<BatchJob>('llm-gateway-batch-progress', (event) => handler(event.payload));
+}
+
+/**
+ * Listen for providers whose remaining quota has just run low
+ */
+export async function onGatewayQuotaWarning(
+  handler: (warning: QuotaWarning) => void
+): Promise<UnlistenFn> {
+  return listen<QuotaWarning>('llm-gateway-quota-warning', (event) => handler(event.payload));
+}
+
+/**
+ * Listen for the progress of Ollama model pulls
+ */
+export async function onOllamaPullProgress(
+  handler: (progress: OllamaPullProgress) => void
+): Promise<UnlistenFn> {
+  return listen<OllamaPullProgress>('llm-gateway-ollama-pull', (event) => handler(event.payload));
+}
+
+/**
+ * Listen for providers demoted for breaching their SLO, and restored once they recover
+ */
+export async function onGatewaySlo(handler: (event: SloEvent) => void): Promise<UnlistenFn> {
+  return listen<SloEvent>('llm-gateway-slo', (event) => handler(event.payload));
+}
+
+/**
+ * Listen for the cost of each completed request and the day's running total
+ */
+export async function onGatewayCost(handler: (tick: CostTick) => void): Promise<UnlistenFn> {
+  return listen<CostTick>('llm-gateway-cost', (event) => handler(event.payload));
+}
+
+/**
+ * Get whether the stored gateway settings are encrypted and unlocked
+ */
+export async function getSettingsEncryptionStatus(): Promise<SettingsEncryptionStatus> {
+  try {
+    return await apiCall<SettingsEncryptionStatus>('get_settings_encryption_status');
+  } catch (error) {
+    console.error('Failed to get settings encryption status:', error);
+    throw error;
+  }
+}
+
+/**
+ * Encrypt the stored gateway settings with a master password
+ */
+export async function enableSettingsEncryption(password: string): Promise<void> {
+  try {
+    await apiCall<void>('enable_settings_encryption', { password });
+  } catch (error) {
+    console.error('Failed to enable settings encryption:', error);
+    throw error;
+  }
+}
+
+/**
+ * Unlock encrypted gateway settings for this session
+ */
+export async function unlockGatewaySettings(password: string): Promise<GatewaySettings> {
+  try {
+    return await apiCall<GatewaySettings>('unlock_gateway_settings', { password });
+  } catch (error) {
+    console.error('Failed to unlock gateway settings:', error);
+    throw error;
+  }
+}
+
+/**
+ * Decrypt the stored gateway settings and stop requiring a master password
+ */
+export async function disableSettingsEncryption(password: string): Promise<void> {
+  try {
+    await apiCall<void>('disable_settings_encryption', { password });
+  } catch (error) {
+    console.error('Failed to disable settings encryption:', error);
+    throw error;
+  }
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================